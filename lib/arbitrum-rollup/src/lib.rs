@@ -0,0 +1,265 @@
+//! Queries of the state of an arbitrum rollup as it is settled on the l1.
+//!
+//! The rollup contract on the l1 stores the latest confirmed node (`_latestConfirmed`) and the
+//! `confirmData` of every node, which commits to the l2 block the node asserts. The l2 block itself
+//! is only available in the `NodeCreated` event emitted when the node was created. This is the
+//! same data that the arbitrum light client verifies headers against.
+
+use alloy::{
+    eips::BlockNumberOrTag,
+    primitives::B256,
+    providers::{Provider, RootProvider},
+    rpc::types::{Block, BlockTransactionsKind, Filter},
+    sol_types::SolEvent,
+    transports::{BoxTransport, TransportError},
+};
+use serde::{Deserialize, Serialize};
+use tracing::{debug, instrument};
+use unionlabs::{
+    bounded::BoundedU32,
+    ethereum::slot::{MappingKey, Slot},
+    hash::{H160, H256},
+    uint::U256,
+};
+
+alloy::sol! {
+    /// See <https://github.com/OffchainLabs/nitro-contracts/blob/90037b996509312ef1addb3f9352457b8a99d6a6/src/state/GlobalState.sol>
+    struct GlobalState {
+        bytes32[2] bytes32Vals;
+        uint64[2] u64Vals;
+    }
+
+    enum MachineStatus {
+        RUNNING,
+        FINISHED,
+        ERRORED,
+        TOO_FAR
+    }
+
+    /// See <https://github.com/OffchainLabs/nitro-contracts/blob/90037b996509312ef1addb3f9352457b8a99d6a6/src/rollup/Node.sol#L10>
+    struct ExecutionState {
+        GlobalState globalState;
+        MachineStatus machineStatus;
+    }
+
+    /// See <https://github.com/OffchainLabs/nitro-contracts/blob/90037b996509312ef1addb3f9352457b8a99d6a6/src/rollup/Node.sol#L15>
+    struct Assertion {
+        ExecutionState beforeState;
+        ExecutionState afterState;
+        uint64 numBlocks;
+    }
+
+    /// See <https://github.com/OffchainLabs/nitro-contracts/blob/90037b996509312ef1addb3f9352457b8a99d6a6/src/rollup/IRollupEventInbox.sol>
+    event NodeCreated(
+        uint64 indexed nodeNum,
+        bytes32 indexed parentNodeHash,
+        bytes32 indexed nodeHash,
+        bytes32 executionHash,
+        Assertion assertion,
+        bytes32 afterInboxBatchAcc,
+        bytes32 wasmModuleRoot,
+        uint256 inboxMaxCount
+    );
+}
+
+/// The location of the rollup state on the l1. These are the same values as are stored in the
+/// client state of the arbitrum light client.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RollupConfig {
+    /// The address of the rollup contract on the l1.
+    pub l1_contract_address: H160,
+    /// The slot of `RollupCore._latestConfirmed`.
+    pub l1_next_node_num_slot: U256,
+    /// The offset of `RollupCore._latestConfirmed` in [`Self::l1_next_node_num_slot`], since it
+    /// is packed with other values.
+    pub l1_next_node_num_slot_offset_bytes: BoundedU32<0, 24>,
+    /// The slot of `RollupCore._nodes`.
+    pub l1_nodes_slot: U256,
+    /// The offset of `Node.confirmData` in the `Node` struct.
+    pub l1_nodes_confirm_data_offset: U256,
+}
+
+impl RollupConfig {
+    /// The slot of `_nodes[node_num].confirmData`.
+    #[must_use]
+    pub fn nodes_confirm_data_slot(&self, node_num: u64) -> U256 {
+        Slot::Mapping(
+            &Slot::Offset(self.l1_nodes_slot),
+            MappingKey::Uint64(node_num),
+        )
+        .slot()
+            + self.l1_nodes_confirm_data_offset
+    }
+
+    /// Read the latest confirmed node num out of the raw value of
+    /// [`Self::l1_next_node_num_slot`].
+    #[must_use]
+    pub fn node_num_from_slot_value(&self, value: [u8; 32]) -> u64 {
+        let offset = self.l1_next_node_num_slot_offset_bytes.inner() as usize;
+
+        u64::from_be_bytes(
+            value[offset..offset + 8]
+                .try_into()
+                .expect("offset is at most 24 bytes; qed;"),
+        )
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("error fetching the latest confirmed node at l1 height {l1_height}")]
+    LatestConfirmed {
+        l1_height: u64,
+        #[source]
+        error: TransportError,
+    },
+    #[error("error fetching the creation of node {node_num}")]
+    NodeCreated {
+        node_num: u64,
+        #[source]
+        error: TransportError,
+    },
+    #[error("expected exactly one `NodeCreated` event for node {node_num}, but found {found}")]
+    InvalidNodeCreatedCount { node_num: u64, found: usize },
+    #[error("unable to decode the `NodeCreated` event of node {node_num}")]
+    DecodeNodeCreated {
+        node_num: u64,
+        #[source]
+        error: alloy::sol_types::Error,
+    },
+    #[error("error fetching l2 block {block_hash}")]
+    L2Block {
+        block_hash: H256,
+        #[source]
+        error: TransportError,
+    },
+    #[error("l2 block {0} not found")]
+    L2BlockNotFound(H256),
+}
+
+#[derive(Debug, Clone)]
+pub struct Rollup {
+    pub config: RollupConfig,
+    pub l1_provider: RootProvider<BoxTransport>,
+    pub l2_provider: RootProvider<BoxTransport>,
+}
+
+impl Rollup {
+    #[must_use]
+    pub fn new(
+        config: RollupConfig,
+        l1_provider: RootProvider<BoxTransport>,
+        l2_provider: RootProvider<BoxTransport>,
+    ) -> Self {
+        Self {
+            config,
+            l1_provider,
+            l2_provider,
+        }
+    }
+
+    /// The latest node confirmed on the l1 at `l1_height`.
+    #[instrument(skip_all, fields(%l1_height))]
+    pub async fn latest_confirmed_at(&self, l1_height: u64) -> Result<u64, Error> {
+        let value = self
+            .l1_provider
+            .get_storage_at(
+                self.config.l1_contract_address.into(),
+                alloy::primitives::U256::from_be_bytes(
+                    self.config.l1_next_node_num_slot.to_be_bytes(),
+                ),
+            )
+            .block_id(l1_height.into())
+            .await
+            .map_err(|error| Error::LatestConfirmed { l1_height, error })?;
+
+        let node_num = self
+            .config
+            .node_num_from_slot_value(value.to_be_bytes::<32>());
+
+        debug!("latest confirmed node at l1 height {l1_height} is {node_num}");
+
+        Ok(node_num)
+    }
+
+    /// The hash of the l2 block asserted by the node `node_num`.
+    #[instrument(skip_all, fields(%node_num))]
+    pub async fn l2_block_hash_of_node(&self, node_num: u64) -> Result<H256, Error> {
+        let logs = self
+            .l1_provider
+            .get_logs(
+                &Filter::new()
+                    .address(alloy::primitives::Address::from(
+                        self.config.l1_contract_address,
+                    ))
+                    .event_signature(NodeCreated::SIGNATURE_HASH)
+                    .topic1(B256::from(alloy::primitives::U256::from(node_num)))
+                    .from_block(BlockNumberOrTag::Earliest)
+                    .to_block(BlockNumberOrTag::Latest),
+            )
+            .await
+            .map_err(|error| Error::NodeCreated { node_num, error })?;
+
+        let [log] = <[_; 1]>::try_from(logs).map_err(|logs| Error::InvalidNodeCreatedCount {
+            node_num,
+            found: logs.len(),
+        })?;
+
+        let event = NodeCreated::decode_log(&log.inner, true)
+            .map_err(|error| Error::DecodeNodeCreated { node_num, error })?;
+
+        let block_hash = event.data.assertion.afterState.globalState.bytes32Vals[0].into();
+
+        debug!("node {node_num} asserts l2 block {block_hash}");
+
+        Ok(block_hash)
+    }
+
+    /// The l2 block asserted by the latest node confirmed on the l1 at `l1_height`.
+    pub async fn l2_block_at(&self, l1_height: u64) -> Result<Block, Error> {
+        let node_num = self.latest_confirmed_at(l1_height).await?;
+
+        let block_hash = self.l2_block_hash_of_node(node_num).await?;
+
+        self.l2_provider
+            .get_block_by_hash(block_hash.into(), BlockTransactionsKind::Hashes)
+            .await
+            .map_err(|error| Error::L2Block { block_hash, error })?
+            .ok_or(Error::L2BlockNotFound(block_hash))
+    }
+
+    /// The number of the l2 block asserted by the latest node confirmed on the l1 at `l1_height`.
+    pub async fn l2_height_at(&self, l1_height: u64) -> Result<u64, Error> {
+        Ok(self.l2_block_at(l1_height).await?.header.number)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn node_num_is_read_at_offset() {
+        let config = RollupConfig {
+            l1_contract_address: H160::default(),
+            l1_next_node_num_slot: U256::from(117_u64),
+            l1_next_node_num_slot_offset_bytes: BoundedU32::new(0_u32).unwrap(),
+            l1_nodes_slot: U256::from(118_u64),
+            l1_nodes_confirm_data_offset: U256::from(2_u64),
+        };
+
+        let mut value = [0; 32];
+        value[..8].copy_from_slice(&7_u64.to_be_bytes());
+        value[8..16].copy_from_slice(&42_u64.to_be_bytes());
+
+        assert_eq!(config.node_num_from_slot_value(value), 7);
+
+        let config = RollupConfig {
+            l1_next_node_num_slot_offset_bytes: BoundedU32::new(8_u32).unwrap(),
+            ..config
+        };
+
+        assert_eq!(config.node_num_from_slot_value(value), 42);
+    }
+}
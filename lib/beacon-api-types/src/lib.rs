@@ -33,6 +33,8 @@ pub mod indexed_attestation;
 pub mod light_client_bootstrap;
 pub mod light_client_finality_update;
 pub mod light_client_header;
+/// <https://github.com/ethereum/consensus-specs/blob/dev/specs/altair/light-client/sync-protocol.md#lightclientoptimisticupdate>
+pub mod light_client_optimistic_update;
 /// <https://github.com/ethereum/consensus-specs/blob/087e7378b44f327cdad4549304fc308613b780c3/specs/altair/light-client/sync-protocol.md#lightclientupdate>
 pub mod light_client_update;
 /// <https://github.com/ethereum/consensus-specs/blob/dev/specs/phase0/beacon-chain.md#proposerslashing>
@@ -70,9 +72,10 @@ pub use crate::{
     fork::Fork, fork_data::ForkData, fork_parameters::ForkParameters, genesis_data::GenesisData,
     indexed_attestation::IndexedAttestation, light_client_bootstrap::LightClientBootstrap,
     light_client_finality_update::LightClientFinalityUpdate,
-    light_client_header::LightClientHeader, light_client_update::LightClientUpdate,
-    proposer_slashing::ProposerSlashing, signed_beacon_block::SignedBeaconBlock,
-    signed_beacon_block_header::SignedBeaconBlockHeader,
+    light_client_header::LightClientHeader,
+    light_client_optimistic_update::LightClientOptimisticUpdate,
+    light_client_update::LightClientUpdate, proposer_slashing::ProposerSlashing,
+    signed_beacon_block::SignedBeaconBlock, signed_beacon_block_header::SignedBeaconBlockHeader,
     signed_bls_to_execution_change::SignedBlsToExecutionChange,
     signed_voluntary_exit::SignedVoluntaryExit, signing_data::SigningData,
     sync_aggregate::SyncAggregate, sync_committee::SyncCommittee, voluntary_exit::VoluntaryExit,
@@ -0,0 +1,13 @@
+use crate::{light_client_header::LightClientHeader, SyncAggregate};
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LightClientOptimisticUpdate {
+    /// Header attested to by the sync committee
+    pub attested_header: LightClientHeader,
+    /// Sync committee aggregate signature
+    pub sync_aggregate: SyncAggregate,
+    /// Slot at which the aggregate signature was created (untrusted)
+    #[cfg_attr(feature = "serde", serde(with = "::serde_utils::string"))]
+    pub signature_slot: u64,
+}
@@ -1,26 +1,45 @@
 //! Beacon API client, implemented as per <https://ethereum.github.io/beacon-APIs/releases/v2.4.1/beacon-node-oapi.json>
+//!
+//! Requests are sent to the primary beacon node, falling back to the configured fallback nodes (in
+//! order) if the request fails. If the request failed on all nodes and any of the failures was
+//! transient (i.e. the node was unreachable or overloaded), the request is retried against all
+//! nodes again, up to [`DEFAULT_MAX_RETRIES`] times.
 
-use std::fmt::Display;
+use std::{fmt::Display, time::Duration};
 
 use beacon_api_types::{
-    GenesisData, LightClientBootstrap, LightClientFinalityUpdate, SignedBeaconBlock,
+    GenesisData, LightClientBootstrap, LightClientFinalityUpdate, LightClientOptimisticUpdate,
+    SignedBeaconBlock,
 };
 use reqwest::{Client, StatusCode};
 use serde::{de::DeserializeOwned, Deserialize, Serialize};
-use tracing::{debug, info, trace};
-use unionlabs::hash::H256;
+use tracing::{debug, info, trace, warn};
+use unionlabs::{hash::H256, ErrorReporter};
 
 use crate::{
     errors::{Error, InternalServerError, NotFoundError},
-    types::{BeaconHeaderData, LightClientUpdatesResponse, Spec},
+    types::{BeaconHeaderData, LightClientUpdateResponse, LightClientUpdatesResponse, Spec},
 };
 
 pub type Result<T> = core::result::Result<T, Error>;
 
+/// The default amount of times a request is retried against all nodes after a transient failure.
+pub const DEFAULT_MAX_RETRIES: u32 = 3;
+
+/// The delay before the first retry of a request, increasing linearly with each retry.
+const RETRY_DELAY: Duration = Duration::from_millis(500);
+
+/// The maximum number of light client updates that can be requested at once.
+///
+/// <https://github.com/ethereum/consensus-specs/blob/dev/specs/altair/light-client/p2p-interface.md#configuration>
+pub const MAX_REQUEST_LIGHT_CLIENT_UPDATES: u64 = 128;
+
 #[derive(Debug, Clone)]
 pub struct BeaconApiClient {
     client: Client,
-    base_url: String,
+    /// The primary node, followed by the fallback nodes.
+    base_urls: Vec<String>,
+    max_retries: u32,
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -33,9 +52,19 @@ pub enum NewError {
 
 impl BeaconApiClient {
     pub async fn new(base_url: String) -> core::result::Result<Self, NewError> {
+        Self::new_with_fallbacks(base_url, vec![]).await
+    }
+
+    /// Create a client for the beacon node at `base_url`, falling back to the nodes at
+    /// `fallback_urls` if a request to it fails. All nodes must be on the same network.
+    pub async fn new_with_fallbacks(
+        base_url: String,
+        fallback_urls: Vec<String>,
+    ) -> core::result::Result<Self, NewError> {
         let this = Self {
             client: reqwest::Client::new(),
-            base_url,
+            base_urls: [base_url].into_iter().chain(fallback_urls).collect(),
+            max_retries: DEFAULT_MAX_RETRIES,
         };
 
         // TODO: Do checks against a spec?
@@ -52,6 +81,13 @@ impl BeaconApiClient {
         Ok(this)
     }
 
+    /// Set the amount of times a request is retried against all nodes after a transient failure.
+    #[must_use]
+    pub fn with_max_retries(mut self, max_retries: u32) -> Self {
+        self.max_retries = max_retries;
+        self
+    }
+
     pub async fn spec(&self) -> Result<Response<Spec>> {
         self.get_json("/eth/v1/config/spec").await
     }
@@ -61,6 +97,13 @@ impl BeaconApiClient {
             .await
     }
 
+    pub async fn optimistic_update(
+        &self,
+    ) -> Result<Response<LightClientOptimisticUpdate, Version>> {
+        self.get_json("/eth/v1/beacon/light_client/optimistic_update")
+            .await
+    }
+
     pub async fn header(
         &self,
         block_id: BlockId,
@@ -102,6 +145,46 @@ impl BeaconApiClient {
         .await
     }
 
+    /// Fetch the light client updates for the `count` sync committee periods starting at
+    /// `start_period`, split into requests of at most [`MAX_REQUEST_LIGHT_CLIENT_UPDATES`] periods.
+    ///
+    /// Beacon nodes may not have the updates for all of the requested periods (for example if they
+    /// were checkpoint synced recently), in which case the updates are fetched from the fallback
+    /// nodes instead.
+    pub async fn light_client_updates_range(
+        &self,
+        start_period: u64,
+        count: u64,
+    ) -> Result<Vec<LightClientUpdateResponse>> {
+        let mut updates = vec![];
+
+        for (start_period, count) in light_client_update_requests(start_period, count) {
+            debug!(start_period, count, "fetching light client updates");
+
+            let path = format!(
+                "/eth/v1/beacon/light_client/updates?start_period={start_period}&count={count}"
+            );
+
+            let response = self
+                .get_json_checked(path, |response: &LightClientUpdatesResponse| {
+                    if response.0.len() as u64 == count {
+                        Ok(())
+                    } else {
+                        Err(Error::IncompleteLightClientUpdates {
+                            start_period,
+                            count,
+                            found: response.0.len() as u64,
+                        })
+                    }
+                })
+                .await?;
+
+            updates.extend(response.0);
+        }
+
+        Ok(updates)
+    }
+
     /// Convenience method to fetch the execution height of a beacon height.
     pub async fn execution_height(&self, block_id: BlockId) -> Result<u64> {
         let height = self
@@ -173,7 +256,70 @@ impl BeaconApiClient {
     // Helper functions
 
     async fn get_json<T: DeserializeOwned>(&self, path: impl Into<String>) -> Result<T> {
-        let url = format!("{}{}", self.base_url, path.into());
+        self.get_json_checked(path, |_| Ok(())).await
+    }
+
+    /// Request `path` from each node in turn until one of them returns a response that passes
+    /// `check`, retrying all nodes if any of them failed transiently.
+    async fn get_json_checked<T: DeserializeOwned>(
+        &self,
+        path: impl Into<String>,
+        check: impl Fn(&T) -> Result<()>,
+    ) -> Result<T> {
+        let path = path.into();
+
+        let mut retry = 0;
+
+        loop {
+            let mut transient = false;
+            let mut last_error = None;
+
+            for base_url in &self.base_urls {
+                match self
+                    .get_json_from(base_url, &path)
+                    .await
+                    .and_then(|res| check(&res).map(|()| res))
+                {
+                    Ok(res) => return Ok(res),
+                    Err(err) => {
+                        // non-transient errors (i.e. a missing block) are often expected by the
+                        // caller
+                        if err.is_transient() {
+                            warn!(
+                                %base_url,
+                                %path,
+                                error = %ErrorReporter(&err),
+                                "beacon api request failed"
+                            );
+                        } else {
+                            debug!(
+                                %base_url,
+                                %path,
+                                error = %ErrorReporter(&err),
+                                "beacon api request failed"
+                            );
+                        }
+
+                        transient |= err.is_transient();
+                        last_error = Some(err);
+                    }
+                }
+            }
+
+            let err = last_error.expect("there is at least one base url; qed;");
+
+            if !transient || retry >= self.max_retries {
+                return Err(err);
+            }
+
+            retry += 1;
+
+            tokio::time::sleep(RETRY_DELAY * retry).await;
+        }
+    }
+
+    async fn get_json_from<T: DeserializeOwned>(&self, base_url: &str, path: &str) -> Result<T> {
+        let url = format!("{base_url}{path}");
 
         debug!(%url, "get_json");
 
@@ -287,3 +433,35 @@ impl Display for BlockId {
         }
     }
 }
+
+/// The `(start_period, count)` of the requests needed to fetch the light client updates for the
+/// `count` periods starting at `start_period`.
+fn light_client_update_requests(start_period: u64, count: u64) -> Vec<(u64, u64)> {
+    let end_period = start_period + count;
+
+    (start_period..end_period)
+        .step_by(MAX_REQUEST_LIGHT_CLIENT_UPDATES as usize)
+        .map(|start_period| {
+            (
+                start_period,
+                (end_period - start_period).min(MAX_REQUEST_LIGHT_CLIENT_UPDATES),
+            )
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn light_client_update_requests_are_chunked() {
+        assert_eq!(light_client_update_requests(11, 0), vec![]);
+        assert_eq!(light_client_update_requests(11, 1), vec![(11, 1)]);
+        assert_eq!(light_client_update_requests(11, 128), vec![(11, 128)]);
+        assert_eq!(
+            light_client_update_requests(11, 290),
+            vec![(11, 128), (139, 128), (267, 34)]
+        );
+    }
+}
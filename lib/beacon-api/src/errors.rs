@@ -14,6 +14,32 @@ pub enum Error {
     Json(#[from] serde_json::Error),
     #[error("unknown error ({code}): {text}")]
     Other { code: StatusCode, text: String },
+    #[error(
+        "requested {count} light client updates starting at period {start_period}, but the \
+        beacon node returned {found}"
+    )]
+    IncompleteLightClientUpdates {
+        start_period: u64,
+        count: u64,
+        found: u64,
+    },
+}
+
+impl Error {
+    /// Whether the request may succeed if it is retried, i.e. the beacon node was unreachable or
+    /// overloaded.
+    pub fn is_transient(&self) -> bool {
+        match self {
+            Error::Http(_) => true,
+            Error::Other { code, .. } => {
+                code.is_server_error() || *code == StatusCode::TOO_MANY_REQUESTS
+            }
+            Error::Internal(_)
+            | Error::NotFound(_)
+            | Error::Json(_)
+            | Error::IncompleteLightClientUpdates { .. } => false,
+        }
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize, thiserror::Error)]
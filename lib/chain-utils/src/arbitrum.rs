@@ -28,7 +28,7 @@ use crate::{
         EthereumIbcChain, EthereumKeyring, EthereumSignerMiddleware, EthereumSignersConfig,
         ReadWrite, Readonly,
     },
-    keyring::{ChainKeyring, ConcurrentKeyring, KeyringConfig, SignerBalance},
+    keyring::{ChainKeyring, ConcurrentKeyring, KeyringConfig, KeyringConfigError, SignerBalance},
 };
 
 pub const ARBITRUM_REVISION_NUMBER: u64 = 0;
@@ -102,6 +102,8 @@ pub enum ArbitrumInitError {
     Ws(#[from] WsClientError),
     #[error("provider error")]
     Provider(#[from] ProviderError),
+    #[error("invalid keyring")]
+    Keyring(#[from] KeyringConfigError),
 }
 
 impl Arbitrum {
@@ -117,7 +119,7 @@ impl Arbitrum {
                 config.ibc_handler_address,
                 chain_id.as_u64(),
                 provider.clone(),
-            ),
+            )?,
             ibc_handler_address: config.ibc_handler_address,
             multicall_address: config.multicall_address,
             provider: Arc::new(provider),
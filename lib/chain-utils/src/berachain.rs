@@ -35,7 +35,7 @@ use crate::{
         balance_of_signers, EthereumConsensusChain, EthereumIbcChain, EthereumSignerMiddleware,
         EthereumSignersConfig, ReadWrite,
     },
-    keyring::{ChainKeyring, ConcurrentKeyring, SignerBalance},
+    keyring::{ChainKeyring, ConcurrentKeyring, KeyringConfigError, SignerBalance},
     BoxDynError,
 };
 
@@ -247,6 +247,8 @@ pub enum BerachainInitError {
         #[source]
         source: Option<ParseIntError>,
     },
+    #[error("invalid keyring")]
+    Keyring(#[from] KeyringConfigError),
 }
 
 impl Berachain {
@@ -283,7 +285,7 @@ impl Berachain {
                 config.ibc_handler_address,
                 execution_chain_id.as_u64(),
                 provider.clone(),
-            )),
+            )?),
             provider: Arc::new(provider),
             consensus_chain_revision,
         })
@@ -1,18 +1,27 @@
-use std::{num::ParseIntError, sync::Arc};
+use std::{
+    collections::{BTreeMap, HashMap},
+    num::ParseIntError,
+    sync::Arc,
+};
 
 use bip32::secp256k1::ecdsa;
 use protos::cosmos::auth::v1beta1::Bech32PrefixRequest;
 use serde::{Deserialize, Serialize};
 use tendermint_rpc::{Client, WebSocketClient, WebSocketClientUrl};
+use tracing::info;
 use unionlabs::{
     hash::H256, ibc::core::client::height::Height, signer::CosmosSigner, WasmClientType,
 };
 
 use crate::{
     cosmos_sdk::{CosmosKeyring, CosmosSdkChain, CosmosSdkChainRpcs, GasConfig},
-    keyring::{ChainKeyring, ConcurrentKeyring, KeyringConfig, KeyringEntry, SignerBalance},
+    keyring::{
+        ChainKeyring, ConcurrentKeyring, KeyringConfig, KeyringConfigError, KeyringEntry,
+        SignerBalance,
+    },
 };
 
+/// Any Cosmos SDK chain with a standard 07-tendermint client on its counterparties.
 #[derive(Debug, Clone)]
 pub struct Cosmos {
     pub chain_id: String,
@@ -32,6 +41,10 @@ pub struct Config {
     pub ws_url: WebSocketClientUrl,
     pub grpc_url: String,
     pub gas_config: GasConfig,
+    /// The bech32 prefix of account addresses on this chain. If not set, it is queried from the
+    /// chain with `cosmos.auth.v1beta1.Query/Bech32Prefix`, which not all chains support.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bech32_prefix: Option<String>,
 }
 
 impl ChainKeyring for Cosmos {
@@ -89,6 +102,24 @@ pub enum CosmosInitError {
         #[source]
         source: Option<ParseIntError>,
     },
+    #[error("unable to connect to grpc endpoint {grpc_url}")]
+    GrpcConnect {
+        grpc_url: String,
+        #[source]
+        source: tonic::transport::Error,
+    },
+    #[error("unable to query the bech32 prefix")]
+    Bech32Prefix(#[source] tonic::Status),
+    #[error("invalid keyring")]
+    Keyring(#[from] KeyringConfigError),
+    #[error("the private key of {name} must be 32 bytes, found {len}")]
+    PrivateKeyLength { name: String, len: usize },
+    #[error("the private key of {name} is not a valid secp256k1 private key")]
+    InvalidPrivateKey {
+        name: String,
+        #[source]
+        source: ecdsa::Error,
+    },
 }
 
 impl Cosmos {
@@ -102,51 +133,61 @@ impl Cosmos {
 
         let chain_id = tm_client.status().await?.node_info.network.to_string();
 
-        let chain_revision = chain_id
-            .split('-')
-            .last()
-            .ok_or_else(|| CosmosInitError::ChainIdParse {
-                found: chain_id.clone(),
-                source: None,
-            })?
-            .parse()
-            .map_err(|err| CosmosInitError::ChainIdParse {
-                found: chain_id.clone(),
-                source: Some(err),
-            })?;
-
-        let prefix = protos::cosmos::auth::v1beta1::query_client::QueryClient::connect(
-            config.grpc_url.clone(),
-        )
-        .await
-        .unwrap()
-        .bech32_prefix(Bech32PrefixRequest {})
-        .await
-        .unwrap()
-        .into_inner()
-        .bech32_prefix;
+        let chain_revision = parse_chain_revision(&chain_id)?;
+
+        let prefix = match config.bech32_prefix {
+            Some(prefix) => prefix,
+            None => {
+                protos::cosmos::auth::v1beta1::query_client::QueryClient::connect(
+                    config.grpc_url.clone(),
+                )
+                .await
+                .map_err(|source| CosmosInitError::GrpcConnect {
+                    grpc_url: config.grpc_url.clone(),
+                    source,
+                })?
+                .bech32_prefix(Bech32PrefixRequest {})
+                .await
+                .map_err(CosmosInitError::Bech32Prefix)?
+                .into_inner()
+                .bech32_prefix
+            }
+        };
+
+        let keys = config
+            .keyring
+            .keys
+            .iter()
+            .map(|entry| {
+                let name = entry.name();
+                let key = entry.value()?;
+
+                let key = <[u8; 32]>::try_from(key.as_slice()).map_err(|_| {
+                    CosmosInitError::PrivateKeyLength {
+                        name: name.clone(),
+                        len: key.len(),
+                    }
+                })?;
+
+                match ecdsa::SigningKey::from_bytes(&key.into()) {
+                    Ok(signing_key) => Ok((name, signing_key)),
+                    Err(source) => Err(CosmosInitError::InvalidPrivateKey { name, source }),
+                }
+            })
+            .collect::<Result<Vec<_>, CosmosInitError>>()?;
 
         Ok(Self {
             keyring: CosmosKeyring::new(
                 config.keyring.name,
-                config
-                    .keyring
-                    .keys
-                    .into_iter()
-                    // TODO: Make this configurable or fetch it from the chain
-                    .map(|entry| {
-                        let signer = CosmosSigner::new(
-                            ecdsa::SigningKey::from_bytes(entry.value().as_slice().into())
-                                .expect("invalid private key"),
-                            prefix.clone(),
-                        );
-
-                        KeyringEntry {
-                            name: entry.name(),
-                            address: signer.to_string(),
-                            signer,
-                        }
-                    }),
+                keys.into_iter().map(|(name, signing_key)| {
+                    let signer = CosmosSigner::new(signing_key, prefix.clone());
+
+                    KeyringEntry {
+                        name,
+                        address: signer.to_string(),
+                        signer,
+                    }
+                }),
             ),
             tm_client,
             chain_id,
@@ -163,3 +204,114 @@ impl Cosmos {
         Height::new_with_revision(self.chain_revision, height)
     }
 }
+
+/// Parse the revision number out of a chain id of the form `<chain>-<revision-number>`.
+pub fn parse_chain_revision(chain_id: &str) -> Result<u64, CosmosInitError> {
+    chain_id
+        .rsplit_once('-')
+        .ok_or_else(|| CosmosInitError::ChainIdParse {
+            found: chain_id.to_owned(),
+            source: None,
+        })?
+        .1
+        .parse()
+        .map_err(|err| CosmosInitError::ChainIdParse {
+            found: chain_id.to_owned(),
+            source: Some(err),
+        })
+}
+
+/// The configuration of a set of Cosmos SDK chains, keyed by chain id.
+///
+/// ```json
+/// {
+///   "osmosis-1": { "ws_url": "...", "grpc_url": "...", "keyring": { ... }, "gas_config": { ... } },
+///   "neutron-1": { "ws_url": "...", "grpc_url": "...", "keyring": { ... }, "gas_config": { ... } }
+/// }
+/// ```
+pub type RegistryConfig = BTreeMap<String, Config>;
+
+/// A set of Cosmos SDK chains, keyed by chain id. This allows for any number of Cosmos SDK chains
+/// to be configured without them needing their own chain type.
+#[derive(Debug, Clone, Default)]
+pub struct CosmosRegistry {
+    chains: HashMap<String, Cosmos>,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum CosmosRegistryError {
+    #[error("error initializing chain {chain_id}")]
+    Init {
+        chain_id: String,
+        #[source]
+        source: CosmosInitError,
+    },
+    #[error("chain {expected} is configured with an endpoint for chain {found}")]
+    ChainIdMismatch { expected: String, found: String },
+}
+
+impl CosmosRegistry {
+    /// Initialize all of the chains in `config`, ensuring that the endpoints of each chain are for
+    /// the chain id they are configured under.
+    pub async fn new(config: RegistryConfig) -> Result<Self, CosmosRegistryError> {
+        let mut chains = HashMap::with_capacity(config.len());
+
+        for (chain_id, config) in config {
+            let chain = Cosmos::new(config)
+                .await
+                .map_err(|source| CosmosRegistryError::Init {
+                    chain_id: chain_id.clone(),
+                    source,
+                })?;
+
+            if chain.chain_id != chain_id {
+                return Err(CosmosRegistryError::ChainIdMismatch {
+                    expected: chain_id,
+                    found: chain.chain_id,
+                });
+            }
+
+            info!(%chain_id, bech32_prefix = %chain.bech32_prefix, "registered cosmos sdk chain");
+
+            chains.insert(chain_id, chain);
+        }
+
+        Ok(Self { chains })
+    }
+
+    #[must_use]
+    pub fn get(&self, chain_id: &str) -> Option<&Cosmos> {
+        self.chains.get(chain_id)
+    }
+
+    pub fn chain_ids(&self) -> impl Iterator<Item = &str> {
+        self.chains.keys().map(String::as_str)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Cosmos> {
+        self.chains.values()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn chain_revision() {
+        assert_eq!(parse_chain_revision("osmosis-1").unwrap(), 1);
+        assert_eq!(parse_chain_revision("union-testnet-9").unwrap(), 9);
+
+        assert!(matches!(
+            parse_chain_revision("localnet"),
+            Err(CosmosInitError::ChainIdParse { source: None, .. })
+        ));
+        assert!(matches!(
+            parse_chain_revision("pion-one"),
+            Err(CosmosInitError::ChainIdParse {
+                source: Some(_),
+                ..
+            })
+        ));
+    }
+}
@@ -1,4 +1,4 @@
-use std::sync::Arc;
+use std::{fmt::Display, sync::Arc};
 
 use prost::{Message, Name};
 use serde::{Deserialize, Serialize};
@@ -6,6 +6,8 @@ use sha2::Digest;
 use tendermint_rpc::{Client, WebSocketClient};
 use tracing::{debug, error, info, warn};
 use unionlabs::{
+    bech32::Bech32,
+    bytes::Bytes,
     cosmos::{
         auth::base_account::BaseAccount,
         base::{abci::gas_info::GasInfo, coin::Coin},
@@ -28,10 +30,55 @@ use unionlabs::{
 use crate::{
     cosmos_sdk::cosmos_sdk_error::{CosmosSdkError, SdkError},
     keyring::{ConcurrentKeyring, SignerBalance},
+    signer::{AnySigner, Signer, SignerError},
 };
 
 pub type CosmosKeyring = ConcurrentKeyring<String, CosmosSigner>;
 
+/// A keyring of [`CosmosSdkSigner`]s, where each key can be held by any of the supported signing
+/// backends.
+pub type CosmosSdkKeyring = ConcurrentKeyring<String, CosmosSdkSigner>;
+
+/// The signer of a cosmos sdk account, displayed as the bech32 address of the account.
+#[derive(Debug, Clone)]
+pub struct CosmosSdkSigner<S = AnySigner> {
+    signer: S,
+    address: String,
+}
+
+impl<S: Signer> CosmosSdkSigner<S> {
+    pub fn new(signer: S, bech32_prefix: String) -> Self {
+        // bech32(prefix, ripemd(sha256(pubkey)))
+        let address = Bech32::new(
+            bech32_prefix,
+            Bytes::from(
+                ripemd::Ripemd160::digest(sha2::Sha256::digest(signer.public_key())).to_vec(),
+            ),
+        )
+        .to_string();
+
+        Self { signer, address }
+    }
+
+    pub fn public_key(&self) -> [u8; 33] {
+        self.signer.public_key()
+    }
+
+    /// Sign `bytes` (i.e. an encoded `SignDoc`), returning the 64 byte `r || s` signature.
+    pub async fn sign(&self, bytes: &[u8]) -> Result<Vec<u8>, SignerError> {
+        self.signer
+            .sign_prehash(H256::new(sha2::Sha256::digest(bytes).into()))
+            .await
+            .map(|signature| signature.signature.to_bytes().to_vec())
+    }
+}
+
+impl<S> Display for CosmosSdkSigner<S> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.address)
+    }
+}
+
 // TODO: Look into how to support `osmosis.txfees.v1beta1.Query/GetEipBaseFee`
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct GasConfig {
@@ -43,6 +90,10 @@ pub struct GasConfig {
     pub max_gas: u64,
     #[serde(default)]
     pub min_gas: u64,
+    /// The maximum fee (in `gas_denom`) that will be paid for a single transaction. Transactions
+    /// that would cost more than this after simulation will not be submitted.
+    #[serde(default, with = "::serde_utils::string_opt")]
+    pub max_fee: Option<u128>,
 }
 
 impl GasConfig {
@@ -51,7 +102,8 @@ impl GasConfig {
         let gas_limit = u128_saturating_mul_f64(gas.into(), self.gas_multiplier)
             .clamp(self.min_gas.into(), self.max_gas.into());
 
-        let amount = u128_saturating_mul_f64(gas.into(), self.gas_price);
+        // the fee is paid for the full gas limit, not just the gas that is used
+        let amount = u128_saturating_mul_f64(gas_limit, self.gas_price);
 
         Fee {
             amount: vec![Coin {
@@ -63,6 +115,28 @@ impl GasConfig {
             granter: String::new(),
         }
     }
+
+    /// Returns an error if the amount of `fee` exceeds the configured `max_fee`.
+    pub fn check_max_fee(&self, fee: &Fee) -> Result<(), MaxFeeExceeded> {
+        let amount = fee
+            .amount
+            .iter()
+            .filter(|coin| coin.denom == self.gas_denom)
+            .map(|coin| coin.amount)
+            .sum::<u128>();
+
+        match self.max_fee {
+            Some(max_fee) if amount > max_fee => Err(MaxFeeExceeded { amount, max_fee }),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, thiserror::Error)]
+#[error("fee of {amount} exceeds the max fee of {max_fee}")]
+pub struct MaxFeeExceeded {
+    pub amount: u128,
+    pub max_fee: u128,
 }
 
 pub trait CosmosSdkChainRpcs {
@@ -207,6 +281,8 @@ pub trait CosmosSdkChainExt: CosmosSdkChainRpcs {
 
         auth_info.fee = self.gas_config().mk_fee(simulation_gas_info.gas_used);
 
+        self.gas_config().check_max_fee(&auth_info.fee)?;
+
         // dbg!(&auth_info.fee);
 
         info!(
@@ -455,8 +531,8 @@ pub trait CosmosSdkChainExt: CosmosSdkChainRpcs {
     }
 }
 
-pub async fn fetch_balances(
-    keyring: &CosmosKeyring,
+pub async fn fetch_balances<S: 'static>(
+    keyring: &ConcurrentKeyring<String, S>,
     gas_denom: String,
     grpc_url: String,
 ) -> Vec<SignerBalance<String>> {
@@ -509,6 +585,35 @@ fn test_u128_mul_f64() {
     assert_eq!(val, 110);
 }
 
+#[test]
+fn test_mk_fee() {
+    let gas_config = GasConfig {
+        gas_price: 2.0,
+        gas_denom: "muno".to_owned(),
+        gas_multiplier: 1.5,
+        max_gas: 1000,
+        min_gas: 0,
+        max_fee: Some(1500),
+    };
+
+    let fee = gas_config.mk_fee(500);
+    assert_eq!(fee.gas_limit, 750);
+    assert_eq!(fee.amount[0].amount, 1500);
+    assert_eq!(gas_config.check_max_fee(&fee), Ok(()));
+
+    // the gas limit is clamped to max_gas, and the fee is paid for the gas limit
+    let fee = gas_config.mk_fee(u64::MAX);
+    assert_eq!(fee.gas_limit, 1000);
+    assert_eq!(fee.amount[0].amount, 2000);
+    assert_eq!(
+        gas_config.check_max_fee(&fee),
+        Err(MaxFeeExceeded {
+            amount: 2000,
+            max_fee: 1500
+        })
+    );
+}
+
 impl<T: CosmosSdkChain + CosmosSdkChainRpcs> CosmosSdkChainIbcExt for T {}
 
 impl<T: CosmosSdkChainRpcs> CosmosSdkChainExt for T {}
@@ -529,6 +634,8 @@ pub enum BroadcastTxCommitError {
     AccountSequenceMismatch(#[source] Option<tonic::Status>),
     #[error("out of gas")]
     OutOfGas,
+    #[error(transparent)]
+    MaxFeeExceeded(#[from] MaxFeeExceeded),
 }
 
 #[allow(non_upper_case_globals)] // TODO: Report this upstream to num_enum
@@ -920,5 +1027,29 @@ pub mod cosmos_sdk_error {
             ErrRouteNotFound                          = errorsmod.Register(SubModuleName, 32, "light client module route not found")
             ErrClientTypeNotSupported                 = errorsmod.Register(SubModuleName, 33, "client type not supported")
         )
+
+        // https://github.com/cosmos/cosmos-sdk/blob/v0.50.10/x/feegrant/errors.go
+        #[err(name = FeegrantError, codespace = "feegrant")]
+        var (
+            ErrFeeLimitExceeded  = errorsmod.Register(DefaultCodespace, 2, "fee limit exceeded")
+            ErrFeeLimitExpired   = errorsmod.Register(DefaultCodespace, 3, "fee allowance expired")
+            ErrInvalidDuration   = errorsmod.Register(DefaultCodespace, 4, "invalid duration")
+            ErrNoAllowance       = errorsmod.Register(DefaultCodespace, 5, "no allowance")
+            ErrNoMessages        = errorsmod.Register(DefaultCodespace, 6, "allowed messages are empty")
+            ErrMessageNotAllowed = errorsmod.Register(DefaultCodespace, 7, "message not allowed")
+        )
+
+        // https://github.com/cosmos/cosmos-sdk/blob/v0.50.10/x/authz/errors.go
+        #[err(name = AuthzError, codespace = "authz")]
+        var (
+            ErrNoAuthorizationFound      = errorsmod.Register(ModuleName, 2, "authorization not found")
+            ErrInvalidExpirationTime     = errorsmod.Register(ModuleName, 3, "expiration time of authorization should be more than current time")
+            ErrUnknownAuthorizationType  = errorsmod.Register(ModuleName, 4, "unknown authorization type")
+            ErrNoGrantKeyFound           = errorsmod.Register(ModuleName, 5, "grant key not found")
+            ErrAuthorizationExpired      = errorsmod.Register(ModuleName, 6, "authorization expired")
+            ErrGranteeIsGranter          = errorsmod.Register(ModuleName, 7, "grantee and granter should be different")
+            ErrAuthorizationNumOfSigners = errorsmod.Register(ModuleName, 9, "authorization can be given to msg with only one signer")
+            ErrNegativeMaxTokens         = errorsmod.Register(ModuleName, 12, "max tokens should be positive")
+        )
     }
 }
@@ -0,0 +1,227 @@
+//! ICS-20 denom tracing.
+//!
+//! Tokens transferred over ICS-20 are represented on the receiving chain by a denom of the form
+//! `ibc/{HASH}`, where `HASH` is the uppercase hex encoded sha256 hash of the full denom path
+//! (`{port}/{channel}/.../{base_denom}`). The hash is not reversible, so the full trace must be
+//! queried from the transfer module of the chain that minted the voucher.
+
+use std::fmt::Display;
+
+use sha2::{Digest, Sha256};
+use tracing::debug;
+use unionlabs::id::{ChannelId, PortId};
+
+/// The prefix of all ICS-20 voucher denoms.
+pub const IBC_DENOM_PREFIX: &str = "ibc/";
+
+/// The trace of an ICS-20 denom, as returned by the transfer module.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
+pub struct DenomTrace {
+    /// The chain of `{port}/{channel}` pairs the token was transferred over, most recent hop
+    /// first. Empty for native tokens.
+    pub path: String,
+    pub base_denom: String,
+}
+
+impl DenomTrace {
+    /// Split a full denom path (i.e. `transfer/channel-0/transfer/channel-7/uatom`) into its trace
+    /// path and base denom.
+    ///
+    /// Base denoms are allowed to contain `/` (such as `gamm/pool/1` or
+    /// `factory/{creator}/{subdenom}`), so only leading segments that form valid
+    /// `{port}/{channel}` pairs are considered part of the trace path.
+    pub fn from_full_path(full_path: &str) -> Self {
+        let mut segments = full_path.split('/').peekable();
+        let mut hops = vec![];
+
+        loop {
+            let mut rest = segments.clone();
+
+            match (rest.next(), rest.next()) {
+                (Some(port), Some(channel))
+                    if rest.peek().is_some()
+                        && port.parse::<PortId>().is_ok()
+                        && ChannelId::from_str_prefixed(channel).is_ok() =>
+                {
+                    hops.push(format!("{port}/{channel}"));
+                    segments = rest;
+                }
+                _ => break,
+            }
+        }
+
+        Self {
+            path: hops.join("/"),
+            base_denom: segments.collect::<Vec<_>>().join("/"),
+        }
+    }
+
+    /// The full path of this denom, `{path}/{base_denom}`.
+    pub fn full_path(&self) -> String {
+        if self.path.is_empty() {
+            self.base_denom.clone()
+        } else {
+            format!("{}/{}", self.path, self.base_denom)
+        }
+    }
+
+    /// The denom of this token on the chain it was last transferred to. This is the base denom for
+    /// native tokens, and `ibc/{HASH}` otherwise.
+    pub fn ibc_denom(&self) -> String {
+        if self.path.is_empty() {
+            self.base_denom.clone()
+        } else {
+            format!("{IBC_DENOM_PREFIX}{}", denom_hash(&self.full_path()))
+        }
+    }
+
+    /// The trace of this token after it has been received on `port_id`/`channel_id`.
+    pub fn prefixed(&self, port_id: &PortId, channel_id: &ChannelId) -> Self {
+        let hop = format!("{port_id}/{channel_id:#}");
+
+        Self {
+            path: if self.path.is_empty() {
+                hop
+            } else {
+                format!("{hop}/{}", self.path)
+            },
+            base_denom: self.base_denom.clone(),
+        }
+    }
+}
+
+/// The uppercase hex encoded sha256 hash of a full denom path, as used in `ibc/{HASH}` denoms.
+pub fn denom_hash(full_path: &str) -> String {
+    hex::encode_upper(Sha256::digest(full_path.as_bytes()))
+}
+
+/// Compute the `ibc/{HASH}` denom of `base_denom` after it has been received on
+/// `port_id`/`channel_id`.
+///
+/// `base_denom` may itself be a full denom path (for tokens that have already been transferred
+/// over other channels), but must not be an `ibc/{HASH}` denom; resolve it with
+/// [`query_denom_trace`] first.
+pub fn ibc_denom(port_id: &PortId, channel_id: &ChannelId, base_denom: &str) -> String {
+    DenomTrace::from_full_path(base_denom)
+        .prefixed(port_id, channel_id)
+        .ibc_denom()
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum DenomTraceError {
+    #[error("error connecting to grpc server at {grpc_url}")]
+    Connect {
+        grpc_url: String,
+        #[source]
+        source: tonic::transport::Error,
+    },
+    #[error("error querying denom trace for {denom}")]
+    Query {
+        denom: String,
+        #[source]
+        source: tonic::Status,
+    },
+    #[error("denom trace response for {0} was empty")]
+    MissingDenomTrace(String),
+}
+
+/// Resolve the full trace of an `ibc/{HASH}` denom (or bare `HASH`) via the transfer module gRPC
+/// query on the chain that minted it.
+///
+/// Returns `None` if the denom is not known to the chain. Denoms without the `ibc/` prefix are
+/// assumed to be native (or a full path already), and are parsed without querying the chain.
+pub async fn query_denom_trace(
+    grpc_url: impl Display,
+    denom: &str,
+) -> Result<Option<DenomTrace>, DenomTraceError> {
+    let Some(hash) = denom.strip_prefix(IBC_DENOM_PREFIX).or_else(|| {
+        (denom.len() == 64 && denom.bytes().all(|b| b.is_ascii_hexdigit())).then_some(denom)
+    }) else {
+        return Ok(Some(DenomTrace::from_full_path(denom)));
+    };
+
+    let grpc_url = grpc_url.to_string();
+
+    let mut client = protos::ibc::applications::transfer::v1::query_client::QueryClient::connect(
+        grpc_url.clone(),
+    )
+    .await
+    .map_err(|source| DenomTraceError::Connect {
+        grpc_url: grpc_url.clone(),
+        source,
+    })?;
+
+    let response = client
+        .denom_trace(
+            protos::ibc::applications::transfer::v1::QueryDenomTraceRequest {
+                hash: hash.to_owned(),
+            },
+        )
+        .await;
+
+    match response {
+        Ok(response) => {
+            let trace = response
+                .into_inner()
+                .denom_trace
+                .ok_or_else(|| DenomTraceError::MissingDenomTrace(denom.to_owned()))?;
+
+            Ok(Some(DenomTrace {
+                path: trace.path,
+                base_denom: trace.base_denom,
+            }))
+        }
+        Err(status) if status.code() == tonic::Code::NotFound => {
+            debug!(%denom, %status, "denom trace not found");
+
+            Ok(None)
+        }
+        Err(source) => Err(DenomTraceError::Query {
+            denom: denom.to_owned(),
+            source,
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn from_full_path() {
+        assert_eq!(
+            DenomTrace::from_full_path("transfer/channel-0/transfer/channel-7/uatom"),
+            DenomTrace {
+                path: "transfer/channel-0/transfer/channel-7".to_owned(),
+                base_denom: "uatom".to_owned(),
+            }
+        );
+
+        assert_eq!(
+            DenomTrace::from_full_path("transfer/channel-0/gamm/pool/1"),
+            DenomTrace {
+                path: "transfer/channel-0".to_owned(),
+                base_denom: "gamm/pool/1".to_owned(),
+            }
+        );
+
+        assert_eq!(
+            DenomTrace::from_full_path("muno"),
+            DenomTrace {
+                path: String::new(),
+                base_denom: "muno".to_owned(),
+            }
+        );
+    }
+
+    #[test]
+    fn ibc_denom_hash() {
+        // atom on osmosis
+        assert_eq!(
+            ibc_denom(&"transfer".parse().unwrap(), &ChannelId::new(0), "uatom"),
+            "ibc/27394FB092D2ECCD56123C74F36E4C1F926001CEADA9CA97EA622B25F41E5EB2"
+        );
+
+        assert_eq!(DenomTrace::from_full_path("muno").ibc_denom(), "muno");
+    }
+}
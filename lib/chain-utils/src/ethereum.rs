@@ -42,7 +42,9 @@ use unionlabs::{
     ErrorReporter,
 };
 
-use crate::keyring::{ChainKeyring, ConcurrentKeyring, KeyringConfig, KeyringEntry, SignerBalance};
+use crate::keyring::{
+    ChainKeyring, ConcurrentKeyring, KeyringConfig, KeyringConfigError, KeyringEntry, SignerBalance,
+};
 
 pub type EthereumKeyring = ConcurrentKeyring<H160, IBCHandler<EthereumSignerMiddleware>>;
 
@@ -271,7 +273,7 @@ pub trait EthereumSignersConfig: Send + Sync + 'static {
         ibc_handler_address: H160,
         chain_id: u64,
         provider: Provider<Ws>,
-    ) -> Self::Out;
+    ) -> Result<Self::Out, KeyringConfigError>;
 }
 
 pub enum Readonly {}
@@ -285,8 +287,8 @@ impl EthereumSignersConfig for Readonly {
         _ibc_handler_address: H160,
         _chain_id: u64,
         _provider: Provider<Ws>,
-    ) -> Self::Out {
-        config
+    ) -> Result<Self::Out, KeyringConfigError> {
+        Ok(config)
     }
 }
 
@@ -301,12 +303,18 @@ impl EthereumSignersConfig for ReadWrite {
         ibc_handler_address: H160,
         chain_id: u64,
         provider: Provider<Ws>,
-    ) -> Self::Out {
-        ConcurrentKeyring::new(
+    ) -> Result<Self::Out, KeyringConfigError> {
+        let keys = config
+            .keys
+            .iter()
+            .map(|config| Ok((config.name(), config.value()?)))
+            .collect::<Result<Vec<_>, KeyringConfigError>>()?;
+
+        Ok(ConcurrentKeyring::new(
             config.name,
-            config.keys.into_iter().map(|config| {
+            keys.into_iter().map(|(name, key)| {
                 let signing_key = <ecdsa::SigningKey as bip32::PrivateKey>::from_bytes(
-                    &config.value().as_slice().try_into().unwrap(),
+                    &key.as_slice().try_into().unwrap(),
                 )
                 .unwrap();
 
@@ -320,12 +328,12 @@ impl EthereumSignersConfig for ReadWrite {
                 ));
 
                 KeyringEntry {
-                    name: config.name(),
+                    name,
                     address: address.into(),
                     signer: IBCHandler::new(ibc_handler_address, signer_middleware.clone()),
                 }
             }),
-        )
+        ))
     }
 }
 
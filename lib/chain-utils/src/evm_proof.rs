@@ -0,0 +1,193 @@
+//! Fetching and verification of execution layer state proofs via `eth_getProof`.
+//!
+//! Proofs returned by the execution RPC are verified locally before being used in a message, such
+//! that an RPC returning an invalid (or inconsistent) proof is caught here with a useful error,
+//! rather than by the counterparty light client failing the submitted transaction.
+
+use alloy::{
+    providers::Provider,
+    rpc::types::EIP1186AccountProofResponse,
+    transports::{Transport, TransportError},
+};
+use ethereum_light_client_types::{AccountProof, StorageProof};
+use evm_storage_verifier::{
+    error::Error as VerifierError, verify_account_storage_root, verify_storage_absence,
+    verify_storage_proof,
+};
+use unionlabs::{
+    hash::{H160, H256},
+    uint::U256,
+};
+
+#[derive(Debug, thiserror::Error)]
+pub enum EvmProofError {
+    #[error("error fetching proof")]
+    Rpc(#[source] TransportError),
+    #[error("expected {expected} storage proofs, but eth_getProof returned {found}")]
+    StorageProofCount { expected: usize, found: usize },
+    #[error("expected a storage proof for slot {expected}, but eth_getProof returned {found}")]
+    UnexpectedSlot { expected: U256, found: U256 },
+    #[error("invalid account proof")]
+    InvalidAccountProof(#[source] VerifierError),
+    #[error("invalid storage proof for slot {slot}")]
+    InvalidStorageProof {
+        slot: U256,
+        #[source]
+        source: VerifierError,
+    },
+    #[error(
+        "invalid storage proof for slot {slot}, the slot is empty but the proof is for a value"
+    )]
+    NonEmptyAbsenceProof { slot: U256 },
+}
+
+/// The account proof of a contract along with proofs of some of its storage slots, as returned by
+/// `eth_getProof`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ContractProof {
+    pub address: H160,
+    pub account_proof: AccountProof,
+    /// The proofs of the requested storage slots, in the order they were requested.
+    pub storage_proofs: Vec<StorageProof>,
+}
+
+impl ContractProof {
+    /// Fetch the proofs of `slots` in the storage of the contract at `address`, at the execution
+    /// block `block_number`.
+    ///
+    /// NOTE: The returned proof is not verified; see [`Self::verify`] and
+    /// [`Self::fetch_verified`].
+    pub async fn fetch<P: Provider<T>, T: Transport + Clone>(
+        provider: &P,
+        address: H160,
+        slots: Vec<U256>,
+        block_number: u64,
+    ) -> Result<Self, EvmProofError> {
+        let response = provider
+            .get_proof(
+                address.get().into(),
+                slots.iter().map(|slot| slot.to_be_bytes().into()).collect(),
+            )
+            .block_id(block_number.into())
+            .await
+            .map_err(EvmProofError::Rpc)?;
+
+        Self::from_response(response, &slots)
+    }
+
+    /// Fetch the proofs of `slots` as in [`Self::fetch`], and verify them against `state_root`,
+    /// which must be the state root of the execution block `block_number`.
+    pub async fn fetch_verified<P: Provider<T>, T: Transport + Clone>(
+        provider: &P,
+        address: H160,
+        slots: Vec<U256>,
+        block_number: u64,
+        state_root: H256,
+    ) -> Result<Self, EvmProofError> {
+        let proof = Self::fetch(provider, address, slots, block_number).await?;
+
+        proof.verify(state_root)?;
+
+        Ok(proof)
+    }
+
+    fn from_response(
+        response: EIP1186AccountProofResponse,
+        slots: &[U256],
+    ) -> Result<Self, EvmProofError> {
+        if response.storage_proof.len() != slots.len() {
+            return Err(EvmProofError::StorageProofCount {
+                expected: slots.len(),
+                found: response.storage_proof.len(),
+            });
+        }
+
+        let storage_proofs = response
+            .storage_proof
+            .into_iter()
+            .zip(slots)
+            .map(|(proof, slot)| {
+                let key = U256::from_be_bytes(proof.key.as_b256().0);
+
+                if key != *slot {
+                    return Err(EvmProofError::UnexpectedSlot {
+                        expected: *slot,
+                        found: key,
+                    });
+                }
+
+                Ok(StorageProof {
+                    key,
+                    value: U256::from_be_bytes(proof.value.to_be_bytes()),
+                    proof: proof.proof.into_iter().map(|node| node.to_vec()).collect(),
+                })
+            })
+            .collect::<Result<_, _>>()?;
+
+        Ok(Self {
+            address: response.address.into(),
+            account_proof: AccountProof {
+                storage_root: response.storage_hash.into(),
+                proof: response
+                    .account_proof
+                    .into_iter()
+                    .map(|node| node.to_vec())
+                    .collect(),
+            },
+            storage_proofs,
+        })
+    }
+
+    /// Verify the account proof against the execution `state_root`, and the storage proofs against
+    /// the proven storage root of the account.
+    pub fn verify(&self, state_root: H256) -> Result<(), EvmProofError> {
+        verify_account_storage_root(
+            state_root,
+            &self.address,
+            &self.account_proof.proof,
+            &self.account_proof.storage_root,
+        )
+        .map_err(EvmProofError::InvalidAccountProof)?;
+
+        for proof in &self.storage_proofs {
+            verify_storage_proof_of(self.account_proof.storage_root, proof)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Verify a single storage proof against `storage_root`, which must already be verified.
+///
+/// Empty slots are proven by proving the absence of the slot in the storage trie.
+pub fn verify_storage_proof_of(
+    storage_root: H256,
+    proof: &StorageProof,
+) -> Result<(), EvmProofError> {
+    if proof.value == U256::ZERO {
+        let absent =
+            verify_storage_absence(storage_root, proof.key, &proof.proof).map_err(|source| {
+                EvmProofError::InvalidStorageProof {
+                    slot: proof.key,
+                    source,
+                }
+            })?;
+
+        if absent {
+            Ok(())
+        } else {
+            Err(EvmProofError::NonEmptyAbsenceProof { slot: proof.key })
+        }
+    } else {
+        verify_storage_proof(
+            storage_root,
+            proof.key,
+            &rlp::encode(&proof.value),
+            &proof.proof,
+        )
+        .map_err(|source| EvmProofError::InvalidStorageProof {
+            slot: proof.key,
+            source,
+        })
+    }
+}
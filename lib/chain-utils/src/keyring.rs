@@ -1,10 +1,20 @@
-use std::{collections::HashMap, fmt::Display, hash::Hash, path::PathBuf, sync::Arc};
+use std::{
+    collections::HashMap,
+    fmt::Display,
+    hash::Hash,
+    num::NonZeroUsize,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+};
 
 use crossbeam_queue::ArrayQueue;
 use futures::Future;
 use rand::prelude::SliceRandom;
 use serde::{Deserialize, Serialize};
-use tracing::{info_span, warn, Instrument};
+use tokio::sync::OwnedMutexGuard;
+use tracing::{debug, info_span, warn, Instrument};
+
+use crate::keystore::{load_keystore_file, KeystoreError};
 
 pub trait ChainKeyring {
     type Address: Hash + Eq + Clone + Display + Send + Sync;
@@ -33,9 +43,13 @@ pub struct ConcurrentKeyring<A: Hash + Eq, S> {
     key_to_address: Arc<HashMap<String, A>>,
 
     /// Ring buffer containing the addresses, used to index into `keys`. Items are popped out of this and then pushed to the back once they're finished being used.
+    ///
+    /// Every address is present once for each transaction it is allowed to have in flight, see [`Self::with_max_in_flight`].
     addresses_buffer: Arc<ArrayQueue<A>>,
 
     signers: Arc<HashMap<A, S>>,
+
+    nonces: SignerNonces<A>,
 }
 
 pub struct KeyringEntry<A, S> {
@@ -76,9 +90,43 @@ impl<A: Hash + Eq + Clone + Display, S: 'static> ConcurrentKeyring<A, S> {
             key_to_address: Arc::new(key_to_address),
             addresses_buffer: Arc::new(addresses_buffer),
             signers: Arc::new(signers),
+            nonces: SignerNonces::default(),
+        }
+    }
+
+    /// Allow every key in this keyring to be used by up to `max_in_flight` submissions concurrently.
+    ///
+    /// Keys are still handed out round-robin, such that load is spread evenly across all keys
+    /// before any single key is used concurrently.
+    pub fn with_max_in_flight(self, max_in_flight: NonZeroUsize) -> Self {
+        let mut addresses = vec![];
+
+        while let Some(address) = self.addresses_buffer.pop() {
+            addresses.push(address);
+        }
+
+        let addresses_buffer = ArrayQueue::new(addresses.len() * max_in_flight.get());
+
+        for _ in 0..max_in_flight.get() {
+            for address in &addresses {
+                addresses_buffer
+                    .push(address.clone())
+                    .ok()
+                    .expect("buffer is created with the expected length; qed;");
+            }
+        }
+
+        Self {
+            addresses_buffer: Arc::new(addresses_buffer),
+            ..self
         }
     }
 
+    /// The locally tracked nonces of the signers in this keyring.
+    pub fn nonces(&self) -> &SignerNonces<A> {
+        &self.nonces
+    }
+
     pub fn keys(&self) -> impl Iterator<Item = (&str, &A)> {
         self.key_to_address.iter().map(|(a, b)| (a.as_str(), b))
     }
@@ -116,30 +164,143 @@ impl<A: Hash + Eq + Clone + Display, S: 'static> ConcurrentKeyring<A, S> {
     }
 }
 
+/// Locally tracked nonces (account sequences on cosmos chains) for the signers in a
+/// [`ConcurrentKeyring`].
+///
+/// Nodes often lag behind their own mempool when queried for the nonce of an account, which causes
+/// back-to-back submissions from the same signer to reuse a nonce. The next nonce is remembered
+/// after every successful submission and preferred over the queried value if it is ahead. After a
+/// nonce mismatch error, the local value is resynced to either the nonce the chain expects or
+/// dropped entirely, such that the next submission falls back to the queried value.
+///
+/// Since a signer can have multiple transactions in flight (see
+/// [`ConcurrentKeyring::with_max_in_flight`]), the nonce of a signer must be locked (via
+/// [`Self::lock`]) from picking a nonce until the transaction has been accepted into the mempool.
+/// This ensures nonces are assigned and broadcast in order, while waiting for inclusion can still
+/// happen concurrently.
+#[derive(Debug, Clone)]
+pub struct SignerNonces<A: Hash + Eq> {
+    nonces: Arc<Mutex<HashMap<A, Arc<tokio::sync::Mutex<Option<u64>>>>>>,
+}
+
+impl<A: Hash + Eq> Default for SignerNonces<A> {
+    fn default() -> Self {
+        Self {
+            nonces: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+}
+
+impl<A: Hash + Eq + Clone + Display> SignerNonces<A> {
+    pub async fn lock(&self, address: &A) -> SignerNonce<A> {
+        let nonce = self
+            .nonces
+            .lock()
+            .expect("mutex is poisoned")
+            .entry(address.clone())
+            .or_default()
+            .clone();
+
+        SignerNonce {
+            address: address.clone(),
+            local: nonce.lock_owned().await,
+        }
+    }
+}
+
+/// The locked nonce of a single signer, see [`SignerNonces`].
+pub struct SignerNonce<A> {
+    address: A,
+    local: OwnedMutexGuard<Option<u64>>,
+}
+
+impl<A: Display> SignerNonce<A> {
+    /// The nonce to use for the next submission, given the nonce as queried from the chain.
+    pub fn next(&self, queried: u64) -> u64 {
+        match *self.local {
+            Some(local) if local > queried => {
+                debug!(address = %self.address, %local, %queried, "using locally tracked nonce");
+                local
+            }
+            _ => queried,
+        }
+    }
+
+    /// Record that a transaction with `nonce` was successfully submitted.
+    pub fn submitted(&mut self, nonce: u64) {
+        *self.local = Some(nonce + 1);
+    }
+
+    /// Resync the nonce after a nonce mismatch. If the nonce the chain expects is known, it will be
+    /// used for the next submission; otherwise the next submission will use the nonce as queried
+    /// from the chain.
+    pub fn resync(&mut self, expected: Option<u64>) {
+        warn!(address = %self.address, ?expected, "resyncing nonce");
+
+        *self.local = expected;
+    }
+}
+
 #[derive(Default)] // NOTE: Default impl is temporary until the EthereumSignersConfig stuff gets removed/ refactored
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
 pub struct KeyringConfig {
     pub name: String,
     pub keys: Vec<KeyringConfigEntry>,
+    /// The maximum amount of transactions each key can have in flight at once. Defaults to 1.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_in_flight: Option<NonZeroUsize>,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum KeyringConfigError {
+    #[error("unable to load key {name}")]
+    Keystore {
+        name: String,
+        #[source]
+        source: KeystoreError,
+    },
+    #[error(
+        "the private key of {name} cannot be loaded, {kind} keyring entries are not supported \
+        by this chain"
+    )]
+    Unsupported { name: String, kind: &'static str },
 }
 
 impl KeyringConfigEntry {
-    pub fn value(&self) -> Vec<u8> {
+    /// The private key of this entry. Keys stored in cloud KMS can only be loaded asynchronously
+    /// (see [`AnySigner::from_config`](crate::signer::AnySigner::from_config)), and the keys of
+    /// remote entries are never available to the relayer, so both are unsupported here.
+    pub fn value(&self) -> Result<Vec<u8>, KeyringConfigError> {
         match &self {
-            KeyringConfigEntry::File { path: _ } => {
-                panic!("file keyring is currently unimplemented")
-            }
-            KeyringConfigEntry::Raw { name: _, key } => key.clone(),
+            KeyringConfigEntry::File {
+                name,
+                path,
+                passphrase_env,
+            } => load_keystore_file(path, passphrase_env).map_err(|source| {
+                KeyringConfigError::Keystore {
+                    name: name.clone(),
+                    source,
+                }
+            }),
+            KeyringConfigEntry::Raw { name: _, key } => Ok(key.clone()),
+            KeyringConfigEntry::GcpKms { name, .. } => Err(KeyringConfigError::Unsupported {
+                name: name.clone(),
+                kind: "gcp_kms",
+            }),
+            KeyringConfigEntry::Remote { name, .. } => Err(KeyringConfigError::Unsupported {
+                name: name.clone(),
+                kind: "remote",
+            }),
         }
     }
 
     pub fn name(&self) -> String {
         match &self {
-            KeyringConfigEntry::File { path: _ } => {
-                panic!("file keyring is currently unimplemented")
-            }
+            KeyringConfigEntry::File { name, .. } => name.clone(),
             KeyringConfigEntry::Raw { name, key: _ } => name.clone(),
+            KeyringConfigEntry::GcpKms { name, .. } => name.clone(),
+            KeyringConfigEntry::Remote { name, .. } => name.clone(),
         }
     }
 }
@@ -147,12 +308,39 @@ impl KeyringConfigEntry {
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case", tag = "type")]
 pub enum KeyringConfigEntry {
+    /// A key stored in an encrypted keystore file, see [`Keystore`](crate::keystore::Keystore).
     File {
+        name: String,
         path: PathBuf,
+        /// The environment variable containing the passphrase of the keystore.
+        #[serde(default = "default_passphrase_env")]
+        passphrase_env: String,
     },
     Raw {
         name: String,
         #[serde(with = "::serde_utils::hex_string")]
         key: Vec<u8>,
     },
+    /// A key stored in a file, encrypted with a Google Cloud KMS key.
+    ///
+    /// `crypto_key` is the resource name of the KMS key, i.e.
+    /// `projects/<project>/locations/<location>/keyRings/<key_ring>/cryptoKeys/<key>`.
+    GcpKms {
+        name: String,
+        path: PathBuf,
+        crypto_key: String,
+    },
+    /// A key held by a remote signing service, see [`RemoteSigner`](crate::signer::RemoteSigner).
+    Remote {
+        name: String,
+        url: String,
+        key_id: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        auth_token: Option<String>,
+    },
+}
+
+#[must_use]
+pub fn default_passphrase_env() -> String {
+    "VOYAGER_KEYSTORE_PASSPHRASE".to_owned()
 }
@@ -0,0 +1,245 @@
+//! Loading of private keys from encrypted keystore files and cloud KMS.
+//!
+//! A keystore file is a JSON encoded [`Keystore`], containing a private key encrypted with
+//! AES-256-GCM under a key derived from a passphrase with Argon2id. Keystore files can be created
+//! with `voyager keystore encrypt`. The passphrase is read from an environment variable when the
+//! keyring is built, such that it never has to be written to the config.
+//!
+//! Alternatively, keys can be stored encrypted with a Google Cloud KMS key, in which case they are
+//! decrypted through the KMS API at startup, authenticated as the service account of the instance
+//! (via the metadata server).
+//!
+//! Keys are rotated without restarting voyager by pointing the keyring entries in the config at the
+//! new keystore files; the config is hot reloaded, and the plugins whose config changed are
+//! restarted with their new keys.
+
+use std::path::{Path, PathBuf};
+
+use aes_gcm::{aead::Aead, Aes256Gcm, KeyInit, Nonce};
+use argon2::{Algorithm, Argon2, Params, Version};
+use base64::{prelude::BASE64_STANDARD, Engine};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+
+pub const KEYSTORE_VERSION: u8 = 1;
+
+const NONCE_LEN: usize = 12;
+const SALT_LEN: usize = 16;
+
+const GCP_METADATA_TOKEN_URL: &str =
+    "http://metadata.google.internal/computeMetadata/v1/instance/service-accounts/default/token";
+const GCP_KMS_URL: &str = "https://cloudkms.googleapis.com/v1";
+
+#[derive(Debug, thiserror::Error)]
+pub enum KeystoreError {
+    #[error("error reading keystore file `{}`", .0.display())]
+    Read(PathBuf, #[source] std::io::Error),
+    #[error("error writing keystore file `{}`", .0.display())]
+    Write(PathBuf, #[source] std::io::Error),
+    #[error("invalid keystore file")]
+    InvalidKeystore(#[source] serde_json::Error),
+    #[error("unsupported keystore version {0}, expected {KEYSTORE_VERSION}")]
+    UnsupportedVersion(u8),
+    #[error("the passphrase environment variable `{0}` is not set")]
+    MissingPassphrase(String),
+    #[error("invalid key derivation parameters: {0}")]
+    Kdf(argon2::Error),
+    #[error("unable to decrypt keystore, the passphrase is incorrect or the file is corrupted")]
+    Decrypt,
+    #[error("error requesting the gcp kms api")]
+    Kms(#[source] reqwest::Error),
+    #[error("invalid response from the gcp kms api")]
+    InvalidKmsResponse(#[source] serde_json::Error),
+    #[error("invalid base64 returned by the gcp kms api")]
+    InvalidKmsPlaintext(#[source] base64::DecodeError),
+}
+
+/// An encrypted private key.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Keystore {
+    pub version: u8,
+    pub kdf: KdfParams,
+    #[serde(with = "::serde_utils::hex_string")]
+    pub nonce: Vec<u8>,
+    #[serde(with = "::serde_utils::hex_string")]
+    pub ciphertext: Vec<u8>,
+}
+
+/// Argon2id parameters.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct KdfParams {
+    pub memory_cost_kib: u32,
+    pub time_cost: u32,
+    pub parallelism: u32,
+    #[serde(with = "::serde_utils::hex_string")]
+    pub salt: Vec<u8>,
+}
+
+impl KdfParams {
+    fn derive_key(&self, passphrase: &[u8]) -> Result<[u8; 32], KeystoreError> {
+        let params = Params::new(
+            self.memory_cost_kib,
+            self.time_cost,
+            self.parallelism,
+            Some(32),
+        )
+        .map_err(KeystoreError::Kdf)?;
+
+        let mut key = [0; 32];
+
+        Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+            .hash_password_into(passphrase, &self.salt, &mut key)
+            .map_err(KeystoreError::Kdf)?;
+
+        Ok(key)
+    }
+}
+
+impl Keystore {
+    /// Encrypt `key` with `passphrase`, using the default Argon2id parameters.
+    pub fn encrypt(key: &[u8], passphrase: &[u8]) -> Result<Self, KeystoreError> {
+        let mut salt = vec![0; SALT_LEN];
+        rand::thread_rng().fill_bytes(&mut salt);
+
+        let mut nonce = vec![0; NONCE_LEN];
+        rand::thread_rng().fill_bytes(&mut nonce);
+
+        let kdf = KdfParams {
+            memory_cost_kib: Params::DEFAULT_M_COST,
+            time_cost: Params::DEFAULT_T_COST,
+            parallelism: Params::DEFAULT_P_COST,
+            salt,
+        };
+
+        let ciphertext = Aes256Gcm::new(&kdf.derive_key(passphrase)?.into())
+            .encrypt(Nonce::from_slice(&nonce), key)
+            .map_err(|_| KeystoreError::Decrypt)?;
+
+        Ok(Self {
+            version: KEYSTORE_VERSION,
+            kdf,
+            nonce,
+            ciphertext,
+        })
+    }
+
+    pub fn decrypt(&self, passphrase: &[u8]) -> Result<Vec<u8>, KeystoreError> {
+        if self.version != KEYSTORE_VERSION {
+            return Err(KeystoreError::UnsupportedVersion(self.version));
+        }
+
+        if self.nonce.len() != NONCE_LEN {
+            return Err(KeystoreError::Decrypt);
+        }
+
+        Aes256Gcm::new(&self.kdf.derive_key(passphrase)?.into())
+            .decrypt(Nonce::from_slice(&self.nonce), &*self.ciphertext)
+            .map_err(|_| KeystoreError::Decrypt)
+    }
+
+    pub fn read(path: &Path) -> Result<Self, KeystoreError> {
+        let bz = std::fs::read(path).map_err(|err| KeystoreError::Read(path.to_owned(), err))?;
+
+        serde_json::from_slice(&bz).map_err(KeystoreError::InvalidKeystore)
+    }
+
+    pub fn write(&self, path: &Path) -> Result<(), KeystoreError> {
+        std::fs::write(
+            path,
+            serde_json::to_vec_pretty(self).expect("serialization is infallible; qed;"),
+        )
+        .map_err(|err| KeystoreError::Write(path.to_owned(), err))
+    }
+}
+
+/// Read the keystore file at `path` and decrypt it with the passphrase in the environment variable
+/// `passphrase_env`.
+pub fn load_keystore_file(path: &Path, passphrase_env: &str) -> Result<Vec<u8>, KeystoreError> {
+    let passphrase = std::env::var(passphrase_env)
+        .map_err(|_| KeystoreError::MissingPassphrase(passphrase_env.to_owned()))?;
+
+    Keystore::read(path)?.decrypt(passphrase.as_bytes())
+}
+
+#[derive(Debug, Deserialize)]
+struct GcpAccessToken {
+    access_token: String,
+}
+
+#[derive(Debug, Serialize)]
+struct GcpKmsDecryptRequest {
+    ciphertext: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct GcpKmsDecryptResponse {
+    plaintext: String,
+}
+
+/// Decrypt the ciphertext in the file at `path` with the Google Cloud KMS key `crypto_key` (i.e.
+/// `projects/<project>/locations/<location>/keyRings/<key ring>/cryptoKeys/<key>`).
+pub async fn load_gcp_kms_file(path: &Path, crypto_key: &str) -> Result<Vec<u8>, KeystoreError> {
+    let ciphertext =
+        std::fs::read(path).map_err(|err| KeystoreError::Read(path.to_owned(), err))?;
+
+    let client = reqwest::Client::new();
+
+    let token = client
+        .get(GCP_METADATA_TOKEN_URL)
+        .header("Metadata-Flavor", "Google")
+        .send()
+        .await
+        .and_then(reqwest::Response::error_for_status)
+        .map_err(KeystoreError::Kms)?
+        .bytes()
+        .await
+        .map_err(KeystoreError::Kms)?;
+
+    let token = serde_json::from_slice::<GcpAccessToken>(&token)
+        .map_err(KeystoreError::InvalidKmsResponse)?;
+
+    let response = client
+        .post(format!("{GCP_KMS_URL}/{crypto_key}:decrypt"))
+        .bearer_auth(token.access_token)
+        .header(reqwest::header::CONTENT_TYPE, "application/json")
+        .body(
+            serde_json::to_vec(&GcpKmsDecryptRequest {
+                ciphertext: BASE64_STANDARD.encode(ciphertext),
+            })
+            .expect("serialization is infallible; qed;"),
+        )
+        .send()
+        .await
+        .and_then(reqwest::Response::error_for_status)
+        .map_err(KeystoreError::Kms)?
+        .bytes()
+        .await
+        .map_err(KeystoreError::Kms)?;
+
+    let response = serde_json::from_slice::<GcpKmsDecryptResponse>(&response)
+        .map_err(KeystoreError::InvalidKmsResponse)?;
+
+    BASE64_STANDARD
+        .decode(response.plaintext)
+        .map_err(KeystoreError::InvalidKmsPlaintext)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encrypt_decrypt() {
+        let key = [0x42; 32];
+
+        let keystore = Keystore::encrypt(&key, b"passphrase").unwrap();
+
+        assert_eq!(keystore.decrypt(b"passphrase").unwrap(), key);
+        assert!(matches!(
+            keystore.decrypt(b"wrong passphrase"),
+            Err(KeystoreError::Decrypt)
+        ));
+    }
+}
@@ -13,4 +13,12 @@ pub mod private_key;
 
 pub mod keyring;
 
+pub mod keystore;
+
+pub mod signer;
+
+pub mod denom_trace;
+
+pub mod evm_proof;
+
 pub type BoxDynError = Box<dyn core::error::Error + Send + Sync + 'static>;
@@ -23,7 +23,7 @@ use crate::{
         balance_of_signers, Ethereum, EthereumConsensusChain, EthereumIbcChain, EthereumKeyring,
         EthereumSignerMiddleware, EthereumSignersConfig, ReadWrite, Readonly,
     },
-    keyring::{ChainKeyring, ConcurrentKeyring, KeyringConfig, SignerBalance},
+    keyring::{ChainKeyring, ConcurrentKeyring, KeyringConfig, KeyringConfigError, SignerBalance},
     BoxDynError,
 };
 
@@ -157,6 +157,8 @@ pub enum ScrollInitError {
     Provider(#[from] ProviderError),
     #[error("jsonrpc error")]
     JsonRpc(#[from] scroll_rpc::JsonRpcError),
+    #[error("invalid keyring")]
+    Keyring(#[from] KeyringConfigError),
 }
 
 impl Scroll {
@@ -172,7 +174,7 @@ impl Scroll {
                 config.ibc_handler_address,
                 chain_id.as_u64(),
                 provider.clone(),
-            ),
+            )?,
             ibc_handler_address: config.ibc_handler_address,
             multicall_address: config.multicall_address,
             provider: Arc::new(provider),
@@ -0,0 +1,358 @@
+//! Signing backends for the keys in a [`ConcurrentKeyring`](crate::keyring::ConcurrentKeyring).
+//!
+//! Transaction submission only requires a secp256k1 signature over a 32 byte prehash (the sha256
+//! of the sign doc on cosmos sdk chains, the signing hash of the transaction on ethereum), so the
+//! private key does not need to be held by the relayer process. [`LocalSigner`] signs with a key
+//! held in memory, and [`RemoteSigner`] delegates signing to an external signing service (i.e. a
+//! threshold signer, or a service backed by an HSM or hardware wallet) over HTTP.
+
+use std::time::Duration;
+
+use bip32::{
+    secp256k1::ecdsa::{self, RecoveryId, Signature, SigningKey, VerifyingKey},
+    PublicKey,
+};
+use futures::Future;
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+use unionlabs::{hash::H256, ErrorReporter};
+
+use crate::{
+    keyring::KeyringConfigEntry,
+    keystore::{load_gcp_kms_file, load_keystore_file, KeystoreError},
+};
+
+#[derive(Debug, thiserror::Error)]
+pub enum SignerError {
+    #[error("invalid key")]
+    InvalidKey(#[source] ecdsa::Error),
+    #[error("error loading key")]
+    Keystore(#[source] KeystoreError),
+    #[error("signing failed")]
+    Sign(#[source] ecdsa::Error),
+    #[error("error requesting remote signer")]
+    Request(#[source] reqwest::Error),
+    #[error("invalid response from remote signer")]
+    InvalidResponse(#[source] serde_json::Error),
+    #[error("remote signer returned a signature of {0} bytes, expected 65")]
+    InvalidSignatureLength(usize),
+    #[error("invalid recovery id {0} returned by remote signer")]
+    InvalidRecoveryId(u8),
+    #[error("invalid signature returned by remote signer")]
+    InvalidSignature(#[source] ecdsa::Error),
+    #[error("remote signer returned a signature that was not signed by the expected key")]
+    SignatureMismatch,
+}
+
+/// A secp256k1 signature with `s` normalized to the lower half of the curve order, along with the
+/// recovery id required to recover the public key from it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RecoverableSignature {
+    pub signature: Signature,
+    pub recovery_id: RecoveryId,
+}
+
+pub trait Signer: Send + Sync {
+    fn verifying_key(&self) -> &VerifyingKey;
+
+    /// Sign `prehash`, which must already be hashed as required by the chain.
+    fn sign_prehash(
+        &self,
+        prehash: H256,
+    ) -> impl Future<Output = Result<RecoverableSignature, SignerError>> + Send;
+
+    /// The compressed SEC1 encoding of the public key.
+    fn public_key(&self) -> [u8; 33] {
+        self.verifying_key().to_bytes()
+    }
+}
+
+/// A signer backed by a private key held in memory.
+#[derive(Debug, Clone)]
+pub struct LocalSigner {
+    signing_key: SigningKey,
+}
+
+impl LocalSigner {
+    pub fn new(signing_key: SigningKey) -> Self {
+        Self { signing_key }
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, SignerError> {
+        SigningKey::from_slice(bytes)
+            .map(Self::new)
+            .map_err(SignerError::InvalidKey)
+    }
+}
+
+impl Signer for LocalSigner {
+    fn verifying_key(&self) -> &VerifyingKey {
+        self.signing_key.verifying_key()
+    }
+
+    async fn sign_prehash(&self, prehash: H256) -> Result<RecoverableSignature, SignerError> {
+        let (signature, recovery_id) = self
+            .signing_key
+            .sign_prehash_recoverable(prehash.get())
+            .map_err(SignerError::Sign)?;
+
+        Ok(RecoverableSignature {
+            signature,
+            recovery_id,
+        })
+    }
+}
+
+/// How long to wait for a connection to the remote signer to be established.
+const REMOTE_SIGNER_CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How long to wait for a request to the remote signer to complete, including connecting.
+/// Signing services backed by hardware may take a while to respond, but a hung service must not
+/// block transaction submission indefinitely.
+const REMOTE_SIGNER_REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// A signer that delegates signing to a remote signing service.
+///
+/// The service must expose the following endpoints, where `key_id` identifies the key within the
+/// service:
+///
+/// - `GET {url}/keys/{key_id}`, returning `{"public_key":"0x..."}` with the SEC1 encoded public
+///   key of the signer.
+/// - `POST {url}/keys/{key_id}/sign` with `{"prehash":"0x..."}`, returning `{"signature":"0x..."}`
+///   with the 65 byte `r || s || v` signature over the prehash, where `v` is the recovery id.
+///
+/// If an auth token is configured, it is sent as a bearer token with every request. Signatures
+/// returned by the service are verified against the public key of the signer before being used.
+#[derive(Debug, Clone)]
+pub struct RemoteSigner {
+    client: reqwest::Client,
+    url: String,
+    key_id: String,
+    auth_token: Option<String>,
+    verifying_key: VerifyingKey,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct PublicKeyResponse {
+    #[serde(with = "::serde_utils::hex_string")]
+    public_key: Vec<u8>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct SignRequest {
+    prehash: H256,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct SignResponse {
+    #[serde(with = "::serde_utils::hex_string")]
+    signature: Vec<u8>,
+}
+
+impl RemoteSigner {
+    /// Connect to the signing service at `url` and fetch the public key of `key_id`.
+    pub async fn connect(
+        url: String,
+        key_id: String,
+        auth_token: Option<String>,
+    ) -> Result<Self, SignerError> {
+        let client = reqwest::Client::builder()
+            .connect_timeout(REMOTE_SIGNER_CONNECT_TIMEOUT)
+            .timeout(REMOTE_SIGNER_REQUEST_TIMEOUT)
+            .build()
+            .map_err(SignerError::Request)?;
+
+        let url = url.trim_end_matches('/').to_owned();
+
+        let mut request = client.get(format!("{url}/keys/{key_id}"));
+        if let Some(auth_token) = &auth_token {
+            request = request.bearer_auth(auth_token);
+        }
+
+        let response = request
+            .send()
+            .await
+            .and_then(reqwest::Response::error_for_status)
+            .map_err(SignerError::Request)?
+            .bytes()
+            .await
+            .map_err(SignerError::Request)?;
+
+        let response = serde_json::from_slice::<PublicKeyResponse>(&response)
+            .map_err(SignerError::InvalidResponse)?;
+
+        let verifying_key =
+            VerifyingKey::from_sec1_bytes(&response.public_key).map_err(SignerError::InvalidKey)?;
+
+        Ok(Self {
+            client,
+            url,
+            key_id,
+            auth_token,
+            verifying_key,
+        })
+    }
+}
+
+impl Signer for RemoteSigner {
+    fn verifying_key(&self) -> &VerifyingKey {
+        &self.verifying_key
+    }
+
+    async fn sign_prehash(&self, prehash: H256) -> Result<RecoverableSignature, SignerError> {
+        let mut request = self
+            .client
+            .post(format!("{}/keys/{}/sign", self.url, self.key_id))
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .body(
+                serde_json::to_vec(&SignRequest { prehash })
+                    .expect("serialization is infallible; qed;"),
+            );
+        if let Some(auth_token) = &self.auth_token {
+            request = request.bearer_auth(auth_token);
+        }
+
+        let response = request
+            .send()
+            .await
+            .and_then(reqwest::Response::error_for_status)
+            .map_err(SignerError::Request)?
+            .bytes()
+            .await
+            .map_err(SignerError::Request)?;
+
+        let response = serde_json::from_slice::<SignResponse>(&response)
+            .map_err(SignerError::InvalidResponse)?;
+
+        let Ok::<[u8; 65], _>(bytes) = response.signature.as_slice().try_into() else {
+            return Err(SignerError::InvalidSignatureLength(
+                response.signature.len(),
+            ));
+        };
+
+        let signature =
+            Signature::from_slice(&bytes[..64]).map_err(SignerError::InvalidSignature)?;
+
+        // some signers return the ethereum style `v` (27 or 28)
+        let mut recovery_id = RecoveryId::from_byte(bytes[64] % 27)
+            .ok_or(SignerError::InvalidRecoveryId(bytes[64]))?;
+
+        // normalizing s negates it, which flips the parity of the recovered y coordinate
+        let signature = match signature.normalize_s() {
+            Some(normalized) => {
+                recovery_id = RecoveryId::new(!recovery_id.is_y_odd(), recovery_id.is_x_reduced());
+                normalized
+            }
+            None => signature,
+        };
+
+        match VerifyingKey::recover_from_prehash(prehash.get(), &signature, recovery_id) {
+            Ok(recovered) if recovered == self.verifying_key => Ok(RecoverableSignature {
+                signature,
+                recovery_id,
+            }),
+            Ok(_) => Err(SignerError::SignatureMismatch),
+            Err(err) => {
+                warn!(
+                    key_id = %self.key_id,
+                    error = %ErrorReporter(&err),
+                    "unable to recover public key from remote signature"
+                );
+                Err(SignerError::SignatureMismatch)
+            }
+        }
+    }
+}
+
+/// Any of the supported signing backends, as configured by a [`KeyringConfigEntry`].
+#[derive(Debug, Clone)]
+pub enum AnySigner {
+    Local(LocalSigner),
+    Remote(RemoteSigner),
+}
+
+impl AnySigner {
+    pub async fn from_config(entry: &KeyringConfigEntry) -> Result<Self, SignerError> {
+        match entry {
+            KeyringConfigEntry::Raw { name: _, key } => {
+                LocalSigner::from_bytes(key).map(Self::Local)
+            }
+            KeyringConfigEntry::File {
+                name: _,
+                path,
+                passphrase_env,
+            } => load_keystore_file(path, passphrase_env)
+                .map_err(SignerError::Keystore)
+                .and_then(|key| LocalSigner::from_bytes(&key))
+                .map(Self::Local),
+            KeyringConfigEntry::GcpKms {
+                name: _,
+                path,
+                crypto_key,
+            } => load_gcp_kms_file(path, crypto_key)
+                .await
+                .map_err(SignerError::Keystore)
+                .and_then(|key| LocalSigner::from_bytes(&key))
+                .map(Self::Local),
+            KeyringConfigEntry::Remote {
+                name: _,
+                url,
+                key_id,
+                auth_token,
+            } => RemoteSigner::connect(url.clone(), key_id.clone(), auth_token.clone())
+                .await
+                .map(Self::Remote),
+        }
+    }
+}
+
+impl Signer for AnySigner {
+    fn verifying_key(&self) -> &VerifyingKey {
+        match self {
+            AnySigner::Local(signer) => signer.verifying_key(),
+            AnySigner::Remote(signer) => signer.verifying_key(),
+        }
+    }
+
+    async fn sign_prehash(&self, prehash: H256) -> Result<RecoverableSignature, SignerError> {
+        match self {
+            AnySigner::Local(signer) => signer.sign_prehash(prehash).await,
+            AnySigner::Remote(signer) => signer.sign_prehash(prehash).await,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::FutureExt;
+    use hex_literal::hex;
+    use sha2::Digest;
+
+    use super::*;
+
+    #[test]
+    fn local_signature_recovers_to_signer() {
+        let signer = LocalSigner::from_bytes(&hex!(
+            // cspell:disable-next-line
+            "4e9444a6efd6d42725a250b650a781da2737ea308c839eaccb0f7f3dbd2fea77"
+        ))
+        .unwrap();
+
+        let prehash = H256::new(sha2::Sha256::digest(b"sign doc").into());
+
+        let RecoverableSignature {
+            signature,
+            recovery_id,
+        } = signer
+            .sign_prehash(prehash)
+            .now_or_never()
+            .expect("local signing is synchronous")
+            .unwrap();
+
+        assert!(signature.normalize_s().is_none());
+        assert_eq!(
+            &VerifyingKey::recover_from_prehash(prehash.get(), &signature, recovery_id).unwrap(),
+            signer.verifying_key()
+        );
+    }
+}
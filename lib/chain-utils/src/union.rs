@@ -1,17 +1,16 @@
-use std::{fmt::Debug, num::ParseIntError, sync::Arc};
+use std::{fmt::Debug, sync::Arc};
 
-use bip32::secp256k1::ecdsa;
-use dashmap::DashMap;
 use serde::{Deserialize, Serialize};
-use tendermint_rpc::{Client, WebSocketClient, WebSocketClientUrl};
+use tendermint_rpc::{WebSocketClient, WebSocketClientUrl};
 use unionlabs::{
     hash::H256, ibc::core::client::height::Height, id::ClientId, signer::CosmosSigner,
     WasmClientType,
 };
 
 use crate::{
+    cosmos::{self, Cosmos, CosmosInitError},
     cosmos_sdk::{CosmosKeyring, CosmosSdkChain, CosmosSdkChainRpcs, GasConfig},
-    keyring::{ChainKeyring, ConcurrentKeyring, KeyringConfig, KeyringEntry, SignerBalance},
+    keyring::{ChainKeyring, ConcurrentKeyring, KeyringConfig, SignerBalance},
 };
 
 #[derive(Debug, Clone)]
@@ -55,73 +54,39 @@ impl ChainKeyring for Union {
 }
 
 #[derive(Debug, thiserror::Error)]
-pub enum UnionInitError {
-    #[error("tendermint rpc error")]
-    Tendermint(#[from] tendermint_rpc::Error),
-    #[error(
-        "unable to parse chain id: expected format `<chain>-<revision-number>`, found `{found}`"
-    )]
-    ChainIdParse {
-        found: String,
-        #[source]
-        source: Option<ParseIntError>,
-    },
-}
+#[error(transparent)]
+pub struct UnionInitError(#[from] CosmosInitError);
 
 impl Union {
     pub async fn new(config: Config) -> Result<Self, UnionInitError> {
-        let (tm_client, driver) = WebSocketClient::builder(config.ws_url)
-            .compat_mode(tendermint_rpc::client::CompatMode::V0_37)
-            .build()
-            .await?;
-
-        tokio::spawn(async move { driver.run().await });
-
-        let chain_id = tm_client.status().await?.node_info.network.to_string();
-
-        let chain_revision = chain_id
-            .split('-')
-            .last()
-            .ok_or_else(|| UnionInitError::ChainIdParse {
-                found: chain_id.clone(),
-                source: None,
-            })?
-            .parse()
-            .map_err(|err| UnionInitError::ChainIdParse {
-                found: chain_id.clone(),
-                source: Some(err),
-            })?;
+        // union is a regular cosmos sdk chain, with the addition of the prover endpoints
+        let Cosmos {
+            chain_id,
+            keyring,
+            tm_client,
+            chain_revision,
+            grpc_url,
+            gas_config,
+            bech32_prefix: _,
+            checksum_cache,
+        } = Cosmos::new(cosmos::Config {
+            keyring: config.keyring,
+            ws_url: config.ws_url,
+            grpc_url: config.grpc_url,
+            gas_config: config.gas_config,
+            bech32_prefix: Some("union".to_owned()),
+        })
+        .await?;
 
         Ok(Self {
-            // TODO: Deduplicate between this and cosmos.rs
-            keyring: CosmosKeyring::new(
-                config.keyring.name,
-                config
-                    .keyring
-                    .keys
-                    .into_iter()
-                    // TODO: Make this configurable or fetch it from the chain
-                    .map(|entry| {
-                        let signer = CosmosSigner::new(
-                            ecdsa::SigningKey::from_bytes(entry.value().as_slice().into())
-                                .expect("invalid private key"),
-                            "union".to_owned(),
-                        );
-
-                        KeyringEntry {
-                            name: entry.name(),
-                            address: signer.to_string(),
-                            signer,
-                        }
-                    }),
-            ),
-            tm_client,
             chain_id,
+            keyring,
+            tm_client,
             chain_revision,
             prover_endpoints: config.prover_endpoints,
-            grpc_url: config.grpc_url,
-            checksum_cache: Arc::new(DashMap::default()),
-            gas_config: config.gas_config,
+            grpc_url,
+            checksum_cache,
+            gas_config,
         })
     }
 
@@ -0,0 +1,115 @@
+//! Routing of historical queries to an archival node.
+//!
+//! Full nodes usually prune old state, so queries at old heights (such as proofs for packets that
+//! were sent a while ago) fail against them. If an archival endpoint is configured, queries at a
+//! height more than [`ArchivalConfig::threshold_blocks`] blocks behind the latest height are sent
+//! to the archival endpoint instead, while all other queries stay on the (faster) primary endpoint.
+
+use std::{
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use ::serde::{Deserialize, Serialize};
+use jsonrpsee::{core::client::ClientT, rpc_params};
+use tracing::debug;
+
+use crate::{failover::FailoverConfig, rpc_types::StatusResponse, ClientInner, JsonRpcError};
+
+/// How long the latest height of the primary endpoint is cached for.
+const LATEST_HEIGHT_CACHE_DURATION: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ArchivalConfig {
+    pub ws_url: String,
+    /// Fallback endpoints to use if `ws_url` is unavailable.
+    #[serde(default)]
+    pub failover: FailoverConfig,
+    /// Queries at heights more than this many blocks behind the latest height are sent to the
+    /// archival endpoint.
+    #[serde(default = "default_threshold_blocks")]
+    pub threshold_blocks: u64,
+}
+
+#[must_use]
+#[inline]
+pub const fn default_threshold_blocks() -> u64 {
+    // the default `pruning-keep-recent` of the cosmos-sdk is 362880, but many nodes prune much more
+    // aggressively
+    1000
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct Archival {
+    pub(crate) client: ClientInner,
+    threshold_blocks: u64,
+    /// The latest height of the primary endpoint, and when it was fetched.
+    latest_height: Arc<Mutex<Option<(u64, Instant)>>>,
+}
+
+impl Archival {
+    pub(crate) fn new(client: ClientInner, threshold_blocks: u64) -> Self {
+        Self {
+            client,
+            threshold_blocks,
+            latest_height: Arc::default(),
+        }
+    }
+
+    /// Whether a query at `height` should be sent to the archival endpoint, fetching the latest
+    /// height from `primary` if the cached value is stale.
+    pub(crate) async fn should_route(
+        &self,
+        primary: &ClientInner,
+        height: u64,
+    ) -> Result<bool, JsonRpcError> {
+        let cached = *self.latest_height.lock().expect("mutex is poisoned");
+
+        let latest_height = match cached {
+            Some((latest_height, fetched_at))
+                if fetched_at.elapsed() < LATEST_HEIGHT_CACHE_DURATION =>
+            {
+                latest_height
+            }
+            _ => {
+                let latest_height = primary
+                    .request::<StatusResponse, _>("status", rpc_params!())
+                    .await?
+                    .sync_info
+                    .latest_block_height;
+
+                *self.latest_height.lock().expect("mutex is poisoned") =
+                    Some((latest_height, Instant::now()));
+
+                latest_height
+            }
+        };
+
+        let archival = is_archival(latest_height, height, self.threshold_blocks);
+
+        if archival {
+            debug!(%height, %latest_height, "routing query to archival endpoint");
+        }
+
+        Ok(archival)
+    }
+}
+
+fn is_archival(latest_height: u64, height: u64, threshold_blocks: u64) -> bool {
+    latest_height.saturating_sub(height) > threshold_blocks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn routes_old_heights() {
+        assert!(!is_archival(2000, 1500, 1000));
+        assert!(!is_archival(2000, 1000, 1000));
+        assert!(is_archival(2000, 999, 1000));
+        // heights ahead of the cached latest height stay on the primary endpoint
+        assert!(!is_archival(2000, 2001, 1000));
+    }
+}
@@ -0,0 +1,344 @@
+//! Failover between multiple rpc endpoints of the same chain.
+//!
+//! Requests are sent to the currently active endpoint. If a request fails for any reason other
+//! than the node returning a JSON-RPC error, the endpoint is marked as unhealthy and the request is
+//! retried against the next endpoint. A background task periodically checks the health (and
+//! latency) of all endpoints, and picks the active endpoint according to the configured
+//! [`EndpointSelection`].
+
+use std::{
+    fmt,
+    future::Future,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
+        Arc, Weak,
+    },
+    time::{Duration, Instant},
+};
+
+use ::serde::{de::DeserializeOwned, Deserialize, Serialize};
+use jsonrpsee::{
+    core::{
+        async_trait,
+        client::{BatchResponse, ClientT},
+        params::BatchRequestBuilder,
+        traits::ToRpcParams,
+    },
+    rpc_params,
+};
+use serde_json::value::RawValue;
+use tracing::{debug, info, warn};
+
+use crate::{ClientInner, JsonRpcError};
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FailoverConfig {
+    /// Additional endpoints of the same chain, used if the primary endpoint is unavailable. These
+    /// are tried in the order they are specified.
+    #[serde(default)]
+    pub fallback_urls: Vec<String>,
+    #[serde(default)]
+    pub selection: EndpointSelection,
+    /// How often the health of the endpoints is checked.
+    #[serde(default = "default_health_check_interval_seconds")]
+    pub health_check_interval_seconds: u64,
+}
+
+impl Default for FailoverConfig {
+    fn default() -> Self {
+        Self {
+            fallback_urls: vec![],
+            selection: EndpointSelection::default(),
+            health_check_interval_seconds: default_health_check_interval_seconds(),
+        }
+    }
+}
+
+#[must_use]
+#[inline]
+pub const fn default_health_check_interval_seconds() -> u64 {
+    30
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EndpointSelection {
+    /// Use the first healthy endpoint, in the order they are configured (primary first).
+    #[default]
+    Priority,
+    /// Use the healthy endpoint with the lowest latency, as measured by the health checks.
+    Latency,
+}
+
+#[derive(Clone)]
+pub(crate) struct Failover {
+    inner: Arc<FailoverInner>,
+}
+
+impl fmt::Debug for Failover {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Failover")
+            .field(
+                "endpoints",
+                &self
+                    .inner
+                    .endpoints
+                    .iter()
+                    .map(|e| &e.url)
+                    .collect::<Vec<_>>(),
+            )
+            .field("active", &self.inner.active.load(Ordering::Relaxed))
+            .field("selection", &self.inner.selection)
+            .finish()
+    }
+}
+
+struct FailoverInner {
+    endpoints: Vec<Endpoint>,
+    active: AtomicUsize,
+    selection: EndpointSelection,
+}
+
+struct Endpoint {
+    url: String,
+    client: ClientInner,
+    healthy: AtomicBool,
+    /// The latency of the last successful health check, in microseconds.
+    latency: AtomicU64,
+}
+
+impl Failover {
+    pub(crate) async fn new(
+        urls: Vec<String>,
+        config: &FailoverConfig,
+    ) -> Result<Self, JsonRpcError> {
+        let mut endpoints = vec![];
+        let mut last_error = None;
+
+        for url in urls {
+            match ClientInner::connect(url.clone()).await {
+                Ok(client) => endpoints.push(Endpoint {
+                    url,
+                    client,
+                    healthy: AtomicBool::new(true),
+                    latency: AtomicU64::new(u64::MAX),
+                }),
+                Err(err) => {
+                    warn!(%url, %err, "unable to connect to endpoint, it will not be used");
+                    last_error = Some(err);
+                }
+            }
+        }
+
+        if endpoints.is_empty() {
+            return Err(last_error
+                .unwrap_or_else(|| JsonRpcError::Custom("no endpoints configured".to_owned())));
+        }
+
+        let inner = Arc::new(FailoverInner {
+            endpoints,
+            active: AtomicUsize::new(0),
+            selection: config.selection,
+        });
+
+        tokio::spawn(health_check_task(
+            Arc::downgrade(&inner),
+            Duration::from_secs(config.health_check_interval_seconds.max(1)),
+        ));
+
+        Ok(Self { inner })
+    }
+
+    /// Run `f` against each endpoint, starting with the active one, until it either succeeds or
+    /// fails with an error that is not caused by the endpoint being unavailable.
+    async fn try_each<'a, T, F, Fut>(&'a self, f: F) -> Result<T, JsonRpcError>
+    where
+        F: Fn(&'a ClientInner) -> Fut,
+        Fut: Future<Output = Result<T, JsonRpcError>>,
+    {
+        let endpoints = &self.inner.endpoints;
+        let active = self.inner.active.load(Ordering::Relaxed);
+
+        let mut last_error = None;
+
+        for i in 0..endpoints.len() {
+            let idx = (active + i) % endpoints.len();
+            let endpoint = &endpoints[idx];
+
+            match f(&endpoint.client).await {
+                Ok(t) => {
+                    if idx != active {
+                        info!(url = %endpoint.url, "failed over to endpoint");
+
+                        self.inner.active.store(idx, Ordering::Relaxed);
+                    }
+
+                    return Ok(t);
+                }
+                Err(err @ JsonRpcError::Call(_)) => return Err(err),
+                Err(err) => {
+                    warn!(url = %endpoint.url, %err, "request failed, marking endpoint as unhealthy");
+
+                    endpoint.healthy.store(false, Ordering::Relaxed);
+                    last_error = Some(err);
+                }
+            }
+        }
+
+        Err(last_error.expect("there is at least one endpoint; qed;"))
+    }
+}
+
+#[async_trait]
+impl ClientT for Failover {
+    async fn notification<Params>(&self, method: &str, params: Params) -> Result<(), JsonRpcError>
+    where
+        Params: ToRpcParams + Send,
+    {
+        let params = RawParams(params.to_rpc_params()?);
+
+        self.try_each(|client| client.notification(method, params.clone()))
+            .await
+    }
+
+    async fn request<R, Params>(&self, method: &str, params: Params) -> Result<R, JsonRpcError>
+    where
+        R: DeserializeOwned,
+        Params: ToRpcParams + Send,
+    {
+        let params = RawParams(params.to_rpc_params()?);
+
+        self.try_each(|client| client.request(method, params.clone()))
+            .await
+    }
+
+    async fn batch_request<'a, R>(
+        &self,
+        batch: BatchRequestBuilder<'a>,
+    ) -> Result<BatchResponse<'a, R>, JsonRpcError>
+    where
+        R: DeserializeOwned + fmt::Debug + 'a,
+    {
+        self.try_each(|client| client.batch_request(batch.clone()))
+            .await
+    }
+}
+
+/// Already serialized params, such that the same request can be sent to multiple endpoints.
+#[derive(Clone)]
+struct RawParams(Option<Box<RawValue>>);
+
+impl ToRpcParams for RawParams {
+    fn to_rpc_params(self) -> Result<Option<Box<RawValue>>, serde_json::Error> {
+        Ok(self.0)
+    }
+}
+
+async fn health_check_task(inner: Weak<FailoverInner>, interval: Duration) {
+    loop {
+        tokio::time::sleep(interval).await;
+
+        // the client has been dropped
+        let Some(inner) = inner.upgrade() else {
+            return;
+        };
+
+        for endpoint in &inner.endpoints {
+            let start = Instant::now();
+
+            match endpoint
+                .client
+                .request::<serde_json::Value, _>("health", rpc_params!())
+                .await
+            {
+                Ok(_) => {
+                    let latency = start.elapsed();
+
+                    debug!(url = %endpoint.url, ?latency, "endpoint is healthy");
+
+                    endpoint.healthy.store(true, Ordering::Relaxed);
+                    endpoint.latency.store(
+                        latency.as_micros().try_into().unwrap_or(u64::MAX),
+                        Ordering::Relaxed,
+                    );
+                }
+                Err(err) => {
+                    warn!(url = %endpoint.url, %err, "endpoint is unhealthy");
+
+                    endpoint.healthy.store(false, Ordering::Relaxed);
+                }
+            }
+        }
+
+        let current = inner.active.load(Ordering::Relaxed);
+
+        let selected = select(
+            inner.selection,
+            inner.endpoints.iter().map(|endpoint| {
+                endpoint
+                    .healthy
+                    .load(Ordering::Relaxed)
+                    .then(|| endpoint.latency.load(Ordering::Relaxed))
+            }),
+        )
+        .unwrap_or(current);
+
+        if selected != current {
+            info!(url = %inner.endpoints[selected].url, "switching active endpoint");
+
+            inner.active.store(selected, Ordering::Relaxed);
+        }
+    }
+}
+
+/// Select the endpoint to use, given the latency of each endpoint (or `None` if it is unhealthy).
+/// Returns `None` if no endpoint is healthy.
+fn select(
+    selection: EndpointSelection,
+    latencies: impl IntoIterator<Item = Option<u64>>,
+) -> Option<usize> {
+    let mut healthy = latencies
+        .into_iter()
+        .enumerate()
+        .filter_map(|(idx, latency)| latency.map(|latency| (idx, latency)));
+
+    match selection {
+        EndpointSelection::Priority => healthy.next().map(|(idx, _)| idx),
+        // min_by_key returns the first minimum, so ties are broken by priority
+        EndpointSelection::Latency => healthy
+            .min_by_key(|(_, latency)| *latency)
+            .map(|(idx, _)| idx),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn select_priority() {
+        assert_eq!(
+            select(EndpointSelection::Priority, [Some(50), Some(10)]),
+            Some(0)
+        );
+        assert_eq!(
+            select(EndpointSelection::Priority, [None, Some(10)]),
+            Some(1)
+        );
+        assert_eq!(select(EndpointSelection::Priority, [None, None]), None);
+    }
+
+    #[test]
+    fn select_latency() {
+        assert_eq!(
+            select(EndpointSelection::Latency, [Some(50), Some(10), Some(30)]),
+            Some(1)
+        );
+        assert_eq!(
+            select(EndpointSelection::Latency, [Some(10), None, Some(10)]),
+            Some(0)
+        );
+        assert_eq!(select(EndpointSelection::Latency, [None, None]), None);
+    }
+}
@@ -24,58 +24,88 @@ use unionlabs::{
     option_unwrap, result_unwrap,
 };
 
-use crate::rpc_types::{
-    AbciQueryResponse, AllValidatorsResponse, BlockResponse, BlockResultsResponse,
-    BlockchainResponse, BroadcastTxSyncResponse, CommitResponse, Order, StatusResponse, TxResponse,
-    TxSearchResponse, ValidatorsResponse,
+use crate::{
+    archival::Archival,
+    failover::Failover,
+    rpc_types::{
+        AbciQueryResponse, AllValidatorsResponse, BlockResponse, BlockResultsResponse,
+        BlockchainResponse, BroadcastTxSyncResponse, CommitResponse, Order, StatusResponse,
+        TxResponse, TxSearchResponse, ValidatorsResponse,
+    },
 };
 
 #[cfg(test)]
 mod tests;
 
+mod archival;
+mod failover;
+mod skipping;
+
 pub mod rpc_types;
 pub mod serde;
+pub use archival::ArchivalConfig;
 pub use cometbft_types as types;
+pub use failover::{EndpointSelection, FailoverConfig};
+pub use skipping::{has_trusted_power, TrustLevel};
 
 pub type JsonRpcError = jsonrpsee::core::client::Error;
 
 #[derive(Debug, Clone)]
 pub struct Client {
     inner: ClientInner,
+    archival: Option<Archival>,
 }
 
 impl Client {
     pub async fn new(url: impl AsRef<str>) -> Result<Self, JsonRpcError> {
-        let url = url.as_ref().to_owned();
+        Ok(Self {
+            inner: ClientInner::connect(url.as_ref().to_owned()).await?,
+            archival: None,
+        })
+    }
 
-        let inner = match url.split_once("://") {
-            Some(("ws" | "wss", _)) => {
-                let client = reconnecting_jsonrpc_ws_client::Client::new(move || {
-                    WsClientBuilder::default()
-                        .enable_ws_ping(PingConfig::new())
-                        .build(url.clone())
-                        .instrument(debug_span!("cometbft_rpc_client", %url))
-                });
+    /// Create a new client that fails over to the fallback urls in `failover` if `url` is
+    /// unavailable. If there are no fallback urls, this is equivalent to [`Self::new`].
+    ///
+    /// Fallback endpoints that can't be connected to are skipped; this only fails if none of the
+    /// endpoints can be connected to.
+    pub async fn new_with_failover(
+        url: impl AsRef<str>,
+        failover: &FailoverConfig,
+    ) -> Result<Self, JsonRpcError> {
+        Ok(Self {
+            inner: ClientInner::connect_with_failover(url.as_ref().to_owned(), failover).await?,
+            archival: None,
+        })
+    }
 
-                // TODO: Config
-                client
-                    .wait_until_connected(Duration::from_secs(5))
-                    .await
-                    .map_err(|e| JsonRpcError::Custom(e.to_string()))?;
+    /// Send queries at old heights to the archival endpoint configured in `archival`. See
+    /// [`ArchivalConfig`] for more information.
+    pub async fn with_archival(mut self, archival: &ArchivalConfig) -> Result<Self, JsonRpcError> {
+        self.archival = Some(Archival::new(
+            ClientInner::connect_with_failover(archival.ws_url.clone(), &archival.failover).await?,
+            archival.threshold_blocks,
+        ));
 
-                ClientInner::Ws(client)
-            }
-            Some(("http" | "https", _)) => {
-                ClientInner::Http(HttpClientBuilder::default().build(url)?)
-            }
-            _ => return Err(JsonRpcError::Custom(format!("invalid url {url}"))),
-        };
+        Ok(self)
+    }
 
-        Ok(Self { inner })
+    /// The client to use for a query at `height`. Queries at the latest height always use the
+    /// primary endpoint.
+    async fn client_at(&self, height: Option<u64>) -> Result<&ClientInner, JsonRpcError> {
+        match (&self.archival, height) {
+            (Some(archival), Some(height))
+                if archival.should_route(&self.inner, height).await? =>
+            {
+                Ok(&archival.client)
+            }
+            _ => Ok(&self.inner),
+        }
     }
 
     pub async fn commit(&self, height: Option<NonZeroU64>) -> Result<CommitResponse, JsonRpcError> {
-        self.inner
+        self.client_at(height.map(NonZeroU64::get))
+            .await?
             .request("commit", (height.map(|x| x.to_string()),))
             .await
     }
@@ -85,7 +115,8 @@ impl Client {
         height: Option<NonZeroU64>,
         pagination: Option<rpc_types::ValidatorsPagination>,
     ) -> Result<ValidatorsResponse, JsonRpcError> {
-        self.inner
+        self.client_at(height.map(NonZeroU64::get))
+            .await?
             .request(
                 "validators",
                 (
@@ -162,7 +193,8 @@ impl Client {
         debug!("fetching abci query");
 
         let res: AbciQueryResponse = self
-            .inner
+            .client_at(height.map(|height| height.inner().unsigned_abs()))
+            .await?
             // the rpc needs an un-prefixed hex string
             .request(
                 "abci_query",
@@ -191,12 +223,56 @@ impl Client {
         Ok(res)
     }
 
+    /// Batched version of [`Self::abci_query`], querying all of `queries` (as `(path, data)`
+    /// pairs) at the same height in a single JSON-RPC batch request. The responses are returned in
+    /// the same order as the queries.
+    #[instrument(
+        skip_all,
+        fields(
+            height = %height.map(|x| x.to_string()).as_deref().unwrap_or(""),
+            %prove,
+        )
+    )]
+    pub async fn abci_query_batch<P: AsRef<str>, D: AsRef<[u8]>>(
+        &self,
+        queries: impl IntoIterator<Item = (P, D)>,
+        height: Option<BoundedI64<1>>,
+        prove: bool,
+    ) -> Result<Vec<AbciQueryResponse>, JsonRpcError> {
+        let mut batch = BatchRequestBuilder::new();
+
+        for (path, data) in queries {
+            batch
+                .insert(
+                    "abci_query",
+                    (
+                        path.as_ref(),
+                        hex::encode(data),
+                        height.map(|x| x.to_string()),
+                        prove,
+                    ),
+                )
+                .map_err(JsonRpcError::ParseError)?;
+        }
+
+        debug!("fetching batched abci query");
+
+        self.client_at(height.map(|height| height.inner().unsigned_abs()))
+            .await?
+            .batch_request::<AbciQueryResponse>(batch)
+            .await?
+            .into_iter()
+            .map(|res| res.map_err(|err| JsonRpcError::Call(err.into_owned())))
+            .collect()
+    }
+
     pub async fn status(&self) -> Result<StatusResponse, JsonRpcError> {
         self.inner.request("status", rpc_params!()).await
     }
 
     pub async fn block(&self, height: Option<NonZeroU64>) -> Result<BlockResponse, JsonRpcError> {
-        self.inner
+        self.client_at(height.map(NonZeroU64::get))
+            .await?
             .request("block", (height.map(|x| x.to_string()),))
             .await
     }
@@ -262,7 +338,8 @@ impl Client {
         &self,
         height: Option<NonZeroU64>,
     ) -> Result<BlockResultsResponse, JsonRpcError> {
-        self.inner
+        self.client_at(height.map(NonZeroU64::get))
+            .await?
             .request("block_results", rpc_params![height.map(|x| x.to_string())])
             .await
     }
@@ -272,6 +349,50 @@ impl Client {
 enum ClientInner {
     Http(HttpClient),
     Ws(reconnecting_jsonrpc_ws_client::Client),
+    Failover(Failover),
+}
+
+impl ClientInner {
+    async fn connect_with_failover(
+        url: String,
+        failover: &FailoverConfig,
+    ) -> Result<Self, JsonRpcError> {
+        if failover.fallback_urls.is_empty() {
+            return Self::connect(url).await;
+        }
+
+        let urls = [url]
+            .into_iter()
+            .chain(failover.fallback_urls.iter().cloned())
+            .collect();
+
+        Ok(ClientInner::Failover(Failover::new(urls, failover).await?))
+    }
+
+    async fn connect(url: String) -> Result<Self, JsonRpcError> {
+        match url.split_once("://") {
+            Some(("ws" | "wss", _)) => {
+                let client = reconnecting_jsonrpc_ws_client::Client::new(move || {
+                    WsClientBuilder::default()
+                        .enable_ws_ping(PingConfig::new())
+                        .build(url.clone())
+                        .instrument(debug_span!("cometbft_rpc_client", %url))
+                });
+
+                // TODO: Config
+                client
+                    .wait_until_connected(Duration::from_secs(5))
+                    .await
+                    .map_err(|e| JsonRpcError::Custom(e.to_string()))?;
+
+                Ok(ClientInner::Ws(client))
+            }
+            Some(("http" | "https", _)) => {
+                Ok(ClientInner::Http(HttpClientBuilder::default().build(url)?))
+            }
+            _ => Err(JsonRpcError::Custom(format!("invalid url {url}"))),
+        }
+    }
 }
 
 #[async_trait]
@@ -283,6 +404,7 @@ impl ClientT for ClientInner {
         match self {
             ClientInner::Http(client) => client.notification(method, params).await,
             ClientInner::Ws(client) => client.notification(method, params).await,
+            ClientInner::Failover(client) => client.notification(method, params).await,
         }
     }
 
@@ -294,6 +416,7 @@ impl ClientT for ClientInner {
         match self {
             ClientInner::Http(client) => client.request(method, params).await,
             ClientInner::Ws(client) => client.request(method, params).await,
+            ClientInner::Failover(client) => client.request(method, params).await,
         }
     }
 
@@ -307,6 +430,7 @@ impl ClientT for ClientInner {
         match self {
             ClientInner::Http(client) => client.batch_request(batch).await,
             ClientInner::Ws(client) => client.batch_request(batch).await,
+            ClientInner::Failover(client) => client.batch_request(batch).await,
         }
     }
 }
@@ -0,0 +1,167 @@
+//! Skipping verification.
+//!
+//! A light client that trusts the validator set at some height can be updated directly to any
+//! later height (within the trusting period), as long as validators with more than
+//! [`TrustLevel`] of the trusted voting power signed the new header. If the validator set has
+//! changed too much for that, the update has to go through intermediate headers. Rather than
+//! replaying every header between the two heights, the intermediate heights are found by
+//! bisection, which results in the minimal set of headers in the common case where the validator
+//! set changes slowly.
+//!
+//! <https://github.com/cometbft/cometbft/blob/main/spec/light-client/verification/README.md>
+
+use std::num::NonZeroU64;
+
+use cometbft_types::types::{commit_sig::CommitSig, validator::Validator};
+use serde::{Deserialize, Serialize};
+use tracing::debug;
+
+use crate::{Client, JsonRpcError};
+
+/// The fraction of the trusted voting power that must have signed a header for it to be trusted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TrustLevel {
+    pub numerator: u64,
+    pub denominator: u64,
+}
+
+impl Default for TrustLevel {
+    /// The default trust level of ibc-go, 1/3.
+    fn default() -> Self {
+        Self {
+            numerator: 1,
+            denominator: 3,
+        }
+    }
+}
+
+impl Client {
+    /// The heights of the headers needed to update a light client from `trusted_height` to
+    /// `target_height`, in order. The last height is always `target_height`.
+    ///
+    /// The trusted validators of each header are the validators at the height of the previous
+    /// header (or `trusted_height` for the first one).
+    pub async fn skipping_update_heights(
+        &self,
+        trusted_height: NonZeroU64,
+        target_height: NonZeroU64,
+        trust_level: TrustLevel,
+    ) -> Result<Vec<NonZeroU64>, JsonRpcError> {
+        let mut heights = vec![];
+        let mut trusted_height = trusted_height;
+
+        while trusted_height < target_height {
+            let trusted_validators = self.all_validators(Some(trusted_height)).await?.validators;
+
+            let mut pivot = target_height;
+
+            while pivot.get() > trusted_height.get() + 1 {
+                let signatures = self
+                    .commit(Some(pivot))
+                    .await?
+                    .signed_header
+                    .commit
+                    .signatures;
+
+                if has_trusted_power(&trusted_validators, &signatures, trust_level) {
+                    break;
+                }
+
+                debug!(
+                    %trusted_height,
+                    %pivot,
+                    "not enough trusted voting power signed, bisecting"
+                );
+
+                pivot = NonZeroU64::new(
+                    trusted_height.get() + (pivot.get() - trusted_height.get()) / 2,
+                )
+                .expect("pivot is > trusted height; qed;");
+            }
+
+            heights.push(pivot);
+            trusted_height = pivot;
+        }
+
+        Ok(heights)
+    }
+}
+
+/// Whether validators in `trusted_validators` with more than `trust_level` of their total voting
+/// power signed the commit with `signatures`.
+#[must_use]
+pub fn has_trusted_power(
+    trusted_validators: &[Validator],
+    signatures: &[CommitSig],
+    trust_level: TrustLevel,
+) -> bool {
+    let total_power = trusted_validators
+        .iter()
+        .map(|validator| u128::from(validator.voting_power.inner().unsigned_abs()))
+        .sum::<u128>();
+
+    let signed_power = signatures
+        .iter()
+        .filter_map(|signature| match signature {
+            CommitSig::Commit {
+                validator_address, ..
+            } => trusted_validators
+                .iter()
+                .find(|validator| validator.address == *validator_address),
+            _ => None,
+        })
+        .map(|validator| u128::from(validator.voting_power.inner().unsigned_abs()))
+        .sum::<u128>();
+
+    signed_power * u128::from(trust_level.denominator)
+        > total_power * u128::from(trust_level.numerator)
+}
+
+#[cfg(test)]
+mod tests {
+    use cometbft_types::crypto::public_key::PublicKey;
+    use unionlabs::{bounded::BoundedI64, google::protobuf::timestamp::Timestamp, hash::H160};
+
+    use super::*;
+
+    fn validator(address: u8, voting_power: i64) -> Validator {
+        Validator {
+            address: H160::new([address; 20]),
+            pub_key: PublicKey::Ed25519(vec![address; 32].into()),
+            voting_power: BoundedI64::new(voting_power).unwrap(),
+            proposer_priority: 0,
+        }
+    }
+
+    fn signed(address: u8) -> CommitSig {
+        CommitSig::Commit {
+            validator_address: H160::new([address; 20]),
+            timestamp: Timestamp::default(),
+            signature: vec![].into(),
+        }
+    }
+
+    #[test]
+    fn trusted_power() {
+        let validators = [validator(1, 10), validator(2, 10), validator(3, 10)];
+
+        // exactly 1/3 is not enough
+        assert!(!has_trusted_power(
+            &validators,
+            &[signed(1), CommitSig::Absent],
+            TrustLevel::default()
+        ));
+        assert!(has_trusted_power(
+            &validators,
+            &[signed(1), signed(2)],
+            TrustLevel::default()
+        ));
+        // signatures from validators that are not trusted don't count
+        assert!(!has_trusted_power(
+            &validators,
+            &[signed(1), signed(4), signed(5)],
+            TrustLevel::default()
+        ));
+    }
+}
@@ -77,6 +77,20 @@ pub enum StorePath {
     NextSequenceAck(NextSequenceAckPath),
     NextConnectionSequence(NextConnectionSequencePath),
     NextClientSequence(NextClientSequencePath),
+    UpgradedClientState(UpgradedClientStatePath),
+    UpgradedConsensusState(UpgradedConsensusStatePath),
+}
+
+impl StorePath {
+    /// The key of the store that this path is stored in. All paths are in the IBC store, except
+    /// for the upgraded client and consensus states which are written to the upgrade store.
+    #[must_use]
+    pub fn store_key(&self) -> &'static str {
+        match self {
+            Self::UpgradedClientState(_) | Self::UpgradedConsensusState(_) => "upgrade",
+            _ => "ibc",
+        }
+    }
 }
 
 impl fmt::Display for StorePath {
@@ -94,6 +108,8 @@ impl fmt::Display for StorePath {
             Self::NextSequenceAck(path) => write!(f, "{path}"),
             Self::NextConnectionSequence(path) => write!(f, "{path}"),
             Self::NextClientSequence(path) => write!(f, "{path}"),
+            Self::UpgradedClientState(path) => write!(f, "{path}"),
+            Self::UpgradedConsensusState(path) => write!(f, "{path}"),
         }
     }
 }
@@ -114,6 +130,8 @@ impl FromStr for StorePath {
             .or_else(|_| s.parse().map(Self::NextSequenceRecv))
             .or_else(|_| s.parse().map(Self::NextSequenceAck))
             .or_else(|_| s.parse().map(Self::NextConnectionSequence))
+            .or_else(|_| s.parse().map(Self::UpgradedClientState))
+            .or_else(|_| s.parse().map(Self::UpgradedConsensusState))
     }
 }
 
@@ -262,6 +280,24 @@ pub struct NextSequenceRecvPath {
     pub channel_id: ChannelId,
 }
 
+/// The client state that clients tracking this chain will be upgraded to by the upgrade plan at
+/// `upgrade_height`, as a raw `Any`. This is only set once the chain reaches `upgrade_height - 1`.
+///
+/// This is stored in the upgrade store, not the IBC store.
+#[ibc_path("upgradedIBCState/{upgrade_height}/upgradedClient", Option<Bytes>)]
+pub struct UpgradedClientStatePath {
+    pub upgrade_height: u64,
+}
+
+/// The consensus state that clients tracking this chain will be upgraded to by the upgrade plan at
+/// `upgrade_height`, as a raw `Any`. This is only set once the chain reaches `upgrade_height - 1`.
+///
+/// This is stored in the upgrade store, not the IBC store.
+#[ibc_path("upgradedIBCState/{upgrade_height}/upgradedConsState", Option<Bytes>)]
+pub struct UpgradedConsensusStatePath {
+    pub upgrade_height: u64,
+}
+
 #[ibc_path("nextSequenceAck/ports/{port_id}/channels/{channel_id:#}", u64)]
 pub struct NextSequenceAckPath {
     pub port_id: PortId,
@@ -313,6 +349,11 @@ pub enum Datagram {
     RecvPacket(MsgRecvPacket),
     AcknowledgePacket(MsgAcknowledgement),
     TimeoutPacket(MsgTimeout),
+
+    StoreWasmCode(MsgStoreWasmCode),
+    MigrateWasmClient(MsgMigrateWasmClient),
+
+    UpgradeClient(MsgUpgradeClient),
 }
 
 impl Datagram {
@@ -333,6 +374,9 @@ impl Datagram {
             Datagram::RecvPacket(msg) => Some(msg.proof_height),
             Datagram::AcknowledgePacket(msg) => Some(msg.proof_height),
             Datagram::TimeoutPacket(msg) => Some(msg.proof_height),
+            Datagram::StoreWasmCode(_) => None,
+            Datagram::MigrateWasmClient(_) => None,
+            Datagram::UpgradeClient(_) => None,
         }
     }
 
@@ -351,6 +395,9 @@ impl Datagram {
             Datagram::RecvPacket(_) => "recv_packet",
             Datagram::AcknowledgePacket(_) => "acknowledgement",
             Datagram::TimeoutPacket(_) => "timeout",
+            Datagram::StoreWasmCode(_) => "store_wasm_code",
+            Datagram::MigrateWasmClient(_) => "migrate_wasm_client",
+            Datagram::UpgradeClient(_) => "upgrade_client",
         }
     }
 }
@@ -596,6 +643,46 @@ pub struct MsgCreateClientData {
     pub client_type: ClientType,
 }
 
+/// Store the code of an 08-wasm light client (`ibc.lightclients.wasm.v1.MsgStoreCode`).
+///
+/// This must be signed by the authority of the 08-wasm module, which is usually the governance
+/// module.
+#[model]
+pub struct MsgStoreWasmCode {
+    /// The wasm byte code of the light client contract, either raw or gzip compressed.
+    pub wasm_byte_code: Bytes,
+}
+
+/// Migrate an existing 08-wasm client to code that has already been stored with
+/// [`MsgStoreWasmCode`] (`ibc.lightclients.wasm.v1.MsgMigrateContract`).
+///
+/// This must be signed by the authority of the 08-wasm module, which is usually the governance
+/// module.
+#[model]
+pub struct MsgMigrateWasmClient {
+    pub client_id: ClientId,
+    /// The sha256 checksum of the code to migrate the client to.
+    pub checksum: H256,
+    /// The json encoded message passed to the `migrate` entrypoint of the contract.
+    pub migrate_msg: Bytes,
+}
+
+/// Upgrade a client after a planned upgrade of the chain it tracks
+/// (`ibc.core.client.v1.MsgUpgradeClient`).
+///
+/// The client must be updated to the upgrade height before this is submitted, since the proofs
+/// are verified against the consensus state at the latest height of the client.
+#[model]
+pub struct MsgUpgradeClient {
+    pub client_id: ClientId,
+    /// The upgraded client state, as stored at [`UpgradedClientStatePath`].
+    pub client_state: Bytes,
+    /// The upgraded consensus state, as stored at [`UpgradedConsensusStatePath`].
+    pub consensus_state: Bytes,
+    pub proof_upgrade_client: Bytes,
+    pub proof_upgrade_consensus_state: Bytes,
+}
+
 pub fn log_msg(chain_id: &str, effect: &Datagram) {
     match effect.clone() {
         Datagram::ConnectionOpenInit(message) => {
@@ -772,6 +859,25 @@ pub fn log_msg(chain_id: &str, effect: &Datagram) {
                 %message.client_id,
             )
         }
+        Datagram::StoreWasmCode(message) => {
+            info!(
+                %chain_id,
+                wasm_byte_code_len = message.wasm_byte_code.len(),
+            )
+        }
+        Datagram::MigrateWasmClient(message) => {
+            info!(
+                %chain_id,
+                %message.client_id,
+                %message.checksum,
+            )
+        }
+        Datagram::UpgradeClient(message) => {
+            info!(
+                %chain_id,
+                %message.client_id,
+            )
+        }
     }
 }
 
@@ -835,5 +941,21 @@ mod tests {
                 sequence: 1.try_into().unwrap()
             })
         );
+        assert_eq!(
+            "upgradedIBCState/100/upgradedClient"
+                .parse::<StorePath>()
+                .unwrap(),
+            StorePath::UpgradedClientState(UpgradedClientStatePath {
+                upgrade_height: 100
+            })
+        );
+        assert_eq!(
+            "upgradedIBCState/100/upgradedConsState"
+                .parse::<StorePath>()
+                .unwrap(),
+            StorePath::UpgradedConsensusState(UpgradedConsensusStatePath {
+                upgrade_height: 100
+            })
+        );
     }
 }
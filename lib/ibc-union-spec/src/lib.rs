@@ -254,15 +254,15 @@ impl Datagram {
             Self::ConnectionOpenTry(msg) => Some(Height::new(msg.proof_height)),
             Self::ConnectionOpenAck(msg) => Some(Height::new(msg.proof_height)),
             Self::ConnectionOpenConfirm(msg) => Some(Height::new(msg.proof_height)),
-            Self::ChannelOpenInit(_msg) => todo!(),
-            Self::ChannelOpenTry(_msg) => todo!(),
-            Self::ChannelOpenAck(_msg) => todo!(),
-            Self::ChannelOpenConfirm(_msg) => todo!(),
-            Self::ChannelCloseInit(_msg) => todo!(),
-            Self::ChannelCloseConfirm(_msg) => todo!(),
-            Self::PacketRecv(_msg) => todo!(),
-            Self::PacketAcknowledgement(_msg) => todo!(),
-            Self::PacketTimeout(_msg) => todo!(),
+            Self::ChannelOpenInit(_msg) => None,
+            Self::ChannelOpenTry(msg) => Some(Height::new(msg.proof_height)),
+            Self::ChannelOpenAck(msg) => Some(Height::new(msg.proof_height)),
+            Self::ChannelOpenConfirm(msg) => Some(Height::new(msg.proof_height)),
+            Self::ChannelCloseInit(_msg) => None,
+            Self::ChannelCloseConfirm(msg) => Some(Height::new(msg.proof_height)),
+            Self::PacketRecv(msg) => Some(Height::new(msg.proof_height)),
+            Self::PacketAcknowledgement(msg) => Some(Height::new(msg.proof_height)),
+            Self::PacketTimeout(msg) => Some(Height::new(msg.proof_height)),
             Self::IntentPacketRecv(_msg) => todo!(),
             Self::BatchSend(_msg) => todo!(),
             Self::BatchAcks(_msg) => todo!(),
@@ -370,10 +370,16 @@ pub struct MsgChannelOpenConfirm {
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
-pub struct MsgChannelCloseInit {}
+pub struct MsgChannelCloseInit {
+    pub channel_id: u32,
+}
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
-pub struct MsgChannelCloseConfirm {}
+pub struct MsgChannelCloseConfirm {
+    pub channel_id: u32,
+    pub proof_init: Bytes,
+    pub proof_height: u64,
+}
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct MsgPacketRecv {
@@ -392,7 +398,12 @@ pub struct MsgPacketAcknowledgement {
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
-pub struct MsgPacketTimeout {}
+pub struct MsgPacketTimeout {
+    pub packet: Packet,
+    /// Proof of non-membership of the packet receipt on the destination chain.
+    pub proof: Bytes,
+    pub proof_height: u64,
+}
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct MsgIntentPacketRecv {}
@@ -444,8 +455,8 @@ impl FullEvent {
             FullEvent::ChannelOpenTry(event) => Some(event.connection.counterparty_client_id),
             FullEvent::ChannelOpenAck(event) => Some(event.connection.counterparty_client_id),
             FullEvent::ChannelOpenConfirm(event) => Some(event.connection.counterparty_client_id),
-            FullEvent::ChannelCloseInit(_) => todo!(),
-            FullEvent::ChannelCloseConfirm(_) => todo!(),
+            FullEvent::ChannelCloseInit(event) => Some(event.connection.counterparty_client_id),
+            FullEvent::ChannelCloseConfirm(event) => Some(event.connection.counterparty_client_id),
             Self::SendPacket(event) => Some(event.packet.destination_channel.connection.client_id),
             Self::RecvPacket(event) => Some(event.packet.source_channel.connection.client_id),
             Self::RecvIntentPacket(event) => Some(event.packet.source_channel.connection.client_id),
@@ -554,10 +565,22 @@ pub struct ChannelOpenConfirm {
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
-pub struct ChannelCloseInit {}
+pub struct ChannelCloseInit {
+    pub port_id: Bytes,
+    pub channel_id: ChannelId,
+    pub counterparty_port_id: Bytes,
+    pub counterparty_channel_id: ChannelId,
+    pub connection: Connection,
+}
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
-pub struct ChannelCloseConfirm {}
+pub struct ChannelCloseConfirm {
+    pub port_id: Bytes,
+    pub channel_id: ChannelId,
+    pub counterparty_port_id: Bytes,
+    pub counterparty_channel_id: ChannelId,
+    pub connection: Connection,
+}
 
 // TODO: Inline packet_data into PacketMetadata
 
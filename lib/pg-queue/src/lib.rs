@@ -1,5 +1,10 @@
 use std::{
-    borrow::Borrow, cmp::Eq, collections::HashMap, future::Future, hash::Hash, marker::PhantomData,
+    borrow::Borrow,
+    cmp::Eq,
+    collections::{HashMap, HashSet},
+    future::Future,
+    hash::Hash,
+    marker::PhantomData,
     time::Duration,
 };
 
@@ -7,18 +12,24 @@ use frame_support_procedural::{CloneNoBound, DebugNoBound};
 use futures_util::TryStreamExt;
 use itertools::Itertools;
 use schemars::JsonSchema;
-use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde::{Deserialize, Serialize};
 use sqlx::{postgres::PgPoolOptions, prelude::FromRow, types::Json, Either, Executor, PgPool};
 use tracing::{debug, debug_span, info_span, instrument, trace, Instrument};
 use voyager_vm::{
     filter::{FilterResult, InterestFilter},
     pass::{Pass, PassResult},
-    Captures, Op, QueueMessage,
+    wire, Captures, Op, QueueMessage,
 };
 
-use crate::metrics::{ITEM_PROCESSING_DURATION, OPTIMIZE_ITEM_COUNT, OPTIMIZE_PROCESSING_DURATION};
+use crate::{
+    metrics::{
+        ITEM_PROCESSING_DURATION, OPTIMIZE_DEPTH, OPTIMIZE_ITEM_COUNT, OPTIMIZE_PROCESSING_DURATION,
+    },
+    shard::ShardingConfig,
+};
 
 pub mod metrics;
+pub mod shard;
 
 /// A fifo queue backed by a postgres table. Not suitable for high-throughput, but enough for ~1k items/sec.
 ///
@@ -33,6 +44,8 @@ pub mod metrics;
 #[derive(DebugNoBound, CloneNoBound)]
 pub struct PgQueue<T> {
     client: PgPool,
+    /// The instance id that this queue holds shard leases as, if sharding is enabled.
+    shard_owner: Option<String>,
     __marker: PhantomData<fn() -> T>,
 }
 
@@ -44,6 +57,10 @@ pub struct PgQueueConfig {
     pub min_connections: Option<u32>,
     pub idle_timeout: Option<Duration>,
     pub max_lifetime: Option<Duration>,
+    /// Split the ready items between multiple voyager instances sharing this database, such that
+    /// each chain pair is only relayed by one instance. See [`shard`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sharding: Option<ShardingConfig>,
 }
 
 impl PgQueueConfig {
@@ -67,21 +84,174 @@ struct Id {
 struct Record {
     id: i64,
     parents: Vec<i64>,
+    /// `NULL` for items that were enqueued directly, which are the root of their own tree.
+    correlation_id: Option<i64>,
     item: String,
     created_at: sqlx::types::time::OffsetDateTime,
+    /// The [`QueueMessage::WIRE_VERSION`] that the item was serialized with.
+    version: i32,
+    /// See `shard`.
+    shard_key: Option<String>,
+}
+
+impl Record {
+    fn correlation_id(&self) -> i64 {
+        self.correlation_id.unwrap_or(self.id)
+    }
+
+    fn decode<T: QueueMessage>(&self) -> Result<Op<T>, sqlx::Error> {
+        decode_item(self.version, &self.item)
+    }
+}
+
+/// Decode an item that was serialized with wire version `version`, migrating it to the current
+/// [`QueueMessage::WIRE_VERSION`] if necessary.
+fn decode_item<T: QueueMessage>(version: i32, item: &str) -> Result<Op<T>, sqlx::Error> {
+    u32::try_from(version)
+        .map_err(|e| sqlx::Error::Decode(Box::new(e)))
+        .and_then(|version| {
+            wire::decode(version, item).map_err(|e| sqlx::Error::Decode(Box::new(e)))
+        })
 }
 
-#[derive(Debug, FromRow, Serialize)]
+#[derive(Debug, Serialize)]
 #[serde(bound(serialize = ""))]
 pub struct FailedRecord<T: QueueMessage> {
     pub id: i64,
     pub parents: Vec<i64>,
+    pub correlation_id: i64,
     pub item: Json<Op<T>>,
     pub message: String,
     // pub created_at: sqlx::types::time::OffsetDateTime,
 }
 
+/// A [`FailedRecord`] as stored in the database, before the item is decoded.
+#[derive(FromRow)]
+struct FailedRow {
+    id: i64,
+    parents: Vec<i64>,
+    correlation_id: i64,
+    item: String,
+    version: i32,
+    message: String,
+}
+
+impl FailedRow {
+    fn decode<T: QueueMessage>(self) -> Result<FailedRecord<T>, sqlx::Error> {
+        Ok(FailedRecord {
+            id: self.id,
+            parents: self.parents,
+            correlation_id: self.correlation_id,
+            item: Json(decode_item(self.version, &self.item)?),
+            message: self.message,
+        })
+    }
+}
+
+/// An item that is waiting to be processed or optimized.
+#[derive(Debug, Serialize)]
+#[serde(bound(serialize = ""))]
+pub struct QueuedRecord<T: QueueMessage> {
+    pub id: i64,
+    pub parents: Vec<i64>,
+    /// The id of the item that was originally enqueued and that this item descends from.
+    pub correlation_id: i64,
+    pub item: Json<Op<T>>,
+    /// The tag of the optimizer the item is waiting for, if any.
+    pub tag: Option<String>,
+    /// The unix timestamp (in seconds) at which the item was enqueued.
+    pub created_at: i64,
+}
+
+/// A [`QueuedRecord`] as stored in the database, before the item is decoded.
+#[derive(FromRow)]
+struct QueuedRow {
+    id: i64,
+    parents: Vec<i64>,
+    correlation_id: i64,
+    item: String,
+    version: i32,
+    tag: Option<String>,
+    created_at: i64,
+}
+
+impl QueuedRow {
+    fn decode<T: QueueMessage>(self) -> Result<QueuedRecord<T>, sqlx::Error> {
+        Ok(QueuedRecord {
+            id: self.id,
+            parents: self.parents,
+            correlation_id: self.correlation_id,
+            item: Json(decode_item(self.version, &self.item)?),
+            tag: self.tag,
+            created_at: self.created_at,
+        })
+    }
+}
+
+/// The amount of rows to skip to get to the 1-indexed `page`.
+fn page_offset(page: i64, per_page: i64) -> i64 {
+    page.saturating_sub(1).max(0).saturating_mul(per_page)
+}
+
 impl<T: QueueMessage> PgQueue<T> {
+    /// Periodically record the depth of the queue tables to [`metrics::QUEUE_DEPTH`], until
+    /// `shutdown` resolves.
+    ///
+    /// This should be run once per process, not once per queue handle.
+    pub async fn record_depth(&self, shutdown: impl Future<Output = ()>) {
+        metrics::record_depth(self.client.clone(), shutdown).await
+    }
+
+    /// Query the items that are waiting to be processed or optimized, ordered by id.
+    ///
+    /// Items that are currently being processed are still included, since they are only removed
+    /// once they have been processed.
+    pub async fn query_queued(
+        &self,
+        page: i64,
+        per_page: i64,
+    ) -> Result<Vec<QueuedRecord<T>>, sqlx::Error> {
+        sqlx::query(
+            r#"
+            SELECT
+                id,
+                parents,
+                COALESCE(correlation_id, id) AS correlation_id,
+                item::TEXT,
+                version,
+                NULL::TEXT AS tag,
+                EXTRACT(EPOCH FROM created_at)::BIGINT AS created_at
+            FROM
+                queue
+            UNION ALL
+            SELECT
+                id,
+                parents,
+                COALESCE(correlation_id, id) AS correlation_id,
+                item::TEXT,
+                version,
+                tag,
+                EXTRACT(EPOCH FROM created_at)::BIGINT AS created_at
+            FROM
+                optimize
+            ORDER BY
+                id ASC
+            LIMIT
+                $1
+            OFFSET
+                $2
+            "#,
+        )
+        .bind(per_page)
+        .bind(page_offset(page, per_page))
+        .try_map(|row| QueuedRow::from_row(&row))
+        .fetch_all(&self.client)
+        .await?
+        .into_iter()
+        .map(QueuedRow::decode)
+        .collect()
+    }
+
     pub async fn query_failed(
         &self,
         page: i64,
@@ -103,7 +273,9 @@ impl<T: QueueMessage> PgQueue<T> {
             SELECT
                 id,
                 parents,
-                item,
+                COALESCE(correlation_id, id) AS correlation_id,
+                item::TEXT,
+                version,
                 message
             FROM
                 failed 
@@ -121,14 +293,92 @@ impl<T: QueueMessage> PgQueue<T> {
         .bind(item_filters)
         .bind(message_filters)
         .bind(per_page)
-        .bind((page - 1) * per_page)
-        .map(|row| FailedRecord::<T>::from_row(&row))
+        .bind(page_offset(page, per_page))
+        .try_map(|row| FailedRow::from_row(&row))
         .fetch_all(&self.client)
         .await?
         .into_iter()
+        .map(FailedRow::decode)
         .collect()
     }
 
+    /// Move the failed item `id` back into the queue, as if it were enqueued again. Returns `false`
+    /// if there is no failed item with that id.
+    ///
+    /// The item is decoded (migrating it to the current wire version) before it is enqueued, but
+    /// it is only removed from the failed items by the same statement that enqueues it, such that
+    /// it is neither lost nor enqueued twice when it is requeued concurrently.
+    pub async fn requeue_failed(&self, id: i64, filter: &T::Filter) -> Result<bool, sqlx::Error> {
+        let Some(record) = self.query_failed_by_id(id).await? else {
+            return Ok(false);
+        };
+
+        let (optimize, ready): (Vec<_>, Vec<_>) = dedup(record.item.0.normalize())
+            .into_iter()
+            .partition_map(|(op, key)| match filter.check_interest(&op) {
+                FilterResult::Interest(tag) => Either::Left((op, tag, key)),
+                FilterResult::NoInterest => Either::Right((op, key)),
+            });
+
+        let removed = sqlx::query(
+            "
+            WITH removed AS (
+                DELETE FROM failed WHERE id = $1 RETURNING id
+            ),
+            ready_items AS (
+                INSERT INTO queue (item, idempotency_key, due_at, version, shard_key, pause_keys, priority)
+                SELECT t.item, t.idempotency_key, t.due_at, $2::INTEGER, t.shard_key, ARRAY(SELECT jsonb_array_elements_text(t.pause_keys)), t.priority FROM UNNEST($3::JSONB[], $4::TEXT[], $5::BIGINT[], $6::TEXT[], $7::JSONB[], $8::INTEGER[]) AS t(item, idempotency_key, due_at, shard_key, pause_keys, priority)
+                WHERE EXISTS (SELECT 1 FROM removed)
+                ON CONFLICT (idempotency_key) WHERE idempotency_key IS NOT NULL DO NOTHING
+            ),
+            optimize_items AS (
+                INSERT INTO optimize (item, tag, idempotency_key, version, shard_key)
+                SELECT t.item, t.tag, t.idempotency_key, $2::INTEGER, t.shard_key FROM UNNEST($9::JSONB[], $10::TEXT[], $11::TEXT[], $12::TEXT[]) AS t(item, tag, idempotency_key, shard_key)
+                WHERE EXISTS (SELECT 1 FROM removed)
+                ON CONFLICT (idempotency_key) WHERE idempotency_key IS NOT NULL DO NOTHING
+            )
+            SELECT id FROM removed
+            ",
+        )
+        .bind(id)
+        .bind(wire_version::<T>())
+        .bind(ready.iter().map(|(op, _)| Json(op)).collect::<Vec<_>>())
+        .bind(ready.iter().map(|(_, key)| key.clone()).collect::<Vec<_>>())
+        .bind(ready.iter().map(|(op, _)| due_at(op)).collect::<Vec<_>>())
+        .bind(
+            ready
+                .iter()
+                .map(|(op, _)| T::shard_key(op))
+                .collect::<Vec<_>>(),
+        )
+        .bind(
+            ready
+                .iter()
+                .map(|(op, _)| Json(T::pause_keys(op)))
+                .collect::<Vec<_>>(),
+        )
+        .bind(
+            ready
+                .iter()
+                .map(|(op, _)| i32::from(op.priority()))
+                .collect::<Vec<_>>(),
+        )
+        .bind(optimize.iter().map(|(op, _, _)| Json(op)).collect::<Vec<_>>())
+        .bind(optimize.iter().map(|(_, tag, _)| *tag).collect::<Vec<_>>())
+        .bind(optimize.iter().map(|(_, _, key)| key.clone()).collect::<Vec<_>>())
+        .bind(
+            optimize
+                .iter()
+                .map(|(op, _, _)| T::shard_key(op))
+                .collect::<Vec<_>>(),
+        )
+        .try_map(|row| Id::from_row(&row))
+        .fetch_optional(&self.client)
+        .await?;
+
+        Ok(removed.is_some())
+    }
+
     pub async fn query_failed_by_id(
         &self,
         id: i64,
@@ -138,7 +388,9 @@ impl<T: QueueMessage> PgQueue<T> {
             SELECT
                id,
                parents,
-               item,
+               COALESCE(correlation_id, id) AS correlation_id,
+               item::TEXT,
+               version,
                message
             FROM
                failed 
@@ -147,9 +399,10 @@ impl<T: QueueMessage> PgQueue<T> {
             "#,
         )
         .bind(id)
-        .map(|row| FailedRecord::<T>::from_row(&row))
+        .try_map(|row| FailedRow::from_row(&row))
         .fetch_optional(&self.client)
         .await?
+        .map(FailedRow::decode)
         .transpose()
     }
 }
@@ -171,6 +424,8 @@ impl<T: QueueMessage> voyager_vm::Queue<T> for PgQueue<T> {
         //     }
         // });
 
+        let sharding = config.sharding.clone();
+
         let pool = config.into_pg_pool().await?;
 
         pool.execute_many(
@@ -208,6 +463,91 @@ impl<T: QueueMessage> voyager_vm::Queue<T> for PgQueue<T> {
             );
 
             CREATE INDEX IF NOT EXISTS index_queue_id ON queue(id);
+
+            ALTER TABLE queue ADD COLUMN IF NOT EXISTS idempotency_key TEXT;
+            ALTER TABLE optimize ADD COLUMN IF NOT EXISTS idempotency_key TEXT;
+
+            -- at most one item per `QueueMessage::idempotency_key` may be in each table, see `dedup`.
+            -- of the items that were enqueued concurrently before this was enforced, only the
+            -- oldest one keeps its key
+            DO $$
+            BEGIN
+              IF to_regclass('unique_queue_idempotency_key') IS NULL THEN
+                UPDATE queue q SET idempotency_key = NULL
+                WHERE EXISTS (
+                  SELECT 1 FROM queue o
+                  WHERE o.idempotency_key = q.idempotency_key AND o.id < q.id
+                );
+
+                CREATE UNIQUE INDEX unique_queue_idempotency_key ON queue(idempotency_key)
+                WHERE idempotency_key IS NOT NULL;
+              END IF;
+
+              IF to_regclass('unique_optimize_idempotency_key') IS NULL THEN
+                UPDATE optimize q SET idempotency_key = NULL
+                WHERE EXISTS (
+                  SELECT 1 FROM optimize o
+                  WHERE o.idempotency_key = q.idempotency_key AND o.id < q.id
+                );
+
+                CREATE UNIQUE INDEX unique_optimize_idempotency_key ON optimize(idempotency_key)
+                WHERE idempotency_key IS NOT NULL;
+              END IF;
+            END
+            $$;
+
+            DROP INDEX IF EXISTS index_queue_idempotency_key;
+            DROP INDEX IF EXISTS index_optimize_idempotency_key;
+
+            ALTER TABLE queue ADD COLUMN IF NOT EXISTS correlation_id BIGINT;
+            ALTER TABLE optimize ADD COLUMN IF NOT EXISTS correlation_id BIGINT;
+            ALTER TABLE done ADD COLUMN IF NOT EXISTS correlation_id BIGINT;
+            ALTER TABLE failed ADD COLUMN IF NOT EXISTS correlation_id BIGINT;
+
+            -- the unix timestamp (in seconds) before which the item is deferred, see `Op::due_at`
+            ALTER TABLE queue ADD COLUMN IF NOT EXISTS due_at BIGINT;
+
+            -- the wire version the item was serialized with, see `QueueMessage::WIRE_VERSION`
+            ALTER TABLE queue ADD COLUMN IF NOT EXISTS version INTEGER NOT NULL DEFAULT 0;
+            ALTER TABLE optimize ADD COLUMN IF NOT EXISTS version INTEGER NOT NULL DEFAULT 0;
+            ALTER TABLE done ADD COLUMN IF NOT EXISTS version INTEGER NOT NULL DEFAULT 0;
+            ALTER TABLE failed ADD COLUMN IF NOT EXISTS version INTEGER NOT NULL DEFAULT 0;
+
+            -- the `QueueMessage::shard_key` of the item (or of its parent), see `shard`
+            ALTER TABLE queue ADD COLUMN IF NOT EXISTS shard_key TEXT;
+            ALTER TABLE optimize ADD COLUMN IF NOT EXISTS shard_key TEXT;
+
+            -- the `QueueMessage::pause_keys` of the item, see `Queue::process`
+            ALTER TABLE queue ADD COLUMN IF NOT EXISTS pause_keys TEXT[] NOT NULL DEFAULT '{}';
+
+            -- the `Op::priority` of the item, backfilled from the root of the items that were
+            -- enqueued before the column was added
+            DO $$
+            BEGIN
+              IF NOT EXISTS (
+                SELECT 1 FROM information_schema.columns
+                WHERE table_name = 'queue' AND column_name = 'priority'
+              ) THEN
+                ALTER TABLE queue ADD COLUMN priority INTEGER NOT NULL DEFAULT 0;
+
+                UPDATE queue SET priority = (item->'@value'->>'priority')::INTEGER
+                WHERE item->>'@type' = 'with_priority';
+              END IF;
+            END
+            $$;
+
+            CREATE INDEX IF NOT EXISTS index_queue_priority_id ON queue(priority DESC, id ASC);
+
+            CREATE TABLE IF NOT EXISTS shard_leases(
+                shard_key TEXT PRIMARY KEY,
+                owner TEXT NOT NULL,
+                expires_at timestamptz NOT NULL
+            );
+
+            CREATE TABLE IF NOT EXISTS shard_instances(
+                id TEXT PRIMARY KEY,
+                last_seen timestamptz NOT NULL
+            );
             "#,
         )
         .try_for_each(|result| async move {
@@ -217,8 +557,13 @@ impl<T: QueueMessage> voyager_vm::Queue<T> for PgQueue<T> {
         .instrument(info_span!("init"))
         .await?;
 
+        if let Some(sharding) = &sharding {
+            tokio::spawn(shard::maintain_leases(pool.clone(), sharding.clone()));
+        }
+
         Ok(Self {
             client: pool,
+            shard_owner: sharding.map(|sharding| sharding.instance_id),
             __marker: PhantomData,
         })
     }
@@ -227,23 +572,45 @@ impl<T: QueueMessage> voyager_vm::Queue<T> for PgQueue<T> {
         trace!("enqueue");
 
         let (optimize, ready): (Vec<_>, Vec<_>) =
-            op.normalize()
+            dedup(op.normalize())
                 .into_iter()
-                .partition_map(|op| match filter.check_interest(&op) {
-                    FilterResult::Interest(tag) => Either::Left((op, tag)),
-                    FilterResult::NoInterest => Either::Right(op),
+                .partition_map(|(op, key)| match filter.check_interest(&op) {
+                    FilterResult::Interest(tag) => Either::Left((op, tag, key)),
+                    FilterResult::NoInterest => Either::Right((op, key)),
                 });
 
         let mut tx = self.client.begin().await?;
 
         let ready_ids = sqlx::query(
             "
-            INSERT INTO queue (item)
-            SELECT * FROM UNNEST($1::JSONB[])
+            INSERT INTO queue (item, idempotency_key, due_at, version, shard_key, pause_keys, priority)
+            SELECT t.item, t.idempotency_key, t.due_at, $4::INTEGER, t.shard_key, ARRAY(SELECT jsonb_array_elements_text(t.pause_keys)), t.priority FROM UNNEST($1::JSONB[], $2::TEXT[], $3::BIGINT[], $5::TEXT[], $6::JSONB[], $7::INTEGER[]) AS t(item, idempotency_key, due_at, shard_key, pause_keys, priority)
+            ON CONFLICT (idempotency_key) WHERE idempotency_key IS NOT NULL DO NOTHING
             RETURNING id
             ",
         )
-        .bind(ready.into_iter().map(Json).collect::<Vec<_>>())
+        .bind(ready.iter().map(|(op, _)| Json(op)).collect::<Vec<_>>())
+        .bind(ready.iter().map(|(_, key)| key.clone()).collect::<Vec<_>>())
+        .bind(ready.iter().map(|(op, _)| due_at(op)).collect::<Vec<_>>())
+        .bind(wire_version::<T>())
+        .bind(
+            ready
+                .iter()
+                .map(|(op, _)| T::shard_key(op))
+                .collect::<Vec<_>>(),
+        )
+        .bind(
+            ready
+                .iter()
+                .map(|(op, _)| Json(T::pause_keys(op)))
+                .collect::<Vec<_>>(),
+        )
+        .bind(
+            ready
+                .iter()
+                .map(|(op, _)| i32::from(op.priority()))
+                .collect::<Vec<_>>(),
+        )
         .try_map(|x| Id::from_row(&x))
         .fetch_all(tx.as_mut())
         .await?;
@@ -254,19 +621,22 @@ impl<T: QueueMessage> voyager_vm::Queue<T> for PgQueue<T> {
 
         let optimize_further_ids = sqlx::query(
             "
-            INSERT INTO optimize (item, tag)
-            SELECT * FROM UNNEST($1::JSONB[], $2::TEXT[])
+            INSERT INTO optimize (item, tag, idempotency_key, version, shard_key)
+            SELECT t.item, t.tag, t.idempotency_key, $4::INTEGER, t.shard_key FROM UNNEST($1::JSONB[], $2::TEXT[], $3::TEXT[], $5::TEXT[]) AS t(item, tag, idempotency_key, shard_key)
+            ON CONFLICT (idempotency_key) WHERE idempotency_key IS NOT NULL DO NOTHING
             RETURNING id
             ",
         )
+        .bind(optimize.iter().map(|(op, _, _)| Json(op)).collect::<Vec<_>>())
+        .bind(optimize.iter().map(|(_, tag, _)| *tag).collect::<Vec<_>>())
+        .bind(optimize.iter().map(|(_, _, key)| key.clone()).collect::<Vec<_>>())
+        .bind(wire_version::<T>())
         .bind(
             optimize
-                .clone()
-                .into_iter()
-                .map(|x| Json(x.0))
+                .iter()
+                .map(|(op, _, _)| T::shard_key(op))
                 .collect::<Vec<_>>(),
         )
-        .bind(optimize.into_iter().map(|x| x.1).collect::<Vec<_>>())
         .try_map(|x| Id::from_row(&x))
         .fetch_all(tx.as_mut())
         .await?;
@@ -284,6 +654,7 @@ impl<T: QueueMessage> voyager_vm::Queue<T> for PgQueue<T> {
     async fn process<'a, F, Fut, R>(
         &'a self,
         filter: &'a T::Filter,
+        paused: &'a [String],
         f: F,
     ) -> Result<Option<R>, Self::Error>
     where
@@ -295,6 +666,8 @@ impl<T: QueueMessage> voyager_vm::Queue<T> for PgQueue<T> {
 
         let mut tx = self.client.begin().await?;
 
+        // paused items are filtered out by their pause keys, such that they are left in the queue
+        // untouched without holding back the items behind them
         let row = sqlx::query(
             r#"
             DELETE FROM
@@ -305,7 +678,21 @@ impl<T: QueueMessage> voyager_vm::Queue<T> for PgQueue<T> {
                   id
                 FROM
                   queue
+                WHERE
+                  (
+                    due_at IS NULL
+                    OR due_at <= EXTRACT(EPOCH FROM now())::BIGINT
+                  )
+                  AND (
+                    $1::TEXT IS NULL
+                    OR shard_key IS NULL
+                    OR shard_key IN (
+                      SELECT shard_key FROM shard_leases WHERE owner = $1 AND expires_at > now()
+                    )
+                  )
+                  AND NOT (pause_keys && $2::TEXT[])
                 ORDER BY
+                  priority DESC,
                   id ASC
                 FOR UPDATE
                   SKIP LOCKED
@@ -313,22 +700,31 @@ impl<T: QueueMessage> voyager_vm::Queue<T> for PgQueue<T> {
             RETURNING
               id,
               parents,
+              correlation_id,
               item::text,
-              created_at
+              created_at,
+              version,
+              shard_key
             "#,
         )
+        .bind(&self.shard_owner)
+        .bind(paused)
         .try_map(|x| Record::from_row(&x))
         .fetch_optional(tx.as_mut())
         .await?;
 
+        let row = row
+            .map(|row| row.decode::<T>().map(|op| (row, op)))
+            .transpose()?;
+
         match row {
-            Some(row) => {
-                let span = info_span!("processing item", id = row.id);
+            Some((row, op)) => {
+                let correlation_id = row.correlation_id();
+                let parent_shard_key = row.shard_key.clone();
 
-                trace!(%row.item);
+                let span = info_span!("processing item", id = row.id, correlation_id);
 
-                // really don't feel like defining a new error type right now
-                let op = de(&row.item).map_err(|e| sqlx::Error::Decode(Box::new(e)))?;
+                trace!(%row.item);
 
                 let timer = ITEM_PROCESSING_DURATION.start_timer();
                 let (r, res) = f(op).instrument(span).await;
@@ -340,8 +736,8 @@ impl<T: QueueMessage> voyager_vm::Queue<T> for PgQueue<T> {
                         sqlx::query(
                             r#"
                             INSERT INTO
-                            failed (id, parents, item,      created_at, message)
-                            VALUES ($1, $2,      $3::JSONB, $4,         $5     )
+                            failed (id, parents, item,      created_at, message, correlation_id, version)
+                            VALUES ($1, $2,      $3::JSONB, $4,         $5,      $6,             $7     )
                             "#,
                         )
                         .bind(row.id)
@@ -349,6 +745,8 @@ impl<T: QueueMessage> voyager_vm::Queue<T> for PgQueue<T> {
                         .bind(row.item)
                         .bind(row.created_at)
                         .bind(error)
+                        .bind(row.correlation_id)
+                        .bind(row.version)
                         .execute(tx.as_mut())
                         .await?;
                         tx.commit().await?;
@@ -359,14 +757,16 @@ impl<T: QueueMessage> voyager_vm::Queue<T> for PgQueue<T> {
                             sqlx::query(
                                 "
                                 INSERT INTO
-                                done   (id, parents, item,      created_at)
-                                VALUES ($1, $2,      $3::JSONB, $4        )
+                                done   (id, parents, item,      created_at, correlation_id, version)
+                                VALUES ($1, $2,      $3::JSONB, $4,         $5,             $6     )
                                 ",
                             )
                             .bind(row.id)
                             .bind(row.parents)
                             .bind(row.item)
                             .bind(row.created_at)
+                            .bind(row.correlation_id)
+                            .bind(row.version)
                             .execute(tx.as_mut())
                             .await?;
 
@@ -374,32 +774,81 @@ impl<T: QueueMessage> voyager_vm::Queue<T> for PgQueue<T> {
                                 break 'block;
                             }
 
-                            let (optimize, ready): (Vec<_>, Vec<_>) = ops
-                                .into_iter()
-                                .flat_map(Op::normalize)
-                                .partition_map(|op| match filter.check_interest(&op) {
-                                    FilterResult::Interest(tag) => Either::Left((op, tag)),
-                                    FilterResult::NoInterest => Either::Right(op),
-                                });
+                            // the processed item has already been deleted from the queue in this
+                            // transaction, so it may be processed into an identical op
+                            let (optimize, ready): (Vec<_>, Vec<_>) =
+                                dedup(ops.into_iter().flat_map(Op::normalize))
+                                    .into_iter()
+                                    .partition_map(|(op, key)| match filter.check_interest(&op) {
+                                        FilterResult::Interest(tag) => Either::Left((op, tag, key)),
+                                        FilterResult::NoInterest => Either::Right((op, key)),
+                                    });
 
                             sqlx::query(
                                 "
-                                INSERT INTO queue (item)
-                                SELECT * FROM UNNEST($1::JSONB[])
+                                INSERT INTO queue (item, idempotency_key, due_at, correlation_id, version, shard_key, pause_keys, priority)
+                                SELECT t.item, t.idempotency_key, t.due_at, $4::BIGINT, $5::INTEGER, t.shard_key, ARRAY(SELECT jsonb_array_elements_text(t.pause_keys)), t.priority FROM UNNEST($1::JSONB[], $2::TEXT[], $3::BIGINT[], $6::TEXT[], $7::JSONB[], $8::INTEGER[]) AS t(item, idempotency_key, due_at, shard_key, pause_keys, priority)
+                                ON CONFLICT (idempotency_key) WHERE idempotency_key IS NOT NULL DO NOTHING
                                 ",
                             )
-                            .bind(ready.into_iter().map(Json).collect::<Vec<_>>())
+                            .bind(ready.iter().map(|(op, _)| Json(op)).collect::<Vec<_>>())
+                            .bind(ready.iter().map(|(_, key)| key.clone()).collect::<Vec<_>>())
+                            .bind(ready.iter().map(|(op, _)| due_at(op)).collect::<Vec<_>>())
+                            .bind(correlation_id)
+                            .bind(wire_version::<T>())
+                            .bind(
+                                ready
+                                    .iter()
+                                    .map(|(op, _)| {
+                                        T::shard_key(op).or_else(|| parent_shard_key.clone())
+                                    })
+                                    .collect::<Vec<_>>(),
+                            )
+                            .bind(
+                                ready
+                                    .iter()
+                                    .map(|(op, _)| Json(T::pause_keys(op)))
+                                    .collect::<Vec<_>>(),
+                            )
+                            .bind(
+                                ready
+                                    .iter()
+                                    .map(|(op, _)| i32::from(op.priority()))
+                                    .collect::<Vec<_>>(),
+                            )
                             .execute(tx.as_mut())
                             .await?;
 
                             sqlx::query(
                                 "
-                                INSERT INTO optimize (item, tag)
-                                SELECT * FROM UNNEST($1::JSONB[], $2::TEXT[])
+                                INSERT INTO optimize (item, tag, idempotency_key, correlation_id, version, shard_key)
+                                SELECT t.item, t.tag, t.idempotency_key, $4::BIGINT, $5::INTEGER, t.shard_key FROM UNNEST($1::JSONB[], $2::TEXT[], $3::TEXT[], $6::TEXT[]) AS t(item, tag, idempotency_key, shard_key)
+                                ON CONFLICT (idempotency_key) WHERE idempotency_key IS NOT NULL DO NOTHING
                                 ",
                             )
-                            .bind(optimize.iter().map(|(op, _)| Json(op)).collect::<Vec<_>>())
-                            .bind(optimize.iter().map(|(_, tag)| *tag).collect::<Vec<_>>())
+                            .bind(
+                                optimize
+                                    .iter()
+                                    .map(|(op, _, _)| Json(op))
+                                    .collect::<Vec<_>>(),
+                            )
+                            .bind(optimize.iter().map(|(_, tag, _)| *tag).collect::<Vec<_>>())
+                            .bind(
+                                optimize
+                                    .iter()
+                                    .map(|(_, _, key)| key.clone())
+                                    .collect::<Vec<_>>(),
+                            )
+                            .bind(correlation_id)
+                            .bind(wire_version::<T>())
+                            .bind(
+                                optimize
+                                    .iter()
+                                    .map(|(op, _, _)| {
+                                        T::shard_key(op).or_else(|| parent_shard_key.clone())
+                                    })
+                                    .collect::<Vec<_>>(),
+                            )
                             .execute(tx.as_mut())
                             .await?;
                         }
@@ -455,8 +904,11 @@ impl<T: QueueMessage> voyager_vm::Queue<T> for PgQueue<T> {
             RETURNING
               id,
               parents,
+              correlation_id,
               item::text,
-              created_at
+              created_at,
+              version,
+              shard_key
             "#,
         )
         .bind(tag)
@@ -465,20 +917,22 @@ impl<T: QueueMessage> voyager_vm::Queue<T> for PgQueue<T> {
         .await
         .map_err(Either::Left)?;
 
+        OPTIMIZE_DEPTH
+            .with_label_values(&[tag])
+            .set(msgs.len() as i64);
+
         if msgs.is_empty() {
             trace!("optimizer queue is empty");
             tokio::time::sleep(Duration::from_millis(100)).await;
             return Ok(());
         }
 
+        let correlation_ids = msgs.iter().map(Record::correlation_id).collect::<Vec<_>>();
+        let shard_keys = msgs.iter().map(|r| r.shard_key.clone()).collect::<Vec<_>>();
+
         let (ids, msgs) = msgs
             .into_iter()
-            .map(|r| {
-                Ok((
-                    r.id,
-                    de(&r.item).map_err(|e| sqlx::Error::Decode(Box::new(e)))?,
-                ))
-            })
+            .map(|r| Ok((r.id, r.decode()?)))
             .collect::<Result<(Vec<_>, Vec<_>), sqlx::Error>>()
             .map_err(Either::Left)?;
 
@@ -515,49 +969,91 @@ impl<T: QueueMessage> voyager_vm::Queue<T> for PgQueue<T> {
                 .collect::<Vec<_>>()
         };
 
+        // ops optimized out of multiple items are attributed to the tree of the first one
+        let get_correlation_id =
+            |parent_idxs: &[usize]| parent_idxs.first().map(|&idx| correlation_ids[idx]);
+
+        // likewise, ops without a shard key of their own inherit the one of the first item
+        let get_shard_key = |new_msg: &Op<T>, parent_idxs: &[usize]| {
+            T::shard_key(new_msg)
+                .or_else(|| parent_idxs.first().and_then(|&idx| shard_keys[idx].clone()))
+        };
+
         for (parent_idxs, new_msg, tag) in optimize_further {
             let parents = get_parent_ids(&parent_idxs);
             trace!(parent_idxs = ?&parent_idxs, parents = ?&parents);
 
+            let idempotency_key = T::idempotency_key(&new_msg);
+            let shard_key = get_shard_key(&new_msg, &parent_idxs);
+
             let new_row = sqlx::query(
                 "
-                INSERT INTO optimize (item, parents, tag)
-                VALUES
-                    ($1::JSONB, $2, $3)
+                INSERT INTO optimize (item, parents, tag, idempotency_key, correlation_id, version, shard_key)
+                VALUES ($1::JSONB, $2, $3, $4, $5, $6, $7)
+                ON CONFLICT (idempotency_key) WHERE idempotency_key IS NOT NULL DO NOTHING
                 RETURNING id
                 ",
             )
             .bind(Json(new_msg))
             .bind(&parents)
             .bind(tag)
+            .bind(&idempotency_key)
+            .bind(get_correlation_id(&parent_idxs))
+            .bind(wire_version::<T>())
+            .bind(shard_key)
             .try_map(|row| Id::from_row(&row))
-            .fetch_one(tx.as_mut())
+            .fetch_optional(tx.as_mut())
             .await
             .map_err(Either::Left)?;
 
-            debug!(id = new_row.id, "inserted new optimizer message");
+            match new_row {
+                Some(new_row) => debug!(id = new_row.id, "inserted new optimizer message"),
+                None => debug!(
+                    ?idempotency_key,
+                    "identical op is already in flight, skipping"
+                ),
+            }
         }
 
         for (parent_idxs, new_msg) in ready {
             let parents = get_parent_ids(&parent_idxs);
             trace!(parent_idxs = ?&parent_idxs, parents = ?&parents);
 
+            let idempotency_key = T::idempotency_key(&new_msg);
+            let new_msg_due_at = due_at(&new_msg);
+            let shard_key = get_shard_key(&new_msg, &parent_idxs);
+            let pause_keys = T::pause_keys(&new_msg);
+            let priority = i32::from(new_msg.priority());
+
             let new_row = sqlx::query(
                 "
-                INSERT INTO queue (item, parents)
-                VALUES
-                    ($1::JSONB, $2)
+                INSERT INTO queue (item, parents, idempotency_key, correlation_id, due_at, version, shard_key, pause_keys, priority)
+                VALUES ($1::JSONB, $2, $3, $4, $5, $6, $7, $8, $9)
+                ON CONFLICT (idempotency_key) WHERE idempotency_key IS NOT NULL DO NOTHING
                 RETURNING id
                 ",
             )
             .bind(Json(new_msg))
             .bind(&parents)
+            .bind(&idempotency_key)
+            .bind(get_correlation_id(&parent_idxs))
+            .bind(new_msg_due_at)
+            .bind(wire_version::<T>())
+            .bind(shard_key)
+            .bind(pause_keys)
+            .bind(priority)
             .try_map(|x| Id::from_row(&x))
-            .fetch_one(tx.as_mut())
+            .fetch_optional(tx.as_mut())
             .await
             .map_err(Either::Left)?;
 
-            debug!(id = new_row.id, "inserted new message");
+            match new_row {
+                Some(new_row) => debug!(id = new_row.id, "inserted new message"),
+                None => debug!(
+                    ?idempotency_key,
+                    "identical op is already in flight, skipping"
+                ),
+            }
         }
 
         tx.commit().await.map_err(Either::Left)?;
@@ -573,12 +1069,30 @@ pub enum EnqueueStatus {
     Optimize,
 }
 
-fn de<T: DeserializeOwned>(s: &str) -> Result<T, serde_json::Error> {
-    let mut deserializer = serde_json::Deserializer::from_str(s);
-    deserializer.disable_recursion_limit();
-    // let deserializer = serde_stacker::Deserializer::new(&mut deserializer);
-    let json = T::deserialize(&mut deserializer)?;
-    Ok(json)
+/// Remove ops that are identical to an earlier op in `ops`, pairing each remaining op with its
+/// [`QueueMessage::idempotency_key`]. Ops that are identical to an op already in the database are
+/// skipped on insertion by the unique index on the key.
+fn dedup<T: QueueMessage>(ops: impl IntoIterator<Item = Op<T>>) -> Vec<(Op<T>, Option<String>)> {
+    let mut seen = HashSet::new();
+
+    ops.into_iter()
+        .map(|op| {
+            let key = T::idempotency_key(&op);
+            (op, key)
+        })
+        .filter(|(_, key)| key.as_ref().map_or(true, |key| seen.insert(key.clone())))
+        .collect()
+}
+
+/// [`Op::due_at`] as stored in the `due_at` column, which is a (signed) `BIGINT`.
+fn due_at<T: QueueMessage>(op: &Op<T>) -> Option<i64> {
+    op.due_at()
+        .map(|due_at| i64::try_from(due_at).unwrap_or(i64::MAX))
+}
+
+/// [`QueueMessage::WIRE_VERSION`] as stored in the `version` column, which is a (signed) `INTEGER`.
+fn wire_version<T: QueueMessage>() -> i32 {
+    i32::try_from(T::WIRE_VERSION).expect("wire version is too large")
 }
 
 pub trait MapExt<K, V> {
@@ -1,6 +1,12 @@
-use std::sync::LazyLock;
+use std::{future::Future, pin::pin, sync::LazyLock, time::Duration};
 
-use prometheus::{register_histogram, Histogram};
+use futures_util::future;
+use prometheus::{register_histogram, register_int_gauge_vec, Histogram, IntGaugeVec};
+use sqlx::{PgPool, Row};
+use tracing::{error, info_span, Instrument};
+
+/// How often [`QUEUE_DEPTH`] is refreshed.
+const DEPTH_REFRESH_INTERVAL: Duration = Duration::from_secs(15);
 
 pub static ITEM_PROCESSING_DURATION: LazyLock<Histogram> = LazyLock::new(|| {
     register_histogram!(
@@ -28,3 +34,66 @@ pub static OPTIMIZE_ITEM_COUNT: LazyLock<Histogram> = LazyLock::new(|| {
     )
     .unwrap()
 });
+
+pub static QUEUE_DEPTH: LazyLock<IntGaugeVec> = LazyLock::new(|| {
+    register_int_gauge_vec!(
+        "pg_queue_depth",
+        "The estimated amount of items in each of the queue, optimize, and failed tables.",
+        &["table"],
+    )
+    .unwrap()
+});
+
+pub static OPTIMIZE_DEPTH: LazyLock<IntGaugeVec> = LazyLock::new(|| {
+    register_int_gauge_vec!(
+        "pg_queue_optimize_depth",
+        "The amount of items that were waiting to be optimized (i.e. aggregated) at the start of the last optimize pass, by optimizer tag.",
+        &["tag"],
+    )
+    .unwrap()
+});
+
+/// Refresh [`QUEUE_DEPTH`] until `shutdown` resolves.
+pub(crate) async fn record_depth(pool: PgPool, shutdown: impl Future<Output = ()>) {
+    let record = async move {
+        loop {
+            if let Err(err) = refresh_depth(&pool).await {
+                error!(%err, "error refreshing queue depth");
+            }
+
+            tokio::time::sleep(DEPTH_REFRESH_INTERVAL).await;
+        }
+    };
+
+    future::select(
+        pin!(record.instrument(info_span!("queue_depth"))),
+        pin!(shutdown),
+    )
+    .await;
+}
+
+async fn refresh_depth(pool: &PgPool) -> sqlx::Result<()> {
+    // use the planner's row estimates, since counting the rows would scan each table on every
+    // refresh. tables that have never been vacuumed or analyzed have an estimate of -1.
+    let rows = sqlx::query(
+        "
+        SELECT
+          relname::TEXT AS name,
+          greatest(reltuples, 0)::BIGINT AS count
+        FROM
+          pg_class
+        WHERE
+          oid IN ('queue'::regclass, 'optimize'::regclass, 'failed'::regclass)
+        ",
+    )
+    .fetch_all(pool)
+    .await?;
+
+    for row in rows {
+        QUEUE_DEPTH
+            .with_label_values(&[row.try_get::<&str, _>("name")?])
+            .set(row.try_get("count")?);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,166 @@
+//! Sharding of ready items between multiple voyager instances sharing one database.
+//!
+//! Every item is stored with its [`QueueMessage::shard_key`], or the shard key of the item it was
+//! produced from if it doesn't have one of its own. An instance only processes items whose shard
+//! key it holds a lease on (items without a shard key are processed by any instance), such that
+//! all of the messages for a chain pair are submitted by the same instance.
+//!
+//! Leases are stored in the `shard_leases` table and are renewed periodically. Each instance
+//! claims at most its fair share of the shard keys currently in the queue, based on the amount of
+//! instances that have sent a heartbeat within the last lease duration. If an instance stops, its
+//! leases expire and are picked up by the remaining instances.
+//!
+//! [`QueueMessage::shard_key`]: voyager_vm::QueueMessage::shard_key
+
+use std::time::Duration;
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use sqlx::{PgPool, Row};
+use tracing::{debug, error, info, info_span, Instrument};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct ShardingConfig {
+    /// The id of this instance, which must be unique between all instances sharing the database.
+    pub instance_id: String,
+    /// How long (in seconds) a lease is valid for if it is not renewed. Leases are renewed three
+    /// times per lease duration, so this is also roughly how long it takes for new shard keys to be
+    /// claimed.
+    #[serde(default = "default_lease_duration_seconds")]
+    pub lease_duration_seconds: u64,
+}
+
+fn default_lease_duration_seconds() -> u64 {
+    30
+}
+
+/// Renew the leases of `config.instance_id` forever.
+pub(crate) async fn maintain_leases(pool: PgPool, config: ShardingConfig) {
+    let span = info_span!("shard_leases", instance_id = %config.instance_id);
+
+    async move {
+        loop {
+            if let Err(err) = renew_leases(&pool, &config).await {
+                error!(%err, "error renewing shard leases");
+            }
+
+            tokio::time::sleep(Duration::from_secs(config.lease_duration_seconds) / 3).await;
+        }
+    }
+    .instrument(span)
+    .await
+}
+
+async fn renew_leases(pool: &PgPool, config: &ShardingConfig) -> sqlx::Result<()> {
+    let lease_duration = config.lease_duration_seconds as f64;
+
+    let mut tx = pool.begin().await?;
+
+    sqlx::query(
+        "
+        INSERT INTO shard_instances (id, last_seen) VALUES ($1, now())
+        ON CONFLICT (id) DO UPDATE SET last_seen = now()
+        ",
+    )
+    .bind(&config.instance_id)
+    .execute(tx.as_mut())
+    .await?;
+
+    sqlx::query(
+        "
+        DELETE FROM shard_instances WHERE last_seen < now() - make_interval(secs => $1)
+        ",
+    )
+    .bind(lease_duration)
+    .execute(tx.as_mut())
+    .await?;
+
+    let held = sqlx::query(
+        "
+        UPDATE shard_leases SET expires_at = now() + make_interval(secs => $2)
+        WHERE owner = $1
+        ",
+    )
+    .bind(&config.instance_id)
+    .bind(lease_duration)
+    .execute(tx.as_mut())
+    .await?
+    .rows_affected();
+
+    let row = sqlx::query(
+        "
+        SELECT
+            (SELECT COUNT(*) FROM shard_instances) AS instances,
+            (SELECT COUNT(*) FROM (
+                SELECT shard_key FROM queue WHERE shard_key IS NOT NULL
+                UNION
+                SELECT shard_key FROM shard_leases WHERE expires_at > now()
+            ) keys) AS keys
+        ",
+    )
+    .fetch_one(tx.as_mut())
+    .await?;
+
+    let instances = u64::try_from(row.get::<i64, _>("instances"))
+        .unwrap_or(0)
+        .max(1);
+    let keys = u64::try_from(row.get::<i64, _>("keys")).unwrap_or(0);
+
+    let fair_share = keys.div_ceil(instances);
+
+    if held > fair_share {
+        let released = sqlx::query(
+            "
+            DELETE FROM shard_leases WHERE shard_key IN (
+                SELECT shard_key FROM shard_leases WHERE owner = $1 LIMIT $2
+            )
+            RETURNING shard_key
+            ",
+        )
+        .bind(&config.instance_id)
+        .bind(i64::try_from(held - fair_share).unwrap_or(i64::MAX))
+        .fetch_all(tx.as_mut())
+        .await?;
+
+        for row in released {
+            info!(
+                shard_key = row.get::<String, _>("shard_key"),
+                "released shard"
+            );
+        }
+    } else if held < fair_share {
+        let claimed = sqlx::query(
+            "
+            INSERT INTO shard_leases (shard_key, owner, expires_at)
+            SELECT k.shard_key, $1, now() + make_interval(secs => $2)
+            FROM (SELECT DISTINCT shard_key FROM queue WHERE shard_key IS NOT NULL) k
+            LEFT JOIN shard_leases l ON l.shard_key = k.shard_key
+            WHERE l.shard_key IS NULL OR l.expires_at <= now()
+            LIMIT $3
+            ON CONFLICT (shard_key) DO UPDATE
+            SET owner = EXCLUDED.owner, expires_at = EXCLUDED.expires_at
+            WHERE shard_leases.expires_at <= now()
+            RETURNING shard_key
+            ",
+        )
+        .bind(&config.instance_id)
+        .bind(lease_duration)
+        .bind(i64::try_from(fair_share - held).unwrap_or(i64::MAX))
+        .fetch_all(tx.as_mut())
+        .await?;
+
+        for row in claimed {
+            info!(
+                shard_key = row.get::<String, _>("shard_key"),
+                "claimed shard"
+            );
+        }
+    }
+
+    tx.commit().await?;
+
+    debug!(%held, %fair_share, %instances, %keys, "renewed shard leases");
+
+    Ok(())
+}
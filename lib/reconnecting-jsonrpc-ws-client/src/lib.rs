@@ -11,7 +11,7 @@ use jsonrpsee::core::{
 };
 use tokio::time::sleep;
 use tokio_util::sync::CancellationToken;
-use tracing::{debug, debug_span, error, instrument, trace, Instrument};
+use tracing::{debug, debug_span, error, instrument, trace, warn, Instrument};
 
 #[derive(Debug, Clone)]
 pub struct Client {
@@ -58,7 +58,7 @@ impl Client {
 
                                     tokio::select! {
                                         _ = client.on_disconnect() => {
-                                            debug!("client disconnected");
+                                            warn!(%total_reconnects, "client disconnected, reconnecting");
 
                                             maybe_client.store(None);
 
@@ -161,6 +161,37 @@ impl<'de, E: Encoding> Deserialize<'de> for Bytes<E> {
     }
 }
 
+#[cfg(feature = "schemars")]
+impl ::schemars::JsonSchema for Bytes<HexPrefixed> {
+    fn schema_name() -> String {
+        "Bytes".to_owned()
+    }
+
+    fn schema_id() -> alloc::borrow::Cow<'static, str> {
+        alloc::borrow::Cow::Borrowed(concat!(module_path!(), "::Bytes"))
+    }
+
+    fn json_schema(_: &mut schemars::gen::SchemaGenerator) -> schemars::schema::Schema {
+        use schemars::schema::{
+            InstanceType, Metadata, SchemaObject, SingleOrVec, StringValidation,
+        };
+
+        SchemaObject {
+            metadata: Some(Box::new(Metadata {
+                description: Some("Arbitrary bytes, encoded as 0x-prefixed hex.".to_owned()),
+                ..Default::default()
+            })),
+            instance_type: Some(SingleOrVec::Single(Box::new(InstanceType::String))),
+            string: Some(Box::new(StringValidation {
+                pattern: Some("^0x([0-9a-fA-F]{2})*$".to_owned()),
+                ..Default::default()
+            })),
+            ..Default::default()
+        }
+        .into()
+    }
+}
+
 impl<E: Encoding> FromStr for Bytes<E> {
     type Err = E::Error;
 
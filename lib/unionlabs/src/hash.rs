@@ -434,6 +434,37 @@ pub mod hash_v2 {
         }
     }
 
+    #[cfg(feature = "schemars")]
+    impl<const BYTES: usize> ::schemars::JsonSchema for Hash<BYTES, HexPrefixed> {
+        fn schema_name() -> String {
+            format!("H{}", BYTES * 8)
+        }
+
+        fn schema_id() -> alloc::borrow::Cow<'static, str> {
+            alloc::borrow::Cow::Owned(format!("{}::H{}", module_path!(), BYTES * 8))
+        }
+
+        fn json_schema(_: &mut schemars::gen::SchemaGenerator) -> schemars::schema::Schema {
+            use schemars::schema::{
+                InstanceType, Metadata, SchemaObject, SingleOrVec, StringValidation,
+            };
+
+            SchemaObject {
+                metadata: Some(Box::new(Metadata {
+                    description: Some(format!("{BYTES} bytes, encoded as 0x-prefixed hex.")),
+                    ..Default::default()
+                })),
+                instance_type: Some(SingleOrVec::Single(Box::new(InstanceType::String))),
+                string: Some(Box::new(StringValidation {
+                    pattern: Some(format!("^0x[0-9a-fA-F]{{{}}}$", BYTES * 2)),
+                    ..Default::default()
+                })),
+                ..Default::default()
+            }
+            .into()
+        }
+    }
+
     impl<const BYTES: usize, E: Encoding> FromStr for Hash<BYTES, E> {
         type Err = E::Error;
 
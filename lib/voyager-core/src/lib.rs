@@ -248,6 +248,7 @@ pub struct ChainId;
 ///   {"checksum": "0x..."}))`
 /// - cometbls client on scroll, tracking union: `(ibc-solidity, cometbls)`
 #[model]
+#[derive(schemars::JsonSchema)]
 pub struct ClientInfo {
     pub client_type: ClientType,
     pub ibc_interface: IbcInterface,
@@ -0,0 +1,212 @@
+//! Estimation of the block times of chains.
+//!
+//! Waiting for a chain to reach a height (or a timestamp) is done by repeatedly querying the chain
+//! and deferring in between. Deferring for a fixed amount of time either polls chains with long
+//! block times far more often than necessary, or adds latency on chains with short block times.
+//! Instead, [`BlockTimes`] estimates the average block time of every chain from the heights
+//! observed while waiting, and waits are deferred for the estimated time until the expected block
+//! is produced (see [`BlockTimes::defer_blocks`]).
+//!
+//! The latest and finalized heights of a chain are tracked separately, since the finalized height
+//! lags behind (and often moves in larger steps than) the latest height. The estimate of the latest
+//! heights is preferred if both are known. Until a chain has an estimate, the block time declared
+//! in its [`ChainCapabilities`](crate::module::ChainCapabilities) is used, if any.
+
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{Arc, LazyLock, Mutex},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use prometheus::{register_gauge_vec, GaugeVec};
+use voyager_vm::{defer, now, Op};
+
+use crate::{core::ChainId, VoyagerMessage};
+
+/// The block time assumed for chains that have neither an estimate nor a declared block time, in
+/// milliseconds.
+pub const DEFAULT_BLOCK_TIME_MILLIS: u64 = 1000;
+
+/// The maximum amount of seconds that [`BlockTimes::defer_blocks`] defers for, such that a bad
+/// estimate (or a halted chain) doesn't cause waits to be deferred for an unreasonable amount of
+/// time.
+pub const MAX_DEFER_SECONDS: u64 = 60;
+
+/// The amount of distinct heights of a chain that the estimate is calculated over.
+const MAX_SAMPLES: usize = 32;
+
+pub static BLOCK_TIME: LazyLock<GaugeVec> = LazyLock::new(|| {
+    register_gauge_vec!(
+        "voyager_block_time_seconds",
+        "The estimated average block time of each chain.",
+        &["chain_id", "finalized"],
+    )
+    .unwrap()
+});
+
+#[derive(Debug, Clone, Default)]
+pub struct BlockTimes {
+    inner: Arc<Mutex<HashMap<(ChainId, bool), VecDeque<Sample>>>>,
+    /// The declared average block time of each chain, in milliseconds.
+    declared: Arc<HashMap<ChainId, u64>>,
+}
+
+/// The first time a height of a chain was observed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Sample {
+    height: u64,
+    observed_at_millis: u64,
+}
+
+impl BlockTimes {
+    /// Use the declared average block times (in milliseconds) of chains as the fallback for chains
+    /// that have no estimate yet.
+    pub fn new(declared: impl IntoIterator<Item = (ChainId, u64)>) -> Self {
+        Self {
+            inner: Default::default(),
+            declared: Arc::new(declared.into_iter().collect()),
+        }
+    }
+
+    /// Record that the latest (or latest finalized) height of `chain_id` is `height`.
+    pub fn observe(&self, chain_id: &ChainId, height: u64, finalized: bool) {
+        self.observe_at(chain_id, height, finalized, now_millis());
+    }
+
+    fn observe_at(&self, chain_id: &ChainId, height: u64, finalized: bool, now_millis: u64) {
+        let mut chains = self.inner.lock().expect("mutex is poisoned");
+
+        let samples = chains.entry((chain_id.clone(), finalized)).or_default();
+
+        // only the first observation of every height is recorded; lower heights are from a lagging
+        // rpc and are ignored
+        if samples.back().is_some_and(|last| height <= last.height) {
+            return;
+        }
+
+        samples.push_back(Sample {
+            height,
+            observed_at_millis: now_millis,
+        });
+
+        if samples.len() > MAX_SAMPLES {
+            samples.pop_front();
+        }
+
+        if let Some(block_time_millis) = estimate(samples) {
+            #[allow(clippy::cast_precision_loss)]
+            BLOCK_TIME
+                .with_label_values(&[chain_id.as_str(), &finalized.to_string()])
+                .set(block_time_millis as f64 / 1000.0);
+        }
+    }
+
+    /// The estimated average block time of `chain_id` in milliseconds, if enough heights have been
+    /// observed to estimate it.
+    pub fn block_time_millis(&self, chain_id: &ChainId) -> Option<u64> {
+        let chains = self.inner.lock().expect("mutex is poisoned");
+
+        [false, true]
+            .into_iter()
+            .find_map(|finalized| estimate(chains.get(&(chain_id.clone(), finalized))?))
+    }
+
+    /// The estimated amount of seconds until `blocks` more blocks are produced on `chain_id`. This
+    /// is always at least 1 and at most [`MAX_DEFER_SECONDS`].
+    pub fn seconds_for_blocks(&self, chain_id: &ChainId, blocks: u64) -> u64 {
+        self.block_time_millis(chain_id)
+            .or_else(|| self.declared.get(chain_id).copied())
+            .unwrap_or(DEFAULT_BLOCK_TIME_MILLIS)
+            .saturating_mul(blocks)
+            .div_ceil(1000)
+            .clamp(1, MAX_DEFER_SECONDS)
+    }
+
+    /// Defer until `blocks` more blocks are expected to be produced on `chain_id`.
+    #[must_use = "constructing an instruction has no effect"]
+    pub fn defer_blocks(&self, chain_id: &ChainId, blocks: u64) -> Op<VoyagerMessage> {
+        defer(now() + self.seconds_for_blocks(chain_id, blocks))
+    }
+}
+
+/// The average time between the heights in `samples`, in milliseconds.
+fn estimate(samples: &VecDeque<Sample>) -> Option<u64> {
+    let (first, last) = (samples.front()?, samples.back()?);
+
+    let blocks = last.height.checked_sub(first.height).filter(|b| *b > 0)?;
+
+    Some(
+        last.observed_at_millis
+            .saturating_sub(first.observed_at_millis)
+            / blocks,
+    )
+}
+
+fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("system time is after the unix epoch")
+        .as_millis()
+        .try_into()
+        .expect("how many milliseconds can there be")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn block_time_is_estimated_from_new_heights() {
+        let block_times = BlockTimes::default();
+        let chain_id = ChainId::new("chain");
+
+        assert_eq!(block_times.block_time_millis(&chain_id), None);
+        assert_eq!(block_times.seconds_for_blocks(&chain_id, 3), 3);
+
+        block_times.observe_at(&chain_id, 10, false, 0);
+        // polled again before the next block
+        block_times.observe_at(&chain_id, 10, false, 2_000);
+        block_times.observe_at(&chain_id, 11, false, 6_000);
+        // lagging rpc
+        block_times.observe_at(&chain_id, 9, false, 8_000);
+        block_times.observe_at(&chain_id, 13, false, 18_000);
+
+        assert_eq!(block_times.block_time_millis(&chain_id), Some(6_000));
+        assert_eq!(block_times.seconds_for_blocks(&chain_id, 2), 12);
+        assert_eq!(
+            block_times.seconds_for_blocks(&chain_id, 100),
+            MAX_DEFER_SECONDS
+        );
+        assert_eq!(block_times.seconds_for_blocks(&chain_id, 0), 1);
+    }
+
+    #[test]
+    fn latest_heights_are_preferred() {
+        let block_times = BlockTimes::default();
+        let chain_id = ChainId::new("chain");
+
+        block_times.observe_at(&chain_id, 32, true, 0);
+        block_times.observe_at(&chain_id, 64, true, 32 * 12_000);
+
+        assert_eq!(block_times.block_time_millis(&chain_id), Some(12_000));
+
+        block_times.observe_at(&chain_id, 100, false, 0);
+        block_times.observe_at(&chain_id, 110, false, 10 * 11_000);
+
+        assert_eq!(block_times.block_time_millis(&chain_id), Some(11_000));
+    }
+
+    #[test]
+    fn declared_block_time_is_used_until_estimated() {
+        let chain_id = ChainId::new("chain");
+        let block_times = BlockTimes::new([(chain_id.clone(), 12_000)]);
+
+        assert_eq!(block_times.seconds_for_blocks(&chain_id, 2), 24);
+        assert_eq!(block_times.seconds_for_blocks(&ChainId::new("other"), 2), 2);
+
+        block_times.observe_at(&chain_id, 10, false, 0);
+        block_times.observe_at(&chain_id, 12, false, 4_000);
+
+        assert_eq!(block_times.seconds_for_blocks(&chain_id, 2), 4);
+    }
+}
@@ -1,27 +1,48 @@
+use std::{num::NonZeroU64, time::Duration};
+
 use enumorph::Enumorph;
 use macros::model;
-use serde::de::DeserializeOwned;
-use tracing::{debug, error, info};
-use unionlabs::{ibc::core::client::height::Height, traits::Member};
+use schemars::JsonSchema;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use tracing::{debug, error, field::Empty, info, info_span, Instrument, Span};
+use unionlabs::{
+    hash::H256,
+    ibc::core::client::height::Height,
+    id::{ChannelId, PortId},
+    traits::Member,
+};
 use voyager_core::{IbcSpecId, QueryHeight};
-use voyager_vm::{call, defer, noop, now, seq, CallT, Op, QueueError};
+use voyager_vm::{call, noop, now, seq, CallT, Op, QueueError};
 
 use crate::{
-    core::ChainId, error_object_to_queue_error, json_rpc_error_to_queue_error,
-    module::PluginClient, Context, PluginMessage, RawClientId, VoyagerMessage,
+    core::ChainId,
+    error_object_to_queue_error,
+    metrics::{
+        call_labels, error_kind, CALL_ERROR_COUNT, CALL_PROCESSED_COUNT, CALL_PROCESSING_DURATION,
+        CALL_TIMEOUT_COUNT, FETCH_DURATION, SUBMISSION_ERROR_COUNT, WAIT_DEADLINE_EXCEEDED_COUNT,
+    },
+    module::PluginClient,
+    rpc::json_rpc_error_to_error_object,
+    Context, PluginMessage, RawClientId, VoyagerMessage,
 };
 
 #[model]
-#[derive(Enumorph)]
+#[derive(JsonSchema, Enumorph)]
 pub enum Call {
     FetchBlocks(FetchBlocks),
+    FetchBlockRange(FetchBlockRange),
+    FetchPacketEvents(FetchPacketEvents),
 
     FetchUpdateHeaders(FetchUpdateHeaders),
 
     // MakeMsgCreateClient(MakeMsgCreateClient),
     WaitForHeight(WaitForHeight),
+    WaitForFinality(WaitForFinality),
     WaitForTimestamp(WaitForTimestamp),
     WaitForTrustedHeight(WaitForTrustedHeight),
+    WaitForTxInclusion(WaitForTxInclusion),
+
+    RecordCheckpoint(RecordCheckpoint),
 
     Plugin(PluginMessage),
 }
@@ -38,13 +59,48 @@ impl Call {
     }
 }
 
+/// Fetch all blocks on a chain in the range `from_height..=to_height`.
+///
+/// Unlike [`FetchBlocks`], this does not unfold into an infinite stream,
+/// and is used to backfill events that were missed (for example, when
+/// resuming from a checkpoint after a restart). If it is not handled by a
+/// plugin, this will return with a fatal error.
 #[model]
+#[derive(JsonSchema)]
 pub struct FetchBlockRange {
     pub chain_id: ChainId,
     pub from_height: Height,
     pub to_height: Height,
 }
 
+/// Fetch the events for the packets with the provided sequences on a channel.
+///
+/// This is used to relay packets that were missed by the event source (see
+/// [`clear_packets`](crate::clear_packets)). The events must be emitted as
+/// [`ChainEvent`](crate::data::ChainEvent)s, exactly as they would have been
+/// when fetching the blocks they were included in. If it is not handled by a
+/// plugin, this will return with a fatal error.
+#[model]
+#[derive(JsonSchema)]
+pub struct FetchPacketEvents {
+    pub chain_id: ChainId,
+    pub kind: PacketEventKind,
+    pub port_id: PortId,
+    pub channel_id: ChannelId,
+    pub sequences: Vec<NonZeroU64>,
+}
+
+/// The packet lifecycle event to fetch in a [`FetchPacketEvents`] call.
+#[model]
+#[derive(JsonSchema, Copy)]
+pub enum PacketEventKind {
+    /// The packet was sent; the port and channel are the source of the packet.
+    SendPacket,
+    /// The packet was acknowledged on the destination; the port and channel are
+    /// the destination of the packet.
+    WriteAcknowledgement,
+}
+
 /// Fetch blocks on a chain, starting at height `start_height`.
 ///
 /// This represents a request for IBC events on a chain and must be
@@ -61,6 +117,7 @@ pub struct FetchBlockRange {
 /// be the exact implementation, but the semantics of the unfold should
 /// still hold.
 #[model]
+#[derive(JsonSchema)]
 pub struct FetchBlocks {
     pub chain_id: ChainId,
     pub start_height: Height,
@@ -80,6 +137,7 @@ pub struct FetchBlocks {
 /// [`AggregateMsgUpdateClientsFromOrderedHeaders`] message, which will
 /// be used to build the actual [`MsgUpdateClient`]s.
 #[model]
+#[derive(JsonSchema)]
 pub struct FetchUpdateHeaders {
     pub chain_id: ChainId,
     pub counterparty_chain_id: ChainId,
@@ -88,33 +146,271 @@ pub struct FetchUpdateHeaders {
 }
 
 #[model]
+#[derive(JsonSchema)]
 pub struct WaitForHeight {
     pub chain_id: ChainId,
     pub height: Height,
     pub finalized: bool,
+    /// The unix timestamp (in seconds) after which to stop waiting, failing with a fatal error.
+    /// If not set, this waits indefinitely.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deadline: Option<u64>,
 }
 
+/// Wait for `.height` to be finalized on `.chain_id`.
+///
+/// What finality means is defined by the consensus module of the chain: for chains with instant
+/// finality (tendermint, cometbls) a height is final as soon as it is produced, whereas for
+/// ethereum it is final once it is included in the finalized checkpoint of the beacon chain. This
+/// should be used before generating proofs, since light clients only accept finalized headers.
 #[model]
+#[derive(JsonSchema)]
+pub struct WaitForFinality {
+    pub chain_id: ChainId,
+    pub height: Height,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deadline: Option<u64>,
+}
+
+/// Wait for the latest timestamp of `.chain_id` to be >= `.timestamp`.
+///
+/// This is the timestamp of the chain itself (as returned by the consensus module's
+/// `query_latest_timestamp`), not the local clock, and is what packet timeouts are evaluated
+/// against. Set `.deadline` to fail instead of waiting forever if the chain halts.
+#[model]
+#[derive(JsonSchema)]
 pub struct WaitForTimestamp {
     pub chain_id: ChainId,
     /// THIS IS NANOSECONDS
     pub timestamp: i64,
     pub finalized: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deadline: Option<u64>,
 }
 
 /// Wait for the client `.client_id` on `.chain_id` to trust a height >=
 /// `.height`.
+///
+/// If the counterparty chain halts, the client will never be updated to `.height`; set
+/// `.deadline` to fail instead of waiting forever.
 #[model]
+#[derive(JsonSchema)]
 pub struct WaitForTrustedHeight {
     pub chain_id: ChainId,
     pub ibc_spec_id: IbcSpecId,
     pub client_id: RawClientId,
     pub height: Height,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deadline: Option<u64>,
+}
+
+/// Wait for the transaction `.tx_hash` to be included on `.chain_id`.
+///
+/// This resolves once the transaction has been included and succeeded (and, for chains without
+/// instant finality, once the block it was included in is finalized). A failed transaction is
+/// returned as a retryable error, such that the ops depending on it are not processed.
+///
+/// Like [`FetchBlocks`], this must be picked up by a plugin for the chain (the event source
+/// plugins handle this), since querying transactions is chain specific.
+#[model]
+#[derive(JsonSchema)]
+pub struct WaitForTxInclusion {
+    pub chain_id: ChainId,
+    pub tx_hash: H256,
+}
+
+/// Record that all events in the block at `.height` on `.chain_id` have been processed, advancing
+/// the checkpoint of the chain once all blocks before it have been processed as well.
+///
+/// Event source plugins queue this after the ops that make the events of a block (i.e.
+/// `seq([conc(events), call(RecordCheckpoint { .. })])`), such that the checkpoint never covers a
+/// block with events that are still being processed. This is handled by voyager directly.
+#[model]
+#[derive(JsonSchema)]
+pub struct RecordCheckpoint {
+    pub chain_id: ChainId,
+    pub height: Height,
+}
+
+/// Timeouts for the processing of a single [`Call`], in seconds, by kind of call. A call that
+/// does not complete within its timeout (i.e. due to an rpc request that never returns) fails with
+/// a retryable error, rather than blocking the worker processing it forever.
+///
+/// Calls of a kind without a timeout are never timed out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct CallTimeouts {
+    /// The timeout of the `fetch_*` calls.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fetch_seconds: Option<u64>,
+    /// The timeout of a single check of the `wait_for_*` calls. This is unrelated to the deadline
+    /// of the wait itself.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub wait_seconds: Option<u64>,
+    /// The timeout of calls to plugins. The submission of msgs by the transaction plugins (see
+    /// [`SUBMISSION_CALLS`]) is never timed out.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub plugin_seconds: Option<u64>,
+}
+
+/// The `@type`s of the calls that transaction plugins submit msgs to their chain with.
+///
+/// These are exempt from [`CallTimeouts::plugin_seconds`]: a submission that is timed out after its
+/// transaction was broadcast would be retried, submitting the msgs again.
+pub const SUBMISSION_CALLS: &[&str] = &["submit_transaction", "submit_multicall"];
+
+impl CallTimeouts {
+    #[must_use]
+    pub fn timeout(&self, call: &Call) -> Option<Duration> {
+        match call {
+            Call::FetchBlocks(_)
+            | Call::FetchBlockRange(_)
+            | Call::FetchPacketEvents(_)
+            | Call::FetchUpdateHeaders(_) => self.fetch_seconds,
+            Call::WaitForHeight(_)
+            | Call::WaitForFinality(_)
+            | Call::WaitForTimestamp(_)
+            | Call::WaitForTrustedHeight(_)
+            | Call::WaitForTxInclusion(_) => self.wait_seconds,
+            Call::RecordCheckpoint(_) => None,
+            Call::Plugin(msg) if is_submission(msg) => None,
+            Call::Plugin(_) => self.plugin_seconds,
+        }
+        .map(Duration::from_secs)
+    }
+}
+
+/// The `@type` of the call to the plugin, if any.
+fn plugin_call_type(msg: &PluginMessage) -> Option<&str> {
+    msg.message.get("@type").and_then(|ty| ty.as_str())
+}
+
+/// Whether `msg` is the submission of msgs to a transaction plugin, see [`SUBMISSION_CALLS`].
+fn is_submission(msg: &PluginMessage) -> bool {
+    plugin_call_type(msg).is_some_and(|ty| SUBMISSION_CALLS.contains(&ty))
 }
 
 impl CallT<VoyagerMessage> for Call {
-    // #[instrument(skip_all, fields(chain_id = %self.chain_id))]
     async fn process(self, ctx: &Context) -> Result<Op<VoyagerMessage>, QueueError> {
+        let [call, plugin] = call_labels(&self).map(ToOwned::to_owned);
+
+        CALL_PROCESSED_COUNT
+            .with_label_values(&[&call, &plugin])
+            .inc();
+
+        let timer = CALL_PROCESSING_DURATION
+            .with_label_values(&[&call, &plugin])
+            .start_timer();
+
+        // plugin fetches are additionally recorded by their @type, such that the latency of the
+        // fetches for a chain isn't mixed with that of the other calls to its plugins
+        let fetch_timer = match &self {
+            Call::Plugin(msg) => plugin_call_type(msg)
+                .filter(|ty| ty.starts_with("fetch_"))
+                .map(|ty| {
+                    FETCH_DURATION
+                        .with_label_values(&[ty, &plugin])
+                        .start_timer()
+                }),
+            _ => None,
+        };
+
+        let submission = matches!(&self, Call::Plugin(msg) if is_submission(msg));
+
+        let span = self.span(&call, &plugin);
+
+        let res = match ctx.call_timeouts.timeout(&self) {
+            Some(timeout) => {
+                match tokio::time::timeout(timeout, self.process_inner(ctx).instrument(span)).await
+                {
+                    Ok(res) => res,
+                    Err(_) => {
+                        CALL_TIMEOUT_COUNT
+                            .with_label_values(&[&call, &plugin])
+                            .inc();
+
+                        let message = format!("{call} call timed out after {timeout:?}");
+
+                        error!(%plugin, %message);
+
+                        Err(QueueError::Retry(message.into()))
+                    }
+                }
+            }
+            None => self.process_inner(ctx).instrument(span).await,
+        };
+
+        timer.observe_duration();
+
+        if let Some(fetch_timer) = fetch_timer {
+            fetch_timer.observe_duration();
+        }
+
+        if let Err(err) = &res {
+            CALL_ERROR_COUNT
+                .with_label_values(&[&call, &plugin, error_kind(err)])
+                .inc();
+
+            if submission {
+                SUBMISSION_ERROR_COUNT
+                    .with_label_values(&[&plugin, error_kind(err)])
+                    .inc();
+            }
+        }
+
+        res
+    }
+}
+
+impl Call {
+    /// The span that the processing of this call is recorded in, tagged with the chain (and
+    /// client) that the call is for.
+    fn span(&self, call: &str, plugin: &str) -> Span {
+        let span = info_span!(
+            "call",
+            call,
+            plugin,
+            chain_id = Empty,
+            counterparty_chain_id = Empty,
+            client_id = Empty,
+        );
+
+        match self {
+            Call::FetchBlocks(FetchBlocks { chain_id, .. })
+            | Call::FetchBlockRange(FetchBlockRange { chain_id, .. })
+            | Call::FetchPacketEvents(FetchPacketEvents { chain_id, .. })
+            | Call::WaitForHeight(WaitForHeight { chain_id, .. })
+            | Call::WaitForFinality(WaitForFinality { chain_id, .. })
+            | Call::WaitForTimestamp(WaitForTimestamp { chain_id, .. })
+            | Call::WaitForTxInclusion(WaitForTxInclusion { chain_id, .. })
+            | Call::RecordCheckpoint(RecordCheckpoint { chain_id, .. }) => {
+                span.record("chain_id", chain_id.as_str());
+            }
+            Call::FetchUpdateHeaders(FetchUpdateHeaders {
+                chain_id,
+                counterparty_chain_id,
+                ..
+            }) => {
+                span.record("chain_id", chain_id.as_str());
+                span.record("counterparty_chain_id", counterparty_chain_id.as_str());
+            }
+            Call::WaitForTrustedHeight(WaitForTrustedHeight {
+                chain_id,
+                client_id,
+                ..
+            }) => {
+                span.record("chain_id", chain_id.as_str());
+                span.record("client_id", client_id.0.to_string());
+            }
+            // the plugin name identifies the chain, if any
+            Call::Plugin(_) => {}
+        }
+
+        span
+    }
+
+    // #[instrument(skip_all, fields(chain_id = %self.chain_id))]
+    async fn process_inner(self, ctx: &Context) -> Result<Op<VoyagerMessage>, QueueError> {
         match self {
             // Call::Version(VersionMessage {
             //     ibc_spec_id,
@@ -137,6 +433,39 @@ impl CallT<VoyagerMessage> for Call {
                 Err(QueueError::Fatal(message.into()))
             }
 
+            Call::FetchBlockRange(FetchBlockRange {
+                chain_id,
+                from_height,
+                to_height,
+            }) => {
+                let message = format!(
+                    "fetch block range request received for chain `{chain_id}` from height \
+                    {from_height} to {to_height} but it was not picked up by a plugin"
+                );
+
+                error!(%message);
+
+                Err(QueueError::Fatal(message.into()))
+            }
+
+            Call::FetchPacketEvents(FetchPacketEvents {
+                chain_id,
+                kind,
+                port_id,
+                channel_id,
+                sequences,
+            }) => {
+                let message = format!(
+                    "fetch packet events request received for {kind:?} events on chain \
+                    `{chain_id}` ({port_id}/{channel_id:#}, sequences {sequences:?}) but it was \
+                    not picked up by a plugin"
+                );
+
+                error!(%message);
+
+                Err(QueueError::Fatal(message.into()))
+            }
+
             Call::FetchUpdateHeaders(FetchUpdateHeaders {
                 chain_id,
                 counterparty_chain_id,
@@ -179,6 +508,7 @@ impl CallT<VoyagerMessage> for Call {
                 chain_id,
                 height,
                 finalized,
+                deadline,
             }) => {
                 let chain_height = ctx
                     .rpc_server
@@ -198,15 +528,67 @@ impl CallT<VoyagerMessage> for Call {
 
                 debug!("latest height is {chain_height}, waiting for {height}");
 
+                ctx.block_times
+                    .observe(&chain_id, chain_height.height(), finalized);
+
                 if chain_height.height() >= height.height() {
                     Ok(noop())
                 } else {
+                    check_deadline("wait_for_height", &chain_id, deadline)?;
+
                     Ok(seq([
-                        defer(now() + 1),
+                        ctx.block_times
+                            .defer_blocks(&chain_id, height.height() - chain_height.height()),
                         call(WaitForHeight {
                             chain_id,
                             height,
                             finalized,
+                            deadline,
+                        }),
+                    ]))
+                }
+            }
+
+            Call::WaitForFinality(WaitForFinality {
+                chain_id,
+                height,
+                deadline,
+            }) => {
+                let finalized_height = ctx
+                    .rpc_server
+                    .query_latest_height(&chain_id, true)
+                    .await
+                    .map_err(error_object_to_queue_error)?;
+
+                if !finalized_height.revision_matches(&height) {
+                    return Err(QueueError::Fatal(
+                        format!(
+                            "revision number mismatch, \
+                            finalized_height: {finalized_height}, height: {height}"
+                        )
+                        .into(),
+                    ));
+                }
+
+                ctx.block_times
+                    .observe(&chain_id, finalized_height.height(), true);
+
+                if finalized_height.height() >= height.height() {
+                    debug!(%chain_id, %height, %finalized_height, "height finalized");
+
+                    Ok(noop())
+                } else {
+                    debug!(%chain_id, %height, %finalized_height, "height not yet finalized");
+
+                    check_deadline("wait_for_finality", &chain_id, deadline)?;
+
+                    Ok(seq([
+                        ctx.block_times
+                            .defer_blocks(&chain_id, height.height() - finalized_height.height()),
+                        call(WaitForFinality {
+                            chain_id,
+                            height,
+                            deadline,
                         }),
                     ]))
                 }
@@ -216,6 +598,7 @@ impl CallT<VoyagerMessage> for Call {
                 chain_id,
                 timestamp,
                 finalized,
+                deadline,
             }) => {
                 let chain_timestamp = ctx
                     .rpc_server
@@ -228,14 +611,17 @@ impl CallT<VoyagerMessage> for Call {
                     Ok(noop())
                 } else {
                     debug!(%chain_id, %timestamp, %chain_timestamp, "timestamp not yet reached");
+
+                    check_deadline("wait_for_timestamp", &chain_id, deadline)?;
+
                     Ok(seq([
-                        // REVIEW: Defer until `now + chain.block_time()`? Would require a new
-                        // method on chain
-                        defer(now() + 1),
+                        // the timestamp can only be reached by the next block
+                        ctx.block_times.defer_blocks(&chain_id, 1),
                         call(WaitForTimestamp {
                             chain_id,
                             timestamp,
                             finalized,
+                            deadline,
                         }),
                     ]))
                 }
@@ -246,6 +632,7 @@ impl CallT<VoyagerMessage> for Call {
                 ibc_spec_id,
                 client_id,
                 height,
+                deadline,
             }) => {
                 let trusted_client_state_meta = ctx
                     .rpc_server
@@ -266,24 +653,153 @@ impl CallT<VoyagerMessage> for Call {
 
                     Ok(noop())
                 } else {
+                    check_deadline("wait_for_trusted_height", &chain_id, deadline)?;
+
+                    // the client can only be updated to a height once the counterparty has
+                    // produced it, so this is sized by the block time of the counterparty
                     Ok(seq([
-                        // REVIEW: Defer until `now + counterparty_chain.block_time()`? Would
-                        // require a new method on chain
-                        defer(now() + 1),
+                        ctx.block_times.defer_blocks(
+                            &trusted_client_state_meta.chain_id,
+                            height.height() - trusted_client_state_meta.height.height(),
+                        ),
                         call(WaitForTrustedHeight {
                             chain_id,
                             ibc_spec_id,
                             client_id,
                             height,
+                            deadline,
                         }),
                     ]))
                 }
             }
-            Call::Plugin(PluginMessage { plugin, message }) => Ok(ctx
-                .plugin(plugin)?
-                .call(message)
-                .await
-                .map_err(json_rpc_error_to_queue_error)?),
+            Call::WaitForTxInclusion(WaitForTxInclusion { chain_id, tx_hash }) => {
+                let message = format!(
+                    "tx inclusion request received for transaction {tx_hash} on chain \
+                    `{chain_id}` but it was not picked up by a plugin"
+                );
+
+                error!(%message);
+
+                Err(QueueError::Fatal(message.into()))
+            }
+            Call::RecordCheckpoint(RecordCheckpoint { chain_id, height }) => {
+                ctx.rpc_server.record_checkpoint(chain_id, height);
+
+                Ok(noop())
+            }
+            Call::Plugin(PluginMessage { plugin, message }) => {
+                // identical calls (i.e. the same client update requested by several ops) are only
+                // sent to the plugin once, with all of them receiving the same result
+                let client = ctx.plugin(&plugin)?;
+
+                let mut op = ctx
+                    .in_flight_calls
+                    .run((plugin, message.to_string()), async {
+                        client
+                            .call(message)
+                            .await
+                            .map(|mut op| {
+                                // only recorded once, rather than by every caller sharing the
+                                // result
+                                ctx.tx_costs.record_op(&mut op);
+                                ctx.notifier.notify_op(&mut op);
+                                ctx.packet_statuses.record_op(&mut op);
+                                op
+                            })
+                            .map_err(json_rpc_error_to_error_object)
+                    })
+                    .await
+                    .map_err(error_object_to_queue_error)?;
+
+                ctx.event_dedup.dedup_op(&mut op);
+                ctx.packet_data_decoders.decode_op(&mut op);
+
+                Ok(op)
+            }
         }
     }
 }
+
+/// Fail a wait that has not resolved by its `deadline`, if it has one.
+///
+/// A wait that is still pending past its deadline usually means that the chain (or, for
+/// [`WaitForTrustedHeight`], the chain tracked by the client) has halted, which requires operator
+/// intervention; these are counted in [`WAIT_DEADLINE_EXCEEDED_COUNT`] so that they can be
+/// alerted on.
+fn check_deadline(wait: &str, chain_id: &ChainId, deadline: Option<u64>) -> Result<(), QueueError> {
+    match deadline {
+        Some(deadline) if now() >= deadline => {
+            WAIT_DEADLINE_EXCEEDED_COUNT
+                .with_label_values(&[wait, chain_id.as_str()])
+                .inc();
+
+            let message = format!(
+                "{wait} on chain `{chain_id}` did not resolve before its deadline ({deadline})"
+            );
+
+            error!(%message);
+
+            Err(QueueError::Fatal(message.into()))
+        }
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn deadline() {
+        let chain_id = ChainId::new("chain");
+
+        assert!(check_deadline("wait_for_height", &chain_id, None).is_ok());
+        assert!(check_deadline("wait_for_height", &chain_id, Some(now() + 60)).is_ok());
+
+        let err = check_deadline("wait_for_height", &chain_id, Some(now() - 1)).unwrap_err();
+
+        assert!(!err.is_retryable());
+    }
+
+    #[test]
+    fn call_timeouts() {
+        let timeouts = CallTimeouts {
+            fetch_seconds: Some(10),
+            wait_seconds: None,
+            plugin_seconds: Some(60),
+        };
+
+        let chain_id = ChainId::new("chain");
+
+        assert_eq!(
+            timeouts.timeout(&Call::FetchBlocks(FetchBlocks {
+                chain_id: chain_id.clone(),
+                start_height: Height::new(1),
+            })),
+            Some(Duration::from_secs(10))
+        );
+        assert_eq!(
+            timeouts.timeout(&Call::WaitForHeight(WaitForHeight {
+                chain_id,
+                height: Height::new(1),
+                finalized: false,
+                deadline: None,
+            })),
+            None
+        );
+        assert_eq!(
+            timeouts.timeout(&Call::Plugin(PluginMessage::new(
+                "plugin",
+                serde_json::json!({ "@type": "make_msg" }),
+            ))),
+            Some(Duration::from_secs(60))
+        );
+        assert_eq!(
+            timeouts.timeout(&Call::Plugin(PluginMessage::new(
+                "voyager-transaction-plugin-ethereum",
+                serde_json::json!({ "@type": "submit_multicall", "@value": [] }),
+            ))),
+            None
+        );
+    }
+}
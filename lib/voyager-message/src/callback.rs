@@ -4,23 +4,30 @@ use enumorph::Enumorph;
 use futures::{stream, StreamExt, TryFutureExt, TryStreamExt};
 use itertools::Itertools;
 use macros::model;
+use schemars::JsonSchema;
 use serde::de::DeserializeOwned;
+use tracing::{field::Empty, info_span, Instrument, Span};
 use unionlabs::traits::Member;
 use voyager_core::{ClientInfo, IbcSpecId};
 use voyager_vm::{CallbackT, Op, QueueError};
 
 use crate::{
     core::ChainId,
-    data::{ClientUpdate, Data, OrderedClientUpdates, OrderedHeaders},
+    data::{ClientUpdate, Data, IbcDatagram, OrderedClientUpdates, OrderedHeaders, WithChainId},
     error_object_to_queue_error, json_rpc_error_to_queue_error,
+    metrics::{
+        callback_labels, error_kind, CALLBACK_DATA_COUNT, CALLBACK_ERROR_COUNT,
+        CALLBACK_PROCESSED_COUNT,
+    },
     module::{ClientModuleClient, PluginClient},
     Context, PluginMessage, RawClientId, VoyagerMessage,
 };
 
 #[model]
-#[derive(Enumorph)]
+#[derive(JsonSchema, Enumorph)]
 pub enum Callback {
     AggregateMsgUpdateClientsFromOrderedHeaders(AggregateMsgUpdateClientsFromOrderedHeaders),
+    AggregateSubmitTxFromOrderedClientUpdates(AggregateSubmitTxFromOrderedClientUpdates),
 
     Plugin(PluginMessage),
 }
@@ -42,6 +49,70 @@ impl CallbackT<VoyagerMessage> for Callback {
         self,
         ctx: &Context,
         data: VecDeque<Data>,
+    ) -> Result<Op<VoyagerMessage>, QueueError> {
+        let [callback, plugin] = callback_labels(&self).map(ToOwned::to_owned);
+
+        CALLBACK_PROCESSED_COUNT
+            .with_label_values(&[&callback, &plugin])
+            .inc();
+
+        #[allow(clippy::cast_precision_loss)]
+        CALLBACK_DATA_COUNT
+            .with_label_values(&[&callback, &plugin])
+            .observe(data.len() as f64);
+
+        let span = self.span(&callback, &plugin);
+
+        let res = self.process_inner(ctx, data).instrument(span).await;
+
+        if let Err(err) = &res {
+            CALLBACK_ERROR_COUNT
+                .with_label_values(&[&callback, &plugin, error_kind(err)])
+                .inc();
+        }
+
+        res
+    }
+}
+
+impl Callback {
+    /// The span that the processing of this callback is recorded in, tagged with the chain (and
+    /// client) that the callback is for.
+    fn span(&self, callback: &str, plugin: &str) -> Span {
+        let span = info_span!(
+            "callback",
+            callback,
+            plugin,
+            chain_id = Empty,
+            client_id = Empty
+        );
+
+        match self {
+            Callback::AggregateMsgUpdateClientsFromOrderedHeaders(
+                AggregateMsgUpdateClientsFromOrderedHeaders {
+                    chain_id,
+                    counterparty_client_id,
+                    ..
+                },
+            ) => {
+                span.record("chain_id", chain_id.as_str());
+                span.record("client_id", counterparty_client_id.0.to_string());
+            }
+            Callback::AggregateSubmitTxFromOrderedClientUpdates(
+                AggregateSubmitTxFromOrderedClientUpdates { chain_id },
+            ) => {
+                span.record("chain_id", chain_id.as_str());
+            }
+            Callback::Plugin(_) => {}
+        }
+
+        span
+    }
+
+    async fn process_inner(
+        self,
+        ctx: &Context,
+        data: VecDeque<Data>,
     ) -> Result<Op<VoyagerMessage>, QueueError> {
         match self {
             Callback::AggregateMsgUpdateClientsFromOrderedHeaders(
@@ -108,6 +179,54 @@ impl CallbackT<VoyagerMessage> for Callback {
                         .await?,
                 }))
             }
+            Callback::AggregateSubmitTxFromOrderedClientUpdates(
+                AggregateSubmitTxFromOrderedClientUpdates { chain_id },
+            ) => {
+                let OrderedClientUpdates { updates } = data
+                    .into_iter()
+                    .exactly_one()
+                    .map_err(|found| serde_json::to_string(&found.collect::<Vec<_>>()).unwrap())
+                    .and_then(|d| {
+                        d.try_into()
+                            .map_err(|found| serde_json::to_string(&found).unwrap())
+                    })
+                    .map_err(|found| {
+                        QueueError::Fatal(
+                            format!(
+                                "OrderedClientUpdates not present in data queue for \
+                                AggregateSubmitTxFromOrderedClientUpdates, \
+                                found {found}",
+                            )
+                            .into(),
+                        )
+                    })?;
+
+                let modules = ctx
+                    .rpc_server
+                    .modules()
+                    .map_err(error_object_to_queue_error)?;
+
+                Ok(voyager_vm::data(WithChainId {
+                    chain_id,
+                    message: updates
+                        .into_iter()
+                        .map(|(_, update)| {
+                            let datagram = (modules
+                                .ibc_spec_handlers
+                                .get(&update.ibc_spec_id)?
+                                .update_client_datagram)(
+                                update.client_id, update.client_message
+                            )
+                            .map_err(|err| QueueError::Fatal(err.into()))?;
+
+                            Ok(IbcDatagram {
+                                ibc_spec_id: update.ibc_spec_id,
+                                datagram,
+                            })
+                        })
+                        .collect::<Result<Vec<_>, QueueError>>()?,
+                }))
+            }
             Callback::Plugin(PluginMessage { plugin, message }) => Ok(ctx
                 .plugin(&plugin)?
                 .callback(message, data)
@@ -119,8 +238,19 @@ impl CallbackT<VoyagerMessage> for Callback {
 
 /// Required data: [`OrderedHeaders`]
 #[model]
+#[derive(JsonSchema)]
 pub struct AggregateMsgUpdateClientsFromOrderedHeaders {
     pub ibc_spec_id: IbcSpecId,
     pub chain_id: ChainId,
     pub counterparty_client_id: RawClientId,
 }
+
+/// Submit all of the client updates in [`OrderedClientUpdates`] to `chain_id` in a single
+/// transaction, independent of any other IBC messages.
+///
+/// Required data: [`OrderedClientUpdates`]
+#[model]
+#[derive(JsonSchema)]
+pub struct AggregateSubmitTxFromOrderedClientUpdates {
+    pub chain_id: ChainId,
+}
@@ -0,0 +1,211 @@
+//! Clearing of packets that were never relayed on an [`IbcClassic`] channel.
+//!
+//! This is intended for recovering from periods where the relayer was not running (or was not
+//! configured to relay on a channel). All sequences sent on the channel are checked against the
+//! counterparty, and the events for the packets (and acknowledgements) that are still pending are
+//! fetched again and fed back into the queue, where they are relayed like any other event. Packets
+//! that have timed out on the counterparty by then are detected (and not submitted) when they are
+//! batched, since the timeout of a packet is not known until its event has been fetched.
+
+use std::num::NonZeroU64;
+
+use ibc_classic_spec::IbcClassic;
+use jsonrpsee::core::RpcResult;
+use macros::model;
+use tracing::{debug, info};
+use unionlabs::{
+    ibc::core::channel::order::Order,
+    id::{ChannelId, PortId},
+};
+use voyager_core::{IbcSpec, QueryHeight};
+use voyager_vm::{call, conc, noop, Op};
+
+use crate::{
+    call::{FetchPacketEvents, PacketEventKind},
+    core::ChainId,
+    query::{
+        query_channel, query_connection, query_next_sequence_recv, query_packet_commitments,
+        query_unreceived_packets,
+    },
+    rpc::{json_rpc_error_to_error_object, missing_state, VoyagerRpcClient},
+    RawClientId, VoyagerMessage,
+};
+
+/// The packets sent on a channel that have not been fully relayed.
+#[model]
+pub struct PendingPackets {
+    pub chain_id: ChainId,
+    pub port_id: PortId,
+    pub channel_id: ChannelId,
+
+    pub counterparty_chain_id: ChainId,
+    pub counterparty_port_id: PortId,
+    pub counterparty_channel_id: ChannelId,
+
+    /// Packets that have been sent on `chain_id` but not yet received on `counterparty_chain_id`.
+    pub unreceived_packets: Vec<NonZeroU64>,
+    /// Packets that have been received on `counterparty_chain_id`, but whose acknowledgement has
+    /// not yet been relayed back to `chain_id`.
+    pub unreceived_acks: Vec<NonZeroU64>,
+}
+
+impl PendingPackets {
+    /// Build the op to relay all of the pending packets and acknowledgements.
+    #[must_use]
+    pub fn into_op(self) -> Op<VoyagerMessage> {
+        if self.unreceived_packets.is_empty() && self.unreceived_acks.is_empty() {
+            return noop();
+        }
+
+        conc(
+            [
+                (!self.unreceived_packets.is_empty()).then(|| {
+                    call(FetchPacketEvents {
+                        chain_id: self.chain_id,
+                        kind: PacketEventKind::SendPacket,
+                        port_id: self.port_id,
+                        channel_id: self.channel_id,
+                        sequences: self.unreceived_packets,
+                    })
+                }),
+                (!self.unreceived_acks.is_empty()).then(|| {
+                    call(FetchPacketEvents {
+                        chain_id: self.counterparty_chain_id,
+                        kind: PacketEventKind::WriteAcknowledgement,
+                        port_id: self.counterparty_port_id,
+                        channel_id: self.counterparty_channel_id,
+                        sequences: self.unreceived_acks,
+                    })
+                }),
+            ]
+            .into_iter()
+            .flatten(),
+        )
+    }
+}
+
+/// Find all packets sent on `port_id`/`channel_id` on `chain_id` with a sequence
+/// `>= from_sequence` that have not been fully relayed to the counterparty.
+///
+/// Packets whose commitment has already been removed (i.e. they have been acknowledged or timed
+/// out) are not considered pending. Whether a packet has been received is checked through the
+/// packet receipts for unordered channels, and through the next sequence to be received for
+/// ordered channels (which don't write receipts).
+pub async fn pending_packets(
+    client: &impl VoyagerRpcClient,
+    chain_id: ChainId,
+    port_id: PortId,
+    channel_id: ChannelId,
+    from_sequence: NonZeroU64,
+) -> RpcResult<PendingPackets> {
+    let channel = query_channel(
+        client,
+        &chain_id,
+        QueryHeight::Latest,
+        port_id.clone(),
+        channel_id.clone(),
+    )
+    .await?
+    .ok_or_else(missing_state("channel not found", None))?;
+
+    let counterparty_channel_id = channel
+        .counterparty
+        .channel_id
+        .ok_or_else(missing_state("channel is not open", None))?;
+
+    let connection_id = channel
+        .connection_hops
+        .first()
+        .cloned()
+        .ok_or_else(missing_state("channel has no connection hops", None))?;
+
+    let connection = query_connection(client, &chain_id, QueryHeight::Latest, connection_id)
+        .await?
+        .ok_or_else(missing_state("connection not found", None))?;
+
+    let counterparty_chain_id = client
+        .client_meta(
+            chain_id.clone(),
+            IbcClassic::ID,
+            QueryHeight::Latest,
+            RawClientId::new(connection.client_id),
+        )
+        .await
+        .map_err(json_rpc_error_to_error_object)?
+        .chain_id;
+
+    info!(
+        %chain_id,
+        %port_id,
+        %channel_id,
+        %counterparty_chain_id,
+        "checking for pending packets"
+    );
+
+    let sequences = query_packet_commitments(
+        client,
+        &chain_id,
+        QueryHeight::Latest,
+        port_id.clone(),
+        channel_id.clone(),
+        from_sequence,
+    )
+    .await?
+    .into_iter()
+    .map(|(sequence, _)| sequence)
+    .collect::<Vec<_>>();
+
+    let unreceived_packets = match channel.ordering {
+        Order::Ordered => {
+            let next_sequence_recv = query_next_sequence_recv(
+                client,
+                &counterparty_chain_id,
+                QueryHeight::Latest,
+                channel.counterparty.port_id.clone(),
+                counterparty_channel_id.clone(),
+            )
+            .await?;
+
+            sequences
+                .iter()
+                .copied()
+                .filter(|sequence| sequence.get() >= next_sequence_recv)
+                .collect()
+        }
+        _ => {
+            query_unreceived_packets(
+                client,
+                &counterparty_chain_id,
+                QueryHeight::Latest,
+                channel.counterparty.port_id.clone(),
+                counterparty_channel_id.clone(),
+                sequences.iter().copied(),
+            )
+            .await?
+        }
+    };
+
+    // packets that still have a commitment but have been received only need their
+    // acknowledgement relayed
+    let unreceived_acks = sequences
+        .into_iter()
+        .filter(|sequence| !unreceived_packets.contains(sequence))
+        .collect::<Vec<_>>();
+
+    debug!(
+        ?unreceived_packets,
+        ?unreceived_acks,
+        "found pending packets"
+    );
+
+    Ok(PendingPackets {
+        chain_id,
+        port_id,
+        channel_id,
+        counterparty_chain_id,
+        counterparty_port_id: channel.counterparty.port_id,
+        counterparty_channel_id,
+        unreceived_packets,
+        unreceived_acks,
+    })
+}
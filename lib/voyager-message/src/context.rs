@@ -1,8 +1,8 @@
 use std::{
-    collections::HashMap,
+    collections::{HashMap, HashSet},
     path::{Path, PathBuf},
     process::Stdio,
-    sync::Arc,
+    sync::{Arc, Mutex},
     time::Duration,
 };
 
@@ -12,7 +12,10 @@ use futures::{
     stream::{self, FuturesUnordered},
     Future, StreamExt, TryStreamExt,
 };
-use jsonrpsee::types::{ErrorObject, ErrorObjectOwned};
+use jsonrpsee::{
+    core::RpcResult,
+    types::{ErrorObject, ErrorObjectOwned},
+};
 use macros::model;
 use schemars::JsonSchema;
 use serde::Serialize;
@@ -20,20 +23,31 @@ use serde_json::{Map, Value};
 use tokio::time::sleep;
 use tokio_util::sync::CancellationToken;
 use tracing::{debug, debug_span, error, info, instrument, trace, warn, Instrument};
-use unionlabs::{ethereum::keccak256, hash::hash_v2::HexUnprefixed, traits::Member, ErrorReporter};
+use unionlabs::{
+    bytes::Bytes, ethereum::keccak256, hash::hash_v2::HexUnprefixed, traits::Member, ErrorReporter,
+};
 use voyager_core::{ConsensusType, IbcSpecId};
-use voyager_vm::QueueError;
+use voyager_vm::{Op, QueueError};
 
 use crate::{
+    block_time::BlockTimes,
+    call::CallTimeouts,
     core::{ChainId, ClientType, IbcInterface, IbcSpec},
+    costs::TxCosts,
+    dedup::EventDedup,
+    event::PacketDataDecoders,
     into_value,
     module::{
-        ClientModuleClient, ClientModuleInfo, ConsensusModuleClient, ConsensusModuleInfo,
-        PluginClient, PluginInfo, ProofModuleInfo, RawProofModuleClient, RawStateModuleClient,
-        StateModuleInfo,
+        ChainCapabilities, ClientModuleClient, ClientModuleInfo, ConsensusModuleClient,
+        ConsensusModuleInfo, PluginClient, PluginInfo, ProofModuleInfo, RawProofModuleClient,
+        RawStateModuleClient, StateModuleInfo,
     },
+    notify::Notifier,
+    packet_status::PacketStatuses,
+    pause::Pauses,
     rpc::{server::Server, VoyagerRpcServer},
-    RawClientId, FATAL_JSONRPC_ERROR_CODE,
+    singleflight::SingleFlight,
+    RawClientId, VoyagerMessage, FATAL_JSONRPC_ERROR_CODE,
 };
 
 pub const INVALID_CONFIG_EXIT_CODE: u8 = 13;
@@ -47,10 +61,126 @@ pub struct Context {
 
     interest_filters: HashMap<String, String>,
 
+    /// Decoders for the packet data of the events returned from plugins.
+    pub packet_data_decoders: PacketDataDecoders,
+
+    /// The events that have already been returned from plugins, used to drop duplicate events from
+    /// redundant event sources.
+    pub event_dedup: EventDedup,
+
+    /// The ops that are currently paused.
+    pub pauses: Pauses,
+
+    /// The timeouts applied to the processing of each call.
+    pub call_timeouts: CallTimeouts,
+
+    /// The costs of all transactions submitted by transaction plugins.
+    pub tx_costs: TxCosts,
+
+    /// The webhooks notified of relaying lifecycle events.
+    pub notifier: Notifier,
+
+    /// The lifecycle state of every packet observed in events and transaction receipts.
+    pub packet_statuses: PacketStatuses,
+
+    /// The estimated block times of all chains, used to size the defers of waits.
+    pub block_times: BlockTimes,
+
+    /// The plugin calls that are currently being processed, keyed by plugin name and message.
+    pub(crate) in_flight_calls: SingleFlight<(String, String), RpcResult<Op<VoyagerMessage>>>,
+
+    /// The child processes of all loaded plugins and modules.
+    processes: ChildProcesses,
+
     pub cancellation_token: CancellationToken,
     // module_servers: Vec<ModuleRpcServer>,
 }
 
+/// The child processes of the loaded plugins and modules, keyed by name.
+#[derive(Debug, Clone, Default)]
+struct ChildProcesses(Arc<Mutex<HashMap<String, ChildProcess>>>);
+
+#[derive(Debug)]
+struct ChildProcess {
+    path: PathBuf,
+    config: Value,
+    /// Cancelling this token kills the process (without restarting it).
+    cancellation_token: CancellationToken,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Restart {
+    Restarted,
+    Unchanged,
+    NotLoaded,
+}
+
+impl ChildProcesses {
+    /// Register a new child process, returning the token to kill it with.
+    fn register(
+        &self,
+        name: &str,
+        path: &Path,
+        config: &Value,
+        cancellation_token: &CancellationToken,
+    ) -> CancellationToken {
+        let child_token = cancellation_token.child_token();
+
+        self.0.lock().expect("mutex is poisoned").insert(
+            name.to_owned(),
+            ChildProcess {
+                path: path.to_owned(),
+                config: config.clone(),
+                cancellation_token: child_token.clone(),
+            },
+        );
+
+        child_token
+    }
+
+    /// Kill the child process `name` and start it again with `spawn` if it is running with a
+    /// different path or config.
+    fn restart_if_changed(
+        &self,
+        name: &str,
+        path: &Path,
+        config: &Value,
+        cancellation_token: &CancellationToken,
+        spawn: impl FnOnce(CancellationToken),
+    ) -> Restart {
+        let mut processes = self.0.lock().expect("mutex is poisoned");
+
+        let Some(process) = processes.get_mut(name) else {
+            return Restart::NotLoaded;
+        };
+
+        if process.path == path && &process.config == config {
+            return Restart::Unchanged;
+        }
+
+        process.cancellation_token.cancel();
+
+        *process = ChildProcess {
+            path: path.to_owned(),
+            config: config.clone(),
+            cancellation_token: cancellation_token.child_token(),
+        };
+
+        spawn(process.cancellation_token.clone());
+
+        Restart::Restarted
+    }
+
+    fn names(&self) -> HashSet<String> {
+        self.0
+            .lock()
+            .expect("mutex is poisoned")
+            .keys()
+            .cloned()
+            .collect()
+    }
+}
+
 #[derive(macros::Debug)]
 pub struct Modules {
     state_modules: HashMap<(ChainId, IbcSpecId), ModuleRpcClient>,
@@ -64,6 +194,8 @@ pub struct Modules {
 
     chain_consensus_types: HashMap<ChainId, ConsensusType>,
 
+    chain_capabilities: HashMap<ChainId, ChainCapabilities>,
+
     client_consensus_types: HashMap<ClientType, ConsensusType>,
 
     // ibc version id => handler
@@ -71,13 +203,48 @@ pub struct Modules {
     pub ibc_spec_handlers: IbcSpecHandlers,
 }
 
+/// Registry of all of the [`IbcSpec`]s supported by this build of voyager.
+///
+/// Supporting a new IBC specification only requires registering it here (see
+/// [`Self::register`]); all of the type-erased plumbing is generated from the
+/// [`IbcSpec`] implementation.
+#[derive(Default)]
 pub struct IbcSpecHandlers {
     pub(crate) handlers: HashMap<IbcSpecId, IbcSpecHandler>,
 }
 
 impl IbcSpecHandlers {
-    pub fn register<S: IbcSpec>(&mut self) {
-        self.handlers.insert(S::ID, IbcSpecHandler::new::<S>());
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn register<S: IbcSpec>(&mut self) -> &mut Self {
+        if self
+            .handlers
+            .insert(S::ID, IbcSpecHandler::new::<S>())
+            .is_some()
+        {
+            warn!("IBC version `{}` was registered multiple times", S::ID);
+        }
+
+        self
+    }
+
+    pub fn get(&self, ibc_spec_id: &IbcSpecId) -> Result<&IbcSpecHandler, IbcSpecNotSupported> {
+        self.handlers
+            .get(ibc_spec_id)
+            .ok_or_else(|| IbcSpecNotSupported {
+                ibc_spec_id: ibc_spec_id.clone(),
+            })
+    }
+
+    pub fn contains(&self, ibc_spec_id: &IbcSpecId) -> bool {
+        self.handlers.contains_key(ibc_spec_id)
+    }
+
+    pub fn ibc_spec_ids(&self) -> impl Iterator<Item = &IbcSpecId> {
+        self.handlers.keys()
     }
 }
 
@@ -85,6 +252,7 @@ impl IbcSpecHandlers {
 pub struct IbcSpecHandler {
     pub client_state_path: fn(RawClientId) -> anyhow::Result<Value>,
     pub consensus_state_path: fn(RawClientId, String) -> anyhow::Result<Value>,
+    pub update_client_datagram: fn(RawClientId, Bytes) -> anyhow::Result<Value>,
 }
 
 impl IbcSpecHandler {
@@ -101,6 +269,12 @@ impl IbcSpecHandler {
                     height.parse()?,
                 )))
             },
+            update_client_datagram: |client_id, client_message| {
+                Ok(into_value(T::update_client_datagram(
+                    serde_json::from_value(client_id.0)?,
+                    client_message,
+                )))
+            },
         }
     }
 }
@@ -237,9 +411,7 @@ impl Context {
     ) -> anyhow::Result<Self> {
         let cancellation_token = CancellationToken::new();
 
-        let mut ibc_spec_handlers = IbcSpecHandlers {
-            handlers: Default::default(),
-        };
+        let mut ibc_spec_handlers = IbcSpecHandlers::new();
 
         register_ibc_spec_handlers(&mut ibc_spec_handlers);
 
@@ -249,6 +421,7 @@ impl Context {
             client_modules: Default::default(),
             consensus_modules: Default::default(),
             chain_consensus_types: Default::default(),
+            chain_capabilities: Default::default(),
             client_consensus_types: Default::default(),
             ibc_spec_handlers,
         };
@@ -257,6 +430,8 @@ impl Context {
 
         let mut interest_filters = HashMap::default();
 
+        let processes = ChildProcesses::default();
+
         let main_rpc_server = Server::new();
 
         info!("spawning {} plugins", plugin_configs.len());
@@ -305,6 +480,12 @@ impl Context {
                     tokio::spawn(plugin_child_process(
                         name.clone(),
                         plugin_config.clone(),
+                        processes.register(
+                            &name,
+                            &plugin_config.path,
+                            &plugin_config.config,
+                            &cancellation_token,
+                        ),
                         cancellation_token.clone(),
                     ));
 
@@ -330,6 +511,7 @@ impl Context {
         module_startup(
             module_configs.state,
             cancellation_token.clone(),
+            &processes,
             main_rpc_server.clone(),
             |info| info.id(),
             |StateModuleInfo {
@@ -356,6 +538,7 @@ impl Context {
         module_startup(
             module_configs.proof,
             cancellation_token.clone(),
+            &processes,
             main_rpc_server.clone(),
             |info| info.id(),
             |ProofModuleInfo {
@@ -382,11 +565,13 @@ impl Context {
         module_startup(
             module_configs.consensus,
             cancellation_token.clone(),
+            &processes,
             main_rpc_server.clone(),
             |info| info.id(),
             |ConsensusModuleInfo {
                  chain_id,
                  consensus_type,
+                 capabilities,
              },
              rpc_client| {
                 let prev = modules
@@ -407,6 +592,10 @@ impl Context {
                     unreachable!()
                 };
 
+                modules
+                    .chain_capabilities
+                    .insert(chain_id.clone(), capabilities.clone());
+
                 Ok(())
             },
         )
@@ -415,6 +604,7 @@ impl Context {
         module_startup(
             module_configs.client,
             cancellation_token.clone(),
+            &processes,
             main_rpc_server.clone(),
             |info| info.id(),
             |ClientModuleInfo {
@@ -424,7 +614,7 @@ impl Context {
                  ibc_spec_id,
              },
              rpc_client| {
-                if !modules.ibc_spec_handlers.handlers.contains_key(ibc_spec_id) {
+                if !modules.ibc_spec_handlers.contains(ibc_spec_id) {
                     return Err(anyhow!(
                         "IBC version `{ibc_spec_id}` is not supported in this build of voyager"
                     ));
@@ -466,6 +656,12 @@ impl Context {
         )
         .await?;
 
+        let block_times = BlockTimes::new(modules.chain_capabilities.iter().filter_map(
+            |(chain_id, capabilities)| {
+                Some((chain_id.clone(), capabilities.average_block_time_ms?))
+            },
+        ));
+
         main_rpc_server.start(Arc::new(modules));
 
         info!("checking for plugin health...");
@@ -503,10 +699,117 @@ impl Context {
             rpc_server: main_rpc_server,
             plugins,
             interest_filters,
+            packet_data_decoders: PacketDataDecoders::default(),
+            event_dedup: EventDedup::default(),
+            pauses: Pauses::default(),
+            call_timeouts: CallTimeouts::default(),
+            tx_costs: TxCosts::default(),
+            notifier: Notifier::default(),
+            packet_statuses: PacketStatuses::default(),
+            block_times,
+            in_flight_calls: SingleFlight::default(),
+            processes,
             cancellation_token,
         })
     }
 
+    /// Restart all plugins and modules whose config changed, returning the info of the plugins
+    /// that were restarted.
+    ///
+    /// Plugins and modules are identified by their name and info respectively, and only the
+    /// configs of those that are already loaded can be reloaded; adding or removing a plugin or
+    /// module requires a restart of voyager. Note that [`Self::interest_filters`] is not updated,
+    /// the caller is responsible for updating the interest filters of the restarted plugins.
+    #[instrument(name = "context_reload", skip_all)]
+    pub async fn reload(
+        &self,
+        plugin_configs: Vec<PluginConfig>,
+        module_configs: ModulesConfig,
+    ) -> anyhow::Result<Vec<PluginInfo>> {
+        let mut loaded = self.processes.names();
+        let mut reloaded = vec![];
+
+        for plugin_config in plugin_configs.into_iter().filter(|c| c.enabled) {
+            let plugin_info = get_plugin_info(&plugin_config)?;
+            let name = plugin_info.name.clone();
+
+            loaded.remove(&name);
+
+            match self.processes.restart_if_changed(
+                &name,
+                &plugin_config.path,
+                &plugin_config.config,
+                &self.cancellation_token,
+                |child_token| {
+                    tokio::spawn(plugin_child_process(
+                        name.clone(),
+                        plugin_config.clone(),
+                        child_token,
+                        self.cancellation_token.clone(),
+                    ));
+                },
+            ) {
+                Restart::Restarted => {
+                    info!("restarted plugin {name}");
+                    reloaded.push(plugin_info);
+                }
+                Restart::Unchanged => {}
+                Restart::NotLoaded => {
+                    warn!("plugin {name} is not loaded, a restart is required to load it");
+                }
+            }
+        }
+
+        self.reload_modules(module_configs.state, StateModuleInfo::id, &mut loaded);
+        self.reload_modules(module_configs.proof, ProofModuleInfo::id, &mut loaded);
+        self.reload_modules(
+            module_configs.consensus,
+            ConsensusModuleInfo::id,
+            &mut loaded,
+        );
+        self.reload_modules(module_configs.client, ClientModuleInfo::id, &mut loaded);
+
+        for name in loaded {
+            warn!("{name} was removed from the config, a restart is required to unload it");
+        }
+
+        Ok(reloaded)
+    }
+
+    fn reload_modules<Info: Serialize + Clone + Send + 'static>(
+        &self,
+        configs: Vec<ModuleConfig<Info>>,
+        id_f: fn(&Info) -> String,
+        loaded: &mut HashSet<String>,
+    ) {
+        for module_config in configs.into_iter().filter(|c| c.enabled) {
+            let id = id_f(&module_config.info);
+
+            loaded.remove(&id);
+
+            match self.processes.restart_if_changed(
+                &id,
+                &module_config.path,
+                &module_config.config,
+                &self.cancellation_token,
+                |child_token| {
+                    tokio::spawn(module_child_process(
+                        id.clone(),
+                        module_config.clone(),
+                        child_token,
+                        self.cancellation_token.clone(),
+                    ));
+                },
+            ) {
+                Restart::Restarted => info!("restarted module {id}"),
+                Restart::Unchanged => {}
+                Restart::NotLoaded => {
+                    warn!("module {id} is not loaded, a restart is required to load it");
+                }
+            }
+        }
+    }
+
     pub async fn shutdown(self) {
         self.cancellation_token.cancel();
 
@@ -573,6 +876,7 @@ impl Modules {
             .cloned()
             .map(|chain_id| ConsensusModuleInfo {
                 consensus_type: self.chain_consensus_types[&chain_id].clone(),
+                capabilities: self.chain_capabilities[&chain_id].clone(),
                 chain_id,
             })
             .collect();
@@ -607,6 +911,15 @@ impl Modules {
             .ok_or_else(|| ConsensusModuleNotFound(chain_id.clone()))
     }
 
+    pub fn chain_capabilities<'a, 'b, 'c: 'a>(
+        &'a self,
+        chain_id: &ChainId,
+    ) -> Result<&'a ChainCapabilities, ConsensusModuleNotFound> {
+        self.chain_capabilities
+            .get(chain_id)
+            .ok_or_else(|| ConsensusModuleNotFound(chain_id.clone()))
+    }
+
     pub fn client_consensus_type<'a, 'b, 'c: 'a>(
         &'a self,
         client_type: &ClientType,
@@ -692,6 +1005,7 @@ pub struct LoadedModulesInfo {
 async fn plugin_child_process(
     name: String,
     module_config: PluginConfig,
+    child_token: CancellationToken,
     cancellation_token: CancellationToken,
 ) {
     let client_socket = ModuleRpcClient::make_socket_path(&name);
@@ -713,6 +1027,7 @@ async fn plugin_child_process(
             &server_socket,
             &module_config.config.to_string(),
         ],
+        child_token,
         cancellation_token,
     )
     .await
@@ -722,6 +1037,7 @@ async fn plugin_child_process(
 async fn module_child_process<Info: Serialize>(
     name: String,
     module_config: ModuleConfig<Info>,
+    child_token: CancellationToken,
     cancellation_token: CancellationToken,
 ) {
     let client_socket = ModuleRpcClient::make_socket_path(&name);
@@ -738,12 +1054,20 @@ async fn module_child_process<Info: Serialize>(
             &module_config.config.to_string(),
             &serde_json::to_string(&module_config.info).unwrap(),
         ],
+        child_token,
         cancellation_token,
     )
     .await
 }
 
-async fn lazarus_pit(cmd: &Path, args: &[&str], cancellation_token: CancellationToken) {
+/// Run `cmd` until `child_token` is cancelled, restarting it whenever it exits. If the process exits
+/// due to an invalid config, `cancellation_token` is cancelled, shutting down voyager.
+async fn lazarus_pit(
+    cmd: &Path,
+    args: &[&str],
+    child_token: CancellationToken,
+    cancellation_token: CancellationToken,
+) {
     let mut attempt = 0;
 
     loop {
@@ -775,7 +1099,7 @@ async fn lazarus_pit(cmd: &Path, args: &[&str], cancellation_token: Cancellation
         let id = child.id().unwrap();
 
         tokio::select! {
-            _ = cancellation_token.cancelled() => {
+            _ = child_token.cancelled() => {
                 debug!(%id, "killing plugin");
                 match child.kill().await {
                     Ok(()) => {
@@ -893,6 +1217,14 @@ pub struct PluginNotFound {
 
 module_error!(PluginNotFound);
 
+#[derive(Debug, Clone, PartialEq, thiserror::Error)]
+#[error("IBC version `{ibc_spec_id}` is not supported in this build of voyager")]
+pub struct IbcSpecNotSupported {
+    pub ibc_spec_id: IbcSpecId,
+}
+
+module_error!(IbcSpecNotSupported);
+
 pub fn get_plugin_info(module_config: &PluginConfig) -> anyhow::Result<PluginInfo> {
     debug!(
         "querying module info from plugin at {}",
@@ -938,6 +1270,7 @@ pub fn get_plugin_info(module_config: &PluginConfig) -> anyhow::Result<PluginInf
 async fn module_startup<Info: Serialize + Clone + Unpin + Send + 'static>(
     configs: Vec<ModuleConfig<Info>>,
     cancellation_token: CancellationToken,
+    processes: &ChildProcesses,
     main_rpc_server: Server,
     id_f: fn(&Info) -> String,
     mut push_f: impl FnMut(&Info, ModuleRpcClient) -> anyhow::Result<()>,
@@ -984,6 +1317,12 @@ async fn module_startup<Info: Serialize + Clone + Unpin + Send + 'static>(
             tokio::spawn(module_child_process(
                 id.clone(),
                 module_config.clone(),
+                processes.register(
+                    &id,
+                    &module_config.path,
+                    &module_config.config,
+                    &cancellation_token,
+                ),
                 cancellation_token.clone(),
             ));
 
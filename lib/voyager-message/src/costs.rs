@@ -0,0 +1,287 @@
+//! Accounting of the gas used and fees paid by submitted transactions.
+//!
+//! Transaction plugins return a [`TxReceipt`] for every transaction they submit, which includes
+//! the fee paid and the messages in the transaction. Receipts are recorded as they are returned
+//! from plugin calls, aggregated per chain, client, and message type, and exposed both as metrics
+//! and as a report via the control API.
+//!
+//! The cost of the individual messages in a transaction is not known, so the gas used and fee paid
+//! by a transaction are split evenly between its messages.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::{Arc, LazyLock, Mutex},
+};
+
+use prometheus::{register_counter_vec, register_int_counter_vec, CounterVec, IntCounterVec};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use voyager_vm::{Op, Visit};
+
+use crate::{
+    core::ChainId,
+    data::{Data, TxMsg, TxReceipt, WithChainId},
+    RawClientId, VoyagerMessage,
+};
+
+/// The message type that costs are attributed to if a receipt does not contain any messages.
+const UNKNOWN_MSG_TYPE: &str = "unknown";
+
+pub static TX_GAS_USED: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!(
+        "voyager_tx_gas_used_total",
+        "The gas used by submitted transactions, split evenly between the messages in each \
+        transaction.",
+        &["chain_id", "client_id", "msg_type"],
+    )
+    .unwrap()
+});
+
+pub static TX_FEES_PAID: LazyLock<CounterVec> = LazyLock::new(|| {
+    register_counter_vec!(
+        "voyager_tx_fees_paid_total",
+        "The fees paid for submitted transactions, in the smallest unit of the denom, split evenly \
+        between the messages in each transaction.",
+        &["chain_id", "client_id", "msg_type", "denom"],
+    )
+    .unwrap()
+});
+
+pub static TX_MSGS_SUBMITTED: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!(
+        "voyager_tx_msgs_submitted_total",
+        "The amount of messages included in submitted transactions.",
+        &["chain_id", "client_id", "msg_type"],
+    )
+    .unwrap()
+});
+
+#[derive(Debug, Clone, Default)]
+pub struct TxCosts {
+    inner: Arc<Mutex<HashMap<CostKey, Cost>>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct CostKey {
+    chain_id: ChainId,
+    client_id: Option<RawClientId>,
+    msg_type: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Cost {
+    /// The amount of messages submitted.
+    pub msgs: u64,
+    pub gas_used: u64,
+    /// The fees paid, by denom.
+    pub fees: BTreeMap<String, u128>,
+}
+
+/// The costs of the messages of one type targeting one client on a chain.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CostEntry {
+    pub chain_id: ChainId,
+    /// The targeted client, or `None` for messages that don't target a client directly.
+    pub client_id: Option<RawClientId>,
+    pub msg_type: String,
+    #[serde(flatten)]
+    pub cost: Cost,
+}
+
+impl TxCosts {
+    /// Record the cost of all transaction receipts in `op`, including those nested in the queues
+    /// of promises.
+    pub fn record_op(&self, op: &mut Op<VoyagerMessage>) {
+        RecordVisitor(self).visit_op(op);
+    }
+
+    /// Record the cost of a transaction submitted on `chain_id`.
+    pub fn record(&self, chain_id: &ChainId, receipt: &TxReceipt) {
+        let unknown = [TxMsg {
+            msg_type: UNKNOWN_MSG_TYPE.to_owned(),
+            client_id: None,
+            error: None,
+        }];
+
+        let msgs = if receipt.msgs.is_empty() {
+            &unknown[..]
+        } else {
+            &receipt.msgs[..]
+        };
+
+        let mut costs = self.inner.lock().expect("mutex is poisoned");
+
+        for (idx, msg) in msgs.iter().enumerate() {
+            let gas_used = split(receipt.gas_used.into(), msgs.len(), idx);
+            let gas_used = u64::try_from(gas_used).expect("share is <= gas_used; qed;");
+            let fee = receipt
+                .fee
+                .as_ref()
+                .map(|fee| (&fee.denom, split(fee.amount, msgs.len(), idx)));
+
+            let client_id = msg
+                .client_id
+                .as_ref()
+                .map(client_id_label)
+                .unwrap_or_default();
+
+            let labels = [chain_id.as_str(), &client_id, &msg.msg_type];
+
+            TX_MSGS_SUBMITTED.with_label_values(&labels).inc();
+            TX_GAS_USED.with_label_values(&labels).inc_by(gas_used);
+
+            let cost = costs
+                .entry(CostKey {
+                    chain_id: chain_id.clone(),
+                    client_id: msg.client_id.clone(),
+                    msg_type: msg.msg_type.clone(),
+                })
+                .or_default();
+
+            cost.msgs += 1;
+            cost.gas_used = cost.gas_used.saturating_add(gas_used);
+
+            if let Some((denom, amount)) = fee {
+                #[allow(clippy::cast_precision_loss)]
+                TX_FEES_PAID
+                    .with_label_values(&[labels[0], labels[1], labels[2], denom])
+                    .inc_by(amount as f64);
+
+                let fees = cost.fees.entry(denom.clone()).or_default();
+                *fees = fees.saturating_add(amount);
+            }
+        }
+    }
+
+    /// All recorded costs, ordered by chain id, message type, and client id.
+    pub fn report(&self) -> Vec<CostEntry> {
+        let mut entries = self
+            .inner
+            .lock()
+            .expect("mutex is poisoned")
+            .iter()
+            .map(|(key, cost)| CostEntry {
+                chain_id: key.chain_id.clone(),
+                client_id: key.client_id.clone(),
+                msg_type: key.msg_type.clone(),
+                cost: cost.clone(),
+            })
+            .collect::<Vec<_>>();
+
+        entries.sort_by_cached_key(|entry| {
+            (
+                entry.chain_id.to_string(),
+                entry.msg_type.clone(),
+                entry.client_id.as_ref().map(client_id_label),
+            )
+        });
+
+        entries
+    }
+}
+
+struct RecordVisitor<'a>(&'a TxCosts);
+
+impl Visit<VoyagerMessage> for RecordVisitor<'_> {
+    fn visit_data(&mut self, data: &mut Data) {
+        if let Data::TxReceipt(WithChainId { chain_id, message }) = data {
+            self.0.record(chain_id, message);
+        }
+    }
+}
+
+/// The share of `total` attributed to the `idx`th of `n` messages. The remainder of the division
+/// is attributed to the first message, such that the shares always add up to `total`.
+fn split(total: u128, n: usize, idx: usize) -> u128 {
+    let n = u128::try_from(n).expect("usize fits in u128; qed;");
+
+    let share = total / n;
+
+    if idx == 0 {
+        share + total % n
+    } else {
+        share
+    }
+}
+
+fn client_id_label(client_id: &RawClientId) -> String {
+    match &client_id.0 {
+        Value::String(s) => s.clone(),
+        value => value.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use unionlabs::hash::H256;
+
+    use super::*;
+    use crate::data::TxFee;
+
+    #[test]
+    fn costs_are_split_between_msgs() {
+        let costs = TxCosts::default();
+
+        let chain_id = ChainId::new("union-1");
+
+        let update = |client_id: u32| TxMsg {
+            msg_type: "update_client".to_owned(),
+            client_id: Some(RawClientId(json!(client_id))),
+            error: None,
+        };
+
+        costs.record(
+            &chain_id,
+            &TxReceipt {
+                tx_hash: H256::default(),
+                height: 1,
+                gas_used: 101,
+                fee: Some(TxFee {
+                    amount: 1001,
+                    denom: "muno".to_owned(),
+                }),
+                msgs: vec![update(1), update(2)],
+                events: vec![],
+            },
+        );
+
+        costs.record(
+            &chain_id,
+            &TxReceipt {
+                tx_hash: H256::default(),
+                height: 2,
+                gas_used: 50,
+                fee: None,
+                msgs: vec![update(1)],
+                events: vec![],
+            },
+        );
+
+        assert_eq!(
+            costs.report(),
+            [
+                CostEntry {
+                    chain_id: chain_id.clone(),
+                    client_id: Some(RawClientId(json!(1))),
+                    msg_type: "update_client".to_owned(),
+                    cost: Cost {
+                        msgs: 2,
+                        gas_used: 101,
+                        fees: [("muno".to_owned(), 501)].into(),
+                    },
+                },
+                CostEntry {
+                    chain_id,
+                    client_id: Some(RawClientId(json!(2))),
+                    msg_type: "update_client".to_owned(),
+                    cost: Cost {
+                        msgs: 1,
+                        gas_used: 50,
+                        fees: [("muno".to_owned(), 500)].into(),
+                    },
+                },
+            ]
+        );
+    }
+}
@@ -1,5 +1,6 @@
 use enumorph::Enumorph;
 use macros::model;
+use schemars::JsonSchema;
 use serde::de::DeserializeOwned;
 use serde_json::Value;
 use subset_of::SubsetOf;
@@ -8,11 +9,12 @@ use voyager_core::IbcSpecId;
 
 use crate::{
     core::{ChainId, ClientInfo, ClientStateMeta, IbcSpec},
+    event::{DecodedAppEvent, DecodedPacketData},
     into_value, PluginMessage, RawClientId,
 };
 
 #[model]
-#[derive(Enumorph, SubsetOf)]
+#[derive(JsonSchema, Enumorph, SubsetOf)]
 #[allow(clippy::large_enum_variant)]
 pub enum Data {
     IbcEvent(ChainEvent),
@@ -24,6 +26,12 @@ pub enum Data {
     OrderedHeaders(OrderedHeaders),
     OrderedMsgUpdateClients(OrderedClientUpdates),
 
+    TxReceipt(WithChainId<TxReceipt>),
+
+    SignerBalances(WithChainId<Vec<SignerBalance>>),
+
+    AppEvent(AppEvent),
+
     Plugin(PluginMessage),
 }
 
@@ -40,6 +48,7 @@ impl Data {
 }
 
 #[model]
+#[derive(JsonSchema)]
 pub struct ChainEvent {
     /// The chain where this event was emitted.
     pub chain_id: ChainId,
@@ -57,6 +66,13 @@ pub struct ChainEvent {
     /// The full IBC event, encoded as JSON value. This is really [`IbcSpec::Event`],
     /// and will be interpreted based on the implementation defined by [`Self::ibc_spec_id`].
     pub event: Value,
+    /// The application level packet data of this event, if this is a packet event and the packet
+    /// data could be decoded by any of the [`PacketDataDecoders`](crate::event::PacketDataDecoders).
+    ///
+    /// This is populated by voyager for all events returned from plugins, and as such should be
+    /// left empty by event source plugins.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub decoded_packet_data: Option<DecodedPacketData>,
 }
 
 impl ChainEvent {
@@ -69,7 +85,24 @@ impl ChainEvent {
     }
 }
 
+/// An application level event emitted on a chain, decoded by the event source plugin of the chain.
+///
+/// Unlike [`ChainEvent`]s, these do not cause any action by voyager itself, but carry the metadata
+/// of the application (such as the tokens of a transfer) for use in filters and metrics.
+#[model]
+#[derive(JsonSchema)]
+pub struct AppEvent {
+    pub chain_id: ChainId,
+    pub tx_hash: H256,
+    /// The height of the block that the event was emitted in.
+    pub height: Height,
+    /// The address of the contract (or the name of the module) that emitted the event.
+    pub emitter: String,
+    pub event: DecodedAppEvent,
+}
+
 #[model]
+#[derive(JsonSchema)]
 pub struct IbcDatagram {
     pub ibc_spec_id: IbcSpecId,
     /// The IBC datagram, encoded as JSON value. This is really [`IbcSpec::Datagram`],
@@ -102,6 +135,7 @@ pub struct UnfinalizedTrustedClientState {
 }
 
 #[model]
+#[derive(JsonSchema)]
 pub struct DecodedHeaderMeta {
     /// The new trusted height that the header provides a consensus update to.
     pub height: Height,
@@ -109,23 +143,130 @@ pub struct DecodedHeaderMeta {
 
 // client update plugins produce this data which is then used when constructing the OrderedClientUpdates
 #[model]
+#[derive(JsonSchema)]
 pub struct OrderedHeaders {
     pub headers: Vec<(DecodedHeaderMeta, Value)>,
 }
 
 #[model]
+#[derive(JsonSchema)]
 pub struct OrderedClientUpdates {
     pub updates: Vec<(DecodedHeaderMeta, ClientUpdate)>,
 }
 
 #[model]
+#[derive(JsonSchema)]
 pub struct ClientUpdate {
     pub client_id: RawClientId,
     pub ibc_spec_id: IbcSpecId,
     pub client_message: Bytes,
 }
 
+/// The receipt of a transaction submitted by a transaction plugin.
+///
+/// This is produced once the transaction is included, and can be used by callbacks to act on the
+/// outcome of the submitted datagrams (such as the id assigned to a newly opened connection or
+/// channel) without querying the chain again.
+#[model]
+#[derive(JsonSchema)]
+pub struct TxReceipt {
+    pub tx_hash: H256,
+    /// The height of the block that the transaction was included in.
+    pub height: u64,
+    pub gas_used: u64,
+    /// The fee paid for the transaction, if known.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fee: Option<TxFee>,
+    /// The messages included in the transaction, in the order they were submitted. Used to
+    /// attribute the cost of the transaction, see [`TxCosts`](crate::costs::TxCosts).
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub msgs: Vec<TxMsg>,
+    /// The IBC events emitted by the transaction, in the order they were emitted.
+    pub events: Vec<TxEvent>,
+}
+
+impl TxReceipt {
+    /// Returns the attribute `key` of the first event named `name`, if any.
+    #[must_use]
+    pub fn event_attribute(&self, name: &str, key: &str) -> Option<&Value> {
+        self.events
+            .iter()
+            .find(|event| event.name == name)
+            .and_then(|event| event.attributes.get(key))
+    }
+}
+
+#[model]
+#[derive(JsonSchema)]
+pub struct TxFee {
+    /// The amount paid, in the smallest unit of `denom`.
+    #[serde(with = "::serde_utils::string")]
+    #[schemars(with = "String")]
+    pub amount: u128,
+    pub denom: String,
+}
+
+/// A message included in a transaction.
+#[model]
+#[derive(JsonSchema)]
+pub struct TxMsg {
+    /// The type of the message, i.e. the type url on cosmos-sdk chains.
+    pub msg_type: String,
+    /// The client targeted by the message, if it targets one directly.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_id: Option<RawClientId>,
+    /// The error the message failed with, if the transaction was included but the message itself
+    /// failed (i.e. a call in a multicall that allows failures).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl TxMsg {
+    /// The [`TxMsg`] for an IBC datagram, encoded as JSON. The client is taken from the
+    /// `client_id` field of the datagram, if it has one.
+    #[must_use]
+    pub fn from_datagram(msg_type: impl Into<String>, datagram: &Value) -> Self {
+        Self {
+            msg_type: msg_type.into(),
+            client_id: client_id_of(datagram).cloned().map(RawClientId),
+            error: None,
+        }
+    }
+}
+
+/// The `client_id` of an IBC event or datagram, encoded as JSON. Both are `#[model]` enums, so
+/// the fields are under `@value`.
+pub(crate) fn client_id_of(value: &Value) -> Option<&Value> {
+    value.get("@value")?.get("client_id")
+}
+
+/// The balance of one of the signers of a transaction plugin.
+#[model]
+#[derive(JsonSchema)]
+pub struct SignerBalance {
+    /// The name of the key in the keyring of the plugin.
+    pub key_name: String,
+    pub address: String,
+    /// The balance, in the smallest unit of `denom`.
+    #[serde(with = "::serde_utils::string")]
+    #[schemars(with = "String")]
+    pub balance: u128,
+    /// The denom that fees are paid in.
+    pub denom: String,
+}
+
+/// An event emitted by a transaction, in the native representation of the chain.
+#[model]
+#[derive(JsonSchema)]
+pub struct TxEvent {
+    /// The event type on cosmos-sdk chains, or the event name on EVM chains.
+    pub name: String,
+    /// The attributes (or fields) of the event, as a JSON object.
+    pub attributes: Value,
+}
+
 #[model]
+#[derive(JsonSchema)]
 pub struct WithChainId<T> {
     pub chain_id: ChainId,
     pub message: T,
@@ -0,0 +1,229 @@
+//! Deduplication of the events returned from plugins.
+//!
+//! Running several event source plugins for the same chain (i.e. subscribed to different RPC
+//! providers, for redundancy) causes every event on that chain to be returned once per plugin.
+//! [`EventDedup`] remembers the events it has seen within a configurable window, and drops any
+//! event that has already been seen, such that redundant event sources can run simultaneously
+//! without every packet being relayed multiple times.
+//!
+//! [`ChainEvent`] does not carry the block hash or the index of the event within the block, so
+//! events are identified by the chain, the transaction, and the event itself instead. This is
+//! equivalent for all practical purposes, since a transaction is only included once, and two
+//! identical IBC events can not be emitted by the same transaction.
+
+use std::{
+    collections::{hash_map::DefaultHasher, HashSet, VecDeque},
+    hash::{Hash, Hasher},
+    sync::{Arc, LazyLock, Mutex},
+    time::{Duration, Instant},
+};
+
+use prometheus::{register_int_counter_vec, IntCounterVec};
+use tracing::debug;
+use unionlabs::hash::H256;
+use voyager_vm::Op;
+
+use crate::{
+    core::{ChainId, IbcSpecId},
+    data::{ChainEvent, Data},
+    VoyagerMessage,
+};
+
+/// The default window within which duplicate events are dropped, in seconds.
+pub const DEFAULT_EVENT_DEDUP_WINDOW_SECONDS: u64 = 600;
+
+pub static DUPLICATE_EVENTS_DROPPED: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!(
+        "voyager_duplicate_events_dropped_total",
+        "The amount of events returned from plugins that were dropped as duplicates of an event \
+        that was already seen.",
+        &["chain_id"],
+    )
+    .unwrap()
+});
+
+#[derive(Debug, Clone)]
+pub struct EventDedup {
+    window: Duration,
+    inner: Arc<Mutex<Seen>>,
+}
+
+#[derive(Debug, Default)]
+struct Seen {
+    keys: HashSet<EventKey>,
+    /// The keys in [`Self::keys`], in the order they were first seen.
+    expiry: VecDeque<(Instant, EventKey)>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct EventKey {
+    chain_id: ChainId,
+    tx_hash: H256,
+    ibc_spec_id: IbcSpecId,
+    /// A hash of the JSON encoding of [`ChainEvent::event`].
+    event_hash: u64,
+}
+
+impl EventKey {
+    fn new(event: &ChainEvent) -> Self {
+        let mut hasher = DefaultHasher::new();
+        event.event.to_string().hash(&mut hasher);
+
+        Self {
+            chain_id: event.chain_id.clone(),
+            tx_hash: event.tx_hash,
+            ibc_spec_id: event.ibc_spec_id.clone(),
+            event_hash: hasher.finish(),
+        }
+    }
+}
+
+impl Default for EventDedup {
+    fn default() -> Self {
+        Self::new(Duration::from_secs(DEFAULT_EVENT_DEDUP_WINDOW_SECONDS))
+    }
+}
+
+impl EventDedup {
+    /// Drop events that have already been seen within `window`. A zero window disables
+    /// deduplication.
+    #[must_use]
+    pub fn new(window: Duration) -> Self {
+        Self {
+            window,
+            inner: Arc::default(),
+        }
+    }
+
+    /// Whether `event` has already been seen within the window. The event is marked as seen if it
+    /// has not.
+    pub fn is_duplicate(&self, event: &ChainEvent) -> bool {
+        self.is_duplicate_at(event, Instant::now())
+    }
+
+    fn is_duplicate_at(&self, event: &ChainEvent, now: Instant) -> bool {
+        if self.window.is_zero() {
+            return false;
+        }
+
+        let mut seen = self.inner.lock().expect("mutex is poisoned");
+
+        while let Some((first_seen, _)) = seen.expiry.front() {
+            if now.saturating_duration_since(*first_seen) < self.window {
+                break;
+            }
+
+            let (_, key) = seen.expiry.pop_front().expect("front exists; qed;");
+            seen.keys.remove(&key);
+        }
+
+        let key = EventKey::new(event);
+
+        if seen.keys.contains(&key) {
+            true
+        } else {
+            seen.keys.insert(key.clone());
+            seen.expiry.push_back((now, key));
+            false
+        }
+    }
+
+    /// Replace all events in `op` that have already been seen with [`Op::Noop`].
+    ///
+    /// Only events that will be sent as outputs are deduplicated; events that are awaited by a
+    /// promise are left as-is, as the promise would otherwise never receive them.
+    pub fn dedup_op(&self, op: &mut Op<VoyagerMessage>) {
+        match op {
+            Op::Data(Data::IbcEvent(event)) => {
+                if self.is_duplicate(event) {
+                    debug!(
+                        chain_id = %event.chain_id,
+                        tx_hash = %event.tx_hash,
+                        "dropping duplicate event"
+                    );
+
+                    DUPLICATE_EVENTS_DROPPED
+                        .with_label_values(&[event.chain_id.as_str()])
+                        .inc();
+
+                    *op = Op::Noop;
+                }
+            }
+            Op::Seq(ops) | Op::Conc(ops) => ops.iter_mut().for_each(|op| self.dedup_op(op)),
+            Op::Void(msg) | Op::Retry { msg, .. } | Op::WithPriority { msg, .. } => {
+                self.dedup_op(msg);
+            }
+            Op::Data(_) | Op::Call(_) | Op::Defer { .. } | Op::Promise(_) | Op::Noop => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ibc_union_spec::IbcUnion;
+    use serde_json::json;
+    use unionlabs::ibc::core::client::height::Height;
+
+    use super::*;
+    use crate::core::{ClientInfo, ClientType, IbcInterface, IbcSpec};
+
+    fn event(tx_hash: H256, sequence: u64) -> ChainEvent {
+        ChainEvent {
+            chain_id: ChainId::new("chain"),
+            client_info: ClientInfo {
+                client_type: ClientType::new("client"),
+                ibc_interface: IbcInterface::new("interface"),
+                metadata: Default::default(),
+            },
+            counterparty_chain_id: ChainId::new("counterparty"),
+            tx_hash,
+            provable_height: Height::new(1),
+            ibc_spec_id: IbcUnion::ID,
+            event: json!({ "sequence": sequence, "client_id": 1 }),
+            decoded_packet_data: None,
+        }
+    }
+
+    #[test]
+    fn duplicates_within_window_are_dropped() {
+        let dedup = EventDedup::new(Duration::from_secs(10));
+        let now = Instant::now();
+
+        assert!(!dedup.is_duplicate_at(&event(H256::new([1; 32]), 1), now));
+        assert!(dedup.is_duplicate_at(&event(H256::new([1; 32]), 1), now));
+
+        // different event in the same transaction
+        assert!(!dedup.is_duplicate_at(&event(H256::new([1; 32]), 2), now));
+        // same event in a different transaction
+        assert!(!dedup.is_duplicate_at(&event(H256::new([2; 32]), 1), now));
+
+        // seen events expire after the window
+        assert!(
+            !dedup.is_duplicate_at(&event(H256::new([1; 32]), 1), now + Duration::from_secs(10))
+        );
+    }
+
+    #[test]
+    fn dedup_op_replaces_duplicates_with_noop() {
+        let dedup = EventDedup::default();
+
+        let mut first = voyager_vm::conc([
+            Op::Data(Data::IbcEvent(event(H256::new([1; 32]), 1))),
+            Op::Data(Data::IbcEvent(event(H256::new([1; 32]), 2))),
+        ]);
+        dedup.dedup_op(&mut first);
+
+        let mut second = voyager_vm::conc([
+            Op::Data(Data::IbcEvent(event(H256::new([1; 32]), 2))),
+            Op::Data(Data::IbcEvent(event(H256::new([1; 32]), 3))),
+        ]);
+        dedup.dedup_op(&mut second);
+
+        let Op::Conc(ops) = second else {
+            panic!("expected conc, found {second:?}");
+        };
+
+        assert!(matches!(ops[0], Op::Noop));
+        assert!(matches!(ops[1], Op::Data(Data::IbcEvent(_))));
+    }
+}
@@ -0,0 +1,591 @@
+//! Decoding of the application level data carried by packet events.
+//!
+//! The packet data in [`ChainEvent`]s is opaque bytes, the interpretation of which is defined by
+//! the application on either end of the channel. The [`PacketDataDecoders`] registry attempts to
+//! decode the packet data of all events produced by plugins with the decoders for the known
+//! applications, and attaches the result to the event as [`ChainEvent::decoded_packet_data`]. This
+//! allows for interest filters and metrics to key on the contents of the packets (sender,
+//! receiver, denom, amount, ...) without having to understand the wire format of each application.
+
+use std::collections::BTreeMap;
+
+use alloy::sol_types::{SolType, SolValue};
+use macros::model;
+use schemars::JsonSchema;
+use serde_json::Value;
+use tracing::trace;
+use unionlabs::bytes::Bytes;
+use voyager_vm::{Op, Visit};
+
+use crate::{
+    data::{ChainEvent, Data},
+    VoyagerMessage,
+};
+
+/// The decoded data of a packet, for any of the known applications.
+#[model]
+#[derive(JsonSchema)]
+pub enum DecodedPacketData {
+    /// ICS-20 fungible token transfer.
+    Ics20(FungibleTokenPacketData),
+    /// ICS-721 non-fungible token transfer.
+    Ics721(NonFungibleTokenPacketData),
+    /// UCS-01 relay (union's multi-token transfer protocol).
+    Ucs01(Ucs01TransferPacket),
+    /// UCS-00 pingpong.
+    Ucs00(PingPongPacket),
+    /// Packet data decoded by a custom decoder registered with
+    /// [`PacketDataDecoders::register`].
+    Other { app: String, data: Value },
+}
+
+/// <https://github.com/cosmos/ibc/tree/main/spec/app/ics-020-fungible-token-transfer#data-structures>
+#[model]
+#[derive(JsonSchema)]
+pub struct FungibleTokenPacketData {
+    pub denom: String,
+    /// The amount, as a decimal string. This is a 256 bit unsigned integer in ibc-go.
+    pub amount: String,
+    pub sender: String,
+    pub receiver: String,
+    #[serde(default)]
+    pub memo: String,
+}
+
+/// <https://github.com/cosmos/ibc/tree/main/spec/app/ics-721-nft-transfer#data-structures>
+#[model]
+#[derive(JsonSchema)]
+pub struct NonFungibleTokenPacketData {
+    #[serde(rename = "classId")]
+    pub class_id: String,
+    #[serde(rename = "classUri", default, skip_serializing_if = "Option::is_none")]
+    pub class_uri: Option<String>,
+    /// Base64 encoded class metadata.
+    #[serde(rename = "classData", default, skip_serializing_if = "Option::is_none")]
+    pub class_data: Option<String>,
+    #[serde(rename = "tokenIds")]
+    pub token_ids: Vec<String>,
+    /// Either empty, or the uri of each token in `token_ids`.
+    #[serde(rename = "tokenUris", default)]
+    pub token_uris: Vec<String>,
+    /// Either empty, or the base64 encoded metadata of each token in `token_ids`.
+    #[serde(rename = "tokenData", default)]
+    pub token_data: Vec<String>,
+    pub sender: String,
+    pub receiver: String,
+    #[serde(default)]
+    pub memo: String,
+}
+
+#[model]
+#[derive(JsonSchema)]
+pub struct Ucs01TransferPacket {
+    pub sender: Bytes,
+    pub receiver: Bytes,
+    pub tokens: Vec<Ucs01TransferToken>,
+    pub memo: String,
+}
+
+#[model]
+#[derive(JsonSchema)]
+pub struct Ucs01TransferToken {
+    pub denom: String,
+    #[serde(with = "::serde_utils::string")]
+    #[schemars(with = "String")]
+    pub amount: u128,
+    #[serde(with = "::serde_utils::string")]
+    #[schemars(with = "String")]
+    pub fee: u128,
+}
+
+#[model]
+#[derive(JsonSchema)]
+pub struct PingPongPacket {
+    pub ping: bool,
+}
+
+alloy::sol! {
+    struct SolUcs01TransferToken {
+        string denom;
+        uint128 amount;
+        uint128 fee;
+    }
+
+    struct SolUcs01TransferPacket {
+        bytes sender;
+        bytes receiver;
+        SolUcs01TransferToken[] tokens;
+        string memo;
+    }
+}
+
+/// Attempt to decode raw packet data. Decoders must be strict, since they are tried in order
+/// until one succeeds.
+pub type PacketDataDecoder = fn(&[u8]) -> Option<DecodedPacketData>;
+
+/// An ordered registry of [`PacketDataDecoder`]s.
+#[derive(Debug, Clone)]
+pub struct PacketDataDecoders {
+    decoders: Vec<(String, PacketDataDecoder)>,
+}
+
+impl Default for PacketDataDecoders {
+    /// A registry containing the decoders for ICS-20, ICS-721, UCS-01 and UCS-00.
+    fn default() -> Self {
+        Self::empty()
+            .register("ics20", decode_ics20)
+            .register("ics721", decode_ics721)
+            .register("ucs01", decode_ucs01)
+            .register("ucs00", decode_ucs00)
+    }
+}
+
+impl PacketDataDecoders {
+    #[must_use]
+    pub fn empty() -> Self {
+        Self { decoders: vec![] }
+    }
+
+    /// Register a decoder, to be tried after all of the currently registered decoders.
+    #[must_use]
+    pub fn register(mut self, name: impl Into<String>, decoder: PacketDataDecoder) -> Self {
+        self.decoders.push((name.into(), decoder));
+        self
+    }
+
+    /// Decode `packet_data` with the first registered decoder that accepts it.
+    pub fn decode(&self, packet_data: &[u8]) -> Option<DecodedPacketData> {
+        self.decoders.iter().find_map(|(name, decoder)| {
+            let decoded = decoder(packet_data);
+
+            if decoded.is_some() {
+                trace!(decoder = %name, "decoded packet data");
+            }
+
+            decoded
+        })
+    }
+
+    /// Decode the packet data of `event`, if it is a packet event.
+    pub fn decode_event(&self, event: &ChainEvent) -> Option<DecodedPacketData> {
+        self.decode(&packet_data(event)?)
+    }
+
+    /// Decode the packet data of all events in `op`, including those nested in the queues of
+    /// promises. Events that have already been decoded are left as-is.
+    pub fn decode_op(&self, op: &mut Op<VoyagerMessage>) {
+        DecodeVisitor(self).visit_op(op);
+    }
+}
+
+struct DecodeVisitor<'a>(&'a PacketDataDecoders);
+
+impl Visit<VoyagerMessage> for DecodeVisitor<'_> {
+    fn visit_data(&mut self, data: &mut Data) {
+        if let Data::IbcEvent(event) = data {
+            if event.decoded_packet_data.is_none() {
+                event.decoded_packet_data = self.0.decode_event(event);
+            }
+        }
+    }
+}
+
+/// The raw packet data of a packet event, for any of the IBC specifications supported by voyager.
+fn packet_data(event: &ChainEvent) -> Option<Bytes> {
+    if let Some(event) = event.decode_event::<ibc_classic_spec::IbcClassic>() {
+        use ibc_classic_spec::FullEvent;
+
+        return match event.ok()? {
+            FullEvent::SendPacket(event) => Some(event.packet_data),
+            FullEvent::RecvPacket(event) => Some(event.packet_data),
+            FullEvent::WriteAcknowledgement(event) => Some(event.packet_data),
+            _ => None,
+        };
+    }
+
+    if let Some(event) = event.decode_event::<ibc_union_spec::IbcUnion>() {
+        use ibc_union_spec::FullEvent;
+
+        return match event.ok()? {
+            FullEvent::SendPacket(event) => Some(event.packet_data),
+            FullEvent::RecvPacket(event) => Some(event.packet_data),
+            FullEvent::RecvIntentPacket(event) => Some(event.packet_data),
+            FullEvent::WriteAcknowledgement(event) => Some(event.packet_data),
+            FullEvent::AcknowledgePacket(event) => Some(event.packet_data),
+            FullEvent::TimeoutPacket(event) => Some(event.packet_data),
+            _ => None,
+        };
+    }
+
+    None
+}
+
+pub fn decode_ics20(packet_data: &[u8]) -> Option<DecodedPacketData> {
+    serde_json::from_slice::<FungibleTokenPacketData>(packet_data)
+        .ok()
+        .filter(|data| data.amount.parse::<alloy::primitives::U256>().is_ok())
+        .map(DecodedPacketData::Ics20)
+}
+
+pub fn decode_ics721(packet_data: &[u8]) -> Option<DecodedPacketData> {
+    serde_json::from_slice::<NonFungibleTokenPacketData>(packet_data)
+        .ok()
+        .filter(|data| {
+            !data.token_ids.is_empty()
+                && (data.token_uris.is_empty() || data.token_uris.len() == data.token_ids.len())
+                && (data.token_data.is_empty() || data.token_data.len() == data.token_ids.len())
+        })
+        .map(DecodedPacketData::Ics721)
+}
+
+pub fn decode_ucs01(packet_data: &[u8]) -> Option<DecodedPacketData> {
+    let packet = SolUcs01TransferPacket::abi_decode_params(packet_data, true).ok()?;
+
+    Some(DecodedPacketData::Ucs01(Ucs01TransferPacket {
+        sender: packet.sender.to_vec().into(),
+        receiver: packet.receiver.to_vec().into(),
+        tokens: packet
+            .tokens
+            .into_iter()
+            .map(|token| Ucs01TransferToken {
+                denom: token.denom,
+                amount: token.amount,
+                fee: token.fee,
+            })
+            .collect(),
+        memo: packet.memo,
+    }))
+}
+
+pub fn decode_ucs00(packet_data: &[u8]) -> Option<DecodedPacketData> {
+    // a single abi encoded bool; anything else is not a pingpong packet
+    if packet_data.len() != 32 {
+        return None;
+    }
+
+    bool::abi_decode(packet_data, true)
+        .ok()
+        .map(|ping| DecodedPacketData::Ucs00(PingPongPacket { ping }))
+}
+
+/// An application level event, emitted by an IBC application alongside the core IBC events.
+#[model]
+#[derive(JsonSchema)]
+pub enum DecodedAppEvent {
+    Ucs01(Ucs01RelayEvent),
+}
+
+/// The wasm events emitted by the ucs01-relay contract. See `ucs01-relay-api` for the emitting side.
+#[model]
+#[derive(JsonSchema)]
+pub enum Ucs01RelayEvent {
+    /// `wasm-ibc_transfer`, emitted when a transfer is sent.
+    Transfer(Ucs01TransferEvent),
+    /// `wasm-fungible_token_packet`, emitted when a transfer is received.
+    Recv(Ucs01RecvEvent),
+    /// `wasm-fungible_token_packet`, emitted when a received transfer could not be processed.
+    RecvFailure(Ucs01RecvFailureEvent),
+    /// `wasm-fungible_token_packet`, emitted when a sent transfer is acknowledged.
+    Acknowledgement(Ucs01AcknowledgementEvent),
+    /// `wasm-fungible_token_packet`, emitted when a sent transfer times out and is refunded.
+    Timeout(Ucs01TimeoutEvent),
+    /// `wasm-packet_forward_hop`, emitted when a received transfer is forwarded to another chain.
+    ForwardHop(Ucs01ForwardHopEvent),
+}
+
+#[model]
+#[derive(JsonSchema)]
+pub struct Ucs01TransferEvent {
+    pub sender: String,
+    pub receiver: String,
+    pub memo: String,
+    /// The amounts transferred, excluding the fees.
+    pub assets: Vec<Coin>,
+    pub fee_assets: Vec<Coin>,
+}
+
+#[model]
+#[derive(JsonSchema)]
+pub struct Ucs01RecvEvent {
+    pub sender: String,
+    pub receiver: String,
+    pub memo: String,
+    pub assets: Vec<Coin>,
+    pub fee_assets: Vec<Coin>,
+}
+
+#[model]
+#[derive(JsonSchema)]
+pub struct Ucs01RecvFailureEvent {
+    pub error: String,
+}
+
+#[model]
+#[derive(JsonSchema)]
+pub struct Ucs01AcknowledgementEvent {
+    pub sender: String,
+    pub receiver: String,
+    pub memo: String,
+    /// The base64 encoded acknowledgement.
+    pub acknowledgement: String,
+    pub assets: Vec<Coin>,
+    pub fee_assets: Vec<Coin>,
+}
+
+#[model]
+#[derive(JsonSchema)]
+pub struct Ucs01TimeoutEvent {
+    pub refund_receiver: String,
+    pub memo: String,
+    pub assets: Vec<Coin>,
+    pub fee_assets: Vec<Coin>,
+}
+
+#[model]
+#[derive(JsonSchema)]
+pub struct Ucs01ForwardHopEvent {
+    /// The received packet, as a cosmwasm `IbcPacket`.
+    pub src_packet: Value,
+    /// The forwarded packet, as a cosmwasm `IbcPacket`.
+    pub dst_packet: Value,
+}
+
+#[model]
+#[derive(JsonSchema)]
+pub struct Coin {
+    pub denom: String,
+    #[serde(with = "::serde_utils::string")]
+    #[schemars(with = "String")]
+    pub amount: u128,
+}
+
+/// Decode an event emitted by the ucs01-relay contract. `ty` is the type of the event as emitted by
+/// the chain, i.e. prefixed with `wasm-`.
+///
+/// Returns `None` if the event is not one of the ucs01-relay events, or if any of the expected
+/// attributes are missing or malformed.
+pub fn decode_ucs01_relay_event<'a>(
+    ty: &str,
+    attributes: impl IntoIterator<Item = (&'a str, &'a str)>,
+) -> Option<Ucs01RelayEvent> {
+    let attributes = attributes.into_iter().collect::<BTreeMap<_, _>>();
+
+    let attr = |key: &str| attributes.get(key).map(|value| (*value).to_owned());
+    let memo = || attr("memo").unwrap_or_default();
+    let coins = |key: &str| serde_json::from_str::<Vec<Coin>>(attributes.get(key)?).ok();
+    let json = |key: &str| serde_json::from_str::<Value>(attributes.get(key)?).ok();
+
+    let event = match ty.strip_prefix("wasm-")? {
+        "ibc_transfer" => Ucs01RelayEvent::Transfer(Ucs01TransferEvent {
+            sender: attr("sender")?,
+            receiver: attr("receiver")?,
+            memo: memo(),
+            assets: coins("assets")?,
+            fee_assets: coins("fee_assets")?,
+        }),
+        "fungible_token_packet" if attributes.contains_key("acknowledgement") => {
+            Ucs01RelayEvent::Acknowledgement(Ucs01AcknowledgementEvent {
+                sender: attr("sender")?,
+                receiver: attr("receiver")?,
+                memo: memo(),
+                acknowledgement: attr("acknowledgement")?,
+                assets: coins("assets")?,
+                fee_assets: coins("fee_assets")?,
+            })
+        }
+        "fungible_token_packet" if attributes.contains_key("refund_receiver") => {
+            Ucs01RelayEvent::Timeout(Ucs01TimeoutEvent {
+                refund_receiver: attr("refund_receiver")?,
+                memo: memo(),
+                assets: coins("assets")?,
+                fee_assets: coins("fee_assets")?,
+            })
+        }
+        // the result of an acknowledgement is emitted in a separate event without a module, which
+        // is redundant with the acknowledgement itself
+        "fungible_token_packet" if attributes.get("module") == Some(&"transfer") => {
+            match *attributes.get("success")? {
+                "true" => Ucs01RelayEvent::Recv(Ucs01RecvEvent {
+                    sender: attr("sender")?,
+                    receiver: attr("receiver")?,
+                    memo: memo(),
+                    assets: coins("assets")?,
+                    fee_assets: coins("fee_assets")?,
+                }),
+                "false" => Ucs01RelayEvent::RecvFailure(Ucs01RecvFailureEvent {
+                    error: attr("error")?,
+                }),
+                _ => return None,
+            }
+        }
+        "packet_forward_hop" => Ucs01RelayEvent::ForwardHop(Ucs01ForwardHopEvent {
+            src_packet: json("src_packet")?,
+            dst_packet: json("dst_packet")?,
+        }),
+        _ => return None,
+    };
+
+    trace!(%ty, "decoded ucs01-relay event");
+
+    Some(event)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decode_known_applications() {
+        let decoders = PacketDataDecoders::default();
+
+        assert_eq!(
+            decoders.decode(
+                br#"{"denom":"transfer/channel-0/uatom","amount":"100","sender":"cosmos1a","receiver":"union1b"}"#
+            ),
+            Some(DecodedPacketData::Ics20(FungibleTokenPacketData {
+                denom: "transfer/channel-0/uatom".to_owned(),
+                amount: "100".to_owned(),
+                sender: "cosmos1a".to_owned(),
+                receiver: "union1b".to_owned(),
+                memo: String::new(),
+            }))
+        );
+
+        assert_eq!(
+            decoders.decode(
+                br#"{"classId":"transfer-nft/channel-0/stars1c","classUri":"https://example.com","tokenIds":["1","2"],"sender":"stars1a","receiver":"union1b"}"#
+            ),
+            Some(DecodedPacketData::Ics721(NonFungibleTokenPacketData {
+                class_id: "transfer-nft/channel-0/stars1c".to_owned(),
+                class_uri: Some("https://example.com".to_owned()),
+                class_data: None,
+                token_ids: vec!["1".to_owned(), "2".to_owned()],
+                token_uris: vec![],
+                token_data: vec![],
+                sender: "stars1a".to_owned(),
+                receiver: "union1b".to_owned(),
+                memo: String::new(),
+            }))
+        );
+
+        // the token uris must match the token ids
+        assert_eq!(
+            decoders.decode(
+                br#"{"classId":"c","tokenIds":["1","2"],"tokenUris":["a"],"sender":"a","receiver":"b"}"#
+            ),
+            None
+        );
+
+        let ucs01 = SolUcs01TransferPacket {
+            sender: vec![1; 20].into(),
+            receiver: vec![2; 20].into(),
+            tokens: vec![SolUcs01TransferToken {
+                denom: "muno".to_owned(),
+                amount: 100,
+                fee: 1,
+            }],
+            memo: "memo".to_owned(),
+        };
+
+        assert_eq!(
+            decoders.decode(&SolUcs01TransferPacket::abi_encode_params(&ucs01)),
+            Some(DecodedPacketData::Ucs01(Ucs01TransferPacket {
+                sender: vec![1; 20].into(),
+                receiver: vec![2; 20].into(),
+                tokens: vec![Ucs01TransferToken {
+                    denom: "muno".to_owned(),
+                    amount: 100,
+                    fee: 1,
+                }],
+                memo: "memo".to_owned(),
+            }))
+        );
+
+        assert_eq!(
+            decoders.decode(&true.abi_encode()),
+            Some(DecodedPacketData::Ucs00(PingPongPacket { ping: true }))
+        );
+
+        assert_eq!(decoders.decode(b"\x00\x01"), None);
+    }
+
+    #[test]
+    fn decode_ucs01_relay_events() {
+        let assets = r#"[{"denom":"muno","amount":"99"}]"#;
+        let fee_assets = r#"[{"denom":"muno","amount":"1"}]"#;
+
+        assert_eq!(
+            decode_ucs01_relay_event(
+                "wasm-ibc_transfer",
+                [
+                    ("_contract_address", "union1relay"),
+                    ("sender", "union1a"),
+                    ("receiver", "0x0102"),
+                    ("assets", assets),
+                    ("fee_assets", fee_assets),
+                ]
+            ),
+            Some(Ucs01RelayEvent::Transfer(Ucs01TransferEvent {
+                sender: "union1a".to_owned(),
+                receiver: "0x0102".to_owned(),
+                memo: String::new(),
+                assets: vec![Coin {
+                    denom: "muno".to_owned(),
+                    amount: 99,
+                }],
+                fee_assets: vec![Coin {
+                    denom: "muno".to_owned(),
+                    amount: 1,
+                }],
+            }))
+        );
+
+        assert_eq!(
+            decode_ucs01_relay_event(
+                "wasm-fungible_token_packet",
+                [
+                    ("module", "transfer"),
+                    ("success", "false"),
+                    ("error", "insufficient funds"),
+                ]
+            ),
+            Some(Ucs01RelayEvent::RecvFailure(Ucs01RecvFailureEvent {
+                error: "insufficient funds".to_owned(),
+            }))
+        );
+
+        assert!(matches!(
+            decode_ucs01_relay_event(
+                "wasm-fungible_token_packet",
+                [
+                    ("memo", "hi"),
+                    ("module", "transfer"),
+                    ("refund_receiver", "union1a"),
+                    ("assets", assets),
+                    ("fee_assets", fee_assets),
+                ]
+            ),
+            Some(Ucs01RelayEvent::Timeout(Ucs01TimeoutEvent { memo, .. })) if memo == "hi"
+        ));
+
+        // the result of an acknowledgement
+        assert_eq!(
+            decode_ucs01_relay_event("wasm-fungible_token_packet", [("success", "AQ==")]),
+            None
+        );
+
+        // not emitted by a contract
+        assert_eq!(
+            decode_ucs01_relay_event(
+                "ibc_transfer",
+                [
+                    ("sender", "a"),
+                    ("receiver", "b"),
+                    ("assets", "[]"),
+                    ("fee_assets", "[]")
+                ]
+            ),
+            None
+        );
+    }
+}
@@ -1,4 +1,7 @@
-use std::rc::Rc;
+use std::{
+    rc::Rc,
+    sync::{Arc, RwLock},
+};
 
 use anyhow::anyhow;
 use jaq_interpret::{Ctx, Filter, FilterT, ParseCtx, RcIter, Val};
@@ -11,9 +14,11 @@ use voyager_vm::{
 
 use crate::{module::PluginInfo, VoyagerMessage};
 
+/// The interest filters of all loaded plugins. Clones share the same filters, such that updates
+/// made with [`Self::update`] are visible to all of them.
 #[derive(Debug, Clone)]
 pub struct JaqInterestFilter {
-    pub filters: Vec<(Filter, String)>,
+    pub filters: Vec<(Arc<RwLock<Filter>>, String)>,
 }
 
 impl JaqInterestFilter {
@@ -21,10 +26,27 @@ impl JaqInterestFilter {
         Ok(Self {
             filters: filters
                 .into_iter()
-                .map(make_filter)
+                .map(|info| {
+                    make_filter(info).map(|(filter, name)| (Arc::new(RwLock::new(filter)), name))
+                })
                 .collect::<anyhow::Result<_>>()?,
         })
     }
+
+    /// Replace the interest filter of an already loaded plugin.
+    pub fn update(&self, plugin_info: PluginInfo) -> anyhow::Result<()> {
+        let (filter, name) = make_filter(plugin_info)?;
+
+        let (current, _) = self
+            .filters
+            .iter()
+            .find(|(_, plugin_name)| *plugin_name == name)
+            .ok_or_else(|| anyhow!("plugin `{name}` is not loaded"))?;
+
+        *current.write().expect("lock is poisoned") = filter;
+
+        Ok(())
+    }
 }
 
 pub fn make_filter(
@@ -81,7 +103,9 @@ impl InterestFilter<VoyagerMessage> for JaqInterestFilter {
         let msg_json = Val::from(serde_json::to_value(op.clone()).unwrap());
 
         for (filter, plugin_name) in &self.filters {
-            match run_filter(filter, plugin_name, msg_json.clone()) {
+            let filter = filter.read().expect("lock is poisoned");
+
+            match run_filter(&filter, plugin_name, msg_json.clone()) {
                 Ok(interest @ FilterResult::Interest(_)) => return interest,
                 Ok(FilterResult::NoInterest) => {}
                 Err(_) => {}
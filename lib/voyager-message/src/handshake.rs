@@ -0,0 +1,725 @@
+//! Initiation of [`IbcUnion`] connection and channel handshakes.
+//!
+//! Only the first step of a handshake is submitted directly. Every following step is built by the
+//! transaction-batch plugin of the destination chain in response to the event emitted by the
+//! previous step, which includes waiting for the client on the destination chain to be updated to
+//! a height at which that event is provable.
+//!
+//! Since the handshake is driven by events, an interrupted handshake (for example, if the queue
+//! was lost on restart) can be resumed by re-emitting the event of the last step that completed on
+//! either end. The helpers in this module detect the current state of the handshake and do exactly
+//! that, only initiating a new handshake if there is no unfinished one to resume. A specific
+//! connection or channel can also be resumed directly with [`resume_connection_handshake`] and
+//! [`resume_channel_handshake`].
+
+use ibc_solidity::{Channel, ChannelState, Connection, ConnectionState};
+use ibc_union_spec::{
+    ChannelOpenAck, ChannelOpenInit, ChannelOpenTry, ChannelPath, ConnectionOpenAck,
+    ConnectionOpenInit, ConnectionOpenTry, ConnectionPath, FullEvent, IbcUnion, MsgChannelOpenInit,
+    MsgConnectionOpenInit,
+};
+use jsonrpsee::core::RpcResult;
+use tracing::{debug, info};
+use unionlabs::{bytes::Bytes, hash::H256};
+use voyager_core::{IbcSpec, IbcStorePathKey, QueryHeight};
+use voyager_vm::{data, noop, Op};
+
+use crate::{
+    core::ChainId,
+    data::{ChainEvent, IbcDatagram, WithChainId},
+    into_value,
+    rpc::{
+        json_rpc_error_to_error_object, missing_state, query_latest_ibc_state, VoyagerRpcClient,
+    },
+    RawClientId, VoyagerMessage,
+};
+
+/// Build the op to open a connection between `client_a` on `chain_a` and `client_b` on `chain_b`.
+///
+/// If the newest connection on `chain_a` between these clients is not yet open on both ends, the
+/// handshake of that connection is resumed instead of initiating a new one.
+///
+/// Note that this queries every connection on both chains, and as such may take a while on chains
+/// with many connections.
+pub async fn init_connection(
+    client: &impl VoyagerRpcClient,
+    chain_a: ChainId,
+    chain_b: ChainId,
+    client_a: u32,
+    client_b: u32,
+) -> RpcResult<Op<VoyagerMessage>> {
+    let existing = find_last(
+        client,
+        &chain_a,
+        |connection_id| ConnectionPath { connection_id },
+        |connection: &Connection| {
+            connection.client_id == client_a && connection.counterparty_client_id == client_b
+        },
+    )
+    .await?;
+
+    let Some((connection_id_a, connection_a)) = existing else {
+        info!(%chain_a, %client_a, %chain_b, %client_b, "initiating new connection handshake");
+
+        return Ok(init_connection_op(chain_a, client_a, client_b));
+    };
+
+    match resume_connection(client, &chain_a, &chain_b, connection_id_a, &connection_a).await? {
+        Some(op) => Ok(op),
+        None => {
+            info!(
+                %connection_id_a,
+                "existing connection is already open, initiating new connection handshake"
+            );
+
+            Ok(init_connection_op(chain_a, client_a, client_b))
+        }
+    }
+}
+
+/// Build the op to resume the handshake of the connection `connection_id` on `chain_id`, for
+/// example after a previous relayer instance stopped between two steps of the handshake.
+///
+/// The state of the connection on both chains is queried to determine the last step that was
+/// completed, and only the remaining steps are relayed. This resolves to a noop if the connection
+/// is already open on both ends.
+pub async fn resume_connection_handshake(
+    client: &impl VoyagerRpcClient,
+    chain_id: ChainId,
+    connection_id: u32,
+) -> RpcResult<Op<VoyagerMessage>> {
+    let connection = query_latest_ibc_state(client, &chain_id, ConnectionPath { connection_id })
+        .await?
+        .ok_or_else(missing_state("connection not found", None))?;
+
+    let counterparty_chain_id = tracked_chain_id(client, &chain_id, connection.client_id).await?;
+
+    match resume_connection(
+        client,
+        &chain_id,
+        &counterparty_chain_id,
+        connection_id,
+        &connection,
+    )
+    .await?
+    {
+        Some(op) => Ok(op),
+        None => {
+            info!(%chain_id, %connection_id, "connection is already open, nothing to resume");
+
+            Ok(noop())
+        }
+    }
+}
+
+/// Resume the handshake of `connection_id_a` on `chain_a` with its counterparty on `chain_b`,
+/// returning `None` if the connection is already open on both ends.
+async fn resume_connection(
+    client: &impl VoyagerRpcClient,
+    chain_a: &ChainId,
+    chain_b: &ChainId,
+    connection_id_a: u32,
+    connection_a: &Connection,
+) -> RpcResult<Option<Op<VoyagerMessage>>> {
+    let client_a = connection_a.client_id;
+    let client_b = connection_a.counterparty_client_id;
+
+    let end_b = if connection_a.counterparty_connection_id == 0 {
+        // the counterparty connection id is only known after the ack, but the try may have already
+        // been submitted
+        find_last(
+            client,
+            chain_b,
+            |connection_id| ConnectionPath { connection_id },
+            |connection: &Connection| {
+                connection.client_id == client_b
+                    && connection.counterparty_client_id == client_a
+                    && connection.counterparty_connection_id == connection_id_a
+            },
+        )
+        .await?
+    } else {
+        let connection_id_b = connection_a.counterparty_connection_id;
+
+        let connection_b = query_latest_ibc_state(
+            client,
+            chain_b,
+            ConnectionPath {
+                connection_id: connection_id_b,
+            },
+        )
+        .await?
+        .ok_or_else(missing_state("counterparty connection not found", None))?;
+
+        Some((connection_id_b, connection_b))
+    };
+
+    debug!(
+        %connection_id_a,
+        state_a = ?connection_a.state,
+        state_b = ?end_b.as_ref().map(|(_, connection)| connection.state),
+        "found existing connection"
+    );
+
+    let a = (chain_a, connection_id_a, connection_a);
+
+    match end_b {
+        None => match connection_a.state {
+            ConnectionState::Init => resume(client, a, chain_b, |connection_id, connection| {
+                ConnectionOpenInit {
+                    connection_id,
+                    client_id: connection.client_id,
+                    counterparty_client_id: connection.counterparty_client_id,
+                }
+                .into()
+            })
+            .await
+            .map(Some),
+            _ => Err(missing_state("counterparty connection not found", None)()),
+        },
+        Some((connection_id_b, connection_b)) => {
+            let b = (chain_b, connection_id_b, &connection_b);
+
+            match (connection_a.state, connection_b.state) {
+                (ConnectionState::Init, ConnectionState::TryOpen) => {
+                    resume(client, b, chain_a, connection_open_try)
+                        .await
+                        .map(Some)
+                }
+                (ConnectionState::TryOpen, ConnectionState::Init) => {
+                    resume(client, a, chain_b, connection_open_try)
+                        .await
+                        .map(Some)
+                }
+                (ConnectionState::Open, ConnectionState::TryOpen) => {
+                    resume(client, a, chain_b, connection_open_ack)
+                        .await
+                        .map(Some)
+                }
+                (ConnectionState::TryOpen, ConnectionState::Open) => {
+                    resume(client, b, chain_a, connection_open_ack)
+                        .await
+                        .map(Some)
+                }
+                (ConnectionState::Open, ConnectionState::Open) => Ok(None),
+                (state_a, state_b) => Err(missing_state(
+                    format!(
+                        "unable to resume connection handshake with connection \
+                        {connection_id_a} in state {state_a:?} and counterparty \
+                        connection {connection_id_b} in state {state_b:?}"
+                    ),
+                    None,
+                )()),
+            }
+        }
+    }
+}
+
+fn init_connection_op(
+    chain_id: ChainId,
+    client_id: u32,
+    counterparty_client_id: u32,
+) -> Op<VoyagerMessage> {
+    data(WithChainId {
+        chain_id,
+        message: IbcDatagram::new::<IbcUnion>(
+            MsgConnectionOpenInit {
+                client_id,
+                counterparty_client_id,
+            }
+            .into(),
+        ),
+    })
+}
+
+fn connection_open_try(connection_id: u32, connection: &Connection) -> FullEvent {
+    ConnectionOpenTry {
+        connection_id,
+        client_id: connection.client_id,
+        counterparty_client_id: connection.counterparty_client_id,
+        counterparty_connection_id: connection.counterparty_connection_id,
+    }
+    .into()
+}
+
+fn connection_open_ack(connection_id: u32, connection: &Connection) -> FullEvent {
+    ConnectionOpenAck {
+        connection_id,
+        client_id: connection.client_id,
+        counterparty_client_id: connection.counterparty_client_id,
+        counterparty_connection_id: connection.counterparty_connection_id,
+    }
+    .into()
+}
+
+/// Re-emit the event of the last completed step of a connection handshake on `chain_id`, such that
+/// the next step is relayed to `counterparty_chain_id`.
+async fn resume(
+    client: &impl VoyagerRpcClient,
+    (chain_id, connection_id, connection): (&ChainId, u32, &Connection),
+    counterparty_chain_id: &ChainId,
+    event: impl FnOnce(u32, &Connection) -> FullEvent,
+) -> RpcResult<Op<VoyagerMessage>> {
+    let event = event(connection_id, connection);
+
+    info!(
+        %chain_id,
+        %connection_id,
+        state = ?connection.state,
+        "resuming connection handshake"
+    );
+
+    chain_event(
+        client,
+        chain_id.clone(),
+        counterparty_chain_id.clone(),
+        connection.client_id,
+        event,
+    )
+    .await
+}
+
+/// Build the op to open a channel between `port_a` on `chain_a` and `port_b` on the counterparty
+/// chain of `connection_id_a`, which must already be open.
+///
+/// IBC union channels are always unordered, so unlike ICS-004 there is no ordering to choose. The
+/// `version` is proposed by `chain_a`, and may be changed by the application on the counterparty
+/// during the handshake.
+///
+/// If the newest channel on `connection_id_a` to `port_b` is not yet open on both ends, the
+/// handshake of that channel is resumed instead of initiating a new one. As with
+/// [`init_connection`], this queries every channel on both chains.
+pub async fn init_channel(
+    client: &impl VoyagerRpcClient,
+    chain_a: ChainId,
+    connection_id_a: u32,
+    port_a: Bytes,
+    port_b: Bytes,
+    version: String,
+) -> RpcResult<Op<VoyagerMessage>> {
+    let connection_a = query_latest_ibc_state(
+        client,
+        &chain_a,
+        ConnectionPath {
+            connection_id: connection_id_a,
+        },
+    )
+    .await?
+    .ok_or_else(missing_state("connection not found", None))?;
+
+    if connection_a.state != ConnectionState::Open {
+        return Err(missing_state(
+            format!(
+                "connection {connection_id_a} is in state {:?}, it must be open before a \
+                channel can be opened on it",
+                connection_a.state
+            ),
+            None,
+        )());
+    }
+
+    let chain_b = tracked_chain_id(client, &chain_a, connection_a.client_id).await?;
+
+    let connection_id_b = connection_a.counterparty_connection_id;
+
+    let connection_b = query_latest_ibc_state(
+        client,
+        &chain_b,
+        ConnectionPath {
+            connection_id: connection_id_b,
+        },
+    )
+    .await?
+    .ok_or_else(missing_state("counterparty connection not found", None))?;
+
+    let existing = find_last(
+        client,
+        &chain_a,
+        |channel_id| ChannelPath { channel_id },
+        |channel: &Channel| {
+            channel.connection_id == connection_id_a
+                && channel.counterparty_port_id[..] == port_b[..]
+        },
+    )
+    .await?;
+
+    let Some((channel_id_a, channel_a)) = existing else {
+        info!(%chain_a, %connection_id_a, %port_a, %port_b, "initiating new channel handshake");
+
+        return Ok(init_channel_op(
+            chain_a,
+            connection_id_a,
+            port_a,
+            port_b,
+            version,
+        ));
+    };
+
+    let a = ChannelEnd {
+        chain_id: &chain_a,
+        port_id: Some(&port_a),
+        channel_id: channel_id_a,
+        channel: &channel_a,
+        connection: &connection_a,
+    };
+
+    match resume_channel(client, a, &chain_b, connection_id_b, &connection_b).await? {
+        Some(op) => Ok(op),
+        None => {
+            info!(
+                %channel_id_a,
+                "existing channel is already open, initiating new channel handshake"
+            );
+
+            Ok(init_channel_op(
+                chain_a,
+                connection_id_a,
+                port_a,
+                port_b,
+                version,
+            ))
+        }
+    }
+}
+
+/// Build the op to resume the handshake of the channel `channel_id` on `chain_id`, for example
+/// after a previous relayer instance stopped between two steps of the handshake.
+///
+/// As with [`resume_connection_handshake`], only the remaining steps are relayed, and this
+/// resolves to a noop if the channel is already open on both ends. The port of the channel is not
+/// stored in the channel itself, so `port_id` must be provided if the handshake has not yet reached
+/// the counterparty (i.e. the channel is in the init state and there is no counterparty channel).
+pub async fn resume_channel_handshake(
+    client: &impl VoyagerRpcClient,
+    chain_id: ChainId,
+    channel_id: u32,
+    port_id: Option<Bytes>,
+) -> RpcResult<Op<VoyagerMessage>> {
+    let channel = query_latest_ibc_state(client, &chain_id, ChannelPath { channel_id })
+        .await?
+        .ok_or_else(missing_state("channel not found", None))?;
+
+    let connection_id = channel.connection_id;
+
+    let connection = query_latest_ibc_state(client, &chain_id, ConnectionPath { connection_id })
+        .await?
+        .ok_or_else(missing_state("connection not found", None))?;
+
+    let counterparty_chain_id = tracked_chain_id(client, &chain_id, connection.client_id).await?;
+
+    let counterparty_connection_id = connection.counterparty_connection_id;
+
+    let counterparty_connection = query_latest_ibc_state(
+        client,
+        &counterparty_chain_id,
+        ConnectionPath {
+            connection_id: counterparty_connection_id,
+        },
+    )
+    .await?
+    .ok_or_else(missing_state("counterparty connection not found", None))?;
+
+    let end = ChannelEnd {
+        chain_id: &chain_id,
+        port_id: port_id.as_ref(),
+        channel_id,
+        channel: &channel,
+        connection: &connection,
+    };
+
+    match resume_channel(
+        client,
+        end,
+        &counterparty_chain_id,
+        counterparty_connection_id,
+        &counterparty_connection,
+    )
+    .await?
+    {
+        Some(op) => Ok(op),
+        None => {
+            info!(%chain_id, %channel_id, "channel is already open, nothing to resume");
+
+            Ok(noop())
+        }
+    }
+}
+
+/// Resume the handshake of the channel `a` with its counterparty on `connection_id_b` on
+/// `chain_b`, returning `None` if the channel is already open on both ends.
+async fn resume_channel(
+    client: &impl VoyagerRpcClient,
+    a: ChannelEnd<'_>,
+    chain_b: &ChainId,
+    connection_id_b: u32,
+    connection_b: &Connection,
+) -> RpcResult<Option<Op<VoyagerMessage>>> {
+    let channel_id_a = a.channel_id;
+    let channel_a = a.channel;
+
+    let end_b = if channel_a.counterparty_channel_id == 0 {
+        find_last(
+            client,
+            chain_b,
+            |channel_id| ChannelPath { channel_id },
+            |channel: &Channel| {
+                channel.connection_id == connection_id_b
+                    && channel.counterparty_channel_id == channel_id_a
+                    && a.port_id
+                        .is_none_or(|port_a| channel.counterparty_port_id[..] == port_a[..])
+            },
+        )
+        .await?
+    } else {
+        let channel_id_b = channel_a.counterparty_channel_id;
+
+        let channel_b = query_latest_ibc_state(
+            client,
+            chain_b,
+            ChannelPath {
+                channel_id: channel_id_b,
+            },
+        )
+        .await?
+        .ok_or_else(missing_state("counterparty channel not found", None))?;
+
+        Some((channel_id_b, channel_b))
+    };
+
+    debug!(
+        %channel_id_a,
+        state_a = ?channel_a.state,
+        state_b = ?end_b.as_ref().map(|(_, channel)| channel.state),
+        "found existing channel"
+    );
+
+    let Some((channel_id_b, channel_b)) = end_b else {
+        return match channel_a.state {
+            ChannelState::Init => a.resume(client, chain_b, ChannelStep::Init).await.map(Some),
+            _ => Err(missing_state("counterparty channel not found", None)()),
+        };
+    };
+
+    // the port of each end is the counterparty port of the other end
+    let port_a = Bytes::from(channel_b.counterparty_port_id.clone());
+    let port_b = Bytes::from(channel_a.counterparty_port_id.clone());
+
+    let a = ChannelEnd {
+        port_id: Some(&port_a),
+        ..a
+    };
+
+    let b = ChannelEnd {
+        chain_id: chain_b,
+        port_id: Some(&port_b),
+        channel_id: channel_id_b,
+        channel: &channel_b,
+        connection: connection_b,
+    };
+
+    match (channel_a.state, channel_b.state) {
+        (ChannelState::Init, ChannelState::TryOpen) => b
+            .resume(client, a.chain_id, ChannelStep::Try)
+            .await
+            .map(Some),
+        (ChannelState::TryOpen, ChannelState::Init) => {
+            a.resume(client, chain_b, ChannelStep::Try).await.map(Some)
+        }
+        (ChannelState::Open, ChannelState::TryOpen) => {
+            a.resume(client, chain_b, ChannelStep::Ack).await.map(Some)
+        }
+        (ChannelState::TryOpen, ChannelState::Open) => b
+            .resume(client, a.chain_id, ChannelStep::Ack)
+            .await
+            .map(Some),
+        (ChannelState::Open, ChannelState::Open) => Ok(None),
+        (state_a, state_b) => Err(missing_state(
+            format!(
+                "unable to resume channel handshake with channel {channel_id_a} in state \
+                {state_a:?} and counterparty channel {channel_id_b} in state {state_b:?}"
+            ),
+            None,
+        )()),
+    }
+}
+
+fn init_channel_op(
+    chain_id: ChainId,
+    connection_id: u32,
+    port_id: Bytes,
+    counterparty_port_id: Bytes,
+    version: String,
+) -> Op<VoyagerMessage> {
+    data(WithChainId {
+        chain_id,
+        message: IbcDatagram::new::<IbcUnion>(
+            MsgChannelOpenInit {
+                port_id,
+                counterparty_port_id,
+                connection_id,
+                version,
+            }
+            .into(),
+        ),
+    })
+}
+
+/// The step of a channel handshake that has been completed on a [`ChannelEnd`].
+enum ChannelStep {
+    Init,
+    Try,
+    Ack,
+}
+
+/// One end of a channel.
+struct ChannelEnd<'a> {
+    chain_id: &'a ChainId,
+    /// The port of this end, which is only known if it was provided or the counterparty channel
+    /// exists.
+    port_id: Option<&'a Bytes>,
+    channel_id: u32,
+    channel: &'a Channel,
+    connection: &'a Connection,
+}
+
+impl ChannelEnd<'_> {
+    /// Re-emit the event of `step` on this end, such that the next step is relayed to
+    /// `counterparty_chain_id`.
+    async fn resume(
+        self,
+        client: &impl VoyagerRpcClient,
+        counterparty_chain_id: &ChainId,
+        step: ChannelStep,
+    ) -> RpcResult<Op<VoyagerMessage>> {
+        let port_id = self.port_id.cloned().ok_or_else(missing_state(
+            "the port id of the channel must be provided to resume its handshake",
+            None,
+        ))?;
+        let channel_id = self.channel_id;
+        let counterparty_port_id = self.channel.counterparty_port_id.clone().into();
+        let counterparty_channel_id = self.channel.counterparty_channel_id;
+        let connection = self.connection.clone();
+        let version = self.channel.version.clone();
+
+        info!(
+            chain_id = %self.chain_id,
+            %channel_id,
+            state = ?self.channel.state,
+            "resuming channel handshake"
+        );
+
+        let event = match step {
+            ChannelStep::Init => ChannelOpenInit {
+                port_id,
+                channel_id,
+                counterparty_port_id,
+                connection,
+                version,
+            }
+            .into(),
+            ChannelStep::Try => ChannelOpenTry {
+                port_id,
+                channel_id,
+                counterparty_port_id,
+                counterparty_channel_id,
+                connection,
+                version,
+            }
+            .into(),
+            ChannelStep::Ack => ChannelOpenAck {
+                port_id,
+                channel_id,
+                counterparty_port_id,
+                counterparty_channel_id,
+                connection,
+                version,
+            }
+            .into(),
+        };
+
+        chain_event(
+            client,
+            self.chain_id.clone(),
+            counterparty_chain_id.clone(),
+            self.connection.client_id,
+            event,
+        )
+        .await
+    }
+}
+
+/// The chain tracked by the client `client_id` on `chain_id`.
+async fn tracked_chain_id(
+    client: &impl VoyagerRpcClient,
+    chain_id: &ChainId,
+    client_id: u32,
+) -> RpcResult<ChainId> {
+    client
+        .client_meta(
+            chain_id.clone(),
+            IbcUnion::ID,
+            QueryHeight::Latest,
+            RawClientId::new(client_id),
+        )
+        .await
+        .map(|meta| meta.chain_id)
+        .map_err(json_rpc_error_to_error_object)
+}
+
+/// Construct an [`IbcUnion`] event that was emitted on `chain_id` at some point in the past.
+///
+/// The event is provable at the latest finalized height of `chain_id`, as long as the state it
+/// describes has not changed since. The hash of the transaction that emitted the original event is
+/// not known, and is left zeroed.
+async fn chain_event(
+    client: &impl VoyagerRpcClient,
+    chain_id: ChainId,
+    counterparty_chain_id: ChainId,
+    client_id: u32,
+    event: FullEvent,
+) -> RpcResult<Op<VoyagerMessage>> {
+    let client_info = client
+        .client_info(chain_id.clone(), IbcUnion::ID, RawClientId::new(client_id))
+        .await
+        .map_err(json_rpc_error_to_error_object)?;
+
+    let provable_height = client
+        .query_latest_height(chain_id.clone(), true)
+        .await
+        .map_err(json_rpc_error_to_error_object)?;
+
+    Ok(data(ChainEvent {
+        chain_id,
+        client_info,
+        counterparty_chain_id,
+        tx_hash: H256::default(),
+        provable_height,
+        ibc_spec_id: IbcUnion::ID,
+        event: into_value(event),
+        decoded_packet_data: None,
+    }))
+}
+
+/// Find the entry with the highest id on `chain_id` that matches `predicate`, for paths with
+/// sequential ids starting at 1 (such as connections and channels).
+async fn find_last<P, T>(
+    client: &impl VoyagerRpcClient,
+    chain_id: &ChainId,
+    path: impl Fn(u32) -> P,
+    predicate: impl Fn(&T) -> bool,
+) -> RpcResult<Option<(u32, T)>>
+where
+    P: IbcStorePathKey<Spec = IbcUnion, Value = Option<T>>,
+{
+    let mut last = None;
+
+    for id in 1.. {
+        match query_latest_ibc_state(client, chain_id, path(id)).await? {
+            Some(value) if predicate(&value) => last = Some((id, value)),
+            Some(_) => {}
+            None => break,
+        }
+    }
+
+    Ok(last)
+}
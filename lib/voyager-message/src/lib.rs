@@ -15,6 +15,11 @@ use jsonrpsee::{
 };
 use macros::model;
 use reth_ipc::{client::IpcClientBuilder, server::RpcServiceBuilder};
+use schemars::{
+    gen::{SchemaGenerator, SchemaSettings},
+    schema::{RootSchema, Schema},
+    JsonSchema,
+};
 use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use serde_json::{json, Value};
 use tracing::{debug, debug_span, error, info, instrument, trace, Instrument};
@@ -23,41 +28,91 @@ use voyager_core::{
     ChainId, ClientInfo, ClientStateMeta, ClientType, IbcInterface, IbcSpec, IbcStorePathKey,
     QueryHeight,
 };
-use voyager_vm::{QueueError, QueueMessage};
+use voyager_vm::{op_hash, Op, QueueError, QueueMessage};
 
 use crate::{
-    call::Call,
-    callback::Callback,
+    call::{
+        Call, FetchBlockRange, FetchBlocks, FetchPacketEvents, FetchUpdateHeaders,
+        RecordCheckpoint, WaitForFinality, WaitForHeight, WaitForTimestamp, WaitForTrustedHeight,
+        WaitForTxInclusion,
+    },
+    callback::{
+        AggregateMsgUpdateClientsFromOrderedHeaders, AggregateSubmitTxFromOrderedClientUpdates,
+        Callback,
+    },
     context::{Context, INVALID_CONFIG_EXIT_CODE, STARTUP_ERROR_EXIT_CODE},
-    data::Data,
+    data::{AppEvent, Data, WithChainId},
     filter::JaqInterestFilter,
     module::{
-        ClientModuleInfo, ClientModuleServer, ConsensusModuleInfo, ConsensusModuleServer,
-        PluginInfo, PluginServer, ProofModuleInfo, ProofModuleServer, StateModuleInfo,
-        StateModuleServer,
+        ChainCapabilities, ClientModuleInfo, ClientModuleServer, ConsensusModuleInfo,
+        ConsensusModuleServer, PluginInfo, PluginServer, ProofModuleInfo, ProofModuleServer,
+        StateModuleInfo, StateModuleServer,
     },
+    notify::{DeadLettered, Notification},
     rpc::{json_rpc_error_to_error_object, IbcProof, IbcState, VoyagerRpcClient},
+    telemetry::LogFormat,
 };
 
 pub mod call;
 pub mod callback;
 pub mod data;
+pub mod event;
 
+pub mod block_time;
 pub mod context;
+pub mod costs;
+pub mod dedup;
 pub mod filter;
 pub mod module;
+pub mod notify;
+pub mod packet_status;
 pub mod pass;
+pub mod pause;
+pub mod singleflight;
+pub mod tx_error;
 
 pub mod hook;
 
+pub mod metrics;
+
 pub mod rpc;
 
+pub mod clear_packets;
+pub mod handshake;
+pub mod query;
+pub mod template;
+pub mod upgrade;
+
+pub mod telemetry;
+
+pub mod testing;
+
 pub use reconnecting_jsonrpc_ws_client;
 pub use reth_ipc;
 pub use voyager_core as core;
 
 pub enum VoyagerMessage {}
 
+/// [`VoyagerMessage`] is only used as a type level marker, and never appears in the serialized ops;
+/// this only exists such that the schemas of the messages generic over it can be named.
+impl JsonSchema for VoyagerMessage {
+    fn schema_name() -> String {
+        "VoyagerMessage".to_owned()
+    }
+
+    fn json_schema(_: &mut SchemaGenerator) -> Schema {
+        Schema::Bool(false)
+    }
+}
+
+/// The JSON Schema of the ops accepted by the queue, i.e. via the `/enqueue` endpoint of the REST
+/// API.
+#[must_use]
+pub fn op_schema() -> RootSchema {
+    SchemaGenerator::new(SchemaSettings::draft2019_09())
+        .into_root_schema_for::<Op<VoyagerMessage>>()
+}
+
 impl QueueMessage for VoyagerMessage {
     type Call = Call;
     type Data = Data;
@@ -66,10 +121,141 @@ impl QueueMessage for VoyagerMessage {
     type Filter = JaqInterestFilter;
 
     type Context = Context;
+
+    /// Ops are keyed by the pair of chains they relay between where that is known (client
+    /// updates and IBC events), by the chain they operate on otherwise, and by the plugin
+    /// handling them for plugin messages (plugins are instantiated per chain).
+    fn concurrency_key(op: &Op<Self>) -> Option<String> {
+        match op {
+            Op::Call(call) => Some(match call {
+                Call::FetchBlocks(FetchBlocks { chain_id, .. })
+                | Call::FetchBlockRange(FetchBlockRange { chain_id, .. })
+                | Call::FetchPacketEvents(FetchPacketEvents { chain_id, .. })
+                | Call::WaitForHeight(WaitForHeight { chain_id, .. })
+                | Call::WaitForFinality(WaitForFinality { chain_id, .. })
+                | Call::WaitForTimestamp(WaitForTimestamp { chain_id, .. })
+                | Call::WaitForTrustedHeight(WaitForTrustedHeight { chain_id, .. })
+                | Call::WaitForTxInclusion(WaitForTxInclusion { chain_id, .. })
+                | Call::RecordCheckpoint(RecordCheckpoint { chain_id, .. }) => chain_id.to_string(),
+                Call::FetchUpdateHeaders(FetchUpdateHeaders {
+                    chain_id,
+                    counterparty_chain_id,
+                    ..
+                }) => chain_pair_key(chain_id, counterparty_chain_id),
+                Call::Plugin(PluginMessage { plugin, .. }) => plugin.clone(),
+            }),
+            Op::Data(data) => match data {
+                Data::IbcEvent(event) => Some(chain_pair_key(
+                    &event.chain_id,
+                    &event.counterparty_chain_id,
+                )),
+                Data::IdentifiedIbcDatagram(WithChainId { chain_id, .. })
+                | Data::IdentifiedIbcDatagramBatch(WithChainId { chain_id, .. })
+                | Data::TxReceipt(WithChainId { chain_id, .. })
+                | Data::SignerBalances(WithChainId { chain_id, .. })
+                | Data::AppEvent(AppEvent { chain_id, .. }) => Some(chain_id.to_string()),
+                Data::Plugin(PluginMessage { plugin, .. }) => Some(plugin.clone()),
+                Data::IbcDatagram(_)
+                | Data::OrderedHeaders(_)
+                | Data::OrderedMsgUpdateClients(_) => None,
+            },
+            Op::Promise(promise) => Some(match &promise.receiver {
+                Callback::AggregateMsgUpdateClientsFromOrderedHeaders(
+                    AggregateMsgUpdateClientsFromOrderedHeaders { chain_id, .. },
+                )
+                | Callback::AggregateSubmitTxFromOrderedClientUpdates(
+                    AggregateSubmitTxFromOrderedClientUpdates { chain_id },
+                ) => chain_id.to_string(),
+                Callback::Plugin(PluginMessage { plugin, .. }) => plugin.clone(),
+            }),
+            Op::Seq(ops) | Op::Conc(ops) => ops.iter().find_map(Self::concurrency_key),
+            Op::Void(op) | Op::Retry { msg: op, .. } | Op::WithPriority { msg: op, .. } => {
+                Self::concurrency_key(op)
+            }
+            Op::Defer { .. } | Op::Noop => None,
+        }
+    }
+
+    /// Ops are sharded by the pair of chains they relay between where that is known (client
+    /// updates and IBC events), and by the chain they read from for the ops fetching events.
+    /// Everything else, notably plugin messages (which don't carry the chain pair they are for),
+    /// inherits the shard key of the op it was produced from, such that the ops relaying between
+    /// a chain pair stay on one instance.
+    fn shard_key(op: &Op<Self>) -> Option<String> {
+        match op {
+            Op::Call(Call::FetchUpdateHeaders(FetchUpdateHeaders {
+                chain_id,
+                counterparty_chain_id,
+                ..
+            })) => Some(chain_pair_key(chain_id, counterparty_chain_id)),
+            Op::Call(
+                Call::FetchBlocks(FetchBlocks { chain_id, .. })
+                | Call::FetchBlockRange(FetchBlockRange { chain_id, .. })
+                | Call::FetchPacketEvents(FetchPacketEvents { chain_id, .. })
+                | Call::RecordCheckpoint(RecordCheckpoint { chain_id, .. }),
+            ) => Some(chain_id.to_string()),
+            Op::Data(Data::IbcEvent(event)) => Some(chain_pair_key(
+                &event.chain_id,
+                &event.counterparty_chain_id,
+            )),
+            Op::Seq(ops) | Op::Conc(ops) => ops.iter().find_map(Self::shard_key),
+            Op::Void(op) | Op::Retry { msg: op, .. } | Op::WithPriority { msg: op, .. } => {
+                Self::shard_key(op)
+            }
+            _ => None,
+        }
+    }
+
+    /// Only the ops that are wasteful to repeat are deduplicated: the calls that fetch from a
+    /// chain, and the client update trees (which are identical for repeated `UpdateClient`
+    /// commands). Everything else, notably plugin messages, may be enqueued multiple times
+    /// intentionally.
+    fn idempotency_key(op: &Op<Self>) -> Option<String> {
+        match op {
+            Op::Call(
+                Call::FetchUpdateHeaders(_) | Call::FetchBlockRange(_) | Call::FetchPacketEvents(_),
+            ) => Some(op_hash(op)),
+            Op::Promise(promise) => match promise.receiver {
+                Callback::AggregateMsgUpdateClientsFromOrderedHeaders(_)
+                | Callback::AggregateSubmitTxFromOrderedClientUpdates(_) => Some(op_hash(op)),
+                Callback::Plugin(_) => None,
+            },
+            Op::Retry { msg, .. } | Op::WithPriority { msg, .. } => {
+                Self::idempotency_key(msg).map(|_| op_hash(op))
+            }
+            _ => None,
+        }
+    }
+
+    fn pause_keys(op: &Op<Self>) -> Vec<String> {
+        pause::pause_keys(op)
+    }
+
+    fn paused_keys(ctx: &Context) -> Vec<String> {
+        ctx.pauses.paused_keys()
+    }
+
+    fn on_failure(ctx: &Context, op: &Op<Self>, error: &str) {
+        ctx.notifier
+            .notify(&Notification::DeadLettered(DeadLettered {
+                op: into_value(op),
+                error: error.to_owned(),
+            }));
+    }
+}
+
+/// The concurrency key for ops relaying between two chains. This is the same regardless of the
+/// direction, such that both directions share the same limit.
+pub fn chain_pair_key(a: &ChainId, b: &ChainId) -> String {
+    if a.as_str() <= b.as_str() {
+        format!("{a}<>{b}")
+    } else {
+        format!("{b}<>{a}")
+    }
 }
 
 /// Simple wrapper around a [`Value`] for raw client ids.
-#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize, JsonSchema)]
 #[serde(transparent)]
 pub struct RawClientId(Value);
 
@@ -121,6 +307,15 @@ impl RawClientId {
 /// code, it will be treated as fatal and not retried.
 pub const FATAL_JSONRPC_ERROR_CODE: i32 = -0xBADBEEF;
 
+/// Error code for errors that should be retried after a delay. If a plugin or
+/// module responds with this error code, the error data is expected to be the
+/// delay in seconds before the message should be retried (i.e. when an RPC is
+/// rate limiting requests).
+///
+/// If the data is missing or invalid, the error is treated as a normal
+/// retryable error.
+pub const RETRY_AFTER_JSONRPC_ERROR_CODE: i32 = -0xBADC0DE;
+
 /// Convert a [`jsonrpsee::core::client::Error`] to a `voyager-vm`
 /// [`QueueError`].
 ///
@@ -150,6 +345,9 @@ pub fn json_rpc_error_to_queue_error(error: jsonrpsee::core::client::Error) -> Q
 ///   due a bug in the plugin or module (JSON serialization not roundtripping
 ///   correctly) or a message that was manually inserted into the queue via
 ///   `/enqueue`.
+///
+/// Errors with the code [`RETRY_AFTER_JSONRPC_ERROR_CODE`] are converted to
+/// [`QueueError::RetryAfter`], all other errors are retryable.
 pub fn error_object_to_queue_error(error: ErrorObject<'_>) -> QueueError {
     if error.code() == FATAL_JSONRPC_ERROR_CODE
         || error.code() == METHOD_NOT_FOUND_CODE
@@ -157,16 +355,33 @@ pub fn error_object_to_queue_error(error: ErrorObject<'_>) -> QueueError {
         || error.code() == PARSE_ERROR_CODE
     {
         QueueError::Fatal(Box::new(error.into_owned()))
+    } else if let Some(delay) = (error.code() == RETRY_AFTER_JSONRPC_ERROR_CODE)
+        .then(|| error.data())
+        .flatten()
+        .and_then(|data| serde_json::from_str::<u64>(data.get()).ok())
+    {
+        QueueError::RetryAfter {
+            delay,
+            error: Box::new(error.into_owned()),
+        }
     } else {
         QueueError::Retry(Box::new(error.into_owned()))
     }
 }
 
+/// Construct an [`ErrorObject`] that will be converted to a
+/// [`QueueError::RetryAfter`] with the provided delay (in seconds). See
+/// [`RETRY_AFTER_JSONRPC_ERROR_CODE`].
+pub fn retry_after_error(delay: u64, message: impl Into<String>) -> ErrorObject<'static> {
+    ErrorObject::owned(RETRY_AFTER_JSONRPC_ERROR_CODE, message, Some(delay))
+}
+
 /// A message specific to a plugin.
 ///
 /// This is used in [`Call`], [`Callback`], and [`Data`] to route messages to
 /// plugins.
 #[model]
+#[derive(JsonSchema)]
 pub struct PluginMessage {
     pub plugin: String,
     pub message: Value,
@@ -199,11 +414,6 @@ impl PluginMessage {
 pub enum DefaultCmd {}
 
 fn init_log() {
-    enum LogFormat {
-        Text,
-        Json,
-    }
-
     let format = match std::env::var("RUST_LOG_FORMAT").as_deref() {
         Err(VarError::NotPresent) | Ok("text") => LogFormat::Text,
         Ok("json") => LogFormat::Json,
@@ -217,21 +427,13 @@ fn init_log() {
         }
     };
 
-    match format {
-        LogFormat::Text => {
-            tracing_subscriber::fmt()
-                .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
-                // .with_span_events(FmtSpan::CLOSE)
-                .init();
-        }
-        LogFormat::Json => {
-            tracing_subscriber::fmt()
-                .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
-                // .with_span_events(FmtSpan::CLOSE)
-                .json()
-                .init();
-        }
-    }
+    // plugins and modules are identified by their executable in exported traces
+    let service_name = std::env::current_exe()
+        .ok()
+        .and_then(|exe| Some(exe.file_name()?.to_string_lossy().into_owned()))
+        .unwrap_or_else(|| "voyager-module".to_owned());
+
+    telemetry::init(format, &service_name);
 }
 
 #[allow(async_fn_in_trait)]
@@ -467,6 +669,38 @@ impl VoyagerClient {
         Ok(latest_height)
     }
 
+    /// Record that all events in the block at `height` on `chain_id` have been processed. See
+    /// [`VoyagerRpcClient::record_checkpoint`].
+    pub async fn record_checkpoint(&self, chain_id: ChainId, height: Height) -> RpcResult<()> {
+        self.0
+            .record_checkpoint(chain_id, height)
+            .await
+            .map_err(json_rpc_error_to_error_object)
+    }
+
+    /// The capabilities declared for `chain_id`, see [`ChainCapabilities`].
+    pub async fn chain_capabilities(&self, chain_id: ChainId) -> RpcResult<ChainCapabilities> {
+        self.0
+            .chain_capabilities(chain_id)
+            .await
+            .map_err(json_rpc_error_to_error_object)
+    }
+
+    /// Returns the latest timestamp of the chain, in nanoseconds.
+    pub async fn query_latest_timestamp(
+        &self,
+        chain_id: ChainId,
+        finalized: bool,
+    ) -> RpcResult<i64> {
+        let latest_timestamp = self
+            .0
+            .query_latest_timestamp(chain_id, finalized)
+            .await
+            .map_err(json_rpc_error_to_error_object)?;
+
+        Ok(latest_timestamp)
+    }
+
     #[instrument(
         skip_all,
         name = "voyager_client_encode_proof",
@@ -497,6 +731,27 @@ impl VoyagerClient {
         height: QueryHeight,
         path: P,
     ) -> RpcResult<IbcState<P::Value>> {
+        self.query_ibc_state_as(chain_id, height, path).await
+    }
+
+    /// Query the state at `path`, which may not be set. For paths whose value can't represent its
+    /// own absence (such as commitments), state modules may return `null` if the state is not set,
+    /// which is returned as `None` here.
+    pub async fn query_optional_ibc_state<P: IbcStorePathKey>(
+        &self,
+        chain_id: ChainId,
+        height: QueryHeight,
+        path: P,
+    ) -> RpcResult<IbcState<Option<P::Value>>> {
+        self.query_ibc_state_as(chain_id, height, path).await
+    }
+
+    async fn query_ibc_state_as<P: IbcStorePathKey, T: DeserializeOwned>(
+        &self,
+        chain_id: ChainId,
+        height: QueryHeight,
+        path: P,
+    ) -> RpcResult<IbcState<T>> {
         let ibc_state = self
             .0
             .query_ibc_state(
@@ -718,3 +973,62 @@ pub fn into_value<T: Debug + Serialize>(t: T) -> Value {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn op_schema_contains_all_messages() {
+        let schema = serde_json::to_value(op_schema()).unwrap();
+
+        let definitions = schema["definitions"].as_object().unwrap();
+
+        for name in [
+            "Call",
+            "Callback",
+            "Data",
+            "FetchBlocks",
+            "ChainEvent",
+            "H256",
+        ] {
+            assert!(
+                definitions.contains_key(name),
+                "missing definition for {name}"
+            );
+        }
+    }
+
+    #[test]
+    fn shard_key() {
+        let a = ChainId::new("a");
+        let b = ChainId::new("b");
+
+        let update = |chain_id: &ChainId, counterparty_chain_id: &ChainId| {
+            Op::Call(Call::FetchUpdateHeaders(FetchUpdateHeaders {
+                chain_id: chain_id.clone(),
+                counterparty_chain_id: counterparty_chain_id.clone(),
+                update_from: Height::new(1),
+                update_to: Height::new(2),
+            }))
+        };
+
+        // both directions of a chain pair are sharded together
+        assert_eq!(
+            VoyagerMessage::shard_key(&update(&a, &b)),
+            Some("a<>b".to_owned())
+        );
+        assert_eq!(
+            VoyagerMessage::shard_key(&update(&b, &a)),
+            Some("a<>b".to_owned())
+        );
+
+        // plugin messages inherit the shard key of their parent, unlike their concurrency key
+        let plugin_call = Op::Call(Call::Plugin(PluginMessage::new("plugin/a", ())));
+        assert_eq!(VoyagerMessage::shard_key(&plugin_call), None);
+        assert_eq!(
+            VoyagerMessage::concurrency_key(&plugin_call),
+            Some("plugin/a".to_owned())
+        );
+    }
+}
@@ -0,0 +1,147 @@
+//! Prometheus metrics for [`Call`] and [`Callback`] processing.
+//!
+//! All metrics are registered in the default registry, and as such are exposed
+//! by the `/metrics` endpoint of the voyager REST api.
+//!
+//! Plugin names are scoped to the chain they operate on (i.e.
+//! `voyager-transaction-plugin-cosmos-sdk/union-testnet-8`), so the `plugin`
+//! label can be used to break down latencies and failures per chain.
+//!
+//! The depth of the queue is recorded by the queue implementation (see `pg_queue::metrics`), and
+//! ops that exhaust their retries by the vm (see [`voyager_vm::metrics`]).
+
+use std::sync::LazyLock;
+
+use prometheus::{register_histogram_vec, register_int_counter_vec, HistogramVec, IntCounterVec};
+use voyager_vm::QueueError;
+
+use crate::{call::Call, callback::Callback, PluginMessage};
+
+pub static CALL_PROCESSED_COUNT: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!(
+        "voyager_call_processed_total",
+        "The amount of calls that have been processed.",
+        &["call", "plugin"],
+    )
+    .unwrap()
+});
+
+pub static CALL_PROCESSING_DURATION: LazyLock<HistogramVec> = LazyLock::new(|| {
+    register_histogram_vec!(
+        "voyager_call_processing_duration_seconds",
+        "The time it takes to process a call.",
+        &["call", "plugin"],
+    )
+    .unwrap()
+});
+
+pub static CALL_ERROR_COUNT: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!(
+        "voyager_call_error_total",
+        "The amount of calls that have failed, by error kind (fatal, retry, or retry_after).",
+        &["call", "plugin", "kind"],
+    )
+    .unwrap()
+});
+
+pub static CALL_TIMEOUT_COUNT: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!(
+        "voyager_call_timeout_total",
+        "The amount of calls that have failed due to exceeding their timeout.",
+        &["call", "plugin"],
+    )
+    .unwrap()
+});
+
+pub static FETCH_DURATION: LazyLock<HistogramVec> = LazyLock::new(|| {
+    register_histogram_vec!(
+        "voyager_fetch_duration_seconds",
+        "The time it takes for a plugin to process a fetch call (i.e. a plugin call with a `fetch_*` @type).",
+        &["fetch", "plugin"],
+    )
+    .unwrap()
+});
+
+pub static SUBMISSION_ERROR_COUNT: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!(
+        "voyager_submission_error_total",
+        "The amount of submissions of msgs by a transaction plugin that have failed, by error kind (fatal, retry, or retry_after).",
+        &["plugin", "kind"],
+    )
+    .unwrap()
+});
+
+pub static WAIT_DEADLINE_EXCEEDED_COUNT: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!(
+        "voyager_wait_deadline_exceeded_total",
+        "The amount of waits that have failed due to not resolving before their deadline.",
+        &["wait", "chain_id"],
+    )
+    .unwrap()
+});
+
+pub static CALLBACK_PROCESSED_COUNT: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!(
+        "voyager_callback_processed_total",
+        "The amount of callbacks that have been processed.",
+        &["callback", "plugin"],
+    )
+    .unwrap()
+});
+
+pub static CALLBACK_ERROR_COUNT: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!(
+        "voyager_callback_error_total",
+        "The amount of callbacks that have failed, by error kind (fatal, retry, or retry_after).",
+        &["callback", "plugin", "kind"],
+    )
+    .unwrap()
+});
+
+pub static CALLBACK_DATA_COUNT: LazyLock<HistogramVec> = LazyLock::new(|| {
+    register_histogram_vec!(
+        "voyager_callback_data_count",
+        "The amount of data aggregated by a promise before its callback is processed.",
+        &["callback", "plugin"],
+        vec![0.0, 1.0, 2.0, 5.0, 10.0, 20.0, 50.0, 100.0, 200.0, 500.0],
+    )
+    .unwrap()
+});
+
+/// The `(call, plugin)` label values for a [`Call`].
+pub(crate) fn call_labels(call: &Call) -> [&str; 2] {
+    match call {
+        Call::FetchBlocks(_) => ["fetch_blocks", ""],
+        Call::FetchBlockRange(_) => ["fetch_block_range", ""],
+        Call::FetchPacketEvents(_) => ["fetch_packet_events", ""],
+        Call::FetchUpdateHeaders(_) => ["fetch_update_headers", ""],
+        Call::WaitForHeight(_) => ["wait_for_height", ""],
+        Call::WaitForFinality(_) => ["wait_for_finality", ""],
+        Call::WaitForTimestamp(_) => ["wait_for_timestamp", ""],
+        Call::WaitForTrustedHeight(_) => ["wait_for_trusted_height", ""],
+        Call::WaitForTxInclusion(_) => ["wait_for_tx_inclusion", ""],
+        Call::RecordCheckpoint(_) => ["record_checkpoint", ""],
+        Call::Plugin(PluginMessage { plugin, .. }) => ["plugin", plugin],
+    }
+}
+
+/// The `(callback, plugin)` label values for a [`Callback`].
+pub(crate) fn callback_labels(callback: &Callback) -> [&str; 2] {
+    match callback {
+        Callback::AggregateMsgUpdateClientsFromOrderedHeaders(_) => {
+            ["aggregate_msg_update_clients_from_ordered_headers", ""]
+        }
+        Callback::AggregateSubmitTxFromOrderedClientUpdates(_) => {
+            ["aggregate_submit_tx_from_ordered_client_updates", ""]
+        }
+        Callback::Plugin(PluginMessage { plugin, .. }) => ["plugin", plugin],
+    }
+}
+
+pub(crate) fn error_kind(error: &QueueError) -> &'static str {
+    match error {
+        QueueError::Fatal(_) => "fatal",
+        QueueError::Retry(_) => "retry",
+        QueueError::RetryAfter { .. } => "retry_after",
+    }
+}
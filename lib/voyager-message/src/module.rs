@@ -81,12 +81,36 @@ pub struct ConsensusModuleInfo {
     pub chain_id: ChainId,
     #[arg(value_parser(|s: &str| ok(ConsensusType::new(s.to_owned()))))]
     pub consensus_type: ConsensusType,
+    /// What this chain supports, used to pick strategies (e.g. batching or wait intervals) per
+    /// chain. Unlike the other fields, this does not identify the module.
+    #[arg(skip)]
+    #[serde(default)]
+    pub capabilities: ChainCapabilities,
     // REVIEW: Maybe we need this? Do different client types for a single consensus necessarily have the same client and consensus state types?
     // /// The type of client this consensus module provides state for.
     // #[arg(value_parser(|s: &str| ok(ClientType::new(s.to_owned()))))]
     // pub client_type: ClientType,
 }
 
+/// The capabilities of a chain, as declared in the config of its consensus module.
+///
+/// All fields are optional, with `None` meaning that the capability is unknown; consumers are
+/// expected to fall back to their default strategy in that case.
+#[model]
+#[derive(Default, JsonSchema)]
+pub struct ChainCapabilities {
+    /// Whether multiple messages can be submitted in a single transaction. If `false`, the
+    /// transaction plugins submit every message in its own transaction, regardless of their
+    /// configured batch size.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub batch_txs: Option<bool>,
+    /// The average time between blocks, in milliseconds. This is used to size the defers of waits
+    /// on this chain until its block time has been estimated (see
+    /// [`BlockTimes`](crate::block_time::BlockTimes)).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub average_block_time_ms: Option<u64>,
+}
+
 impl ConsensusModuleInfo {
     pub fn id(&self) -> String {
         format!("consensus/{}/{}", self.chain_id, self.consensus_type)
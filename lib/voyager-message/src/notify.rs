@@ -0,0 +1,355 @@
+//! Webhook notifications for relaying lifecycle events.
+//!
+//! External systems can track the activity of the relayer without polling the chains by
+//! configuring webhooks, which are sent a [`Notification`] as a JSON `POST` request whenever:
+//!
+//! - a packet, acknowledgement, or timeout is relayed,
+//! - a step of a connection or channel handshake is submitted,
+//! - a client update is submitted, or
+//! - an op fails with a fatal error and is dead-lettered.
+//!
+//! The relayed messages are determined from the [`TxReceipt`]s returned by transaction plugins,
+//! based on the types of the messages in the transaction. Messages that wrap the IBC datagram in
+//! another message (i.e. a cosmwasm `MsgExecuteContract`) can not be classified, and are not
+//! notified.
+//!
+//! If a webhook is configured with a secret, every request is signed with HMAC-SHA256 over
+//! `{timestamp}.{body}`, where `timestamp` is the unix timestamp sent in the
+//! `X-Voyager-Timestamp` header. The hex encoded signature is sent in the `X-Voyager-Signature`
+//! header as `sha256=<signature>`. Receivers should reject requests with an old timestamp to
+//! prevent replays.
+//!
+//! Delivery is best-effort: failed requests are logged and counted in
+//! [`WEBHOOK_DELIVERY_FAILURES`], but are not retried.
+
+use std::{
+    sync::{Arc, LazyLock},
+    time::Duration,
+};
+
+use hmac::{Hmac, Mac};
+use macros::model;
+use prometheus::{register_int_counter_vec, IntCounterVec};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::Sha256;
+use tracing::{debug, warn};
+use unionlabs::{hash::H256, ErrorReporter};
+use voyager_vm::{now, Op, Visit};
+
+use crate::{
+    core::ChainId,
+    data::{Data, TxReceipt, WithChainId},
+    RawClientId, VoyagerMessage,
+};
+
+pub const SIGNATURE_HEADER: &str = "X-Voyager-Signature";
+pub const TIMESTAMP_HEADER: &str = "X-Voyager-Timestamp";
+
+const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(10);
+
+pub static WEBHOOK_DELIVERY_FAILURES: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!(
+        "voyager_webhook_delivery_failures_total",
+        "The amount of webhook notifications that could not be delivered.",
+        &["kind"],
+    )
+    .unwrap()
+});
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct WebhookConfig {
+    /// The url to `POST` the notifications to.
+    pub url: String,
+    /// The secret used to sign the notifications. If not set, notifications are not signed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub secret: Option<String>,
+    /// The kinds of notifications to send to this webhook. If empty, all notifications are sent.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub kinds: Vec<NotificationKind>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum NotificationKind {
+    PacketRelayed,
+    AckRelayed,
+    TimeoutRelayed,
+    HandshakeStep,
+    ClientUpdated,
+    DeadLettered,
+}
+
+impl NotificationKind {
+    #[must_use]
+    pub const fn as_str(&self) -> &'static str {
+        match self {
+            Self::PacketRelayed => "packet_relayed",
+            Self::AckRelayed => "ack_relayed",
+            Self::TimeoutRelayed => "timeout_relayed",
+            Self::HandshakeStep => "handshake_step",
+            Self::ClientUpdated => "client_updated",
+            Self::DeadLettered => "dead_lettered",
+        }
+    }
+
+    /// The kind of notification for a message of type `msg_type` in a submitted transaction, if
+    /// any. This accepts both the type urls of cosmos-sdk messages (i.e.
+    /// `/ibc.core.channel.v1.MsgRecvPacket`) and the names of ibc-union datagrams (i.e.
+    /// `packet_recv`).
+    #[must_use]
+    pub fn of_msg_type(msg_type: &str) -> Option<Self> {
+        let msg_type = msg_type
+            .rsplit('.')
+            .next()
+            .unwrap_or_default()
+            .chars()
+            .filter(char::is_ascii_alphanumeric)
+            .collect::<String>()
+            .to_ascii_lowercase();
+
+        match msg_type.trim_start_matches("msg") {
+            "recvpacket" | "packetrecv" | "intentpacketrecv" => Some(Self::PacketRelayed),
+            "acknowledgement" | "packetacknowledgement" | "batchacks" => Some(Self::AckRelayed),
+            "timeout" | "timeoutonclose" | "packettimeout" => Some(Self::TimeoutRelayed),
+            "updateclient" => Some(Self::ClientUpdated),
+            step if step.starts_with("connectionopen") || step.starts_with("channelopen") => {
+                Some(Self::HandshakeStep)
+            }
+            _ => None,
+        }
+    }
+}
+
+#[model]
+#[derive(JsonSchema)]
+pub enum Notification {
+    PacketRelayed(RelayedMsg),
+    AckRelayed(RelayedMsg),
+    TimeoutRelayed(RelayedMsg),
+    HandshakeStep(RelayedMsg),
+    ClientUpdated(RelayedMsg),
+    DeadLettered(DeadLettered),
+}
+
+impl Notification {
+    #[must_use]
+    pub const fn kind(&self) -> NotificationKind {
+        match self {
+            Self::PacketRelayed(_) => NotificationKind::PacketRelayed,
+            Self::AckRelayed(_) => NotificationKind::AckRelayed,
+            Self::TimeoutRelayed(_) => NotificationKind::TimeoutRelayed,
+            Self::HandshakeStep(_) => NotificationKind::HandshakeStep,
+            Self::ClientUpdated(_) => NotificationKind::ClientUpdated,
+            Self::DeadLettered(_) => NotificationKind::DeadLettered,
+        }
+    }
+}
+
+/// A message that was included in a transaction submitted on `chain_id`.
+#[model]
+#[derive(JsonSchema)]
+pub struct RelayedMsg {
+    pub chain_id: ChainId,
+    pub tx_hash: H256,
+    pub height: u64,
+    pub msg_type: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_id: Option<RawClientId>,
+}
+
+/// An op that failed with a fatal error, and will not be retried.
+#[model]
+#[derive(JsonSchema)]
+pub struct DeadLettered {
+    pub op: Value,
+    pub error: String,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Notifier {
+    client: reqwest::Client,
+    webhooks: Arc<[WebhookConfig]>,
+}
+
+impl Notifier {
+    #[must_use]
+    pub fn new(webhooks: Vec<WebhookConfig>) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            webhooks: webhooks.into(),
+        }
+    }
+
+    /// Send `notification` to all webhooks that are interested in it. The requests are sent in the
+    /// background.
+    pub fn notify(&self, notification: &Notification) {
+        let kind = notification.kind();
+
+        let webhooks = self
+            .webhooks
+            .iter()
+            .filter(|webhook| webhook.kinds.is_empty() || webhook.kinds.contains(&kind))
+            .cloned()
+            .collect::<Vec<_>>();
+
+        if webhooks.is_empty() {
+            return;
+        }
+
+        let body = serde_json::to_string(notification).expect("serialization is infallible; qed;");
+
+        for webhook in webhooks {
+            let client = self.client.clone();
+            let body = body.clone();
+
+            tokio::spawn(async move {
+                let timestamp = now().to_string();
+
+                let mut request = client
+                    .post(&webhook.url)
+                    .timeout(WEBHOOK_TIMEOUT)
+                    .header(reqwest::header::CONTENT_TYPE, "application/json")
+                    .header(TIMESTAMP_HEADER, &timestamp);
+
+                if let Some(secret) = &webhook.secret {
+                    request = request.header(
+                        SIGNATURE_HEADER,
+                        format!("sha256={}", sign(secret, &timestamp, &body)),
+                    );
+                }
+
+                match request
+                    .body(body)
+                    .send()
+                    .await
+                    .and_then(reqwest::Response::error_for_status)
+                {
+                    Ok(_) => debug!(url = %webhook.url, kind = kind.as_str(), "sent notification"),
+                    Err(err) => {
+                        warn!(
+                            url = %webhook.url,
+                            kind = kind.as_str(),
+                            error = %ErrorReporter(err),
+                            "error sending notification"
+                        );
+
+                        WEBHOOK_DELIVERY_FAILURES
+                            .with_label_values(&[kind.as_str()])
+                            .inc();
+                    }
+                }
+            });
+        }
+    }
+
+    /// Notify all relayed messages in the transaction receipts in `op`, including those nested in
+    /// the queues of promises.
+    pub fn notify_op(&self, op: &mut Op<VoyagerMessage>) {
+        if !self.webhooks.is_empty() {
+            NotifyVisitor(self).visit_op(op);
+        }
+    }
+
+    /// Notify all relayed messages in a transaction submitted on `chain_id`. Messages that failed
+    /// are not notified.
+    pub fn notify_receipt(&self, chain_id: &ChainId, receipt: &TxReceipt) {
+        for msg in receipt.msgs.iter().filter(|msg| msg.error.is_none()) {
+            let Some(kind) = NotificationKind::of_msg_type(&msg.msg_type) else {
+                continue;
+            };
+
+            let relayed = RelayedMsg {
+                chain_id: chain_id.clone(),
+                tx_hash: receipt.tx_hash,
+                height: receipt.height,
+                msg_type: msg.msg_type.clone(),
+                client_id: msg.client_id.clone(),
+            };
+
+            self.notify(&match kind {
+                NotificationKind::PacketRelayed => Notification::PacketRelayed(relayed),
+                NotificationKind::AckRelayed => Notification::AckRelayed(relayed),
+                NotificationKind::TimeoutRelayed => Notification::TimeoutRelayed(relayed),
+                NotificationKind::HandshakeStep => Notification::HandshakeStep(relayed),
+                NotificationKind::ClientUpdated => Notification::ClientUpdated(relayed),
+                NotificationKind::DeadLettered => unreachable!("not a msg type; qed;"),
+            });
+        }
+    }
+}
+
+struct NotifyVisitor<'a>(&'a Notifier);
+
+impl Visit<VoyagerMessage> for NotifyVisitor<'_> {
+    fn visit_data(&mut self, data: &mut Data) {
+        if let Data::TxReceipt(WithChainId { chain_id, message }) = data {
+            self.0.notify_receipt(chain_id, message);
+        }
+    }
+}
+
+/// The hex encoded HMAC-SHA256 of `{timestamp}.{body}`.
+fn sign(secret: &str, timestamp: &str, body: &str) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("hmac accepts any key length");
+
+    mac.update(timestamp.as_bytes());
+    mac.update(b".");
+    mac.update(body.as_bytes());
+
+    hex::encode(mac.finalize().into_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn msg_type_kinds() {
+        assert_eq!(
+            NotificationKind::of_msg_type("/ibc.core.channel.v1.MsgRecvPacket"),
+            Some(NotificationKind::PacketRelayed)
+        );
+        assert_eq!(
+            NotificationKind::of_msg_type("/ibc.core.channel.v1.MsgAcknowledgement"),
+            Some(NotificationKind::AckRelayed)
+        );
+        assert_eq!(
+            NotificationKind::of_msg_type("/ibc.core.connection.v1.MsgConnectionOpenTry"),
+            Some(NotificationKind::HandshakeStep)
+        );
+        assert_eq!(
+            NotificationKind::of_msg_type("/ibc.core.client.v1.MsgUpdateClient"),
+            Some(NotificationKind::ClientUpdated)
+        );
+        assert_eq!(
+            NotificationKind::of_msg_type("packet_recv"),
+            Some(NotificationKind::PacketRelayed)
+        );
+        assert_eq!(
+            NotificationKind::of_msg_type("channel_open_confirm"),
+            Some(NotificationKind::HandshakeStep)
+        );
+        assert_eq!(
+            NotificationKind::of_msg_type("packet_timeout"),
+            Some(NotificationKind::TimeoutRelayed)
+        );
+        assert_eq!(
+            NotificationKind::of_msg_type("/cosmwasm.wasm.v1.MsgExecuteContract"),
+            None
+        );
+        assert_eq!(NotificationKind::of_msg_type("create_client"), None);
+    }
+
+    #[test]
+    fn signature() {
+        // echo -n '1700000000.{}' | openssl dgst -sha256 -hmac secret
+        assert_eq!(
+            sign("secret", "1700000000", "{}"),
+            "b8569b78799ff9e3cbff0fc2d63a33a2b57f3282abd07c37ae5e8e7d79a5f163"
+        );
+    }
+}
@@ -0,0 +1,708 @@
+//! Tracking of the lifecycle of individual packets.
+//!
+//! Every packet that voyager observes is tracked through its lifecycle, from being sent on the
+//! source chain, to being received on the destination chain, to its acknowledgement being relayed
+//! back to the source chain (or the packet timing out). The state of a packet can be queried via
+//! the control API, answering "where is my transfer" without searching through the events of both
+//! chains.
+//!
+//! States are updated both from the events returned by event source plugins and from the
+//! [`TxReceipt`]s returned by transaction plugins, such that a packet relayed by voyager is updated
+//! as soon as the transaction is included, even if the event source for the chain lags behind.
+//! States only ever move forwards, so events that are observed out of order (or more than once) do
+//! not cause a packet to regress.
+//!
+//! [`IbcClassic`] packets are identified by their channel and sequence, and [`IbcUnion`] packets by
+//! their hash (see [`PacketId`]). Only the receipts of cosmos-sdk transactions are understood, so
+//! [`IbcUnion`] packets are updated from events alone. The states are kept in memory and are not
+//! persisted; at most [`DEFAULT_PACKET_STATUS_CAPACITY`] packets are tracked, after which the
+//! oldest packets are forgotten.
+
+use std::{
+    collections::{HashMap, VecDeque},
+    num::NonZeroU64,
+    sync::{Arc, LazyLock, Mutex},
+};
+
+use alloy::sol_types::SolValue;
+use ibc_classic_spec::IbcClassic;
+use ibc_union_spec::IbcUnion;
+use prometheus::{register_int_counter_vec, IntCounterVec};
+use serde::{Deserialize, Serialize};
+use unionlabs::{ethereum::keccak256, hash::H256, id::ChannelId};
+use voyager_vm::{now, Op, Visit};
+
+use crate::{
+    core::ChainId,
+    data::{ChainEvent, Data, TxEvent, TxReceipt, WithChainId},
+    VoyagerMessage,
+};
+
+/// The default amount of packets to track the state of.
+pub const DEFAULT_PACKET_STATUS_CAPACITY: usize = 100_000;
+
+pub static PACKET_TRANSITIONS: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!(
+        "voyager_packet_transitions_total",
+        "The amount of packets that transitioned into each state, by source chain.",
+        &["chain_id", "state"],
+    )
+    .unwrap()
+});
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PacketState {
+    /// The packet was sent on the source chain.
+    Sent,
+    /// The packet was received on the destination chain.
+    Received,
+    /// The acknowledgement of the packet was relayed back to the source chain.
+    Acknowledged,
+    /// The packet timed out, and the timeout was relayed back to the source chain.
+    TimedOut,
+}
+
+impl PacketState {
+    #[must_use]
+    pub const fn as_str(&self) -> &'static str {
+        match self {
+            Self::Sent => "sent",
+            Self::Received => "received",
+            Self::Acknowledged => "acknowledged",
+            Self::TimedOut => "timed_out",
+        }
+    }
+
+    /// The position of this state in the lifecycle of a packet. Both final states share the same
+    /// rank, as a packet can only ever reach one of them.
+    const fn rank(self) -> u8 {
+        match self {
+            Self::Sent => 0,
+            Self::Received => 1,
+            Self::Acknowledged | Self::TimedOut => 2,
+        }
+    }
+}
+
+/// The identifier of one end of a packet.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PacketId {
+    /// An [`IbcClassic`] packet, identified by the channel it was sent or received on and its
+    /// sequence (which is the same on both ends).
+    Classic {
+        channel_id: ChannelId,
+        sequence: NonZeroU64,
+    },
+    /// An [`IbcUnion`] packet, identified by its hash (which is the same on both ends).
+    Union { packet_hash: H256 },
+}
+
+/// The state of a packet, along with the transactions that moved it through its lifecycle.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PacketStatus {
+    /// The chain the packet was sent on.
+    pub chain_id: ChainId,
+    pub packet: PacketId,
+    /// The chain the packet is sent to, if known.
+    pub counterparty_chain_id: Option<ChainId>,
+    /// The packet on `counterparty_chain_id`, if known.
+    pub counterparty_packet: Option<PacketId>,
+    pub state: PacketState,
+    /// Every state the packet has been observed in, in order.
+    pub transitions: Vec<PacketTransition>,
+}
+
+/// A transaction that moved a packet into `state`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PacketTransition {
+    pub state: PacketState,
+    /// The chain the transaction was included on.
+    pub chain_id: ChainId,
+    pub tx_hash: H256,
+    pub height: u64,
+    /// The unix timestamp (in seconds) at which the transition was observed.
+    pub observed_at: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct PacketKey {
+    chain_id: ChainId,
+    packet: PacketId,
+}
+
+#[derive(Debug, Clone)]
+pub struct PacketStatuses {
+    capacity: usize,
+    inner: Arc<Mutex<Packets>>,
+}
+
+#[derive(Debug, Default)]
+struct Packets {
+    statuses: HashMap<PacketKey, PacketStatus>,
+    /// The destination end of the packets in [`Self::statuses`], keyed by the destination chain
+    /// and packet. Transactions on the destination chain only identify the destination end of the
+    /// packet, so they are resolved to the source end of the packet via this index.
+    counterparties: HashMap<PacketKey, PacketKey>,
+    /// The keys in [`Self::statuses`], in the order they were first seen.
+    order: VecDeque<PacketKey>,
+}
+
+/// An observed transition of a packet, identified by the end of the packet on the chain the
+/// transition was observed on.
+struct Observation {
+    /// The end of the packet on the chain the transition was observed on.
+    local: PacketKey,
+    /// The other end of the packet, if known.
+    remote: Option<PacketKey>,
+    /// Whether `local` is the source end of the packet.
+    local_is_source: bool,
+    transition: PacketTransition,
+}
+
+impl Default for PacketStatuses {
+    fn default() -> Self {
+        Self::new(DEFAULT_PACKET_STATUS_CAPACITY)
+    }
+}
+
+impl PacketStatuses {
+    /// Track the state of at most `capacity` packets.
+    #[must_use]
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            inner: Arc::default(),
+        }
+    }
+
+    /// The status of `packet` on `chain_id`. Either end of the packet can be used to look it up.
+    pub fn packet_status(&self, chain_id: &ChainId, packet: PacketId) -> Option<PacketStatus> {
+        let packets = self.inner.lock().expect("mutex is poisoned");
+
+        let key = PacketKey {
+            chain_id: chain_id.clone(),
+            packet,
+        };
+
+        packets
+            .statuses
+            .get(packets.counterparties.get(&key).unwrap_or(&key))
+            .cloned()
+    }
+
+    /// Record the packet transitions of all events and transaction receipts in `op`, including
+    /// those nested in the queues of promises.
+    pub fn record_op(&self, op: &mut Op<VoyagerMessage>) {
+        RecordVisitor(self).visit_op(op);
+    }
+
+    /// Record the packet transition of `event`, if it is a packet event.
+    pub fn record_event(&self, event: &ChainEvent) {
+        let Some((state, source, destination)) =
+            classic_packet_event(event).or_else(|| union_packet_event(event))
+        else {
+            return;
+        };
+
+        let local_is_source = state != PacketState::Received;
+
+        let (local, remote) = if local_is_source {
+            (source, destination)
+        } else {
+            (destination, source)
+        };
+
+        self.record(Observation {
+            local: PacketKey {
+                chain_id: event.chain_id.clone(),
+                packet: local,
+            },
+            remote: Some(PacketKey {
+                chain_id: event.counterparty_chain_id.clone(),
+                packet: remote,
+            }),
+            local_is_source,
+            transition: PacketTransition {
+                state,
+                chain_id: event.chain_id.clone(),
+                tx_hash: event.tx_hash,
+                height: event.provable_height.height(),
+                observed_at: now(),
+            },
+        });
+    }
+
+    /// Record the packet transitions of a transaction submitted on `chain_id`.
+    ///
+    /// Receipts only identify the end of the packet on `chain_id`, so packets received on
+    /// `chain_id` are only recorded if the packet was already seen being sent.
+    pub fn record_receipt(&self, chain_id: &ChainId, receipt: &TxReceipt) {
+        for event in &receipt.events {
+            let Some((state, packet)) = receipt_event(event) else {
+                continue;
+            };
+
+            self.record(Observation {
+                local: PacketKey {
+                    chain_id: chain_id.clone(),
+                    packet,
+                },
+                remote: None,
+                local_is_source: state != PacketState::Received,
+                transition: PacketTransition {
+                    state,
+                    chain_id: chain_id.clone(),
+                    tx_hash: receipt.tx_hash,
+                    height: receipt.height,
+                    observed_at: now(),
+                },
+            });
+        }
+    }
+
+    fn record(&self, observation: Observation) {
+        let mut packets = self.inner.lock().expect("mutex is poisoned");
+
+        let (source, destination) = if observation.local_is_source {
+            (Some(observation.local), observation.remote)
+        } else {
+            (observation.remote, Some(observation.local))
+        };
+
+        let source = match (source, &destination) {
+            (Some(source), _) => source,
+            (None, Some(destination)) => match packets.counterparties.get(destination) {
+                Some(source) => source.clone(),
+                None => return,
+            },
+            (None, None) => return,
+        };
+
+        if !packets.statuses.contains_key(&source) {
+            packets.insert(source.clone(), self.capacity);
+        }
+
+        let status = packets
+            .statuses
+            .get_mut(&source)
+            .expect("status was inserted; qed;");
+
+        if let Some(destination) = &destination {
+            status.counterparty_chain_id = Some(destination.chain_id.clone());
+            status.counterparty_packet = Some(destination.packet.clone());
+        }
+
+        let transition = observation.transition;
+
+        // the same transition is usually observed both from the receipt and the event
+        if !status
+            .transitions
+            .iter()
+            .any(|seen| seen.state == transition.state)
+        {
+            PACKET_TRANSITIONS
+                .with_label_values(&[source.chain_id.as_str(), transition.state.as_str()])
+                .inc();
+
+            if transition.state.rank() > status.state.rank() {
+                status.state = transition.state;
+            }
+
+            status.transitions.push(transition);
+            status.transitions.sort_by_key(|seen| seen.state.rank());
+        }
+
+        if let Some(destination) = destination {
+            packets.counterparties.insert(destination, source);
+        }
+    }
+}
+
+impl Packets {
+    /// Start tracking the packet `key`, forgetting the oldest packets if more than `capacity`
+    /// packets are tracked.
+    fn insert(&mut self, key: PacketKey, capacity: usize) {
+        while self.order.len() >= capacity.max(1) {
+            let oldest = self.order.pop_front().expect("order is not empty; qed;");
+
+            if let Some(status) = self.statuses.remove(&oldest) {
+                if let (Some(chain_id), Some(packet)) =
+                    (status.counterparty_chain_id, status.counterparty_packet)
+                {
+                    self.counterparties.remove(&PacketKey { chain_id, packet });
+                }
+            }
+        }
+
+        self.statuses.insert(
+            key.clone(),
+            PacketStatus {
+                chain_id: key.chain_id.clone(),
+                packet: key.packet.clone(),
+                counterparty_chain_id: None,
+                counterparty_packet: None,
+                state: PacketState::Sent,
+                transitions: vec![],
+            },
+        );
+        self.order.push_back(key);
+    }
+}
+
+/// The packet transition of an [`IbcClassic`] event, along with the source and destination end of
+/// the packet, if it is a packet event.
+fn classic_packet_event(event: &ChainEvent) -> Option<(PacketState, PacketId, PacketId)> {
+    use ibc_classic_spec::FullEvent;
+
+    let (packet, state) = match event.decode_event::<IbcClassic>()?.ok()? {
+        FullEvent::SendPacket(event) => (event.packet, PacketState::Sent),
+        FullEvent::RecvPacket(event) => (event.packet, PacketState::Received),
+        FullEvent::WriteAcknowledgement(event) => (event.packet, PacketState::Received),
+        FullEvent::AcknowledgePacket(event) => (event.packet, PacketState::Acknowledged),
+        FullEvent::TimeoutPacket(event) => (event.packet, PacketState::TimedOut),
+        _ => return None,
+    };
+
+    Some((
+        state,
+        PacketId::Classic {
+            channel_id: packet.source_channel.channel_id,
+            sequence: packet.sequence,
+        },
+        PacketId::Classic {
+            channel_id: packet.destination_channel.channel_id,
+            sequence: packet.sequence,
+        },
+    ))
+}
+
+/// The packet transition of an [`IbcUnion`] event, along with the source and destination end of
+/// the packet, if it is a packet event.
+fn union_packet_event(event: &ChainEvent) -> Option<(PacketState, PacketId, PacketId)> {
+    use ibc_union_spec::FullEvent;
+
+    let (packet, packet_data, state) = match event.decode_event::<IbcUnion>()?.ok()? {
+        FullEvent::SendPacket(event) => (event.packet, event.packet_data, PacketState::Sent),
+        FullEvent::RecvPacket(event) => (event.packet, event.packet_data, PacketState::Received),
+        FullEvent::RecvIntentPacket(event) => {
+            (event.packet, event.packet_data, PacketState::Received)
+        }
+        FullEvent::WriteAcknowledgement(event) => {
+            (event.packet, event.packet_data, PacketState::Received)
+        }
+        FullEvent::AcknowledgePacket(event) => {
+            (event.packet, event.packet_data, PacketState::Acknowledged)
+        }
+        FullEvent::TimeoutPacket(event) => (event.packet, event.packet_data, PacketState::TimedOut),
+        _ => return None,
+    };
+
+    let packet_hash = keccak256(
+        ibc_solidity::Packet {
+            source_channel: packet.source_channel.channel_id,
+            destination_channel: packet.destination_channel.channel_id,
+            data: packet_data.into(),
+            timeout_height: packet.timeout_height,
+            timeout_timestamp: packet.timeout_timestamp,
+        }
+        .abi_encode(),
+    );
+
+    Some((
+        state,
+        PacketId::Union { packet_hash },
+        PacketId::Union { packet_hash },
+    ))
+}
+
+/// The packet transition of an event emitted by a cosmos-sdk transaction, if it is a packet event.
+fn receipt_event(event: &TxEvent) -> Option<(PacketState, PacketId)> {
+    let (state, channel_attribute) = match event.name.as_str() {
+        "send_packet" => (PacketState::Sent, "packet_src_channel"),
+        "recv_packet" | "write_acknowledgement" => (PacketState::Received, "packet_dst_channel"),
+        "acknowledge_packet" => (PacketState::Acknowledged, "packet_src_channel"),
+        "timeout_packet" => (PacketState::TimedOut, "packet_src_channel"),
+        _ => return None,
+    };
+
+    let channel_id = event.attributes.get(channel_attribute)?.as_str()?;
+    let sequence = event.attributes.get("packet_sequence")?.as_str()?;
+
+    Some((
+        state,
+        PacketId::Classic {
+            channel_id: ChannelId::from_str_prefixed(channel_id).ok()?,
+            sequence: sequence.parse().ok()?,
+        },
+    ))
+}
+
+struct RecordVisitor<'a>(&'a PacketStatuses);
+
+impl Visit<VoyagerMessage> for RecordVisitor<'_> {
+    fn visit_data(&mut self, data: &mut Data) {
+        match data {
+            Data::IbcEvent(event) => self.0.record_event(event),
+            Data::TxReceipt(WithChainId { chain_id, message }) => {
+                self.0.record_receipt(chain_id, message);
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ibc_classic_spec::{
+        AcknowledgePacket, ChannelMetadata, ConnectionMetadata, FullEvent, PacketMetadata,
+        SendPacket,
+    };
+    use serde_json::json;
+    use unionlabs::{
+        ibc::core::{channel::order::Order, client::height::Height},
+        id::{ClientId, ConnectionId, PortId},
+        option_unwrap,
+    };
+
+    use super::*;
+    use crate::{
+        core::{ClientInfo, ClientType, IbcInterface, IbcSpec},
+        into_value,
+    };
+
+    const SEQUENCE: NonZeroU64 = option_unwrap!(NonZeroU64::new(7));
+
+    fn classic(channel_id: u32) -> PacketId {
+        PacketId::Classic {
+            channel_id: ChannelId::new(channel_id),
+            sequence: SEQUENCE,
+        }
+    }
+
+    fn packet() -> PacketMetadata {
+        let channel = |channel_id| ChannelMetadata {
+            port_id: PortId::new("transfer").unwrap(),
+            channel_id: ChannelId::new(channel_id),
+            version: "ics20-1".to_owned(),
+            connection: ConnectionMetadata {
+                client_id: ClientId::new_static("07-tendermint", 0),
+                connection_id: ConnectionId::new(0),
+            },
+        };
+
+        PacketMetadata {
+            sequence: SEQUENCE,
+            source_channel: channel(1),
+            destination_channel: channel(2),
+            channel_ordering: Order::Unordered,
+            timeout_height: Height::new(0),
+            timeout_timestamp: 0,
+        }
+    }
+
+    fn event<V: IbcSpec>(
+        chain_id: &str,
+        counterparty_chain_id: &str,
+        event: V::Event,
+    ) -> ChainEvent {
+        ChainEvent {
+            chain_id: ChainId::new(chain_id.to_owned()),
+            client_info: ClientInfo {
+                client_type: ClientType::new("client"),
+                ibc_interface: IbcInterface::new("interface"),
+                metadata: Default::default(),
+            },
+            counterparty_chain_id: ChainId::new(counterparty_chain_id.to_owned()),
+            tx_hash: H256::new([1; 32]),
+            provable_height: Height::new(10),
+            ibc_spec_id: V::ID,
+            event: into_value(event),
+            decoded_packet_data: None,
+        }
+    }
+
+    fn receipt(name: &str, channel_attribute: &str, channel_id: &str) -> TxReceipt {
+        TxReceipt {
+            tx_hash: H256::new([2; 32]),
+            height: 20,
+            gas_used: 0,
+            fee: None,
+            msgs: vec![],
+            events: vec![TxEvent {
+                name: name.to_owned(),
+                attributes: json!({
+                    (channel_attribute): channel_id,
+                    "packet_sequence": SEQUENCE.to_string(),
+                }),
+            }],
+        }
+    }
+
+    #[test]
+    fn lifecycle() {
+        let statuses = PacketStatuses::default();
+
+        let source = ChainId::new("source");
+        let destination = ChainId::new("destination");
+
+        statuses.record_event(&event::<IbcClassic>(
+            "source",
+            "destination",
+            FullEvent::SendPacket(SendPacket {
+                packet_data: Default::default(),
+                packet: packet(),
+            }),
+        ));
+
+        let status = statuses.packet_status(&source, classic(1)).unwrap();
+        assert_eq!(status.state, PacketState::Sent);
+        assert_eq!(status.counterparty_chain_id, Some(destination.clone()));
+
+        // the receipt on the destination only identifies the destination channel
+        statuses.record_receipt(
+            &destination,
+            &receipt("recv_packet", "packet_dst_channel", "channel-2"),
+        );
+
+        // either end of the packet can be queried
+        let status = statuses.packet_status(&destination, classic(2)).unwrap();
+        assert_eq!(status.state, PacketState::Received);
+        assert_eq!(status.transitions[1].chain_id, destination);
+        assert_eq!(status.transitions[1].height, 20);
+
+        statuses.record_event(&event::<IbcClassic>(
+            "source",
+            "destination",
+            FullEvent::AcknowledgePacket(AcknowledgePacket { packet: packet() }),
+        ));
+
+        // observing the send again does not regress the packet
+        statuses.record_event(&event::<IbcClassic>(
+            "source",
+            "destination",
+            FullEvent::SendPacket(SendPacket {
+                packet_data: Default::default(),
+                packet: packet(),
+            }),
+        ));
+
+        let status = statuses.packet_status(&source, classic(1)).unwrap();
+        assert_eq!(status.state, PacketState::Acknowledged);
+        assert_eq!(
+            status
+                .transitions
+                .iter()
+                .map(|transition| transition.state)
+                .collect::<Vec<_>>(),
+            [
+                PacketState::Sent,
+                PacketState::Received,
+                PacketState::Acknowledged
+            ]
+        );
+    }
+
+    #[test]
+    fn union_packets_are_identified_by_their_hash() {
+        use ibc_union_spec::{
+            ChannelMetadata, ConnectionMetadata, FullEvent, PacketMetadata, RecvPacket, SendPacket,
+        };
+
+        let statuses = PacketStatuses::default();
+
+        let channel = |channel_id| ChannelMetadata {
+            channel_id,
+            version: "ucs03-zkgm-0".to_owned(),
+            connection: ConnectionMetadata {
+                client_id: 1,
+                connection_id: 1,
+            },
+        };
+
+        let packet = PacketMetadata {
+            source_channel: channel(1),
+            destination_channel: channel(2),
+            timeout_height: 0,
+            timeout_timestamp: 100,
+        };
+
+        statuses.record_event(&event::<IbcUnion>(
+            "source",
+            "destination",
+            FullEvent::SendPacket(SendPacket {
+                packet_data: Default::default(),
+                packet: packet.clone(),
+            }),
+        ));
+        statuses.record_event(&event::<IbcUnion>(
+            "destination",
+            "source",
+            FullEvent::RecvPacket(RecvPacket {
+                packet_data: Default::default(),
+                packet,
+                relayer_msg: Default::default(),
+            }),
+        ));
+
+        let status = statuses
+            .packet_status(
+                &ChainId::new("source"),
+                PacketId::Union {
+                    packet_hash: keccak256(
+                        ibc_solidity::Packet {
+                            source_channel: 1,
+                            destination_channel: 2,
+                            data: Default::default(),
+                            timeout_height: 0,
+                            timeout_timestamp: 100,
+                        }
+                        .abi_encode(),
+                    ),
+                },
+            )
+            .unwrap();
+        assert_eq!(status.state, PacketState::Received);
+
+        // the hash is the same on both ends of the packet
+        assert_eq!(
+            statuses.packet_status(&ChainId::new("destination"), status.packet.clone()),
+            Some(status)
+        );
+    }
+
+    #[test]
+    fn unknown_destination_receipts_are_ignored() {
+        let statuses = PacketStatuses::default();
+
+        statuses.record_receipt(
+            &ChainId::new("destination"),
+            &receipt("recv_packet", "packet_dst_channel", "channel-2"),
+        );
+
+        assert_eq!(
+            statuses.packet_status(&ChainId::new("destination"), classic(2)),
+            None
+        );
+    }
+
+    #[test]
+    fn oldest_packets_are_forgotten() {
+        let statuses = PacketStatuses::new(1);
+
+        statuses.record_receipt(
+            &ChainId::new("a"),
+            &receipt("send_packet", "packet_src_channel", "channel-1"),
+        );
+        statuses.record_receipt(
+            &ChainId::new("b"),
+            &receipt("send_packet", "packet_src_channel", "channel-1"),
+        );
+
+        assert_eq!(statuses.packet_status(&ChainId::new("a"), classic(1)), None);
+        assert!(statuses
+            .packet_status(&ChainId::new("b"), classic(1))
+            .is_some());
+    }
+}
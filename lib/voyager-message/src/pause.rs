@@ -0,0 +1,380 @@
+//! Runtime switches for pausing the processing of ops.
+//!
+//! Ops can be paused by their [`QueueMessage::concurrency_key`] (i.e. the pair of chains they relay
+//! between), by any chain they interact with, or by the client they target, such that all traffic
+//! on a route, chain, or client can be held during a chain halt or upgrade. Paused ops stay in the
+//! queue and are processed as normal once resumed.
+//!
+//! Ops sent to plugins are only matched by their concurrency key, since their contents are opaque
+//! to voyager.
+//!
+//! Everything that can be paused is identified by a pause key (see [`pause_keys`]), which queues
+//! that persist ops store alongside them such that paused ops can be skipped without being decoded.
+//!
+//! Separately from the pauses set by operators, the calls to a transaction plugin (i.e. its
+//! submissions) can be held while none of its signers can pay for fees. Holding the submissions
+//! leaves the event ingestion of the chain and the relaying to its counterparties unaffected, and
+//! neither kind of pause resumes the other.
+
+use std::{
+    collections::{BTreeSet, HashSet},
+    sync::{Arc, RwLock},
+};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tracing::info;
+use voyager_core::ChainId;
+use voyager_vm::{Op, QueueMessage};
+
+use crate::{
+    call::{
+        Call, FetchBlockRange, FetchBlocks, FetchPacketEvents, FetchUpdateHeaders,
+        RecordCheckpoint, WaitForFinality, WaitForHeight, WaitForTimestamp, WaitForTrustedHeight,
+        WaitForTxInclusion,
+    },
+    callback::{
+        AggregateMsgUpdateClientsFromOrderedHeaders, AggregateSubmitTxFromOrderedClientUpdates,
+        Callback,
+    },
+    data::{client_id_of, AppEvent, ChainEvent, Data, WithChainId},
+    PluginMessage, RawClientId, VoyagerMessage,
+};
+
+#[derive(Debug, Clone, Default)]
+pub struct Pauses {
+    inner: Arc<RwLock<Paused>>,
+}
+
+/// Everything that is currently paused.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Paused {
+    /// The paused concurrency keys.
+    pub keys: BTreeSet<String>,
+    pub chains: HashSet<ChainId>,
+    /// The paused clients, along with the chain they are on.
+    pub clients: HashSet<(ChainId, RawClientId)>,
+    /// The plugins that calls are held for, i.e. transaction plugins whose signers are all out of
+    /// funds.
+    pub submissions: BTreeSet<String>,
+}
+
+impl Paused {
+    fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+            && self.chains.is_empty()
+            && self.clients.is_empty()
+            && self.submissions.is_empty()
+    }
+}
+
+impl Pauses {
+    /// Pause all ops with the concurrency key `key`. Returns `false` if `key` was already paused.
+    pub fn pause(&self, key: String) -> bool {
+        info!(%key, "pausing");
+
+        self.write().keys.insert(key)
+    }
+
+    /// Resume all ops with the concurrency key `key`. Returns `false` if `key` was not paused.
+    pub fn resume(&self, key: &str) -> bool {
+        info!(%key, "resuming");
+
+        self.write().keys.remove(key)
+    }
+
+    /// Pause all ops interacting with `chain_id`. Returns `false` if the chain was already paused.
+    pub fn pause_chain(&self, chain_id: ChainId) -> bool {
+        info!(%chain_id, "pausing chain");
+
+        self.write().chains.insert(chain_id)
+    }
+
+    /// Resume all ops interacting with `chain_id`. Returns `false` if the chain was not paused.
+    pub fn resume_chain(&self, chain_id: &ChainId) -> bool {
+        info!(%chain_id, "resuming chain");
+
+        self.write().chains.remove(chain_id)
+    }
+
+    /// Pause all ops targeting the client `client_id` on `chain_id`. Returns `false` if the
+    /// client was already paused.
+    pub fn pause_client(&self, chain_id: ChainId, client_id: RawClientId) -> bool {
+        info!(%chain_id, client_id = %client_id.0, "pausing client");
+
+        self.write().clients.insert((chain_id, client_id))
+    }
+
+    /// Resume all ops targeting the client `client_id` on `chain_id`. Returns `false` if the
+    /// client was not paused.
+    pub fn resume_client(&self, chain_id: ChainId, client_id: RawClientId) -> bool {
+        info!(%chain_id, client_id = %client_id.0, "resuming client");
+
+        self.write().clients.remove(&(chain_id, client_id))
+    }
+
+    /// Hold all calls to the plugin `plugin`. Returns `false` if the calls were already held.
+    pub fn hold_submissions(&self, plugin: String) -> bool {
+        self.write().submissions.insert(plugin)
+    }
+
+    /// Release the calls to the plugin `plugin`. Returns `false` if the calls were not held.
+    pub fn release_submissions(&self, plugin: &str) -> bool {
+        self.write().submissions.remove(plugin)
+    }
+
+    /// Everything that is currently paused.
+    pub fn paused(&self) -> Paused {
+        self.inner.read().expect("lock is poisoned").clone()
+    }
+
+    /// The [pause keys](pause_keys) of everything that is currently paused.
+    pub fn paused_keys(&self) -> Vec<String> {
+        let paused = self.inner.read().expect("lock is poisoned");
+
+        if paused.is_empty() {
+            return vec![];
+        }
+
+        paused
+            .keys
+            .iter()
+            .map(|key| key_pause_key(key))
+            .chain(paused.chains.iter().map(chain_pause_key))
+            .chain(
+                paused
+                    .clients
+                    .iter()
+                    .map(|(chain_id, client_id)| client_pause_key(chain_id, &client_id.0)),
+            )
+            .chain(
+                paused
+                    .submissions
+                    .iter()
+                    .map(|plugin| plugin_pause_key(plugin)),
+            )
+            .collect()
+    }
+
+    pub fn is_paused(&self, op: &Op<VoyagerMessage>) -> bool {
+        op.is_paused(&self.paused_keys())
+    }
+
+    fn write(&self) -> std::sync::RwLockWriteGuard<'_, Paused> {
+        self.inner.write().expect("lock is poisoned")
+    }
+}
+
+/// The keys that `op` can be paused by: its concurrency key, the chains and clients it interacts
+/// with, and the plugins it calls.
+pub fn pause_keys(op: &Op<VoyagerMessage>) -> Vec<String> {
+    let mut targets = Targets::default();
+    targets.visit(op);
+
+    let mut keys = VoyagerMessage::concurrency_key(op)
+        .map(|key| key_pause_key(&key))
+        .into_iter()
+        .chain(targets.chains.into_iter().map(chain_pause_key))
+        .chain(
+            targets
+                .clients
+                .into_iter()
+                .map(|(chain_id, client_id)| client_pause_key(chain_id, client_id)),
+        )
+        .chain(targets.plugins.into_iter().map(plugin_pause_key))
+        .collect::<Vec<_>>();
+
+    keys.sort_unstable();
+    keys.dedup();
+
+    keys
+}
+
+fn key_pause_key(key: &str) -> String {
+    format!("key:{key}")
+}
+
+fn chain_pause_key(chain_id: &ChainId) -> String {
+    format!("chain:{chain_id}")
+}
+
+fn client_pause_key(chain_id: &ChainId, client_id: &Value) -> String {
+    format!("client:{chain_id}:{client_id}")
+}
+
+fn plugin_pause_key(plugin: &str) -> String {
+    format!("plugin:{plugin}")
+}
+
+/// The chains and clients that an op interacts with, and the plugins it calls.
+#[derive(Default)]
+struct Targets<'a> {
+    chains: Vec<&'a ChainId>,
+    clients: Vec<(&'a ChainId, &'a Value)>,
+    plugins: Vec<&'a str>,
+}
+
+impl<'a> Targets<'a> {
+    fn visit(&mut self, op: &'a Op<VoyagerMessage>) {
+        match op {
+            Op::Call(call) => self.visit_call(call),
+            Op::Data(data) => self.visit_data(data),
+            Op::Promise(promise) => {
+                promise.queue.iter().for_each(|op| self.visit(op));
+                promise.data.iter().for_each(|data| self.visit_data(data));
+                self.visit_callback(&promise.receiver);
+            }
+            Op::Seq(ops) | Op::Conc(ops) => ops.iter().for_each(|op| self.visit(op)),
+            Op::Void(op) | Op::Retry { msg: op, .. } | Op::WithPriority { msg: op, .. } => {
+                self.visit(op);
+            }
+            Op::Defer { .. } | Op::Noop => {}
+        }
+    }
+
+    fn visit_call(&mut self, call: &'a Call) {
+        match call {
+            Call::FetchBlocks(FetchBlocks { chain_id, .. })
+            | Call::FetchBlockRange(FetchBlockRange { chain_id, .. })
+            | Call::FetchPacketEvents(FetchPacketEvents { chain_id, .. })
+            | Call::WaitForHeight(WaitForHeight { chain_id, .. })
+            | Call::WaitForFinality(WaitForFinality { chain_id, .. })
+            | Call::WaitForTimestamp(WaitForTimestamp { chain_id, .. })
+            | Call::WaitForTxInclusion(WaitForTxInclusion { chain_id, .. })
+            | Call::RecordCheckpoint(RecordCheckpoint { chain_id, .. }) => {
+                self.chains.push(chain_id);
+            }
+            Call::FetchUpdateHeaders(FetchUpdateHeaders {
+                chain_id,
+                counterparty_chain_id,
+                ..
+            }) => {
+                self.chains.push(chain_id);
+                self.chains.push(counterparty_chain_id);
+            }
+            Call::WaitForTrustedHeight(WaitForTrustedHeight {
+                chain_id,
+                client_id,
+                ..
+            }) => {
+                self.chains.push(chain_id);
+                self.clients.push((chain_id, &client_id.0));
+            }
+            Call::Plugin(PluginMessage { plugin, .. }) => self.plugins.push(plugin),
+        }
+    }
+
+    fn visit_data(&mut self, data: &'a Data) {
+        match data {
+            Data::IbcEvent(ChainEvent {
+                chain_id,
+                counterparty_chain_id,
+                event,
+                ..
+            }) => {
+                self.chains.push(chain_id);
+                self.chains.push(counterparty_chain_id);
+                self.clients
+                    .extend(client_id_of(event).map(|id| (chain_id, id)));
+            }
+            Data::IdentifiedIbcDatagram(WithChainId { chain_id, message }) => {
+                self.chains.push(chain_id);
+                self.clients
+                    .extend(client_id_of(&message.datagram).map(|id| (chain_id, id)));
+            }
+            Data::IdentifiedIbcDatagramBatch(WithChainId { chain_id, message }) => {
+                self.chains.push(chain_id);
+                self.clients.extend(
+                    message
+                        .iter()
+                        .filter_map(|datagram| client_id_of(&datagram.datagram))
+                        .map(|id| (chain_id, id)),
+                );
+            }
+            Data::TxReceipt(WithChainId { chain_id, .. })
+            | Data::SignerBalances(WithChainId { chain_id, .. })
+            | Data::AppEvent(AppEvent { chain_id, .. }) => self.chains.push(chain_id),
+            Data::IbcDatagram(_)
+            | Data::OrderedHeaders(_)
+            | Data::OrderedMsgUpdateClients(_)
+            | Data::Plugin(_) => {}
+        }
+    }
+
+    fn visit_callback(&mut self, callback: &'a Callback) {
+        match callback {
+            Callback::AggregateMsgUpdateClientsFromOrderedHeaders(
+                AggregateMsgUpdateClientsFromOrderedHeaders { chain_id, .. },
+            )
+            | Callback::AggregateSubmitTxFromOrderedClientUpdates(
+                AggregateSubmitTxFromOrderedClientUpdates { chain_id },
+            ) => self.chains.push(chain_id),
+            Callback::Plugin(_) => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use unionlabs::ibc::core::client::height::Height;
+    use voyager_vm::{call, seq};
+
+    use super::*;
+    use crate::core::IbcSpecId;
+
+    fn wait_for_trusted_height(chain_id: &str, client_id: u32) -> Op<VoyagerMessage> {
+        call(WaitForTrustedHeight {
+            chain_id: ChainId::new(chain_id.to_owned()),
+            ibc_spec_id: IbcSpecId::new_static(IbcSpecId::UNION),
+            client_id: RawClientId(json!(client_id)),
+            height: Height::new(1),
+            deadline: None,
+        })
+    }
+
+    #[test]
+    fn pause_chain_and_client() {
+        let pauses = Pauses::default();
+
+        let op = seq([wait_for_trusted_height("union-1", 3)]);
+
+        assert!(!pauses.is_paused(&op));
+
+        assert!(pauses.pause_chain(ChainId::new("union-1")));
+        assert!(!pauses.pause_chain(ChainId::new("union-1")));
+        assert!(pauses.is_paused(&op));
+        assert!(!pauses.is_paused(&wait_for_trusted_height("osmosis-1", 3)));
+
+        assert!(pauses.resume_chain(&ChainId::new("union-1")));
+        assert!(!pauses.is_paused(&op));
+
+        assert!(pauses.pause_client(ChainId::new("union-1"), RawClientId(json!(3))));
+        assert!(pauses.is_paused(&op));
+        assert!(!pauses.is_paused(&wait_for_trusted_height("union-1", 4)));
+        assert!(!pauses.is_paused(&wait_for_trusted_height("osmosis-1", 3)));
+
+        assert!(pauses.resume_client(ChainId::new("union-1"), RawClientId(json!(3))));
+        assert!(!pauses.is_paused(&op));
+    }
+
+    #[test]
+    fn hold_submissions() {
+        let pauses = Pauses::default();
+
+        let submission = call(PluginMessage::new("transaction/union-1", json!({})));
+        let op = seq([wait_for_trusted_height("union-1", 3)]);
+
+        assert!(pauses.hold_submissions("transaction/union-1".to_owned()));
+        assert!(pauses.is_paused(&submission));
+        assert!(!pauses.is_paused(&op));
+
+        // operator pauses are independent of held submissions
+        assert!(pauses.pause("transaction/union-1".to_owned()));
+        assert!(pauses.release_submissions("transaction/union-1"));
+        assert!(pauses.is_paused(&submission));
+
+        assert!(pauses.resume("transaction/union-1"));
+        assert!(!pauses.is_paused(&submission));
+    }
+}
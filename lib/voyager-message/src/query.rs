@@ -0,0 +1,173 @@
+//! Typed queries of the [`IbcClassic`](ibc_classic_spec::IbcClassic) state stored on a chain.
+//!
+//! These build the store path, query it through any [`VoyagerRpcClient`], and decode the stored
+//! value, such that tooling built on top of voyager does not need to deal with raw store paths
+//! and JSON values.
+
+use std::num::NonZeroU64;
+
+use futures::{stream, StreamExt, TryStreamExt};
+use ibc_classic_spec::{
+    ChannelEndPath, ClientStatePath, CommitmentPath, ConnectionPath, NextSequenceRecvPath,
+    NextSequenceSendPath, ReceiptPath,
+};
+use jsonrpsee::core::RpcResult;
+use tracing::debug;
+use unionlabs::{
+    bytes::Bytes,
+    hash::H256,
+    ibc::core::{channel::channel::Channel, connection::connection_end::ConnectionEnd},
+    id::{ChannelId, ClientId, ConnectionId, PortId},
+};
+use voyager_core::QueryHeight;
+
+use crate::{
+    core::ChainId,
+    rpc::{query_ibc_state_at, VoyagerRpcClient},
+};
+
+/// The maximum amount of concurrent requests made by the queries that query the state of many
+/// packets.
+pub const MAX_CONCURRENT_PACKET_QUERIES: usize = 16;
+
+/// The raw client state of `client_id` on `chain_id`, as encoded by the light client.
+pub async fn query_client_state(
+    client: &impl VoyagerRpcClient,
+    chain_id: &ChainId,
+    height: QueryHeight,
+    client_id: ClientId,
+) -> RpcResult<Bytes> {
+    query_ibc_state_at(client, chain_id, height, ClientStatePath { client_id }).await
+}
+
+/// The connection end of `connection_id` on `chain_id`, if it exists.
+pub async fn query_connection(
+    client: &impl VoyagerRpcClient,
+    chain_id: &ChainId,
+    height: QueryHeight,
+    connection_id: ConnectionId,
+) -> RpcResult<Option<ConnectionEnd>> {
+    query_ibc_state_at(client, chain_id, height, ConnectionPath { connection_id }).await
+}
+
+/// The channel end of `port_id`/`channel_id` on `chain_id`, if it exists.
+pub async fn query_channel(
+    client: &impl VoyagerRpcClient,
+    chain_id: &ChainId,
+    height: QueryHeight,
+    port_id: PortId,
+    channel_id: ChannelId,
+) -> RpcResult<Option<Channel>> {
+    query_ibc_state_at(
+        client,
+        chain_id,
+        height,
+        ChannelEndPath {
+            port_id,
+            channel_id,
+        },
+    )
+    .await
+}
+
+/// The commitments of all packets sent on `port_id`/`channel_id` on `chain_id` with a sequence
+/// `>= from_sequence`, in order of their sequence.
+///
+/// Packets whose commitment has been removed (i.e. they have been acknowledged or timed out) are
+/// not included.
+pub async fn query_packet_commitments(
+    client: &impl VoyagerRpcClient,
+    chain_id: &ChainId,
+    height: QueryHeight,
+    port_id: PortId,
+    channel_id: ChannelId,
+    from_sequence: NonZeroU64,
+) -> RpcResult<Vec<(NonZeroU64, H256)>> {
+    let next_sequence_send = query_ibc_state_at(
+        client,
+        chain_id,
+        height.clone(),
+        NextSequenceSendPath {
+            port_id: port_id.clone(),
+            channel_id: channel_id.clone(),
+        },
+    )
+    .await?;
+
+    debug!(%chain_id, %port_id, %channel_id, %next_sequence_send, "querying packet commitments");
+
+    stream::iter((from_sequence.get()..next_sequence_send).filter_map(NonZeroU64::new))
+        .map(|sequence| {
+            let path = CommitmentPath {
+                port_id: port_id.clone(),
+                channel_id: channel_id.clone(),
+                sequence,
+            };
+            let height = height.clone();
+
+            async move {
+                query_ibc_state_at(client, chain_id, height, path)
+                    .await
+                    .map(|commitment| commitment.map(|commitment| (sequence, commitment)))
+            }
+        })
+        .buffered(MAX_CONCURRENT_PACKET_QUERIES)
+        .try_filter_map(|commitment| async move { Ok(commitment) })
+        .try_collect()
+        .await
+}
+
+/// The packets out of `sequences` that have not been received on `port_id`/`channel_id` on
+/// `chain_id`, where `chain_id` is the destination chain of the packets.
+///
+/// This checks the packet receipts, and as such is only correct for unordered channels. Ordered
+/// channels don't write receipts; use [`query_next_sequence_recv`] for them instead.
+pub async fn query_unreceived_packets(
+    client: &impl VoyagerRpcClient,
+    chain_id: &ChainId,
+    height: QueryHeight,
+    port_id: PortId,
+    channel_id: ChannelId,
+    sequences: impl IntoIterator<Item = NonZeroU64>,
+) -> RpcResult<Vec<NonZeroU64>> {
+    stream::iter(sequences)
+        .map(|sequence| {
+            let path = ReceiptPath {
+                port_id: port_id.clone(),
+                channel_id: channel_id.clone(),
+                sequence,
+            };
+            let height = height.clone();
+
+            async move {
+                query_ibc_state_at(client, chain_id, height, path)
+                    .await
+                    .map(|received| (!received).then_some(sequence))
+            }
+        })
+        .buffered(MAX_CONCURRENT_PACKET_QUERIES)
+        .try_filter_map(|sequence| async move { Ok(sequence) })
+        .try_collect()
+        .await
+}
+
+/// The sequence of the next packet to be received on the ordered channel `port_id`/`channel_id`
+/// on `chain_id`. All packets with a lower sequence have been received.
+pub async fn query_next_sequence_recv(
+    client: &impl VoyagerRpcClient,
+    chain_id: &ChainId,
+    height: QueryHeight,
+    port_id: PortId,
+    channel_id: ChannelId,
+) -> RpcResult<u64> {
+    query_ibc_state_at(
+        client,
+        chain_id,
+        height,
+        NextSequenceRecvPath {
+            port_id,
+            channel_id,
+        },
+    )
+    .await
+}
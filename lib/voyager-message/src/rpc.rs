@@ -1,3 +1,5 @@
+use std::collections::HashMap;
+
 use jsonrpsee::{
     self,
     core::RpcResult,
@@ -8,11 +10,16 @@ use macros::model;
 use serde::de::DeserializeOwned;
 use serde_json::{json, Value};
 use unionlabs::{bytes::Bytes, ibc::core::client::height::Height, ErrorReporter};
-use voyager_core::IbcSpecId;
+use voyager_core::{IbcSpec, IbcSpecId, IbcStorePathKey};
 
 use crate::{
     context::LoadedModulesInfo,
-    core::{ChainId, ClientInfo, ClientStateMeta, ClientType, IbcInterface, QueryHeight},
+    core::{
+        ChainId, ClientInfo, ClientStateMeta, ClientType, ConsensusStateMeta, IbcInterface,
+        QueryHeight,
+    },
+    into_value,
+    module::ChainCapabilities,
     RawClientId, FATAL_JSONRPC_ERROR_CODE,
 };
 
@@ -37,10 +44,29 @@ pub trait VoyagerRpc {
     #[method(name = "queryLatestHeight")]
     async fn query_latest_height(&self, chain_id: ChainId, finalized: bool) -> RpcResult<Height>;
 
+    /// The latest timestamp of `chain_id`, in nanoseconds. Timestamps returned by consensus
+    /// modules in other units are converted based on the consensus type of the chain.
     #[method(name = "queryLatestTimestamp")]
     // TODO: Make this return a better type than i64
     async fn query_latest_timestamp(&self, chain_id: ChainId, finalized: bool) -> RpcResult<i64>;
 
+    /// The capabilities declared for `chain_id`, see [`ChainCapabilities`].
+    #[method(name = "chainCapabilities")]
+    async fn chain_capabilities(&self, chain_id: ChainId) -> RpcResult<ChainCapabilities>;
+
+    // ===========
+    // checkpoints
+    // ===========
+
+    /// Record that all events in the block at `height` on `chain_id` have been processed.
+    /// Checkpoints only ever move forwards, and only over contiguous processed blocks; recording a
+    /// height at or below the current checkpoint is a no-op.
+    #[method(name = "recordCheckpoint")]
+    async fn record_checkpoint(&self, chain_id: ChainId, height: Height) -> RpcResult<()>;
+
+    #[method(name = "checkpoints")]
+    async fn checkpoints(&self) -> RpcResult<HashMap<ChainId, Height>>;
+
     // =================
     // IBC state queries
     // =================
@@ -62,6 +88,27 @@ pub trait VoyagerRpc {
         client_id: RawClientId,
     ) -> RpcResult<ClientStateMeta>;
 
+    #[method(name = "consensusMeta")]
+    async fn consensus_meta(
+        &self,
+        chain_id: ChainId,
+        ibc_spec_id: IbcSpecId,
+        at: QueryHeight,
+        client_id: RawClientId,
+        trusted_height: Height,
+    ) -> RpcResult<ConsensusStateMeta>;
+
+    /// Discover the metadata required to encode the client state of a new client of
+    /// `client_type` on `chain_id`, for the given `ibc_interface`. This is the metadata expected by
+    /// [`ClientModule::encode_client_state`](crate::module::ClientModuleServer::encode_client_state).
+    #[method(name = "discoverClientMetadata")]
+    async fn discover_client_metadata(
+        &self,
+        chain_id: ChainId,
+        client_type: ClientType,
+        ibc_interface: IbcInterface,
+    ) -> RpcResult<Value>;
+
     #[method(name = "queryIbcState")]
     async fn query_ibc_state(
         &self,
@@ -199,3 +246,31 @@ pub fn missing_state(
 ) -> impl FnOnce() -> ErrorObjectOwned {
     move || ErrorObject::owned(FATAL_JSONRPC_ERROR_CODE, message, data)
 }
+
+/// Query `path` on `chain_id` at the latest height, and decode the state as the value of the path.
+pub async fn query_latest_ibc_state<P: IbcStorePathKey>(
+    client: &impl VoyagerRpcClient,
+    chain_id: &ChainId,
+    path: P,
+) -> RpcResult<P::Value> {
+    query_ibc_state_at(client, chain_id, QueryHeight::Latest, path).await
+}
+
+/// Query `path` on `chain_id` at `height`, and decode the state as the value of the path.
+pub async fn query_ibc_state_at<P: IbcStorePathKey>(
+    client: &impl VoyagerRpcClient,
+    chain_id: &ChainId,
+    height: QueryHeight,
+    path: P,
+) -> RpcResult<P::Value> {
+    client
+        .query_ibc_state(
+            chain_id.clone(),
+            P::Spec::ID,
+            height,
+            into_value(<P::Spec as IbcSpec>::StorePath::from(path.into())),
+        )
+        .await
+        .map_err(json_rpc_error_to_error_object)?
+        .decode_state()
+}
@@ -1,12 +1,15 @@
 use std::{
+    collections::{BTreeSet, HashMap},
     fmt::Debug,
-    sync::{Arc, OnceLock},
+    sync::{Arc, Mutex, OnceLock},
 };
 
+use ibc_classic_spec::{IbcClassic, NextClientSequencePath};
 use jsonrpsee::{
     core::{async_trait, RpcResult},
     types::{ErrorObject, ErrorObjectOwned},
 };
+use moka::policy::EvictionPolicy;
 use serde_json::Value;
 use tracing::{debug, instrument, trace};
 use unionlabs::{bytes::Bytes, ibc::core::client::height::Height, ErrorReporter};
@@ -16,15 +19,20 @@ use voyager_core::IbcSpecId;
 // use voyager_core::IbcStoreFormat;
 use crate::{
     context::{LoadedModulesInfo, Modules},
-    core::{ChainId, ClientInfo, ClientStateMeta, ClientType, IbcInterface, QueryHeight},
+    core::{
+        ChainId, ClientInfo, ClientStateMeta, ClientType, ConsensusStateMeta, ConsensusType,
+        IbcInterface, QueryHeight,
+    },
     into_value,
     module::{
-        ClientModuleClient, ConsensusModuleClient, RawProofModuleClient, RawStateModuleClient,
+        ChainCapabilities, ClientModuleClient, ConsensusModuleClient, RawProofModuleClient,
+        RawStateModuleClient,
     },
     rpc::{
         json_rpc_error_to_error_object, IbcProof, IbcState, SelfClientState, SelfConsensusState,
         VoyagerRpcServer,
     },
+    singleflight::SingleFlight,
     IbcSpec, IbcStorePathKey, RawClientId, FATAL_JSONRPC_ERROR_CODE,
 };
 
@@ -33,20 +41,70 @@ pub struct Server {
     inner: Arc<ServerInner>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug)]
 pub struct ServerInner {
     modules: OnceLock<Arc<Modules>>,
-    // ibc_state_cache: Cache,
+    /// The last height that all events have been processed up to, per chain.
+    checkpoints: Mutex<HashMap<ChainId, Checkpoint>>,
+    /// IBC state queried at fixed heights, see [`Server::query_ibc_state_raw`].
+    ibc_state_cache: Cache,
+    /// Queries at [`QueryHeight::Latest`] that are currently in flight, since they are not cached.
+    in_flight_ibc_state: SingleFlight<StateQuery, RpcResult<Value>>,
 }
 
-// #[derive(Clone)]
-// struct Cache(moka::future::Cache<StateQuery, Value>);
+/// The checkpoint of a single chain.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Checkpoint {
+    /// All blocks up to and including this height have been processed.
+    height: Height,
+    /// Blocks above `height` that have been processed, but that are not yet contiguous with it
+    /// since blocks before them are still being processed.
+    processed: BTreeSet<Height>,
+}
 
-// impl Debug for Cache {
-//     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-//         write!(f, "Cache({:?}, {})", self.0.name(), self.0.entry_count())
-//     }
-// }
+impl Checkpoint {
+    fn new(height: Height) -> Self {
+        Self {
+            height,
+            processed: BTreeSet::new(),
+        }
+    }
+
+    /// Record that the block at `height` has been processed, advancing the checkpoint over all
+    /// contiguous processed blocks.
+    fn record(&mut self, height: Height) {
+        if height <= self.height {
+            return;
+        }
+
+        self.processed.insert(height);
+
+        while self.processed.remove(&self.height.increment()) {
+            self.height = self.height.increment();
+        }
+    }
+}
+
+/// The maximum number of entries in the IBC state cache, across all chains.
+const IBC_STATE_CACHE_CAPACITY: u64 = 10_000;
+
+#[derive(Clone)]
+struct Cache(moka::future::Cache<StateQuery, Value>);
+
+impl Debug for Cache {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Cache({:?}, {})", self.0.name(), self.0.entry_count())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct StateQuery {
+    chain_id: ChainId,
+    ibc_spec_id: IbcSpecId,
+    height: Height,
+    /// The store path, serialized as JSON.
+    path: String,
+}
 
 impl Server {
     #[allow(clippy::new_without_default)]
@@ -54,15 +112,15 @@ impl Server {
         Server {
             inner: Arc::new(ServerInner {
                 modules: OnceLock::new(),
-                // ibc_state_cache: Cache(
-                //     moka::future::Cache::builder()
-                //         .eviction_listener(|k, v, why| {
-                //             error!(?k, ?v, ?why, "value evicted from the cache")
-                //         })
-                //         .max_capacity(10_000)
-                //         .name("ibc_state_cache")
-                //         .build(),
-                // ),
+                checkpoints: Mutex::new(HashMap::new()),
+                ibc_state_cache: Cache(
+                    moka::future::Cache::builder()
+                        .eviction_policy(EvictionPolicy::lru())
+                        .max_capacity(IBC_STATE_CACHE_CAPACITY)
+                        .name("ibc_state_cache")
+                        .build(),
+                ),
+                in_flight_ibc_state: SingleFlight::default(),
             }),
         }
     }
@@ -78,6 +136,34 @@ impl Server {
         self.inner.modules()
     }
 
+    /// Record that all events in the block at `height` on `chain_id` have been processed.
+    ///
+    /// Blocks can finish processing out of order, so the checkpoint only advances once all blocks
+    /// before `height` have been processed as well. If there is no checkpoint for the chain yet,
+    /// `height` becomes its checkpoint. Heights at or below the current checkpoint are ignored.
+    pub fn record_checkpoint(&self, chain_id: ChainId, height: Height) {
+        let mut checkpoints = self.inner.checkpoints.lock().expect("mutex is poisoned");
+
+        let checkpoint = checkpoints
+            .entry(chain_id)
+            .or_insert_with(|| Checkpoint::new(height));
+
+        checkpoint.record(height);
+
+        trace!(%height, checkpoint = %checkpoint.height, "recorded checkpoint");
+    }
+
+    /// Returns the current checkpoints for all chains.
+    pub fn checkpoints(&self) -> HashMap<ChainId, Height> {
+        self.inner
+            .checkpoints
+            .lock()
+            .expect("mutex is poisoned")
+            .iter()
+            .map(|(chain_id, checkpoint)| (chain_id.clone(), checkpoint.height))
+            .collect()
+    }
+
     #[instrument(skip_all, fields(%height, %chain_id))]
     pub async fn query_height(&self, chain_id: &ChainId, height: QueryHeight) -> RpcResult<Height> {
         match height {
@@ -110,6 +196,53 @@ impl Server {
             QueryHeight::Specific(height) => Ok(height),
         }
     }
+
+    /// Query the raw IBC state at `path` on `chain_id`, reading through the IBC state cache.
+    ///
+    /// The state at a given height never changes, so queries at [`QueryHeight::Finalized`] and
+    /// [`QueryHeight::Specific`] are cached keyed by the height they resolve to. Queries at
+    /// [`QueryHeight::Latest`] always go to the state module, since the latest height may not be
+    /// final yet and can be reorged out; identical concurrent queries are still only sent once.
+    #[instrument(skip_all, fields(%chain_id, %ibc_spec_id, %at))]
+    pub async fn query_ibc_state_raw(
+        &self,
+        chain_id: &ChainId,
+        ibc_spec_id: &IbcSpecId,
+        at: QueryHeight,
+        path: Value,
+    ) -> RpcResult<IbcState<Value>> {
+        let height = self.query_height(chain_id, at).await?;
+
+        let fetch = async {
+            self.inner
+                .modules()?
+                .state_module(chain_id, ibc_spec_id)
+                .map_err(fatal_error)?
+                .query_ibc_state_raw(height, path.clone())
+                .await
+                .map_err(json_rpc_error_to_error_object)
+        };
+
+        let query = StateQuery {
+            chain_id: chain_id.clone(),
+            ibc_spec_id: ibc_spec_id.clone(),
+            height,
+            path: path.to_string(),
+        };
+
+        let state = match at {
+            QueryHeight::Latest => self.inner.in_flight_ibc_state.run(query, fetch).await?,
+            QueryHeight::Finalized | QueryHeight::Specific(_) => self
+                .inner
+                .ibc_state_cache
+                .0
+                .try_get_with(query, fetch)
+                .await
+                .map_err(|err| (*err).clone())?,
+        };
+
+        Ok(IbcState { height, state })
+    }
 }
 
 impl ServerInner {
@@ -156,20 +289,35 @@ impl Server {
     ) -> RpcResult<i64> {
         trace!("querying latest timestamp");
 
-        let latest_timestamp = self
-            .inner
-            .modules()?
+        let modules = self.inner.modules()?;
+
+        let latest_timestamp = modules
             .consensus_module(chain_id)
             .map_err(fatal_error)?
             .query_latest_timestamp(finalized)
             .await
             .map_err(json_rpc_error_to_error_object)?;
 
+        let latest_timestamp = timestamp_nanos(
+            modules
+                .chain_consensus_type(chain_id)
+                .map_err(fatal_error)?,
+            latest_timestamp,
+        );
+
         trace!(latest_timestamp, "queried latest timestamp");
 
         Ok(latest_timestamp)
     }
 
+    pub fn chain_capabilities(&self, chain_id: &ChainId) -> RpcResult<ChainCapabilities> {
+        self.inner
+            .modules()?
+            .chain_capabilities(chain_id)
+            .cloned()
+            .map_err(fatal_error)
+    }
+
     #[instrument(skip_all, fields(%chain_id, %ibc_spec_id, client_id = %client_id.0))]
     pub async fn client_info(
         &self,
@@ -207,34 +355,48 @@ impl Server {
     ) -> RpcResult<ClientStateMeta> {
         trace!("fetching client meta");
 
-        let height = self.query_height(chain_id, at).await?;
-
         let modules = self.inner.modules()?;
 
-        let state_module = modules.state_module(chain_id, ibc_spec_id)?;
-
-        let client_info = state_module
+        let client_info = modules
+            .state_module(chain_id, ibc_spec_id)?
             .client_info_raw(client_id.clone())
             .await
             .map_err(json_rpc_error_to_error_object)?;
 
-        let client_state = state_module
+        let client_state = self
             .query_ibc_state_raw(
-                height,
-                (self
-                    .modules()?
+                chain_id,
+                ibc_spec_id,
+                at,
+                (modules
                     .ibc_spec_handlers
-                    .handlers
-                    .get(ibc_spec_id)
-                    .unwrap()
+                    .get(ibc_spec_id)?
                     .client_state_path)(client_id.clone())
-                .unwrap(),
+                .map_err(|err| {
+                    ErrorObject::owned(
+                        FATAL_JSONRPC_ERROR_CODE,
+                        format!("invalid client id `{}`: {err:#}", client_id.0),
+                        None::<()>,
+                    )
+                })?,
             )
-            .await
-            .map_err(fatal_error)?;
+            .await?
+            .state;
 
         trace!(%client_state);
 
+        let client_state = serde_json::from_value::<Bytes>(client_state).map_err(|err| {
+            ErrorObject::owned(
+                FATAL_JSONRPC_ERROR_CODE,
+                format!(
+                    "invalid client state returned from the state module for chain \
+                    `{chain_id}` and IBC version `{ibc_spec_id}`: {}",
+                    ErrorReporter(err)
+                ),
+                None::<()>,
+            )
+        })?;
+
         let meta = modules
             .client_module(
                 &client_info.client_type,
@@ -242,7 +404,7 @@ impl Server {
                 ibc_spec_id,
             )
             .map_err(fatal_error)?
-            .decode_client_state_meta(client_state.as_str().unwrap().parse().unwrap())
+            .decode_client_state_meta(client_state)
             .await
             .map_err(json_rpc_error_to_error_object)?;
 
@@ -257,6 +419,143 @@ impl Server {
         Ok(meta)
     }
 
+    /// Fetch the metadata of the consensus state of `client_id` at the (counterparty) height
+    /// `trusted_height`.
+    #[instrument(
+        skip_all,
+        fields(%chain_id, %ibc_spec_id, height = %at, client_id = %client_id.0, %trusted_height)
+    )]
+    pub async fn consensus_meta(
+        &self,
+        chain_id: &ChainId,
+        ibc_spec_id: &IbcSpecId,
+        at: QueryHeight,
+        client_id: RawClientId,
+        trusted_height: Height,
+    ) -> RpcResult<ConsensusStateMeta> {
+        trace!("fetching consensus meta");
+
+        let modules = self.inner.modules()?;
+
+        let client_info = modules
+            .state_module(chain_id, ibc_spec_id)?
+            .client_info_raw(client_id.clone())
+            .await
+            .map_err(json_rpc_error_to_error_object)?;
+
+        let consensus_state = self
+            .query_ibc_state_raw(
+                chain_id,
+                ibc_spec_id,
+                at,
+                (modules
+                    .ibc_spec_handlers
+                    .get(ibc_spec_id)?
+                    .consensus_state_path)(
+                    client_id.clone(), trusted_height.to_string()
+                )
+                .map_err(|err| {
+                    ErrorObject::owned(
+                        FATAL_JSONRPC_ERROR_CODE,
+                        format!("invalid client id `{}`: {err:#}", client_id.0),
+                        None::<()>,
+                    )
+                })?,
+            )
+            .await?
+            .state;
+
+        trace!(%consensus_state);
+
+        let consensus_state = serde_json::from_value::<Bytes>(consensus_state).map_err(|err| {
+            ErrorObject::owned(
+                FATAL_JSONRPC_ERROR_CODE,
+                format!(
+                    "invalid consensus state returned from the state module for chain \
+                    `{chain_id}` and IBC version `{ibc_spec_id}`: {}",
+                    ErrorReporter(err)
+                ),
+                None::<()>,
+            )
+        })?;
+
+        let meta = modules
+            .client_module(
+                &client_info.client_type,
+                &client_info.ibc_interface,
+                ibc_spec_id,
+            )
+            .map_err(fatal_error)?
+            .decode_consensus_state_meta(consensus_state)
+            .await
+            .map_err(json_rpc_error_to_error_object)?;
+
+        trace!(
+            consensus_state_meta.timestamp_nanos = meta.timestamp_nanos,
+            "fetched consensus meta"
+        );
+
+        Ok(meta)
+    }
+
+    /// Discover the client state metadata for a new client of `client_type` on `chain_id`.
+    ///
+    /// Only 08-wasm clients require metadata, namely the checksum of the wasm code of the light
+    /// client. The code is not tagged with the client type it implements, so the checksum is taken
+    /// from the most recently created 08-wasm client of the same type on the chain. Clients on
+    /// other IBC interfaces don't require any metadata (ibc-solidity, for example, instantiates
+    /// clients from the client registry by client type).
+    #[instrument(skip_all, fields(%chain_id, %client_type, %ibc_interface))]
+    pub async fn discover_client_metadata(
+        &self,
+        chain_id: &ChainId,
+        client_type: &ClientType,
+        ibc_interface: &IbcInterface,
+    ) -> RpcResult<Value> {
+        if ibc_interface.as_str() != IbcInterface::IBC_GO_V8_08_WASM {
+            return Ok(Value::Null);
+        }
+
+        let height = self.query_latest_height(chain_id, false).await?;
+
+        let next_client_sequence = self
+            .query_ibc_state::<NextClientSequencePath>(
+                chain_id,
+                height,
+                NextClientSequencePath {}.into(),
+            )
+            .await?
+            .state;
+
+        // the client sequence is shared between all client types, so not every sequence is an
+        // 08-wasm client
+        for sequence in (0..next_client_sequence).rev() {
+            let client_id = format!("08-wasm-{sequence}");
+
+            match self
+                .client_info(chain_id, &IbcClassic::ID, RawClientId::new(&client_id))
+                .await
+            {
+                Ok(client_info) if client_info.client_type == *client_type => {
+                    debug!(%client_id, metadata = %client_info.metadata, "discovered client metadata");
+
+                    return Ok(client_info.metadata);
+                }
+                Ok(_) => {}
+                Err(error) => trace!(%client_id, error = %ErrorReporter(error), "skipping client"),
+            }
+        }
+
+        Err(ErrorObject::owned(
+            FATAL_JSONRPC_ERROR_CODE,
+            format!(
+                "no existing 08-wasm client of type `{client_type}` found on \
+                `{chain_id}`, the client metadata must be provided manually"
+            ),
+            None::<()>,
+        ))
+    }
+
     #[instrument(skip_all, fields(%chain_id, %height))]
     pub async fn query_ibc_state<P: IbcStorePathKey>(
         &self,
@@ -282,7 +581,18 @@ impl Server {
 
         Ok(IbcState {
             height,
-            state: serde_json::from_value(state).unwrap(),
+            state: serde_json::from_value(state).map_err(|err| {
+                ErrorObject::owned(
+                    FATAL_JSONRPC_ERROR_CODE,
+                    format!(
+                        "invalid ibc state returned from the state module for chain \
+                        `{chain_id}` and IBC version `{}`: {}",
+                        P::Spec::ID,
+                        ErrorReporter(err)
+                    ),
+                    None::<()>,
+                )
+            })?,
         })
     }
 
@@ -476,6 +786,24 @@ impl VoyagerRpcServer for Server {
         self.query_latest_timestamp(&chain_id, finalized).await
     }
 
+    async fn chain_capabilities(&self, chain_id: ChainId) -> RpcResult<ChainCapabilities> {
+        self.chain_capabilities(&chain_id)
+    }
+
+    // ===========
+    // CHECKPOINTS
+    // ===========
+
+    async fn record_checkpoint(&self, chain_id: ChainId, height: Height) -> RpcResult<()> {
+        self.record_checkpoint(chain_id, height);
+
+        Ok(())
+    }
+
+    async fn checkpoints(&self) -> RpcResult<HashMap<ChainId, Height>> {
+        Ok(self.checkpoints())
+    }
+
     // =====
     // STATE
     // =====
@@ -500,46 +828,27 @@ impl VoyagerRpcServer for Server {
             .await
     }
 
-    // async fn query_client_state(
-    //     &self,
-    //     chain_id: ChainId,
-    //     height: QueryHeight,
-    //     client_id: ClientId,
-    // ) -> RpcResult<IbcState<Bytes>> {
-    //     let height = self.query_height(&chain_id, height).await?;
-
-    //     self.modules()?
-    //         .chain_module(&chain_id)?
-    //         .query_client_state(height, client_id)
-    //         .await
-    //         .map(|state| IbcState {
-    //             chain_id,
-    //             height,
-    //             state,
-    //         })
-    //         .map_err(json_rpc_error_to_error_object)
-    // }
-
-    // async fn query_client_consensus_state(
-    //     &self,
-    //     chain_id: ChainId,
-    //     height: QueryHeight,
-    //     client_id: ClientId,
-    //     trusted_height: Height,
-    // ) -> RpcResult<IbcState<Bytes>> {
-    //     let height = self.query_height(&chain_id, height).await?;
-
-    //     self.modules()?
-    //         .chain_module(&chain_id)?
-    //         .query_client_consensus_state(height, client_id, trusted_height)
-    //         .await
-    //         .map(|state| IbcState {
-    //             chain_id,
-    //             height,
-    //             state,
-    //         })
-    //         .map_err(json_rpc_error_to_error_object)
-    // }
+    async fn consensus_meta(
+        &self,
+        chain_id: ChainId,
+        ibc_spec_id: IbcSpecId,
+        at: QueryHeight,
+        client_id: RawClientId,
+        trusted_height: Height,
+    ) -> RpcResult<ConsensusStateMeta> {
+        self.consensus_meta(&chain_id, &ibc_spec_id, at, client_id, trusted_height)
+            .await
+    }
+
+    async fn discover_client_metadata(
+        &self,
+        chain_id: ChainId,
+        client_type: ClientType,
+        ibc_interface: IbcInterface,
+    ) -> RpcResult<Value> {
+        self.discover_client_metadata(&chain_id, &client_type, &ibc_interface)
+            .await
+    }
 
     #[instrument(skip_all, fields(%chain_id, %height))]
     async fn query_ibc_state(
@@ -549,25 +858,16 @@ impl VoyagerRpcServer for Server {
         height: QueryHeight,
         path: Value,
     ) -> RpcResult<IbcState<Value>> {
-        let height = self.query_height(&chain_id, height).await?;
-
         debug!("fetching ibc state");
 
-        let state_module = self
-            .inner
-            .modules()?
-            .state_module(&chain_id, &ibc_spec_id)
-            .map_err(fatal_error)?;
-
-        let state = state_module
-            .query_ibc_state_raw(height, path)
-            .await
-            .map_err(json_rpc_error_to_error_object)?;
+        let ibc_state = self
+            .query_ibc_state_raw(&chain_id, &ibc_spec_id, height, path)
+            .await?;
 
         // TODO: Use valuable here
-        debug!(%state, "fetched ibc state");
+        debug!(state = %ibc_state.state, "fetched ibc state");
 
-        Ok(IbcState { height, state })
+        Ok(ibc_state)
     }
 
     #[instrument(skip_all, fields(%chain_id, %height))]
@@ -672,69 +972,67 @@ pub(crate) fn fatal_error(t: impl core::error::Error) -> ErrorObjectOwned {
     )
 }
 
-// #[derive(Debug, Clone, PartialEq, Eq, Hash)]
-// struct StateQuery {
-//     chain_id: ChainId,
-//     height: Height,
-//     kind: StateQueryKind,
-// }
-
-// #[derive(Debug, Clone, PartialEq, Eq, Hash)]
-// enum StateQueryKind {
-//     ClientState {
-//         client_id: ClientId,
-//     },
-
-//     ClientConsensusState {
-//         client_id: ClientId,
-//         trusted_height: Height,
-//     },
-
-//     Connection {
-//         connection_id: ConnectionId,
-//     },
-
-//     ChannelEnd {
-//         channel_id: ChannelId,
-//     },
-
-//     Commitment {
-//         channel_id: ChannelId,
-//         sequence: NonZeroU64,
-//     },
-
-//     Acknowledgement {
-//         channel_id: ChannelId,
-//         sequence: NonZeroU64,
-//     },
-
-//     Receipt {
-//         channel_id: ChannelId,
-//         sequence: NonZeroU64,
-//     },
-
-//     NextSequenceSend {
-//         channel_id: ChannelId,
-//         sequence: NonZeroU64,
-//     },
-
-//     NextSequenceRecv {
-//         channel_id: ChannelId,
-//         sequence: NonZeroU64,
-//     },
-
-//     NextSequenceAck {
-//         channel_id: ChannelId,
-//         sequence: NonZeroU64,
-//     },
-
-//     NextConnectionSequence {
-//         channel_id: ChannelId,
-//         sequence: NonZeroU64,
-//     },
-
-//     NextClientSequence {
-//         channel_id: ChannelId,
-//         sequence: NonZeroU64,
-//     },
-// }
+/// Convert a timestamp returned by the `query_latest_timestamp` of a consensus module of the
+/// specified consensus type to nanoseconds.
+///
+/// Not all consensus modules return the latest timestamp in nanoseconds: the EVM based ones return
+/// the block timestamp as is (in seconds), and movement returns the aptos block timestamp (in
+/// microseconds).
+fn timestamp_nanos(consensus_type: &ConsensusType, timestamp: i64) -> i64 {
+    match consensus_type.as_str() {
+        ConsensusType::ETHEREUM | ConsensusType::ARBITRUM | ConsensusType::SCROLL => {
+            timestamp.saturating_mul(1_000_000_000)
+        }
+        ConsensusType::MOVEMENT => timestamp.saturating_mul(1_000),
+        _ => timestamp,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn checkpoint_advances_over_contiguous_heights() {
+        let mut checkpoint = Checkpoint::new(Height::new(10));
+
+        // blocks finish processing out of order
+        checkpoint.record(Height::new(12));
+        checkpoint.record(Height::new(14));
+        assert_eq!(checkpoint.height, Height::new(10));
+
+        checkpoint.record(Height::new(11));
+        assert_eq!(checkpoint.height, Height::new(12));
+
+        // lower heights are ignored
+        checkpoint.record(Height::new(9));
+        checkpoint.record(Height::new(13));
+        assert_eq!(checkpoint.height, Height::new(14));
+        assert!(checkpoint.processed.is_empty());
+    }
+
+    #[test]
+    fn timestamp_nanos_of_host_returning_seconds() {
+        let seconds = 1_700_000_000;
+
+        assert_eq!(
+            timestamp_nanos(&ConsensusType::new(ConsensusType::ETHEREUM), seconds),
+            seconds * 1_000_000_000
+        );
+        assert_eq!(
+            timestamp_nanos(
+                &ConsensusType::new(ConsensusType::MOVEMENT),
+                seconds * 1_000_000
+            ),
+            seconds * 1_000_000_000
+        );
+        // hosts returning nanoseconds are left as is
+        assert_eq!(
+            timestamp_nanos(
+                &ConsensusType::new(ConsensusType::TENDERMINT),
+                seconds * 1_000_000_000
+            ),
+            seconds * 1_000_000_000
+        );
+    }
+}
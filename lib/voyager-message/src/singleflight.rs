@@ -0,0 +1,156 @@
+//! Coalescing of identical concurrent requests.
+//!
+//! During bursts, many identical fetches (for example the same state at the same height, or the
+//! same client update) are often in flight at the same time. [`SingleFlight`] ensures that only
+//! one of them is executed, with the result being shared with all other callers.
+
+use std::{
+    collections::HashMap,
+    fmt,
+    future::Future,
+    hash::Hash,
+    sync::{Arc, Mutex},
+};
+
+use tokio::sync::broadcast;
+use tracing::trace;
+
+pub struct SingleFlight<K, V> {
+    in_flight: Arc<Mutex<HashMap<K, broadcast::Sender<V>>>>,
+}
+
+impl<K, V> Default for SingleFlight<K, V> {
+    fn default() -> Self {
+        Self {
+            in_flight: Arc::default(),
+        }
+    }
+}
+
+impl<K, V> Clone for SingleFlight<K, V> {
+    fn clone(&self) -> Self {
+        Self {
+            in_flight: self.in_flight.clone(),
+        }
+    }
+}
+
+impl<K, V> fmt::Debug for SingleFlight<K, V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SingleFlight")
+            .field(
+                "in_flight",
+                &self.in_flight.lock().expect("mutex is poisoned").len(),
+            )
+            .finish()
+    }
+}
+
+impl<K: Hash + Eq + Clone, V: Clone> SingleFlight<K, V> {
+    /// Run `f`, unless another call with the same `key` is already in flight, in which case its
+    /// result is awaited instead.
+    ///
+    /// If the call that is in flight is cancelled before it completes, `f` is run instead.
+    pub async fn run(&self, key: K, f: impl Future<Output = V>) -> V {
+        let rx = {
+            let mut in_flight = self.in_flight.lock().expect("mutex is poisoned");
+
+            match in_flight.get(&key) {
+                Some(tx) => Some(tx.subscribe()),
+                None => {
+                    in_flight.insert(key.clone(), broadcast::channel(1).0);
+                    None
+                }
+            }
+        };
+
+        if let Some(mut rx) = rx {
+            trace!("identical request is already in flight, waiting for its result");
+
+            return match rx.recv().await {
+                Ok(value) => value,
+                Err(_) => f.await,
+            };
+        }
+
+        // removes the key if `f` is cancelled, such that waiting callers don't wait forever
+        let guard = RemoveOnDrop {
+            in_flight: &self.in_flight,
+            key: Some(key),
+        };
+
+        let value = f.await;
+
+        if let Some(tx) = guard.remove() {
+            // there may not be any other callers
+            let _ = tx.send(value.clone());
+        }
+
+        value
+    }
+}
+
+struct RemoveOnDrop<'a, K: Hash + Eq, V> {
+    in_flight: &'a Mutex<HashMap<K, broadcast::Sender<V>>>,
+    key: Option<K>,
+}
+
+impl<K: Hash + Eq, V> RemoveOnDrop<'_, K, V> {
+    fn remove(mut self) -> Option<broadcast::Sender<V>> {
+        self.key.take().and_then(|key| {
+            self.in_flight
+                .lock()
+                .expect("mutex is poisoned")
+                .remove(&key)
+        })
+    }
+}
+
+impl<K: Hash + Eq, V> Drop for RemoveOnDrop<'_, K, V> {
+    fn drop(&mut self) {
+        if let Some(key) = self.key.take() {
+            self.in_flight
+                .lock()
+                .expect("mutex is poisoned")
+                .remove(&key);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::atomic::{AtomicUsize, Ordering},
+        time::Duration,
+    };
+
+    use futures::future::join_all;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn coalesces_concurrent_calls() {
+        let single_flight = SingleFlight::<u32, u32>::default();
+        let calls = AtomicUsize::new(0);
+
+        let f = || async {
+            calls.fetch_add(1, Ordering::SeqCst);
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            1
+        };
+
+        let results = join_all([
+            single_flight.run(0, f()),
+            single_flight.run(0, f()),
+            single_flight.run(1, f()),
+        ])
+        .await;
+
+        assert_eq!(results, [1, 1, 1]);
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+
+        // completed calls are not memoized
+        single_flight.run(0, f()).await;
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+    }
+}
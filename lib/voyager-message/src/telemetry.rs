@@ -0,0 +1,69 @@
+//! Initialization of logging, and optional export of tracing spans over OTLP.
+//!
+//! Span export is enabled by setting `OTEL_EXPORTER_OTLP_ENDPOINT` to the endpoint of an OTLP
+//! collector (the other standard `OTEL_EXPORTER_OTLP_*` variables are respected as well). Plugins
+//! and modules inherit the environment of voyager, so setting it for voyager exports the spans of
+//! every process in the relaying pipeline: event detection in the event source plugins, header and
+//! proof fetching in the modules, and submission in the transaction plugins, as well as the
+//! processing of the queue items in voyager that drives them. Items are tagged with the
+//! correlation id of the op tree they belong to, and calls with the chain and client ids they are
+//! for, which allows for following a packet end-to-end across both chains.
+
+use opentelemetry::{trace::TracerProvider as _, KeyValue};
+use opentelemetry_sdk::{runtime, trace::TracerProvider, Resource};
+use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt, EnvFilter, Layer};
+
+pub const OTLP_ENDPOINT_ENV_VAR: &str = "OTEL_EXPORTER_OTLP_ENDPOINT";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LogFormat {
+    #[default]
+    Text,
+    Json,
+}
+
+/// Install the global tracing subscriber, logging to stdout in `format`.
+///
+/// If [`OTLP_ENDPOINT_ENV_VAR`] is set, spans are additionally exported as `service_name`. The
+/// returned provider must be [shut down](TracerProvider::shutdown) before exiting to flush the
+/// spans that have not been exported yet. This must be called from within a tokio runtime, since
+/// spans are exported in the background.
+pub fn init(format: LogFormat, service_name: &str) -> Option<TracerProvider> {
+    let fmt_layer = match format {
+        LogFormat::Text => tracing_subscriber::fmt::layer().boxed(),
+        LogFormat::Json => tracing_subscriber::fmt::layer().json().boxed(),
+    };
+
+    let provider = std::env::var_os(OTLP_ENDPOINT_ENV_VAR).and_then(|_| {
+        // the subscriber is not installed yet, so errors can't be logged
+        opentelemetry_otlp::SpanExporter::builder()
+            .with_tonic()
+            .build()
+            .map_err(|err| eprintln!("unable to build otlp span exporter: {err}"))
+            .ok()
+            .map(|exporter| {
+                TracerProvider::builder()
+                    .with_batch_exporter(exporter, runtime::Tokio)
+                    .with_resource(Resource::new([KeyValue::new(
+                        "service.name",
+                        service_name.to_owned(),
+                    )]))
+                    .build()
+            })
+    });
+
+    let otel_layer = provider.as_ref().map(|provider| {
+        // keep the provider alive for the lifetime of the process
+        opentelemetry::global::set_tracer_provider(provider.clone());
+
+        tracing_opentelemetry::layer().with_tracer(provider.tracer(service_name.to_owned()))
+    });
+
+    tracing_subscriber::registry()
+        .with(EnvFilter::from_default_env())
+        .with(fmt_layer)
+        .with(otel_layer)
+        .init();
+
+    provider
+}
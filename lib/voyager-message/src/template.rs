@@ -0,0 +1,208 @@
+//! Canonical templates of the ops for common operations.
+//!
+//! Ops are usually built by plugins, but it is often required to enqueue an op by hand (i.e. to
+//! seed the queue, or to manually relay a packet). Writing the JSON for these ops by hand is error
+//! prone, so the templates here produce the canonical serialized form of the op, which is validated
+//! by deserializing it again before it is returned. These are exposed on the CLI via
+//! `voyager msg generate`.
+
+use std::num::NonZeroU64;
+
+use ibc_classic_spec::IbcClassic;
+use ibc_union_spec::IbcUnion;
+use serde_json::Value;
+use unionlabs::{
+    bytes::Bytes,
+    ibc::core::client::height::Height,
+    id::{ChannelId, PortId},
+};
+use voyager_core::IbcSpec;
+use voyager_vm::{call, data, promise, Op};
+
+use crate::{
+    call::{FetchBlocks, FetchPacketEvents, FetchUpdateHeaders, PacketEventKind},
+    callback::{
+        AggregateMsgUpdateClientsFromOrderedHeaders, AggregateSubmitTxFromOrderedClientUpdates,
+    },
+    core::{ChainId, ClientType, IbcSpecId},
+    data::{IbcDatagram, WithChainId},
+    RawClientId, VoyagerMessage,
+};
+
+#[derive(Debug, thiserror::Error)]
+pub enum TemplateError {
+    #[error("unknown ibc spec `{0}`")]
+    UnknownIbcSpec(IbcSpecId),
+    #[error("error serializing op")]
+    Serialize(#[source] serde_json::Error),
+    #[error("generated op does not match the serde schema of the queue messages")]
+    Deserialize(#[source] serde_json::Error),
+    #[error("generated op does not roundtrip through serde, found {found}")]
+    Roundtrip { found: Value },
+}
+
+/// Build the op to create a client of type `client_type` on `chain_id` with the provided
+/// (already encoded) client and consensus states.
+pub fn create_client(
+    chain_id: ChainId,
+    ibc_spec_id: IbcSpecId,
+    client_type: ClientType,
+    client_state: Bytes,
+    consensus_state: Bytes,
+) -> Result<Op<VoyagerMessage>, TemplateError> {
+    let message = match ibc_spec_id.as_str() {
+        IbcSpecId::CLASSIC => IbcDatagram::new::<IbcClassic>(ibc_classic_spec::Datagram::from(
+            ibc_classic_spec::MsgCreateClientData {
+                msg: unionlabs::ibc::core::client::msg_create_client::MsgCreateClient {
+                    client_state,
+                    consensus_state,
+                },
+                client_type,
+            },
+        )),
+        IbcSpecId::UNION => IbcDatagram::new::<IbcUnion>(ibc_union_spec::Datagram::from(
+            ibc_union_spec::MsgCreateClient {
+                client_type,
+                client_state_bytes: client_state,
+                consensus_state_bytes: consensus_state,
+            },
+        )),
+        _ => return Err(TemplateError::UnknownIbcSpec(ibc_spec_id)),
+    };
+
+    Ok(data(WithChainId { chain_id, message }))
+}
+
+/// Build the op to update `client_id` on `chain_id` from `update_from` to `update_to`, and submit
+/// the update on its own.
+#[must_use]
+pub fn update_client(
+    chain_id: ChainId,
+    ibc_spec_id: IbcSpecId,
+    client_id: RawClientId,
+    counterparty_chain_id: ChainId,
+    update_from: Height,
+    update_to: Height,
+) -> Op<VoyagerMessage> {
+    promise(
+        [promise(
+            [call(FetchUpdateHeaders {
+                chain_id: counterparty_chain_id,
+                counterparty_chain_id: chain_id.clone(),
+                update_from,
+                update_to,
+            })],
+            [],
+            AggregateMsgUpdateClientsFromOrderedHeaders {
+                ibc_spec_id,
+                chain_id: chain_id.clone(),
+                counterparty_client_id: client_id,
+            },
+        )],
+        [],
+        AggregateSubmitTxFromOrderedClientUpdates { chain_id },
+    )
+}
+
+/// Build the op to relay the packets with `sequences` sent on `port_id`/`channel_id` on
+/// `chain_id`. The packets are relayed exactly as if they were picked up by the event source.
+#[must_use]
+pub fn relay_packets(
+    chain_id: ChainId,
+    port_id: PortId,
+    channel_id: ChannelId,
+    sequences: Vec<NonZeroU64>,
+) -> Op<VoyagerMessage> {
+    call(FetchPacketEvents {
+        chain_id,
+        kind: PacketEventKind::SendPacket,
+        port_id,
+        channel_id,
+        sequences,
+    })
+}
+
+/// Build the op to start indexing `chain_id` at `start_height`.
+#[must_use]
+pub fn fetch_blocks(chain_id: ChainId, start_height: Height) -> Op<VoyagerMessage> {
+    call(FetchBlocks {
+        chain_id,
+        start_height,
+    })
+}
+
+/// Serialize `op`, ensuring that the serialized op deserializes back into the same op.
+pub fn to_canonical_json(op: &Op<VoyagerMessage>) -> Result<Value, TemplateError> {
+    let json = serde_json::to_value(op).map_err(TemplateError::Serialize)?;
+
+    let roundtripped = serde_json::from_value::<Op<VoyagerMessage>>(json.clone())
+        .map_err(TemplateError::Deserialize)?;
+
+    if &roundtripped == op {
+        Ok(json)
+    } else {
+        Err(TemplateError::Roundtrip {
+            found: serde_json::to_value(roundtripped).map_err(TemplateError::Serialize)?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn templates_roundtrip() {
+        let ops = [
+            create_client(
+                ChainId::new("union-devnet-1"),
+                IbcUnion::ID,
+                ClientType::new(ClientType::COMETBLS_GROTH16),
+                Bytes::from(vec![1, 2, 3]),
+                Bytes::from(vec![4, 5, 6]),
+            )
+            .unwrap(),
+            create_client(
+                ChainId::new("union-devnet-1"),
+                IbcClassic::ID,
+                ClientType::new(ClientType::TENDERMINT),
+                Bytes::from(vec![1, 2, 3]),
+                Bytes::from(vec![4, 5, 6]),
+            )
+            .unwrap(),
+            update_client(
+                ChainId::new("union-devnet-1"),
+                IbcUnion::ID,
+                RawClientId::new(1),
+                ChainId::new("32382"),
+                Height::new(10),
+                Height::new(20),
+            ),
+            relay_packets(
+                ChainId::new("union-devnet-1"),
+                PortId::new("transfer").unwrap(),
+                ChannelId::new(0),
+                vec![NonZeroU64::new(1).unwrap(), NonZeroU64::new(2).unwrap()],
+            ),
+            fetch_blocks(ChainId::new("union-devnet-1"), Height::new(1)),
+        ];
+
+        for op in ops {
+            to_canonical_json(&op).unwrap();
+        }
+    }
+
+    #[test]
+    fn unknown_ibc_spec() {
+        assert!(matches!(
+            create_client(
+                ChainId::new("union-devnet-1"),
+                IbcSpecId::new("ibc-unknown"),
+                ClientType::new(ClientType::TENDERMINT),
+                Bytes::default(),
+                Bytes::default(),
+            ),
+            Err(TemplateError::UnknownIbcSpec(_))
+        ));
+    }
+}
@@ -0,0 +1,518 @@
+//! In-memory mock chains, for testing handshake and packet flows end-to-end without devnets.
+//!
+//! A [`MockChain`] implements the consensus, state, and proof module servers for any [`IbcSpec`],
+//! backed by a versioned in-memory store. It additionally provides the functionality of the client
+//! update plugins ([`MockChain::fetch_update_headers`]) and the transaction plugins
+//! ([`MockChain::submit`]). The height of the chain only changes when it is explicitly
+//! [advanced](MockChain::advance) (submitting a transaction advances it by one block), and failures
+//! can be [injected](MockChain::inject_failure) into any method to exercise the retry paths.
+//!
+//! Cloning a [`MockChain`] shares the underlying chain, such that a test can keep a handle to a
+//! chain while it is being served by a module.
+
+use std::{
+    collections::{BTreeMap, HashMap, VecDeque},
+    sync::{Arc, Mutex},
+};
+
+use jsonrpsee::{
+    core::{async_trait, RpcResult},
+    types::{ErrorObject, ErrorObjectOwned},
+    Extensions, RpcModule,
+};
+use serde::Serialize;
+use serde_json::{json, Value};
+use unionlabs::ibc::core::client::height::Height;
+use voyager_core::IbcSpec;
+
+use crate::{
+    core::{ChainId, ClientInfo},
+    data::{DecodedHeaderMeta, IbcDatagram, OrderedHeaders},
+    module::{ConsensusModuleServer, ProofModuleServer, StateModuleServer},
+    rpc::missing_state,
+    FATAL_JSONRPC_ERROR_CODE,
+};
+
+/// The time between blocks on a [`MockChain`], in nanoseconds.
+pub const BLOCK_TIME_NANOS: i64 = 1_000_000_000;
+
+/// The methods of a [`MockChain`] that failures can be injected into.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MockMethod {
+    QueryLatestHeight,
+    QueryLatestTimestamp,
+    SelfClientState,
+    SelfConsensusState,
+    QueryIbcState,
+    QueryIbcProof,
+    ClientInfo,
+    FetchUpdateHeaders,
+    Submit,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MockFailure {
+    /// Fail with an error that will be retried by the queue.
+    Retryable(String),
+    /// Fail with an error that will not be retried by the queue.
+    Fatal(String),
+}
+
+impl From<MockFailure> for ErrorObjectOwned {
+    fn from(value: MockFailure) -> Self {
+        match value {
+            MockFailure::Retryable(message) => ErrorObject::owned(-1, message, None::<()>),
+            MockFailure::Fatal(message) => {
+                ErrorObject::owned(FATAL_JSONRPC_ERROR_CODE, message, None::<()>)
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct MockChain {
+    chain_id: ChainId,
+    state: Arc<Mutex<MockChainState>>,
+}
+
+#[derive(Debug, Default)]
+struct MockChainState {
+    height: u64,
+    /// The amount of blocks that the finalized height trails the latest height by.
+    finality_lag: u64,
+    /// Keyed by the JSON encoding of the store path, then by the height the value was written at.
+    store: HashMap<String, BTreeMap<u64, Value>>,
+    /// Keyed by the JSON encoding of the client id.
+    clients: HashMap<String, ClientInfo>,
+    /// All submitted datagrams, along with the height they were included at.
+    submitted: Vec<(Height, IbcDatagram)>,
+    failures: HashMap<MockMethod, VecDeque<MockFailure>>,
+}
+
+impl MockChain {
+    /// Create a new chain, starting at height 1.
+    #[must_use]
+    pub fn new(chain_id: ChainId) -> Self {
+        Self {
+            chain_id,
+            state: Arc::new(Mutex::new(MockChainState {
+                height: 1,
+                ..Default::default()
+            })),
+        }
+    }
+
+    #[must_use]
+    pub fn chain_id(&self) -> &ChainId {
+        &self.chain_id
+    }
+
+    /// Merge the consensus, state, and proof module servers of this chain for the IBC spec `V` into
+    /// a single [`RpcModule`].
+    #[must_use]
+    pub fn into_rpc_module<V: IbcSpec>(self) -> RpcModule<Self> {
+        let mut module = ConsensusModuleServer::into_rpc(self.clone());
+
+        module
+            .merge(StateModuleServer::<V>::into_rpc(self.clone()))
+            .expect("method names are namespaced; qed;");
+        module
+            .merge(ProofModuleServer::<V>::into_rpc(self))
+            .expect("method names are namespaced; qed;");
+
+        module
+    }
+
+    #[must_use]
+    pub fn latest_height(&self) -> Height {
+        Height::new(self.lock().height)
+    }
+
+    /// Produce `blocks` new blocks, returning the new latest height.
+    pub fn advance(&self, blocks: u64) -> Height {
+        let mut state = self.lock();
+
+        state.height += blocks;
+
+        Height::new(state.height)
+    }
+
+    pub fn set_finality_lag(&self, blocks: u64) {
+        self.lock().finality_lag = blocks;
+    }
+
+    /// Write `value` to `path` at the latest height. The value is visible at this height and all
+    /// later heights, until it is overwritten.
+    pub fn set_state<P: Serialize, T: Serialize>(&self, path: P, value: T) {
+        let mut state = self.lock();
+
+        let height = state.height;
+
+        state.store.entry(store_key(&path)).or_default().insert(
+            height,
+            serde_json::to_value(value).expect("infallible; qed;"),
+        );
+    }
+
+    pub fn set_client_info<C: Serialize>(&self, client_id: C, client_info: ClientInfo) {
+        self.lock()
+            .clients
+            .insert(store_key(&client_id), client_info);
+    }
+
+    /// Fail the next call to `method` with `failure`. Multiple failures injected into the same
+    /// method are returned in the order they were injected.
+    pub fn inject_failure(&self, method: MockMethod, failure: MockFailure) {
+        self.lock()
+            .failures
+            .entry(method)
+            .or_default()
+            .push_back(failure);
+    }
+
+    /// All datagrams that have been submitted to this chain, along with the height they were
+    /// included at.
+    #[must_use]
+    pub fn submitted(&self) -> Vec<(Height, IbcDatagram)> {
+        self.lock().submitted.clone()
+    }
+
+    /// Include `datagrams` in a new block, returning the height they were included at.
+    pub fn submit(&self, datagrams: Vec<IbcDatagram>) -> RpcResult<Height> {
+        self.check_failure(MockMethod::Submit)?;
+
+        let mut state = self.lock();
+
+        state.height += 1;
+        let height = Height::new(state.height);
+
+        state
+            .submitted
+            .extend(datagrams.into_iter().map(|datagram| (height, datagram)));
+
+        Ok(height)
+    }
+
+    /// Fetch the headers to update a client tracking this chain from `update_from` to `update_to`.
+    ///
+    /// A single header is returned that updates the client directly to `update_to`, which must not
+    /// be greater than the latest height of this chain.
+    pub fn fetch_update_headers(
+        &self,
+        update_from: Height,
+        update_to: Height,
+    ) -> RpcResult<OrderedHeaders> {
+        self.check_failure(MockMethod::FetchUpdateHeaders)?;
+
+        let latest_height = self.latest_height();
+
+        if update_to > latest_height {
+            return Err(missing_state(
+                "update height is greater than the latest height",
+                Some(json!({
+                    "chain_id": self.chain_id,
+                    "latest_height": latest_height,
+                    "update_to": update_to,
+                })),
+            )());
+        }
+
+        Ok(OrderedHeaders {
+            headers: vec![(
+                DecodedHeaderMeta { height: update_to },
+                json!({
+                    "chain_id": self.chain_id,
+                    "trusted_height": update_from,
+                    "height": update_to,
+                    "timestamp": timestamp_at(update_to),
+                }),
+            )],
+        })
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, MockChainState> {
+        self.state.lock().expect("mutex is poisoned")
+    }
+
+    fn check_failure(&self, method: MockMethod) -> RpcResult<()> {
+        match self
+            .lock()
+            .failures
+            .get_mut(&method)
+            .and_then(VecDeque::pop_front)
+        {
+            Some(failure) => Err(failure.into()),
+            None => Ok(()),
+        }
+    }
+
+    fn query_height(&self, finalized: bool) -> Height {
+        let state = self.lock();
+
+        if finalized {
+            Height::new(state.height.saturating_sub(state.finality_lag).max(1))
+        } else {
+            Height::new(state.height)
+        }
+    }
+
+    fn check_height(&self, at: Height) -> RpcResult<()> {
+        let latest_height = self.latest_height();
+
+        if at > latest_height {
+            Err(missing_state(
+                "height is greater than the latest height",
+                Some(json!({
+                    "chain_id": self.chain_id,
+                    "latest_height": latest_height,
+                    "at": at,
+                })),
+            )())
+        } else {
+            Ok(())
+        }
+    }
+
+    /// The value stored under `path` at height `at`, or null if there is none.
+    fn state_at<P: Serialize>(&self, at: Height, path: &P) -> Value {
+        self.lock()
+            .store
+            .get(&store_key(path))
+            .and_then(|values| values.range(..=at.height()).next_back())
+            .map_or(Value::Null, |(_, value)| value.clone())
+    }
+}
+
+/// The timestamp of the block at `height`, in nanoseconds.
+#[must_use]
+pub fn timestamp_at(height: Height) -> i64 {
+    i64::try_from(height.height()).expect("height is too large") * BLOCK_TIME_NANOS
+}
+
+fn store_key<T: Serialize>(value: &T) -> String {
+    serde_json::to_string(value).expect("infallible; qed;")
+}
+
+/// Two [`MockChain`]s, for testing flows between a chain and its counterparty.
+#[derive(Debug, Clone)]
+pub struct MockChains {
+    pub a: MockChain,
+    pub b: MockChain,
+}
+
+impl MockChains {
+    #[must_use]
+    pub fn new(a: ChainId, b: ChainId) -> Self {
+        Self {
+            a: MockChain::new(a),
+            b: MockChain::new(b),
+        }
+    }
+
+    #[must_use]
+    pub fn get(&self, chain_id: &ChainId) -> Option<&MockChain> {
+        [&self.a, &self.b]
+            .into_iter()
+            .find(|chain| chain.chain_id() == chain_id)
+    }
+
+    /// Produce `blocks` new blocks on both chains.
+    pub fn advance(&self, blocks: u64) {
+        self.a.advance(blocks);
+        self.b.advance(blocks);
+    }
+}
+
+#[async_trait]
+impl ConsensusModuleServer for MockChain {
+    async fn query_latest_height(&self, _: &Extensions, finalized: bool) -> RpcResult<Height> {
+        self.check_failure(MockMethod::QueryLatestHeight)?;
+
+        Ok(self.query_height(finalized))
+    }
+
+    async fn query_latest_timestamp(&self, _: &Extensions, finalized: bool) -> RpcResult<i64> {
+        self.check_failure(MockMethod::QueryLatestTimestamp)?;
+
+        Ok(timestamp_at(self.query_height(finalized)))
+    }
+
+    async fn self_client_state(&self, _: &Extensions, height: Height) -> RpcResult<Value> {
+        self.check_failure(MockMethod::SelfClientState)?;
+        self.check_height(height)?;
+
+        Ok(json!({
+            "chain_id": self.chain_id,
+            "latest_height": height,
+        }))
+    }
+
+    async fn self_consensus_state(&self, _: &Extensions, height: Height) -> RpcResult<Value> {
+        self.check_failure(MockMethod::SelfConsensusState)?;
+        self.check_height(height)?;
+
+        Ok(json!({
+            "timestamp": timestamp_at(height),
+        }))
+    }
+}
+
+#[async_trait]
+impl<V: IbcSpec> StateModuleServer<V> for MockChain {
+    async fn query_ibc_state(
+        &self,
+        _: &Extensions,
+        at: Height,
+        path: V::StorePath,
+    ) -> RpcResult<Value> {
+        self.check_failure(MockMethod::QueryIbcState)?;
+        self.check_height(at)?;
+
+        Ok(self.state_at(at, &path))
+    }
+
+    async fn client_info(&self, _: &Extensions, client_id: V::ClientId) -> RpcResult<ClientInfo> {
+        self.check_failure(MockMethod::ClientInfo)?;
+
+        self.lock()
+            .clients
+            .get(&store_key(&client_id))
+            .cloned()
+            .ok_or_else(missing_state(
+                "client not found",
+                Some(json!({
+                    "chain_id": self.chain_id,
+                    "client_id": client_id.to_string(),
+                })),
+            ))
+    }
+}
+
+#[async_trait]
+impl<V: IbcSpec> ProofModuleServer<V> for MockChain {
+    /// The proof is the value being proven along with where it was read from, such that a mock
+    /// client can verify it by comparing the fields.
+    async fn query_ibc_proof(
+        &self,
+        _: &Extensions,
+        at: Height,
+        path: V::StorePath,
+    ) -> RpcResult<Value> {
+        self.check_failure(MockMethod::QueryIbcProof)?;
+        self.check_height(at)?;
+
+        Ok(json!({
+            "chain_id": self.chain_id,
+            "height": at,
+            "path": path,
+            "value": self.state_at(at, &path),
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ibc_union_spec::{ClientStatePath, IbcUnion, StorePath};
+    use unionlabs::bytes::Bytes;
+
+    use super::*;
+
+    fn chains() -> MockChains {
+        MockChains::new(ChainId::new("chain-a"), ChainId::new("chain-b"))
+    }
+
+    #[tokio::test]
+    async fn state_is_versioned() {
+        let chains = chains();
+
+        let path = StorePath::from(ClientStatePath { client_id: 1 });
+
+        chains.a.set_state(&path, Bytes::from(vec![1]));
+        let first = chains.a.advance(2);
+        chains.a.set_state(&path, Bytes::from(vec![2]));
+
+        let query = |at| {
+            StateModuleServer::<IbcUnion>::query_ibc_state(
+                &chains.a,
+                &Extensions::new(),
+                at,
+                path.clone(),
+            )
+        };
+
+        assert_eq!(query(Height::new(1)).await.unwrap(), json!("0x01"));
+        assert_eq!(query(first).await.unwrap(), json!("0x02"));
+        // state is only written on the chain it was set on
+        assert_eq!(
+            StateModuleServer::<IbcUnion>::query_ibc_state(
+                &chains.b,
+                &Extensions::new(),
+                Height::new(1),
+                path.clone(),
+            )
+            .await
+            .unwrap(),
+            Value::Null
+        );
+        // heights past the latest height can't be queried
+        assert_eq!(
+            query(first.increment()).await.unwrap_err().code(),
+            FATAL_JSONRPC_ERROR_CODE
+        );
+    }
+
+    #[tokio::test]
+    async fn injected_failures() {
+        let chains = chains();
+
+        chains.a.inject_failure(
+            MockMethod::QueryLatestHeight,
+            MockFailure::Retryable("unavailable".to_owned()),
+        );
+        chains.a.inject_failure(
+            MockMethod::QueryLatestHeight,
+            MockFailure::Fatal("broken".to_owned()),
+        );
+
+        let query = || chains.a.query_latest_height(&Extensions::new(), false);
+
+        assert_eq!(query().await.unwrap_err().code(), -1);
+        assert_eq!(query().await.unwrap_err().code(), FATAL_JSONRPC_ERROR_CODE);
+        assert_eq!(query().await.unwrap(), Height::new(1));
+    }
+
+    #[test]
+    fn submit_advances_height() {
+        let chains = chains();
+
+        chains.a.set_finality_lag(1);
+
+        let datagram = IbcDatagram {
+            ibc_spec_id: IbcUnion::ID,
+            datagram: json!({}),
+        };
+
+        let included_at = chains.a.submit(vec![datagram.clone()]).unwrap();
+
+        assert_eq!(included_at, Height::new(2));
+        assert_eq!(chains.a.submitted(), vec![(included_at, datagram)]);
+        assert_eq!(chains.a.query_height(true), Height::new(1));
+        assert_eq!(chains.b.latest_height(), Height::new(1));
+
+        assert!(chains
+            .a
+            .fetch_update_headers(Height::new(1), Height::new(3))
+            .is_err());
+        assert_eq!(
+            chains
+                .a
+                .fetch_update_headers(Height::new(1), included_at)
+                .unwrap()
+                .headers[0]
+                .0,
+            DecodedHeaderMeta {
+                height: included_at
+            }
+        );
+    }
+}
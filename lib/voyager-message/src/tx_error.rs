@@ -0,0 +1,90 @@
+//! Classification of transaction submission errors.
+//!
+//! The errors returned by chains on transaction submission are specific to each chain (and often
+//! only distinguishable by their message), so transaction plugins classify them into a
+//! [`TxErrorClass`], which determines how the failed submission is handled.
+
+use jsonrpsee::types::ErrorObject;
+
+use crate::{retry_after_error, FATAL_JSONRPC_ERROR_CODE};
+
+/// The delay (in seconds) before a submission that failed with
+/// [`TxErrorClass::RequiresHigherGas`] is retried.
+pub const REQUIRES_HIGHER_GAS_RETRY_DELAY: u64 = 12;
+
+/// How a failed transaction submission should be handled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TxErrorClass {
+    /// The failure is transient (i.e. the node is unavailable, or the transaction was not
+    /// included in time), and the submission can be retried immediately.
+    RetryNow,
+    /// The locally tracked state of the signer (i.e. the nonce) is out of sync with the chain. The
+    /// plugin is expected to resync it, after which the submission can be retried immediately.
+    RetryAfterResync,
+    /// The transaction can not be paid for with the current gas settings, i.e. it ran out of gas,
+    /// was underpriced, or the gas price is above the configured maximum. The submission is
+    /// retried after [`REQUIRES_HIGHER_GAS_RETRY_DELAY`], by which time gas prices may have moved
+    /// or the signer may have been topped up. This is also used for failures that can only be
+    /// resolved by another account, such as an exhausted fee grant or an expired authz grant.
+    RequiresHigherGas,
+    /// The submission will never succeed, and is not retried.
+    Permanent,
+}
+
+impl TxErrorClass {
+    #[must_use]
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::RetryNow => "retry_now",
+            Self::RetryAfterResync => "retry_after_resync",
+            Self::RequiresHigherGas => "requires_higher_gas",
+            Self::Permanent => "permanent",
+        }
+    }
+
+    /// The error to return from a plugin call for a submission that failed with an error of this
+    /// class. This is converted to the corresponding [`QueueError`](voyager_vm::QueueError) by
+    /// voyager.
+    ///
+    /// [`Self::RetryAfterResync`] submissions must be returned as errors rather than requeued by
+    /// the plugin, such that a signer that is persistently out of sync (i.e. because another
+    /// relayer is using the same key) is subject to the retry limit and backoff.
+    pub fn into_error_object(self, message: impl Into<String>) -> ErrorObject<'static> {
+        match self {
+            Self::RetryNow | Self::RetryAfterResync => ErrorObject::owned(-1, message, None::<()>),
+            Self::RequiresHigherGas => retry_after_error(REQUIRES_HIGHER_GAS_RETRY_DELAY, message),
+            Self::Permanent => ErrorObject::owned(FATAL_JSONRPC_ERROR_CODE, message, None::<()>),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use voyager_vm::QueueError;
+
+    use super::*;
+    use crate::error_object_to_queue_error;
+
+    #[test]
+    fn classes_map_to_queue_errors() {
+        assert!(matches!(
+            error_object_to_queue_error(TxErrorClass::RetryNow.into_error_object("err")),
+            QueueError::Retry(_)
+        ));
+        assert!(matches!(
+            error_object_to_queue_error(TxErrorClass::RetryAfterResync.into_error_object("err")),
+            QueueError::Retry(_)
+        ));
+        assert!(matches!(
+            error_object_to_queue_error(TxErrorClass::RequiresHigherGas.into_error_object("err")),
+            QueueError::RetryAfter {
+                delay: REQUIRES_HIGHER_GAS_RETRY_DELAY,
+                ..
+            }
+        ));
+        assert!(matches!(
+            error_object_to_queue_error(TxErrorClass::Permanent.into_error_object("err")),
+            QueueError::Fatal(_)
+        ));
+    }
+}
@@ -0,0 +1,178 @@
+//! Upgrading of [`IbcClassic`] clients after a planned upgrade of the chain they track.
+//!
+//! When a cosmos-sdk chain performs a planned upgrade that breaks clients tracking it (such as a
+//! change of chain id or unbonding period), it writes the upgraded client and consensus states to
+//! its upgrade store at `upgrade_height - 1` and halts at `upgrade_height`. Clients tracking the
+//! chain are then upgraded with `MsgUpgradeClient`, which proves these states against the
+//! consensus state of the client at `upgrade_height`.
+
+use ibc_classic_spec::{
+    IbcClassic, MsgUpgradeClient, StorePath, UpgradedClientStatePath, UpgradedConsensusStatePath,
+};
+use jsonrpsee::core::RpcResult;
+use serde_json::json;
+use tracing::info;
+use unionlabs::{bytes::Bytes, ibc::core::client::height::Height, id::ClientId};
+use voyager_core::{IbcSpec, IbcStorePathKey, QueryHeight};
+use voyager_vm::{call, data, seq, Op};
+
+use crate::{
+    call::WaitForTrustedHeight,
+    core::ChainId,
+    data::{IbcDatagram, WithChainId},
+    into_value,
+    rpc::{json_rpc_error_to_error_object, missing_state, VoyagerRpcClient},
+    RawClientId, VoyagerMessage,
+};
+
+/// Build the op to upgrade `client_id` on `chain_id` with the upgrade plan executed at
+/// `upgrade_height` on the chain it tracks.
+///
+/// The op waits for the client to be updated to `upgrade_height` before submitting the upgrade.
+/// Since the counterparty halts at this height, this is the last update the client will receive
+/// before the upgrade.
+pub async fn upgrade_client(
+    client: &impl VoyagerRpcClient,
+    chain_id: ChainId,
+    client_id: ClientId,
+    upgrade_height: u64,
+) -> RpcResult<Op<VoyagerMessage>> {
+    let raw_client_id = RawClientId::new(client_id.clone());
+
+    let client_info = client
+        .client_info(chain_id.clone(), IbcClassic::ID, raw_client_id.clone())
+        .await
+        .map_err(json_rpc_error_to_error_object)?;
+
+    let client_meta = client
+        .client_meta(
+            chain_id.clone(),
+            IbcClassic::ID,
+            QueryHeight::Latest,
+            raw_client_id.clone(),
+        )
+        .await
+        .map_err(json_rpc_error_to_error_object)?;
+
+    let counterparty_chain_id = client_meta.chain_id;
+
+    // the upgrade plan height is in the revision of the chain before the upgrade
+    let proof_height = Height::new_with_revision(client_meta.height.revision(), upgrade_height);
+    let state_height = Height::new_with_revision(
+        client_meta.height.revision(),
+        upgrade_height
+            .checked_sub(1)
+            .ok_or_else(missing_state("upgrade height must be greater than 0", None))?,
+    );
+
+    info!(
+        %chain_id,
+        %client_id,
+        %counterparty_chain_id,
+        %proof_height,
+        "upgrading client"
+    );
+
+    let client_state_path = UpgradedClientStatePath { upgrade_height };
+    let consensus_state_path = UpgradedConsensusStatePath { upgrade_height };
+
+    let client_state = query_upgraded_state(
+        client,
+        &counterparty_chain_id,
+        state_height,
+        client_state_path.clone(),
+    )
+    .await?;
+
+    let consensus_state = query_upgraded_state(
+        client,
+        &counterparty_chain_id,
+        state_height,
+        consensus_state_path.clone(),
+    )
+    .await?;
+
+    let mut proofs = vec![];
+
+    for path in [
+        StorePath::from(client_state_path),
+        StorePath::from(consensus_state_path),
+    ] {
+        let proof = client
+            .query_ibc_proof(
+                counterparty_chain_id.clone(),
+                IbcClassic::ID,
+                QueryHeight::Specific(proof_height),
+                into_value(path),
+            )
+            .await
+            .map_err(json_rpc_error_to_error_object)?
+            .proof;
+
+        proofs.push(
+            client
+                .encode_proof(
+                    client_info.client_type.clone(),
+                    client_info.ibc_interface.clone(),
+                    IbcClassic::ID,
+                    proof,
+                )
+                .await
+                .map_err(json_rpc_error_to_error_object)?,
+        );
+    }
+
+    let [proof_upgrade_client, proof_upgrade_consensus_state] =
+        <[Bytes; 2]>::try_from(proofs).expect("two proofs were queried; qed;");
+
+    Ok(seq([
+        call(WaitForTrustedHeight {
+            chain_id: chain_id.clone(),
+            ibc_spec_id: IbcClassic::ID,
+            client_id: raw_client_id,
+            height: proof_height,
+            deadline: None,
+        }),
+        data(WithChainId {
+            chain_id,
+            message: IbcDatagram::new::<IbcClassic>(
+                MsgUpgradeClient {
+                    client_id,
+                    client_state,
+                    consensus_state,
+                    proof_upgrade_client,
+                    proof_upgrade_consensus_state,
+                }
+                .into(),
+            ),
+        }),
+    ]))
+}
+
+async fn query_upgraded_state<P: IbcStorePathKey<Spec = IbcClassic, Value = Option<Bytes>>>(
+    client: &impl VoyagerRpcClient,
+    chain_id: &ChainId,
+    height: Height,
+    path: P,
+) -> RpcResult<Bytes> {
+    let path = <IbcClassic as IbcSpec>::StorePath::from(path.into());
+
+    client
+        .query_ibc_state(
+            chain_id.clone(),
+            IbcClassic::ID,
+            QueryHeight::Specific(height),
+            into_value(&path),
+        )
+        .await
+        .map_err(json_rpc_error_to_error_object)?
+        .decode_state::<Option<Bytes>>()?
+        .ok_or_else(missing_state(
+            "upgraded state not found, the upgrade may not have been scheduled at this height",
+            Some(json!({
+                "chain_id": chain_id,
+                "height": height,
+                "path": path,
+            })),
+        ))
+}
@@ -0,0 +1,87 @@
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use unionlabs::ibc::core::client::height::Height;
+use voyager_message::{
+    call::WaitForHeight,
+    callback::AggregateMsgUpdateClientsFromOrderedHeaders,
+    core::{ChainId, IbcSpecId},
+    PluginMessage, RawClientId, VoyagerMessage,
+};
+use voyager_vm::{
+    call,
+    codec::{Cbor, Codec, Json},
+    conc, data, promise, seq, Op,
+};
+
+fn bench_codec(c: &mut Criterion) {
+    let mut group = c.benchmark_group("codec");
+
+    for depth in [1_u64, 4, 8] {
+        let op = mk_msg(depth);
+
+        // criterion has no way to report sizes, so print them alongside the timings
+        println!(
+            "depth {depth}: json {} bytes, cbor {} bytes",
+            Json::encode(&op).unwrap().len(),
+            Cbor::encode(&op).unwrap().len()
+        );
+
+        bench::<Json>(&mut group, "json", depth, &op);
+        bench::<Cbor>(&mut group, "cbor", depth, &op);
+    }
+
+    group.finish();
+}
+
+fn bench<C: Codec>(
+    group: &mut criterion::BenchmarkGroup<'_, criterion::measurement::WallTime>,
+    name: &str,
+    depth: u64,
+    op: &Op<VoyagerMessage>,
+) {
+    group.bench_with_input(
+        BenchmarkId::new(format!("{name}/encode"), depth),
+        op,
+        |b, op| {
+            b.iter(|| C::encode(black_box(op)).unwrap());
+        },
+    );
+
+    let bytes = C::encode(op).unwrap();
+
+    group.bench_with_input(
+        BenchmarkId::new(format!("{name}/decode"), depth),
+        &bytes,
+        |b, bytes| b.iter(|| C::decode::<Op<VoyagerMessage>>(black_box(bytes)).unwrap()),
+    );
+}
+
+/// An op with `depth` levels of nested promises, each level containing a few calls and data.
+fn mk_msg(depth: u64) -> Op<VoyagerMessage> {
+    let leaf = |i: u64| {
+        conc([
+            call(WaitForHeight {
+                chain_id: ChainId::new("chain"),
+                height: Height::new_with_revision(1, i),
+                finalized: true,
+                deadline: None,
+            }),
+            data(PluginMessage::new("plugin", [i; 16])),
+        ])
+    };
+
+    (0..depth).fold(leaf(0), |op, i| {
+        promise(
+            [op, leaf(i), seq([leaf(i), leaf(i + 1)])],
+            [],
+            AggregateMsgUpdateClientsFromOrderedHeaders {
+                ibc_spec_id: IbcSpecId::new(IbcSpecId::UNION),
+                chain_id: ChainId::new("chain"),
+                counterparty_client_id: RawClientId::new(i),
+            },
+        )
+    })
+}
+
+criterion_group!(benches, bench_codec);
+
+criterion_main!(benches);
@@ -0,0 +1,111 @@
+//! [`Arbitrary`] implementations for [`Op`] and [`Promise`], for property testing the serde
+//! representation of ops (and anything built on top of it, such as the queue persistence).
+//!
+//! Ops are recursive, so a derived implementation could generate arbitrarily deep ops (which can
+//! overflow the stack when (de)serializing them). Instead, ops nested deeper than
+//! [`MAX_ARBITRARY_DEPTH`] are always leaves.
+
+use arbitrary::{Arbitrary, Result, Unstructured};
+
+use crate::{Op, Promise, QueueMessage, VecDeque};
+
+/// The maximum nesting depth of an arbitrary [`Op`].
+pub const MAX_ARBITRARY_DEPTH: usize = 4;
+
+impl<'a, T: QueueMessage> Arbitrary<'a> for Op<T>
+where
+    T::Data: Arbitrary<'a>,
+    T::Call: Arbitrary<'a>,
+    T::Callback: Arbitrary<'a>,
+{
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        arbitrary_op(u, 0)
+    }
+}
+
+impl<'a, T: QueueMessage> Arbitrary<'a> for Promise<T>
+where
+    T::Data: Arbitrary<'a>,
+    T::Call: Arbitrary<'a>,
+    T::Callback: Arbitrary<'a>,
+{
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        arbitrary_promise(u, 0)
+    }
+}
+
+fn arbitrary_op<'a, T: QueueMessage>(u: &mut Unstructured<'a>, depth: usize) -> Result<Op<T>>
+where
+    T::Data: Arbitrary<'a>,
+    T::Call: Arbitrary<'a>,
+    T::Callback: Arbitrary<'a>,
+{
+    if depth >= MAX_ARBITRARY_DEPTH {
+        return Ok(match u.int_in_range(0..=3)? {
+            0 => Op::Data(u.arbitrary()?),
+            1 => Op::Call(u.arbitrary()?),
+            2 => Op::Defer {
+                until: u.arbitrary()?,
+            },
+            _ => Op::Noop,
+        });
+    }
+
+    Ok(match u.int_in_range(0..=9)? {
+        0 => Op::Data(u.arbitrary()?),
+        1 => Op::Call(u.arbitrary()?),
+        2 => Op::Defer {
+            until: u.arbitrary()?,
+        },
+        3 => Op::Seq(arbitrary_ops(u, depth + 1)?),
+        4 => Op::Conc(arbitrary_ops(u, depth + 1)?),
+        5 => Op::Promise(arbitrary_promise(u, depth + 1)?),
+        6 => Op::Void(Box::new(arbitrary_op(u, depth + 1)?)),
+        7 => Op::Retry {
+            attempt: u.arbitrary()?,
+            backoff: u.arbitrary()?,
+            msg: Box::new(arbitrary_op(u, depth + 1)?),
+        },
+        8 => Op::WithPriority {
+            priority: u.arbitrary()?,
+            msg: Box::new(arbitrary_op(u, depth + 1)?),
+        },
+        _ => Op::Noop,
+    })
+}
+
+fn arbitrary_ops<'a, T: QueueMessage>(
+    u: &mut Unstructured<'a>,
+    depth: usize,
+) -> Result<VecDeque<Op<T>>>
+where
+    T::Data: Arbitrary<'a>,
+    T::Call: Arbitrary<'a>,
+    T::Callback: Arbitrary<'a>,
+{
+    let mut ops = VecDeque::new();
+
+    // same as `Unstructured::arbitrary_iter`, which can't be used here since the elements need to
+    // be generated with the current depth
+    while u.arbitrary()? {
+        ops.push_back(arbitrary_op(u, depth)?);
+    }
+
+    Ok(ops)
+}
+
+fn arbitrary_promise<'a, T: QueueMessage>(
+    u: &mut Unstructured<'a>,
+    depth: usize,
+) -> Result<Promise<T>>
+where
+    T::Data: Arbitrary<'a>,
+    T::Call: Arbitrary<'a>,
+    T::Callback: Arbitrary<'a>,
+{
+    Ok(Promise {
+        queue: arbitrary_ops(u, depth)?,
+        data: u.arbitrary()?,
+        receiver: u.arbitrary()?,
+    })
+}
@@ -0,0 +1,67 @@
+//! Encodings for persisting and transmitting ops.
+//!
+//! JSON is used for everything that is human-facing (the RPC APIs, the CLI, the queue inspection
+//! endpoints), but deeply nested ops (such as large promises) are both large and slow to
+//! (de)serialize as JSON. [`Cbor`] is a self-describing binary encoding that supports the same
+//! serde representation as JSON (including the `@type`/`@value` tagging of ops), and is usually a
+//! good deal more compact. See `benches/codec.rs` for a comparison of the two.
+
+use std::io;
+
+use serde::{de::DeserializeOwned, Serialize};
+
+/// An encoding for serde types.
+pub trait Codec {
+    type Error: std::error::Error + Send + Sync + 'static;
+
+    fn encode<T: Serialize>(value: &T) -> Result<Vec<u8>, Self::Error>;
+
+    fn decode<T: DeserializeOwned>(bytes: &[u8]) -> Result<T, Self::Error>;
+}
+
+/// The JSON encoding, as used in all human-facing APIs.
+pub enum Json {}
+
+impl Codec for Json {
+    type Error = serde_json::Error;
+
+    fn encode<T: Serialize>(value: &T) -> Result<Vec<u8>, Self::Error> {
+        serde_json::to_vec(value)
+    }
+
+    fn decode<T: DeserializeOwned>(bytes: &[u8]) -> Result<T, Self::Error> {
+        // ops can be arbitrarily deeply nested
+        let mut deserializer = serde_json::Deserializer::from_slice(bytes);
+        deserializer.disable_recursion_limit();
+        T::deserialize(&mut deserializer)
+    }
+}
+
+/// The CBOR encoding ([RFC 8949](https://www.rfc-editor.org/rfc/rfc8949)).
+pub enum Cbor {}
+
+#[derive(Debug, thiserror::Error)]
+pub enum CborError {
+    #[error("error encoding cbor")]
+    Encode(#[from] ciborium::ser::Error<io::Error>),
+    #[error("error decoding cbor")]
+    Decode(#[from] ciborium::de::Error<io::Error>),
+}
+
+impl Codec for Cbor {
+    type Error = CborError;
+
+    fn encode<T: Serialize>(value: &T) -> Result<Vec<u8>, Self::Error> {
+        let mut bytes = vec![];
+        ciborium::into_writer(value, &mut bytes)?;
+        Ok(bytes)
+    }
+
+    fn decode<T: DeserializeOwned>(bytes: &[u8]) -> Result<T, Self::Error> {
+        // ops can be arbitrarily deeply nested
+        Ok(ciborium::de::from_reader_with_recursion_limit(
+            bytes,
+            usize::MAX,
+        )?)
+    }
+}
@@ -2,15 +2,22 @@ use std::{future::Future, time::Duration};
 
 use futures::{stream, FutureExt, Stream, StreamExt};
 use tokio::time::sleep;
-use tracing::error;
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, error};
 use unionlabs::ErrorReporter;
 
-use crate::{defer, now, seq, BoxDynError, Captures, Queue, QueueError, QueueMessage};
+use crate::{
+    defer, metrics::RETRIES_EXHAUSTED_COUNT, now, record::Recorder, seq, Backoff, BoxDynError,
+    Captures, LimitExceeded, Limits, Op, Queue, QueueError, QueueMessage,
+};
 
 pub struct Engine<'a, T: QueueMessage, Q: Queue<T>> {
     store: &'a T::Context,
     queue: &'a Q,
     optimizer: &'a T::Filter,
+    limits: Limits,
+    recorder: Option<Recorder>,
+    shutdown: CancellationToken,
 }
 
 impl<'a, T: QueueMessage, Q: Queue<T>> Engine<'a, T, Q> {
@@ -19,12 +26,40 @@ impl<'a, T: QueueMessage, Q: Queue<T>> Engine<'a, T, Q> {
             store,
             queue,
             optimizer: filter,
+            limits: Limits::default(),
+            recorder: None,
+            shutdown: CancellationToken::new(),
         }
     }
 
+    #[must_use]
+    pub fn with_limits(mut self, limits: Limits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Record every op handled by this engine with `recorder`; see [`crate::record`].
+    #[must_use]
+    pub fn with_recorder(mut self, recorder: Recorder) -> Self {
+        self.recorder = Some(recorder);
+        self
+    }
+
+    /// Stop processing new ops once `shutdown` is cancelled. The op that is being processed when
+    /// `shutdown` is cancelled is processed to completion, after which [`Self::run`] ends.
+    #[must_use]
+    pub fn with_shutdown(mut self, shutdown: CancellationToken) -> Self {
+        self.shutdown = shutdown;
+        self
+    }
+
     pub fn run(self) -> impl Stream<Item = Result<T::Data, BoxDynError>> + Send + Captures<'a> {
         futures::stream::try_unfold(self, |this| async move {
             sleep(Duration::from_millis(10)).await;
+            if this.shutdown.is_cancelled() {
+                debug!("shutting down, not processing any more ops");
+                return Ok(None);
+            }
             let res = this.step().await;
             res.map(move |x| x.map(|x| (x, this)))
         })
@@ -38,28 +73,84 @@ impl<'a, T: QueueMessage, Q: Queue<T>> Engine<'a, T, Q> {
            + Captures<'b>
            + Send {
         // yield back to the runtime and throttle a bit, prevents 100% cpu usage while still allowing for a fast spin-loop
-        sleep(Duration::from_millis(10)).then(|()| {
+        sleep(Duration::from_millis(10)).then(|()| async {
+            let paused = T::paused_keys(self.store);
+
             self.queue
-                .process::<_, _, Option<T::Data>>(self.optimizer, |op| {
-                    op.clone().process(self.store, 0).map(|res| match res {
+                .process::<_, _, Option<T::Data>>(self.optimizer, &paused, |op| async move {
+                    let res = op
+                        .clone()
+                        .process_with_limits(self.store, self.limits, 0)
+                        .await;
+
+                    if let Some(recorder) = &self.recorder {
+                        if let Err(err) = recorder.record(&op, &res) {
+                            error!(error = %ErrorReporter(err), "error recording op");
+                        }
+                    }
+
+                    match res {
                         Ok(op) => (None, Ok(op.into_iter().collect())),
+                        Err(err) if err.is_retryable() && self.limits.retries_exceeded(0) => {
+                            RETRIES_EXHAUSTED_COUNT.inc();
+
+                            let full_err = ErrorReporter(LimitExceeded::Retries {
+                                retries: 0,
+                                error: err,
+                            });
+                            error!(error = %full_err, "fatal error");
+                            T::on_failure(self.store, &op, &full_err.to_string());
+                            (None, Err(full_err.to_string()))
+                        }
                         Err(QueueError::Fatal(fatal)) => {
                             let full_err = ErrorReporter(&*fatal);
                             error!(error = %full_err, "fatal error");
+                            T::on_failure(self.store, &op, &full_err.to_string());
                             (None, Err(full_err.to_string()))
                         }
-                        Err(QueueError::Retry(retry)) => {
-                            // TODO: Add some backoff logic here based on `full_err`?
-                            let full_err = ErrorReporter(&*retry);
+                        Err(QueueError::Retry(err)) => {
+                            let full_err = ErrorReporter(&*err);
                             error!(error = %full_err, "retryable error");
-                            (None, Ok(vec![seq([defer(now() + 3), op])]))
+
+                            // further failures will be retried with exponential backoff, see
+                            // `Op::Retry`
+                            let backoff = Backoff::default();
+
+                            (
+                                None,
+                                Ok(vec![seq([
+                                    defer(now() + backoff.delay(0)),
+                                    Op::Retry {
+                                        attempt: 1,
+                                        backoff,
+                                        msg: Box::new(op),
+                                    },
+                                ])]),
+                            )
+                        }
+                        Err(QueueError::RetryAfter { delay, error }) => {
+                            let full_err = ErrorReporter(&*error);
+                            error!(error = %full_err, %delay, "retryable error");
+
+                            (
+                                None,
+                                Ok(vec![seq([
+                                    defer(now() + delay),
+                                    Op::Retry {
+                                        attempt: 1,
+                                        backoff: Backoff::default(),
+                                        msg: Box::new(op),
+                                    },
+                                ])]),
+                            )
                         }
-                    })
+                    }
                 })
                 .map(|data| match data {
                     Ok(data) => Ok(Some(data.flatten())),
                     Err(err) => Err(err.into()),
                 })
+                .await
         })
     }
 }
@@ -1,6 +1,7 @@
 use std::{
-    collections::BTreeMap,
+    collections::{BTreeMap, HashMap, HashSet},
     future::Future,
+    num::NonZeroUsize,
     sync::{
         atomic::{AtomicU32, Ordering},
         Arc, Mutex,
@@ -9,40 +10,319 @@ use std::{
 
 use either::Either;
 use frame_support_procedural::{CloneNoBound, DebugNoBound};
-use tracing::{debug, info_span, warn, Instrument};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use tracing::{debug, error, info_span, warn, Instrument};
 
 use crate::{
     filter::{FilterResult, InterestFilter},
+    now,
     pass::Pass,
     Captures, Op, Queue, QueueMessage,
 };
 
+/// The maximum amount of failed items that are kept. Once exceeded, the oldest failed items are
+/// dropped.
+pub const MAX_FAILED_ITEMS: usize = 1000;
+
 #[derive(DebugNoBound, CloneNoBound)]
 pub struct InMemoryQueue<T: QueueMessage> {
     idx: Arc<AtomicU32>,
     ready: Arc<Mutex<BTreeMap<u32, Item<T>>>>,
     done: Arc<Mutex<BTreeMap<u32, Item<T>>>>,
+    /// Items that failed to process with a fatal error, along with the error message. At most
+    /// [`MAX_FAILED_ITEMS`] are kept.
+    failed: Arc<Mutex<BTreeMap<u32, (Item<T>, String)>>>,
     #[allow(clippy::type_complexity)]
     optimizer_queue: Arc<Mutex<BTreeMap<String, BTreeMap<u32, Item<T>>>>>,
+    scheduler: Arc<Mutex<Scheduler>>,
+    /// The [`QueueMessage::idempotency_key`]s of all ops that are either waiting to be processed
+    /// or optimized, or are currently being processed.
+    idempotency_keys: Arc<Mutex<HashSet<String>>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct InMemoryQueueConfig {
+    /// The maximum amount of ops with the same [`QueueMessage::concurrency_key`] that will be
+    /// processed concurrently. Ready ops are picked round-robin between the keys that are below
+    /// this limit, such that a single busy key cannot starve the others.
+    ///
+    /// If not set, ops are processed strictly in the order they became ready.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_in_flight_per_key: Option<NonZeroUsize>,
+}
+
+/// Fair scheduling of ready items between concurrency keys.
+#[derive(Debug, Default)]
+pub(crate) struct Scheduler {
+    max_in_flight_per_key: Option<NonZeroUsize>,
+    in_flight: HashMap<String, usize>,
+    /// The key of the last scheduled item, used to resume the round-robin after it.
+    last_key: Option<String>,
+}
+
+impl Scheduler {
+    pub(crate) fn new(max_in_flight_per_key: Option<NonZeroUsize>) -> Self {
+        Self {
+            max_in_flight_per_key,
+            ..Default::default()
+        }
+    }
+
+    /// Pick the next item to process out of `ready`, and mark it as in flight. Returns the id of
+    /// the item and the key it was scheduled under.
+    ///
+    /// Only the items with the highest [`Op::priority`] are considered; the fairness between keys
+    /// applies within a priority lane. Items that are [deferred](Op::due_at) are held back until
+    /// they are due, and items that are paused are held back until they are resumed.
+    pub(crate) fn next<T: QueueMessage>(
+        &mut self,
+        ready: &BTreeMap<u32, Item<T>>,
+        is_paused: impl Fn(&Op<T>) -> bool,
+    ) -> Option<(u32, Option<String>)> {
+        let now = now();
+
+        let is_ready = |item: &Item<T>| item.op.is_due(now) && !is_paused(&item.op);
+
+        let Some(max) = self.max_in_flight_per_key else {
+            // the oldest item in the highest priority lane
+            return ready
+                .iter()
+                .rev()
+                .filter(|(_, item)| is_ready(item))
+                .max_by_key(|(_, item)| item.op.priority())
+                .map(|(id, _)| (*id, None));
+        };
+
+        // the oldest ready item for each key that is below the limit, in the highest priority lane
+        // that has any such items
+        let mut candidates = BTreeMap::<Option<String>, u32>::new();
+        let mut lane = 0;
+
+        for (id, item) in ready {
+            if !is_ready(item) {
+                continue;
+            }
+
+            let key = T::concurrency_key(&item.op);
+
+            let at_limit = key.as_ref().is_some_and(|key| {
+                self.in_flight.get(key).copied().unwrap_or_default() >= max.get()
+            });
+
+            if at_limit {
+                continue;
+            }
+
+            let priority = item.op.priority();
+
+            if priority > lane {
+                lane = priority;
+                candidates.clear();
+            }
+
+            if priority == lane {
+                candidates.entry(key).or_insert(*id);
+            }
+        }
+
+        // resume the round-robin after the last scheduled key, wrapping around to the start
+        let (key, id) = candidates
+            .iter()
+            .find(|(key, _)| key.is_some() && **key > self.last_key)
+            .or_else(|| candidates.first_key_value())
+            .map(|(key, id)| (key.clone(), *id))?;
+
+        if let Some(key) = &key {
+            *self.in_flight.entry(key.clone()).or_default() += 1;
+            self.last_key = Some(key.clone());
+        }
+
+        Some((id, key))
+    }
+
+    pub(crate) fn done(&mut self, key: &str) {
+        if let Some(in_flight) = self.in_flight.get_mut(key) {
+            *in_flight -= 1;
+
+            if *in_flight == 0 {
+                self.in_flight.remove(key);
+            }
+        }
+    }
+}
+
+/// Marks an item as no longer in flight when dropped, even if processing is cancelled.
+struct InFlight<'a> {
+    scheduler: &'a Mutex<Scheduler>,
+    key: Option<String>,
+    idempotency_keys: &'a Mutex<HashSet<String>>,
+    idempotency_key: Option<String>,
+}
+
+impl InFlight<'_> {
+    /// Allow ops identical to the item to be enqueued again.
+    fn release_idempotency_key(&mut self) {
+        if let Some(idempotency_key) = self.idempotency_key.take() {
+            self.idempotency_keys
+                .lock()
+                .expect("mutex is poisoned")
+                .remove(&idempotency_key);
+        }
+    }
+}
+
+impl Drop for InFlight<'_> {
+    fn drop(&mut self) {
+        if let Some(key) = &self.key {
+            self.scheduler.lock().expect("mutex is poisoned").done(key);
+        }
+
+        self.release_idempotency_key();
+    }
+}
+
+/// An item that is waiting to be processed or optimized.
+#[derive(DebugNoBound, CloneNoBound)]
+pub struct QueuedItem<T: QueueMessage> {
+    pub id: u32,
+    pub parents: Vec<u32>,
+    /// The id of the item that was originally enqueued and that this item descends from.
+    pub correlation_id: u32,
+    /// The tag of the optimizer the item is waiting for, if any.
+    pub tag: Option<String>,
+    pub op: Op<T>,
+    /// The unix timestamp (in seconds) at which the item was enqueued.
+    pub created_at: u64,
+}
+
+/// An item that failed to process with a fatal error.
+#[derive(DebugNoBound, CloneNoBound)]
+pub struct FailedItem<T: QueueMessage> {
+    pub id: u32,
+    pub parents: Vec<u32>,
+    /// The id of the item that was originally enqueued and that this item descends from.
+    pub correlation_id: u32,
+    pub op: Op<T>,
+    /// The error the item failed with.
+    pub message: String,
+}
+
+impl<T: QueueMessage> InMemoryQueue<T> {
+    /// The most recent items that failed to process with a fatal error, most recent first.
+    pub fn failed(&self) -> Vec<FailedItem<T>> {
+        self.failed
+            .lock()
+            .expect("mutex is poisoned")
+            .iter()
+            .rev()
+            .map(|(id, (item, message))| FailedItem {
+                id: *id,
+                parents: item.parents.clone(),
+                correlation_id: item.correlation_id(*id),
+                op: item.op.clone(),
+                message: message.clone(),
+            })
+            .collect()
+    }
+
+    /// Remove the failed item `id`, returning its op such that it can be enqueued again.
+    pub fn take_failed(&self, id: u32) -> Option<Op<T>> {
+        self.failed
+            .lock()
+            .expect("mutex is poisoned")
+            .remove(&id)
+            .map(|(item, _)| item.op)
+    }
+
+    /// All items that are currently waiting to be processed or optimized, ordered by id.
+    ///
+    /// Items that are currently being processed are not included.
+    pub fn queued(&self) -> Vec<QueuedItem<T>> {
+        let ready = self.ready.lock().expect("mutex is poisoned");
+        let optimizer_queue = self.optimizer_queue.lock().expect("mutex is poisoned");
+
+        let mut items =
+            ready
+                .iter()
+                .map(|(id, item)| (None, id, item))
+                .chain(optimizer_queue.iter().flat_map(|(tag, queue)| {
+                    queue.iter().map(move |(id, item)| (Some(tag), id, item))
+                }))
+                .map(|(tag, id, item)| QueuedItem {
+                    id: *id,
+                    parents: item.parents.clone(),
+                    correlation_id: item.correlation_id(*id),
+                    tag: tag.cloned(),
+                    op: item.op.clone(),
+                    created_at: item.created_at,
+                })
+                .collect::<Vec<_>>();
+
+        items.sort_unstable_by_key(|item| item.id);
+
+        items
+    }
+
+    /// Insert `item` into `queue`, unless an identical op is already in flight.
+    fn insert(
+        &self,
+        queue: &mut BTreeMap<u32, Item<T>>,
+        idempotency_keys: &mut HashSet<String>,
+        item: Item<T>,
+    ) {
+        if let Some(idempotency_key) = T::idempotency_key(&item.op) {
+            if !idempotency_keys.insert(idempotency_key.clone()) {
+                debug!(%idempotency_key, "identical op is already in flight, skipping");
+                return;
+            }
+        }
+
+        queue.insert(self.idx.fetch_add(1, Ordering::SeqCst), item);
+    }
 }
 
 #[derive(DebugNoBound, CloneNoBound)]
 pub(crate) struct Item<T: QueueMessage> {
-    #[allow(dead_code)] // used in debug
-    parents: Vec<u32>,
-    op: Op<T>,
+    pub(crate) parents: Vec<u32>,
+    /// The correlation id of the tree this item belongs to, or `None` if this item is the root of
+    /// its tree (i.e. it was enqueued directly), in which case its own id is used.
+    pub(crate) correlation_id: Option<u32>,
+    pub(crate) op: Op<T>,
+    /// The unix timestamp (in seconds) at which the item was enqueued.
+    pub(crate) created_at: u64,
+}
+
+impl<T: QueueMessage> Item<T> {
+    pub(crate) fn new(parents: Vec<u32>, correlation_id: Option<u32>, op: Op<T>) -> Self {
+        Self {
+            parents,
+            correlation_id,
+            op,
+            created_at: now(),
+        }
+    }
+
+    /// The correlation id of this item, given its `id`.
+    pub(crate) fn correlation_id(&self, id: u32) -> u32 {
+        self.correlation_id.unwrap_or(id)
+    }
 }
 
 impl<T: QueueMessage> Queue<T> for InMemoryQueue<T> {
     type Error = std::convert::Infallible;
-    type Config = ();
+    type Config = InMemoryQueueConfig;
 
-    fn new(_cfg: Self::Config) -> impl Future<Output = Result<Self, Self::Error>> {
+    fn new(cfg: Self::Config) -> impl Future<Output = Result<Self, Self::Error>> {
         futures::future::ok(Self {
             idx: Arc::new(AtomicU32::default()),
             done: Arc::new(Mutex::new(BTreeMap::default())),
+            failed: Arc::new(Mutex::new(BTreeMap::default())),
             ready: Arc::new(Mutex::new(BTreeMap::default())),
             optimizer_queue: Arc::new(Mutex::new(BTreeMap::default())),
+            scheduler: Arc::new(Mutex::new(Scheduler::new(cfg.max_in_flight_per_key))),
+            idempotency_keys: Arc::new(Mutex::new(HashSet::default())),
         })
     }
 
@@ -55,27 +335,18 @@ impl<T: QueueMessage> Queue<T> for InMemoryQueue<T> {
 
         let mut optimizer_queue = self.optimizer_queue.lock().expect("mutex is poisoned");
         let mut ready = self.ready.lock().expect("mutex is poisoned");
+        let mut idempotency_keys = self.idempotency_keys.lock().expect("mutex is poisoned");
 
         for op in op.normalize() {
-            match filter.check_interest(&op) {
-                FilterResult::Interest(tag) => {
-                    optimizer_queue.entry(tag.to_owned()).or_default().insert(
-                        self.idx.fetch_add(1, Ordering::SeqCst),
-                        Item {
-                            parents: vec![],
-                            op,
-                        },
-                    );
-                }
-                FilterResult::NoInterest => {
-                    ready.insert(
-                        self.idx.fetch_add(1, Ordering::SeqCst),
-                        Item {
-                            parents: vec![],
-                            op,
-                        },
-                    );
-                }
+            let item = Item::new(vec![], None, op);
+
+            match filter.check_interest(&item.op) {
+                FilterResult::Interest(tag) => self.insert(
+                    optimizer_queue.entry(tag.to_owned()).or_default(),
+                    &mut idempotency_keys,
+                    item,
+                ),
+                FilterResult::NoInterest => self.insert(&mut ready, &mut idempotency_keys, item),
             }
         }
 
@@ -87,6 +358,7 @@ impl<T: QueueMessage> Queue<T> for InMemoryQueue<T> {
     async fn process<'a, F, Fut, R>(
         &'a self,
         filter: &'a T::Filter,
+        paused: &'a [String],
         f: F,
     ) -> Result<Option<R>, Self::Error>
     where
@@ -96,7 +368,15 @@ impl<T: QueueMessage> Queue<T> for InMemoryQueue<T> {
     {
         let op = {
             let mut queue = self.ready.lock().expect("mutex is poisoned");
-            let op = queue.pop_first();
+            let mut scheduler = self.scheduler.lock().expect("mutex is poisoned");
+
+            let op = scheduler
+                .next(&queue, |op| op.is_paused(paused))
+                .map(|(id, key)| {
+                    let item = queue.remove(&id).expect("scheduled item is ready; qed;");
+
+                    (id, item, key)
+                });
 
             drop(queue);
 
@@ -104,8 +384,17 @@ impl<T: QueueMessage> Queue<T> for InMemoryQueue<T> {
         };
 
         match op {
-            Some((id, item)) => {
-                let span = info_span!("processing item", %id);
+            Some((id, item, key)) => {
+                let mut in_flight = InFlight {
+                    scheduler: &self.scheduler,
+                    key,
+                    idempotency_keys: &self.idempotency_keys,
+                    idempotency_key: T::idempotency_key(&item.op),
+                };
+
+                let correlation_id = item.correlation_id(id);
+
+                let span = info_span!("processing item", %id, %correlation_id);
 
                 self.done
                     .lock()
@@ -113,38 +402,49 @@ impl<T: QueueMessage> Queue<T> for InMemoryQueue<T> {
                     .insert(id, item.clone());
 
                 let (r, res) = f(item.op.clone()).instrument(span).await;
+
+                // the item may be processed into an identical op, which must not be deduplicated
+                // against the item itself
+                in_flight.release_idempotency_key();
+
                 match res {
                     Ok(ops) => {
                         let mut optimizer_queue =
                             self.optimizer_queue.lock().expect("mutex is poisoned");
                         let mut ready = self.ready.lock().expect("mutex is poisoned");
+                        let mut idempotency_keys =
+                            self.idempotency_keys.lock().expect("mutex is poisoned");
 
                         for op in ops.into_iter().flat_map(Op::normalize) {
-                            match filter.check_interest(&op) {
-                                FilterResult::Interest(tag) => {
-                                    optimizer_queue.entry(tag.to_owned()).or_default().insert(
-                                        self.idx.fetch_add(1, Ordering::SeqCst),
-                                        Item {
-                                            parents: vec![id],
-                                            op,
-                                        },
-                                    );
-                                }
+                            let item = Item::new(vec![id], Some(correlation_id), op);
+
+                            match filter.check_interest(&item.op) {
+                                FilterResult::Interest(tag) => self.insert(
+                                    optimizer_queue.entry(tag.to_owned()).or_default(),
+                                    &mut idempotency_keys,
+                                    item,
+                                ),
                                 FilterResult::NoInterest => {
-                                    ready.insert(
-                                        self.idx.fetch_add(1, Ordering::SeqCst),
-                                        Item {
-                                            parents: vec![id],
-                                            op,
-                                        },
-                                    );
+                                    self.insert(&mut ready, &mut idempotency_keys, item);
                                 }
                             }
                         }
 
                         Ok(Some(r))
                     }
-                    Err(why) => panic!("{why}"),
+                    Err(why) => {
+                        error!(%id, error = %why, "fatal error while processing item");
+
+                        let mut failed = self.failed.lock().expect("mutex is poisoned");
+
+                        failed.insert(id, (item, why));
+
+                        while failed.len() > MAX_FAILED_ITEMS {
+                            failed.pop_first();
+                        }
+
+                        Ok(Some(r))
+                    }
                 }
             }
             None => {
@@ -178,34 +478,52 @@ impl<T: QueueMessage> Queue<T> for InMemoryQueue<T> {
 
             let (ids, ops): (Vec<_>, Vec<_>) = tagged_optimizer_queue.clone().into_iter().unzip();
 
+            // ops optimized out of multiple items are attributed to the tree of the first one
+            let correlation_id = |parents_idxs: &[usize]| {
+                parents_idxs.first().map(|&i| ops[i].correlation_id(ids[i]))
+            };
+
             let res = optimizer
-                .run_pass(ops.into_iter().map(|item| item.op).collect())
+                .run_pass(ops.iter().map(|item| item.op.clone()).collect())
                 .await
                 .map_err(Either::Right)?;
 
             let mut optimizer_queue = self.optimizer_queue.lock().expect("poisoned");
             let mut ready = self.ready.lock().expect("poisoned");
             let mut done = self.done.lock().expect("poisoned");
+            let mut idempotency_keys = self.idempotency_keys.lock().expect("poisoned");
+
+            // the optimized ops are no longer in flight; the ops they were optimized into are
+            // tracked in their place
+            for item in tagged_optimizer_queue.values() {
+                if let Some(idempotency_key) = T::idempotency_key(&item.op) {
+                    idempotency_keys.remove(&idempotency_key);
+                }
+            }
 
             done.append(&mut tagged_optimizer_queue.clone());
 
             for (parents_idxs, op) in res.ready {
-                ready.insert(
-                    self.idx.fetch_add(1, Ordering::SeqCst),
-                    Item {
-                        parents: parents_idxs.iter().map(|&i| &ids[i]).copied().collect(),
+                self.insert(
+                    &mut ready,
+                    &mut idempotency_keys,
+                    Item::new(
+                        parents_idxs.iter().map(|&i| &ids[i]).copied().collect(),
+                        correlation_id(&parents_idxs),
                         op,
-                    },
+                    ),
                 );
             }
 
             for (parents_idxs, op, tag) in res.optimize_further {
-                optimizer_queue.entry(tag.clone()).or_default().insert(
-                    self.idx.fetch_add(1, Ordering::SeqCst),
-                    Item {
-                        parents: parents_idxs.iter().map(|&i| &ids[i]).copied().collect(),
+                self.insert(
+                    optimizer_queue.entry(tag.clone()).or_default(),
+                    &mut idempotency_keys,
+                    Item::new(
+                        parents_idxs.iter().map(|&i| &ids[i]).copied().collect(),
+                        correlation_id(&parents_idxs),
                         op,
-                    },
+                    ),
                 );
             }
 
@@ -14,18 +14,26 @@ use std::{
 };
 
 use either::Either::{self, Left, Right};
+use futures::{stream, StreamExt};
 use itertools::Itertools;
 use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use tokio::time::sleep;
 use tracing::{debug, error, info, trace, warn};
-use unionlabs::never::Never;
+use unionlabs::{never::Never, ErrorReporter};
 
-use crate::{filter::InterestFilter, pass::Pass};
+use crate::{filter::InterestFilter, metrics::RETRIES_EXHAUSTED_COUNT, pass::Pass};
 
+#[cfg(any(test, feature = "arbitrary"))]
+pub mod arbitrary_impl;
+pub mod codec;
 pub mod engine;
 pub mod filter;
 pub mod in_memory;
+pub mod metrics;
 pub mod pass;
+pub mod record;
+pub mod wire;
 
 #[cfg(test)]
 mod tests;
@@ -50,10 +58,15 @@ pub trait Queue<T: QueueMessage>: Debug + Clone + Send + Sync + Sized + 'static
 
     /// Process the item at the front of the queue, if there is one. New items will be pre-processed by `O` before being reenqueued.
     ///
+    /// Items that are [paused](Op::is_paused) by any of the `paused` keys are skipped, and are
+    /// left in the queue as-is (like items that are not yet [due](Op::due_at)) until they are
+    /// resumed.
+    ///
     /// All items will be enqueued to be optimized, unless marked as ready by `O`.
     fn process<'a, F, Fut, R>(
         &'a self,
         filter: &'a T::Filter,
+        paused: &'a [String],
         f: F,
     ) -> impl Future<Output = Result<Option<R>, Self::Error>> + Send + Captures<'a>
     where
@@ -74,6 +87,7 @@ pub trait Queue<T: QueueMessage>: Debug + Clone + Send + Sync + Sized + 'static
     ::frame_support_procedural::PartialEqNoBound,
     ::serde::Serialize,
     ::serde::Deserialize,
+    ::schemars::JsonSchema,
 )]
 #[serde(
     tag = "@type",
@@ -82,6 +96,9 @@ pub trait Queue<T: QueueMessage>: Debug + Clone + Send + Sync + Sized + 'static
     bound(serialize = "", deserialize = ""),
     deny_unknown_fields
 )]
+#[schemars(
+    bound = "T: ::schemars::JsonSchema, T::Data: ::schemars::JsonSchema, T::Call: ::schemars::JsonSchema, T::Callback: ::schemars::JsonSchema"
+)]
 #[debug(bound())]
 pub enum Op<T: QueueMessage> {
     /// Inert data that will either be used in an [`Op::Promise`] or bubbled up to the top and sent as
@@ -117,17 +134,151 @@ pub enum Op<T: QueueMessage> {
     Promise(Promise<T>),
     /// Handle the contained message, voiding any returned `Data` messages that it returns.
     Void(Box<Self>),
+    /// Handle the contained message, retrying it with exponential backoff if it fails with
+    /// [`QueueError::Retry`].
+    ///
+    /// `attempt` is the amount of consecutive failed attempts to handle `msg`. Once `msg` makes
+    /// progress, the messages it is handled into are no longer wrapped in `Retry` (a top-level
+    /// message that fails again is wrapped anew by the [`engine`]).
+    Retry {
+        attempt: u32,
+        backoff: Backoff,
+        msg: Box<Self>,
+    },
+    /// Handle the contained message with the specified priority. Ready messages with a higher
+    /// priority are processed before those with a lower priority; all other messages have a
+    /// priority of 0.
+    ///
+    /// The priority is retained across all of the messages that `msg` is processed into.
+    WithPriority {
+        priority: u8,
+        msg: Box<Self>,
+    },
     Noop,
 }
 
+/// Exponential backoff configuration for [`Op::Retry`].
+///
+/// The delay before retrying after the `n`th failed attempt is
+/// `min(base_delay * multiplier^n, max_delay)`, plus up to `jitter_percent`% of that delay as
+/// random jitter, to avoid many messages failing against the same endpoint all being retried at
+/// once.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, schemars::JsonSchema)]
+#[cfg_attr(any(test, feature = "arbitrary"), derive(arbitrary::Arbitrary))]
+#[serde(deny_unknown_fields)]
+pub struct Backoff {
+    /// The delay before the first retry, in seconds.
+    pub base_delay: u64,
+    /// The factor the delay is multiplied by after every failed attempt.
+    pub multiplier: u32,
+    /// The maximum delay between retries (before jitter is applied), in seconds.
+    pub max_delay: u64,
+    /// The maximum amount of jitter added to the delay, as a percentage of the delay.
+    pub jitter_percent: u8,
+}
+
+impl Default for Backoff {
+    fn default() -> Self {
+        Self {
+            base_delay: 3,
+            multiplier: 2,
+            max_delay: 5 * 60,
+            jitter_percent: 20,
+        }
+    }
+}
+
+/// Limits on the processing of ops, protecting the queue against malformed or maliciously
+/// constructed ops that would otherwise overflow the stack or be retried forever.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, schemars::JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct Limits {
+    /// The maximum depth that an op can be nested to. Ops nested deeper than this fail with
+    /// [`LimitExceeded::Depth`].
+    #[serde(default = "default_max_depth")]
+    pub max_depth: usize,
+    /// The maximum amount of times an op that fails with a retryable error is re-enqueued. Ops
+    /// that are still failing after this many retries fail with [`LimitExceeded::Retries`].
+    ///
+    /// If not set, retryable errors are retried indefinitely.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_retries: Option<u32>,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            max_depth: default_max_depth(),
+            max_retries: None,
+        }
+    }
+}
+
+#[must_use]
+#[inline]
+pub const fn default_max_depth() -> usize {
+    128
+}
+
+impl Limits {
+    /// Whether an op that has already been retried `retries` times may not be retried again.
+    #[must_use]
+    pub fn retries_exceeded(&self, retries: u32) -> bool {
+        self.max_retries
+            .is_some_and(|max_retries| retries >= max_retries)
+    }
+}
+
+/// The error an op fails with when it exceeds the configured [`Limits`].
+#[derive(Debug, thiserror::Error)]
+pub enum LimitExceeded {
+    #[error("op is nested deeper than the maximum depth of {max_depth}")]
+    Depth { max_depth: usize },
+    #[error("op is still failing after being retried {retries} times")]
+    Retries {
+        retries: u32,
+        #[source]
+        error: QueueError,
+    },
+}
+
+impl Backoff {
+    /// The delay, in seconds, before `attempt` should be retried. Attempts are zero-indexed.
+    #[must_use]
+    pub fn delay(&self, attempt: u32) -> u64 {
+        let delay = u64::from(self.multiplier)
+            .checked_pow(attempt)
+            .and_then(|factor| self.base_delay.checked_mul(factor))
+            .unwrap_or(u64::MAX)
+            .min(self.max_delay);
+
+        let max_jitter = delay.saturating_mul(self.jitter_percent.into()) / 100;
+
+        if max_jitter == 0 {
+            delay
+        } else {
+            // this doesn't need to be cryptographically secure, it only needs to spread retries out
+            let entropy = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |d| u64::from(d.subsec_nanos()));
+
+            delay.saturating_add(entropy % (max_jitter + 1))
+        }
+    }
+}
+
 #[derive(
     ::macros::Debug,
     ::frame_support_procedural::CloneNoBound,
     ::frame_support_procedural::PartialEqNoBound,
     ::serde::Serialize,
     ::serde::Deserialize,
+    ::schemars::JsonSchema,
 )]
 #[serde(bound(serialize = "", deserialize = ""), deny_unknown_fields)]
+#[schemars(
+    bound = "T: ::schemars::JsonSchema, T::Data: ::schemars::JsonSchema, T::Call: ::schemars::JsonSchema, T::Callback: ::schemars::JsonSchema"
+)]
 #[debug(bound())]
 pub struct Promise<T: QueueMessage> {
     /// Messages that are expected to resolve to [`Op::Data`].
@@ -154,7 +305,9 @@ pub trait Visit<T: QueueMessage> {
                 queue.iter_mut().for_each(|op| self.visit_op(op));
                 data.iter_mut().for_each(|data| self.visit_data(data));
             }
-            Op::Void(op) => self.visit_op(op),
+            Op::Void(op) | Op::Retry { msg: op, .. } | Op::WithPriority { msg: op, .. } => {
+                self.visit_op(op);
+            }
         }
     }
 
@@ -178,6 +331,78 @@ pub trait QueueMessage: Sized + 'static {
     type Filter: InterestFilter<Self>;
 
     type Context: Context;
+
+    /// The key used to limit the amount of concurrently processed ops of the same kind (for
+    /// example, all ops relaying between the same pair of chains).
+    ///
+    /// Ops that return `None` are not subject to any concurrency limits. Not all queue
+    /// implementations support concurrency limits; see [`in_memory::InMemoryQueueConfig`].
+    fn concurrency_key(op: &Op<Self>) -> Option<String> {
+        let _ = op;
+        None
+    }
+
+    /// The key used to shard ops between multiple instances processing the same queue, such that
+    /// all ops with the same key are processed by the same instance.
+    ///
+    /// Ops that return `None` inherit the shard key of the op they were produced from (if any).
+    /// Not all queue implementations support sharding.
+    fn shard_key(op: &Op<Self>) -> Option<String> {
+        let _ = op;
+        None
+    }
+
+    /// The key used to deduplicate identical ops: an op is not enqueued if another op with the
+    /// same key is already in the queue or being processed.
+    ///
+    /// By default, ops are not deduplicated. Messages opt in for the ops that are wasteful to
+    /// repeat, typically by returning their [`op_hash`].
+    fn idempotency_key(op: &Op<Self>) -> Option<String> {
+        let _ = op;
+        None
+    }
+
+    /// The version of the serialized representation of [`Op<Self>`], which is persisted alongside
+    /// serialized ops by queues that outlive the process (see [`wire`]).
+    ///
+    /// This must be incremented whenever a change to the message types breaks deserialization of
+    /// previously serialized ops, along with adding a migration from the previous version to
+    /// [`Self::migrate`].
+    const WIRE_VERSION: u32 = 0;
+
+    /// Upgrade an op that was serialized with wire version `from` to wire version `from + 1`.
+    fn migrate(from: u32, op: serde_json::Value) -> Result<serde_json::Value, BoxDynError> {
+        let _ = op;
+        Err(format!("no migration from wire version {from}").into())
+    }
+
+    /// The keys that processing of `op` can be paused by. Queues that persist ops store these
+    /// alongside them, such that paused ops can be skipped without being decoded.
+    fn pause_keys(op: &Op<Self>) -> Vec<String> {
+        let _ = op;
+        vec![]
+    }
+
+    /// The keys that are currently paused. Ops with any of these [pause keys](Self::pause_keys)
+    /// are held in the queue as-is until they are resumed, see [`Queue::process`].
+    fn paused_keys(ctx: &Self::Context) -> Vec<String> {
+        let _ = ctx;
+        vec![]
+    }
+
+    /// Called when `op` fails with a fatal error (or exceeds its retry limit), after which it is
+    /// not processed any further.
+    fn on_failure(ctx: &Self::Context, op: &Op<Self>, error: &str) {
+        let _ = (ctx, op, error);
+    }
+}
+
+/// The hex encoded sha256 hash of the JSON serialization of `op`.
+#[must_use]
+pub fn op_hash<T: QueueMessage>(op: &Op<T>) -> String {
+    hex::encode(Sha256::digest(
+        serde_json::to_vec(op).expect("serialization is infallible; qed;"),
+    ))
 }
 
 pub trait Context: Send + Sync {}
@@ -186,16 +411,39 @@ impl Context for () {}
 
 pub type BoxDynError = Box<dyn Error + Send + Sync + 'static>;
 
+/// The maximum amount of ops in the queue of an [`Op::Promise`] that will be processed
+/// concurrently in a single step.
+pub const PROMISE_QUEUE_CONCURRENCY: usize = 16;
+
 impl<T: QueueMessage> Op<T> {
-    // NOTE: Box is required bc recursion
+    /// Process this op with the default [`Limits`].
     #[allow(clippy::type_complexity)]
     pub fn process<'a>(
         self,
         store: &'a T::Context,
         depth: usize,
+    ) -> Pin<Box<dyn Future<Output = Result<Option<Op<T>>, QueueError>> + Send + 'a>> {
+        self.process_with_limits(store, Limits::default(), depth)
+    }
+
+    // NOTE: Box is required bc recursion
+    #[allow(clippy::type_complexity)]
+    pub fn process_with_limits<'a>(
+        self,
+        store: &'a T::Context,
+        limits: Limits,
+        depth: usize,
     ) -> Pin<Box<dyn Future<Output = Result<Option<Op<T>>, QueueError>> + Send + 'a>> {
         trace!(%depth, "handling message");
 
+        if depth > limits.max_depth {
+            return Box::pin(futures::future::err(QueueError::fatal(
+                LimitExceeded::Depth {
+                    max_depth: limits.max_depth,
+                },
+            )));
+        }
+
         let fut = async move {
             match self {
                 Op::Data(data) => {
@@ -229,7 +477,7 @@ impl<T: QueueMessage> Op<T> {
                 }
                 Op::Seq(mut queue) => match queue.pop_front() {
                     Some(op) => {
-                        let op = op.process(store, depth + 1).await?;
+                        let op = op.process_with_limits(store, limits, depth + 1).await?;
 
                         if let Some(op) = op {
                             queue.push_front(op);
@@ -241,7 +489,7 @@ impl<T: QueueMessage> Op<T> {
                 },
                 Op::Conc(mut queue) => match queue.pop_front() {
                     Some(op) => {
-                        let op = op.process(store, depth + 1).await?;
+                        let op = op.process_with_limits(store, limits, depth + 1).await?;
 
                         if let Some(op) = op {
                             queue.push_back(op);
@@ -252,40 +500,76 @@ impl<T: QueueMessage> Op<T> {
                     None => Ok(None),
                 },
                 Op::Promise(Promise {
-                    mut queue,
+                    queue,
                     mut data,
                     receiver,
                 }) => {
-                    if let Some(op) = queue.pop_front() {
-                        match op {
-                            Op::Data(d) => {
-                                data.push_back(d);
-                            }
-                            op => {
-                                let op = op.process(store, depth + 1).await?;
+                    if queue.is_empty() {
+                        // queue is empty, handle op
+                        receiver.process(store, data).await.map(Some)
+                    } else {
+                        // the ops in the queue of a promise are independent of each other, so they
+                        // can all be progressed at once. the order of the queue is retained.
+                        let results = stream::iter(queue)
+                            .map(|op| async move {
+                                match op {
+                                    Op::Data(d) => (None, Ok(Some(Op::Data(d)))),
+                                    op => (
+                                        Some(op.clone()),
+                                        op.process_with_limits(store, limits, depth + 1).await,
+                                    ),
+                                }
+                            })
+                            .buffered(PROMISE_QUEUE_CONCURRENCY)
+                            .collect::<Vec<_>>()
+                            .await;
+
+                        let mut queue = VecDeque::with_capacity(results.len());
+                        let mut progressed = false;
+                        let mut retry_error = None;
+
+                        for (original, res) in results {
+                            match res {
+                                Ok(op) => {
+                                    progressed = true;
 
-                                if let Some(op) = op {
                                     match op {
-                                        Op::Data(d) => {
-                                            data.push_back(d);
-                                        }
-                                        m => {
-                                            queue.push_back(m);
-                                        }
+                                        Some(Op::Data(d)) => data.push_back(d),
+                                        Some(op) => queue.push_back(op),
+                                        None => {}
                                     }
                                 }
+                                // keep the progress of the other ops, only the failed op is
+                                // processed again
+                                Err(err) if err.is_retryable() => {
+                                    queue.push_back(
+                                        original.expect("data is never processed; qed;"),
+                                    );
+                                    retry_error.get_or_insert(err);
+                                }
+                                Err(err) => return Err(err),
                             }
                         }
 
-                        Ok(Some(promise(queue, data, receiver)))
-                    } else {
-                        // queue is empty, handle op
-                        receiver.process(store, data).await.map(Some)
+                        match retry_error {
+                            // nothing was progressed, return the error such that the retry
+                            // backoff and limits apply
+                            Some(err) if !progressed => Err(err),
+                            Some(err) => {
+                                warn!(
+                                    error = %ErrorReporter(&err),
+                                    "error processing op in promise queue, it will be retried"
+                                );
+
+                                Ok(Some(promise(queue, data, receiver)))
+                            }
+                            None => Ok(Some(promise(queue, data, receiver))),
+                        }
                     }
                 }
                 Op::Void(op) => {
                     // TODO: distribute across seq/conc
-                    Ok(op.process(store, depth + 1).await?.map(|op| match op {
+                    Ok(op.process_with_limits(store, limits, depth + 1).await?.map(|op| match op {
                         Op::Data(data) => {
                             debug!(
                                 data = %serde_json::to_string(&data).expect("serialization is infallible; qed;"),
@@ -296,6 +580,60 @@ impl<T: QueueMessage> Op<T> {
                         op => void(op),
                     }))
                 }
+                Op::Retry {
+                    attempt,
+                    backoff,
+                    msg,
+                } => match (*msg)
+                    .clone()
+                    .process_with_limits(store, limits, depth + 1)
+                    .await
+                {
+                    // progress was made, the failing message is no longer retried
+                    Ok(op) => Ok(op),
+                    Err(err) if err.is_retryable() && limits.retries_exceeded(attempt) => {
+                        RETRIES_EXHAUSTED_COUNT.inc();
+
+                        Err(QueueError::fatal(LimitExceeded::Retries {
+                            retries: attempt,
+                            error: err,
+                        }))
+                    }
+                    Err(err) if err.is_retryable() => {
+                        let delay = match &err {
+                            QueueError::RetryAfter { delay, .. } => *delay,
+                            _ => backoff.delay(attempt),
+                        };
+
+                        warn!(
+                            error = %ErrorReporter(&err),
+                            %attempt,
+                            %delay,
+                            "retryable error, retrying with backoff"
+                        );
+
+                        Ok(Some(seq([
+                            defer(now() + delay),
+                            Op::Retry {
+                                attempt: attempt.saturating_add(1),
+                                backoff,
+                                msg,
+                            },
+                        ])))
+                    }
+                    Err(err) => Err(err),
+                },
+                Op::WithPriority {
+                    priority: level,
+                    msg,
+                } => Ok(msg
+                    .process_with_limits(store, limits, depth + 1)
+                    .await?
+                    .map(|op| match op {
+                        // data is passed through as-is so that it can be used in a promise
+                        Op::Data(data) => Op::Data(data),
+                        op => priority(level, op),
+                    })),
                 Op::Noop => Ok(None),
             }
         };
@@ -377,6 +715,44 @@ impl<T: QueueMessage> Op<T> {
                     receiver,
                 })],
                 Op::Void(op) => vec![Op::Void(op)],
+                Op::Retry {
+                    attempt,
+                    backoff,
+                    msg,
+                } => go(*msg)
+                    .into_iter()
+                    .flat_map(|op| match op {
+                        // split conc such that each message is retried independently once it's
+                        // flattened into multiple top-level messages
+                        Op::Conc(ops) => ops.into_iter().collect(),
+                        op => vec![op],
+                    })
+                    .map(|op| match op {
+                        // data is lifted out as-is so that it can be used in a promise
+                        Op::Data(data) => Op::Data(data),
+                        op => Op::Retry {
+                            attempt,
+                            backoff,
+                            msg: Box::new(op),
+                        },
+                    })
+                    .collect(),
+                Op::WithPriority {
+                    priority: level,
+                    msg,
+                } => go(*msg)
+                    .into_iter()
+                    .flat_map(|op| match op {
+                        // split conc such that each message retains the priority once it's
+                        // flattened into multiple top-level messages
+                        Op::Conc(ops) => ops.into_iter().collect(),
+                        op => vec![op],
+                    })
+                    .map(|op| match op {
+                        Op::Data(data) => Op::Data(data),
+                        op => priority(level, op),
+                    })
+                    .collect(),
                 Op::Noop => vec![],
             }
         }
@@ -394,12 +770,180 @@ impl<T: QueueMessage> Op<T> {
     }
 }
 
+impl<T: QueueMessage> Op<T> {
+    /// The priority of this message, as set by [`Op::WithPriority`]. For wrapper messages and
+    /// sequences, this is the priority of the message that will be handled next.
+    #[must_use]
+    pub fn priority(&self) -> u8 {
+        match self {
+            Op::WithPriority { priority, .. } => *priority,
+            Op::Seq(ops) => ops.front().map_or(0, Op::priority),
+            Op::Conc(ops) => ops.iter().map(Op::priority).max().unwrap_or(0),
+            Op::Void(op) | Op::Retry { msg: op, .. } => op.priority(),
+            Op::Data(_) | Op::Call(_) | Op::Defer { .. } | Op::Promise(_) | Op::Noop => 0,
+        }
+    }
+
+    /// Whether any of the [pause keys](QueueMessage::pause_keys) of this message are in `paused`.
+    #[must_use]
+    pub fn is_paused(&self, paused: &[String]) -> bool {
+        !paused.is_empty() && T::pause_keys(self).iter().any(|key| paused.contains(key))
+    }
+
+    /// The unix timestamp (in seconds) before which handling this message can make no progress,
+    /// i.e. when the [`Op::Defer`] that would be handled next elapses. This is `None` if the
+    /// message can be handled immediately.
+    ///
+    /// Queues hold back messages until they are due, rather than repeatedly handling and
+    /// requeueing them while they are deferred.
+    #[must_use]
+    pub fn due_at(&self) -> Option<u64> {
+        match self {
+            Op::Defer { until } => Some(*until),
+            Op::Seq(ops) => ops.front().and_then(Op::due_at),
+            // progress can be made as soon as any of the ops is due
+            Op::Conc(ops) | Op::Promise(Promise { queue: ops, .. }) => {
+                ops.iter().map(Op::due_at).min().flatten()
+            }
+            Op::Void(op) | Op::Retry { msg: op, .. } | Op::WithPriority { msg: op, .. } => {
+                op.due_at()
+            }
+            Op::Data(_) | Op::Call(_) | Op::Noop => None,
+        }
+    }
+
+    /// Whether handling this message at `now` can make progress; see [`Op::due_at`].
+    #[must_use]
+    pub fn is_due(&self, now: u64) -> bool {
+        self.due_at().is_none_or(|due_at| due_at <= now)
+    }
+}
+
+impl<T: QueueMessage> Op<T> {
+    /// A structural view of this message, for inspecting where it currently is in its execution.
+    #[must_use]
+    pub fn tree(&self) -> OpTree {
+        match self {
+            Op::Data(data) => OpTree::leaf(OpKind::Data, type_label(data)),
+            Op::Call(call) => OpTree::leaf(OpKind::Call, type_label(call)),
+            Op::Defer { until } => OpTree::leaf(OpKind::Defer, Some(format!("until {until}"))),
+            Op::Seq(ops) => OpTree::node(OpKind::Seq, None, ops.iter().map(Op::tree).collect()),
+            Op::Conc(ops) => OpTree::node(OpKind::Conc, None, ops.iter().map(Op::tree).collect()),
+            Op::Promise(Promise {
+                queue,
+                data,
+                receiver,
+            }) => OpTree::node(
+                OpKind::Promise,
+                type_label(receiver),
+                queue
+                    .iter()
+                    .map(Op::tree)
+                    .chain(
+                        data.iter()
+                            .map(|data| OpTree::leaf(OpKind::Data, type_label(data))),
+                    )
+                    .collect(),
+            ),
+            Op::Void(op) => OpTree::node(OpKind::Void, None, vec![op.tree()]),
+            Op::Retry { attempt, msg, .. } => OpTree::node(
+                OpKind::Retry,
+                Some(format!("attempt {attempt}")),
+                vec![msg.tree()],
+            ),
+            Op::WithPriority { priority, msg } => OpTree::node(
+                OpKind::WithPriority,
+                Some(format!("priority {priority}")),
+                vec![msg.tree()],
+            ),
+            Op::Noop => OpTree::leaf(OpKind::Noop, None),
+        }
+    }
+}
+
+/// The `@type` of the JSON serialization of `t`, if it has one.
+fn type_label(t: &impl Serialize) -> Option<String> {
+    match serde_json::to_value(t) {
+        Ok(serde_json::Value::Object(mut map)) => match map.remove("@type") {
+            Some(serde_json::Value::String(ty)) => Some(ty),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// A structural view of an [`Op`]. Unlike the serialization of the op itself, this omits the
+/// contents of the contained messages, and is intended to be consumed by dashboards and other
+/// tooling.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct OpTree {
+    pub kind: OpKind,
+    /// A short description of the node, i.e. the `@type` of a call or the attempt of a retry.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub children: Vec<OpTree>,
+}
+
+impl OpTree {
+    fn leaf(kind: OpKind, label: Option<String>) -> Self {
+        Self::node(kind, label, vec![])
+    }
+
+    fn node(kind: OpKind, label: Option<String>, children: Vec<Self>) -> Self {
+        Self {
+            kind,
+            label,
+            children,
+        }
+    }
+
+    /// The amount of nodes on the longest path from this node to a leaf, including both.
+    #[must_use]
+    pub fn depth(&self) -> usize {
+        1 + self.children.iter().map(OpTree::depth).max().unwrap_or(0)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OpKind {
+    Data,
+    Call,
+    Defer,
+    Seq,
+    Conc,
+    Promise,
+    Void,
+    Retry,
+    WithPriority,
+    Noop,
+}
+
+/// Errors that can occur while handling an [`Op`].
+///
+/// The variant determines how the engine treats the failed message:
+///
+/// - [`QueueError::Fatal`]: The message will never succeed (i.e. invalid configuration or a bug
+///   in a plugin) and is removed from the queue, to be inspected manually.
+/// - [`QueueError::Retry`]: The failure is transient (i.e. an RPC hiccup) and the message is
+///   retried with exponential backoff, see [`Op::Retry`].
+/// - [`QueueError::RetryAfter`]: The failure is transient, and the message should not be retried
+///   before the specified delay has passed (i.e. the RPC is rate limiting requests).
 #[derive(Debug, thiserror::Error)]
 pub enum QueueError {
     #[error("fatal error while handling message")]
     Fatal(#[source] BoxDynError),
     #[error("error while handling message")]
     Retry(#[source] BoxDynError),
+    #[error("error while handling message, retrying in {delay} seconds")]
+    RetryAfter {
+        /// The delay before the message can be retried, in seconds.
+        delay: u64,
+        #[source]
+        error: BoxDynError,
+    },
 }
 
 impl QueueError {
@@ -410,6 +954,19 @@ impl QueueError {
     pub fn retry(e: impl std::error::Error + Send + Sync + 'static) -> Self {
         Self::Retry(Box::new(e))
     }
+
+    pub fn retry_after(delay: u64, e: impl std::error::Error + Send + Sync + 'static) -> Self {
+        Self::RetryAfter {
+            delay,
+            error: Box::new(e),
+        }
+    }
+
+    /// Whether or not the message that caused this error can be retried.
+    #[must_use]
+    pub fn is_retryable(&self) -> bool {
+        !matches!(self, Self::Fatal(_))
+    }
 }
 
 pub trait CallT<T: QueueMessage> {
@@ -500,6 +1057,27 @@ pub fn void<T: QueueMessage>(t: impl Into<Op<T>>) -> Op<T> {
     Op::Void(Box::new(t.into()))
 }
 
+/// Convenience constructor for [`Op::Retry`]
+#[inline]
+#[must_use = "constructing an instruction has no effect"]
+pub fn retry<T: QueueMessage>(backoff: Backoff, t: impl Into<Op<T>>) -> Op<T> {
+    Op::Retry {
+        attempt: 0,
+        backoff,
+        msg: Box::new(t.into()),
+    }
+}
+
+/// Convenience constructor for [`Op::WithPriority`]
+#[inline]
+#[must_use = "constructing an instruction has no effect"]
+pub fn priority<T: QueueMessage>(level: u8, t: impl Into<Op<T>>) -> Op<T> {
+    Op::WithPriority {
+        priority: level,
+        msg: Box::new(t.into()),
+    }
+}
+
 #[inline]
 #[must_use = "constructing an instruction has no effect"]
 pub fn noop<T: QueueMessage>() -> Op<T> {
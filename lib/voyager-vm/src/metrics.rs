@@ -0,0 +1,11 @@
+use std::sync::LazyLock;
+
+use prometheus::{register_int_counter, IntCounter};
+
+pub static RETRIES_EXHAUSTED_COUNT: LazyLock<IntCounter> = LazyLock::new(|| {
+    register_int_counter!(
+        "voyager_vm_retries_exhausted_total",
+        "The amount of ops that failed due to still failing after being retried the maximum amount of times.",
+    )
+    .unwrap()
+});
@@ -0,0 +1,168 @@
+//! Recording of every op handled by the [`Engine`](crate::engine::Engine), and replay of the
+//! recorded ops.
+//!
+//! The recording is an append-only log of JSON lines, each containing an op exactly as it was
+//! picked up from the queue along with the [`Outcome`] of handling it. Replaying a recording
+//! handles every recorded op again against the provided context (for example, one backed by mock
+//! chains) and reports the ops whose outcome differs from the recorded one, which allows for
+//! reproducing and bisecting bugs without access to the chains the recording was made against.
+//!
+//! Note that ops that depend on the current time (such as [`Op::Defer`]) are handled against the
+//! time of the replay, not the time of the recording.
+
+use std::{
+    fs::{File, OpenOptions},
+    io::{self, BufRead, BufReader, Write},
+    path::Path,
+    sync::{Arc, Mutex},
+};
+
+use frame_support_procedural::{CloneNoBound, DebugNoBound, PartialEqNoBound};
+use serde::{Deserialize, Serialize};
+use unionlabs::ErrorReporter;
+
+use crate::{Limits, Op, QueueError, QueueMessage};
+
+/// A single handled op.
+#[derive(DebugNoBound, CloneNoBound, PartialEqNoBound, Serialize, Deserialize)]
+#[serde(bound(serialize = "", deserialize = ""), deny_unknown_fields)]
+pub struct Record<T: QueueMessage> {
+    pub op: Op<T>,
+    pub outcome: Outcome<T>,
+}
+
+/// The outcome of handling an op, before any retry handling is applied.
+#[derive(DebugNoBound, CloneNoBound, PartialEqNoBound, Serialize, Deserialize)]
+#[serde(
+    tag = "@type",
+    content = "@value",
+    rename_all = "snake_case",
+    bound(serialize = "", deserialize = ""),
+    deny_unknown_fields
+)]
+pub enum Outcome<T: QueueMessage> {
+    /// The op was handled into the contained op, or into nothing if it has been fully handled.
+    Ok(Option<Op<T>>),
+    /// The op failed with a fatal error.
+    Fatal(String),
+    /// The op failed with a retryable error.
+    Retry(String),
+}
+
+impl<T: QueueMessage> Outcome<T> {
+    #[must_use]
+    pub fn new(res: &Result<Option<Op<T>>, QueueError>) -> Self {
+        match res {
+            Ok(op) => Self::Ok(op.clone()),
+            Err(err @ QueueError::Fatal(_)) => Self::Fatal(ErrorReporter(err).to_string()),
+            Err(err @ (QueueError::Retry(_) | QueueError::RetryAfter { .. })) => {
+                Self::Retry(ErrorReporter(err).to_string())
+            }
+        }
+    }
+}
+
+/// Appends [`Record`]s to a log file. Cloning a recorder shares the underlying file, such that
+/// multiple engines can record to the same log.
+#[derive(Debug, Clone)]
+pub struct Recorder {
+    file: Arc<Mutex<File>>,
+}
+
+impl Recorder {
+    /// Open the log at `path`, creating it if it doesn't exist yet. Records are appended to any
+    /// existing records in the log.
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        Ok(Self {
+            file: Arc::new(Mutex::new(
+                OpenOptions::new().create(true).append(true).open(path)?,
+            )),
+        })
+    }
+
+    pub fn record<T: QueueMessage>(
+        &self,
+        op: &Op<T>,
+        res: &Result<Option<Op<T>>, QueueError>,
+    ) -> io::Result<()> {
+        let mut line = serde_json::to_vec(&Record {
+            op: op.clone(),
+            outcome: Outcome::new(res),
+        })
+        .expect("serialization is infallible; qed;");
+
+        line.push(b'\n');
+
+        // records are written in a single write such that concurrent engines can't interleave them
+        self.file
+            .lock()
+            .expect("mutex is poisoned")
+            .write_all(&line)
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ReadLogError {
+    #[error("error reading log")]
+    Io(#[from] io::Error),
+    #[error("invalid record on line {line}")]
+    InvalidRecord {
+        line: usize,
+        #[source]
+        error: serde_json::Error,
+    },
+}
+
+/// Read all of the records in the log at `path`, in the order they were recorded.
+pub fn read_log<T: QueueMessage>(path: impl AsRef<Path>) -> Result<Vec<Record<T>>, ReadLogError> {
+    BufReader::new(File::open(path)?)
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| line.as_ref().map_or(true, |line| !line.trim().is_empty()))
+        .map(|(idx, line)| {
+            serde_json::from_str(&line?).map_err(|error| ReadLogError::InvalidRecord {
+                line: idx + 1,
+                error,
+            })
+        })
+        .collect()
+}
+
+/// A replayed op whose outcome differs from the recorded outcome.
+#[derive(DebugNoBound, CloneNoBound, PartialEqNoBound, Serialize)]
+#[serde(bound(serialize = ""))]
+pub struct Divergence<T: QueueMessage> {
+    /// The index of the record in the log.
+    pub index: usize,
+    pub op: Op<T>,
+    pub recorded: Outcome<T>,
+    pub replayed: Outcome<T>,
+}
+
+/// Replay `records` against `store`, returning all of the records whose outcome diverged from the
+/// recorded outcome.
+///
+/// Records are replayed sequentially in the order they were recorded, regardless of how many
+/// engines they were originally handled by.
+pub async fn replay<T: QueueMessage>(
+    records: impl IntoIterator<Item = Record<T>>,
+    store: &T::Context,
+    limits: Limits,
+) -> Vec<Divergence<T>> {
+    let mut divergences = vec![];
+
+    for (index, Record { op, outcome }) in records.into_iter().enumerate() {
+        let replayed = Outcome::new(&op.clone().process_with_limits(store, limits, 0).await);
+
+        if replayed != outcome {
+            divergences.push(Divergence {
+                index,
+                op,
+                recorded: outcome,
+                replayed,
+            });
+        }
+    }
+
+    divergences
+}
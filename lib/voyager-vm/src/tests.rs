@@ -1,9 +1,23 @@
+use std::{collections::BTreeMap, num::NonZeroUsize};
+
+use arbitrary::{Arbitrary, Unstructured};
 use macros::model;
+use proptest::{prelude::any, prop_assert_eq, proptest};
 
 use crate::{
-    call, conc, data, defer, noop, now, promise, seq,
-    tests::utils::{BuildPrintAbc, DataA, DataB, DataC, FetchA, FetchB, PrintAbc, SimpleMessage},
-    CallT, CallbackT, Op, QueueError, QueueMessage, VecDeque,
+    call,
+    codec::{Cbor, Codec},
+    conc, data, defer,
+    in_memory::{InMemoryQueue, InMemoryQueueConfig, Item, Scheduler},
+    noop, now, op_hash, priority, promise,
+    record::{read_log, replay, Outcome, Record, Recorder},
+    retry, seq,
+    tests::utils::{
+        BuildPrintAbc, DataA, DataB, DataC, FetchA, FetchB, FetchC, PrintAbc, SimpleMessage,
+    },
+    wire::{self, DecodeError},
+    Backoff, BoxDynError, CallT, CallbackT, LimitExceeded, Limits, Op, Queue, QueueError,
+    QueueMessage, VecDeque,
 };
 
 pub mod utils;
@@ -283,3 +297,632 @@ fn seq_conc_conc() {
 
     assert_eq!(op.normalize(), expected_output);
 }
+
+#[tokio::test]
+async fn promise_queue_is_processed_in_a_single_step() {
+    let op = promise::<SimpleMessage>(
+        [
+            call(FetchA {}),
+            seq([call(FetchB {}), call(FetchC {})]),
+            data(DataC {}),
+        ],
+        [],
+        BuildPrintAbc {},
+    );
+
+    assert_eq!(
+        op.process(&(), 0).await.unwrap(),
+        Some(promise(
+            [seq([data(DataB {}), call(FetchC {})])],
+            [DataA {}.into(), DataC {}.into()],
+            BuildPrintAbc {},
+        ))
+    );
+}
+
+/// Calls with `true` succeed with data, calls with `false` fail with a retryable error.
+enum FallibleMessage {}
+
+impl QueueMessage for FallibleMessage {
+    type Data = ();
+    type Call = bool;
+    type Callback = ();
+
+    type Filter = ();
+
+    type Context = ();
+}
+
+impl CallT<FallibleMessage> for bool {
+    async fn process(self, (): &()) -> Result<Op<FallibleMessage>, QueueError> {
+        if self {
+            Ok(data(()))
+        } else {
+            Err(QueueError::Retry("call failed".into()))
+        }
+    }
+}
+
+impl CallbackT<FallibleMessage> for () {
+    async fn process(self, (): &(), _: VecDeque<()>) -> Result<Op<FallibleMessage>, QueueError> {
+        Ok(noop())
+    }
+}
+
+#[tokio::test]
+async fn promise_queue_keeps_progress_of_ops_when_one_fails() {
+    let op = promise::<FallibleMessage>([call(true), call(false), call(true)], [], ());
+
+    // the failed op is kept in the queue, the data of the others is not lost
+    assert_eq!(
+        op.process(&(), 0).await.unwrap(),
+        Some(promise([call(false)], [(), ()], ()))
+    );
+
+    // no progress was made, the error is returned
+    let err = promise::<FallibleMessage>([call(false)], [], ())
+        .process(&(), 0)
+        .await
+        .unwrap_err();
+
+    assert!(err.is_retryable());
+}
+
+#[test]
+fn backoff_delay_is_exponential_and_capped() {
+    let backoff = Backoff {
+        base_delay: 1,
+        multiplier: 2,
+        max_delay: 10,
+        jitter_percent: 0,
+    };
+
+    assert_eq!(
+        (0..6)
+            .map(|attempt| backoff.delay(attempt))
+            .collect::<Vec<_>>(),
+        [1, 2, 4, 8, 10, 10]
+    );
+
+    // overflow saturates to the max delay
+    assert_eq!(backoff.delay(u32::MAX), 10);
+}
+
+#[test]
+fn backoff_jitter_is_bounded() {
+    let backoff = Backoff {
+        base_delay: 100,
+        multiplier: 2,
+        max_delay: 1000,
+        jitter_percent: 10,
+    };
+
+    for _ in 0..100 {
+        assert!((100..=110).contains(&backoff.delay(0)));
+        assert!((1000..=1100).contains(&backoff.delay(10)));
+    }
+}
+
+#[tokio::test]
+async fn retry_passes_through_data() {
+    let op = retry::<SimpleMessage>(Backoff::default(), call(FetchA {}));
+
+    assert_eq!(op.process(&(), 0).await.unwrap(), Some(data(DataA {})));
+
+    let op = retry::<SimpleMessage>(Backoff::default(), seq([call(FetchA {}), call(FetchB {})]));
+
+    // the wrapper is dropped once the message makes progress
+    assert_eq!(
+        op.process(&(), 0).await.unwrap(),
+        Some(seq([data(DataA {}), call(FetchB {})]))
+    );
+
+    // data and conc are lifted out of the wrapper, such that the data surfaces to the top level
+    let op = retry::<SimpleMessage>(
+        Backoff::default(),
+        conc([data(DataA {}), call(FetchB {}), call(FetchC {})]),
+    );
+
+    assert_eq!(
+        op.normalize(),
+        vec![
+            data(DataA {}),
+            retry(Backoff::default(), call(FetchB {})),
+            retry(Backoff::default(), call(FetchC {})),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn max_depth_is_enforced() {
+    let limits = Limits {
+        max_depth: 2,
+        max_retries: None,
+    };
+
+    let op = seq::<SimpleMessage>([seq([call(FetchA {}), call(FetchB {})]), call(FetchC {})]);
+
+    assert_eq!(
+        op.process_with_limits(&(), limits, 0).await.unwrap(),
+        Some(seq([
+            seq([data(DataA {}), call(FetchB {})]),
+            call(FetchC {})
+        ]))
+    );
+
+    let op = seq::<SimpleMessage>([seq([seq([call(FetchA {})])])]);
+
+    let err = op.process_with_limits(&(), limits, 0).await.unwrap_err();
+
+    assert!(!err.is_retryable());
+    assert!(matches!(
+        err,
+        QueueError::Fatal(err)
+            if matches!(
+                err.downcast_ref::<LimitExceeded>(),
+                Some(LimitExceeded::Depth { max_depth: 2 })
+            )
+    ));
+}
+
+#[test]
+fn retries_exceeded() {
+    assert!(!Limits::default().retries_exceeded(u32::MAX));
+
+    let limits = Limits {
+        max_retries: Some(3),
+        ..Default::default()
+    };
+
+    assert!(!limits.retries_exceeded(2));
+    assert!(limits.retries_exceeded(3));
+}
+
+/// Deduplicates all calls, for testing [`QueueMessage::idempotency_key`].
+enum DedupMessage {}
+
+impl QueueMessage for DedupMessage {
+    type Data = ();
+    type Call = ();
+    type Callback = ();
+
+    type Filter = ();
+
+    type Context = ();
+
+    fn idempotency_key(op: &Op<Self>) -> Option<String> {
+        matches!(op, Op::Call(_)).then(|| op_hash(op))
+    }
+}
+
+impl CallT<DedupMessage> for () {
+    async fn process(self, (): &()) -> Result<Op<DedupMessage>, QueueError> {
+        Ok(noop())
+    }
+}
+
+impl CallbackT<DedupMessage> for () {
+    async fn process(self, (): &(), _: VecDeque<()>) -> Result<Op<DedupMessage>, QueueError> {
+        Ok(noop())
+    }
+}
+
+/// Keyed by the timestamp of the top-level defer op, for testing the scheduler.
+enum KeyedMessage {}
+
+impl QueueMessage for KeyedMessage {
+    type Data = ();
+    type Call = ();
+    type Callback = ();
+
+    type Filter = ();
+
+    type Context = ();
+
+    fn concurrency_key(op: &Op<Self>) -> Option<String> {
+        match op {
+            Op::Defer { until } => Some(until.to_string()),
+            _ => None,
+        }
+    }
+}
+
+impl CallT<KeyedMessage> for () {
+    async fn process(self, (): &()) -> Result<Op<KeyedMessage>, QueueError> {
+        Ok(noop())
+    }
+}
+
+impl CallbackT<KeyedMessage> for () {
+    async fn process(self, (): &(), _: VecDeque<()>) -> Result<Op<KeyedMessage>, QueueError> {
+        Ok(noop())
+    }
+}
+
+#[test]
+fn scheduler_is_fair_between_keys() {
+    let ready = (0..)
+        .zip([defer(1), defer(1), defer(1), defer(2), noop()])
+        .map(|(id, op)| (id, Item::<KeyedMessage>::new(vec![], None, op)))
+        .collect::<BTreeMap<_, _>>();
+
+    let mut scheduler = Scheduler::new(Some(NonZeroUsize::MIN));
+
+    let mut next = |ready: &mut BTreeMap<u32, Item<KeyedMessage>>| {
+        let (id, key) = scheduler.next(ready, |_| false)?;
+        ready.remove(&id);
+        Some((id, key))
+    };
+
+    let mut ready_1 = ready.clone();
+
+    // round-robin between the keys, with unkeyed ops scheduled once the round wraps around
+    assert_eq!(next(&mut ready_1), Some((0, Some("1".to_owned()))));
+    assert_eq!(next(&mut ready_1), Some((3, Some("2".to_owned()))));
+    assert_eq!(next(&mut ready_1), Some((4, None)));
+
+    // both keys are at the limit
+    assert_eq!(next(&mut ready_1), None);
+
+    drop(next);
+    scheduler.done("1");
+
+    assert_eq!(
+        scheduler.next(&ready_1, |_| false),
+        Some((1, Some("1".to_owned())))
+    );
+
+    // without a limit, ops are scheduled in order
+    assert_eq!(
+        Scheduler::new(None).next(&ready, |_| false),
+        Some((0, None))
+    );
+}
+
+#[test]
+fn scheduler_skips_paused_items() {
+    let ready = (0..)
+        .zip([defer(1), defer(2), noop()])
+        .map(|(id, op)| (id, Item::<KeyedMessage>::new(vec![], None, op)))
+        .collect::<BTreeMap<_, _>>();
+
+    let is_paused = |op: &Op<KeyedMessage>| matches!(op, Op::Defer { until: 1 });
+
+    assert_eq!(
+        Scheduler::new(None).next(&ready, is_paused),
+        Some((1, None))
+    );
+    assert_eq!(
+        Scheduler::new(Some(NonZeroUsize::MIN)).next(&ready, is_paused),
+        Some((1, Some("2".to_owned())))
+    );
+}
+
+#[test]
+fn priority_is_propagated_through_normalize() {
+    let op: Op<SimpleMessage> = priority(
+        2,
+        conc([
+            call(FetchA {}),
+            data(DataA {}),
+            seq([call(FetchB {}), call(FetchC {})]),
+        ]),
+    );
+
+    assert_eq!(
+        op.normalize(),
+        vec![
+            data(DataA {}),
+            priority(2, call(FetchA {})),
+            priority(2, seq([call(FetchB {}), call(FetchC {})])),
+        ]
+    );
+
+    assert_eq!(
+        conc([priority(1, noop()), priority(3, noop())]).priority(),
+        3
+    );
+    assert_eq!(
+        seq::<SimpleMessage>([noop(), priority(3, noop())]).priority(),
+        0
+    );
+}
+
+#[test]
+fn scheduler_prefers_higher_priority() {
+    let ready = (0..)
+        .zip([defer(1), priority(1, noop()), defer(2), priority(1, noop())])
+        .map(|(id, op)| (id, Item::<KeyedMessage>::new(vec![], None, op)))
+        .collect::<BTreeMap<_, _>>();
+
+    assert_eq!(
+        Scheduler::new(None).next(&ready, |_| false),
+        Some((1, None))
+    );
+    assert_eq!(
+        Scheduler::new(Some(NonZeroUsize::MIN)).next(&ready, |_| false),
+        Some((1, None))
+    );
+}
+
+#[test]
+fn due_at() {
+    assert_eq!(defer::<SimpleMessage>(10).due_at(), Some(10));
+    assert_eq!(
+        retry::<SimpleMessage>(Backoff::default(), seq([defer(10), call(FetchA {})])).due_at(),
+        Some(10)
+    );
+    assert_eq!(
+        conc::<SimpleMessage>([defer(10), defer(5)]).due_at(),
+        Some(5)
+    );
+
+    // any op that can be handled immediately makes the whole op due
+    assert_eq!(
+        conc::<SimpleMessage>([defer(10), call(FetchA {})]).due_at(),
+        None
+    );
+    assert_eq!(
+        seq::<SimpleMessage>([call(FetchA {}), defer(10)]).due_at(),
+        None
+    );
+
+    assert!(defer::<SimpleMessage>(10).is_due(10));
+    assert!(!defer::<SimpleMessage>(10).is_due(9));
+}
+
+#[test]
+fn scheduler_holds_back_deferred_items() {
+    let ready = (0..)
+        .zip([defer(u64::MAX), seq([defer(u64::MAX), noop()]), defer(1)])
+        .map(|(id, op)| (id, Item::<KeyedMessage>::new(vec![], None, op)))
+        .collect::<BTreeMap<_, _>>();
+
+    assert_eq!(
+        Scheduler::new(None).next(&ready, |_| false),
+        Some((2, None))
+    );
+    assert_eq!(
+        Scheduler::new(Some(NonZeroUsize::MIN)).next(&ready, |_| false),
+        Some((2, Some("1".to_owned())))
+    );
+}
+
+#[tokio::test]
+async fn identical_in_flight_ops_are_deduplicated() {
+    let queue = InMemoryQueue::<DedupMessage>::new(InMemoryQueueConfig::default())
+        .await
+        .unwrap();
+
+    queue.enqueue(call(()), &()).await.unwrap();
+    queue.enqueue(call(()), &()).await.unwrap();
+
+    let process = || {
+        queue.process(&(), &[], |op| async move {
+            assert_eq!(op, call(()));
+            ((), Ok(vec![]))
+        })
+    };
+
+    assert_eq!(process().await.unwrap(), Some(()));
+
+    // the duplicate was never enqueued
+    assert_eq!(process().await.unwrap(), None);
+
+    // once processed, the op can be enqueued again
+    queue.enqueue(call(()), &()).await.unwrap();
+    assert_eq!(process().await.unwrap(), Some(()));
+}
+
+#[tokio::test]
+async fn ops_are_not_deduplicated_by_default() {
+    let queue = InMemoryQueue::<SimpleMessage>::new(InMemoryQueueConfig::default())
+        .await
+        .unwrap();
+
+    queue.enqueue(call(FetchA {}), &()).await.unwrap();
+    queue.enqueue(call(FetchA {}), &()).await.unwrap();
+
+    for _ in 0..2 {
+        let processed = queue
+            .process(&(), &[], |op| async move {
+                assert_eq!(op, call(FetchA {}));
+                ((), Ok(vec![]))
+            })
+            .await
+            .unwrap();
+
+        assert_eq!(processed, Some(()));
+    }
+}
+
+#[tokio::test]
+async fn failed_items_can_be_requeued() {
+    let queue = InMemoryQueue::<SimpleMessage>::new(InMemoryQueueConfig::default())
+        .await
+        .unwrap();
+
+    queue.enqueue(call(FetchA {}), &()).await.unwrap();
+
+    queue
+        .process(&(), &[], |_| async { ((), Err("fatal".to_owned())) })
+        .await
+        .unwrap();
+
+    let failed = queue.failed();
+    assert_eq!(failed.len(), 1);
+    assert_eq!(failed[0].op, call(FetchA {}));
+    assert_eq!(failed[0].message, "fatal");
+
+    assert_eq!(queue.take_failed(failed[0].id), Some(call(FetchA {})));
+    assert!(queue.failed().is_empty());
+    assert_eq!(queue.take_failed(failed[0].id), None);
+}
+
+#[test]
+fn op_tree() {
+    let op: Op<SimpleMessage> = seq([
+        defer(1),
+        retry(
+            Backoff::default(),
+            promise(
+                [call(FetchA {}), data(DataB {})],
+                [DataC {}.into()],
+                BuildPrintAbc {},
+            ),
+        ),
+    ]);
+
+    let tree = op.tree();
+
+    assert_eq!(tree.depth(), 4);
+
+    assert_eq!(
+        serde_json::to_value(&tree).unwrap(),
+        serde_json::json!({
+            "kind": "seq",
+            "children": [
+                { "kind": "defer", "label": "until 1" },
+                {
+                    "kind": "retry",
+                    "label": "attempt 0",
+                    "children": [{
+                        "kind": "promise",
+                        "label": "build_print_abc",
+                        "children": [
+                            { "kind": "call", "label": "a" },
+                            { "kind": "data", "label": "b" },
+                            { "kind": "data", "label": "c" },
+                        ]
+                    }]
+                }
+            ]
+        })
+    );
+}
+
+#[tokio::test]
+async fn recorded_ops_are_replayed() {
+    let path = std::env::temp_dir().join(format!("voyager-vm-record-{}.jsonl", std::process::id()));
+
+    let recorder = Recorder::open(&path).unwrap();
+
+    let op = seq::<SimpleMessage>([call(FetchA {}), call(FetchB {})]);
+    let res = op.clone().process(&(), 0).await;
+
+    recorder.record(&op, &res).unwrap();
+    recorder
+        .record(&call(FetchB {}), &Ok(Some(data(DataA {}))))
+        .unwrap();
+
+    let records = read_log::<SimpleMessage>(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!(
+        records[0],
+        Record {
+            op,
+            outcome: Outcome::Ok(Some(seq([data(DataA {}), call(FetchB {})]))),
+        }
+    );
+
+    // only the second record (which was recorded with an incorrect outcome) diverges
+    let divergences = replay(records, &(), Limits::default()).await;
+
+    assert_eq!(divergences.len(), 1);
+    assert_eq!(divergences[0].index, 1);
+    assert_eq!(divergences[0].replayed, Outcome::Ok(Some(data(DataB {}))));
+}
+
+proptest! {
+    #[test]
+    fn op_serde_round_trip(bytes in proptest::collection::vec(any::<u8>(), 0..4096)) {
+        let op = Op::<SimpleMessage>::arbitrary_take_rest(Unstructured::new(&bytes)).unwrap();
+
+        let json = serde_json::to_string(&op).unwrap();
+
+        prop_assert_eq!(serde_json::from_str::<Op<SimpleMessage>>(&json).unwrap(), op.clone());
+
+        let cbor = Cbor::encode(&op).unwrap();
+
+        prop_assert_eq!(Cbor::decode::<Op<SimpleMessage>>(&cbor).unwrap(), op);
+    }
+}
+
+/// [`UnitMessage`], where [`Op::Defer`] used to be serialized as `{"timestamp": ...}`.
+enum VersionedMessage {}
+
+impl QueueMessage for VersionedMessage {
+    type Data = ();
+    type Call = ();
+    type Callback = ();
+
+    type Filter = ();
+
+    type Context = ();
+
+    const WIRE_VERSION: u32 = 1;
+
+    fn migrate(from: u32, mut op: serde_json::Value) -> Result<serde_json::Value, BoxDynError> {
+        fn rename_timestamp(op: &mut serde_json::Value) {
+            match op {
+                serde_json::Value::Object(map) => {
+                    if map.get("@type").is_some_and(|ty| ty == "defer") {
+                        if let Some(value) = map.get_mut("@value").and_then(|v| v.as_object_mut()) {
+                            if let Some(timestamp) = value.remove("timestamp") {
+                                value.insert("until".to_owned(), timestamp);
+                            }
+                        }
+                    }
+
+                    map.values_mut().for_each(rename_timestamp);
+                }
+                serde_json::Value::Array(ops) => ops.iter_mut().for_each(rename_timestamp),
+                _ => {}
+            }
+        }
+
+        assert_eq!(from, 0);
+
+        rename_timestamp(&mut op);
+
+        Ok(op)
+    }
+}
+
+impl CallT<VersionedMessage> for () {
+    async fn process(self, (): &()) -> Result<Op<VersionedMessage>, QueueError> {
+        Ok(noop())
+    }
+}
+
+impl CallbackT<VersionedMessage> for () {
+    async fn process(self, (): &(), _: VecDeque<()>) -> Result<Op<VersionedMessage>, QueueError> {
+        Ok(noop())
+    }
+}
+
+#[test]
+fn old_wire_versions_are_migrated() {
+    let old =
+        r#"{"@type":"seq","@value":[{"@type":"defer","@value":{"timestamp":1}},{"@type":"noop"}]}"#;
+
+    assert_eq!(
+        wire::decode::<VersionedMessage>(0, old).unwrap(),
+        seq([defer(1), noop()])
+    );
+
+    // the current version is not migrated
+    assert!(matches!(
+        wire::decode::<VersionedMessage>(1, old),
+        Err(DecodeError::Deserialize(_))
+    ));
+
+    assert!(matches!(
+        wire::decode::<VersionedMessage>(2, old),
+        Err(DecodeError::UnsupportedVersion {
+            version: 2,
+            latest: 1
+        })
+    ));
+}
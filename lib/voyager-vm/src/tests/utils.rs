@@ -1,5 +1,6 @@
 use std::collections::VecDeque;
 
+use arbitrary::Arbitrary;
 use enumorph::Enumorph;
 use macros::model;
 use subset_of::SubsetOf;
@@ -59,7 +60,7 @@ impl CallbackT<SimpleMessage> for SimpleAggregate {
 }
 
 #[model]
-#[derive(Enumorph, SubsetOf)]
+#[derive(Enumorph, SubsetOf, Arbitrary)]
 pub enum SimpleData {
     A(DataA),
     B(DataB),
@@ -68,18 +69,23 @@ pub enum SimpleData {
     E(DataE),
 }
 #[model]
+#[derive(Arbitrary)]
 pub struct DataA {}
 #[model]
+#[derive(Arbitrary)]
 pub struct DataB {}
 #[model]
+#[derive(Arbitrary)]
 pub struct DataC {}
 #[model]
+#[derive(Arbitrary)]
 pub struct DataD {}
 #[model]
+#[derive(Arbitrary)]
 pub struct DataE {}
 
 #[model]
-#[derive(Enumorph, SubsetOf)]
+#[derive(Enumorph, SubsetOf, Arbitrary)]
 pub enum SimpleCall {
     A(FetchA),
     B(FetchB),
@@ -89,17 +95,23 @@ pub enum SimpleCall {
     PrintAbc(PrintAbc),
 }
 #[model]
+#[derive(Arbitrary)]
 pub struct FetchA {}
 #[model]
+#[derive(Arbitrary)]
 pub struct FetchB {}
 #[model]
+#[derive(Arbitrary)]
 pub struct FetchC {}
 #[model]
+#[derive(Arbitrary)]
 pub struct FetchD {}
 #[model]
+#[derive(Arbitrary)]
 pub struct FetchE {}
 
 #[model]
+#[derive(Arbitrary)]
 pub struct PrintAbc {
     pub a: DataA,
     pub b: DataB,
@@ -110,12 +122,13 @@ pub struct PrintAbc {
 pub struct SimpleWait {}
 
 #[model]
-#[derive(Enumorph)]
+#[derive(Enumorph, Arbitrary)]
 pub enum SimpleAggregate {
     BuildPrintAbc(BuildPrintAbc),
 }
 
 #[model]
+#[derive(Arbitrary)]
 pub struct BuildPrintAbc {}
 
 fn find_in_vec<T, U>(v: &mut Vec<T>, mut predicate: impl FnMut(&T) -> Option<U>) -> Option<U> {
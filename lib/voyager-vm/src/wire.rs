@@ -0,0 +1,62 @@
+//! Decoding of serialized ops, upgrading ops that were serialized by an older version of the
+//! message types.
+//!
+//! Queues that persist ops (such as the postgres queue) store the [`QueueMessage::WIRE_VERSION`]
+//! that each op was serialized with. When an op with an older version is loaded, the
+//! [migrations](QueueMessage::migrate) are applied one version at a time until it is at the
+//! current version, before it is deserialized. This allows for upgrading a relayer with a
+//! persistent queue without draining the queue first.
+
+use serde::{de::DeserializeOwned, Deserialize};
+use serde_json::Value;
+
+use crate::{BoxDynError, Op, QueueMessage};
+
+#[derive(Debug, thiserror::Error)]
+pub enum DecodeError {
+    #[error(
+        "op was serialized with wire version {version}, but the latest \
+        supported wire version is {latest}"
+    )]
+    UnsupportedVersion { version: u32, latest: u32 },
+    #[error("error migrating op from wire version {from}")]
+    Migration {
+        from: u32,
+        #[source]
+        error: BoxDynError,
+    },
+    #[error("error deserializing op")]
+    Deserialize(#[from] serde_json::Error),
+}
+
+/// Decode an op that was serialized as JSON with wire version `version`.
+pub fn decode<T: QueueMessage>(version: u32, json: &str) -> Result<Op<T>, DecodeError> {
+    if version == T::WIRE_VERSION {
+        return de(json).map_err(Into::into);
+    }
+
+    migrate::<T>(version, de(json)?).and_then(|op| Op::deserialize(op).map_err(Into::into))
+}
+
+/// Upgrade the serialized `op` from wire version `version` to [`QueueMessage::WIRE_VERSION`].
+pub fn migrate<T: QueueMessage>(version: u32, mut op: Value) -> Result<Value, DecodeError> {
+    if version > T::WIRE_VERSION {
+        return Err(DecodeError::UnsupportedVersion {
+            version,
+            latest: T::WIRE_VERSION,
+        });
+    }
+
+    for from in version..T::WIRE_VERSION {
+        op = T::migrate(from, op).map_err(|error| DecodeError::Migration { from, error })?;
+    }
+
+    Ok(op)
+}
+
+/// Deserialize `json` without a recursion limit, since ops can be arbitrarily deeply nested.
+fn de<T: DeserializeOwned>(json: &str) -> Result<T, serde_json::Error> {
+    let mut deserializer = serde_json::Deserializer::from_str(json);
+    deserializer.disable_recursion_limit();
+    T::deserialize(&mut deserializer)
+}
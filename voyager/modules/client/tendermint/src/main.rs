@@ -11,8 +11,9 @@ use tracing::{debug, instrument};
 use unionlabs::{
     self,
     bytes::Bytes,
-    encoding::{DecodeAs, EncodeAs, Proto},
+    encoding::{Bincode, DecodeAs, EncodeAs, EthAbi, Proto},
     google::protobuf::any::Any,
+    ibc::core::commitment::merkle_proof::MerkleProof,
     ErrorReporter,
 };
 use voyager_message::{
@@ -32,6 +33,7 @@ async fn main() {
 #[serde(try_from = "String", into = "String")]
 pub enum SupportedIbcInterface {
     IbcGoV8Native,
+    IbcCosmwasm,
 }
 
 impl TryFrom<String> for SupportedIbcInterface {
@@ -41,6 +43,7 @@ impl TryFrom<String> for SupportedIbcInterface {
     fn try_from(value: String) -> Result<Self, Self::Error> {
         match &*value {
             IbcInterface::IBC_GO_V8_NATIVE => Ok(SupportedIbcInterface::IbcGoV8Native),
+            IbcInterface::IBC_COSMWASM => Ok(SupportedIbcInterface::IbcCosmwasm),
             _ => Err(format!("unsupported IBC interface: `{value}`")),
         }
     }
@@ -50,6 +53,7 @@ impl SupportedIbcInterface {
     fn as_str(&self) -> &'static str {
         match self {
             SupportedIbcInterface::IbcGoV8Native => IbcInterface::IBC_GO_V8_NATIVE,
+            SupportedIbcInterface::IbcCosmwasm => IbcInterface::IBC_COSMWASM,
         }
     }
 }
@@ -96,6 +100,15 @@ impl Module {
                     })
                     .map(|any| any.0)
             }
+            SupportedIbcInterface::IbcCosmwasm => {
+                ConsensusState::decode_as::<EthAbi>(consensus_state).map_err(|err| {
+                    ErrorObject::owned(
+                        FATAL_JSONRPC_ERROR_CODE,
+                        format!("unable to decode consensus state: {}", ErrorReporter(err)),
+                        None::<()>,
+                    )
+                })
+            }
         }
     }
 
@@ -112,6 +125,14 @@ impl Module {
                     })
                     .map(|any| any.0)
             }
+            SupportedIbcInterface::IbcCosmwasm => ClientState::decode_as::<Bincode>(client_state)
+                .map_err(|err| {
+                    ErrorObject::owned(
+                        FATAL_JSONRPC_ERROR_CODE,
+                        format!("unable to decode client state: {}", ErrorReporter(err)),
+                        None::<()>,
+                    )
+                }),
         }
     }
 }
@@ -187,6 +208,7 @@ impl ClientModuleServer for Module {
             })
             .map(|cs| match self.ibc_interface {
                 SupportedIbcInterface::IbcGoV8Native => Any(cs).encode_as::<Proto>().into(),
+                SupportedIbcInterface::IbcCosmwasm => cs.encode_as::<Bincode>().into(),
             })
     }
 
@@ -209,6 +231,7 @@ impl ClientModuleServer for Module {
             })
             .map(|cs| match self.ibc_interface {
                 SupportedIbcInterface::IbcGoV8Native => Any(cs).encode_as::<Proto>().into(),
+                SupportedIbcInterface::IbcCosmwasm => cs.encode_as::<EthAbi>().into(),
             })
     }
 
@@ -244,6 +267,7 @@ impl ClientModuleServer for Module {
             })
             .map(|header| match self.ibc_interface {
                 SupportedIbcInterface::IbcGoV8Native => Any(header).encode_as::<Proto>().into(),
+                SupportedIbcInterface::IbcCosmwasm => header.encode_as::<Bincode>().into(),
             })
     }
 
@@ -251,7 +275,7 @@ impl ClientModuleServer for Module {
     async fn encode_proof(&self, _: &Extensions, proof: Value) -> RpcResult<Bytes> {
         debug!(%proof, "encoding proof");
 
-        serde_json::from_value::<unionlabs::ibc::core::commitment::merkle_proof::MerkleProof>(proof)
+        serde_json::from_value::<MerkleProof>(proof)
             .map_err(|err| {
                 ErrorObject::owned(
                     FATAL_JSONRPC_ERROR_CODE,
@@ -259,8 +283,9 @@ impl ClientModuleServer for Module {
                     None::<()>,
                 )
             })
-            .map(|cs| match self.ibc_interface {
-                SupportedIbcInterface::IbcGoV8Native => cs.encode_as::<Proto>().into(),
+            .map(|proof| match self.ibc_interface {
+                SupportedIbcInterface::IbcGoV8Native => proof.encode_as::<Proto>().into(),
+                SupportedIbcInterface::IbcCosmwasm => proof.encode_as::<Bincode>().into(),
             })
     }
 }
@@ -0,0 +1,199 @@
+use alloy::{
+    eips::BlockNumberOrTag,
+    providers::{Provider, ProviderBuilder},
+    rpc::types::{Block, BlockTransactionsKind},
+};
+use arbitrum_light_client_types::{ClientState, ConsensusState};
+use arbitrum_rollup::{Rollup, RollupConfig};
+use jsonrpsee::{
+    core::{async_trait, RpcResult},
+    types::ErrorObject,
+    Extensions,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tracing::instrument;
+use unionlabs::{
+    hash::{H160, H256},
+    ibc::core::client::height::Height,
+    id::ClientId,
+    ErrorReporter,
+};
+use voyager_message::{
+    core::{ChainId, ClientType, ConsensusType},
+    into_value,
+    module::{ConsensusModuleInfo, ConsensusModuleServer},
+    ConsensusModule,
+};
+use voyager_vm::BoxDynError;
+
+#[tokio::main(flavor = "multi_thread")]
+async fn main() {
+    Module::run().await
+}
+
+/// The heights of arbitrum are the heights of the l1 it settles on, since the arbitrum client can
+/// only be updated to l1 heights that the l1 client tracking the settlement layer has been updated
+/// to. A height is mapped to the l2 block asserted by the latest node confirmed on the l1 at that
+/// height.
+#[derive(Debug, Clone)]
+pub struct Module {
+    pub chain_id: ChainId,
+
+    pub l1_client_id: u32,
+
+    /// The address of the `IBCHandler` smart contract on the l2.
+    pub ibc_handler_address: H160,
+
+    pub rollup: Rollup,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// The id of the client tracking the l1 on the chain hosting the arbitrum client.
+    pub l1_client_id: u32,
+
+    /// The address of the `IBCHandler` smart contract on the l2.
+    pub ibc_handler_address: H160,
+
+    /// The location of the rollup state on the l1.
+    pub rollup: RollupConfig,
+
+    /// The RPC endpoint for the l1 execution chain.
+    pub l1_rpc_url: String,
+    /// The RPC endpoint for arbitrum.
+    pub l2_rpc_url: String,
+}
+
+impl ConsensusModule for Module {
+    type Config = Config;
+
+    async fn new(config: Self::Config, info: ConsensusModuleInfo) -> Result<Self, BoxDynError> {
+        let l1_provider = ProviderBuilder::new()
+            .on_builtin(&config.l1_rpc_url)
+            .await?;
+
+        let l2_provider = ProviderBuilder::new()
+            .on_builtin(&config.l2_rpc_url)
+            .await?;
+
+        let chain_id = ChainId::new(l2_provider.get_chain_id().await?.to_string());
+
+        info.ensure_chain_id(chain_id.to_string())?;
+        info.ensure_consensus_type(ConsensusType::ARBITRUM)?;
+
+        Ok(Self {
+            chain_id,
+            l1_client_id: config.l1_client_id,
+            ibc_handler_address: config.ibc_handler_address,
+            rollup: Rollup::new(config.rollup, l1_provider, l2_provider),
+        })
+    }
+}
+
+impl Module {
+    async fn l2_block_at(&self, l1_height: u64) -> RpcResult<Block> {
+        self.rollup.l2_block_at(l1_height).await.map_err(|e| {
+            ErrorObject::owned(
+                -1,
+                ErrorReporter(e).with_message(&format!(
+                    "error fetching the l2 block confirmed at l1 height {l1_height}"
+                )),
+                None::<()>,
+            )
+        })
+    }
+}
+
+#[async_trait]
+impl ConsensusModuleServer for Module {
+    /// Query the latest height of the l1. Only confirmed nodes are tracked, so the finality of
+    /// arbitrum is that of the l1.
+    #[instrument(skip_all, fields(chain_id = %self.chain_id, finalized))]
+    async fn query_latest_height(&self, _: &Extensions, finalized: bool) -> RpcResult<Height> {
+        let tag = if finalized {
+            BlockNumberOrTag::Finalized
+        } else {
+            BlockNumberOrTag::Latest
+        };
+
+        self.rollup
+            .l1_provider
+            .get_block(tag.into(), BlockTransactionsKind::Hashes)
+            .await
+            .map_err(|e| {
+                ErrorObject::owned(
+                    -1,
+                    ErrorReporter(e).with_message("error fetching l1 block"),
+                    None::<()>,
+                )
+            })?
+            .map(|block| Height::new(block.header.number))
+            .ok_or_else(|| ErrorObject::owned(-1, format!("l1 block {tag} not found"), None::<()>))
+    }
+
+    /// Query the timestamp of the latest confirmed arbitrum block at the latest height.
+    // TODO: Use a better timestamp type here
+    #[instrument(skip_all, fields(chain_id = %self.chain_id, finalized))]
+    async fn query_latest_timestamp(&self, e: &Extensions, finalized: bool) -> RpcResult<i64> {
+        let height = self.query_latest_height(e, finalized).await?;
+
+        Ok(self
+            .l2_block_at(height.height())
+            .await?
+            .header
+            .timestamp
+            .try_into()
+            .expect("timestamp is in range; qed;"))
+    }
+
+    #[instrument(skip_all, fields(chain_id = %self.chain_id, %height))]
+    async fn self_client_state(&self, _: &Extensions, height: Height) -> RpcResult<Value> {
+        let config = &self.rollup.config;
+
+        Ok(into_value(ClientState {
+            l1_client_id: ClientId::new_static(ClientType::ETHEREUM, self.l1_client_id),
+            chain_id: self
+                .chain_id
+                .as_str()
+                .parse()
+                .expect("self.chain_id is a valid u256"),
+            l1_latest_slot: height.height(),
+            l1_contract_address: config.l1_contract_address,
+            l1_next_node_num_slot: config.l1_next_node_num_slot,
+            l1_nodes_slot: config.l1_nodes_slot,
+            l1_next_node_num_slot_offset_bytes: config.l1_next_node_num_slot_offset_bytes,
+            l1_nodes_confirm_data_offset: config.l1_nodes_confirm_data_offset,
+            frozen_height: Height::new(0),
+            l2_ibc_contract_address: self.ibc_handler_address,
+        }))
+    }
+
+    /// The consensus state on this chain at the specified `Height`.
+    #[instrument(skip_all, fields(chain_id = %self.chain_id, %height))]
+    async fn self_consensus_state(&self, _: &Extensions, height: Height) -> RpcResult<Value> {
+        let block = self.l2_block_at(height.height()).await?;
+
+        let ibc_storage_root = self
+            .rollup
+            .l2_provider
+            .get_proof(self.ibc_handler_address.get().into(), vec![])
+            .block_id(block.header.number.into())
+            .await
+            .map_err(|e| {
+                ErrorObject::owned(
+                    -1,
+                    ErrorReporter(e).with_message("error fetching ibc handler account proof"),
+                    None::<()>,
+                )
+            })?
+            .storage_hash;
+
+        Ok(into_value(ConsensusState {
+            ibc_storage_root: H256::from(ibc_storage_root),
+            // Normalize to nanos in order to be compliant with cosmos
+            timestamp: block.header.timestamp * 1_000_000_000,
+        }))
+    }
+}
@@ -59,6 +59,10 @@ pub struct Config {
     pub eth_rpc_api: String,
     /// The RPC endpoint for the beacon chain.
     pub eth_beacon_rpc_api: String,
+    /// Additional RPC endpoints for the beacon chain, used (in order) if a request to
+    /// [`Self::eth_beacon_rpc_api`] fails.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub eth_beacon_rpc_api_fallbacks: Vec<String>,
 }
 
 impl Module {
@@ -122,7 +126,11 @@ impl ConsensusModule for Module {
         info.ensure_chain_id(chain_id.to_string())?;
         info.ensure_consensus_type(ConsensusType::ETHEREUM)?;
 
-        let beacon_api_client = BeaconApiClient::new(config.eth_beacon_rpc_api).await?;
+        let beacon_api_client = BeaconApiClient::new_with_fallbacks(
+            config.eth_beacon_rpc_api,
+            config.eth_beacon_rpc_api_fallbacks,
+        )
+        .await?;
 
         let spec = beacon_api_client.spec().await.unwrap().data;
 
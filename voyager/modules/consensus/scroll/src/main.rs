@@ -0,0 +1,304 @@
+use alloy::{
+    eips::BlockNumberOrTag,
+    providers::{Provider, ProviderBuilder, RootProvider},
+    rpc::types::BlockTransactionsKind,
+    transports::BoxTransport,
+};
+use jsonrpsee::{
+    core::{async_trait, RpcResult},
+    types::ErrorObject,
+    Extensions,
+};
+use scroll_api::ScrollClient;
+use scroll_light_client_types::{ClientState, ConsensusState};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tracing::{debug, instrument};
+use unionlabs::{
+    hash::{H160, H256},
+    ibc::core::client::height::Height,
+    uint::U256,
+    ErrorReporter,
+};
+use voyager_message::{
+    core::{ChainId, ConsensusType},
+    into_value,
+    module::{ConsensusModuleInfo, ConsensusModuleServer},
+    ConsensusModule,
+};
+use voyager_vm::BoxDynError;
+
+#[tokio::main(flavor = "multi_thread")]
+async fn main() {
+    Module::run().await
+}
+
+/// The heights of scroll are the heights of the l1 it settles on, since the scroll client can
+/// only be updated to l1 heights that the l1 client tracking the settlement layer has been updated
+/// to. A height is mapped to the last block of the latest batch finalized on the l1 at that
+/// height.
+#[derive(Debug, Clone)]
+pub struct Module {
+    pub chain_id: ChainId,
+
+    pub l1_client_id: u32,
+
+    /// The address of the `IBCHandler` smart contract.
+    pub ibc_handler_address: H160,
+
+    /// The address of the `ScrollChain` rollup contract on the l1.
+    pub rollup_contract_address: H160,
+    pub rollup_finalized_state_roots_slot: U256,
+    pub rollup_last_finalized_batch_index_slot: U256,
+    pub rollup_committed_batches_slot: U256,
+
+    pub l1_provider: RootProvider<BoxTransport>,
+    pub l2_provider: RootProvider<BoxTransport>,
+
+    pub scroll_api_client: ScrollClient,
+    pub scroll_rpc: scroll_rpc::JsonRpcClient,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// The id of the client tracking the l1 on the chain hosting the scroll client.
+    pub l1_client_id: u32,
+
+    /// The address of the `IBCHandler` smart contract.
+    pub ibc_handler_address: H160,
+
+    /// The address of the `ScrollChain` rollup contract on the l1.
+    pub rollup_contract_address: H160,
+    /// The slot of [`ScrollChain.finalizedStateRoots`](https://github.com/scroll-tech/scroll/blob/71f88b04f5a69196138c8cec63a75cf1f0ba2d99/contracts/src/L1/rollup/ScrollChain.sol#L159).
+    pub rollup_finalized_state_roots_slot: U256,
+    /// The slot of [`ScrollChain.lastFinalizedBatchIndex`](https://github.com/scroll-tech/scroll/blob/71f88b04f5a69196138c8cec63a75cf1f0ba2d99/contracts/src/L1/rollup/ScrollChain.sol#L153).
+    pub rollup_last_finalized_batch_index_slot: U256,
+    /// The slot of [`ScrollChain.committedBatches`](https://github.com/scroll-tech/scroll/blob/71f88b04f5a69196138c8cec63a75cf1f0ba2d99/contracts/src/L1/rollup/ScrollChain.sol#L156).
+    pub rollup_committed_batches_slot: U256,
+
+    /// The RPC endpoint for the l1 execution chain.
+    pub l1_rpc_url: String,
+    /// The RPC endpoint for scroll.
+    pub scroll_rpc_url: String,
+    /// The endpoint of the scroll api, used to map batches to scroll blocks.
+    pub scroll_api_url: String,
+}
+
+impl ConsensusModule for Module {
+    type Config = Config;
+
+    async fn new(config: Self::Config, info: ConsensusModuleInfo) -> Result<Self, BoxDynError> {
+        let l1_provider = ProviderBuilder::new()
+            .on_builtin(&config.l1_rpc_url)
+            .await?;
+
+        let l2_provider = ProviderBuilder::new()
+            .on_builtin(&config.scroll_rpc_url)
+            .await?;
+
+        let chain_id = ChainId::new(l2_provider.get_chain_id().await?.to_string());
+
+        info.ensure_chain_id(chain_id.to_string())?;
+        info.ensure_consensus_type(ConsensusType::SCROLL)?;
+
+        Ok(Self {
+            chain_id,
+            l1_client_id: config.l1_client_id,
+            ibc_handler_address: config.ibc_handler_address,
+            rollup_contract_address: config.rollup_contract_address,
+            rollup_finalized_state_roots_slot: config.rollup_finalized_state_roots_slot,
+            rollup_last_finalized_batch_index_slot: config.rollup_last_finalized_batch_index_slot,
+            rollup_committed_batches_slot: config.rollup_committed_batches_slot,
+            l1_provider,
+            l2_provider,
+            scroll_api_client: ScrollClient::new(config.scroll_api_url),
+            scroll_rpc: scroll_rpc::JsonRpcClient::new(config.scroll_rpc_url).await?,
+        })
+    }
+}
+
+impl Module {
+    // TODO: Deduplicate this from the other scroll modules and the scroll client-update plugin
+    #[instrument(skip_all, fields(%l1_height))]
+    async fn batch_index_of_l1_height(&self, l1_height: u64) -> RpcResult<u64> {
+        let batch_index = self
+            .l1_provider
+            .get_storage_at(
+                self.rollup_contract_address.into(),
+                alloy::primitives::U256::from_be_bytes(
+                    self.rollup_last_finalized_batch_index_slot.to_be_bytes(),
+                ),
+            )
+            .block_id(l1_height.into())
+            .await
+            .map_err(|e| {
+                ErrorObject::owned(
+                    -1,
+                    ErrorReporter(e).with_message("error fetching last finalized batch index"),
+                    None::<()>,
+                )
+            })?;
+
+        let batch_index =
+            u64::try_from(U256::from_be_bytes(batch_index.to_be_bytes())).map_err(|()| {
+                ErrorObject::owned(
+                    -1,
+                    format!("last finalized batch index {batch_index} is out of range"),
+                    None::<()>,
+                )
+            })?;
+
+        debug!("l1 height {l1_height} is batch index {batch_index}");
+
+        Ok(batch_index)
+    }
+
+    #[instrument(skip_all, fields(%l1_height))]
+    async fn scroll_height_of_l1_height(&self, l1_height: u64) -> RpcResult<u64> {
+        let batch_index = self.batch_index_of_l1_height(l1_height).await?;
+
+        let batch = self.scroll_api_client.batch(batch_index).await.batch;
+
+        debug!(
+            "batch index {batch_index} is scroll height range {}..={}",
+            batch.start_block_number, batch.end_block_number
+        );
+
+        Ok(batch.end_block_number)
+    }
+
+    async fn scroll_timestamp_of_l1_height(&self, l1_height: u64) -> RpcResult<u64> {
+        let scroll_height = self.scroll_height_of_l1_height(l1_height).await?;
+
+        Ok(self
+            .l2_provider
+            .get_block(scroll_height.into(), BlockTransactionsKind::Hashes)
+            .await
+            .map_err(|e| {
+                ErrorObject::owned(
+                    -1,
+                    ErrorReporter(e).with_message("error fetching scroll block"),
+                    None::<()>,
+                )
+            })?
+            .ok_or_else(|| {
+                ErrorObject::owned(
+                    -1,
+                    format!("scroll block {scroll_height} not found"),
+                    None::<()>,
+                )
+            })?
+            .header
+            .timestamp)
+    }
+}
+
+#[async_trait]
+impl ConsensusModuleServer for Module {
+    /// Query the latest height of the l1. Only finalized batches are tracked, so the finality of
+    /// scroll is that of the l1.
+    #[instrument(skip_all, fields(chain_id = %self.chain_id, finalized))]
+    async fn query_latest_height(&self, _: &Extensions, finalized: bool) -> RpcResult<Height> {
+        let tag = if finalized {
+            BlockNumberOrTag::Finalized
+        } else {
+            BlockNumberOrTag::Latest
+        };
+
+        self.l1_provider
+            .get_block(tag.into(), BlockTransactionsKind::Hashes)
+            .await
+            .map_err(|e| {
+                ErrorObject::owned(
+                    -1,
+                    ErrorReporter(e).with_message("error fetching l1 block"),
+                    None::<()>,
+                )
+            })?
+            .map(|block| Height::new(block.header.number))
+            .ok_or_else(|| ErrorObject::owned(-1, format!("l1 block {tag} not found"), None::<()>))
+    }
+
+    /// Query the timestamp of the latest finalized scroll block at the latest height.
+    // TODO: Use a better timestamp type here
+    #[instrument(skip_all, fields(chain_id = %self.chain_id, finalized))]
+    async fn query_latest_timestamp(&self, e: &Extensions, finalized: bool) -> RpcResult<i64> {
+        let height = self.query_latest_height(e, finalized).await?;
+
+        Ok(self
+            .scroll_timestamp_of_l1_height(height.height())
+            .await?
+            .try_into()
+            .expect("timestamp is in range; qed;"))
+    }
+
+    #[instrument(skip_all, fields(chain_id = %self.chain_id, %height))]
+    async fn self_client_state(&self, _: &Extensions, height: Height) -> RpcResult<Value> {
+        Ok(into_value(ClientState {
+            chain_id: self
+                .chain_id
+                .as_str()
+                .parse()
+                .expect("self.chain_id is a valid u256"),
+            frozen_height: Height::new(0),
+            ibc_contract_address: self.ibc_handler_address,
+            l1_client_id: self.l1_client_id,
+            l2_committed_batches_slot: self.rollup_committed_batches_slot,
+            l2_contract_address: self.rollup_contract_address,
+            l2_finalized_state_roots_slot: self.rollup_finalized_state_roots_slot,
+            latest_batch_index_slot: self.rollup_last_finalized_batch_index_slot,
+            latest_slot: height.height(),
+        }))
+    }
+
+    /// The consensus state on this chain at the specified `Height`.
+    #[instrument(skip_all, fields(chain_id = %self.chain_id, %height))]
+    async fn self_consensus_state(&self, _: &Extensions, height: Height) -> RpcResult<Value> {
+        let scroll_height = self.scroll_height_of_l1_height(height.height()).await?;
+
+        let block = self
+            .l2_provider
+            .get_block(scroll_height.into(), BlockTransactionsKind::Hashes)
+            .await
+            .map_err(|e| {
+                ErrorObject::owned(
+                    -1,
+                    ErrorReporter(e).with_message("error fetching scroll block"),
+                    None::<()>,
+                )
+            })?
+            .ok_or_else(|| {
+                ErrorObject::owned(
+                    -1,
+                    format!("scroll block {scroll_height} not found"),
+                    None::<()>,
+                )
+            })?;
+
+        // scroll uses a zktrie for its state, so the proof must be fetched with the scroll rpc
+        let ibc_storage_root = self
+            .scroll_rpc
+            .get_proof(
+                self.ibc_handler_address,
+                [],
+                scroll_rpc::BlockId::Number(scroll_height),
+            )
+            .await
+            .map_err(|e| {
+                ErrorObject::owned(
+                    -1,
+                    ErrorReporter(e).with_message("error fetching ibc handler account proof"),
+                    None::<()>,
+                )
+            })?
+            .storage_hash;
+
+        Ok(into_value(ConsensusState {
+            state_root: H256::from(block.header.state_root),
+            // Normalize to nanos in order to be compliant with cosmos
+            timestamp: block.header.timestamp * 1_000_000_000,
+            ibc_storage_root,
+        }))
+    }
+}
@@ -42,6 +42,9 @@ pub struct Module {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Config {
     pub ws_url: String,
+    /// Fallback endpoints to use if `ws_url` is unavailable.
+    #[serde(default)]
+    pub failover: cometbft_rpc::FailoverConfig,
     pub grpc_url: String,
 }
 
@@ -49,7 +52,8 @@ impl ConsensusModule for Module {
     type Config = Config;
 
     async fn new(config: Self::Config, info: ConsensusModuleInfo) -> Result<Self, BoxDynError> {
-        let tm_client = cometbft_rpc::Client::new(config.ws_url).await?;
+        let tm_client =
+            cometbft_rpc::Client::new_with_failover(config.ws_url, &config.failover).await?;
 
         let chain_id = tm_client.status().await?.node_info.network.to_string();
 
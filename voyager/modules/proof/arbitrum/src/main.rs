@@ -0,0 +1,145 @@
+#![warn(clippy::unwrap_used)]
+
+use alloy::providers::{Provider, ProviderBuilder};
+use arbitrum_rollup::{Rollup, RollupConfig};
+use ethereum_light_client_types::StorageProof;
+use ibc_union_spec::{IbcUnion, StorePath};
+use jsonrpsee::{
+    core::{async_trait, RpcResult},
+    types::ErrorObject,
+    Extensions,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tracing::{debug, instrument};
+use unionlabs::{
+    ethereum::ibc_commitment_key, hash::H160, ibc::core::client::height::Height, uint::U256,
+    ErrorReporter,
+};
+use voyager_message::{
+    core::ChainId,
+    into_value,
+    module::{ProofModuleInfo, ProofModuleServer},
+    ProofModule,
+};
+use voyager_vm::BoxDynError;
+
+#[tokio::main(flavor = "multi_thread")]
+async fn main() {
+    Module::run().await
+}
+
+#[derive(Debug, Clone)]
+pub struct Module {
+    pub chain_id: ChainId,
+
+    pub ibc_handler_address: H160,
+
+    pub rollup: Rollup,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// The address of the `IBCHandler` smart contract on the l2.
+    pub ibc_handler_address: H160,
+
+    /// The location of the rollup state on the l1.
+    pub rollup: RollupConfig,
+
+    /// The RPC endpoint for the l1 execution chain.
+    pub l1_rpc_url: String,
+    /// The RPC endpoint for arbitrum.
+    pub l2_rpc_url: String,
+}
+
+impl ProofModule<IbcUnion> for Module {
+    type Config = Config;
+
+    async fn new(config: Self::Config, info: ProofModuleInfo) -> Result<Self, BoxDynError> {
+        let l1_provider = ProviderBuilder::new()
+            .on_builtin(&config.l1_rpc_url)
+            .await?;
+
+        let l2_provider = ProviderBuilder::new()
+            .on_builtin(&config.l2_rpc_url)
+            .await?;
+
+        let chain_id = l2_provider.get_chain_id().await?;
+
+        info.ensure_chain_id(chain_id.to_string())?;
+
+        Ok(Module {
+            chain_id: ChainId::new(chain_id.to_string()),
+            ibc_handler_address: config.ibc_handler_address,
+            rollup: Rollup::new(config.rollup, l1_provider, l2_provider),
+        })
+    }
+}
+
+#[async_trait]
+impl ProofModuleServer<IbcUnion> for Module {
+    #[instrument(skip_all, fields(chain_id = %self.chain_id, %at, ?path))]
+    async fn query_ibc_proof(
+        &self,
+        _: &Extensions,
+        at: Height,
+        path: StorePath,
+    ) -> RpcResult<Value> {
+        let location = ibc_commitment_key(path.key());
+
+        debug!(
+            "querying proof for slot {location} for IBC handler contract {}",
+            self.ibc_handler_address
+        );
+
+        let l2_height = self.rollup.l2_height_at(at.height()).await.map_err(|e| {
+            ErrorObject::owned(
+                -1,
+                ErrorReporter(e).with_message("error fetching the confirmed l2 height"),
+                None::<()>,
+            )
+        })?;
+
+        let proof = self
+            .rollup
+            .l2_provider
+            .get_proof(
+                self.ibc_handler_address.get().into(),
+                vec![location.to_be_bytes().into()],
+            )
+            .block_id(l2_height.into())
+            .await
+            .map_err(|e| {
+                ErrorObject::owned(
+                    -1,
+                    format!("error fetching proof: {}", ErrorReporter(e)),
+                    None::<()>,
+                )
+            })?;
+
+        let proof = match <[_; 1]>::try_from(proof.storage_proof) {
+            Ok([proof]) => proof,
+            Err(invalid) => {
+                return Err(ErrorObject::owned(
+                    -1,
+                    format!(
+                        "received invalid response from eth_getProof, expected \
+                        length of 1 but got `{invalid:#?}`"
+                    ),
+                    None::<()>,
+                ));
+            }
+        };
+
+        Ok(into_value(StorageProof {
+            key: U256::from_be_bytes(proof.key.as_b256().0),
+            value: U256::from_be_bytes(proof.value.to_be_bytes()),
+            proof: proof
+                .proof
+                .into_iter()
+                .map(|bytes| bytes.to_vec())
+                .collect(),
+        }))
+    }
+}
@@ -3,10 +3,11 @@
 use std::{
     error::Error,
     fmt::{Debug, Display},
-    num::ParseIntError,
+    num::{NonZeroU64, ParseIntError},
 };
 
 use ibc_union_spec::{IbcUnion, StorePath};
+use ics23::ibc_api::SDK_SPECS;
 use jsonrpsee::{
     core::{async_trait, RpcResult},
     types::{ErrorObject, ErrorObjectOwned},
@@ -14,12 +15,15 @@ use jsonrpsee::{
 };
 use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
-use tracing::{error, instrument};
+use tracing::{debug, error, instrument};
 use unionlabs::{
     bech32::Bech32,
     bounded::BoundedI64,
     hash::H256,
-    ibc::core::{client::height::Height, commitment::merkle_proof::MerkleProof},
+    ibc::core::{
+        client::height::Height,
+        commitment::{merkle_proof::MerkleProof, merkle_root::MerkleRoot},
+    },
     ErrorReporter,
 };
 use voyager_message::{
@@ -50,21 +54,38 @@ pub struct Module {
     pub grpc_url: String,
 
     pub ibc_host_contract_address: Bech32<H256>,
+
+    pub verify_proofs: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(deny_unknown_fields)]
 pub struct Config {
     pub ws_url: String,
+    /// Fallback endpoints to use if `ws_url` is unavailable.
+    #[serde(default)]
+    pub failover: cometbft_rpc::FailoverConfig,
+    /// An archival endpoint to send queries at old heights to.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub archival: Option<cometbft_rpc::ArchivalConfig>,
     pub grpc_url: String,
     pub ibc_host_contract_address: Bech32<H256>,
+    /// Verify all proofs against the app hash of the block they are queried for before returning
+    /// them. This costs an additional query per proof.
+    #[serde(default)]
+    pub verify_proofs: bool,
 }
 
 impl ProofModule<IbcUnion> for Module {
     type Config = Config;
 
     async fn new(config: Self::Config, info: ProofModuleInfo) -> Result<Self, BoxDynError> {
-        let tm_client = cometbft_rpc::Client::new(config.ws_url).await?;
+        let mut tm_client =
+            cometbft_rpc::Client::new_with_failover(config.ws_url, &config.failover).await?;
+
+        if let Some(archival) = &config.archival {
+            tm_client = tm_client.with_archival(archival).await?;
+        }
 
         let chain_id = tm_client.status().await?.node_info.network;
 
@@ -89,6 +110,7 @@ impl ProofModule<IbcUnion> for Module {
             chain_revision,
             grpc_url: config.grpc_url,
             ibc_host_contract_address: config.ibc_host_contract_address,
+            verify_proofs: config.verify_proofs,
         })
     }
 }
@@ -98,6 +120,67 @@ impl Module {
     pub fn make_height(&self, height: u64) -> Height {
         Height::new_with_revision(self.chain_revision, height)
     }
+
+    /// Verify `proof` of the value at `key` in the wasm store (or of its absence, if `value` is
+    /// `None`) against the app hash of the block at `at`.
+    async fn verify_proof(
+        &self,
+        at: Height,
+        key: Vec<u8>,
+        value: Option<Vec<u8>>,
+        proof: &MerkleProof,
+    ) -> RpcResult<()> {
+        let height = NonZeroU64::new(at.height()).ok_or_else(|| {
+            ErrorObject::owned(
+                FATAL_JSONRPC_ERROR_CODE,
+                "cannot verify a proof at height 0",
+                Some(json!({ "height": at })),
+            )
+        })?;
+
+        let root = MerkleRoot {
+            hash: self
+                .tm_client
+                .commit(Some(height))
+                .await
+                .map_err(rpc_error(
+                    format_args!("error fetching commit at height {at}"),
+                    Some(json!({ "height": at })),
+                ))?
+                .signed_header
+                .header
+                .app_hash
+                .into_encoding(),
+        };
+
+        let key_path = [b"wasm".to_vec(), key];
+
+        let is_membership = value.is_some();
+
+        match value {
+            Some(value) => {
+                ics23::ibc_api::verify_membership(proof, &SDK_SPECS, &root, &key_path, value)
+            }
+            None => ics23::ibc_api::verify_non_membership(proof, &SDK_SPECS, &root, &key_path),
+        }
+        .map_err(|e| {
+            let message = format!("invalid proof: {}", ErrorReporter(e));
+            error!(%message, %at, %root.hash, is_membership);
+            ErrorObject::owned(
+                FATAL_JSONRPC_ERROR_CODE,
+                message,
+                Some(json!({
+                    "height": at,
+                    "root": root,
+                    "membership": is_membership,
+                })),
+            )
+        })?;
+
+        debug!(%at, is_membership, "verified proof");
+
+        Ok(())
+    }
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -127,7 +210,7 @@ impl ProofModuleServer<IbcUnion> for Module {
             .tm_client
             .abci_query(
                 "store/wasm/key",
-                data,
+                &data,
                 // THIS -1 IS VERY IMPORTANT!!!
                 //
                 // a proof at height H is provable at height H + 1
@@ -146,42 +229,56 @@ impl ProofModuleServer<IbcUnion> for Module {
             .await
             .map_err(rpc_error("error querying connection proof", None))?;
 
-        Ok(into_value(
-            MerkleProof::try_from(protos::ibc::core::commitment::v1::MerkleProof {
-                proofs: query_result
-                    .response
-                    .proof_ops
-                    .ok_or_else(|| {
+        let proof = MerkleProof::try_from(protos::ibc::core::commitment::v1::MerkleProof {
+            proofs: query_result
+                .response
+                .proof_ops
+                .ok_or_else(|| {
+                    ErrorObject::owned(
+                        FATAL_JSONRPC_ERROR_CODE,
+                        "proofOps must be present on abci query when called with prove = true",
+                        None::<()>,
+                    )
+                })?
+                .ops
+                .into_iter()
+                .map(|op| {
+                    <protos::cosmos::ics23::v1::CommitmentProof as prost::Message>::decode(
+                        &*op.data,
+                    )
+                    .map_err(|e| {
                         ErrorObject::owned(
                             FATAL_JSONRPC_ERROR_CODE,
-                            "proofOps must be present on abci query when called with prove = true",
-                            None::<()>,
+                            format!("invalid height value: {}", ErrorReporter(e)),
+                            Some(json!({ "height": at })),
                         )
-                    })?
-                    .ops
-                    .into_iter()
-                    .map(|op| {
-                        <protos::cosmos::ics23::v1::CommitmentProof as prost::Message>::decode(
-                            &*op.data,
-                        )
-                        .map_err(|e| {
-                            ErrorObject::owned(
-                                FATAL_JSONRPC_ERROR_CODE,
-                                format!("invalid height value: {}", ErrorReporter(e)),
-                                Some(json!({ "height": at })),
-                            )
-                        })
                     })
-                    .collect::<Result<Vec<_>, _>>()?,
-            })
-            .map_err(|e| {
-                ErrorObject::owned(
-                    FATAL_JSONRPC_ERROR_CODE,
-                    format!("invalid height value: {}", ErrorReporter(e)),
-                    Some(json!({ "height": at })),
-                )
-            })?,
-        ))
+                })
+                .collect::<Result<Vec<_>, _>>()?,
+        })
+        .map_err(|e| {
+            ErrorObject::owned(
+                FATAL_JSONRPC_ERROR_CODE,
+                format!("invalid height value: {}", ErrorReporter(e)),
+                Some(json!({ "height": at })),
+            )
+        })?;
+
+        if self.verify_proofs {
+            self.verify_proof(
+                at,
+                data,
+                query_result
+                    .response
+                    .value
+                    .map(|value| value.into_vec())
+                    .filter(|value| !value.is_empty()),
+                &proof,
+            )
+            .await?;
+        }
+
+        Ok(into_value(proof))
     }
 }
 
@@ -3,12 +3,13 @@
 use std::{
     error::Error,
     fmt::{Debug, Display},
-    num::ParseIntError,
+    num::{NonZeroU64, ParseIntError},
     sync::Arc,
 };
 
 use dashmap::DashMap;
 use ibc_classic_spec::{IbcClassic, StorePath};
+use ics23::ibc_api::SDK_SPECS;
 use jsonrpsee::{
     core::{async_trait, RpcResult},
     types::{ErrorObject, ErrorObjectOwned},
@@ -16,17 +17,20 @@ use jsonrpsee::{
 };
 use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
-use tracing::{error, instrument};
+use tracing::{debug, error, instrument};
 use unionlabs::{
     hash::H256,
-    ibc::core::{client::height::Height, commitment::merkle_proof::MerkleProof},
+    ibc::core::{
+        client::height::Height,
+        commitment::{merkle_proof::MerkleProof, merkle_root::MerkleRoot},
+    },
     ErrorReporter, WasmClientType,
 };
 use voyager_message::{
     core::ChainId,
     into_value,
     module::{ProofModuleInfo, ProofModuleServer},
-    ProofModule,
+    ProofModule, FATAL_JSONRPC_ERROR_CODE,
 };
 use voyager_vm::BoxDynError;
 
@@ -50,20 +54,38 @@ pub struct Module {
     pub grpc_url: String,
 
     pub checksum_cache: Arc<DashMap<H256, WasmClientType>>,
+
+    pub verify_proofs: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(deny_unknown_fields)]
 pub struct Config {
     pub ws_url: String,
+    /// Fallback endpoints to use if `ws_url` is unavailable.
+    #[serde(default)]
+    pub failover: cometbft_rpc::FailoverConfig,
+    /// An archival endpoint to send queries at old heights to.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub archival: Option<cometbft_rpc::ArchivalConfig>,
     pub grpc_url: String,
+    /// Verify all proofs against the app hash of the block they are queried for before returning
+    /// them, such that an invalid proof is caught here instead of reverting on the counterparty.
+    /// This costs an additional query per proof.
+    #[serde(default)]
+    pub verify_proofs: bool,
 }
 
 impl ProofModule<IbcClassic> for Module {
     type Config = Config;
 
     async fn new(config: Self::Config, info: ProofModuleInfo) -> Result<Self, BoxDynError> {
-        let tm_client = cometbft_rpc::Client::new(config.ws_url).await?;
+        let mut tm_client =
+            cometbft_rpc::Client::new_with_failover(config.ws_url, &config.failover).await?;
+
+        if let Some(archival) = &config.archival {
+            tm_client = tm_client.with_archival(archival).await?;
+        }
 
         let chain_id = tm_client.status().await?.node_info.network;
 
@@ -88,6 +110,7 @@ impl ProofModule<IbcClassic> for Module {
             chain_revision,
             grpc_url: config.grpc_url,
             checksum_cache: Arc::new(DashMap::default()),
+            verify_proofs: config.verify_proofs,
         })
     }
 }
@@ -97,6 +120,66 @@ impl Module {
     pub fn make_height(&self, height: u64) -> Height {
         Height::new_with_revision(self.chain_revision, height)
     }
+
+    /// Verify `proof` of the value at `key_path` (or of its absence, if `value` is `None`) against
+    /// the app hash of the block at `at`. This is the root that the counterparty's consensus state
+    /// at `at` will contain, and as such what the proof will be verified against on chain.
+    async fn verify_proof(
+        &self,
+        at: Height,
+        key_path: &[Vec<u8>],
+        value: Option<Vec<u8>>,
+        proof: &MerkleProof,
+    ) -> RpcResult<()> {
+        let height = NonZeroU64::new(at.height()).ok_or_else(|| {
+            ErrorObject::owned(
+                FATAL_JSONRPC_ERROR_CODE,
+                "cannot verify a proof at height 0",
+                Some(json!({ "height": at })),
+            )
+        })?;
+
+        let root = MerkleRoot {
+            hash: self
+                .tm_client
+                .commit(Some(height))
+                .await
+                .map_err(rpc_error(
+                    format_args!("error fetching commit at height {at}"),
+                    Some(json!({ "height": at })),
+                ))?
+                .signed_header
+                .header
+                .app_hash
+                .into_encoding(),
+        };
+
+        let is_membership = value.is_some();
+
+        match value {
+            Some(value) => {
+                ics23::ibc_api::verify_membership(proof, &SDK_SPECS, &root, key_path, value)
+            }
+            None => ics23::ibc_api::verify_non_membership(proof, &SDK_SPECS, &root, key_path),
+        }
+        .map_err(|e| {
+            let message = format!("invalid proof: {}", ErrorReporter(e));
+            error!(%message, %at, %root.hash, is_membership);
+            ErrorObject::owned(
+                FATAL_JSONRPC_ERROR_CODE,
+                message,
+                Some(json!({
+                    "height": at,
+                    "root": root,
+                    "membership": is_membership,
+                })),
+            )
+        })?;
+
+        debug!(%at, is_membership, "verified proof");
+
+        Ok(())
+    }
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -116,14 +199,14 @@ impl ProofModuleServer<IbcClassic> for Module {
         at: Height,
         path: StorePath,
     ) -> RpcResult<Value> {
-        const IBC_STORE_PATH: &str = "store/ibc/key";
+        let store_path = format!("store/{}/key", path.store_key());
 
         let path_string = path.to_string();
 
         let query_result = self
             .tm_client
             .abci_query(
-                IBC_STORE_PATH,
+                &store_path,
                 &path_string,
                 // a proof at height H is provable at height H + 1
                 // we assume that the height passed in to this function is the intended height to prove against, thus we have to query the height - 1
@@ -140,24 +223,41 @@ impl ProofModuleServer<IbcClassic> for Module {
                 Some(json!({ "height": at, "path": path })),
             ))?;
 
-        Ok(into_value(
-            MerkleProof::try_from(protos::ibc::core::commitment::v1::MerkleProof {
-                proofs: query_result
-                    .response
-                    .proof_ops
+        let proof = MerkleProof::try_from(protos::ibc::core::commitment::v1::MerkleProof {
+            proofs: query_result
+                .response
+                .proof_ops
+                .unwrap()
+                .ops
+                .into_iter()
+                .map(|op| {
+                    <protos::cosmos::ics23::v1::CommitmentProof as prost::Message>::decode(
+                        &*op.data,
+                    )
                     .unwrap()
-                    .ops
-                    .into_iter()
-                    .map(|op| {
-                        <protos::cosmos::ics23::v1::CommitmentProof as prost::Message>::decode(
-                            &*op.data,
-                        )
-                        .unwrap()
-                    })
-                    .collect::<Vec<_>>(),
-            })
-            .unwrap(),
-        ))
+                })
+                .collect::<Vec<_>>(),
+        })
+        .unwrap();
+
+        if self.verify_proofs {
+            self.verify_proof(
+                at,
+                &[
+                    path.store_key().as_bytes().to_vec(),
+                    path_string.into_bytes(),
+                ],
+                query_result
+                    .response
+                    .value
+                    .map(|value| value.into_vec())
+                    .filter(|value| !value.is_empty()),
+                &proof,
+            )
+            .await?;
+        }
+
+        Ok(into_value(proof))
     }
 }
 
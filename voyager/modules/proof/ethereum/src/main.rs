@@ -2,9 +2,10 @@
 
 use alloy::{
     providers::{Provider, ProviderBuilder, RootProvider},
+    rpc::types::BlockTransactionsKind,
     transports::BoxTransport,
 };
-use ethereum_light_client_types::StorageProof;
+use chain_utils::evm_proof::ContractProof;
 use ibc_union_spec::{IbcUnion, StorePath};
 use jsonrpsee::{
     core::{async_trait, RpcResult},
@@ -15,8 +16,7 @@ use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use tracing::{debug, instrument};
 use unionlabs::{
-    ethereum::ibc_commitment_key, hash::H160, ibc::core::client::height::Height, uint::U256,
-    ErrorReporter,
+    ethereum::ibc_commitment_key, hash::H160, ibc::core::client::height::Height, ErrorReporter,
 };
 use voyager_message::{
     core::ChainId,
@@ -95,38 +95,48 @@ impl ProofModuleServer<IbcUnion> for Module {
 
         let execution_height = at.height();
 
-        let proof = self
+        // the proof is verified against the state root of the execution block, to catch an rpc
+        // returning a proof that is inconsistent with the block it is queried at. the proven
+        // storage root is verified against the beacon chain by the counterparty light client.
+        let state_root = self
             .provider
-            .get_proof(
-                self.ibc_handler_address.get().into(),
-                vec![location.to_be_bytes().into()],
-            )
-            .block_id(execution_height.into())
+            .get_block(execution_height.into(), BlockTransactionsKind::Hashes)
             .await
             .map_err(|e| {
                 ErrorObject::owned(
                     -1,
-                    format!("error fetching proof: {}", ErrorReporter(e)),
+                    format!("error fetching block: {}", ErrorReporter(e)),
                     None::<()>,
                 )
-            })?;
-
-        let proof = match <[_; 1]>::try_from(proof.storage_proof) {
-            Ok([proof]) => proof,
-            Err(invalid) => {
-                panic!("received invalid response from eth_getProof, expected length of 1 but got `{invalid:#?}`");
-            }
-        };
-
-        let proof = StorageProof {
-            key: U256::from_be_bytes(proof.key.as_b256().0),
-            value: U256::from_be_bytes(proof.value.to_be_bytes()),
-            proof: proof
-                .proof
-                .into_iter()
-                .map(|bytes| bytes.to_vec())
-                .collect(),
-        };
+            })?
+            .ok_or_else(|| {
+                ErrorObject::owned(
+                    -1,
+                    format!("block {execution_height} does not exist"),
+                    None::<()>,
+                )
+            })?
+            .header
+            .state_root;
+
+        let proof = ContractProof::fetch_verified(
+            &self.provider,
+            self.ibc_handler_address,
+            vec![location],
+            execution_height,
+            state_root.into(),
+        )
+        .await
+        .map_err(|e| {
+            ErrorObject::owned(
+                -1,
+                format!("error fetching proof: {}", ErrorReporter(e)),
+                None::<()>,
+            )
+        })?;
+
+        let [proof] = <[_; 1]>::try_from(proof.storage_proofs)
+            .expect("exactly one storage proof was requested and verified; qed;");
 
         Ok(into_value(proof))
     }
@@ -0,0 +1,193 @@
+#![warn(clippy::unwrap_used)]
+
+use alloy::{
+    providers::{Provider, ProviderBuilder, RootProvider},
+    transports::BoxTransport,
+};
+use ethereum_light_client_types::StorageProof;
+use ibc_union_spec::{IbcUnion, StorePath};
+use jsonrpsee::{
+    core::{async_trait, RpcResult},
+    types::ErrorObject,
+    Extensions,
+};
+use scroll_api::ScrollClient;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tracing::{debug, instrument};
+use unionlabs::{
+    ethereum::ibc_commitment_key, hash::H160, ibc::core::client::height::Height, uint::U256,
+    ErrorReporter,
+};
+use voyager_message::{
+    core::ChainId,
+    into_value,
+    module::{ProofModuleInfo, ProofModuleServer},
+    ProofModule,
+};
+use voyager_vm::BoxDynError;
+
+#[tokio::main(flavor = "multi_thread")]
+async fn main() {
+    Module::run().await
+}
+
+#[derive(Debug, Clone)]
+pub struct Module {
+    pub chain_id: ChainId,
+
+    pub ibc_handler_address: H160,
+
+    pub rollup_contract_address: H160,
+    pub rollup_last_finalized_batch_index_slot: U256,
+
+    pub l1_provider: RootProvider<BoxTransport>,
+
+    pub scroll_api_client: ScrollClient,
+    pub scroll_rpc: scroll_rpc::JsonRpcClient,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// The address of the `IBCHandler` smart contract.
+    pub ibc_handler_address: H160,
+
+    /// The address of the `ScrollChain` rollup contract on the l1.
+    pub rollup_contract_address: H160,
+    /// The slot of `ScrollChain.lastFinalizedBatchIndex`.
+    pub rollup_last_finalized_batch_index_slot: U256,
+
+    /// The RPC endpoint for the l1 execution chain.
+    pub l1_rpc_url: String,
+    /// The RPC endpoint for scroll.
+    pub scroll_rpc_url: String,
+    /// The endpoint of the scroll api, used to map batches to scroll blocks.
+    pub scroll_api_url: String,
+}
+
+impl ProofModule<IbcUnion> for Module {
+    type Config = Config;
+
+    async fn new(config: Self::Config, info: ProofModuleInfo) -> Result<Self, BoxDynError> {
+        let l1_provider = ProviderBuilder::new()
+            .on_builtin(&config.l1_rpc_url)
+            .await?;
+
+        let chain_id = ProviderBuilder::new()
+            .on_builtin(&config.scroll_rpc_url)
+            .await?
+            .get_chain_id()
+            .await?;
+
+        info.ensure_chain_id(chain_id.to_string())?;
+
+        Ok(Module {
+            chain_id: ChainId::new(chain_id.to_string()),
+            ibc_handler_address: config.ibc_handler_address,
+            rollup_contract_address: config.rollup_contract_address,
+            rollup_last_finalized_batch_index_slot: config.rollup_last_finalized_batch_index_slot,
+            l1_provider,
+            scroll_api_client: ScrollClient::new(config.scroll_api_url),
+            scroll_rpc: scroll_rpc::JsonRpcClient::new(config.scroll_rpc_url).await?,
+        })
+    }
+}
+
+impl Module {
+    // TODO: Deduplicate this from the other scroll modules and the scroll client-update plugin
+    #[instrument(skip_all, fields(%l1_height))]
+    async fn scroll_height_of_l1_height(&self, l1_height: u64) -> RpcResult<u64> {
+        let batch_index = self
+            .l1_provider
+            .get_storage_at(
+                self.rollup_contract_address.into(),
+                alloy::primitives::U256::from_be_bytes(
+                    self.rollup_last_finalized_batch_index_slot.to_be_bytes(),
+                ),
+            )
+            .block_id(l1_height.into())
+            .await
+            .map_err(|e| {
+                ErrorObject::owned(
+                    -1,
+                    ErrorReporter(e).with_message("error fetching last finalized batch index"),
+                    None::<()>,
+                )
+            })?;
+
+        let batch_index =
+            u64::try_from(U256::from_be_bytes(batch_index.to_be_bytes())).map_err(|()| {
+                ErrorObject::owned(
+                    -1,
+                    format!("last finalized batch index {batch_index} is out of range"),
+                    None::<()>,
+                )
+            })?;
+
+        let batch = self.scroll_api_client.batch(batch_index).await.batch;
+
+        debug!(
+            "l1 height {l1_height} is batch index {batch_index}, which is scroll height range {}..={}",
+            batch.start_block_number, batch.end_block_number
+        );
+
+        Ok(batch.end_block_number)
+    }
+}
+
+#[async_trait]
+impl ProofModuleServer<IbcUnion> for Module {
+    #[instrument(skip_all, fields(chain_id = %self.chain_id, %at, ?path))]
+    async fn query_ibc_proof(
+        &self,
+        _: &Extensions,
+        at: Height,
+        path: StorePath,
+    ) -> RpcResult<Value> {
+        let location = ibc_commitment_key(path.key());
+
+        debug!(
+            "querying proof for slot {location} for IBC handler contract {}",
+            self.ibc_handler_address
+        );
+
+        let scroll_height = self.scroll_height_of_l1_height(at.height()).await?;
+
+        let proof = self
+            .scroll_rpc
+            .get_proof(
+                self.ibc_handler_address,
+                [location],
+                scroll_rpc::BlockId::Number(scroll_height),
+            )
+            .await
+            .map_err(|e| {
+                ErrorObject::owned(
+                    -1,
+                    format!("error fetching proof: {}", ErrorReporter(e)),
+                    None::<()>,
+                )
+            })?;
+
+        let proof = match <[_; 1]>::try_from(proof.storage_proof) {
+            Ok([proof]) => proof,
+            Err(invalid) => {
+                return Err(ErrorObject::owned(
+                    -1,
+                    format!(
+                        "received invalid response from eth_getProof, expected \
+                        length of 1 but got `{invalid:#?}`"
+                    ),
+                    None::<()>,
+                ));
+            }
+        };
+
+        Ok(into_value(StorageProof {
+            key: proof.key,
+            value: proof.value,
+            proof: proof.proof,
+        }))
+    }
+}
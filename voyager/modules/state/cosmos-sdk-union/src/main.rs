@@ -64,6 +64,12 @@ pub struct Module {
 #[serde(deny_unknown_fields)]
 pub struct Config {
     pub ws_url: String,
+    /// Fallback endpoints to use if `ws_url` is unavailable.
+    #[serde(default)]
+    pub failover: cometbft_rpc::FailoverConfig,
+    /// An archival endpoint to send queries at old heights to.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub archival: Option<cometbft_rpc::ArchivalConfig>,
     pub grpc_url: String,
     pub ibc_host_contract_address: Bech32<H256>,
 }
@@ -72,7 +78,12 @@ impl StateModule<IbcUnion> for Module {
     type Config = Config;
 
     async fn new(config: Self::Config, info: StateModuleInfo) -> Result<Self, BoxDynError> {
-        let tm_client = cometbft_rpc::Client::new(config.ws_url).await?;
+        let mut tm_client =
+            cometbft_rpc::Client::new_with_failover(config.ws_url, &config.failover).await?;
+
+        if let Some(archival) = &config.archival {
+            tm_client = tm_client.with_archival(archival).await?;
+        }
 
         let chain_id = tm_client.status().await?.node_info.network;
 
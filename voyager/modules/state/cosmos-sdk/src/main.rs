@@ -43,6 +43,8 @@ use voyager_vm::BoxDynError;
 
 const IBC_STORE_PATH: &str = "store/ibc/key";
 
+const UPGRADE_STORE_PATH: &str = "store/upgrade/key";
+
 #[tokio::main(flavor = "multi_thread")]
 async fn main() {
     <Module as StateModule<IbcClassic>>::run().await;
@@ -69,6 +71,12 @@ pub struct Module {
 #[serde(deny_unknown_fields)]
 pub struct Config {
     pub ws_url: String,
+    /// Fallback endpoints to use if `ws_url` is unavailable.
+    #[serde(default)]
+    pub failover: cometbft_rpc::FailoverConfig,
+    /// An archival endpoint to send queries at old heights to.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub archival: Option<cometbft_rpc::ArchivalConfig>,
     pub grpc_url: String,
 }
 
@@ -76,7 +84,12 @@ impl StateModule<IbcClassic> for Module {
     type Config = Config;
 
     async fn new(config: Self::Config, info: StateModuleInfo) -> Result<Self, BoxDynError> {
-        let tm_client = cometbft_rpc::Client::new(config.ws_url).await?;
+        let mut tm_client =
+            cometbft_rpc::Client::new_with_failover(config.ws_url, &config.failover).await?;
+
+        if let Some(archival) = &config.archival {
+            tm_client = tm_client.with_archival(archival).await?;
+        }
 
         let chain_id = tm_client.status().await?.node_info.network;
 
@@ -228,9 +241,19 @@ impl Module {
     }
 
     async fn abci_query(&self, path_string: &str, height: Height) -> RpcResult<QueryResponse> {
+        self.abci_query_store(IBC_STORE_PATH, path_string, height)
+            .await
+    }
+
+    async fn abci_query_store(
+        &self,
+        store_path: &str,
+        path_string: &str,
+        height: Height,
+    ) -> RpcResult<QueryResponse> {
         self.tm_client
             .abci_query(
-                IBC_STORE_PATH,
+                store_path,
                 &path_string,
                 Some(
                     i64::try_from(height.height())
@@ -481,6 +504,20 @@ impl Module {
         ))
     }
 
+    /// Query the upgraded client or consensus state at `path_string` in the upgrade store.
+    #[instrument(skip_all, fields(chain_id = %self.chain_id, %height, %path_string))]
+    async fn query_upgraded_state(
+        &self,
+        height: Height,
+        path_string: String,
+    ) -> RpcResult<Option<Bytes>> {
+        let query_result = self
+            .abci_query_store(UPGRADE_STORE_PATH, &path_string, height)
+            .await?;
+
+        Ok(query_result.value.map(|value| value.into_encoding()))
+    }
+
     #[instrument(skip_all, fields(chain_id = %self.chain_id, %height))]
     async fn query_next_client_sequence(&self, height: Height) -> RpcResult<u64> {
         let path_string = NextClientSequencePath {}.to_string();
@@ -605,6 +642,14 @@ impl StateModuleServer<IbcClassic> for Module {
             StorePath::NextClientSequence(_path) => {
                 self.query_next_client_sequence(at).await.map(into_value)
             }
+            StorePath::UpgradedClientState(path) => self
+                .query_upgraded_state(at, path.to_string())
+                .await
+                .map(into_value),
+            StorePath::UpgradedConsensusState(path) => self
+                .query_upgraded_state(at, path.to_string())
+                .await
+                .map(into_value),
         }
     }
 
@@ -0,0 +1,473 @@
+#![warn(clippy::unwrap_used)]
+
+use std::collections::VecDeque;
+
+use alloy::{
+    providers::{Provider, ProviderBuilder},
+    rpc::types::{Block, BlockTransactionsKind},
+};
+use arbitrum_light_client_types::{Header, L2Header};
+use arbitrum_rollup::{Rollup, RollupConfig};
+use ethereum_light_client_types::{AccountProof, StorageProof};
+use ibc_union_spec::IbcUnion;
+use jsonrpsee::{
+    core::{async_trait, RpcResult},
+    types::ErrorObject,
+    Extensions,
+};
+use serde::{Deserialize, Serialize};
+use tracing::{debug, info, instrument};
+use unionlabs::{
+    hash::{H160, H256},
+    ibc::core::client::height::Height,
+    uint::U256,
+    ErrorReporter,
+};
+use voyager_message::{
+    call::{Call, FetchUpdateHeaders, WaitForTrustedHeight},
+    callback::{
+        AggregateMsgUpdateClientsFromOrderedHeaders, AggregateSubmitTxFromOrderedClientUpdates,
+    },
+    core::{ChainId, IbcSpec, QueryHeight},
+    data::{Data, DecodedHeaderMeta, OrderedHeaders},
+    hook::UpdateHook,
+    into_value,
+    module::{PluginInfo, PluginServer},
+    DefaultCmd, ExtensionsExt, Plugin, PluginMessage, RawClientId, VoyagerClient, VoyagerMessage,
+};
+use voyager_vm::{call, data, pass::PassResult, promise, seq, BoxDynError, Op, Visit};
+
+use crate::{
+    call::{FetchUpdate, ModuleCall},
+    callback::ModuleCallback,
+};
+
+pub mod call;
+pub mod callback;
+
+#[tokio::main(flavor = "multi_thread")]
+async fn main() {
+    Module::run().await
+}
+
+#[derive(Debug, Clone)]
+pub struct Module {
+    pub chain_id: ChainId,
+
+    pub l1_client_id: u32,
+
+    /// The address of the `IBCHandler` smart contract on the l2.
+    pub ibc_handler_address: H160,
+
+    pub rollup: Rollup,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    pub chain_id: ChainId,
+
+    /// The id of the client tracking the l1 on the chain hosting the arbitrum client.
+    pub l1_client_id: u32,
+
+    /// The address of the `IBCHandler` smart contract on the l2.
+    pub ibc_handler_address: H160,
+
+    /// The location of the rollup state on the l1.
+    pub rollup: RollupConfig,
+
+    /// The RPC endpoint for the l1 execution chain.
+    pub l1_rpc_url: String,
+    /// The RPC endpoint for arbitrum.
+    pub l2_rpc_url: String,
+}
+
+fn plugin_name(chain_id: &ChainId) -> String {
+    pub const PLUGIN_NAME: &str = env!("CARGO_PKG_NAME");
+
+    format!("{PLUGIN_NAME}/{}", chain_id)
+}
+
+impl Module {
+    fn plugin_name(&self) -> String {
+        plugin_name(&self.chain_id)
+    }
+}
+
+impl Plugin for Module {
+    type Call = ModuleCall;
+    type Callback = ModuleCallback;
+
+    type Config = Config;
+    type Cmd = DefaultCmd;
+
+    async fn new(config: Self::Config) -> Result<Self, BoxDynError> {
+        let l1_provider = ProviderBuilder::new()
+            .on_builtin(&config.l1_rpc_url)
+            .await?;
+
+        let l2_provider = ProviderBuilder::new()
+            .on_builtin(&config.l2_rpc_url)
+            .await?;
+
+        let chain_id = ChainId::new(l2_provider.get_chain_id().await?.to_string());
+
+        if chain_id != config.chain_id {
+            return Err(format!(
+                "incorrect chain id: expected `{}`, but found `{}`",
+                config.chain_id, chain_id
+            )
+            .into());
+        }
+
+        Ok(Self {
+            chain_id,
+            l1_client_id: config.l1_client_id,
+            ibc_handler_address: config.ibc_handler_address,
+            rollup: Rollup::new(config.rollup, l1_provider, l2_provider),
+        })
+    }
+
+    fn info(config: Self::Config) -> PluginInfo {
+        PluginInfo {
+            name: plugin_name(&config.chain_id),
+            interest_filter: UpdateHook::filter(&config.chain_id),
+        }
+    }
+
+    async fn cmd(_config: Self::Config, cmd: Self::Cmd) {
+        match cmd {}
+    }
+}
+
+#[async_trait]
+impl PluginServer<ModuleCall, ModuleCallback> for Module {
+    #[instrument(skip_all, fields(chain_id = %self.chain_id))]
+    async fn run_pass(
+        &self,
+        _: &Extensions,
+        msgs: Vec<Op<VoyagerMessage>>,
+    ) -> RpcResult<PassResult<VoyagerMessage>> {
+        Ok(PassResult {
+            optimize_further: vec![],
+            ready: msgs
+                .into_iter()
+                .map(|mut op| {
+                    UpdateHook::new(&self.chain_id, |fetch| {
+                        Call::Plugin(PluginMessage::new(
+                            self.plugin_name(),
+                            ModuleCall::from(FetchUpdate {
+                                from_height: fetch.update_from,
+                                to_height: fetch.update_to,
+                                counterparty_chain_id: fetch.counterparty_chain_id.clone(),
+                            }),
+                        ))
+                    })
+                    .visit_op(&mut op);
+
+                    op
+                })
+                .enumerate()
+                .map(|(i, op)| (vec![i], op))
+                .collect(),
+        })
+    }
+
+    #[instrument(skip_all, fields(chain_id = %self.chain_id))]
+    async fn call(&self, e: &Extensions, msg: ModuleCall) -> RpcResult<Op<VoyagerMessage>> {
+        match msg {
+            ModuleCall::FetchUpdate(FetchUpdate {
+                from_height,
+                to_height,
+                counterparty_chain_id,
+            }) => {
+                self.fetch_update(
+                    e.try_get::<VoyagerClient>()?,
+                    from_height,
+                    to_height,
+                    counterparty_chain_id,
+                )
+                .await
+            }
+        }
+    }
+
+    #[instrument(skip_all, fields(chain_id = %self.chain_id))]
+    async fn callback(
+        &self,
+        _: &Extensions,
+        cb: ModuleCallback,
+        _data: VecDeque<Data>,
+    ) -> RpcResult<Op<VoyagerMessage>> {
+        match cb {}
+    }
+}
+
+impl Module {
+    /// Fetch a client update from the provided trusted height (`update_from`) to at least the
+    /// desired new height (`update_to`).
+    ///
+    /// Arbitrum headers are verified against the consensus state of the l1 client on the
+    /// counterparty at the l1 height of the header, so the update is always generated at the
+    /// latest height of the l1 client. If the l1 client has not yet been updated to `update_to`,
+    /// it is updated first and the update is fetched again once that update has landed.
+    #[instrument(
+        skip_all,
+        fields(
+            chain_id = %self.chain_id,
+            %counterparty_chain_id,
+            %update_from,
+            %update_to
+        )
+    )]
+    async fn fetch_update(
+        &self,
+        voyager_client: &VoyagerClient,
+        update_from: Height,
+        update_to: Height,
+        counterparty_chain_id: ChainId,
+    ) -> RpcResult<Op<VoyagerMessage>> {
+        let l1_client_meta = voyager_client
+            .client_meta::<IbcUnion>(
+                counterparty_chain_id.clone(),
+                QueryHeight::Latest,
+                self.l1_client_id,
+            )
+            .await?;
+
+        if l1_client_meta.height.height() < update_to.height() {
+            info!(
+                l1_client_id = self.l1_client_id,
+                l1_client_height = %l1_client_meta.height,
+                "l1 client is behind the requested height, updating the l1 client first"
+            );
+
+            let l1_client_id = RawClientId::new(self.l1_client_id);
+
+            return Ok(seq([
+                promise(
+                    [promise(
+                        [call(FetchUpdateHeaders {
+                            chain_id: l1_client_meta.chain_id,
+                            counterparty_chain_id: counterparty_chain_id.clone(),
+                            update_from: l1_client_meta.height,
+                            update_to,
+                        })],
+                        [],
+                        AggregateMsgUpdateClientsFromOrderedHeaders {
+                            ibc_spec_id: IbcUnion::ID,
+                            chain_id: counterparty_chain_id.clone(),
+                            counterparty_client_id: l1_client_id.clone(),
+                        },
+                    )],
+                    [],
+                    AggregateSubmitTxFromOrderedClientUpdates {
+                        chain_id: counterparty_chain_id.clone(),
+                    },
+                ),
+                call(WaitForTrustedHeight {
+                    chain_id: counterparty_chain_id.clone(),
+                    ibc_spec_id: IbcUnion::ID,
+                    client_id: l1_client_id,
+                    height: update_to,
+                    deadline: None,
+                }),
+                call(FetchUpdateHeaders {
+                    chain_id: self.chain_id.clone(),
+                    counterparty_chain_id,
+                    update_from,
+                    update_to,
+                }),
+            ]));
+        }
+
+        let header = self.make_header(l1_client_meta.height.height()).await?;
+
+        Ok(data(OrderedHeaders {
+            headers: vec![(
+                DecodedHeaderMeta {
+                    height: header.l1_height,
+                },
+                into_value(header),
+            )],
+        }))
+    }
+
+    /// Build a header proving the l2 block asserted by the latest node confirmed on the l1 at
+    /// `l1_height`.
+    #[instrument(skip_all, fields(chain_id = %self.chain_id, %l1_height))]
+    async fn make_header(&self, l1_height: u64) -> RpcResult<Header> {
+        let node_num = self
+            .rollup
+            .latest_confirmed_at(l1_height)
+            .await
+            .map_err(|e| {
+                ErrorObject::owned(
+                    -1,
+                    ErrorReporter(e).with_message("error fetching latest confirmed node"),
+                    None::<()>,
+                )
+            })?;
+
+        let config = &self.rollup.config;
+
+        let rollup_proof = self
+            .rollup
+            .l1_provider
+            .get_proof(
+                config.l1_contract_address.into(),
+                [
+                    config.l1_next_node_num_slot,
+                    config.nodes_confirm_data_slot(node_num),
+                ]
+                .into_iter()
+                .map(|slot| slot.to_be_bytes().into())
+                .collect(),
+            )
+            .block_id(l1_height.into())
+            .await
+            .map_err(|e| {
+                ErrorObject::owned(
+                    -1,
+                    ErrorReporter(e).with_message("error fetching rollup contract proof"),
+                    None::<()>,
+                )
+            })?;
+
+        let [l1_next_node_num_slot_proof, l1_nodes_slot_proof] =
+            <[_; 2]>::try_from(rollup_proof.storage_proof)
+                .map_err(|invalid| {
+                    ErrorObject::owned(
+                        -1,
+                        format!(
+                            "received invalid response from eth_getProof, expected \
+                            length of 2 but got `{invalid:#?}`"
+                        ),
+                        None::<()>,
+                    )
+                })?
+                .map(|proof| StorageProof {
+                    key: U256::from_be_bytes(proof.key.as_b256().0),
+                    value: U256::from_be_bytes(proof.value.to_be_bytes()),
+                    proof: proof
+                        .proof
+                        .into_iter()
+                        .map(|bytes| bytes.to_vec())
+                        .collect(),
+                });
+
+        let block_hash = self
+            .rollup
+            .l2_block_hash_of_node(node_num)
+            .await
+            .map_err(|e| {
+                ErrorObject::owned(
+                    -1,
+                    ErrorReporter(e).with_message(&format!(
+                        "error fetching the l2 block asserted by node {node_num}"
+                    )),
+                    None::<()>,
+                )
+            })?;
+
+        let block = self
+            .rollup
+            .l2_provider
+            .get_block_by_hash(block_hash.into(), BlockTransactionsKind::Hashes)
+            .await
+            .map_err(|e| {
+                ErrorObject::owned(
+                    -1,
+                    ErrorReporter(e).with_message("error fetching l2 block"),
+                    None::<()>,
+                )
+            })?
+            .ok_or_else(|| {
+                ErrorObject::owned(-1, format!("l2 block {block_hash} not found"), None::<()>)
+            })?;
+
+        debug!(
+            "l1 height {l1_height} is node {node_num}, which asserts l2 block {}",
+            block.header.number
+        );
+
+        let l2_ibc_account_proof = self
+            .rollup
+            .l2_provider
+            .get_proof(self.ibc_handler_address.get().into(), vec![])
+            .block_id(block.header.number.into())
+            .await
+            .map_err(|e| {
+                ErrorObject::owned(
+                    -1,
+                    ErrorReporter(e).with_message("error fetching ibc handler account proof"),
+                    None::<()>,
+                )
+            })?;
+
+        Ok(Header {
+            l1_height: Height::new(l1_height),
+            l1_account_proof: AccountProof {
+                storage_root: rollup_proof.storage_hash.into(),
+                proof: rollup_proof
+                    .account_proof
+                    .into_iter()
+                    .map(|bytes| bytes.to_vec())
+                    .collect(),
+            },
+            l2_ibc_account_proof: AccountProof {
+                storage_root: l2_ibc_account_proof.storage_hash.into(),
+                proof: l2_ibc_account_proof
+                    .account_proof
+                    .into_iter()
+                    .map(|bytes| bytes.to_vec())
+                    .collect(),
+            },
+            l1_next_node_num_slot_proof,
+            l1_nodes_slot_proof,
+            l2_header: l2_header(&block)?,
+        })
+    }
+}
+
+fn l2_header(block: &Block) -> RpcResult<L2Header> {
+    let header = &block.header;
+
+    Ok(L2Header {
+        parent_hash: header.parent_hash.into(),
+        sha3_uncles: header.ommers_hash.into(),
+        miner: header.beneficiary.into(),
+        state_root: header.state_root.into(),
+        transactions_root: header.transactions_root.into(),
+        receipts_root: header.receipts_root.into(),
+        logs_bloom: Box::new(header.logs_bloom.0.into()),
+        difficulty: U256::from_be_bytes(header.difficulty.to_be_bytes()),
+        number: header.number.into(),
+        gas_limit: header.gas_limit,
+        gas_used: header.gas_used,
+        timestamp: header.timestamp,
+        // arbitrum stores the send root of the block in the extra data
+        extra_data: H256::try_from(&header.extra_data[..]).map_err(|e| {
+            ErrorObject::owned(
+                -1,
+                ErrorReporter(e).with_message(&format!(
+                    "the extra data of l2 block {} is not 32 bytes",
+                    header.number
+                )),
+                None::<()>,
+            )
+        })?,
+        mix_hash: header.mix_hash.into(),
+        nonce: header.nonce.into(),
+        base_fee_per_gas: header
+            .base_fee_per_gas
+            .ok_or_else(|| {
+                ErrorObject::owned(
+                    -1,
+                    format!("l2 block {} has no base fee", header.number),
+                    None::<()>,
+                )
+            })?
+            .into(),
+    })
+}
@@ -7,6 +7,7 @@ use unionlabs::ibc::core::client::height::Height;
 #[allow(clippy::large_enum_variant)]
 pub enum ModuleCall {
     FetchUpdate(FetchUpdate),
+    FetchHeader(FetchHeader),
     FetchProveRequest(FetchProveRequest),
 }
 
@@ -16,6 +17,13 @@ pub struct FetchUpdate {
     pub update_to: Height,
 }
 
+/// Fetch a single header for `update_to`, trusting the validators at `update_from`.
+#[model]
+pub struct FetchHeader {
+    pub update_from: Height,
+    pub update_to: Height,
+}
+
 #[model]
 pub struct FetchProveRequest {
     pub request: galois_rpc::prove_request::ProveRequest,
@@ -18,6 +18,7 @@ use crate::{data::ProveResponse, Module};
 #[allow(clippy::large_enum_variant)]
 pub enum ModuleCallback {
     AggregateHeader(AggregateHeader),
+    AggregateOrderedHeaders(AggregateOrderedHeaders),
 }
 
 #[model]
@@ -30,6 +31,10 @@ pub struct AggregateHeader {
     pub update_to: Height,
 }
 
+/// Concatenate the [`OrderedHeaders`] of each hop of a skipping update.
+#[model]
+pub struct AggregateOrderedHeaders {}
+
 impl Module {
     pub fn aggregate_header(
         &self,
@@ -73,4 +78,20 @@ impl Module {
             )],
         })
     }
+
+    pub fn aggregate_ordered_headers(
+        &self,
+        AggregateOrderedHeaders {}: AggregateOrderedHeaders,
+        hops: Vec<OrderedHeaders>,
+    ) -> Op<VoyagerMessage> {
+        let mut headers = hops
+            .into_iter()
+            .flat_map(|hop| hop.headers)
+            .collect::<Vec<_>>();
+
+        // the hops are not guaranteed to resolve in order
+        headers.sort_by_key(|(meta, _)| meta.height);
+
+        data(OrderedHeaders { headers })
+    }
 }
@@ -21,13 +21,14 @@ use galois_rpc::{
 use itertools::Itertools;
 use jsonrpsee::{
     core::{async_trait, RpcResult},
+    types::ErrorObject,
     Extensions,
 };
 use num_bigint::BigUint;
 use protos::union::galois::api::v3::union_prover_api_client;
 use serde::{Deserialize, Serialize};
 use tracing::{debug, error, info, instrument, trace};
-use unionlabs::bounded::BoundedI64;
+use unionlabs::{bounded::BoundedI64, ibc::core::client::height::Height, ErrorReporter};
 use voyager_message::{
     call::{Call, WaitForHeight},
     core::ChainId,
@@ -41,8 +42,8 @@ use voyager_vm::{
 };
 
 use crate::{
-    call::{FetchProveRequest, FetchUpdate, ModuleCall},
-    callback::{AggregateHeader, ModuleCallback},
+    call::{FetchHeader, FetchProveRequest, FetchUpdate, ModuleCall},
+    callback::{AggregateHeader, AggregateOrderedHeaders, ModuleCallback},
     data::{ModuleData, ProveResponse},
 };
 
@@ -62,6 +63,7 @@ pub struct Module {
     pub tm_client: cometbft_rpc::Client,
     pub chain_revision: u64,
     pub grpc_url: String,
+    pub trust_level: cometbft_rpc::TrustLevel,
 
     pub prover_endpoints: Vec<String>,
 }
@@ -71,7 +73,18 @@ pub struct Config {
     pub chain_id: ChainId,
 
     pub ws_url: String,
+    /// Fallback endpoints to use if `ws_url` is unavailable.
+    #[serde(default)]
+    pub failover: cometbft_rpc::FailoverConfig,
+    /// An archival endpoint to send queries at old heights to.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub archival: Option<cometbft_rpc::ArchivalConfig>,
     pub grpc_url: String,
+    /// The trust level of the clients tracking this chain. Updates skip directly to the target
+    /// height if validators with more than this fraction of the trusted voting power signed it,
+    /// and go through intermediate headers otherwise.
+    #[serde(default)]
+    pub trust_level: cometbft_rpc::TrustLevel,
 
     pub prover_endpoints: Vec<String>,
 }
@@ -84,7 +97,12 @@ impl Plugin for Module {
     type Cmd = DefaultCmd;
 
     async fn new(config: Self::Config) -> Result<Self, BoxDynError> {
-        let tm_client = cometbft_rpc::Client::new(config.ws_url).await?;
+        let mut tm_client =
+            cometbft_rpc::Client::new_with_failover(config.ws_url, &config.failover).await?;
+
+        if let Some(archival) = &config.archival {
+            tm_client = tm_client.with_archival(archival).await?;
+        }
 
         let chain_id = tm_client.status().await?.node_info.network.to_string();
 
@@ -115,6 +133,7 @@ impl Plugin for Module {
             chain_revision,
             prover_endpoints: config.prover_endpoints,
             grpc_url: config.grpc_url,
+            trust_level: config.trust_level,
         })
     }
 
@@ -188,6 +207,66 @@ impl PluginServer<ModuleCall, ModuleCallback> for Module {
             ModuleCall::FetchUpdate(FetchUpdate {
                 update_from,
                 update_to,
+            }) => {
+                let heights = self
+                    .tm_client
+                    .skipping_update_heights(
+                        update_from.height().try_into().unwrap(),
+                        update_to.height().try_into().unwrap(),
+                        self.trust_level,
+                    )
+                    .await
+                    .map_err(|err| {
+                        ErrorObject::owned(
+                            -1,
+                            format!(
+                                "error computing the headers to update from {update_from} to \
+                                {update_to}: {}",
+                                ErrorReporter(err)
+                            ),
+                            None::<()>,
+                        )
+                    })?;
+
+                debug!(
+                    %update_from,
+                    %update_to,
+                    hops = heights.len(),
+                    "fetching headers"
+                );
+
+                let fetch_headers = heights
+                    .into_iter()
+                    .scan(update_from, |trusted_height, height| {
+                        let untrusted_height =
+                            Height::new_with_revision(update_from.revision(), height.get());
+
+                        let fetch_header = call(PluginMessage::new(
+                            self.plugin_name(),
+                            ModuleCall::from(FetchHeader {
+                                update_from: *trusted_height,
+                                update_to: untrusted_height,
+                            }),
+                        ));
+
+                        *trusted_height = untrusted_height;
+
+                        Some(fetch_header)
+                    })
+                    .collect::<Vec<_>>();
+
+                Ok(promise(
+                    fetch_headers,
+                    [],
+                    PluginMessage::new(
+                        self.plugin_name(),
+                        ModuleCallback::from(AggregateOrderedHeaders {}),
+                    ),
+                ))
+            }
+            ModuleCall::FetchHeader(FetchHeader {
+                update_from,
+                update_to,
             }) => {
                 let trusted_validators = self
                     .tm_client
@@ -312,6 +391,7 @@ impl PluginServer<ModuleCall, ModuleCallback> for Module {
                         chain_id: self.chain_id.clone(),
                         height: update_to,
                         finalized: true,
+                        deadline: None,
                     })),
                     promise(
                         [call(PluginMessage::new(
@@ -445,6 +525,10 @@ impl PluginServer<ModuleCall, ModuleCallback> for Module {
                     .try_into()
                     .unwrap(),
             ),
+            ModuleCallback::AggregateOrderedHeaders(aggregate) => self.aggregate_ordered_headers(
+                aggregate,
+                data.into_iter().map(|d| d.try_into().unwrap()).collect(),
+            ),
         })
     }
 }
@@ -12,6 +12,7 @@ use beacon_api_types::{
     light_client_update::NextSyncCommitteeBranch, PresetBaseKind, SyncCommittee,
 };
 use bitvec::{order::Msb0, vec::BitVec};
+use chain_utils::evm_proof::ContractProof;
 use ethereum_light_client_types::{
     AccountProof, EpochChangeUpdate, Header, LightClientUpdate, LightClientUpdateData,
     WithinEpochUpdate,
@@ -81,6 +82,10 @@ pub struct Config {
     pub eth_rpc_api: String,
     /// The RPC endpoint for the beacon chain.
     pub eth_beacon_rpc_api: String,
+    /// Additional RPC endpoints for the beacon chain, used (in order) if a request to
+    /// [`Self::eth_beacon_rpc_api`] fails.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub eth_beacon_rpc_api_fallbacks: Vec<String>,
 }
 
 fn plugin_name(chain_id: &ChainId) -> String {
@@ -94,30 +99,29 @@ impl Module {
         plugin_name(&self.chain_id)
     }
 
-    pub async fn fetch_account_update(&self, block_number: u64) -> RpcResult<AccountProof> {
-        let account_update = self
-            .provider
-            .get_proof(self.ibc_handler_address.into(), vec![])
-            .block_id(
-                // NOTE: Proofs are from the execution layer, so we use execution height, not beacon slot.
-                block_number.into(),
+    /// Fetch the account proof of the IBC handler at `block_number`, verified against the
+    /// `state_root` of the execution payload of the (finalized) beacon header the client is being
+    /// updated to.
+    pub async fn fetch_account_update(
+        &self,
+        block_number: u64,
+        state_root: H256,
+    ) -> RpcResult<AccountProof> {
+        ContractProof::fetch_verified(
+            &self.provider,
+            self.ibc_handler_address,
+            vec![],
+            block_number,
+            state_root,
+        )
+        .await
+        .map(|proof| proof.account_proof)
+        .map_err(|e| {
+            ErrorObject::owned(
+                -1,
+                ErrorReporter(e).with_message("error fetching account update"),
+                None::<()>,
             )
-            .await
-            .map_err(|e| {
-                ErrorObject::owned(
-                    -1,
-                    ErrorReporter(e).with_message("error fetching account update"),
-                    None::<()>,
-                )
-            })?;
-
-        Ok(AccountProof {
-            storage_root: account_update.storage_hash.into(),
-            proof: account_update
-                .account_proof
-                .into_iter()
-                .map(|x| x.to_vec())
-                .collect(),
         })
     }
 }
@@ -144,7 +148,11 @@ impl Plugin for Module {
             .into());
         }
 
-        let beacon_api_client = BeaconApiClient::new(config.eth_beacon_rpc_api).await?;
+        let beacon_api_client = BeaconApiClient::new_with_fallbacks(
+            config.eth_beacon_rpc_api,
+            config.eth_beacon_rpc_api_fallbacks,
+        )
+        .await?;
 
         let spec = beacon_api_client
             .spec()
@@ -385,32 +393,23 @@ impl Module {
 
         info!("target period: {target_period}, trusted period: {trusted_period}");
 
-        assert!(
-            trusted_period <= target_period,
-            "trusted period {trusted_period} is behind target \
-            period {target_period}, something is wrong!",
-        );
+        if trusted_period > target_period {
+            return Err(ErrorObject::owned(
+                -1,
+                format!(
+                    "trusted period {trusted_period} is ahead of target period {target_period}, \
+                    something is wrong!"
+                ),
+                None::<()>,
+            )
+            .into());
+        }
 
         // Eth chain is more than 1 signature period ahead of us. We need to do sync committee
         // updates until we reach the `target_period - 1`.
-
-        // let target_period = sync_committee_period(finality_update.signature_slot, spec.period());
-
         let light_client_updates = self
-            .beacon_api_client
-            .light_client_updates(trusted_period + 1, target_period - trusted_period)
-            .await
-            .map_err(|e| {
-                ErrorObject::owned(
-                    -1,
-                    ErrorReporter(e).with_message("error fetching light client updates"),
-                    None::<()>,
-                )
-            })?
-            .0
-            .into_iter()
-            .map(|x| x.data)
-            .collect::<Vec<_>>();
+            .fetch_light_client_updates(trusted_period, target_period)
+            .await?;
 
         info!(
             "fetched {} light client updates",
@@ -535,6 +534,7 @@ impl Module {
                 .expect("if this fails good luck")
                     * NANOS_PER_SECOND as i64,
                 finalized: false,
+                deadline: None,
             }),
             voyager_vm::data(OrderedHeaders {
                 headers: headers
@@ -559,6 +559,33 @@ impl Module {
         ]))
     }
 
+    /// Fetch one light client update per sync committee period after `trusted_period`, up to and
+    /// including `target_period`. Each update contains the next sync committee, so applying them in
+    /// order moves the client across every period boundary in between.
+    async fn fetch_light_client_updates(
+        &self,
+        trusted_period: u64,
+        target_period: u64,
+    ) -> RpcResult<Vec<beacon_api_types::light_client_update::LightClientUpdate>> {
+        // the beacon node may not have updates for all of the requested periods (for example if it
+        // was checkpoint synced recently), in which case the client can't be updated past the
+        // first missing period unless one of the fallback nodes has them
+        self.beacon_api_client
+            .light_client_updates_range(
+                trusted_period + 1,
+                target_period.saturating_sub(trusted_period),
+            )
+            .await
+            .map(|updates| updates.into_iter().map(|update| update.data).collect())
+            .map_err(|e| {
+                ErrorObject::owned(
+                    -1,
+                    ErrorReporter(e).with_message("error fetching light client updates"),
+                    None::<()>,
+                )
+            })
+    }
+
     #[instrument(
         skip_all,
         fields(
@@ -589,6 +616,10 @@ impl Module {
                     .finalized_header
                     .execution
                     .block_number,
+                light_client_update_data
+                    .finalized_header
+                    .execution
+                    .state_root,
             )
             .await?;
 
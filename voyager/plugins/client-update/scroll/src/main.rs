@@ -0,0 +1,506 @@
+#![warn(clippy::unwrap_used)]
+
+use std::collections::VecDeque;
+
+use alloy::{
+    providers::{Provider, ProviderBuilder, RootProvider},
+    sol_types::SolCall,
+    transports::BoxTransport,
+};
+use ethereum_light_client_types::{AccountProof, StorageProof};
+use ibc_union_spec::IbcUnion;
+use jsonrpsee::{
+    core::{async_trait, RpcResult},
+    types::ErrorObject,
+    Extensions,
+};
+use scroll_api::ScrollClient;
+use scroll_codec::{finalizeBundleCall, finalizeBundleWithProofCall};
+use scroll_light_client_types::Header;
+use serde::{Deserialize, Serialize};
+use tracing::{debug, info, instrument};
+use unionlabs::{
+    ethereum::slot::{MappingKey, Slot},
+    hash::{H160, H256},
+    ibc::core::client::height::Height,
+    uint::U256,
+    ErrorReporter,
+};
+use voyager_message::{
+    call::{Call, FetchUpdateHeaders, WaitForTrustedHeight},
+    callback::{
+        AggregateMsgUpdateClientsFromOrderedHeaders, AggregateSubmitTxFromOrderedClientUpdates,
+    },
+    core::{ChainId, IbcSpec, QueryHeight},
+    data::{Data, DecodedHeaderMeta, OrderedHeaders},
+    hook::UpdateHook,
+    into_value,
+    module::{PluginInfo, PluginServer},
+    DefaultCmd, ExtensionsExt, Plugin, PluginMessage, RawClientId, VoyagerClient, VoyagerMessage,
+};
+use voyager_vm::{call, data, pass::PassResult, promise, seq, BoxDynError, Op, Visit};
+
+use crate::{
+    call::{FetchUpdate, ModuleCall},
+    callback::ModuleCallback,
+};
+
+pub mod call;
+pub mod callback;
+
+#[tokio::main(flavor = "multi_thread")]
+async fn main() {
+    Module::run().await
+}
+
+#[derive(Debug, Clone)]
+pub struct Module {
+    pub chain_id: ChainId,
+
+    pub l1_client_id: u32,
+
+    /// The address of the `IBCHandler` smart contract.
+    pub ibc_handler_address: H160,
+
+    /// The address of the `ScrollChain` rollup contract on the l1.
+    pub rollup_contract_address: H160,
+    pub rollup_finalized_state_roots_slot: U256,
+    pub rollup_last_finalized_batch_index_slot: U256,
+    pub rollup_committed_batches_slot: U256,
+
+    pub l1_provider: RootProvider<BoxTransport>,
+
+    pub scroll_api_client: ScrollClient,
+    pub scroll_rpc: scroll_rpc::JsonRpcClient,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    pub chain_id: ChainId,
+
+    /// The id of the client tracking the l1 on the chain hosting the scroll client.
+    pub l1_client_id: u32,
+
+    /// The address of the `IBCHandler` smart contract.
+    pub ibc_handler_address: H160,
+
+    /// The address of the `ScrollChain` rollup contract on the l1.
+    pub rollup_contract_address: H160,
+    /// The slot of `ScrollChain.finalizedStateRoots`.
+    pub rollup_finalized_state_roots_slot: U256,
+    /// The slot of `ScrollChain.lastFinalizedBatchIndex`.
+    pub rollup_last_finalized_batch_index_slot: U256,
+    /// The slot of `ScrollChain.committedBatches`.
+    pub rollup_committed_batches_slot: U256,
+
+    /// The RPC endpoint for the l1 execution chain.
+    pub l1_rpc_url: String,
+    /// The RPC endpoint for scroll.
+    pub scroll_rpc_url: String,
+    /// The endpoint of the scroll api, used to map batches to scroll blocks.
+    pub scroll_api_url: String,
+}
+
+fn plugin_name(chain_id: &ChainId) -> String {
+    pub const PLUGIN_NAME: &str = env!("CARGO_PKG_NAME");
+
+    format!("{PLUGIN_NAME}/{}", chain_id)
+}
+
+impl Module {
+    fn plugin_name(&self) -> String {
+        plugin_name(&self.chain_id)
+    }
+}
+
+impl Plugin for Module {
+    type Call = ModuleCall;
+    type Callback = ModuleCallback;
+
+    type Config = Config;
+    type Cmd = DefaultCmd;
+
+    async fn new(config: Self::Config) -> Result<Self, BoxDynError> {
+        let chain_id = ChainId::new(
+            ProviderBuilder::new()
+                .on_builtin(&config.scroll_rpc_url)
+                .await?
+                .get_chain_id()
+                .await?
+                .to_string(),
+        );
+
+        if chain_id != config.chain_id {
+            return Err(format!(
+                "incorrect chain id: expected `{}`, but found `{}`",
+                config.chain_id, chain_id
+            )
+            .into());
+        }
+
+        Ok(Self {
+            chain_id,
+            l1_client_id: config.l1_client_id,
+            ibc_handler_address: config.ibc_handler_address,
+            rollup_contract_address: config.rollup_contract_address,
+            rollup_finalized_state_roots_slot: config.rollup_finalized_state_roots_slot,
+            rollup_last_finalized_batch_index_slot: config.rollup_last_finalized_batch_index_slot,
+            rollup_committed_batches_slot: config.rollup_committed_batches_slot,
+            l1_provider: ProviderBuilder::new()
+                .on_builtin(&config.l1_rpc_url)
+                .await?,
+            scroll_api_client: ScrollClient::new(config.scroll_api_url),
+            scroll_rpc: scroll_rpc::JsonRpcClient::new(config.scroll_rpc_url).await?,
+        })
+    }
+
+    fn info(config: Self::Config) -> PluginInfo {
+        PluginInfo {
+            name: plugin_name(&config.chain_id),
+            interest_filter: UpdateHook::filter(&config.chain_id),
+        }
+    }
+
+    async fn cmd(_config: Self::Config, cmd: Self::Cmd) {
+        match cmd {}
+    }
+}
+
+#[async_trait]
+impl PluginServer<ModuleCall, ModuleCallback> for Module {
+    #[instrument(skip_all, fields(chain_id = %self.chain_id))]
+    async fn run_pass(
+        &self,
+        _: &Extensions,
+        msgs: Vec<Op<VoyagerMessage>>,
+    ) -> RpcResult<PassResult<VoyagerMessage>> {
+        Ok(PassResult {
+            optimize_further: vec![],
+            ready: msgs
+                .into_iter()
+                .map(|mut op| {
+                    UpdateHook::new(&self.chain_id, |fetch| {
+                        Call::Plugin(PluginMessage::new(
+                            self.plugin_name(),
+                            ModuleCall::from(FetchUpdate {
+                                from_height: fetch.update_from,
+                                to_height: fetch.update_to,
+                                counterparty_chain_id: fetch.counterparty_chain_id.clone(),
+                            }),
+                        ))
+                    })
+                    .visit_op(&mut op);
+
+                    op
+                })
+                .enumerate()
+                .map(|(i, op)| (vec![i], op))
+                .collect(),
+        })
+    }
+
+    #[instrument(skip_all, fields(chain_id = %self.chain_id))]
+    async fn call(&self, e: &Extensions, msg: ModuleCall) -> RpcResult<Op<VoyagerMessage>> {
+        match msg {
+            ModuleCall::FetchUpdate(FetchUpdate {
+                from_height,
+                to_height,
+                counterparty_chain_id,
+            }) => {
+                self.fetch_update(
+                    e.try_get::<VoyagerClient>()?,
+                    from_height,
+                    to_height,
+                    counterparty_chain_id,
+                )
+                .await
+            }
+        }
+    }
+
+    #[instrument(skip_all, fields(chain_id = %self.chain_id))]
+    async fn callback(
+        &self,
+        _: &Extensions,
+        cb: ModuleCallback,
+        _data: VecDeque<Data>,
+    ) -> RpcResult<Op<VoyagerMessage>> {
+        match cb {}
+    }
+}
+
+impl Module {
+    /// Fetch a client update from the provided trusted height (`update_from`) to at least the
+    /// desired new height (`update_to`).
+    ///
+    /// Scroll headers are verified against the consensus state of the l1 client on the
+    /// counterparty at the l1 height of the header, so the update is always generated at the
+    /// latest height of the l1 client. If the l1 client has not yet been updated to `update_to`,
+    /// it is updated first and the update is fetched again once that update has landed.
+    #[instrument(
+        skip_all,
+        fields(
+            chain_id = %self.chain_id,
+            %counterparty_chain_id,
+            %update_from,
+            %update_to
+        )
+    )]
+    async fn fetch_update(
+        &self,
+        voyager_client: &VoyagerClient,
+        update_from: Height,
+        update_to: Height,
+        counterparty_chain_id: ChainId,
+    ) -> RpcResult<Op<VoyagerMessage>> {
+        let l1_client_meta = voyager_client
+            .client_meta::<IbcUnion>(
+                counterparty_chain_id.clone(),
+                QueryHeight::Latest,
+                self.l1_client_id,
+            )
+            .await?;
+
+        if l1_client_meta.height.height() < update_to.height() {
+            info!(
+                l1_client_id = self.l1_client_id,
+                l1_client_height = %l1_client_meta.height,
+                "l1 client is behind the requested height, updating the l1 client first"
+            );
+
+            let l1_client_id = RawClientId::new(self.l1_client_id);
+
+            return Ok(seq([
+                promise(
+                    [promise(
+                        [call(FetchUpdateHeaders {
+                            chain_id: l1_client_meta.chain_id,
+                            counterparty_chain_id: counterparty_chain_id.clone(),
+                            update_from: l1_client_meta.height,
+                            update_to,
+                        })],
+                        [],
+                        AggregateMsgUpdateClientsFromOrderedHeaders {
+                            ibc_spec_id: IbcUnion::ID,
+                            chain_id: counterparty_chain_id.clone(),
+                            counterparty_client_id: l1_client_id.clone(),
+                        },
+                    )],
+                    [],
+                    AggregateSubmitTxFromOrderedClientUpdates {
+                        chain_id: counterparty_chain_id.clone(),
+                    },
+                ),
+                call(WaitForTrustedHeight {
+                    chain_id: counterparty_chain_id.clone(),
+                    ibc_spec_id: IbcUnion::ID,
+                    client_id: l1_client_id,
+                    height: update_to,
+                    deadline: None,
+                }),
+                call(FetchUpdateHeaders {
+                    chain_id: self.chain_id.clone(),
+                    counterparty_chain_id,
+                    update_from,
+                    update_to,
+                }),
+            ]));
+        }
+
+        let header = self.make_header(l1_client_meta.height.height()).await?;
+
+        Ok(data(OrderedHeaders {
+            headers: vec![(
+                DecodedHeaderMeta {
+                    height: header.l1_height,
+                },
+                into_value(header),
+            )],
+        }))
+    }
+
+    /// Build a header proving the latest batch finalized on the l1 at `l1_height`.
+    #[instrument(skip_all, fields(chain_id = %self.chain_id, %l1_height))]
+    async fn make_header(&self, l1_height: u64) -> RpcResult<Header> {
+        let batch_index = self
+            .l1_provider
+            .get_storage_at(
+                self.rollup_contract_address.into(),
+                alloy::primitives::U256::from_be_bytes(
+                    self.rollup_last_finalized_batch_index_slot.to_be_bytes(),
+                ),
+            )
+            .block_id(l1_height.into())
+            .await
+            .map_err(|e| {
+                ErrorObject::owned(
+                    -1,
+                    ErrorReporter(e).with_message("error fetching last finalized batch index"),
+                    None::<()>,
+                )
+            })?;
+
+        let batch_index = U256::from_be_bytes(batch_index.to_be_bytes());
+
+        let rollup_proof = self
+            .l1_provider
+            .get_proof(
+                self.rollup_contract_address.into(),
+                [
+                    self.rollup_last_finalized_batch_index_slot,
+                    Slot::Mapping(
+                        &Slot::Offset(self.rollup_finalized_state_roots_slot),
+                        MappingKey::Uint256(batch_index),
+                    )
+                    .slot(),
+                    Slot::Mapping(
+                        &Slot::Offset(self.rollup_committed_batches_slot),
+                        MappingKey::Uint256(batch_index),
+                    )
+                    .slot(),
+                ]
+                .into_iter()
+                .map(|slot| slot.to_be_bytes().into())
+                .collect(),
+            )
+            .block_id(l1_height.into())
+            .await
+            .map_err(|e| {
+                ErrorObject::owned(
+                    -1,
+                    ErrorReporter(e).with_message("error fetching rollup contract proof"),
+                    None::<()>,
+                )
+            })?;
+
+        let [last_batch_index_proof, l2_state_root_proof, batch_hash_proof] =
+            <[_; 3]>::try_from(rollup_proof.storage_proof)
+                .map_err(|invalid| {
+                    ErrorObject::owned(
+                        -1,
+                        format!(
+                            "received invalid response from eth_getProof, expected \
+                            length of 3 but got `{invalid:#?}`"
+                        ),
+                        None::<()>,
+                    )
+                })?
+                .map(|proof| StorageProof {
+                    key: U256::from_be_bytes(proof.key.as_b256().0),
+                    value: U256::from_be_bytes(proof.value.to_be_bytes()),
+                    proof: proof
+                        .proof
+                        .into_iter()
+                        .map(|bytes| bytes.to_vec())
+                        .collect(),
+                });
+
+        let batch_index = u64::try_from(batch_index).map_err(|()| {
+            ErrorObject::owned(
+                -1,
+                format!("last finalized batch index {batch_index} is out of range"),
+                None::<()>,
+            )
+        })?;
+
+        let batch = self.scroll_api_client.batch(batch_index).await.batch;
+
+        debug!(
+            "l1 height {l1_height} is batch index {batch_index}, which is scroll height range {}..={}",
+            batch.start_block_number, batch.end_block_number
+        );
+
+        let batch_header = self
+            .batch_header(batch_index, batch.finalize_tx_hash)
+            .await?;
+
+        // scroll uses a zktrie for its state, so the proof must be fetched with the scroll rpc
+        let l2_ibc_account_proof = self
+            .scroll_rpc
+            .get_proof(
+                self.ibc_handler_address,
+                [],
+                scroll_rpc::BlockId::Number(batch.end_block_number),
+            )
+            .await
+            .map_err(|e| {
+                ErrorObject::owned(
+                    -1,
+                    ErrorReporter(e).with_message("error fetching ibc handler account proof"),
+                    None::<()>,
+                )
+            })?;
+
+        Ok(Header {
+            l1_height: Height::new(l1_height),
+            l1_account_proof: AccountProof {
+                storage_root: rollup_proof.storage_hash.into(),
+                proof: rollup_proof
+                    .account_proof
+                    .into_iter()
+                    .map(|bytes| bytes.to_vec())
+                    .collect(),
+            },
+            l2_state_root_proof,
+            last_batch_index_proof,
+            batch_hash_proof,
+            l2_ibc_account_proof: AccountProof {
+                storage_root: l2_ibc_account_proof.storage_hash,
+                proof: l2_ibc_account_proof.account_proof,
+            },
+            batch_header,
+        })
+    }
+
+    /// The header of the batch `batch_index`, read from the calldata of the transaction that
+    /// finalized the bundle it is the last batch of.
+    async fn batch_header(
+        &self,
+        batch_index: u64,
+        finalize_tx_hash: Option<H256>,
+    ) -> RpcResult<Vec<u8>> {
+        let finalize_tx_hash = finalize_tx_hash.ok_or_else(|| {
+            ErrorObject::owned(
+                -1,
+                format!("batch {batch_index} is finalized on the l1, but the scroll api has no finalize transaction for it"),
+                None::<()>,
+            )
+        })?;
+
+        let tx = self
+            .l1_provider
+            .get_transaction_by_hash(finalize_tx_hash.into())
+            .await
+            .map_err(|e| {
+                ErrorObject::owned(
+                    -1,
+                    ErrorReporter(e).with_message("error fetching finalize transaction"),
+                    None::<()>,
+                )
+            })?
+            .ok_or_else(|| {
+                ErrorObject::owned(
+                    -1,
+                    format!("finalize transaction {finalize_tx_hash} not found"),
+                    None::<()>,
+                )
+            })?;
+
+        finalizeBundleWithProofCall::abi_decode(&tx.input, true)
+            .map(|call| call._batchHeader)
+            .or_else(|_| {
+                finalizeBundleCall::abi_decode(&tx.input, true).map(|call| call._batchHeader)
+            })
+            .map(|batch_header| batch_header.to_vec())
+            .map_err(|e| {
+                ErrorObject::owned(
+                    -1,
+                    ErrorReporter(e).with_message(&format!(
+                        "unable to decode the calldata of finalize transaction {finalize_tx_hash}"
+                    )),
+                    None::<()>,
+                )
+            })
+    }
+}
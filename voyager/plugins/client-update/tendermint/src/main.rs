@@ -3,12 +3,17 @@ use std::{collections::VecDeque, fmt::Debug, num::ParseIntError};
 use cometbft_types::types::{validator::Validator, validator_set::ValidatorSet};
 use jsonrpsee::{
     core::{async_trait, RpcResult},
+    types::ErrorObject,
     Extensions,
 };
 use serde::{Deserialize, Serialize};
 use tendermint_light_client_types::Header;
-use tracing::instrument;
-use unionlabs::hash::{hash_v2::HexUnprefixed, H160};
+use tracing::{debug, instrument};
+use unionlabs::{
+    hash::{hash_v2::HexUnprefixed, H160},
+    ibc::core::client::height::Height,
+    ErrorReporter,
+};
 use voyager_message::{
     call::Call,
     core::ChainId,
@@ -39,6 +44,7 @@ pub struct Module {
     pub tm_client: cometbft_rpc::Client,
     pub chain_revision: u64,
     pub grpc_url: String,
+    pub trust_level: cometbft_rpc::TrustLevel,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -46,7 +52,18 @@ pub struct Config {
     pub chain_id: ChainId,
 
     pub ws_url: String,
+    /// Fallback endpoints to use if `ws_url` is unavailable.
+    #[serde(default)]
+    pub failover: cometbft_rpc::FailoverConfig,
+    /// An archival endpoint to send queries at old heights to.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub archival: Option<cometbft_rpc::ArchivalConfig>,
     pub grpc_url: String,
+    /// The trust level of the clients tracking this chain. Updates skip directly to the target
+    /// height if validators with more than this fraction of the trusted voting power signed it,
+    /// and go through intermediate headers otherwise.
+    #[serde(default)]
+    pub trust_level: cometbft_rpc::TrustLevel,
 }
 
 impl Plugin for Module {
@@ -57,7 +74,12 @@ impl Plugin for Module {
     type Cmd = DefaultCmd;
 
     async fn new(config: Self::Config) -> Result<Self, BoxDynError> {
-        let tm_client = cometbft_rpc::Client::new(config.ws_url).await?;
+        let mut tm_client =
+            cometbft_rpc::Client::new_with_failover(config.ws_url, &config.failover).await?;
+
+        if let Some(archival) = &config.archival {
+            tm_client = tm_client.with_archival(archival).await?;
+        }
 
         let chain_id = tm_client.status().await?.node_info.network.to_string();
 
@@ -87,6 +109,7 @@ impl Plugin for Module {
             chain_id: ChainId::new(chain_id),
             chain_revision,
             grpc_url: config.grpc_url,
+            trust_level: config.trust_level,
         })
     }
 
@@ -161,49 +184,53 @@ impl PluginServer<ModuleCall, ModuleCallback> for Module {
                 update_from,
                 update_to,
             }) => {
-                let trusted_commit = self
+                let heights = self
                     .tm_client
-                    .commit(Some(update_from.height().try_into().unwrap()))
+                    .skipping_update_heights(
+                        update_from.height().try_into().unwrap(),
+                        update_to.height().try_into().unwrap(),
+                        self.trust_level,
+                    )
                     .await
-                    .unwrap();
-
-                let untrusted_commit = self
-                    .tm_client
-                    .commit(Some(update_to.height().try_into().unwrap()))
-                    .await
-                    .unwrap();
+                    .map_err(|err| {
+                        ErrorObject::owned(
+                            -1,
+                            format!(
+                                "error computing the headers to update from {update_from} to \
+                                {update_to}: {}",
+                                ErrorReporter(err)
+                            ),
+                            None::<()>,
+                        )
+                    })?;
+
+                debug!(
+                    %update_from,
+                    %update_to,
+                    hops = heights.len(),
+                    "fetching headers"
+                );
+
+                let mut headers = vec![];
+                let mut trusted_height = update_from;
+
+                for height in heights {
+                    let untrusted_height =
+                        Height::new_with_revision(update_from.revision(), height.get());
+
+                    let header = self.fetch_header(trusted_height, untrusted_height).await;
+
+                    headers.push((
+                        DecodedHeaderMeta {
+                            height: untrusted_height,
+                        },
+                        serde_json::to_value(header).unwrap(),
+                    ));
 
-                let trusted_validators = self
-                    .tm_client
-                    .all_validators(Some(update_from.height().try_into().unwrap()))
-                    .await
-                    .unwrap();
+                    trusted_height = untrusted_height;
+                }
 
-                let untrusted_validators = self
-                    .tm_client
-                    .all_validators(Some(update_to.height().try_into().unwrap()))
-                    .await
-                    .unwrap();
-
-                let header = Header {
-                    validator_set: mk_validator_set(
-                        untrusted_validators.validators,
-                        untrusted_commit.signed_header.header.proposer_address,
-                    ),
-                    signed_header: untrusted_commit.signed_header,
-                    trusted_height: update_from,
-                    trusted_validators: mk_validator_set(
-                        trusted_validators.validators,
-                        trusted_commit.signed_header.header.proposer_address,
-                    ),
-                };
-
-                Ok(data(OrderedHeaders {
-                    headers: vec![(
-                        DecodedHeaderMeta { height: update_to },
-                        serde_json::to_value(header).unwrap(),
-                    )],
-                }))
+                Ok(data(OrderedHeaders { headers }))
             }
         }
     }
@@ -219,6 +246,49 @@ impl PluginServer<ModuleCall, ModuleCallback> for Module {
     }
 }
 
+impl Module {
+    /// Fetch a header for `untrusted_height`, to be verified against the validators at
+    /// `trusted_height`.
+    async fn fetch_header(&self, trusted_height: Height, untrusted_height: Height) -> Header {
+        let trusted_commit = self
+            .tm_client
+            .commit(Some(trusted_height.height().try_into().unwrap()))
+            .await
+            .unwrap();
+
+        let untrusted_commit = self
+            .tm_client
+            .commit(Some(untrusted_height.height().try_into().unwrap()))
+            .await
+            .unwrap();
+
+        let trusted_validators = self
+            .tm_client
+            .all_validators(Some(trusted_height.height().try_into().unwrap()))
+            .await
+            .unwrap();
+
+        let untrusted_validators = self
+            .tm_client
+            .all_validators(Some(untrusted_height.height().try_into().unwrap()))
+            .await
+            .unwrap();
+
+        Header {
+            validator_set: mk_validator_set(
+                untrusted_validators.validators,
+                untrusted_commit.signed_header.header.proposer_address,
+            ),
+            signed_header: untrusted_commit.signed_header,
+            trusted_height,
+            trusted_validators: mk_validator_set(
+                trusted_validators.validators,
+                trusted_commit.signed_header.header.proposer_address,
+            ),
+        }
+    }
+}
+
 fn mk_validator_set(
     validators: Vec<Validator>,
     proposer_address: H160<HexUnprefixed>,
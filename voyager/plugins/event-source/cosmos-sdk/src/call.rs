@@ -1,8 +1,13 @@
-use std::num::NonZeroU32;
+use std::num::{NonZeroU32, NonZeroU64};
 
 use enumorph::Enumorph;
 use macros::model;
-use unionlabs::{hash::H256, ibc::core::client::height::Height};
+use unionlabs::{
+    hash::H256,
+    ibc::core::client::height::Height,
+    id::{ChannelId, PortId},
+};
+use voyager_message::call::PacketEventKind;
 
 #[model]
 #[derive(Enumorph)]
@@ -10,7 +15,9 @@ use unionlabs::{hash::H256, ibc::core::client::height::Height};
 pub enum ModuleCall {
     FetchBlocks(FetchBlocks),
     FetchTransactions(FetchTransactions),
+    FetchPacketEvent(FetchPacketEvent),
     MakeChainEvent(MakeChainEvent),
+    WaitForTxInclusion(WaitForTxInclusion),
 }
 
 /// Fetch a block at the specified height, requeuing a seq(wait(H+1), fetch(H+1)).
@@ -25,9 +32,25 @@ pub struct FetchTransactions {
     pub page: NonZeroU32,
 }
 
+/// Search for the transaction that emitted the [`PacketEventKind`] event for the packet with the
+/// specified sequence, and requeue the event.
+#[model]
+pub struct FetchPacketEvent {
+    pub kind: PacketEventKind,
+    pub port_id: PortId,
+    pub channel_id: ChannelId,
+    pub sequence: NonZeroU64,
+}
+
 #[model]
 pub struct MakeChainEvent {
     pub height: Height,
     pub tx_hash: H256,
     pub event: crate::ibc_events::IbcEvent,
 }
+
+/// Poll for the inclusion of the transaction `tx_hash`, requeuing itself until it is included.
+#[model]
+pub struct WaitForTxInclusion {
+    pub tx_hash: H256,
+}
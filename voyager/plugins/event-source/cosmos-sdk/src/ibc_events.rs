@@ -385,6 +385,30 @@ event! {
             connection_id: u32,
         },
 
+        #[event(tag = "wasm-channel_close_init")]
+        UnionChannelCloseInit {
+            #[parse(String::from_str)]
+            port_id: String,
+            #[parse(u32::from_str)]
+            channel_id: u32,
+            #[parse(<Bytes<HexUnprefixed>>::from_str)]
+            counterparty_port_id: Bytes<HexUnprefixed>,
+            #[parse(u32::from_str)]
+            counterparty_channel_id: u32,
+        },
+
+        #[event(tag = "wasm-channel_close_confirm")]
+        UnionChannelCloseConfirm {
+            #[parse(String::from_str)]
+            port_id: String,
+            #[parse(u32::from_str)]
+            channel_id: u32,
+            #[parse(<Bytes<HexUnprefixed>>::from_str)]
+            counterparty_port_id: Bytes<HexUnprefixed>,
+            #[parse(u32::from_str)]
+            counterparty_channel_id: u32,
+        },
+
         #[event(tag = "wasm-send_packet")]
         UnionSendPacket {
             #[parse(serde_json::from_str)]
@@ -546,6 +570,8 @@ impl IbcEvent {
             IbcEvent::UnionChannelOpenTry(_) => "channel_open_try",
             // IbcEvent::UnionChannelOpenAck(_) => "channel_open_ack",
             IbcEvent::UnionChannelOpenConfirm(_) => "channel_open_confirm",
+            IbcEvent::UnionChannelCloseInit(_) => "channel_close_init",
+            IbcEvent::UnionChannelCloseConfirm(_) => "channel_close_confirm",
             // IbcEvent::UnionWriteAcknowledgement(_) => "write_acknowledgement",
             // IbcEvent::UnionRecvPacket(_) => "recv_packet",
             IbcEvent::UnionSendPacket(_) => "send_packet",
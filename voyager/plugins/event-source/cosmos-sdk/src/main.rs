@@ -18,7 +18,7 @@ use jsonrpsee::{
 };
 use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
-use tracing::{debug, error, info, instrument};
+use tracing::{debug, error, info, instrument, warn};
 use unionlabs::{
     hash::{hash_v2::HexUnprefixed, H256},
     ibc::core::{
@@ -29,18 +29,22 @@ use unionlabs::{
     option_unwrap, parse_wasm_client_type, ErrorReporter, WasmClientType,
 };
 use voyager_message::{
-    call::{Call, WaitForHeight},
+    call::{Call, FetchBlockRange, PacketEventKind, RecordCheckpoint, WaitForHeight},
     core::{ChainId, ClientInfo, ClientType, IbcSpec, QueryHeight},
-    data::{ChainEvent, Data},
+    data::{AppEvent, ChainEvent, Data},
+    event::{decode_ucs01_relay_event, DecodedAppEvent},
     into_value,
     module::{PluginInfo, PluginServer},
     rpc::missing_state,
     ExtensionsExt, Plugin, PluginMessage, VoyagerClient, VoyagerMessage,
 };
-use voyager_vm::{call, conc, data, pass::PassResult, seq, BoxDynError, Op};
+use voyager_vm::{call, conc, data, defer, noop, now, pass::PassResult, seq, BoxDynError, Op};
 
 use crate::{
-    call::{FetchBlocks, FetchTransactions, MakeChainEvent, ModuleCall},
+    call::{
+        FetchBlocks, FetchPacketEvent, FetchTransactions, MakeChainEvent, ModuleCall,
+        WaitForTxInclusion,
+    },
     callback::ModuleCallback,
     ibc_events::{
         ChannelOpenAck, ChannelOpenConfirm, ChannelOpenInit, ChannelOpenTry, ClientMisbehaviour,
@@ -77,6 +81,8 @@ pub struct Module {
     pub grpc_url: String,
 
     pub checksum_cache: Arc<DashMap<H256, WasmClientType>>,
+
+    pub ucs01_relay_contracts: Vec<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -84,7 +90,14 @@ pub struct Module {
 pub struct Config {
     pub chain_id: ChainId,
     pub ws_url: String,
+    /// Fallback endpoints to use if `ws_url` is unavailable.
+    #[serde(default)]
+    pub failover: cometbft_rpc::FailoverConfig,
     pub grpc_url: String,
+    /// The addresses of the ucs01-relay contracts on this chain. The transfer events emitted by
+    /// these contracts are decoded and emitted as [`AppEvent`]s.
+    #[serde(default)]
+    pub ucs01_relay_contracts: Vec<String>,
 }
 
 impl Plugin for Module {
@@ -95,7 +108,8 @@ impl Plugin for Module {
     type Cmd = Cmd;
 
     async fn new(config: Self::Config) -> Result<Self, BoxDynError> {
-        let tm_client = cometbft_rpc::Client::new(config.ws_url).await?;
+        let tm_client =
+            cometbft_rpc::Client::new_with_failover(config.ws_url, &config.failover).await?;
 
         let chain_id = tm_client.status().await?.node_info.network;
 
@@ -118,6 +132,7 @@ impl Plugin for Module {
             chain_revision,
             grpc_url: config.grpc_url,
             checksum_cache: Arc::new(DashMap::default()),
+            ucs01_relay_contracts: config.ucs01_relay_contracts,
         })
     }
 
@@ -125,7 +140,7 @@ impl Plugin for Module {
         PluginInfo {
             name: plugin_name(&config.chain_id),
             interest_filter: format!(
-                r#"[.. | ."@type"? == "fetch_blocks" and ."@value".chain_id == "{}"] | any"#,
+                r#"[.. | (."@type"? == "fetch_blocks" or ."@type"? == "fetch_block_range" or ."@type"? == "fetch_packet_events" or ."@type"? == "wait_for_tx_inclusion") and ."@value".chain_id == "{}"] | any"#,
                 config.chain_id
             ),
         }
@@ -152,6 +167,35 @@ impl Module {
         plugin_name(&self.chain_id)
     }
 
+    /// Decode an event emitted by one of the configured application contracts, returning the
+    /// address of the emitting contract along with the decoded event.
+    fn decode_app_event<'a>(
+        &self,
+        ty: &str,
+        attributes: impl IntoIterator<Item = (&'a str, &'a str)>,
+    ) -> Option<(String, DecodedAppEvent)> {
+        let attributes = attributes.into_iter().collect::<Vec<_>>();
+
+        let (_, contract_address) = attributes
+            .iter()
+            .find(|(key, _)| *key == "_contract_address")?;
+
+        if !self
+            .ucs01_relay_contracts
+            .iter()
+            .any(|contract| contract == contract_address)
+        {
+            return None;
+        }
+
+        let event = decode_ucs01_relay_event(ty, attributes.iter().copied())?;
+
+        Some((
+            (*contract_address).to_owned(),
+            DecodedAppEvent::Ucs01(event),
+        ))
+    }
+
     #[must_use]
     pub fn make_height(&self, height: u64) -> Height {
         Height::new_with_revision(self.chain_revision, height)
@@ -332,6 +376,52 @@ impl Module {
         Ok(self.make_height(height))
     }
 
+    /// The counterparty chain, client info, and connection of the ibc-union channel `channel_id`,
+    /// which is all of the metadata of the channel close handshake events that is not contained in
+    /// the raw events.
+    async fn make_union_channel_close_metadata(
+        &self,
+        event_height: Height,
+        channel_id: u32,
+        voyager_rpc_client: &VoyagerClient,
+    ) -> RpcResult<(ChainId, ClientInfo, ibc_solidity::Connection)> {
+        let channel = voyager_rpc_client
+            .query_ibc_state(
+                self.chain_id.clone(),
+                QueryHeight::Specific(event_height),
+                ibc_union_spec::ChannelPath { channel_id },
+            )
+            .await?
+            .state
+            .ok_or_else(missing_state("channel must exist", None))?;
+
+        let connection = voyager_rpc_client
+            .query_ibc_state(
+                self.chain_id.clone(),
+                QueryHeight::Specific(event_height),
+                ibc_union_spec::ConnectionPath {
+                    connection_id: channel.connection_id,
+                },
+            )
+            .await?
+            .state
+            .ok_or_else(missing_state("connection must exist", None))?;
+
+        let client_info = voyager_rpc_client
+            .client_info::<IbcUnion>(self.chain_id.clone(), connection.client_id)
+            .await?;
+
+        let client_meta = voyager_rpc_client
+            .client_meta::<IbcUnion>(
+                self.chain_id.clone(),
+                event_height.into(),
+                connection.client_id,
+            )
+            .await?;
+
+        Ok((client_meta.chain_id, client_info, connection))
+    }
+
     #[allow(clippy::too_many_arguments)] // pls
     async fn make_packet_metadata(
         &self,
@@ -460,6 +550,40 @@ impl PluginServer<ModuleCall, ModuleCallback> for Module {
                             }),
                         ))
                     }
+                    Op::Call(Call::FetchBlockRange(range)) if range.chain_id == self.chain_id => {
+                        conc((range.from_height.height()..=range.to_height.height()).map(
+                            |height| {
+                                call(PluginMessage::new(
+                                    self.plugin_name(),
+                                    ModuleCall::from(FetchTransactions {
+                                        height: self.make_height(height),
+                                        page: const { option_unwrap!(NonZeroU32::new(1_u32)) },
+                                    }),
+                                ))
+                            },
+                        ))
+                    }
+                    Op::Call(Call::FetchPacketEvents(fetch)) if fetch.chain_id == self.chain_id => {
+                        conc(fetch.sequences.into_iter().map(|sequence| {
+                            call(PluginMessage::new(
+                                self.plugin_name(),
+                                ModuleCall::from(FetchPacketEvent {
+                                    kind: fetch.kind,
+                                    port_id: fetch.port_id.clone(),
+                                    channel_id: fetch.channel_id.clone(),
+                                    sequence,
+                                }),
+                            ))
+                        }))
+                    }
+                    Op::Call(Call::WaitForTxInclusion(wait)) if wait.chain_id == self.chain_id => {
+                        call(PluginMessage::new(
+                            self.plugin_name(),
+                            ModuleCall::from(WaitForTxInclusion {
+                                tx_hash: wait.tx_hash,
+                            }),
+                        ))
+                    }
                     op => op,
                 })
                 .enumerate()
@@ -499,7 +623,37 @@ impl PluginServer<ModuleCall, ModuleCallback> for Module {
                         Some(json!({ "height": height })),
                     ))?;
 
-                Ok(conc(
+                let has_next_page =
+                    (page.get() * PER_PAGE_LIMIT.get() as u32) < response.total_count;
+
+                let app_events = response
+                    .txs
+                    .iter()
+                    .flat_map(|txr| {
+                        txr.tx_result.events.iter().filter_map(move |event| {
+                            self.decode_app_event(
+                                &event.ty,
+                                event
+                                    .attributes
+                                    .iter()
+                                    .map(|attr| (attr.key.as_str(), attr.value.as_str())),
+                            )
+                            .map(|(emitter, event)| {
+                                debug!(%emitter, "observed app event");
+
+                                data(AppEvent {
+                                    chain_id: self.chain_id.clone(),
+                                    tx_hash: txr.hash.into_encoding(),
+                                    height,
+                                    emitter,
+                                    event,
+                                })
+                            })
+                        })
+                    })
+                    .collect::<Vec<_>>();
+
+                let events = conc(
                     response
                         .txs
                         .into_iter()
@@ -533,43 +687,218 @@ impl PluginServer<ModuleCall, ModuleCallback> for Module {
                                 }),
                             ))
                         })
-                        .chain(
-                            ((page.get() * PER_PAGE_LIMIT.get() as u32) < response.total_count)
-                                .then(|| {
-                                    call(PluginMessage::new(
-                                        self.plugin_name(),
-                                        ModuleCall::from(FetchTransactions {
-                                            height,
-                                            page: page.checked_add(1).expect("too many pages?"),
-                                        }),
-                                    ))
+                        .chain(app_events)
+                        .chain(has_next_page.then(|| {
+                            call(PluginMessage::new(
+                                self.plugin_name(),
+                                ModuleCall::from(FetchTransactions {
+                                    height,
+                                    page: page.checked_add(1).expect("too many pages?"),
                                 }),
-                        ),
-                ))
+                            ))
+                        })),
+                );
+
+                // the following pages are fetched within the events of the first page, so the
+                // block is checkpointed once the events of all of its pages have been processed
+                if page.get() == 1 {
+                    Ok(seq([
+                        events,
+                        call(RecordCheckpoint {
+                            chain_id: self.chain_id.clone(),
+                            height,
+                        }),
+                    ]))
+                } else {
+                    Ok(events)
+                }
             }
-            ModuleCall::FetchBlocks(FetchBlocks { height }) => Ok(conc([
-                call(PluginMessage::new(
-                    self.plugin_name(),
-                    ModuleCall::from(FetchTransactions {
-                        height,
-                        page: const { option_unwrap!(NonZeroU32::new(1_u32)) },
-                    }),
-                )),
-                seq([
-                    // TODO: Make this a config param
-                    call(WaitForHeight {
-                        chain_id: self.chain_id.clone(),
-                        height: height.increment(),
-                        finalized: true,
-                    }),
-                    call(PluginMessage::new(
+            ModuleCall::FetchPacketEvent(FetchPacketEvent {
+                kind,
+                port_id,
+                channel_id,
+                sequence,
+            }) => {
+                info!(?kind, %port_id, %channel_id, %sequence, "fetching packet event");
+
+                let query = match kind {
+                    PacketEventKind::SendPacket => format!(
+                        "send_packet.packet_src_port='{port_id}' AND \
+                        send_packet.packet_src_channel='{channel_id:#}' AND \
+                        send_packet.packet_sequence='{sequence}'"
+                    ),
+                    PacketEventKind::WriteAcknowledgement => format!(
+                        "write_acknowledgement.packet_dst_port='{port_id}' AND \
+                        write_acknowledgement.packet_dst_channel='{channel_id:#}' AND \
+                        write_acknowledgement.packet_sequence='{sequence}'"
+                    ),
+                };
+
+                let response = self
+                    .tm_client
+                    .tx_search(
+                        &query,
+                        false,
+                        const { option_unwrap!(NonZeroU32::new(1_u32)) },
+                        PER_PAGE_LIMIT,
+                        cometbft_rpc::rpc_types::Order::Desc,
+                    )
+                    .await
+                    .map_err(rpc_error(
+                        format_args!("error searching for transactions with query `{query}`"),
+                        Some(json!({ "query": query })),
+                    ))?;
+
+                let mut events = vec![];
+
+                for txr in response.txs {
+                    let height = txr
+                        .height
+                        .ok_or_else(missing_state("transaction height is missing", None))?;
+
+                    for event in txr.tx_result.events {
+                        let Some(ibc_event) = IbcEvent::try_from_tendermint_event(event)
+                            .transpose()
+                            .map_err(|err| {
+                                ErrorObject::owned(
+                                    -1,
+                                    ErrorReporter(err).to_string(),
+                                    Some(json!({ "tx_hash": txr.hash })),
+                                )
+                            })?
+                        else {
+                            continue;
+                        };
+
+                        let is_match = match (&ibc_event, kind) {
+                            (IbcEvent::SendPacket(event), PacketEventKind::SendPacket) => {
+                                event.packet_sequence == sequence
+                                    && event.packet_src_port == port_id
+                                    && event.packet_src_channel == channel_id
+                            }
+                            (
+                                IbcEvent::WriteAcknowledgement(event),
+                                PacketEventKind::WriteAcknowledgement,
+                            ) => {
+                                event.packet_sequence == sequence
+                                    && event.packet_dst_port == port_id
+                                    && event.packet_dst_channel == channel_id
+                            }
+                            _ => false,
+                        };
+
+                        if is_match {
+                            events.push(call(PluginMessage::new(
+                                self.plugin_name(),
+                                ModuleCall::from(MakeChainEvent {
+                                    height: self.make_height(height.get()),
+                                    tx_hash: txr.hash.into_encoding(),
+                                    event: ibc_event,
+                                }),
+                            )));
+                        }
+                    }
+                }
+
+                if events.is_empty() {
+                    warn!(
+                        ?kind,
+                        %port_id,
+                        %channel_id,
+                        %sequence,
+                        "no event found for packet, it may have been pruned from the node"
+                    );
+                }
+
+                Ok(conc(events))
+            }
+            ModuleCall::FetchBlocks(FetchBlocks { height }) => {
+                let latest_height = e
+                    .try_get::<VoyagerClient>()?
+                    .query_latest_height(self.chain_id.clone(), true)
+                    .await?;
+
+                // if the unfold has fallen behind the chain (i.e. the rpc connection was down for
+                // a while), fetch the missed blocks concurrently instead of one at a time and
+                // continue the unfold from the latest height
+                let has_gap = latest_height.height() > height.increment().height();
+
+                let next_height = if has_gap {
+                    warn!(
+                        %height,
+                        %latest_height,
+                        "detected a gap between the last fetched block and the latest block, \
+                        backfilling the missed blocks"
+                    );
+
+                    latest_height.increment()
+                } else {
+                    height.increment()
+                };
+
+                Ok(conc(
+                    [call(PluginMessage::new(
                         self.plugin_name(),
-                        ModuleCall::from(FetchBlocks {
-                            height: height.increment(),
+                        ModuleCall::from(FetchTransactions {
+                            height,
+                            page: const { option_unwrap!(NonZeroU32::new(1_u32)) },
+                        }),
+                    ))]
+                    .into_iter()
+                    .chain(has_gap.then(|| {
+                        call(FetchBlockRange {
+                            chain_id: self.chain_id.clone(),
+                            from_height: height.increment(),
+                            to_height: latest_height,
+                        })
+                    }))
+                    .chain([seq([
+                        // TODO: Make this a config param
+                        call(WaitForHeight {
+                            chain_id: self.chain_id.clone(),
+                            height: next_height,
+                            finalized: true,
+                            deadline: None,
                         }),
+                        call(PluginMessage::new(
+                            self.plugin_name(),
+                            ModuleCall::from(FetchBlocks {
+                                height: next_height,
+                            }),
+                        )),
+                    ])]),
+                ))
+            }
+            ModuleCall::WaitForTxInclusion(WaitForTxInclusion { tx_hash }) => {
+                // cometbft returns an error for transactions that are not (yet) indexed, so any
+                // error is treated as the transaction not being included yet
+                match self.tm_client.tx(tx_hash, false).await {
+                    Ok(tx) if tx.tx_result.code == 0 => {
+                        info!(%tx_hash, height = ?tx.height, "tx included");
+
+                        Ok(noop())
+                    }
+                    Ok(tx) => Err(ErrorObject::owned(
+                        -1,
+                        format!(
+                            "tx {tx_hash} failed with code {} ({}): {}",
+                            tx.tx_result.code, tx.tx_result.codespace, tx.tx_result.log
+                        ),
+                        None::<()>,
                     )),
-                ]),
-            ])),
+                    Err(err) => {
+                        debug!(%tx_hash, err = %ErrorReporter(err), "tx not yet included");
+
+                        Ok(seq([
+                            defer(now() + 1),
+                            call(PluginMessage::new(
+                                self.plugin_name(),
+                                ModuleCall::from(WaitForTxInclusion { tx_hash }),
+                            )),
+                        ]))
+                    }
+                }
+            }
             ModuleCall::MakeChainEvent(MakeChainEvent {
                 height,
                 tx_hash,
@@ -667,6 +996,7 @@ impl PluginServer<ModuleCall, ModuleCallback> for Module {
                                 .into(),
                                 _ => unreachable!("who needs flow typing"),
                             }),
+                            decoded_packet_data: None,
                         }))
                     }
 
@@ -734,6 +1064,7 @@ impl PluginServer<ModuleCall, ModuleCallback> for Module {
                                 }
                                 _ => unreachable!("who needs flow typing"),
                             }),
+                            decoded_packet_data: None,
                         }))
                     }
 
@@ -821,6 +1152,7 @@ impl PluginServer<ModuleCall, ModuleCallback> for Module {
                                 }
                                 _ => unreachable!("who needs flow typing"),
                             }),
+                            decoded_packet_data: None,
                         }))
                     }
                     // packet origin is this chain
@@ -864,6 +1196,7 @@ impl PluginServer<ModuleCall, ModuleCallback> for Module {
                                 }
                                 .into(),
                             ),
+                            decoded_packet_data: None,
                         }))
                     }
                     IbcEvent::TimeoutPacket(event) => {
@@ -905,6 +1238,7 @@ impl PluginServer<ModuleCall, ModuleCallback> for Module {
                                 }
                                 .into(),
                             ),
+                            decoded_packet_data: None,
                         }))
                     }
                     IbcEvent::AcknowledgePacket(event) => {
@@ -946,6 +1280,7 @@ impl PluginServer<ModuleCall, ModuleCallback> for Module {
                                 }
                                 .into(),
                             ),
+                            decoded_packet_data: None,
                         }))
                     }
                     // packet origin is the counterparty chain (if i put this comment above this pattern rustfmt explodes)
@@ -990,6 +1325,7 @@ impl PluginServer<ModuleCall, ModuleCallback> for Module {
                                 }
                                 .into(),
                             ),
+                            decoded_packet_data: None,
                         }))
                     }
                     IbcEvent::RecvPacket(event) => {
@@ -1032,6 +1368,7 @@ impl PluginServer<ModuleCall, ModuleCallback> for Module {
                                 }
                                 .into(),
                             ),
+                            decoded_packet_data: None,
                         }))
                     }
                     IbcEvent::UnionCreateClient(create_client) => {
@@ -1063,6 +1400,7 @@ impl PluginServer<ModuleCall, ModuleCallback> for Module {
                                 }
                                 .into(),
                             ),
+                            decoded_packet_data: None,
                         }))
                     }
                     IbcEvent::UnionUpdateClient(update_client) => {
@@ -1095,6 +1433,7 @@ impl PluginServer<ModuleCall, ModuleCallback> for Module {
                                 }
                                 .into(),
                             ),
+                            decoded_packet_data: None,
                         }))
                     }
                     IbcEvent::UnionConnectionOpenInit(connection_open_init) => {
@@ -1131,6 +1470,7 @@ impl PluginServer<ModuleCall, ModuleCallback> for Module {
                                 }
                                 .into(),
                             ),
+                            decoded_packet_data: None,
                         }))
                     }
                     IbcEvent::UnionConnectionOpenTry(connection_open_try) => {
@@ -1169,6 +1509,7 @@ impl PluginServer<ModuleCall, ModuleCallback> for Module {
                                 }
                                 .into(),
                             ),
+                            decoded_packet_data: None,
                         }))
                     }
                     IbcEvent::UnionConnectionOpenAck(connection_open_ack) => {
@@ -1207,6 +1548,7 @@ impl PluginServer<ModuleCall, ModuleCallback> for Module {
                                 }
                                 .into(),
                             ),
+                            decoded_packet_data: None,
                         }))
                     }
                     IbcEvent::UnionConnectionOpenConfirm(connection_open_confirm) => {
@@ -1245,6 +1587,7 @@ impl PluginServer<ModuleCall, ModuleCallback> for Module {
                                 }
                                 .into(),
                             ),
+                            decoded_packet_data: None,
                         }))
                     }
                     IbcEvent::UnionChannelOpenTry(channel_open_try) => {
@@ -1295,6 +1638,7 @@ impl PluginServer<ModuleCall, ModuleCallback> for Module {
                                 }
                                 .into(),
                             ),
+                            decoded_packet_data: None,
                         }))
                     }
                     IbcEvent::UnionChannelOpenConfirm(channel_open_confirm) => {
@@ -1357,6 +1701,71 @@ impl PluginServer<ModuleCall, ModuleCallback> for Module {
                                 }
                                 .into(),
                             ),
+                            decoded_packet_data: None,
+                        }))
+                    }
+                    IbcEvent::UnionChannelCloseInit(channel_close_init) => {
+                        let (counterparty_chain_id, client_info, connection) = self
+                            .make_union_channel_close_metadata(
+                                height,
+                                channel_close_init.channel_id,
+                                voyager_client,
+                            )
+                            .await?;
+
+                        Ok(data(ChainEvent {
+                            chain_id: self.chain_id.clone(),
+                            client_info,
+                            counterparty_chain_id,
+                            tx_hash,
+                            provable_height,
+                            ibc_spec_id: IbcUnion::ID,
+                            event: into_value::<ibc_union_spec::FullEvent>(
+                                ibc_union_spec::ChannelCloseInit {
+                                    port_id: channel_close_init.port_id.into_bytes().into(),
+                                    channel_id: channel_close_init.channel_id,
+                                    counterparty_port_id: channel_close_init
+                                        .counterparty_port_id
+                                        .into_encoding(),
+                                    counterparty_channel_id: channel_close_init
+                                        .counterparty_channel_id,
+                                    connection,
+                                }
+                                .into(),
+                            ),
+                            decoded_packet_data: None,
+                        }))
+                    }
+                    IbcEvent::UnionChannelCloseConfirm(channel_close_confirm) => {
+                        let (counterparty_chain_id, client_info, connection) = self
+                            .make_union_channel_close_metadata(
+                                height,
+                                channel_close_confirm.channel_id,
+                                voyager_client,
+                            )
+                            .await?;
+
+                        Ok(data(ChainEvent {
+                            chain_id: self.chain_id.clone(),
+                            client_info,
+                            counterparty_chain_id,
+                            tx_hash,
+                            provable_height,
+                            ibc_spec_id: IbcUnion::ID,
+                            event: into_value::<ibc_union_spec::FullEvent>(
+                                ibc_union_spec::ChannelCloseConfirm {
+                                    port_id: channel_close_confirm.port_id.into_bytes().into(),
+                                    channel_id: channel_close_confirm.channel_id,
+                                    counterparty_port_id: channel_close_confirm
+                                        .counterparty_port_id
+                                        .into_encoding(),
+                                    counterparty_channel_id: channel_close_confirm
+                                        .counterparty_channel_id,
+                                    connection,
+                                }
+                                .into(),
+                            ),
+                            decoded_packet_data: None,
                         }))
                     }
                     IbcEvent::UnionSendPacket(send_packet) => {
@@ -1437,6 +1846,7 @@ impl PluginServer<ModuleCall, ModuleCallback> for Module {
                                 }
                                 .into(),
                             ),
+                            decoded_packet_data: None,
                         }))
                     }
                 }
@@ -9,6 +9,7 @@ use unionlabs::hash::H256;
 pub enum ModuleCall {
     FetchGetLogs(FetchGetLogs),
     MakeFullEvent(MakeFullEvent),
+    WaitForTxInclusion(WaitForTxInclusion),
 }
 
 /// Fetch all events in `block_number` emitted by the `IBCHandler` via [`eth_getLogs`].
@@ -30,6 +31,18 @@ pub struct MakeFullEvent {
     /// Tx hash of the transaction that emitted this event.
     pub tx_hash: H256,
     pub event: IbcEvents,
+    /// The height that this event is provable at, if it is not `block_number`. This is the case
+    /// for chains that settle on an l1, where it is the l1 height that the block was confirmed at.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub provable_height: Option<u64>,
+}
+
+/// Poll for the receipt of the transaction `tx_hash`, requeuing itself until the transaction is
+/// included in a finalized block (or, for chains that settle on an l1, a block that has been
+/// confirmed on the l1).
+#[model]
+pub struct WaitForTxInclusion {
+    pub tx_hash: H256,
 }
 
 #[model]
@@ -8,13 +8,15 @@ use alloy::{
     sol_types::SolEventInterface,
     transports::BoxTransport,
 };
+use arbitrum_rollup::{Rollup, RollupConfig};
 use beacon_api::client::BeaconApiClient;
-use ibc_solidity::Ibc;
+use ibc_solidity::{Connection, Ibc};
 use ibc_union_spec::{
-    AcknowledgePacket, ChannelMetadata, ChannelOpenAck, ChannelOpenConfirm, ChannelOpenInit,
-    ChannelOpenTry, ChannelPath, ConnectionMetadata, ConnectionOpenAck, ConnectionOpenConfirm,
-    ConnectionOpenInit, ConnectionOpenTry, ConnectionPath, CreateClient, FullEvent, IbcUnion,
-    PacketMetadata, RecvPacket, SendPacket, TimeoutPacket, UpdateClient, WriteAcknowledgement,
+    AcknowledgePacket, ChannelCloseConfirm, ChannelCloseInit, ChannelMetadata, ChannelOpenAck,
+    ChannelOpenConfirm, ChannelOpenInit, ChannelOpenTry, ChannelPath, ConnectionMetadata,
+    ConnectionOpenAck, ConnectionOpenConfirm, ConnectionOpenInit, ConnectionOpenTry,
+    ConnectionPath, CreateClient, FullEvent, IbcUnion, PacketMetadata, RecvPacket, SendPacket,
+    TimeoutPacket, UpdateClient, WriteAcknowledgement,
 };
 use jsonrpsee::{
     core::{async_trait, RpcResult},
@@ -25,7 +27,7 @@ use serde::{Deserialize, Serialize};
 use tracing::{debug, info, instrument, trace, warn};
 use unionlabs::{hash::H160, ibc::core::client::height::Height, ErrorReporter};
 use voyager_message::{
-    call::Call,
+    call::{Call, RecordCheckpoint},
     core::{ChainId, ClientInfo, IbcSpec, QueryHeight},
     data::{ChainEvent, Data},
     into_value,
@@ -37,7 +39,7 @@ use voyager_message::{
 use voyager_vm::{call, conc, data, defer, noop, now, pass::PassResult, seq, BoxDynError, Op};
 
 use crate::{
-    call::{FetchGetLogs, IbcEvents, MakeFullEvent, ModuleCall},
+    call::{FetchGetLogs, IbcEvents, MakeFullEvent, ModuleCall, WaitForTxInclusion},
     callback::ModuleCallback,
 };
 
@@ -58,6 +60,16 @@ pub struct Module {
 
     pub provider: RootProvider<BoxTransport>,
     pub beacon_api_client: BeaconApiClient,
+
+    pub settlement: Option<Settlement>,
+
+    pub confirmations: Confirmations,
+}
+
+/// The settlement of this chain on an l1, if it is an l2.
+#[derive(Debug, Clone)]
+pub enum Settlement {
+    Arbitrum(Rollup),
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -73,6 +85,49 @@ pub struct Config {
     pub eth_rpc_api: String,
     /// The RPC endpoint for the beacon chain.
     pub eth_beacon_rpc_api: String,
+    /// Additional RPC endpoints for the beacon chain, used (in order) if a request to
+    /// [`Self::eth_beacon_rpc_api`] fails.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub eth_beacon_rpc_api_fallbacks: Vec<String>,
+
+    /// The settlement of this chain on an l1, if it is an l2.
+    ///
+    /// If set, blocks are only considered final once they have been confirmed on the l1, and
+    /// events are provable at the l1 height they were confirmed at, since that is the height that
+    /// clients of the l2 track.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub settlement: Option<SettlementConfig>,
+
+    /// How deep a block must be before the events in it are acted upon, and before a transaction
+    /// included in it is considered successful.
+    #[serde(default)]
+    pub confirmations: Confirmations,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Confirmations {
+    /// Wait until the block is finalized, as reported by the consensus module of this chain.
+    #[default]
+    Finalized,
+    /// Wait until this many blocks have been built on top of the block, as reported by the
+    /// execution RPC. `0` acts on blocks as soon as they are included.
+    ///
+    /// This trades safety for latency: events in blocks that are reorged out after reaching this
+    /// depth will still have been acted upon. Note that the clients tracking this chain may still
+    /// require the block to be finalized before the events in it can be proven.
+    Depth(u64),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields, tag = "type", rename_all = "snake_case")]
+pub enum SettlementConfig {
+    Arbitrum {
+        /// The RPC endpoint for the l1 execution chain.
+        l1_rpc_url: String,
+        /// The location of the rollup state on the l1.
+        rollup: RollupConfig,
+    },
 }
 
 impl Plugin for Module {
@@ -90,7 +145,7 @@ impl Plugin for Module {
         PluginInfo {
             name: plugin_name(&config.chain_id),
             interest_filter: format!(
-                r#"[.. | ."@type"? == "fetch_blocks" and ."@value".chain_id == "{}"] | any"#,
+                r#"[.. | (."@type"? == "fetch_blocks" or ."@type"? == "fetch_block_range" or ."@type"? == "wait_for_tx_inclusion") and ."@value".chain_id == "{}"] | any"#,
                 config.chain_id
             ),
         }
@@ -120,14 +175,133 @@ impl Module {
         // TODO: Assert chain id is correct
         let chain_id = provider.get_chain_id().await?;
 
+        let settlement = match config.settlement {
+            Some(SettlementConfig::Arbitrum { l1_rpc_url, rollup }) => {
+                Some(Settlement::Arbitrum(Rollup::new(
+                    rollup,
+                    ProviderBuilder::new().on_builtin(&l1_rpc_url).await?,
+                    provider.clone(),
+                )))
+            }
+            None => None,
+        };
+
+        if settlement.is_some() && config.confirmations != Confirmations::Finalized {
+            return Err("confirmations must be `finalized` for chains with a settlement".into());
+        }
+
         Ok(Self {
             chain_id: ChainId::new(chain_id.to_string()),
             ibc_handler_address: config.ibc_handler_address,
             provider,
-            beacon_api_client: BeaconApiClient::new(config.eth_beacon_rpc_api).await?,
+            beacon_api_client: BeaconApiClient::new_with_fallbacks(
+                config.eth_beacon_rpc_api,
+                config.eth_beacon_rpc_api_fallbacks,
+            )
+            .await?,
+            settlement,
+            confirmations: config.confirmations,
         })
     }
 
+    /// The latest height of this chain that is considered confirmed, and the latest execution
+    /// block that is confirmed at that height, as configured by [`Config::confirmations`].
+    async fn confirmed_height(&self, voyager_client: &VoyagerClient) -> RpcResult<(Height, u64)> {
+        match self.confirmations {
+            Confirmations::Finalized => self.finalized_height(voyager_client).await,
+            Confirmations::Depth(depth) => {
+                let latest_block_number = self.provider.get_block_number().await.map_err(|e| {
+                    ErrorObject::owned(
+                        -1,
+                        ErrorReporter(e).with_message("error fetching the latest block number"),
+                        None::<()>,
+                    )
+                })?;
+
+                let block_number = latest_block_number.saturating_sub(depth);
+
+                Ok((Height::new(block_number), block_number))
+            }
+        }
+    }
+
+    /// The latest finalized height of this chain, and the latest execution block that is final at
+    /// that height.
+    ///
+    /// Without a settlement these are the same. Otherwise, the finalized height is the finalized
+    /// height of the l1, and the execution block is the latest l2 block confirmed on the l1 at
+    /// that height.
+    async fn finalized_height(&self, voyager_client: &VoyagerClient) -> RpcResult<(Height, u64)> {
+        let finalized_height = voyager_client
+            .query_latest_height(self.chain_id.clone(), true)
+            .await?;
+
+        match &self.settlement {
+            None => Ok((finalized_height, finalized_height.height())),
+            Some(Settlement::Arbitrum(rollup)) => {
+                let block_number = rollup
+                    .l2_height_at(finalized_height.height())
+                    .await
+                    .map_err(|e| {
+                        ErrorObject::owned(
+                            -1,
+                            ErrorReporter(e).with_message(&format!(
+                                "error fetching the l2 block confirmed at {finalized_height}"
+                            )),
+                            None::<()>,
+                        )
+                    })?;
+
+                Ok((finalized_height, block_number))
+            }
+        }
+    }
+
+    /// The counterparty chain, client info, and connection of `channel_id`, which is all of the
+    /// metadata of the channel close handshake events that is not contained in the raw events.
+    async fn make_channel_close_metadata(
+        &self,
+        event_height: Height,
+        channel_id: u32,
+        voyager_rpc_client: &VoyagerClient,
+    ) -> RpcResult<(ChainId, ClientInfo, Connection)> {
+        let channel = voyager_rpc_client
+            .query_ibc_state(
+                self.chain_id.clone(),
+                event_height.into(),
+                ChannelPath { channel_id },
+            )
+            .await?
+            .state
+            .ok_or_else(missing_state("channel must exist", None))?;
+
+        let connection = voyager_rpc_client
+            .query_ibc_state(
+                self.chain_id.clone(),
+                event_height.into(),
+                ConnectionPath {
+                    connection_id: channel.connection_id,
+                },
+            )
+            .await?
+            .state
+            .ok_or_else(missing_state("connection must exist", None))?;
+
+        let client_info = voyager_rpc_client
+            .client_info::<IbcUnion>(self.chain_id.clone(), connection.client_id)
+            .await?;
+
+        let client_meta = voyager_rpc_client
+            .client_meta::<IbcUnion>(
+                self.chain_id.clone(),
+                event_height.into(),
+                connection.client_id,
+            )
+            .await?;
+
+        Ok((client_meta.chain_id, client_info, connection))
+    }
+
     async fn make_packet_metadata(
         &self,
         event_height: Height,
@@ -237,6 +411,23 @@ impl PluginServer<ModuleCall, ModuleCallback> for Module {
                             }),
                         ))
                     }
+                    Op::Call(Call::FetchBlockRange(range)) if range.chain_id == self.chain_id => {
+                        call(PluginMessage::new(
+                            self.plugin_name(),
+                            ModuleCall::from(FetchGetLogs {
+                                block_number: range.from_height.height(),
+                                up_to: Some(range.to_height.height()),
+                            }),
+                        ))
+                    }
+                    Op::Call(Call::WaitForTxInclusion(wait)) if wait.chain_id == self.chain_id => {
+                        call(PluginMessage::new(
+                            self.plugin_name(),
+                            ModuleCall::from(WaitForTxInclusion {
+                                tx_hash: wait.tx_hash,
+                            }),
+                        ))
+                    }
                     op => op,
                 })
                 .enumerate()
@@ -258,12 +449,75 @@ impl PluginServer<ModuleCall, ModuleCallback> for Module {
     #[instrument(skip_all, fields(chain_id = %self.chain_id))]
     async fn call(&self, e: &Extensions, msg: ModuleCall) -> RpcResult<Op<VoyagerMessage>> {
         match msg {
+            ModuleCall::WaitForTxInclusion(WaitForTxInclusion { tx_hash }) => {
+                let receipt = self
+                    .provider
+                    .get_transaction_receipt(tx_hash.into())
+                    .await
+                    .map_err(|e| {
+                        ErrorObject::owned(
+                            -1,
+                            format!(
+                                "error fetching receipt of tx {tx_hash}: {}",
+                                ErrorReporter(e)
+                            ),
+                            None::<()>,
+                        )
+                    })?;
+
+                let requeue = || {
+                    seq([
+                        defer(now() + 1),
+                        call(PluginMessage::new(
+                            self.plugin_name(),
+                            ModuleCall::from(WaitForTxInclusion { tx_hash }),
+                        )),
+                    ])
+                };
+
+                let Some((block_number, succeeded)) =
+                    receipt.and_then(|receipt| Some((receipt.block_number?, receipt.status())))
+                else {
+                    debug!(%tx_hash, "tx not yet included");
+
+                    return Ok(requeue());
+                };
+
+                if !succeeded {
+                    return Err(ErrorObject::owned(
+                        -1,
+                        format!("tx {tx_hash} reverted in block {block_number}"),
+                        None::<()>,
+                    ));
+                }
+
+                // the block containing the transaction may still be reorged out until it is
+                // confirmed, in which case the receipt will be gone (or different) on the next poll
+                let (confirmed_height, confirmed_block_number) =
+                    self.confirmed_height(e.try_get::<VoyagerClient>()?).await?;
+
+                if confirmed_block_number < block_number {
+                    debug!(
+                        %tx_hash,
+                        %block_number,
+                        %confirmed_height,
+                        "tx included but not yet confirmed"
+                    );
+
+                    Ok(requeue())
+                } else {
+                    info!(%tx_hash, %block_number, "tx included");
+
+                    Ok(noop())
+                }
+            }
             ModuleCall::MakeFullEvent(MakeFullEvent {
                 block_number,
                 tx_hash,
                 event,
+                provable_height,
             }) => {
-                let provable_height = Height::new(block_number);
+                let provable_height = Height::new(provable_height.unwrap_or(block_number));
                 let voyager_client = e.try_get::<VoyagerClient>()?;
 
                 match event {
@@ -294,6 +548,7 @@ impl PluginServer<ModuleCall, ModuleCallback> for Module {
                                 }
                                 .into(),
                             ),
+                            decoded_packet_data: None,
                         }))
                     }
                     IbcEvents::ClientRegistered(raw_event) => {
@@ -329,6 +584,7 @@ impl PluginServer<ModuleCall, ModuleCallback> for Module {
                                 }
                                 .into(),
                             ),
+                            decoded_packet_data: None,
                         }))
                     }
 
@@ -360,6 +616,7 @@ impl PluginServer<ModuleCall, ModuleCallback> for Module {
                                 }
                                 .into(),
                             ),
+                            decoded_packet_data: None,
                         }))
                     }
                     IbcEvents::ConnectionOpenTry(raw_event) => {
@@ -392,6 +649,7 @@ impl PluginServer<ModuleCall, ModuleCallback> for Module {
                                 }
                                 .into(),
                             ),
+                            decoded_packet_data: None,
                         }))
                     }
                     IbcEvents::ConnectionOpenAck(raw_event) => {
@@ -424,6 +682,7 @@ impl PluginServer<ModuleCall, ModuleCallback> for Module {
                                 }
                                 .into(),
                             ),
+                            decoded_packet_data: None,
                         }))
                     }
                     IbcEvents::ConnectionOpenConfirm(raw_event) => {
@@ -455,6 +714,7 @@ impl PluginServer<ModuleCall, ModuleCallback> for Module {
                                 }
                                 .into(),
                             ),
+                            decoded_packet_data: None,
                         }))
                     }
                     IbcEvents::ChannelOpenInit(raw_event) => {
@@ -511,6 +771,7 @@ impl PluginServer<ModuleCall, ModuleCallback> for Module {
                                 }
                                 .into(),
                             ),
+                            decoded_packet_data: None,
                         }))
                     }
                     IbcEvents::ChannelOpenTry(raw_event) => {
@@ -568,6 +829,7 @@ impl PluginServer<ModuleCall, ModuleCallback> for Module {
                                 }
                                 .into(),
                             ),
+                            decoded_packet_data: None,
                         }))
                     }
                     IbcEvents::ChannelOpenAck(raw_event) => {
@@ -625,6 +887,7 @@ impl PluginServer<ModuleCall, ModuleCallback> for Module {
                                 }
                                 .into(),
                             ),
+                            decoded_packet_data: None,
                         }))
                     }
                     IbcEvents::ChannelOpenConfirm(raw_event) => {
@@ -682,13 +945,71 @@ impl PluginServer<ModuleCall, ModuleCallback> for Module {
                                 }
                                 .into(),
                             ),
+                            decoded_packet_data: None,
                         }))
                     }
 
-                    IbcEvents::ChannelCloseInit(_) | IbcEvents::ChannelCloseConfirm(_) => {
-                        warn!("observed channel close message, these are not handled currently");
+                    IbcEvents::ChannelCloseInit(raw_event) => {
+                        let channel_id = raw_event.channel_id;
 
-                        Ok(noop())
+                        let (counterparty_chain_id, client_info, connection) = self
+                            .make_channel_close_metadata(
+                                provable_height,
+                                channel_id,
+                                voyager_client,
+                            )
+                            .await?;
+
+                        Ok(data(ChainEvent {
+                            chain_id: self.chain_id.clone(),
+                            client_info,
+                            counterparty_chain_id,
+                            tx_hash,
+                            provable_height,
+                            ibc_spec_id: IbcUnion::ID,
+                            event: into_value::<FullEvent>(
+                                ChannelCloseInit {
+                                    port_id: raw_event.port_id.into(),
+                                    channel_id,
+                                    counterparty_port_id: raw_event.counterparty_port_id.into(),
+                                    counterparty_channel_id: raw_event.counterparty_channel_id,
+                                    connection,
+                                }
+                                .into(),
+                            ),
+                            decoded_packet_data: None,
+                        }))
+                    }
+                    IbcEvents::ChannelCloseConfirm(raw_event) => {
+                        let channel_id = raw_event.channel_id;
+
+                        let (counterparty_chain_id, client_info, connection) = self
+                            .make_channel_close_metadata(
+                                provable_height,
+                                channel_id,
+                                voyager_client,
+                            )
+                            .await?;
+
+                        Ok(data(ChainEvent {
+                            chain_id: self.chain_id.clone(),
+                            client_info,
+                            counterparty_chain_id,
+                            tx_hash,
+                            provable_height,
+                            ibc_spec_id: IbcUnion::ID,
+                            event: into_value::<FullEvent>(
+                                ChannelCloseConfirm {
+                                    port_id: raw_event.port_id.into(),
+                                    channel_id,
+                                    counterparty_port_id: raw_event.counterparty_port_id.into(),
+                                    counterparty_channel_id: raw_event.counterparty_channel_id,
+                                    connection,
+                                }
+                                .into(),
+                            ),
+                            decoded_packet_data: None,
+                        }))
                     }
 
                     // packet origin is this chain
@@ -725,6 +1046,7 @@ impl PluginServer<ModuleCall, ModuleCallback> for Module {
                                 }
                                 .into(),
                             ),
+                            decoded_packet_data: None,
                         }))
                     }
                     IbcEvents::TimeoutPacket(event) => {
@@ -760,6 +1082,7 @@ impl PluginServer<ModuleCall, ModuleCallback> for Module {
                                 }
                                 .into(),
                             ),
+                            decoded_packet_data: None,
                         }))
                     }
                     IbcEvents::AcknowledgePacket(event) => {
@@ -796,6 +1119,7 @@ impl PluginServer<ModuleCall, ModuleCallback> for Module {
                                 }
                                 .into(),
                             ),
+                            decoded_packet_data: None,
                         }))
                     }
                     // packet origin is the counterparty chain
@@ -833,6 +1157,7 @@ impl PluginServer<ModuleCall, ModuleCallback> for Module {
                                 }
                                 .into(),
                             ),
+                            decoded_packet_data: None,
                         }))
                     }
                     IbcEvents::RecvPacket(event) => {
@@ -869,6 +1194,7 @@ impl PluginServer<ModuleCall, ModuleCallback> for Module {
                                 }
                                 .into(),
                             ),
+                            decoded_packet_data: None,
                         }))
                     }
                     IbcEvents::RecvIntentPacket(_event) => {
@@ -888,13 +1214,11 @@ impl PluginServer<ModuleCall, ModuleCallback> for Module {
                     ));
                 }
 
-                let latest_height = e
-                    .try_get::<VoyagerClient>()?
-                    .query_latest_height(self.chain_id.clone(), true)
-                    .await?;
+                let (confirmed_height, confirmed_block_number) =
+                    self.confirmed_height(e.try_get::<VoyagerClient>()?).await?;
 
-                if latest_height.height() < block_number {
-                    debug!(block_number, "block is not yet finalized");
+                if confirmed_block_number < block_number {
+                    debug!(block_number, "block is not yet confirmed");
 
                     return Ok(seq([
                         defer(now() + 1),
@@ -949,6 +1273,10 @@ impl PluginServer<ModuleCall, ModuleCallback> for Module {
                                 ModuleCall::from(MakeFullEvent {
                                     block_number,
                                     tx_hash,
+                                    provable_height: self
+                                        .settlement
+                                        .is_some()
+                                        .then_some(confirmed_height.height()),
                                     event: match event.data {
                                         Ibc::IbcEvents::ClientRegistered(client_registered) => {
                                             IbcEvents::ClientRegistered(client_registered)
@@ -1047,7 +1375,14 @@ impl PluginServer<ModuleCall, ModuleCallback> for Module {
                     )))),
                 };
 
-                Ok(conc(next_fetch.into_iter().chain(events)))
+                // the block is checkpointed once all of its events have been processed
+                Ok(conc(next_fetch.into_iter().chain([seq([
+                    conc(events),
+                    call(RecordCheckpoint {
+                        chain_id: self.chain_id.clone(),
+                        height: Height::new(block_number),
+                    }),
+                ])])))
             }
         }
     }
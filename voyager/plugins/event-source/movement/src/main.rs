@@ -785,6 +785,7 @@ impl PluginServer<ModuleCall, ModuleCallback> for Module {
                     provable_height: self.make_height(height),
                     event: into_value::<FullEvent>(full_event),
                     ibc_spec_id: IbcUnion::ID,
+                    decoded_packet_data: None,
                 }))
             }
         }
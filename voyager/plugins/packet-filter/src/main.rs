@@ -1,6 +1,7 @@
 use std::collections::VecDeque;
 
 use ibc_classic_spec::IbcClassic;
+use ibc_union_spec::IbcUnion;
 use jsonrpsee::{
     core::{async_trait, RpcResult},
     Extensions,
@@ -11,7 +12,7 @@ use serde_with::{serde_as, DisplayFromStr};
 use tracing::{instrument, trace};
 use unionlabs::never::Never;
 use voyager_message::{
-    core::IbcSpec,
+    core::{IbcSpec, IbcSpecId},
     data::Data,
     module::{PluginInfo, PluginServer},
     DefaultCmd, Plugin, VoyagerMessage,
@@ -28,13 +29,26 @@ pub struct Module {
     pub connection_event_filters: Vec<ConnectionEventFilter>,
     pub channel_event_filters: Vec<ChannelEventFilter>,
     pub packet_event_filters: Vec<PacketEventFilter>,
+
+    pub connection_event_deny_filters: Vec<ConnectionEventFilter>,
+    pub channel_event_deny_filters: Vec<ChannelEventFilter>,
+    pub packet_event_deny_filters: Vec<PacketEventFilter>,
 }
 
+/// Events are relayed if they match at least one of the filters for their event type, and none of
+/// the deny filters for their event type.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Config {
     pub connection_event_filters: Vec<ConnectionEventFilter>,
     pub channel_event_filters: Vec<ChannelEventFilter>,
     pub packet_event_filters: Vec<PacketEventFilter>,
+
+    #[serde(default)]
+    pub connection_event_deny_filters: Vec<ConnectionEventFilter>,
+    #[serde(default)]
+    pub channel_event_deny_filters: Vec<ChannelEventFilter>,
+    #[serde(default)]
+    pub packet_event_deny_filters: Vec<PacketEventFilter>,
 }
 
 impl Plugin for Module {
@@ -70,6 +84,9 @@ pub struct ConnectionEventFilter {
     pub chain_id: Regex,
     #[serde_as(as = "DisplayFromStr")]
     #[serde(default = "match_any")]
+    pub counterparty_chain_id: Regex,
+    #[serde_as(as = "DisplayFromStr")]
+    #[serde(default = "match_any")]
     pub client_id: Regex,
     #[serde_as(as = "DisplayFromStr")]
     #[serde(default = "match_any")]
@@ -80,15 +97,18 @@ impl ConnectionEventFilter {
     fn to_jaq(&self) -> String {
         let Self {
             chain_id,
+            counterparty_chain_id,
             client_id,
             counterparty_client_id,
         } = self;
 
+        // ids are strings in ibc-classic and numbers in ibc-union
         format!(
             r#"(
                 ($chain_id | test("{chain_id}"))
-                and ($event.client_id | test("{client_id}"))
-                and ($event.counterparty_client_id | test("{counterparty_client_id}"))
+                and ($counterparty_chain_id | test("{counterparty_chain_id}"))
+                and ($event.client_id | tostring | test("{client_id}"))
+                and ($event.counterparty_client_id | tostring | test("{counterparty_client_id}"))
             )"#
         )
     }
@@ -106,6 +126,9 @@ pub struct ChannelEventFilter {
     pub chain_id: Regex,
     #[serde_as(as = "DisplayFromStr")]
     #[serde(default = "match_any")]
+    pub counterparty_chain_id: Regex,
+    #[serde_as(as = "DisplayFromStr")]
+    #[serde(default = "match_any")]
     pub connection_id: Regex,
     #[serde_as(as = "DisplayFromStr")]
     #[serde(default = "match_any")]
@@ -119,21 +142,29 @@ pub struct ChannelEventFilter {
 }
 
 impl ChannelEventFilter {
-    fn to_jaq(&self) -> String {
+    fn to_jaq(&self, ibc_spec_id: &IbcSpecId) -> String {
         let Self {
             chain_id,
+            counterparty_chain_id,
             connection_id,
             port_id,
             counterparty_port_id,
             channel_version,
         } = self;
 
+        let connection_id_path = if ibc_spec_id == &IbcUnion::ID {
+            "$event.connection.connection_id"
+        } else {
+            "$event.connection_id"
+        };
+
         format!(
             r#"(
                 ($chain_id | test("{chain_id}"))
+                and ($counterparty_chain_id | test("{counterparty_chain_id}"))
                 and ($event.port_id | test("{port_id}"))
-                and ($event.counterparty_port_id | test("{counterparty_port_id }"))
-                and ($event.connection_id | test("{connection_id}"))
+                and ($event.counterparty_port_id | test("{counterparty_port_id}"))
+                and ({connection_id_path} | tostring | test("{connection_id}"))
                 and ($event.version | test("{channel_version}"))
             )"#
         )
@@ -146,6 +177,9 @@ pub struct PacketEventFilter {
     #[serde_as(as = "DisplayFromStr")]
     #[serde(default = "match_any")]
     pub chain_id: Regex,
+    #[serde_as(as = "DisplayFromStr")]
+    #[serde(default = "match_any")]
+    pub counterparty_chain_id: Regex,
 
     #[serde_as(as = "DisplayFromStr")]
     #[serde(default = "match_any")]
@@ -175,9 +209,12 @@ pub struct PacketEventFilter {
 }
 
 impl PacketEventFilter {
-    fn to_jaq(&self) -> String {
+    /// Ports are not part of the packet metadata in ibc-union, so `source_port_id` and
+    /// `destination_port_id` are ignored for ibc-union packets.
+    fn to_jaq(&self, ibc_spec_id: &IbcSpecId) -> String {
         let Self {
             chain_id,
+            counterparty_chain_id,
             source_connection_id,
             source_port_id,
             source_channel_id,
@@ -188,18 +225,30 @@ impl PacketEventFilter {
             destination_channel_version,
         } = self;
 
+        let (source_port_filter, destination_port_filter) = if ibc_spec_id == &IbcUnion::ID {
+            ("true".to_owned(), "true".to_owned())
+        } else {
+            (
+                format!(r#"($event.packet.source_channel.port_id | test("{source_port_id}"))"#),
+                format!(
+                    r#"($event.packet.destination_channel.port_id | test("{destination_port_id}"))"#
+                ),
+            )
+        };
+
         format!(
             r#"(
                 ($chain_id | test("{chain_id}"))
-                and ($event.packet.source_channel.port_id | test("{source_port_id}"))
-                and ($event.packet.source_channel.channel_id | test("{source_channel_id}"))
+                and ($counterparty_chain_id | test("{counterparty_chain_id}"))
+                and {source_port_filter}
+                and ($event.packet.source_channel.channel_id | tostring | test("{source_channel_id}"))
                 and ($event.packet.source_channel.version | test("{source_channel_version}"))
-                and ($event.packet.source_channel.connection.connection_id | test("{source_connection_id}"))
+                and ($event.packet.source_channel.connection.connection_id | tostring | test("{source_connection_id}"))
 
-                and ($event.packet.destination_channel.port_id | test("{destination_port_id}"))
-                and ($event.packet.destination_channel.channel_id | test("{destination_channel_id}"))
+                and {destination_port_filter}
+                and ($event.packet.destination_channel.channel_id | tostring | test("{destination_channel_id}"))
                 and ($event.packet.destination_channel.version | test("{destination_channel_version}"))
-                and ($event.packet.destination_channel.connection.connection_id | test("{destination_connection_id}"))
+                and ($event.packet.destination_channel.connection.connection_id | tostring | test("{destination_connection_id}"))
             )"#
         )
     }
@@ -217,41 +266,89 @@ impl Module {
             connection_event_filters: config.connection_event_filters,
             channel_event_filters: config.channel_event_filters,
             packet_event_filters: config.packet_event_filters,
+            connection_event_deny_filters: config.connection_event_deny_filters,
+            channel_event_deny_filters: config.channel_event_deny_filters,
+            packet_event_deny_filters: config.packet_event_deny_filters,
         }
     }
 
     /// Construct the filter that will run on every event. If this returns true, then this plugin will receive the event in it's optimization queue and drop it.
     /// To accomplish this, the filter expresses "inverted interest" - since the regex filters filter *in* what we want to keep, this filter must return false for all messages that match the regex filters, the regex filters and true for everything else.
-    // TODO: Support IBC union as well
     pub fn make_filter(&self) -> String {
-        // let filter = Term::<&str>::IfThenElse(vec![], Some(Box::new(Term::Call("false", vec![]))));
-
-        // Filter::from(&filter)
-
-        // if no filters are provided, then none all events for that specific IBC message type will be filtered out (i.e we express interest here). to do this, we return `false`, since in the context that this will be called in expresses whether or not the event matched one of the "filter in" regex filters.
-        let packet_filter = ["false".to_owned()]
-            .into_iter()
-            .chain(self.packet_event_filters.iter().map(|x| x.to_jaq()))
-            .collect::<Vec<_>>()
-            .join(" or ");
-        let channel_filter = ["false".to_owned()]
-            .into_iter()
-            .chain(self.channel_event_filters.iter().map(|x| x.to_jaq()))
-            .collect::<Vec<_>>()
-            .join(" or ");
-        let connection_filter = ["false".to_owned()]
-            .into_iter()
-            .chain(self.connection_event_filters.iter().map(|x| x.to_jaq()))
-            .collect::<Vec<_>>()
-            .join(" or ");
-
         format!(
             r#"
 if ."@type" == "data" then
     ."@value" as $data |
 
-    if $data."@type" == "ibc_event" and $data."@value".ibc_spec_id == {ibc_spec_id} then
+    if $data."@type" == "ibc_event" and $data."@value".ibc_spec_id == "{ibc_classic_id}" then
+        {ibc_classic_filter}
+    elif $data."@type" == "ibc_event" and $data."@value".ibc_spec_id == "{ibc_union_id}" then
+        {ibc_union_filter}
+    else
+        # don't filter out data messages that aren't IBC events
+        false
+    end
+else
+    # don't filter out non-data messages
+    false
+end
+    "#,
+            ibc_classic_id = IbcClassic::ID,
+            ibc_classic_filter = self.make_event_filter(&IbcClassic::ID),
+            ibc_union_id = IbcUnion::ID,
+            ibc_union_filter = self.make_event_filter(&IbcUnion::ID),
+        )
+    }
+
+    fn make_event_filter(&self, ibc_spec_id: &IbcSpecId) -> String {
+        // an event matches if it matches at least one of the filters and none of the deny filters.
+        //
+        // if no filters are provided, then all events for that specific IBC message type will be filtered out (i.e we express interest here). to do this, we return `false`, since in the context that this will be called in expresses whether or not the event matched one of the "filter in" regex filters.
+        fn mk(
+            filters: impl IntoIterator<Item = String>,
+            deny_filters: impl IntoIterator<Item = String>,
+        ) -> String {
+            let filter = ["false".to_owned()]
+                .into_iter()
+                .chain(filters)
+                .collect::<Vec<_>>()
+                .join(" or ");
+            let deny_filter = ["false".to_owned()]
+                .into_iter()
+                .chain(deny_filters)
+                .collect::<Vec<_>>()
+                .join(" or ");
+
+            format!("(({filter}) and (({deny_filter}) | not))")
+        }
+
+        let packet_filter = mk(
+            self.packet_event_filters
+                .iter()
+                .map(|x| x.to_jaq(ibc_spec_id)),
+            self.packet_event_deny_filters
+                .iter()
+                .map(|x| x.to_jaq(ibc_spec_id)),
+        );
+        let channel_filter = mk(
+            self.channel_event_filters
+                .iter()
+                .map(|x| x.to_jaq(ibc_spec_id)),
+            self.channel_event_deny_filters
+                .iter()
+                .map(|x| x.to_jaq(ibc_spec_id)),
+        );
+        let connection_filter = mk(
+            self.connection_event_filters.iter().map(|x| x.to_jaq()),
+            self.connection_event_deny_filters
+                .iter()
+                .map(|x| x.to_jaq()),
+        );
+
+        format!(
+            r#"
         $data."@value".chain_id as $chain_id |
+        $data."@value".counterparty_chain_id as $counterparty_chain_id |
         $data."@value".event."@type" as $event_type |
         $data."@value".event."@value" as $event |
 
@@ -282,17 +379,7 @@ if ."@type" == "data" then
         # an IBC event matched the inclusion filters - invert the result to
         # only express interest in messages that didn't match such that they
         # can be dropped in our optimization pass
-        | not
-    else
-        # don't filter out data messages that aren't IBC events
-        false
-    end
-else
-    # don't filter out non-data messages
-    false
-end
-    "#,
-            ibc_spec_id = IbcClassic::ID
+        | not"#
         )
     }
 }
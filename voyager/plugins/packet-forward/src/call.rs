@@ -0,0 +1,168 @@
+use std::num::NonZeroU64;
+
+use enumorph::Enumorph;
+use ibc_classic_spec::{AcknowledgementPath, NextSequenceSendPath};
+use jsonrpsee::core::RpcResult;
+use macros::model;
+use sha2::{Digest, Sha256};
+use tracing::{debug, info, instrument, warn};
+use unionlabs::ibc::core::client::height::Height;
+use voyager_message::{core::QueryHeight, PluginMessage, VoyagerClient, VoyagerMessage};
+use voyager_vm::{call, data, defer, now, seq, Op};
+
+use crate::{
+    data::{FirstHopAcknowledgement, ForwardedPacket, ModuleData, MultiHopTransfer, PacketRef},
+    Module,
+};
+
+#[model]
+#[derive(Enumorph)]
+pub enum ModuleCall {
+    FindForwardedPacket(FindForwardedPacket),
+    WaitForFirstHopAcknowledgement(WaitForFirstHopAcknowledgement),
+}
+
+/// Find the packet sent by the packet forward middleware on the intermediate chain when the first
+/// hop of `transfer` was received.
+#[model]
+pub struct FindForwardedPacket {
+    pub transfer: MultiHopTransfer,
+}
+
+/// Wait for the acknowledgement of the first hop of `transfer` to be written on the intermediate
+/// chain.
+///
+/// The packet forward middleware only acknowledges the first hop once the second hop has been
+/// acknowledged or has timed out, so this resolves once the whole transfer has completed.
+#[model]
+pub struct WaitForFirstHopAcknowledgement {
+    pub transfer: MultiHopTransfer,
+}
+
+/// How often the intermediate chain is checked for the acknowledgement of the first hop.
+const FIRST_HOP_ACKNOWLEDGEMENT_POLL_INTERVAL_SECONDS: u64 = 6;
+
+/// The successful acknowledgement of an ICS-20 packet, `{"result":"AQ=="}`.
+const ICS20_SUCCESS_ACKNOWLEDGEMENT: &[u8] = br#"{"result":"AQ=="}"#;
+
+impl FindForwardedPacket {
+    #[instrument(
+        skip_all,
+        fields(
+            chain_id = %self.transfer.first_hop.chain_id,
+            port_id = %self.transfer.forward.port_id,
+            channel_id = %self.transfer.forward.channel_id,
+            received_height = %self.transfer.received_height,
+        )
+    )]
+    pub async fn call(
+        self,
+        module: &Module,
+        voyager_client: &VoyagerClient,
+    ) -> RpcResult<Op<VoyagerMessage>> {
+        let chain_id = &self.transfer.first_hop.chain_id;
+        let received_height = self.transfer.received_height;
+
+        let next_sequence_send_at = |height: Height| {
+            voyager_client.query_ibc_state(
+                chain_id.clone(),
+                QueryHeight::Specific(height),
+                NextSequenceSendPath {
+                    port_id: self.transfer.forward.port_id.clone(),
+                    channel_id: self.transfer.forward.channel_id.clone(),
+                },
+            )
+        };
+
+        let before = next_sequence_send_at(Height::new_with_revision(
+            received_height.revision(),
+            received_height.height() - 1,
+        ))
+        .await?
+        .state;
+
+        let after = next_sequence_send_at(received_height).await?.state;
+
+        debug!(%before, %after, "packets sent on the forward channel");
+
+        let packet = match (after.checked_sub(before), NonZeroU64::new(before)) {
+            (Some(1), Some(sequence)) => {
+                info!(%sequence, "found forwarded packet");
+
+                Some(PacketRef {
+                    chain_id: chain_id.clone(),
+                    port_id: self.transfer.forward.port_id,
+                    channel_id: self.transfer.forward.channel_id,
+                    sequence,
+                })
+            }
+            _ => {
+                warn!(
+                    "unable to identify the forwarded packet, {} packets were sent on the forward \
+                    channel in the block the first hop was received in",
+                    after.saturating_sub(before)
+                );
+
+                None
+            }
+        };
+
+        Ok(data(PluginMessage::new(
+            module.plugin_name(),
+            ModuleData::from(ForwardedPacket { packet }),
+        )))
+    }
+}
+
+impl WaitForFirstHopAcknowledgement {
+    #[instrument(
+        skip_all,
+        fields(
+            chain_id = %self.transfer.first_hop.chain_id,
+            port_id = %self.transfer.first_hop.port_id,
+            channel_id = %self.transfer.first_hop.channel_id,
+            sequence = %self.transfer.first_hop.sequence,
+        )
+    )]
+    pub async fn call(
+        self,
+        module: &Module,
+        voyager_client: &VoyagerClient,
+    ) -> RpcResult<Op<VoyagerMessage>> {
+        let first_hop = &self.transfer.first_hop;
+
+        let acknowledgement = voyager_client
+            .query_ibc_state(
+                first_hop.chain_id.clone(),
+                QueryHeight::Latest,
+                AcknowledgementPath {
+                    port_id: first_hop.port_id.clone(),
+                    channel_id: first_hop.channel_id.clone(),
+                    sequence: first_hop.sequence,
+                },
+            )
+            .await?
+            .state;
+
+        match acknowledgement {
+            Some(commitment) => {
+                let success = commitment.get().as_slice()
+                    == Sha256::digest(ICS20_SUCCESS_ACKNOWLEDGEMENT).as_slice();
+
+                debug!(%commitment, %success, "first hop has been acknowledged");
+
+                Ok(data(PluginMessage::new(
+                    module.plugin_name(),
+                    ModuleData::from(FirstHopAcknowledgement { success }),
+                )))
+            }
+            None => Ok(seq([
+                defer(now() + FIRST_HOP_ACKNOWLEDGEMENT_POLL_INTERVAL_SECONDS),
+                call(PluginMessage::new(
+                    module.plugin_name(),
+                    ModuleCall::from(self),
+                )),
+            ])),
+        }
+    }
+}
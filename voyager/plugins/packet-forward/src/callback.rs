@@ -0,0 +1,85 @@
+use std::collections::VecDeque;
+
+use enumorph::Enumorph;
+use jsonrpsee::{core::RpcResult, types::ErrorObject};
+use macros::model;
+use tracing::{info, instrument};
+use voyager_message::{data::Data, PluginMessage, VoyagerMessage, FATAL_JSONRPC_ERROR_CODE};
+use voyager_vm::{data, Op};
+
+use crate::{
+    data::{
+        FirstHopAcknowledgement, ForwardedPacket, ModuleData, MultiHopTransfer,
+        MultiHopTransferStatus,
+    },
+    Module,
+};
+
+#[model]
+#[derive(Enumorph)]
+pub enum ModuleCallback {
+    AggregateMultiHopTransferStatus(AggregateMultiHopTransferStatus),
+}
+
+/// Combine the [`ForwardedPacket`] and [`FirstHopAcknowledgement`] of `transfer` into a
+/// [`MultiHopTransferStatus`].
+#[model]
+pub struct AggregateMultiHopTransferStatus {
+    pub transfer: MultiHopTransfer,
+}
+
+impl AggregateMultiHopTransferStatus {
+    #[instrument(
+        skip_all,
+        fields(
+            source_chain_id = %self.transfer.source_chain_id,
+            chain_id = %self.transfer.first_hop.chain_id,
+            sequence = %self.transfer.first_hop.sequence,
+        )
+    )]
+    pub fn call(self, module: &Module, datas: VecDeque<Data>) -> RpcResult<Op<VoyagerMessage>> {
+        let mut second_hop = None;
+        let mut acknowledgement = None;
+
+        for d in datas {
+            match d.as_plugin::<ModuleData>(module.plugin_name()) {
+                Ok(ModuleData::ForwardedPacket(ForwardedPacket { packet })) => {
+                    second_hop = Some(packet);
+                }
+                Ok(ModuleData::FirstHopAcknowledgement(ack)) => acknowledgement = Some(ack),
+                found => {
+                    return Err(ErrorObject::owned(
+                        FATAL_JSONRPC_ERROR_CODE,
+                        "unexpected data",
+                        Some(serde_json::json!({ "found": format!("{found:?}") })),
+                    ))
+                }
+            }
+        }
+
+        let (Some(second_hop), Some(FirstHopAcknowledgement { success })) =
+            (second_hop, acknowledgement)
+        else {
+            return Err(ErrorObject::owned(
+                FATAL_JSONRPC_ERROR_CODE,
+                "expected both the forwarded packet and the first hop acknowledgement",
+                None::<()>,
+            ));
+        };
+
+        info!(
+            %success,
+            second_hop_sequence = ?second_hop.as_ref().map(|packet| packet.sequence),
+            "multi-hop transfer completed"
+        );
+
+        Ok(data(PluginMessage::new(
+            module.plugin_name(),
+            ModuleData::from(MultiHopTransferStatus {
+                transfer: self.transfer,
+                second_hop,
+                success,
+            }),
+        )))
+    }
+}
@@ -0,0 +1,146 @@
+use std::num::NonZeroU64;
+
+use enumorph::Enumorph;
+use macros::model;
+use serde::Deserialize;
+use serde_json::Value;
+use subset_of::SubsetOf;
+use unionlabs::{
+    ibc::core::client::height::Height,
+    id::{ChannelId, PortId},
+};
+use voyager_message::core::ChainId;
+
+#[model]
+#[derive(Enumorph, SubsetOf)]
+pub enum ModuleData {
+    ForwardedPacket(ForwardedPacket),
+    FirstHopAcknowledgement(FirstHopAcknowledgement),
+    MultiHopTransferStatus(MultiHopTransferStatus),
+}
+
+/// A packet on an [`IbcClassic`](ibc_classic_spec::IbcClassic) channel, identified by the end
+/// of the channel on `chain_id`.
+#[model]
+pub struct PacketRef {
+    pub chain_id: ChainId,
+    pub port_id: PortId,
+    pub channel_id: ChannelId,
+    pub sequence: NonZeroU64,
+}
+
+/// A transfer from `source_chain_id` that is forwarded through [`Self::first_hop`]'s chain by the
+/// packet forward middleware.
+#[model]
+pub struct MultiHopTransfer {
+    pub source_chain_id: ChainId,
+    /// The packet of the first hop, as received on the intermediate chain.
+    pub first_hop: PacketRef,
+    /// The height of the block on the intermediate chain that the first hop was received in.
+    pub received_height: Height,
+    pub forward: ForwardMetadata,
+}
+
+/// The second hop of a multi-hop transfer, as found on the intermediate chain.
+#[model]
+pub struct ForwardedPacket {
+    /// `None` if the packet could not be identified, which is the case if multiple packets were
+    /// sent on the forward channel in the block the first hop was received in.
+    pub packet: Option<PacketRef>,
+}
+
+#[model]
+pub struct FirstHopAcknowledgement {
+    pub success: bool,
+}
+
+/// The combined status of a multi-hop transfer, emitted once the transfer has either completed
+/// or failed on all hops.
+#[model]
+pub struct MultiHopTransferStatus {
+    pub transfer: MultiHopTransfer,
+    pub second_hop: Option<PacketRef>,
+    /// Whether the transfer reached its final destination. If this is `false`, the tokens have
+    /// been refunded on the source chain.
+    pub success: bool,
+}
+
+/// The `forward` object of a packet forward middleware memo.
+///
+/// See <https://github.com/cosmos/ibc-apps/tree/main/middleware/packet-forward-middleware#full-example>.
+#[model]
+pub struct ForwardMetadata {
+    pub receiver: String,
+    pub port_id: PortId,
+    pub channel_id: ChannelId,
+    /// The memo of the forwarded packet. If this contains another `forward` object, the transfer
+    /// has more than two hops; only the first two are tracked.
+    pub next: Option<Value>,
+}
+
+#[derive(Deserialize)]
+struct PacketForwardMemo {
+    forward: RawForwardMetadata,
+}
+
+#[derive(Deserialize)]
+struct RawForwardMetadata {
+    receiver: String,
+    port: String,
+    channel: String,
+    #[serde(default)]
+    next: Option<Value>,
+}
+
+impl ForwardMetadata {
+    /// Parse the forward metadata out of the memo of an ICS-20 packet, if the memo contains any.
+    pub fn from_memo(memo: &str) -> Option<Self> {
+        let PacketForwardMemo { forward } = serde_json::from_str(memo).ok()?;
+
+        Some(Self {
+            receiver: forward.receiver,
+            port_id: forward.port.parse().ok()?,
+            channel_id: ChannelId::from_str_prefixed(&forward.channel).ok()?,
+            next: forward.next,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn forward_metadata_from_memo() {
+        assert_eq!(
+            ForwardMetadata::from_memo(
+                r#"{"forward":{"receiver":"osmo1a","port":"transfer","channel":"channel-7","timeout":"10m","retries":2}}"#
+            ),
+            Some(ForwardMetadata {
+                receiver: "osmo1a".to_owned(),
+                port_id: "transfer".parse().unwrap(),
+                channel_id: ChannelId::new(7),
+                next: None,
+            })
+        );
+
+        assert_eq!(
+            ForwardMetadata::from_memo(
+                r#"{"forward":{"receiver":"b","port":"transfer","channel":"channel-1","next":{"forward":{"receiver":"c","port":"transfer","channel":"channel-2"}}}}"#
+            )
+            .and_then(|forward| forward.next)
+            .and_then(|next| ForwardMetadata::from_memo(&next.to_string()))
+            .map(|forward| forward.channel_id),
+            Some(ChannelId::new(2))
+        );
+
+        assert_eq!(ForwardMetadata::from_memo(""), None);
+        assert_eq!(ForwardMetadata::from_memo(r#"{"wasm":{}}"#), None);
+        assert_eq!(
+            ForwardMetadata::from_memo(
+                r#"{"forward":{"receiver":"a","port":"transfer","channel":"7"}}"#
+            ),
+            None
+        );
+    }
+}
@@ -0,0 +1,230 @@
+//! Tracking of transfers that are forwarded through an intermediate chain by the [packet forward
+//! middleware].
+//!
+//! When the first hop of a forwarded transfer (A → intermediate) is received on the intermediate
+//! chain, the middleware sends the second hop (intermediate → B) in the same transaction, and only
+//! acknowledges the first hop once the second hop has been acknowledged or has timed out. Both hops
+//! are relayed like any other packet; this plugin additionally correlates them and emits a single
+//! [`MultiHopTransferStatus`](data::MultiHopTransferStatus) once the transfer has completed on all
+//! hops, such that a multi-hop transfer can be treated as one unit.
+//!
+//! [packet forward middleware]: https://github.com/cosmos/ibc-apps/tree/main/middleware/packet-forward-middleware
+
+use std::collections::VecDeque;
+
+use ibc_classic_spec::{FullEvent, IbcClassic};
+use jsonrpsee::{
+    core::{async_trait, RpcResult},
+    Extensions,
+};
+use serde::{Deserialize, Serialize};
+use tracing::{debug, instrument};
+use unionlabs::ibc::core::client::height::Height;
+use voyager_message::{
+    core::{ChainId, IbcSpec},
+    data::{ChainEvent, Data},
+    event::DecodedPacketData,
+    module::{PluginInfo, PluginServer},
+    DefaultCmd, ExtensionsExt, Plugin, PluginMessage, VoyagerMessage,
+};
+use voyager_vm::{call, pass::PassResult, promise, BoxDynError, Op};
+
+use crate::{
+    call::{FindForwardedPacket, ModuleCall, WaitForFirstHopAcknowledgement},
+    callback::{AggregateMultiHopTransferStatus, ModuleCallback},
+    data::{ForwardMetadata, MultiHopTransfer, PacketRef},
+};
+
+pub mod call;
+pub mod callback;
+pub mod data;
+
+#[tokio::main(flavor = "multi_thread")]
+async fn main() {
+    Module::run().await
+}
+
+#[derive(Debug, Clone)]
+pub struct Module {
+    /// The intermediate chain, running the packet forward middleware.
+    pub chain_id: ChainId,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// The intermediate chain, running the packet forward middleware.
+    pub chain_id: ChainId,
+}
+
+impl Plugin for Module {
+    type Call = ModuleCall;
+    type Callback = ModuleCallback;
+
+    type Config = Config;
+    type Cmd = DefaultCmd;
+
+    async fn new(config: Self::Config) -> Result<Self, BoxDynError> {
+        Ok(Module::new(config))
+    }
+
+    fn info(config: Self::Config) -> PluginInfo {
+        let module = Module::new(config);
+
+        PluginInfo {
+            name: module.plugin_name(),
+            interest_filter: format!(
+                r#"
+if ."@type" == "data" then
+    ."@value" as $data |
+
+    # ics20 packets received on the intermediate chain that are to be forwarded
+    $data."@type" == "ibc_event"
+    and $data."@value".chain_id == "{chain_id}"
+    and $data."@value".ibc_spec_id == "{ibc_classic_id}"
+    and $data."@value".event."@type" == "recv_packet"
+    and $data."@value".decoded_packet_data."@type" == "ics20"
+    and ($data."@value".decoded_packet_data."@value".memo | contains("\"forward\""))
+else
+    false
+end
+"#,
+                chain_id = module.chain_id,
+                ibc_classic_id = IbcClassic::ID,
+            ),
+        }
+    }
+
+    async fn cmd(_config: Self::Config, cmd: Self::Cmd) {
+        match cmd {}
+    }
+}
+
+pub const PLUGIN_NAME: &str = env!("CARGO_PKG_NAME");
+
+impl Module {
+    fn plugin_name(&self) -> String {
+        format!("{PLUGIN_NAME}/{}", self.chain_id)
+    }
+
+    pub fn new(config: Config) -> Self {
+        Self {
+            chain_id: config.chain_id,
+        }
+    }
+
+    /// The multi-hop transfer started by `event`, if it is the first hop of a forwarded ICS-20
+    /// transfer.
+    fn multi_hop_transfer(&self, event: &ChainEvent) -> Option<MultiHopTransfer> {
+        let Some(DecodedPacketData::Ics20(packet_data)) = &event.decoded_packet_data else {
+            return None;
+        };
+
+        let Ok(FullEvent::RecvPacket(recv_packet)) = event.decode_event::<IbcClassic>()? else {
+            return None;
+        };
+
+        let forward = ForwardMetadata::from_memo(&packet_data.memo)?;
+
+        Some(MultiHopTransfer {
+            source_chain_id: event.counterparty_chain_id.clone(),
+            first_hop: PacketRef {
+                chain_id: event.chain_id.clone(),
+                port_id: recv_packet.packet.destination_channel.port_id,
+                channel_id: recv_packet.packet.destination_channel.channel_id,
+                sequence: recv_packet.packet.sequence,
+            },
+            // the provable height of an event is the height after the block it was emitted in
+            received_height: Height::new_with_revision(
+                event.provable_height.revision(),
+                event.provable_height.height() - 1,
+            ),
+            forward,
+        })
+    }
+}
+
+#[async_trait]
+impl PluginServer<ModuleCall, ModuleCallback> for Module {
+    #[instrument(skip_all, fields(chain_id = %self.chain_id))]
+    async fn run_pass(
+        &self,
+        _: &Extensions,
+        msgs: Vec<Op<VoyagerMessage>>,
+    ) -> RpcResult<PassResult<VoyagerMessage>> {
+        let ready = msgs
+            .into_iter()
+            .enumerate()
+            .flat_map(|(idx, op)| {
+                let transfer = match &op {
+                    Op::Data(Data::IbcEvent(event)) => self.multi_hop_transfer(event),
+                    _ => None,
+                };
+
+                let tracking = transfer.map(|transfer| {
+                    debug!(
+                        source_chain_id = %transfer.source_chain_id,
+                        sequence = %transfer.first_hop.sequence,
+                        forward_channel_id = %transfer.forward.channel_id,
+                        "tracking multi-hop transfer"
+                    );
+
+                    (
+                        vec![idx],
+                        promise(
+                            [
+                                call(PluginMessage::new(
+                                    self.plugin_name(),
+                                    ModuleCall::from(FindForwardedPacket {
+                                        transfer: transfer.clone(),
+                                    }),
+                                )),
+                                call(PluginMessage::new(
+                                    self.plugin_name(),
+                                    ModuleCall::from(WaitForFirstHopAcknowledgement {
+                                        transfer: transfer.clone(),
+                                    }),
+                                )),
+                            ],
+                            [],
+                            PluginMessage::new(
+                                self.plugin_name(),
+                                ModuleCallback::from(AggregateMultiHopTransferStatus { transfer }),
+                            ),
+                        ),
+                    )
+                });
+
+                // the event itself is passed through untouched
+                [Some((vec![idx], op)), tracking].into_iter().flatten()
+            })
+            .collect();
+
+        Ok(PassResult {
+            optimize_further: vec![],
+            ready,
+        })
+    }
+
+    #[instrument(skip_all, fields(chain_id = %self.chain_id))]
+    async fn call(&self, e: &Extensions, msg: ModuleCall) -> RpcResult<Op<VoyagerMessage>> {
+        match msg {
+            ModuleCall::FindForwardedPacket(find) => find.call(self, e.try_get()?).await,
+            ModuleCall::WaitForFirstHopAcknowledgement(wait) => wait.call(self, e.try_get()?).await,
+        }
+    }
+
+    #[instrument(skip_all, fields(chain_id = %self.chain_id))]
+    async fn callback(
+        &self,
+        _: &Extensions,
+        cb: ModuleCallback,
+        datas: VecDeque<Data>,
+    ) -> RpcResult<Op<VoyagerMessage>> {
+        match cb {
+            ModuleCallback::AggregateMultiHopTransferStatus(aggregate) => {
+                aggregate.call(self, datas)
+            }
+        }
+    }
+}
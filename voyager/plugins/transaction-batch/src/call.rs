@@ -12,7 +12,7 @@ use voyager_message::{
     core::{ChainId, QueryHeight},
     PluginMessage, RawClientId, VoyagerClient, VoyagerMessage, FATAL_JSONRPC_ERROR_CODE,
 };
-use voyager_vm::{now, promise, Op};
+use voyager_vm::{defer, now, promise, seq, Op};
 
 use crate::{
     call,
@@ -29,6 +29,31 @@ pub enum ModuleCall {
 
     MakeMsgV1(MakeMsg<IbcClassic>),
     MakeMsgUnion(MakeMsg<IbcUnion>),
+
+    CheckPacketTimeoutsUnion(CheckPacketTimeouts),
+}
+
+/// Check whether packets sent to `channel_id` on this chain have timed out without being received,
+/// and hand the ones that have off to the instances of this plugin running for the chains they
+/// were sent from to be timed out.
+///
+/// The packets may never be received on this chain (for example if the client update or the
+/// transaction fails), so this is scheduled once per channel for the packets with a timeout that
+/// are seen in a pass. Packets that have not timed out yet are checked again later.
+#[model]
+pub struct CheckPacketTimeouts {
+    /// The channel on this chain that the packets were sent to.
+    pub channel_id: u32,
+    pub packets: Vec<PendingPacketTimeout>,
+}
+
+#[model]
+pub struct PendingPacketTimeout {
+    /// The chain the packet was sent from.
+    pub origin_chain_id: ChainId,
+    /// unix timestamp (in ms) of when the packet was first seen by this plugin.
+    pub first_seen_at: u64,
+    pub event: ibc_union_spec::SendPacket,
 }
 
 /// Constructs multiple batch transactions, where all of the batches are provable at the new consensus height.
@@ -38,9 +63,13 @@ pub struct MakeTransactionBatchesWithUpdate<V: IbcSpecExt> {
     pub batches: Vec<Vec<BatchableEvent<V>>>,
 }
 
+/// How often a batch that joined a pending client update checks whether the client has been
+/// updated.
+const COALESCED_UPDATE_POLL_INTERVAL_SECONDS: u64 = 3;
+
 impl<V: IbcSpecExt> MakeTransactionBatchesWithUpdate<V>
 where
-    ModuleCall: From<MakeMsg<V>>,
+    ModuleCall: From<MakeMsg<V>> + From<MakeTransactionBatchesWithUpdate<V>>,
     ModuleCallback: From<MakeBatchTransaction<V>> + From<MakeIbcMessagesFromUpdate<V>>,
 {
     pub async fn call(
@@ -96,7 +125,13 @@ where
             ));
         }
 
+        let raw_client_id = RawClientId::new(self.client_id.clone());
+
         if client_meta.height >= target_height {
+            module
+                .update_coalescer
+                .complete(&raw_client_id, client_meta.height);
+
             info!(
                 "client {client_id} has already been updated to a height \
                 >= the desired target height ({} >= {target_height})",
@@ -112,6 +147,27 @@ where
                 client_meta.clone(),
                 client_meta.height,
             )
+        } else if let Some(pending_update_to) = module.update_coalescer.join_or_start(
+            &raw_client_id,
+            target_height,
+            latest_height,
+            now(),
+        ) {
+            info!(
+                %pending_update_to,
+                %target_height,
+                "client {client_id} is already being updated to a height >= the desired target \
+                height, waiting for the pending update",
+                client_id = self.client_id,
+            );
+
+            Ok(seq([
+                defer(now() + COALESCED_UPDATE_POLL_INTERVAL_SECONDS),
+                call(PluginMessage::new(
+                    module.plugin_name(),
+                    ModuleCall::from(self),
+                )),
+            ]))
         } else {
             Ok(promise(
                 [promise(
@@ -125,7 +181,7 @@ where
                     AggregateMsgUpdateClientsFromOrderedHeaders {
                         chain_id: module.chain_id.clone(),
                         ibc_spec_id: V::ID,
-                        counterparty_client_id: RawClientId::new(self.client_id.clone()),
+                        counterparty_client_id: raw_client_id,
                     },
                 )],
                 [],
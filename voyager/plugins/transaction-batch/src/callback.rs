@@ -238,6 +238,7 @@ impl<V: IbcSpecExt> MakeBatchTransaction<V> {
                             client_id: RawClientId::new(self.client_id.clone()),
                             ibc_spec_id: V::ID,
                             height: required_consensus_height,
+                            deadline: None,
                         }),
                         data(WithChainId {
                             chain_id,
@@ -0,0 +1,161 @@
+//! Coalescing of client updates across batches.
+//!
+//! Every batch of events that is not yet provable on the counterparty requests its own client
+//! update, so multiple batches for the same client that are made in quick succession (for example
+//! when packets are relayed in both directions, or when a batch is split due to
+//! `max_batch_size`) would each update the client to (roughly) the same height. Instead, the first
+//! batch starts the update and records it as pending; later batches whose target height is covered
+//! by the pending update wait for the client to be updated and are then built against the height
+//! that the first update provided.
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use serde::{Deserialize, Serialize};
+use unionlabs::ibc::core::client::height::Height;
+use voyager_message::RawClientId;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct UpdateCoalescingConfig {
+    /// How long a pending update is shared with other batches for. This should be longer than it
+    /// usually takes for an update to land on chain; if the update takes longer than this (or
+    /// fails), the next batch will start a new update.
+    #[serde(default = "default_window_seconds")]
+    pub window_seconds: u64,
+}
+
+impl Default for UpdateCoalescingConfig {
+    fn default() -> Self {
+        Self {
+            window_seconds: default_window_seconds(),
+        }
+    }
+}
+
+#[must_use]
+#[inline]
+pub const fn default_window_seconds() -> u64 {
+    60
+}
+
+/// The client updates that are currently in flight on this chain, keyed by client. Plugins are
+/// instantiated per chain, so the client id alone identifies the client.
+#[derive(Debug, Clone, Default)]
+pub struct UpdateCoalescer {
+    window_seconds: u64,
+    /// The height each client is being updated to, along with the unix timestamp (in seconds) at
+    /// which the update was started.
+    pending: Arc<Mutex<HashMap<RawClientId, (Height, u64)>>>,
+}
+
+impl UpdateCoalescer {
+    #[must_use]
+    pub fn new(config: &UpdateCoalescingConfig) -> Self {
+        Self {
+            window_seconds: config.window_seconds,
+            pending: Arc::default(),
+        }
+    }
+
+    /// Join the pending update of `client_id`, if there is one that updates the client to at least
+    /// `target_height`, returning the height it updates the client to. Otherwise, record a new
+    /// pending update to `update_to` and return `None`, in which case the caller is expected to
+    /// start the update.
+    pub fn join_or_start(
+        &self,
+        client_id: &RawClientId,
+        target_height: Height,
+        update_to: Height,
+        now: u64,
+    ) -> Option<Height> {
+        let mut pending = self.pending.lock().expect("mutex is poisoned");
+
+        pending.retain(|_, (_, started_at)| now.saturating_sub(*started_at) < self.window_seconds);
+
+        match pending.get(client_id) {
+            Some((pending_update_to, _)) if *pending_update_to >= target_height => {
+                Some(*pending_update_to)
+            }
+            _ => {
+                pending.insert(client_id.clone(), (update_to, now));
+
+                None
+            }
+        }
+    }
+
+    /// Stop sharing the pending update of `client_id`, if the client has been updated to (or past)
+    /// its height.
+    pub fn complete(&self, client_id: &RawClientId, trusted_height: Height) {
+        let mut pending = self.pending.lock().expect("mutex is poisoned");
+
+        if pending
+            .get(client_id)
+            .is_some_and(|(update_to, _)| *update_to <= trusted_height)
+        {
+            pending.remove(client_id);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn client_id(id: &str) -> RawClientId {
+        RawClientId::new(id)
+    }
+
+    #[test]
+    fn coalesces_covered_updates() {
+        let coalescer = UpdateCoalescer::new(&UpdateCoalescingConfig { window_seconds: 60 });
+
+        assert_eq!(
+            coalescer.join_or_start(&client_id("1"), Height::new(10), Height::new(20), 0),
+            None
+        );
+        // covered by the pending update
+        assert_eq!(
+            coalescer.join_or_start(&client_id("1"), Height::new(15), Height::new(21), 1),
+            Some(Height::new(20))
+        );
+        // other clients are unaffected
+        assert_eq!(
+            coalescer.join_or_start(&client_id("2"), Height::new(15), Height::new(21), 1),
+            None
+        );
+        // not covered by the pending update, which is replaced
+        assert_eq!(
+            coalescer.join_or_start(&client_id("1"), Height::new(25), Height::new(30), 2),
+            None
+        );
+        assert_eq!(
+            coalescer.join_or_start(&client_id("1"), Height::new(20), Height::new(30), 3),
+            Some(Height::new(30))
+        );
+    }
+
+    #[test]
+    fn pending_updates_expire() {
+        let coalescer = UpdateCoalescer::new(&UpdateCoalescingConfig { window_seconds: 60 });
+
+        assert_eq!(
+            coalescer.join_or_start(&client_id("1"), Height::new(10), Height::new(20), 0),
+            None
+        );
+        assert_eq!(
+            coalescer.join_or_start(&client_id("1"), Height::new(10), Height::new(20), 60),
+            None
+        );
+
+        coalescer.complete(&client_id("1"), Height::new(20));
+
+        assert_eq!(
+            coalescer.join_or_start(&client_id("1"), Height::new(10), Height::new(20), 61),
+            None
+        );
+    }
+}
@@ -3,7 +3,7 @@ use ibc_classic_spec::IbcClassic;
 use ibc_union_spec::IbcUnion;
 use macros::model;
 use subset_of::SubsetOf;
-use unionlabs::ibc::core::client::height::Height;
+use unionlabs::{bytes::Bytes, ibc::core::client::height::Height};
 
 use crate::IbcSpecExt;
 
@@ -68,7 +68,6 @@ impl TryFrom<ibc_classic_spec::FullEvent> for EventClassic {
     }
 }
 
-// REVIEW: We probably want to add a way to have "a packet timed out" be a variant here as well
 /// A subset of [`FullEvent`], containing only events that cause an action on the counterparty chain.
 #[model]
 #[derive(Enumorph)]
@@ -81,8 +80,23 @@ pub enum EventUnion {
     ChannelOpenTry(ibc_union_spec::ChannelOpenTry),
     ChannelOpenAck(ibc_union_spec::ChannelOpenAck),
 
+    ChannelCloseInit(ibc_union_spec::ChannelCloseInit),
+
     SendPacket(ibc_union_spec::SendPacket),
     WriteAcknowledgement(ibc_union_spec::WriteAcknowledgement),
+
+    PacketTimeout(PacketTimeout),
+}
+
+/// A packet that was sent from this chain and has timed out on the counterparty chain.
+///
+/// This is not emitted by the chain directly, but is instead constructed by the plugin running
+/// for the destination chain of the packet once it has seen that the packet's timeout has elapsed.
+#[model]
+pub struct PacketTimeout {
+    pub packet_data: Bytes,
+
+    pub packet: ibc_union_spec::PacketMetadata,
 }
 
 impl TryFrom<ibc_union_spec::FullEvent> for EventUnion {
@@ -96,6 +110,7 @@ impl TryFrom<ibc_union_spec::FullEvent> for EventUnion {
             ibc_union_spec::FullEvent::ChannelOpenInit(e) => Ok(Self::ChannelOpenInit(e)),
             ibc_union_spec::FullEvent::ChannelOpenTry(e) => Ok(Self::ChannelOpenTry(e)),
             ibc_union_spec::FullEvent::ChannelOpenAck(e) => Ok(Self::ChannelOpenAck(e)),
+            ibc_union_spec::FullEvent::ChannelCloseInit(e) => Ok(Self::ChannelCloseInit(e)),
             ibc_union_spec::FullEvent::SendPacket(e) => Ok(Self::SendPacket(e)),
             ibc_union_spec::FullEvent::WriteAcknowledgement(e) => Ok(Self::WriteAcknowledgement(e)),
             _ => Err(()),
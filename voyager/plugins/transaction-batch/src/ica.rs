@@ -0,0 +1,225 @@
+//! Interchain accounts (ICS-27) channel handshake metadata.
+//!
+//! The version of an ICA channel is a JSON encoded [`IcaMetadata`], rather than an opaque string.
+//! The controller chain proposes the metadata in `ChanOpenInit`, and the host chain fills in the
+//! address of the interchain account in `ChanOpenTry`. The metadata is validated against the
+//! connections the channel is being opened on before the counterparty message is built, since
+//! the handshake would otherwise fail on the counterparty (after paying for the transaction).
+//!
+//! <https://github.com/cosmos/ibc/tree/main/spec/app/ics-027-interchain-accounts#metadata-negotiation-summary>
+
+use serde::{Deserialize, Serialize};
+use voyager_vm::BoxDynError;
+
+use crate::version::{HandshakeInfo, TryVersion, VersionNegotiator};
+
+pub const ICA_VERSION: &str = "ics27-1";
+
+pub const ICA_HOST_PORT_ID: &str = "icahost";
+
+pub const ICA_CONTROLLER_PORT_ID_PREFIX: &str = "icacontroller-";
+
+pub const SUPPORTED_ENCODINGS: &[&str] = &["proto3", "proto3json"];
+
+pub const SUPPORTED_TX_TYPES: &[&str] = &["sdk_multi_msg"];
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IcaMetadata {
+    pub version: String,
+    pub controller_connection_id: String,
+    pub host_connection_id: String,
+    /// The address of the interchain account on the host chain. This is empty until the host
+    /// chain has registered the account in `ChanOpenTry`.
+    #[serde(default)]
+    pub address: String,
+    pub encoding: String,
+    pub tx_type: String,
+}
+
+#[derive(Debug, PartialEq, thiserror::Error)]
+pub enum IcaMetadataError {
+    #[error("invalid ICA metadata `{0}`")]
+    Invalid(String),
+    #[error("unsupported ICA version `{0}`, expected `{ICA_VERSION}`")]
+    Version(String),
+    #[error("unsupported ICA encoding `{0}`")]
+    Encoding(String),
+    #[error("unsupported ICA tx type `{0}`")]
+    TxType(String),
+    #[error(
+        "ICA metadata has {field} `{found}`, but the channel is being opened on connection \
+        `{expected}`"
+    )]
+    ConnectionMismatch {
+        field: &'static str,
+        expected: String,
+        found: String,
+    },
+    #[error("the ICA metadata from the host chain does not contain an interchain account address")]
+    MissingAddress,
+}
+
+/// The [`VersionNegotiator`] for ICA channels. The controller always initiates the handshake, so
+/// the chain that emitted `ChanOpenInit` is the controller and the chain that emitted
+/// `ChanOpenTry` is the host.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct IcaVersionNegotiator;
+
+impl VersionNegotiator for IcaVersionNegotiator {
+    fn try_version(
+        &self,
+        info: HandshakeInfo<'_>,
+        init_version: &str,
+    ) -> Result<TryVersion, BoxDynError> {
+        IcaMetadata::parse(
+            init_version,
+            info.connection_id,
+            info.counterparty_connection_id,
+        )?;
+
+        // the host fills in the address of the interchain account
+        Ok(TryVersion {
+            version: init_version.to_owned(),
+            counterparty_version: init_version.to_owned(),
+        })
+    }
+
+    fn ack_version(
+        &self,
+        info: HandshakeInfo<'_>,
+        try_version: &str,
+    ) -> Result<String, BoxDynError> {
+        // the version returned by the host must be echoed back to the controller exactly as-is
+        IcaMetadata::parse_host_version(
+            try_version,
+            info.counterparty_connection_id,
+            info.connection_id,
+        )?;
+
+        Ok(try_version.to_owned())
+    }
+}
+
+impl IcaMetadata {
+    /// Parse and validate the version of an ICA channel, as proposed by the controller in
+    /// `ChanOpenInit` or as returned by the host in `ChanOpenTry`.
+    pub fn parse(
+        version: &str,
+        controller_connection_id: &str,
+        host_connection_id: &str,
+    ) -> Result<Self, IcaMetadataError> {
+        let metadata = serde_json::from_str::<Self>(version)
+            .map_err(|_| IcaMetadataError::Invalid(version.to_owned()))?;
+
+        if metadata.version != ICA_VERSION {
+            return Err(IcaMetadataError::Version(metadata.version));
+        }
+
+        if !SUPPORTED_ENCODINGS.contains(&metadata.encoding.as_str()) {
+            return Err(IcaMetadataError::Encoding(metadata.encoding));
+        }
+
+        if !SUPPORTED_TX_TYPES.contains(&metadata.tx_type.as_str()) {
+            return Err(IcaMetadataError::TxType(metadata.tx_type));
+        }
+
+        for (field, expected, found) in [
+            (
+                "controller_connection_id",
+                controller_connection_id,
+                &metadata.controller_connection_id,
+            ),
+            (
+                "host_connection_id",
+                host_connection_id,
+                &metadata.host_connection_id,
+            ),
+        ] {
+            if expected != found {
+                return Err(IcaMetadataError::ConnectionMismatch {
+                    field,
+                    expected: expected.to_owned(),
+                    found: found.clone(),
+                });
+            }
+        }
+
+        Ok(metadata)
+    }
+
+    /// Validate the version returned by the host in `ChanOpenTry`, which is echoed back to the
+    /// controller in `ChanOpenAck`.
+    pub fn parse_host_version(
+        version: &str,
+        controller_connection_id: &str,
+        host_connection_id: &str,
+    ) -> Result<Self, IcaMetadataError> {
+        let metadata = Self::parse(version, controller_connection_id, host_connection_id)?;
+
+        if metadata.address.is_empty() {
+            return Err(IcaMetadataError::MissingAddress);
+        }
+
+        Ok(metadata)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const INIT_VERSION: &str = r#"{"version":"ics27-1","controller_connection_id":"connection-0","host_connection_id":"connection-1","address":"","encoding":"proto3","tx_type":"sdk_multi_msg"}"#;
+
+    #[test]
+    fn parse() {
+        assert_eq!(
+            IcaMetadata::parse(INIT_VERSION, "connection-0", "connection-1"),
+            Ok(IcaMetadata {
+                version: ICA_VERSION.to_owned(),
+                controller_connection_id: "connection-0".to_owned(),
+                host_connection_id: "connection-1".to_owned(),
+                address: String::new(),
+                encoding: "proto3".to_owned(),
+                tx_type: "sdk_multi_msg".to_owned(),
+            })
+        );
+
+        assert_eq!(
+            IcaMetadata::parse(INIT_VERSION, "connection-0", "connection-2"),
+            Err(IcaMetadataError::ConnectionMismatch {
+                field: "host_connection_id",
+                expected: "connection-2".to_owned(),
+                found: "connection-1".to_owned(),
+            })
+        );
+
+        assert_eq!(
+            IcaMetadata::parse("ics20-1", "connection-0", "connection-1"),
+            Err(IcaMetadataError::Invalid("ics20-1".to_owned()))
+        );
+
+        assert_eq!(
+            IcaMetadata::parse(
+                &INIT_VERSION.replace("proto3", "amino"),
+                "connection-0",
+                "connection-1"
+            ),
+            Err(IcaMetadataError::Encoding("amino".to_owned()))
+        );
+    }
+
+    #[test]
+    fn host_version_requires_address() {
+        assert_eq!(
+            IcaMetadata::parse_host_version(INIT_VERSION, "connection-0", "connection-1"),
+            Err(IcaMetadataError::MissingAddress)
+        );
+
+        assert!(IcaMetadata::parse_host_version(
+            &INIT_VERSION.replace(r#""address":"""#, r#""address":"cosmos1ica""#),
+            "connection-0",
+            "connection-1"
+        )
+        .is_ok());
+    }
+}
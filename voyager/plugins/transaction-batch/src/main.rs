@@ -1,5 +1,5 @@
 use std::{
-    collections::{HashMap, VecDeque},
+    collections::{HashMap, HashSet, VecDeque},
     convert,
     future::Future,
     pin::Pin,
@@ -8,7 +8,10 @@ use std::{
 
 use alloy::sol_types::SolValue;
 use either::Either;
-use futures::{stream::FuturesOrdered, StreamExt, TryStreamExt};
+use futures::{
+    stream::{self, FuturesOrdered},
+    try_join, StreamExt, TryStreamExt,
+};
 use ibc_classic_spec::IbcClassic;
 use ibc_solidity::Packet;
 use ibc_union_spec::IbcUnion;
@@ -19,40 +22,58 @@ use jsonrpsee::{
     Extensions,
 };
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use tracing::{debug, error, info, instrument, trace, warn};
 use unionlabs::{
     bytes::Bytes,
     ethereum::keccak256,
+    hash::H256,
     ibc::core::{
+        channel::{
+            self, channel::Channel, msg_acknowledgement::MsgAcknowledgement,
+            msg_channel_open_ack::MsgChannelOpenAck,
+            msg_channel_open_confirm::MsgChannelOpenConfirm,
+            msg_channel_open_try::MsgChannelOpenTry,
+        },
         client::height::Height,
         commitment::merkle_prefix::MerklePrefix,
         connection::{
             self, connection_end::ConnectionEnd, msg_connection_open_try::MsgConnectionOpenTry,
         },
     },
-    id::{ClientId, ConnectionId},
+    id::{ChannelId, ClientId, ConnectionId, PortId},
     traits::Member,
-    DELAY_PERIOD,
+    ErrorReporter, DELAY_PERIOD,
 };
 use voyager_message::{
-    call::WaitForHeight,
+    call::WaitForFinality,
     core::{ChainId, IbcSpec, QueryHeight},
     data::{ChainEvent, Data, IbcDatagram},
     module::{PluginInfo, PluginServer},
     DefaultCmd, ExtensionsExt, Plugin, PluginMessage, RawClientId, VoyagerClient, VoyagerMessage,
     FATAL_JSONRPC_ERROR_CODE,
 };
-use voyager_vm::{call, data, pass::PassResult, seq, BoxDynError, Op};
+use voyager_vm::{call, conc, data, defer, now, pass::PassResult, seq, BoxDynError, Op};
 
 use crate::{
-    call::{MakeMsg, MakeTransactionBatchesWithUpdate, ModuleCall},
+    call::{
+        CheckPacketTimeouts, MakeMsg, MakeTransactionBatchesWithUpdate, ModuleCall,
+        PendingPacketTimeout,
+    },
     callback::ModuleCallback,
-    data::{BatchableEvent, EventBatch, EventClassic, EventUnion, ModuleData},
+    coalesce::{UpdateCoalescer, UpdateCoalescingConfig},
+    data::{BatchableEvent, EventBatch, EventClassic, EventUnion, ModuleData, PacketTimeout},
+    rate_limit::{RateLimitConfig, RateLimiter},
+    version::{HandshakeInfo, TryVersion, VersionNegotiators},
 };
 
 pub mod call;
 pub mod callback;
+pub mod coalesce;
 pub mod data;
+pub mod ica;
+pub mod rate_limit;
+pub mod version;
 
 #[tokio::main(flavor = "multi_thread")]
 async fn main() {
@@ -63,6 +84,9 @@ async fn main() {
 pub struct Module {
     pub chain_id: ChainId,
     pub client_configs: ClientConfigs,
+    pub rate_limiter: RateLimiter,
+    pub update_coalescer: UpdateCoalescer,
+    pub version_negotiators: VersionNegotiators,
 }
 
 #[derive(Debug, Clone)]
@@ -75,6 +99,12 @@ pub enum ClientConfigs {
 pub struct Config {
     pub chain_id: ChainId,
     pub client_configs: ClientConfigsSerde,
+    /// Limits on the value of the transfer packets received on this chain; see [`rate_limit`].
+    #[serde(default)]
+    pub rate_limits: Vec<RateLimitConfig>,
+    /// Sharing of in-flight client updates between batches; see [`coalesce`].
+    #[serde(default)]
+    pub update_coalescing: UpdateCoalescingConfig,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -158,8 +188,10 @@ impl IbcSpecExt for IbcUnion {
             EventUnion::ChannelOpenInit(_) => "channel_open_init",
             EventUnion::ChannelOpenTry(_) => "channel_open_try",
             EventUnion::ChannelOpenAck(_) => "channel_open_ack",
+            EventUnion::ChannelCloseInit(_) => "channel_close_init",
             EventUnion::SendPacket(_) => "send_packet",
             EventUnion::WriteAcknowledgement(_) => "write_acknowledgement",
+            EventUnion::PacketTimeout(_) => "packet_timeout",
         }
     }
 }
@@ -274,6 +306,9 @@ if ."@type" == "data" then
         ) or (
             $event_type == "channel_open_ack"
             and ($event_data.connection.counterparty_client_id as $client_id | {clients_filter})
+        ) or (
+            $event_type == "channel_close_init"
+            and ($event_data.connection.counterparty_client_id as $client_id | {clients_filter})
         ) or (
             $event_type == "send_packet"
             and ($event_data.packet.destination_channel.connection.client_id as $client_id | {clients_filter})
@@ -283,6 +318,9 @@ if ."@type" == "data" then
         ) or ($data."@type" == "plugin"
             and $data."@value".plugin == "{plugin_name}"
             and $data."@value".message."@type" == "event_batch")
+    # event batches constructed by other instances of this plugin (i.e. packet timeouts)
+    elif $data."@type" == "plugin" and $data."@value".plugin == "{plugin_name}" then
+        $data."@value".message."@type" == "batch_events_union"
     else
         false
     end
@@ -306,6 +344,15 @@ end
 
 pub const PLUGIN_NAME: &str = env!("CARGO_PKG_NAME");
 
+/// The minimum amount of time between checks of whether pending packets have timed out, see
+/// [`Module::schedule_timeout_check`].
+const PACKET_TIMEOUT_POLL_INTERVAL_SECONDS: u64 = 60;
+
+const NANOS_PER_SECOND: u64 = 1_000_000_000;
+
+/// The maximum amount of state queries made concurrently when checking channels and packets.
+const MAX_CONCURRENT_STATE_QUERIES: usize = 16;
+
 impl Module {
     fn plugin_name(&self) -> String {
         format!("{PLUGIN_NAME}/{}", self.chain_id)
@@ -318,6 +365,9 @@ impl Module {
         Self {
             chain_id: config.chain_id,
             client_configs: ClientConfigs::new(config.client_configs),
+            rate_limiter: RateLimiter::new(config.rate_limits),
+            update_coalescer: UpdateCoalescer::new(&config.update_coalescing),
+            version_negotiators: VersionNegotiators::default(),
         }
     }
 }
@@ -342,9 +392,14 @@ impl PluginServer<ModuleCall, ModuleCallback> for Module {
             ModuleCall::MakeTransactionBatchesWithUpdateUnion(mk) => {
                 mk.call(self, e.try_get()?).await
             }
-            ModuleCall::MakeMsgV1(make_msg_v1) => do_make_msg_v1(voyager_client, make_msg_v1).await,
+            ModuleCall::MakeMsgV1(make_msg_v1) => {
+                do_make_msg_v1(voyager_client, &self.version_negotiators, make_msg_v1).await
+            }
             ModuleCall::MakeMsgUnion(make_msg_union) => {
-                do_make_msg_union(voyager_client, make_msg_union).await
+                do_make_msg_union(voyager_client, &self.version_negotiators, make_msg_union).await
+            }
+            ModuleCall::CheckPacketTimeoutsUnion(check) => {
+                self.check_packet_timeouts(voyager_client, check).await
             }
         }
     }
@@ -382,6 +437,7 @@ impl PluginServer<ModuleCall, ModuleCallback> for Module {
 )]
 async fn do_make_msg_union(
     voyager_client: &VoyagerClient,
+    version_negotiators: &VersionNegotiators,
     MakeMsg {
         origin_chain_id,
         origin_chain_proof_height,
@@ -634,6 +690,32 @@ async fn do_make_msg_union(
         }
 
         EventUnion::ChannelOpenInit(event) => {
+            let connection_id = query_union_channel_connection_id(
+                voyager_client,
+                origin_chain_id.clone(),
+                origin_chain_proof_height,
+                event.channel_id,
+            )
+            .await?;
+
+            let TryVersion {
+                version,
+                counterparty_version,
+            } = version_negotiators
+                .try_version(
+                    HandshakeInfo {
+                        port_id: &event.port_id.to_string(),
+                        counterparty_port_id: &event.counterparty_port_id.to_string(),
+                        connection_id: &connection_id.to_string(),
+                        counterparty_connection_id: &event
+                            .connection
+                            .counterparty_connection_id
+                            .to_string(),
+                    },
+                    &event.version,
+                )
+                .map_err(version_negotiation_error)?;
+
             let proof_init = voyager_client
                 .query_ibc_proof(
                     origin_chain_id,
@@ -664,9 +746,9 @@ async fn do_make_msg_union(
                         counterparty_channel_id: event.channel_id,
                         counterparty_port_id: event.port_id.into(),
                         connection_id: event.connection.counterparty_connection_id,
-                        version: event.version.clone(),
+                        version,
                     },
-                    counterparty_version: event.version,
+                    counterparty_version,
                     proof_init: encoded_proof_init,
                     proof_height: origin_chain_proof_height.height(),
                 }),
@@ -674,6 +756,29 @@ async fn do_make_msg_union(
         }
 
         EventUnion::ChannelOpenTry(event) => {
+            let connection_id = query_union_channel_connection_id(
+                voyager_client,
+                origin_chain_id.clone(),
+                origin_chain_proof_height,
+                event.channel_id,
+            )
+            .await?;
+
+            let counterparty_version = version_negotiators
+                .ack_version(
+                    HandshakeInfo {
+                        port_id: &event.port_id.to_string(),
+                        counterparty_port_id: &event.counterparty_port_id.to_string(),
+                        connection_id: &connection_id.to_string(),
+                        counterparty_connection_id: &event
+                            .connection
+                            .counterparty_connection_id
+                            .to_string(),
+                    },
+                    &event.version,
+                )
+                .map_err(version_negotiation_error)?;
+
             let proof_try = voyager_client
                 .query_ibc_proof(
                     origin_chain_id,
@@ -700,7 +805,7 @@ async fn do_make_msg_union(
                 ibc_union_spec::Datagram::from(ibc_union_spec::MsgChannelOpenAck {
                     channel_id: event.counterparty_channel_id,
                     counterparty_channel_id: event.channel_id,
-                    counterparty_version: event.version,
+                    counterparty_version,
                     proof_try: encoded_proof_try,
                     proof_height: origin_chain_proof_height.height(),
                 }),
@@ -739,6 +844,38 @@ async fn do_make_msg_union(
             )))
         }
 
+        EventUnion::ChannelCloseInit(event) => {
+            let proof_init = voyager_client
+                .query_ibc_proof(
+                    origin_chain_id,
+                    QueryHeight::Specific(origin_chain_proof_height),
+                    ibc_union_spec::ChannelPath {
+                        channel_id: event.channel_id,
+                    },
+                )
+                .await?;
+
+            let client_info = voyager_client
+                .client_info::<IbcUnion>(target_chain_id, event.connection.counterparty_client_id)
+                .await?;
+
+            let encoded_proof_init = voyager_client
+                .encode_proof::<IbcUnion>(
+                    client_info.client_type,
+                    client_info.ibc_interface,
+                    proof_init.proof,
+                )
+                .await?;
+
+            Ok(data(IbcDatagram::new::<IbcUnion>(
+                ibc_union_spec::Datagram::from(ibc_union_spec::MsgChannelCloseConfirm {
+                    channel_id: event.counterparty_channel_id,
+                    proof_init: encoded_proof_init,
+                    proof_height: origin_chain_proof_height.height(),
+                }),
+            )))
+        }
+
         EventUnion::SendPacket(event) => {
             let packet = Packet {
                 source_channel: event.packet.source_channel.channel_id,
@@ -826,11 +963,58 @@ async fn do_make_msg_union(
                 }),
             )))
         }
+
+        EventUnion::PacketTimeout(event) => {
+            let packet = Packet {
+                source_channel: event.packet.source_channel.channel_id,
+                destination_channel: event.packet.destination_channel.channel_id,
+                data: event.packet_data.into(),
+                timeout_height: event.packet.timeout_height,
+                timeout_timestamp: event.packet.timeout_timestamp,
+            };
+
+            // for timeouts, the origin chain is the destination chain of the packet, and the
+            // proof is a proof of non-membership of the packet receipt
+            let proof_unreceived = voyager_client
+                .query_ibc_proof(
+                    origin_chain_id,
+                    QueryHeight::Specific(origin_chain_proof_height),
+                    ibc_union_spec::BatchReceiptsPath {
+                        channel_id: event.packet.destination_channel.channel_id,
+                        batch_hash: keccak256(packet.abi_encode()),
+                    },
+                )
+                .await?;
+
+            let client_info = voyager_client
+                .client_info::<IbcUnion>(
+                    target_chain_id,
+                    event.packet.source_channel.connection.client_id,
+                )
+                .await?;
+
+            let encoded_proof_unreceived = voyager_client
+                .encode_proof::<IbcUnion>(
+                    client_info.client_type,
+                    client_info.ibc_interface,
+                    proof_unreceived.proof,
+                )
+                .await?;
+
+            Ok(data(IbcDatagram::new::<IbcUnion>(
+                ibc_union_spec::Datagram::from(ibc_union_spec::MsgPacketTimeout {
+                    packet,
+                    proof: encoded_proof_unreceived,
+                    proof_height: origin_chain_proof_height.height(),
+                }),
+            )))
+        }
     }
 }
 
 async fn do_make_msg_v1(
     voyager_client: &VoyagerClient,
+    version_negotiators: &VersionNegotiators,
     MakeMsg {
         origin_chain_id,
         origin_chain_proof_height,
@@ -959,163 +1143,227 @@ async fn do_make_msg_v1(
         //         },
         //     )))
         // }
+        EventClassic::ChannelOpenInit(event) => {
+            let ChannelHandshakeStateAndProof {
+                channel_state,
+                connection_id,
+                encoded_channel_state_proof,
+            } = mk_channel_handshake_state_and_proof(
+                voyager_client,
+                origin_chain_id,
+                target_chain_id,
+                event.connection.counterparty.client_id.clone(),
+                event.port_id.clone(),
+                event.channel_id.clone(),
+                origin_chain_proof_height,
+            )
+            .await?;
 
-        // MakeMsgV1::MakeMsgChannelOpenTry(MakeMsgChannelOpenTry {
-        //     origin_chain_id,
-        //     origin_chain_proof_height,
-        //     target_chain_id,
-        //     channel_open_init_event: event,
-        // }) => {
-        //     let origin_channel = voyager_client
-        //         .query_channel(
-        //             origin_chain_id.clone(),
-        //             QueryHeight::Specific(origin_chain_proof_height),
-        //             event.port_id.clone(),
-        //             event.channel_id.clone(),
-        //         )
-        //         .await
-        //         ?;
+            let counterparty_connection_id =
+                event
+                    .connection
+                    .counterparty
+                    .connection_id
+                    .ok_or(ErrorObject::owned(
+                        FATAL_JSONRPC_ERROR_CODE,
+                        "the connection of the channel must be open",
+                        None::<()>,
+                    ))?;
+
+            let TryVersion {
+                version,
+                counterparty_version,
+            } = version_negotiators
+                .try_version(
+                    HandshakeInfo {
+                        port_id: event.port_id.as_str(),
+                        counterparty_port_id: event.counterparty_port_id.as_str(),
+                        connection_id: &connection_id.to_string_prefixed(),
+                        counterparty_connection_id: &counterparty_connection_id
+                            .to_string_prefixed(),
+                    },
+                    &event.version,
+                )
+                .map_err(version_negotiation_error)?;
 
-        //     let proof_init = voyager_client
-        //         .query_ibc_proof(
-        //             &origin_chain_id,
-        //             origin_chain_proof_height,
-        //             ChannelEndPath {
-        //                 port_id: event.port_id.clone(),
-        //                 channel_id: event.channel_id.clone(),
-        //             }
-        //             .into(),
-        //         )
-        //         .await
-        //         ?;
+            Ok(data(IbcDatagram::new::<IbcClassic>(
+                ibc_classic_spec::Datagram::from(MsgChannelOpenTry {
+                    port_id: event.counterparty_port_id,
+                    channel: Channel {
+                        state: channel::state::State::Tryopen,
+                        ordering: channel_state.ordering,
+                        counterparty: channel::counterparty::Counterparty {
+                            port_id: event.port_id,
+                            channel_id: Some(event.channel_id),
+                        },
+                        connection_hops: vec![counterparty_connection_id],
+                        version,
+                        upgrade_sequence: 0,
+                    },
+                    counterparty_version,
+                    proof_init: encoded_channel_state_proof,
+                    proof_height: origin_chain_proof_height,
+                }),
+            )))
+        }
 
-        //     let client_info = voyager_client
-        //         .client_info(&target_chain_id, event.connection.counterparty.client_id)
-        //         .await
-        //         ?;
+        EventClassic::ChannelOpenTry(event) => {
+            let ChannelHandshakeStateAndProof {
+                connection_id,
+                encoded_channel_state_proof,
+                ..
+            } = mk_channel_handshake_state_and_proof(
+                voyager_client,
+                origin_chain_id,
+                target_chain_id,
+                event.connection.counterparty.client_id.clone(),
+                event.port_id.clone(),
+                event.channel_id.clone(),
+                origin_chain_proof_height,
+            )
+            .await?;
 
-        //     let encoded_proof_init = voyager_client
-        //         .encode_proof(
-        //             &client_info.client_type,
-        //             &client_info.ibc_interface,
-        //             proof_init.proof,
-        //         )
-        //         .await
-        //         ?;
+            let counterparty_connection_id =
+                event
+                    .connection
+                    .counterparty
+                    .connection_id
+                    .ok_or(ErrorObject::owned(
+                        FATAL_JSONRPC_ERROR_CODE,
+                        "the connection of the channel must be open",
+                        None::<()>,
+                    ))?;
+
+            let counterparty_version = version_negotiators
+                .ack_version(
+                    HandshakeInfo {
+                        port_id: event.port_id.as_str(),
+                        counterparty_port_id: event.counterparty_port_id.as_str(),
+                        connection_id: &connection_id.to_string_prefixed(),
+                        counterparty_connection_id: &counterparty_connection_id
+                            .to_string_prefixed(),
+                    },
+                    &event.version,
+                )
+                .map_err(version_negotiation_error)?;
 
-        //     Ok(data(IbcMessage::from(MsgChannelOpenTry {
-        //         port_id: event.counterparty_port_id,
-        //         channel: Channel {
-        //             state: channel::state::State::Tryopen,
-        //             ordering: origin_channel
-        //                 .state
-        //                 .ok_or(QueueError::Fatal("channel must exist".into()))?
-        //                 .ordering,
-        //             counterparty: channel::counterparty::Counterparty {
-        //                 port_id: event.port_id,
-        //                 channel_id: Some(event.channel_id),
-        //             },
-        //             connection_hops: vec![event.connection.counterparty.connection_id.unwrap()],
-        //             version: event.version.clone(),
-        //             upgrade_sequence: 0,
-        //         },
-        //         counterparty_version: event.version,
-        //         proof_init: encoded_proof_init,
-        //         proof_height: origin_chain_proof_height,
-        //     })))
-        // }
+            Ok(data(IbcDatagram::new::<IbcClassic>(
+                ibc_classic_spec::Datagram::from(MsgChannelOpenAck {
+                    port_id: event.counterparty_port_id,
+                    channel_id: event.counterparty_channel_id,
+                    counterparty_channel_id: event.channel_id,
+                    counterparty_version,
+                    proof_try: encoded_channel_state_proof,
+                    proof_height: origin_chain_proof_height,
+                }),
+            )))
+        }
 
-        // MakeMsgV1::MakeMsgChannelOpenAck(MakeMsgChannelOpenAck {
-        //     origin_chain_id,
-        //     origin_chain_proof_height,
-        //     target_chain_id,
-        //     channel_open_try_event,
-        // }) => {
-        //     let origin_channel_path = ChannelEndPath {
-        //         port_id: channel_open_try_event.port_id.clone(),
-        //         channel_id: channel_open_try_event.channel_id.clone(),
-        //     };
-
-        //     let proof_try = voyager_client
-        //         .query_ibc_proof(
-        //             &origin_chain_id,
-        //             origin_chain_proof_height,
-        //             origin_channel_path.into(),
-        //         )
-        //         .await
-        //         ?;
+        EventClassic::ChannelOpenAck(event) => {
+            let ChannelHandshakeStateAndProof {
+                encoded_channel_state_proof,
+                ..
+            } = mk_channel_handshake_state_and_proof(
+                voyager_client,
+                origin_chain_id,
+                target_chain_id,
+                event.connection.counterparty.client_id.clone(),
+                event.port_id.clone(),
+                event.channel_id.clone(),
+                origin_chain_proof_height,
+            )
+            .await?;
 
-        //     let client_info = voyager_client
-        //         .client_info(
-        //             &target_chain_id,
-        //             channel_open_try_event.connection.counterparty.client_id,
-        //         )
-        //         .await
-        //         ?;
+            Ok(data(IbcDatagram::new::<IbcClassic>(
+                ibc_classic_spec::Datagram::from(MsgChannelOpenConfirm {
+                    port_id: event.counterparty_port_id,
+                    channel_id: event.counterparty_channel_id,
+                    proof_ack: encoded_channel_state_proof,
+                    proof_height: origin_chain_proof_height,
+                }),
+            )))
+        }
 
-        //     let encoded_proof_try = voyager_client
-        //         .encode_proof(
-        //             &client_info.client_type,
-        //             &client_info.ibc_interface,
-        //             proof_try.proof,
-        //         )
-        //         .await
-        //         ?;
+        EventClassic::WriteAcknowledgement(event) => {
+            let path = ibc_classic_spec::AcknowledgementPath {
+                port_id: event.packet.destination_channel.port_id.clone(),
+                channel_id: event.packet.destination_channel.channel_id.clone(),
+                sequence: event.packet.sequence,
+            };
 
-        //     Ok(data(IbcMessage::from(MsgChannelOpenAck {
-        //         port_id: channel_open_try_event.counterparty_port_id,
-        //         channel_id: channel_open_try_event.counterparty_channel_id,
-        //         counterparty_channel_id: channel_open_try_event.channel_id,
-        //         counterparty_version: channel_open_try_event.version,
-        //         proof_try: encoded_proof_try,
-        //         proof_height: origin_chain_proof_height,
-        //     })))
-        // }
+            // the acknowledgement is read back at the height it will be proven at, such that the
+            // proof is always of the acknowledgement that is relayed
+            let (ack_commitment, target_client_info, ack_proof) = try_join!(
+                voyager_client.query_ibc_state(
+                    origin_chain_id.clone(),
+                    origin_chain_proof_height.into(),
+                    path.clone(),
+                ),
+                voyager_client.client_info::<IbcClassic>(
+                    target_chain_id,
+                    event.packet.source_channel.connection.client_id.clone(),
+                ),
+                voyager_client.query_ibc_proof(
+                    origin_chain_id,
+                    QueryHeight::Specific(origin_chain_proof_height),
+                    path,
+                ),
+            )?;
+
+            let Some(ack_commitment) = ack_commitment.state else {
+                // the event is provable at this height, so the state should be as well; this is
+                // most likely a lagging rpc
+                return Err(ErrorObject::owned(
+                    -1,
+                    format!(
+                        "acknowledgement of packet {} is not committed at height \
+                        {origin_chain_proof_height}",
+                        event.packet.sequence
+                    ),
+                    None::<()>,
+                ));
+            };
 
-        // MakeMsgV1::MakeMsgChannelOpenConfirm(MakeMsgChannelOpenConfirm {
-        //     origin_chain_id,
-        //     origin_chain_proof_height,
-        //     target_chain_id,
-        //     channel_open_ack_event,
-        // }) => {
-        //     let origin_channel_path = ChannelEndPath {
-        //         port_id: channel_open_ack_event.port_id.clone(),
-        //         channel_id: channel_open_ack_event.channel_id.clone(),
-        //     };
-
-        //     let proof_ack = voyager_client
-        //         .query_ibc_proof(
-        //             &origin_chain_id,
-        //             origin_chain_proof_height,
-        //             origin_channel_path.into(),
-        //         )
-        //         .await
-        //         ?;
+            let expected_commitment = H256::new(Sha256::digest(&event.packet_ack).into());
 
-        //     let client_info = voyager_client
-        //         .client_info(
-        //             &target_chain_id,
-        //             channel_open_ack_event.connection.counterparty.client_id,
-        //         )
-        //         .await
-        //         ?;
+            if ack_commitment != expected_commitment {
+                return Err(ErrorObject::owned(
+                    FATAL_JSONRPC_ERROR_CODE,
+                    format!(
+                        "acknowledgement commitment of packet {} is {ack_commitment}, but the \
+                        acknowledgement in the event commits to {expected_commitment}",
+                        event.packet.sequence
+                    ),
+                    None::<()>,
+                ));
+            }
 
-        //     let encoded_proof_ack = voyager_client
-        //         .encode_proof(
-        //             &client_info.client_type,
-        //             &client_info.ibc_interface,
-        //             proof_ack.proof,
-        //         )
-        //         .await
-        //         ?;
+            let encoded_ack_proof = voyager_client
+                .encode_proof::<IbcClassic>(
+                    target_client_info.client_type,
+                    target_client_info.ibc_interface,
+                    ack_proof.proof,
+                )
+                .await?;
 
-        //     Ok(voyager_vm::data(IbcMessage::from(MsgChannelOpenConfirm {
-        //         port_id: channel_open_ack_event.counterparty_port_id,
-        //         channel_id: channel_open_ack_event.counterparty_channel_id,
-        //         proof_ack: encoded_proof_ack,
-        //         proof_height: origin_chain_proof_height,
-        //     })))
-        // }
+            Ok(data(IbcDatagram::new::<IbcClassic>(
+                ibc_classic_spec::Datagram::from(MsgAcknowledgement {
+                    packet: channel::packet::Packet {
+                        sequence: event.packet.sequence,
+                        source_port: event.packet.source_channel.port_id,
+                        source_channel: event.packet.source_channel.channel_id,
+                        destination_port: event.packet.destination_channel.port_id,
+                        destination_channel: event.packet.destination_channel.channel_id,
+                        data: event.packet_data,
+                        timeout_height: event.packet.timeout_height,
+                        timeout_timestamp: event.packet.timeout_timestamp,
+                    },
+                    acknowledgement: event.packet_ack,
+                    proof_acked: encoded_ack_proof,
+                    proof_height: origin_chain_proof_height,
+                }),
+            )))
+        }
 
         // MakeMsgV1::MakeMsgRecvPacket(msg) => make_msg_recv_packet(ctx, msg).await,
         _ => todo!(),
@@ -1133,6 +1381,14 @@ impl Module {
             let mut batchers_v1 =
                 HashMap::<ClientId, Vec<(usize, BatchableEvent<IbcClassic>)>>::new();
             let mut batchers_union = HashMap::<u32, Vec<(usize, BatchableEvent<IbcUnion>)>>::new();
+            // packets sent to this chain, which need to be checked for timeouts before being batched
+            let mut send_packets_v1 =
+                Vec::<(usize, ClientId, ChainId, BatchableEvent<IbcClassic>)>::new();
+            // packets sent to this chain, which need to be checked for timeouts before being batched
+            let mut send_packets_union =
+                Vec::<(usize, u32, ChainId, BatchableEvent<IbcUnion>)>::new();
+            // transfer packets that are over the rate limit, to be requeued once the limit allows
+            let mut rate_limited = vec![];
 
             for (idx, msg) in msgs.into_iter().enumerate() {
                 let Op::Data(msg) = msg else {
@@ -1142,6 +1398,22 @@ impl Module {
 
                 match ChainEvent::try_from(msg) {
                     Ok(chain_event) => {
+                        if let Err(retry_after) = self.rate_limiter.check(&chain_event, now()) {
+                            info!(
+                                origin_chain_id = %chain_event.chain_id,
+                                tx_hash = %chain_event.tx_hash,
+                                retry_after,
+                                "packet exceeds the rate limit, deferring"
+                            );
+
+                            rate_limited.push((
+                                vec![idx],
+                                seq([defer(now() + retry_after), data(chain_event)]),
+                            ));
+
+                            continue;
+                        }
+
                         let first_seen_at: u64 = SystemTime::now()
                             .duration_since(UNIX_EPOCH)
                             .unwrap()
@@ -1159,17 +1431,28 @@ impl Module {
                                 .counterparty_client_id()
                                 .expect("all batchable messages have a counterparty");
 
-                            trace!(%client_id, "batching event");
+                            let batchable_event = BatchableEvent {
+                                first_seen_at,
+                                provable_height: chain_event.provable_height,
+                                // TODO: Handle this more gracefully
+                                event: full_ibc_event.try_into().unwrap(),
+                            };
+
+                            if let EventClassic::SendPacket(_) = batchable_event.event {
+                                send_packets_v1.push((
+                                    idx,
+                                    client_id,
+                                    chain_event.chain_id.clone(),
+                                    batchable_event,
+                                ));
+                            } else {
+                                trace!(%client_id, "batching event");
 
-                            batchers_v1.entry(client_id.clone()).or_default().push((
-                                idx,
-                                BatchableEvent {
-                                    first_seen_at,
-                                    provable_height: chain_event.provable_height,
-                                    // TODO: Handle this more gracefully
-                                    event: full_ibc_event.try_into().unwrap(),
-                                },
-                            ));
+                                batchers_v1
+                                    .entry(client_id)
+                                    .or_default()
+                                    .push((idx, batchable_event));
+                            }
                         }
 
                         if let Some(full_ibc_event) = chain_event.decode_event::<IbcUnion>() {
@@ -1179,17 +1462,28 @@ impl Module {
                                 .counterparty_client_id()
                                 .expect("all batchable messages have a counterparty");
 
-                            trace!(%client_id, "batching event");
+                            let batchable_event = BatchableEvent {
+                                first_seen_at,
+                                provable_height: chain_event.provable_height,
+                                // TODO: Handle this more gracefully
+                                event: full_ibc_event.try_into().unwrap(),
+                            };
+
+                            if let EventUnion::SendPacket(_) = batchable_event.event {
+                                send_packets_union.push((
+                                    idx,
+                                    client_id,
+                                    chain_event.chain_id.clone(),
+                                    batchable_event,
+                                ));
+                            } else {
+                                trace!(%client_id, "batching event");
 
-                            batchers_union.entry(client_id).or_default().push((
-                                idx,
-                                BatchableEvent {
-                                    first_seen_at,
-                                    provable_height: chain_event.provable_height,
-                                    // TODO: Handle this more gracefully
-                                    event: full_ibc_event.try_into().unwrap(),
-                                },
-                            ));
+                                batchers_union
+                                    .entry(client_id)
+                                    .or_default()
+                                    .push((idx, batchable_event));
+                            }
                         }
                     }
                     Err(msg) => {
@@ -1226,6 +1520,165 @@ impl Module {
                 };
             }
 
+            let voyager_client = e.try_get::<VoyagerClient>()?;
+
+            let mut timeouts_union = vec![];
+            // packets with a timeout that may still be received, by the channel they were sent to
+            let mut pending_timeouts_union =
+                HashMap::<u32, (Vec<usize>, Vec<PendingPacketTimeout>)>::new();
+
+            if !send_packets_union.is_empty() {
+                // the timestamp is queried first, such that the timestamp at `latest_height` (the
+                // height the timeout will be proven at) is always >= `latest_timestamp`
+                let latest_timestamp = voyager_client
+                    .query_latest_timestamp(self.chain_id.clone(), true)
+                    .await?;
+                let latest_height = voyager_client
+                    .query_latest_height(self.chain_id.clone(), true)
+                    .await?;
+
+                let closed_channels = self
+                    .closed_channels_union(
+                        voyager_client,
+                        latest_height,
+                        send_packets_union
+                            .iter()
+                            .filter_map(|(_, _, _, e)| match &e.event {
+                                EventUnion::SendPacket(event) => {
+                                    Some(event.packet.destination_channel.channel_id)
+                                }
+                                _ => None,
+                            })
+                            .collect(),
+                    )
+                    .await?;
+
+                for (idx, client_id, origin_chain_id, batchable_event) in send_packets_union {
+                    let EventUnion::SendPacket(event) = batchable_event.event else {
+                        unreachable!("only send packets are checked for timeouts; qed;")
+                    };
+
+                    if is_timed_out(&event.packet, latest_height, latest_timestamp) {
+                        info!(
+                            %origin_chain_id,
+                            source_channel_id = event.packet.source_channel.channel_id,
+                            timeout_height = event.packet.timeout_height,
+                            timeout_timestamp = event.packet.timeout_timestamp,
+                            %latest_height,
+                            %latest_timestamp,
+                            "packet has timed out, it will be timed out on the origin chain"
+                        );
+
+                        timeouts_union.push((
+                            vec![idx],
+                            packet_timeout_handoff(
+                                &origin_chain_id,
+                                batchable_event.first_seen_at,
+                                latest_height,
+                                event,
+                            ),
+                        ));
+
+                        continue;
+                    }
+
+                    let has_timeout =
+                        event.packet.timeout_height > 0 || event.packet.timeout_timestamp > 0;
+
+                    // the packet may never be received (i.e. if relaying it fails), so check for
+                    // the timeout again once it has elapsed
+                    if has_timeout {
+                        let (idxs, packets) = pending_timeouts_union
+                            .entry(event.packet.destination_channel.channel_id)
+                            .or_default();
+
+                        idxs.push(idx);
+                        packets.push(PendingPacketTimeout {
+                            origin_chain_id: origin_chain_id.clone(),
+                            first_seen_at: batchable_event.first_seen_at,
+                            event: event.clone(),
+                        });
+                    }
+
+                    // a packet sent to a closed channel can never be received. ibc-union has no
+                    // equivalent of MsgTimeoutOnClose, so such a packet can only be timed out once
+                    // its timeout has elapsed, by the check scheduled below
+                    if closed_channels.contains(&event.packet.destination_channel.channel_id) {
+                        warn!(
+                            %origin_chain_id,
+                            destination_channel_id = event.packet.destination_channel.channel_id,
+                            has_timeout,
+                            "packet was sent to a closed channel and cannot be received"
+                        );
+
+                        continue;
+                    }
+
+                    trace!(%client_id, "batching event");
+
+                    batchers_union.entry(client_id).or_default().push((
+                        idx,
+                        BatchableEvent {
+                            event: EventUnion::SendPacket(event),
+                            ..batchable_event
+                        },
+                    ));
+                }
+            }
+
+            timeouts_union.extend(pending_timeouts_union.into_iter().map(
+                |(channel_id, (idxs, packets))| {
+                    (
+                        idxs,
+                        self.schedule_timeout_check(CheckPacketTimeouts {
+                            channel_id,
+                            packets,
+                        }),
+                    )
+                },
+            ));
+
+            if !send_packets_v1.is_empty() {
+                let latest_timestamp = voyager_client
+                    .query_latest_timestamp(self.chain_id.clone(), true)
+                    .await?;
+                let latest_height = voyager_client
+                    .query_latest_height(self.chain_id.clone(), true)
+                    .await?;
+
+                for (idx, client_id, origin_chain_id, batchable_event) in send_packets_v1 {
+                    let EventClassic::SendPacket(event) = &batchable_event.event else {
+                        unreachable!("only send packets are checked for timeouts; qed;")
+                    };
+
+                    // timing out ibc-classic packets is out of scope (see the readme), but there is
+                    // no point in submitting a packet that would be rejected by the destination
+                    // chain
+                    if is_timed_out_classic(&event.packet, latest_height, latest_timestamp) {
+                        warn!(
+                            %origin_chain_id,
+                            source_port_id = %event.packet.source_channel.port_id,
+                            source_channel_id = %event.packet.source_channel.channel_id,
+                            sequence = %event.packet.sequence,
+                            timeout_height = %event.packet.timeout_height,
+                            timeout_timestamp = event.packet.timeout_timestamp,
+                            %latest_height,
+                            %latest_timestamp,
+                            "packet has timed out and cannot be received"
+                        );
+
+                        continue;
+                    }
+
+                    trace!(%client_id, "batching event");
+
+                    batchers_v1
+                        .entry(client_id)
+                        .or_default()
+                        .push((idx, batchable_event));
+                }
+            }
+
             let (ready_v1, optimize_further_v1) = batchers_v1
                 .into_iter()
                 .flat_map(|(client_id, events)| split_ready(client_id, events, self))
@@ -1236,8 +1689,6 @@ impl Module {
                 .flat_map(|(client_id, events)| split_ready(client_id, events, self))
                 .partition_map::<Vec<_>, Vec<_>, _, _, _>(convert::identity);
 
-            let voyager_client = e.try_get::<VoyagerClient>()?;
-
             let ready_v1 = ready_v1
                 .into_iter()
                 .into_group_map()
@@ -1257,12 +1708,230 @@ impl Module {
                     .into_iter()
                     .chain(optimize_further_union)
                     .collect(),
-                ready: ready_v1.chain(ready_union).map(|x| x).try_collect().await?,
+                ready: ready_v1
+                    .chain(ready_union)
+                    .chain(stream::iter(timeouts_union.into_iter().map(Ok)))
+                    .chain(stream::iter(rate_limited.into_iter().map(Ok)))
+                    .try_collect()
+                    .await?,
             })
         })
     }
 }
 
+impl Module {
+    /// Schedule `check` to run once the earliest timeout of its packets may have elapsed on this
+    /// chain.
+    ///
+    /// Timestamp timeouts are waited for by deferring until the timeout, as the local clock is a
+    /// close enough approximation of the chain's (the packet is checked again if it has not timed
+    /// out yet). Height timeouts can't be converted to a time without knowing the block time of the
+    /// chain, so these are polled instead. Either way, the packets are checked at most once every
+    /// [`PACKET_TIMEOUT_POLL_INTERVAL_SECONDS`].
+    fn schedule_timeout_check(&self, check: CheckPacketTimeouts) -> Op<VoyagerMessage> {
+        let poll_at = now() + PACKET_TIMEOUT_POLL_INTERVAL_SECONDS;
+
+        let check_at = check
+            .packets
+            .iter()
+            .map(|pending| {
+                let packet = &pending.event.packet;
+
+                if packet.timeout_height > 0 || packet.timeout_timestamp == 0 {
+                    poll_at
+                } else {
+                    packet
+                        .timeout_timestamp
+                        .div_ceil(NANOS_PER_SECOND)
+                        .max(poll_at)
+                }
+            })
+            .min()
+            .unwrap_or(poll_at);
+
+        seq([
+            defer(check_at),
+            call(PluginMessage::new(
+                self.plugin_name(),
+                ModuleCall::from(check),
+            )),
+        ])
+    }
+
+    #[instrument(
+        skip_all,
+        fields(
+            destination_channel_id = check.channel_id,
+            packets = check.packets.len(),
+        )
+    )]
+    async fn check_packet_timeouts(
+        &self,
+        voyager_client: &VoyagerClient,
+        check: CheckPacketTimeouts,
+    ) -> RpcResult<Op<VoyagerMessage>> {
+        let latest_timestamp = voyager_client
+            .query_latest_timestamp(self.chain_id.clone(), true)
+            .await?;
+        let latest_height = voyager_client
+            .query_latest_height(self.chain_id.clone(), true)
+            .await?;
+
+        let channel_id = check.channel_id;
+
+        let (timed_out, pending) = check.packets.into_iter().partition::<Vec<_>, _>(|packet| {
+            is_timed_out(&packet.event.packet, latest_height, latest_timestamp)
+        });
+
+        // an unset receipt is either not returned at all or returned as the zero hash, depending on
+        // the state module
+        let unreceived = stream::iter(timed_out)
+            .map(|packet| async move {
+                voyager_client
+                    .query_optional_ibc_state(
+                        self.chain_id.clone(),
+                        QueryHeight::Specific(latest_height),
+                        ibc_union_spec::BatchReceiptsPath {
+                            channel_id,
+                            batch_hash: keccak256(union_packet(&packet.event).abi_encode()),
+                        },
+                    )
+                    .await
+                    .map(|receipt| {
+                        receipt
+                            .state
+                            .is_none_or(|receipt| receipt == H256::default())
+                            .then_some(packet)
+                    })
+            })
+            .buffered(MAX_CONCURRENT_STATE_QUERIES)
+            .try_filter_map(|packet| async move { Ok(packet) })
+            .try_collect::<Vec<_>>()
+            .await?;
+
+        debug!(
+            %latest_height,
+            %latest_timestamp,
+            unreceived = unreceived.len(),
+            pending = pending.len(),
+            "checked packets for timeouts"
+        );
+
+        Ok(conc(
+            unreceived
+                .into_iter()
+                .map(|timed_out| {
+                    info!(
+                        origin_chain_id = %timed_out.origin_chain_id,
+                        source_channel_id = timed_out.event.packet.source_channel.channel_id,
+                        "packet has timed out, it will be timed out on the origin chain"
+                    );
+
+                    packet_timeout_handoff(
+                        &timed_out.origin_chain_id,
+                        timed_out.first_seen_at,
+                        latest_height,
+                        timed_out.event,
+                    )
+                })
+                .chain((!pending.is_empty()).then(|| {
+                    self.schedule_timeout_check(CheckPacketTimeouts {
+                        channel_id,
+                        packets: pending,
+                    })
+                })),
+        ))
+    }
+
+    /// The channels out of `channel_ids` on this chain that are closed.
+    async fn closed_channels_union(
+        &self,
+        voyager_client: &VoyagerClient,
+        height: Height,
+        channel_ids: HashSet<u32>,
+    ) -> RpcResult<HashSet<u32>> {
+        stream::iter(channel_ids)
+            .map(|channel_id| async move {
+                voyager_client
+                    .query_ibc_state(
+                        self.chain_id.clone(),
+                        height.into(),
+                        ibc_union_spec::ChannelPath { channel_id },
+                    )
+                    .await
+                    .map(|channel| {
+                        channel
+                            .state
+                            .is_some_and(|channel| {
+                                channel.state == ibc_solidity::ChannelState::Closed
+                            })
+                            .then_some(channel_id)
+                    })
+            })
+            .buffered(MAX_CONCURRENT_STATE_QUERIES)
+            .try_filter_map(|channel_id| async move { Ok(channel_id) })
+            .try_collect()
+            .await
+    }
+}
+
+/// Hand off a packet sent to this chain that has timed out to the instance of this plugin running
+/// for the chain the packet was sent from, where it will be timed out.
+fn packet_timeout_handoff(
+    origin_chain_id: &ChainId,
+    first_seen_at: u64,
+    provable_height: Height,
+    event: ibc_union_spec::SendPacket,
+) -> Op<VoyagerMessage> {
+    data(PluginMessage::new(
+        format!("{PLUGIN_NAME}/{origin_chain_id}"),
+        ModuleData::from(EventBatch::<IbcUnion> {
+            client_id: event.packet.source_channel.connection.client_id,
+            events: vec![BatchableEvent {
+                first_seen_at,
+                provable_height,
+                event: EventUnion::PacketTimeout(PacketTimeout {
+                    packet_data: event.packet_data,
+                    packet: event.packet,
+                }),
+            }],
+        }),
+    ))
+}
+
+/// The packet as committed to by the chains.
+fn union_packet(event: &ibc_union_spec::SendPacket) -> Packet {
+    Packet {
+        source_channel: event.packet.source_channel.channel_id,
+        destination_channel: event.packet.destination_channel.channel_id,
+        data: event.packet_data.clone().into(),
+        timeout_height: event.packet.timeout_height,
+        timeout_timestamp: event.packet.timeout_timestamp,
+    }
+}
+
+/// Whether or not the timeout of the packet has elapsed on the destination chain, given the latest
+/// height and timestamp (in nanoseconds) of that chain.
+fn is_timed_out(
+    packet: &ibc_union_spec::PacketMetadata,
+    latest_height: Height,
+    latest_timestamp: i64,
+) -> bool {
+    (packet.timeout_height > 0 && latest_height.height() >= packet.timeout_height)
+        || (packet.timeout_timestamp > 0
+            && u64::try_from(latest_timestamp).is_ok_and(|ts| ts >= packet.timeout_timestamp))
+}
+
+fn is_timed_out_classic(
+    packet: &ibc_classic_spec::PacketMetadata,
+    latest_height: Height,
+    latest_timestamp: i64,
+) -> bool {
+    (packet.timeout_height.height() > 0 && latest_height >= packet.timeout_height)
+        || (packet.timeout_timestamp > 0
+            && u64::try_from(latest_timestamp).is_ok_and(|ts| ts >= packet.timeout_timestamp))
+}
+
 /// Used to fetch and construct the state and proofs for
 /// MsgConnectionOpenTry/Ack.
 #[instrument(
@@ -1285,12 +1954,34 @@ async fn mk_connection_handshake_state_and_proofs(
     connection_id: ConnectionId,
     origin_chain_proof_height: Height,
 ) -> RpcResult<ConnectionHandshakeStateAndProof> {
-    // info of the client on the target chain that will verify the storage
-    // proofs
-    let target_client_info = voyager_client
+    // none of these queries depend on each other, so they are all issued at once
+    let (target_client_info, origin_client_info, connection_state, connection_proof) = try_join!(
+        // info of the client on the target chain that will verify the storage
+        // proofs
         // counterparty_client_id from open_init/try is the client on the target chain
-        .client_info::<IbcClassic>(target_chain_id.clone(), counterparty_client_id.clone())
-        .await?;
+        voyager_client
+            .client_info::<IbcClassic>(target_chain_id.clone(), counterparty_client_id.clone()),
+        // info of the client on the origin chain, this is used to decode the stored
+        // client state
+        // client_id from open_init/try is the client on the origin chain
+        voyager_client.client_info::<IbcClassic>(origin_chain_id.clone(), client_id.clone()),
+        // the connection end as stored by the origin chain after open_init/try
+        voyager_client.query_ibc_state(
+            origin_chain_id.clone(),
+            origin_chain_proof_height.into(),
+            ibc_classic_spec::ConnectionPath {
+                connection_id: connection_id.clone(),
+            },
+        ),
+        // proof of connection_state, encoded for the client on the target chain
+        voyager_client.query_ibc_proof(
+            origin_chain_id.clone(),
+            QueryHeight::Specific(origin_chain_proof_height),
+            ibc_classic_spec::ConnectionPath {
+                connection_id: connection_id.clone(),
+            },
+        ),
+    )?;
 
     debug!(
         %counterparty_client_id,
@@ -1299,13 +1990,6 @@ async fn mk_connection_handshake_state_and_proofs(
         %target_client_info.metadata,
     );
 
-    // info of the client on the origin chain, this is used to decode the stored
-    // client state
-    let origin_client_info = voyager_client
-        // client_id from open_init/try is the client on the origin chain
-        .client_info::<IbcClassic>(origin_chain_id.clone(), client_id.clone())
-        .await?;
-
     debug!(
         %client_id,
         %origin_client_info.client_type,
@@ -1313,37 +1997,16 @@ async fn mk_connection_handshake_state_and_proofs(
         %origin_client_info.metadata,
     );
 
-    // the connection end as stored by the origin chain after open_init/try
-    let connection_state = voyager_client
-        .query_ibc_state(
-            origin_chain_id.clone(),
-            origin_chain_proof_height.into(),
-            ibc_classic_spec::ConnectionPath {
-                connection_id: connection_id.clone(),
-            },
-        )
-        .await?
-        .state
-        .ok_or(ErrorObject::owned(
-            FATAL_JSONRPC_ERROR_CODE,
-            "connection must exist",
-            None::<()>,
-        ))?;
+    let connection_state = connection_state.state.ok_or(ErrorObject::owned(
+        FATAL_JSONRPC_ERROR_CODE,
+        "connection must exist",
+        None::<()>,
+    ))?;
     debug!(
         connection_state = %serde_json::to_string(&connection_state).unwrap(),
     );
 
-    // proof of connection_state, encoded for the client on the target chain
-    let connection_proof = voyager_client
-        .query_ibc_proof(
-            origin_chain_id.clone(),
-            QueryHeight::Specific(origin_chain_proof_height),
-            ibc_classic_spec::ConnectionPath {
-                connection_id: connection_id.clone(),
-            },
-        )
-        .await?
-        .proof;
+    let connection_proof = connection_proof.proof;
     debug!(%connection_proof);
 
     let encoded_connection_state_proof = voyager_client
@@ -1366,6 +2029,117 @@ struct ConnectionHandshakeStateAndProof {
     encoded_connection_state_proof: Bytes,
 }
 
+async fn mk_channel_handshake_state_and_proof(
+    voyager_client: &VoyagerClient,
+    origin_chain_id: ChainId,
+    target_chain_id: ChainId,
+    counterparty_client_id: ClientId,
+    port_id: PortId,
+    channel_id: ChannelId,
+    origin_chain_proof_height: Height,
+) -> RpcResult<ChannelHandshakeStateAndProof> {
+    let path = ibc_classic_spec::ChannelEndPath {
+        port_id,
+        channel_id,
+    };
+
+    // none of these queries depend on each other, so they are all issued at once
+    let (channel_state, target_client_info, channel_proof) = try_join!(
+        // the channel end as stored by the origin chain after open_init/try/ack
+        voyager_client.query_ibc_state(
+            origin_chain_id.clone(),
+            origin_chain_proof_height.into(),
+            path.clone(),
+        ),
+        // info of the client on the target chain that will verify the storage proof
+        voyager_client.client_info::<IbcClassic>(target_chain_id, counterparty_client_id),
+        voyager_client.query_ibc_proof(
+            origin_chain_id,
+            QueryHeight::Specific(origin_chain_proof_height),
+            path,
+        ),
+    )?;
+
+    let channel_state = channel_state.state.ok_or(ErrorObject::owned(
+        FATAL_JSONRPC_ERROR_CODE,
+        "channel must exist",
+        None::<()>,
+    ))?;
+    debug!(
+        channel_state = %serde_json::to_string(&channel_state).unwrap(),
+    );
+
+    let connection_id =
+        channel_state
+            .connection_hops
+            .first()
+            .cloned()
+            .ok_or(ErrorObject::owned(
+                FATAL_JSONRPC_ERROR_CODE,
+                "channel must have a connection hop",
+                None::<()>,
+            ))?;
+
+    let channel_proof = channel_proof.proof;
+    debug!(%channel_proof);
+
+    let encoded_channel_state_proof = voyager_client
+        .encode_proof::<IbcClassic>(
+            target_client_info.client_type,
+            target_client_info.ibc_interface,
+            channel_proof,
+        )
+        .await?;
+    debug!(%encoded_channel_state_proof);
+
+    Ok(ChannelHandshakeStateAndProof {
+        channel_state,
+        connection_id,
+        encoded_channel_state_proof,
+    })
+}
+
+struct ChannelHandshakeStateAndProof {
+    channel_state: Channel,
+    /// The connection the channel is opened on, on the origin chain.
+    connection_id: ConnectionId,
+    encoded_channel_state_proof: Bytes,
+}
+
+/// The connection a channel is opened on, as stored by the chain.
+async fn query_union_channel_connection_id(
+    voyager_client: &VoyagerClient,
+    chain_id: ChainId,
+    height: Height,
+    channel_id: u32,
+) -> RpcResult<u32> {
+    Ok(voyager_client
+        .query_ibc_state(
+            chain_id,
+            height.into(),
+            ibc_union_spec::ChannelPath { channel_id },
+        )
+        .await?
+        .state
+        .ok_or(ErrorObject::owned(
+            FATAL_JSONRPC_ERROR_CODE,
+            "channel must exist",
+            None::<()>,
+        ))?
+        .connection_id)
+}
+
+fn version_negotiation_error(error: BoxDynError) -> ErrorObject<'static> {
+    ErrorObject::owned(
+        FATAL_JSONRPC_ERROR_CODE,
+        format!(
+            "error negotiating the channel version: {}",
+            ErrorReporter(&*error)
+        ),
+        None::<()>,
+    )
+}
+
 #[allow(clippy::type_complexity)] // skill issue
 fn split_ready<V: IbcSpecExt>(
     client_id: V::ClientId,
@@ -1485,10 +2259,10 @@ where
     Ok((
         idxs.into_iter().flatten().collect::<Vec<_>>(),
         seq([
-            call(WaitForHeight {
+            call(WaitForFinality {
                 chain_id: client_meta.chain_id,
                 height: target_height,
-                finalized: true,
+                deadline: None,
             }),
             call(PluginMessage::new(
                 module.plugin_name(),
@@ -0,0 +1,231 @@
+//! Rate limiting of token transfers by value.
+//!
+//! Transfer packets (ICS-20 and UCS-01) that are received on a rate limited channel are only
+//! batched if the total amount of each denom received on the channel within the configured window
+//! stays within the limit. Packets that would exceed the limit are deferred until enough of the
+//! window has elapsed, instead of being relayed; this bounds the amount of value that can be
+//! drained through a compromised bridge before an operator can intervene.
+
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{Arc, Mutex},
+};
+
+use ibc_classic_spec::IbcClassic;
+use ibc_union_spec::IbcUnion;
+use serde::{Deserialize, Serialize};
+use voyager_message::{
+    data::ChainEvent,
+    event::{DecodedPacketData, Ucs01TransferToken},
+};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RateLimitConfig {
+    /// The channel on this chain that the packets are received on.
+    pub channel_id: String,
+    pub denom: String,
+    /// The maximum total amount of `denom` that can be received on the channel within the window.
+    #[serde(with = "::serde_utils::string")]
+    pub max_amount: u128,
+    pub window_seconds: u64,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct RateLimiter {
+    limits: Vec<RateLimitConfig>,
+    /// The amounts that have been let through for each limit, along with the unix timestamp (in
+    /// seconds) at which they were let through, oldest first.
+    windows: Arc<Mutex<HashMap<(String, String), VecDeque<(u64, u128)>>>>,
+}
+
+impl RateLimiter {
+    #[must_use]
+    pub fn new(limits: Vec<RateLimitConfig>) -> Self {
+        Self {
+            limits,
+            windows: Arc::default(),
+        }
+    }
+
+    /// Check whether the packet of `event` can be relayed at `now`, recording the transferred
+    /// amounts if so. If the packet exceeds any of the limits, this returns the amount of seconds
+    /// after which it should be checked again.
+    ///
+    /// Events that are not transfer packets sent to this chain are never limited.
+    pub fn check(&self, event: &ChainEvent, now: u64) -> Result<(), u64> {
+        if self.limits.is_empty() {
+            return Ok(());
+        }
+
+        let Some(channel_id) = destination_channel_id(event) else {
+            return Ok(());
+        };
+
+        let amounts = match &event.decoded_packet_data {
+            Some(DecodedPacketData::Ics20(data)) => vec![(
+                data.denom.clone(),
+                // amounts that don't fit in a u128 are certainly over any limit
+                data.amount.parse::<u128>().unwrap_or(u128::MAX),
+            )],
+            Some(DecodedPacketData::Ucs01(data)) => data
+                .tokens
+                .iter()
+                .map(|Ucs01TransferToken { denom, amount, .. }| (denom.clone(), *amount))
+                .collect(),
+            _ => return Ok(()),
+        };
+
+        self.try_acquire(&channel_id, &amounts, now)
+    }
+
+    fn try_acquire(
+        &self,
+        channel_id: &str,
+        amounts: &[(String, u128)],
+        now: u64,
+    ) -> Result<(), u64> {
+        let mut windows = self.windows.lock().expect("mutex is poisoned");
+
+        let limited = self
+            .limits
+            .iter()
+            .filter(|limit| limit.channel_id == channel_id)
+            .filter_map(|limit| {
+                let amount = amounts
+                    .iter()
+                    .filter(|(denom, _)| *denom == limit.denom)
+                    .fold(0_u128, |acc, (_, amount)| acc.saturating_add(*amount));
+
+                (amount > 0).then_some((limit, amount))
+            })
+            .collect::<Vec<_>>();
+
+        // check all limits before recording anything, such that a packet that is deferred does
+        // not count towards any of the windows
+        let mut retry_after = None::<u64>;
+
+        for (limit, amount) in &limited {
+            let window = windows
+                .entry((limit.channel_id.clone(), limit.denom.clone()))
+                .or_default();
+
+            while window
+                .front()
+                .is_some_and(|(at, _)| at + limit.window_seconds <= now)
+            {
+                window.pop_front();
+            }
+
+            let mut used = window
+                .iter()
+                .fold(0_u128, |acc, (_, amount)| acc.saturating_add(*amount));
+
+            if used.saturating_add(*amount) <= limit.max_amount {
+                continue;
+            }
+
+            // the time until enough of the window has elapsed for the packet to fit, or the full
+            // window if the packet exceeds the limit on its own
+            let mut wait = limit.window_seconds;
+
+            for (at, expiring) in window.iter() {
+                used -= expiring;
+
+                if used.saturating_add(*amount) <= limit.max_amount {
+                    wait = at + limit.window_seconds - now;
+                    break;
+                }
+            }
+
+            retry_after = Some(retry_after.unwrap_or(0).max(wait.max(1)));
+        }
+
+        if let Some(retry_after) = retry_after {
+            return Err(retry_after);
+        }
+
+        for (limit, amount) in limited {
+            windows
+                .entry((limit.channel_id.clone(), limit.denom.clone()))
+                .or_default()
+                .push_back((now, amount));
+        }
+
+        Ok(())
+    }
+}
+
+/// The channel that the packet of `event` will be received on, if `event` is a `SendPacket`.
+fn destination_channel_id(event: &ChainEvent) -> Option<String> {
+    if let Some(event) = event.decode_event::<IbcClassic>() {
+        return match event.ok()? {
+            ibc_classic_spec::FullEvent::SendPacket(event) => {
+                Some(event.packet.destination_channel.channel_id.to_string())
+            }
+            _ => None,
+        };
+    }
+
+    if let Some(event) = event.decode_event::<IbcUnion>() {
+        return match event.ok()? {
+            ibc_union_spec::FullEvent::SendPacket(event) => {
+                Some(event.packet.destination_channel.channel_id.to_string())
+            }
+            _ => None,
+        };
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limiter() -> RateLimiter {
+        RateLimiter::new(vec![RateLimitConfig {
+            channel_id: "channel-0".to_owned(),
+            denom: "muno".to_owned(),
+            max_amount: 100,
+            window_seconds: 60,
+        }])
+    }
+
+    fn muno(amount: u128) -> Vec<(String, u128)> {
+        vec![("muno".to_owned(), amount)]
+    }
+
+    #[test]
+    fn limits_amount_within_window() {
+        let limiter = limiter();
+
+        assert_eq!(limiter.try_acquire("channel-0", &muno(60), 0), Ok(()));
+        assert_eq!(limiter.try_acquire("channel-0", &muno(30), 10), Ok(()));
+
+        // the first transfer expires at 60
+        assert_eq!(limiter.try_acquire("channel-0", &muno(20), 20), Err(40));
+        // deferred packets don't count towards the window
+        assert_eq!(limiter.try_acquire("channel-0", &muno(10), 20), Ok(()));
+
+        assert_eq!(limiter.try_acquire("channel-0", &muno(20), 60), Ok(()));
+    }
+
+    #[test]
+    fn other_channels_and_denoms_are_not_limited() {
+        let limiter = limiter();
+
+        assert_eq!(limiter.try_acquire("channel-1", &muno(1000), 0), Ok(()));
+        assert_eq!(
+            limiter.try_acquire("channel-0", &[("uatom".to_owned(), 1000)], 0),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn packet_over_limit_waits_full_window() {
+        let limiter = limiter();
+
+        assert_eq!(limiter.try_acquire("channel-0", &muno(101), 0), Err(60));
+    }
+}
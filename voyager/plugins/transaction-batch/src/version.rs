@@ -0,0 +1,282 @@
+//! Channel version negotiation.
+//!
+//! When building the counterparty message for a channel handshake event, the version sent to the
+//! counterparty is determined by the [`VersionNegotiator`] registered for the ports of the channel.
+//! Most applications use an opaque version string that only needs to be passed through as-is
+//! ([`Passthrough`]), but some (such as [ICS-27](crate::ica)) encode structured metadata that
+//! needs to be validated or transformed.
+//!
+//! Versions wrapped by the ICS-29 fee middleware are unwrapped before being passed to the
+//! negotiator of the underlying application, and re-wrapped afterwards, so negotiators only ever
+//! see the version of their own application.
+
+use std::{fmt::Debug, sync::Arc};
+
+use serde::{Deserialize, Serialize};
+use voyager_vm::BoxDynError;
+
+use crate::ica::{IcaVersionNegotiator, ICA_CONTROLLER_PORT_ID_PREFIX, ICA_HOST_PORT_ID};
+
+pub const FEE_VERSION: &str = "ics29-1";
+
+/// The channel that a handshake message is being built for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HandshakeInfo<'a> {
+    /// The port on the chain the handshake event was emitted on.
+    pub port_id: &'a str,
+    /// The port on the chain the message will be sent to.
+    pub counterparty_port_id: &'a str,
+    /// The connection the channel is opened on, on the chain the handshake event was emitted on.
+    pub connection_id: &'a str,
+    /// The connection the channel is opened on, on the chain the message will be sent to.
+    pub counterparty_connection_id: &'a str,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TryVersion {
+    /// The version of the channel proposed in `ChanOpenTry`.
+    pub version: String,
+    /// The version of the channel on the counterparty (the chain that emitted `ChanOpenInit`).
+    pub counterparty_version: String,
+}
+
+pub trait VersionNegotiator: Debug + Send + Sync {
+    /// The versions to use in `ChanOpenTry`, given the version from `ChanOpenInit`.
+    fn try_version(
+        &self,
+        info: HandshakeInfo<'_>,
+        init_version: &str,
+    ) -> Result<TryVersion, BoxDynError> {
+        let _ = info;
+
+        Ok(TryVersion {
+            version: init_version.to_owned(),
+            counterparty_version: init_version.to_owned(),
+        })
+    }
+
+    /// The counterparty version to use in `ChanOpenAck`, given the version from `ChanOpenTry`.
+    fn ack_version(
+        &self,
+        info: HandshakeInfo<'_>,
+        try_version: &str,
+    ) -> Result<String, BoxDynError> {
+        let _ = info;
+
+        Ok(try_version.to_owned())
+    }
+}
+
+/// Passes the version through as-is.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Passthrough;
+
+impl VersionNegotiator for Passthrough {}
+
+/// The registry of [`VersionNegotiator`]s, keyed by port.
+#[derive(Debug, Clone)]
+pub struct VersionNegotiators {
+    ports: Vec<(String, Arc<dyn VersionNegotiator>)>,
+    default: Arc<dyn VersionNegotiator>,
+}
+
+impl Default for VersionNegotiators {
+    /// A registry containing the negotiator for ICS-27, falling back to [`Passthrough`].
+    fn default() -> Self {
+        Self::new(Passthrough)
+            .register(ICA_HOST_PORT_ID, IcaVersionNegotiator)
+            .register(ICA_CONTROLLER_PORT_ID_PREFIX, IcaVersionNegotiator)
+    }
+}
+
+impl VersionNegotiators {
+    /// A registry with no registered negotiators, using `default` for all ports.
+    #[must_use]
+    pub fn new(default: impl VersionNegotiator + 'static) -> Self {
+        Self {
+            ports: vec![],
+            default: Arc::new(default),
+        }
+    }
+
+    /// Register a negotiator for `port_id`. If `port_id` ends with `-`, it is treated as a prefix
+    /// (such as `icacontroller-`).
+    ///
+    /// Negotiators are matched against the port on either end of the channel, in the order they
+    /// were registered.
+    #[must_use]
+    pub fn register(
+        mut self,
+        port_id: impl Into<String>,
+        negotiator: impl VersionNegotiator + 'static,
+    ) -> Self {
+        self.ports.push((port_id.into(), Arc::new(negotiator)));
+        self
+    }
+
+    fn negotiator(&self, info: HandshakeInfo<'_>) -> &dyn VersionNegotiator {
+        [info.port_id, info.counterparty_port_id]
+            .into_iter()
+            .find_map(|port_id| {
+                self.ports.iter().find_map(|(registered, negotiator)| {
+                    let matches = if registered.ends_with('-') {
+                        port_id.starts_with(registered.as_str())
+                    } else {
+                        port_id == registered
+                    };
+
+                    matches.then_some(&**negotiator)
+                })
+            })
+            .unwrap_or(&*self.default)
+    }
+
+    pub fn try_version(
+        &self,
+        info: HandshakeInfo<'_>,
+        init_version: &str,
+    ) -> Result<TryVersion, BoxDynError> {
+        let negotiator = self.negotiator(info);
+
+        match FeeVersion::parse(init_version) {
+            Some(fee_version) => {
+                let TryVersion {
+                    version,
+                    counterparty_version,
+                } = negotiator.try_version(info, &fee_version.app_version)?;
+
+                Ok(TryVersion {
+                    version: fee_version.with_app_version(version),
+                    counterparty_version: fee_version.with_app_version(counterparty_version),
+                })
+            }
+            None => negotiator.try_version(info, init_version),
+        }
+    }
+
+    pub fn ack_version(
+        &self,
+        info: HandshakeInfo<'_>,
+        try_version: &str,
+    ) -> Result<String, BoxDynError> {
+        let negotiator = self.negotiator(info);
+
+        match FeeVersion::parse(try_version) {
+            Some(fee_version) => Ok(fee_version
+                .with_app_version(negotiator.ack_version(info, &fee_version.app_version)?)),
+            None => negotiator.ack_version(info, try_version),
+        }
+    }
+}
+
+/// The version of a channel wrapped by the ICS-29 fee middleware.
+///
+/// <https://github.com/cosmos/ibc/tree/main/spec/app/ics-029-fee-payment#fee-middleware-contract>
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct FeeVersion {
+    fee_version: String,
+    app_version: String,
+    /// The version as it was parsed, such that it can be returned exactly as-is if the app version
+    /// is unchanged.
+    #[serde(skip)]
+    raw: String,
+}
+
+impl FeeVersion {
+    fn parse(version: &str) -> Option<Self> {
+        serde_json::from_str::<Self>(version)
+            .ok()
+            .filter(|fee_version| fee_version.fee_version == FEE_VERSION)
+            .map(|fee_version| Self {
+                raw: version.to_owned(),
+                ..fee_version
+            })
+    }
+
+    fn with_app_version(&self, app_version: String) -> String {
+        if app_version == self.app_version {
+            return self.raw.clone();
+        }
+
+        serde_json::to_string(&Self {
+            fee_version: self.fee_version.clone(),
+            app_version,
+            raw: String::new(),
+        })
+        .expect("serialization is infallible; qed;")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug)]
+    struct Uppercase;
+
+    impl VersionNegotiator for Uppercase {
+        fn ack_version(
+            &self,
+            _: HandshakeInfo<'_>,
+            try_version: &str,
+        ) -> Result<String, BoxDynError> {
+            Ok(try_version.to_uppercase())
+        }
+    }
+
+    fn info<'a>(port_id: &'a str, counterparty_port_id: &'a str) -> HandshakeInfo<'a> {
+        HandshakeInfo {
+            port_id,
+            counterparty_port_id,
+            connection_id: "connection-0",
+            counterparty_connection_id: "connection-1",
+        }
+    }
+
+    #[test]
+    fn negotiator_per_port() {
+        let negotiators = VersionNegotiators::new(Passthrough)
+            .register("upper", Uppercase)
+            .register("upper-", Uppercase);
+
+        assert_eq!(
+            negotiators
+                .ack_version(info("transfer", "transfer"), "ics20-1")
+                .unwrap(),
+            "ics20-1"
+        );
+        assert_eq!(
+            negotiators
+                .ack_version(info("transfer", "upper"), "ics20-1")
+                .unwrap(),
+            "ICS20-1"
+        );
+        assert_eq!(
+            negotiators
+                .ack_version(info("upper-abc", "transfer"), "ics20-1")
+                .unwrap(),
+            "ICS20-1"
+        );
+        assert_eq!(
+            negotiators
+                .ack_version(info("upperabc", "transfer"), "ics20-1")
+                .unwrap(),
+            "ics20-1"
+        );
+    }
+
+    #[test]
+    fn fee_version_is_unwrapped() {
+        let negotiators = VersionNegotiators::new(Passthrough).register("upper", Uppercase);
+
+        assert_eq!(
+            negotiators
+                .ack_version(
+                    info("upper", "transfer"),
+                    r#"{"fee_version":"ics29-1","app_version":"ics20-1"}"#
+                )
+                .unwrap(),
+            r#"{"fee_version":"ics29-1","app_version":"ICS20-1"}"#
+        );
+    }
+}
@@ -7,7 +7,7 @@ use aptos_types::{
     transaction::{EntryFunction, RawTransaction},
 };
 use chain_utils::{
-    keyring::{ConcurrentKeyring, KeyringConfig, KeyringEntry},
+    keyring::{ConcurrentKeyring, KeyringConfig, KeyringConfigError, KeyringEntry},
     BoxDynError,
 };
 use ibc_union_spec::{Datagram, IbcUnion};
@@ -62,19 +62,30 @@ impl Plugin for Module {
     type Cmd = DefaultCmd;
 
     async fn new(config: Self::Config) -> Result<Self, BoxDynError> {
+        // sequence numbers are not tracked locally for aptos, so keys must not be used concurrently
+        if config.keyring.max_in_flight.is_some_and(|n| n.get() > 1) {
+            return Err("max_in_flight is not supported for aptos keyrings".into());
+        }
+
         let aptos_client = aptos_rest_client::Client::new(config.rpc_url.parse().unwrap());
 
         let chain_id = aptos_client.get_index().await?.inner().chain_id;
 
+        let keys = config
+            .keyring
+            .keys
+            .iter()
+            .map(|config| Ok((config.name(), config.value()?)))
+            .collect::<Result<Vec<_>, KeyringConfigError>>()?;
+
         Ok(Self {
             chain_id: ChainId::new(chain_id.to_string()),
             ibc_handler_address: config.ibc_handler_address,
             aptos_client,
             keyring: ConcurrentKeyring::new(
                 config.keyring.name,
-                config.keyring.keys.into_iter().map(|config| {
-                    let pk = aptos_crypto::ed25519::Ed25519PrivateKey::try_from(&*config.value())
-                        .unwrap();
+                keys.into_iter().map(|(name, key)| {
+                    let pk = aptos_crypto::ed25519::Ed25519PrivateKey::try_from(&*key).unwrap();
 
                     let address = (*<H256>::from(
                         sha3::Sha3_256::new()
@@ -86,7 +97,7 @@ impl Plugin for Module {
                     .into();
 
                     KeyringEntry {
-                        name: config.name(),
+                        name,
                         address,
                         signer: Arc::new(pk),
                     }
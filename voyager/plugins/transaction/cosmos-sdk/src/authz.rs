@@ -0,0 +1,30 @@
+//! Submission of messages on behalf of another account through the authz module.
+//!
+//! If an authz granter is configured, all messages are built with the granter as the signer and
+//! wrapped in a single `MsgExec`, which is signed by the key from the keyring (the grantee). This
+//! allows a hot key holding only enough funds to pay for gas (or none at all, if combined with a
+//! fee grant) to relay on behalf of a treasury account. The `cosmos.authz` protos are not included
+//! in the `protos` crate, so the types required for this are defined here.
+
+/// `cosmos.authz.v1beta1.MsgExec`
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct MsgExec {
+    #[prost(string, tag = "1")]
+    pub grantee: String,
+    #[prost(message, repeated, tag = "2")]
+    pub msgs: Vec<protos::google::protobuf::Any>,
+}
+
+impl ::prost::Name for MsgExec {
+    const NAME: &'static str = "MsgExec";
+    const PACKAGE: &'static str = "cosmos.authz.v1beta1";
+    fn full_name() -> String {
+        format!("cosmos.authz.v1beta1.{}", Self::NAME)
+    }
+}
+
+/// Whether a simulation error message indicates that the grant for one of the executed messages
+/// does not exist or has expired.
+pub fn is_missing_grant_message(message: &str) -> bool {
+    message.contains("authorization not found") || message.contains("authorization expired")
+}
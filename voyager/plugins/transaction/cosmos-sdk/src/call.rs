@@ -10,6 +10,20 @@ use voyager_message::{data::IbcDatagram, FATAL_JSONRPC_ERROR_CODE};
 #[derive(Enumorph)]
 pub enum ModuleCall {
     SubmitTransaction(Vec<IbcMessage>),
+    /// Submit a governance proposal to store 08-wasm client code. Only used if
+    /// `wasm_code_governance` is configured.
+    SubmitWasmCodeProposal(ibc_classic_spec::MsgStoreWasmCode),
+    /// Vote yes on the proposal with the key that is currently available in the keyring.
+    VoteOnProposal {
+        proposal_id: u64,
+    },
+    /// Poll the proposal until it has either passed or been rejected.
+    WaitForProposal {
+        proposal_id: u64,
+    },
+    /// Report the balances of all signers in the keyring, in the gas denom. Used by the voyager
+    /// balance monitor.
+    QuerySignerBalances,
 }
 
 #[model]
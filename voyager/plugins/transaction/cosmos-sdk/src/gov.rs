@@ -0,0 +1,190 @@
+//! Submission of 08-wasm light client code through a governance proposal.
+//!
+//! On chains where the authority of the 08-wasm module is the gov module (as is the case on
+//! Union), `MsgStoreCode` can only be executed as part of a passed proposal. The `cosmos.gov`
+//! protos are not included in the `protos` crate, so the (subset of the) types required to submit,
+//! vote on, and query proposals are defined here.
+
+use serde::{Deserialize, Serialize};
+use sha2::Digest;
+use unionlabs::{bech32::Bech32, bytes::Bytes, cosmos::base::coin::Coin};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct WasmCodeGovernanceConfig {
+    /// The initial deposit of the proposal. This must be at least the minimum initial deposit of
+    /// the chain, and the proposal only enters the voting period once the minimum deposit is met.
+    pub deposit: Vec<Coin>,
+    /// Vote yes on the proposal with the key that submitted it. This is only useful on devnets,
+    /// where the relayer key holds enough stake to pass the proposal by itself.
+    #[serde(default)]
+    pub vote: bool,
+    /// How often to poll the status of the proposal while it is pending.
+    #[serde(default = "default_poll_interval_seconds")]
+    pub poll_interval_seconds: u64,
+}
+
+#[must_use]
+#[inline]
+pub const fn default_poll_interval_seconds() -> u64 {
+    10
+}
+
+/// The address of the gov module account, which is the default authority of the 08-wasm module.
+pub fn gov_module_address(bech32_prefix: &str) -> String {
+    // module addresses are the first 20 bytes of the sha256 hash of the module name
+    let hash = sha2::Sha256::digest(b"gov");
+
+    Bech32::new(bech32_prefix.to_owned(), Bytes::from(hash[..20].to_vec())).to_string()
+}
+
+/// The proposal id of a submitted proposal, as emitted in the `submit_proposal` event.
+pub fn proposal_id_from_events<'a>(
+    mut events: impl Iterator<Item = &'a cometbft_types::abci::event::Event>,
+) -> Option<u64> {
+    events.find_map(|event| {
+        (event.ty == "submit_proposal")
+            .then(|| {
+                event
+                    .attributes
+                    .iter()
+                    .find(|attr| attr.key == "proposal_id")
+                    .and_then(|attr| attr.value.parse().ok())
+            })
+            .flatten()
+    })
+}
+
+/// `cosmos.gov.v1.ProposalStatus`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ::prost::Enumeration)]
+#[repr(i32)]
+pub enum ProposalStatus {
+    Unspecified = 0,
+    DepositPeriod = 1,
+    VotingPeriod = 2,
+    Passed = 3,
+    Rejected = 4,
+    Failed = 5,
+}
+
+/// `cosmos.gov.v1.VoteOption`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ::prost::Enumeration)]
+#[repr(i32)]
+pub enum VoteOption {
+    Unspecified = 0,
+    Yes = 1,
+    Abstain = 2,
+    No = 3,
+    NoWithVeto = 4,
+}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct MsgSubmitProposal {
+    #[prost(message, repeated, tag = "1")]
+    pub messages: Vec<protos::google::protobuf::Any>,
+    #[prost(message, repeated, tag = "2")]
+    pub initial_deposit: Vec<protos::cosmos::base::v1beta1::Coin>,
+    #[prost(string, tag = "3")]
+    pub proposer: String,
+    #[prost(string, tag = "4")]
+    pub metadata: String,
+    #[prost(string, tag = "5")]
+    pub title: String,
+    #[prost(string, tag = "6")]
+    pub summary: String,
+    #[prost(bool, tag = "7")]
+    pub expedited: bool,
+}
+
+impl ::prost::Name for MsgSubmitProposal {
+    const NAME: &'static str = "MsgSubmitProposal";
+    const PACKAGE: &'static str = "cosmos.gov.v1";
+    fn full_name() -> String {
+        format!("cosmos.gov.v1.{}", Self::NAME)
+    }
+}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct MsgVote {
+    #[prost(uint64, tag = "1")]
+    pub proposal_id: u64,
+    #[prost(string, tag = "2")]
+    pub voter: String,
+    #[prost(enumeration = "VoteOption", tag = "3")]
+    pub option: i32,
+    #[prost(string, tag = "4")]
+    pub metadata: String,
+}
+
+impl ::prost::Name for MsgVote {
+    const NAME: &'static str = "MsgVote";
+    const PACKAGE: &'static str = "cosmos.gov.v1";
+    fn full_name() -> String {
+        format!("cosmos.gov.v1.{}", Self::NAME)
+    }
+}
+
+pub const QUERY_PROPOSAL_PATH: &str = "/cosmos.gov.v1.Query/Proposal";
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct QueryProposalRequest {
+    #[prost(uint64, tag = "1")]
+    pub proposal_id: u64,
+}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct QueryProposalResponse {
+    #[prost(message, optional, tag = "1")]
+    pub proposal: Option<Proposal>,
+}
+
+/// Only the fields required to track the status of the proposal are decoded.
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct Proposal {
+    #[prost(uint64, tag = "1")]
+    pub id: u64,
+    #[prost(enumeration = "ProposalStatus", tag = "3")]
+    pub status: i32,
+    #[prost(string, tag = "15")]
+    pub failed_reason: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use cometbft_types::abci::{event::Event, event_attribute::EventAttribute};
+
+    use super::*;
+
+    #[test]
+    fn gov_module_address_union() {
+        assert_eq!(
+            gov_module_address("union"),
+            "union10d07y265gmmuvt4z0w9aw880jnsr700js4jdcz"
+        );
+    }
+
+    #[test]
+    fn parse_proposal_id() {
+        let events = [
+            Event {
+                ty: "message".to_owned(),
+                attributes: vec![EventAttribute {
+                    key: "action".to_owned(),
+                    value: "/cosmos.gov.v1.MsgSubmitProposal".to_owned(),
+                    index: true,
+                }],
+            },
+            Event {
+                ty: "submit_proposal".to_owned(),
+                attributes: vec![EventAttribute {
+                    key: "proposal_id".to_owned(),
+                    value: "7".to_owned(),
+                    index: true,
+                }],
+            },
+        ];
+
+        assert_eq!(proposal_id_from_events(events.iter()), Some(7));
+        assert_eq!(proposal_id_from_events(events[..1].iter()), None);
+    }
+}
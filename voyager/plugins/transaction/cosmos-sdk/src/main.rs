@@ -1,13 +1,22 @@
-use std::collections::VecDeque;
+use std::{
+    collections::{HashSet, VecDeque},
+    num::{NonZeroU64, NonZeroUsize},
+    sync::{Arc, Mutex},
+};
 
 use chain_utils::{
     cosmos_sdk::{
-        cosmos_sdk_error::{ChannelError, ClientError, CosmosSdkError, IbcWasmError, SdkError},
-        CosmosKeyring, GasConfig,
+        cosmos_sdk_error::{
+            AuthzError, ChannelError, ClientError, CosmosSdkError, FeegrantError, IbcWasmError,
+            SdkError,
+        },
+        fetch_balances, CosmosSdkKeyring, CosmosSdkSigner, GasConfig, MaxFeeExceeded,
     },
-    keyring::{KeyringConfig, KeyringEntry},
+    keyring::{KeyringConfig, KeyringEntry, SignerNonce},
+    signer::{AnySigner, SignerError},
     BoxDynError,
 };
+use cometbft_rpc::rpc_types::TxResponse;
 use jsonrpsee::{
     core::{async_trait, RpcResult},
     types::ErrorObject,
@@ -15,43 +24,49 @@ use jsonrpsee::{
 };
 use prost::Message;
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
 use sha2::Digest;
 use tracing::{debug, error, info, instrument, warn};
 use unionlabs::{
     self,
     bech32::Bech32,
-    bounded::BoundedI64,
     cosmos::{
         auth::base_account::BaseAccount,
         base::abci::gas_info::GasInfo,
         crypto::{secp256k1, AnyPubKey},
         tx::{
-            auth_info::AuthInfo, mode_info::ModeInfo, sign_doc::SignDoc, signer_info::SignerInfo,
-            signing::sign_info::SignMode, tx::Tx, tx_body::TxBody, tx_raw::TxRaw,
+            auth_info::AuthInfo, fee::Fee, mode_info::ModeInfo, sign_doc::SignDoc,
+            signer_info::SignerInfo, signing::sign_info::SignMode, tx::Tx, tx_body::TxBody,
+            tx_raw::TxRaw,
         },
     },
     encoding::{EncodeAs, Proto},
     google::protobuf::any::{mk_any, Any},
     hash::H256,
-    signer::CosmosSigner,
     ErrorReporter,
 };
 use voyager_message::{
     core::ChainId,
-    data::{Data, WithChainId},
+    data::{Data, SignerBalance, TxEvent, TxFee, TxMsg, TxReceipt, WithChainId},
+    into_value,
     module::{PluginInfo, PluginServer},
-    DefaultCmd, Plugin, PluginMessage, VoyagerMessage, FATAL_JSONRPC_ERROR_CODE,
+    tx_error::TxErrorClass,
+    DefaultCmd, ExtensionsExt, Plugin, PluginMessage, VoyagerClient, VoyagerMessage,
+    FATAL_JSONRPC_ERROR_CODE,
 };
-use voyager_vm::{call, conc, noop, pass::PassResult, Op};
+use voyager_vm::{call, conc, data, defer, noop, now, pass::PassResult, seq, Op};
 
 use crate::{
     call::{IbcMessage, ModuleCall},
     callback::ModuleCallback,
+    gov::WasmCodeGovernanceConfig,
 };
 
+pub mod authz;
 pub mod call;
 pub mod callback;
 pub mod data;
+pub mod gov;
 
 #[tokio::main(flavor = "multi_thread")]
 async fn main() {
@@ -62,11 +77,20 @@ async fn main() {
 pub struct Module {
     pub chain_id: ChainId,
     pub ibc_host_contract_address: Bech32<H256>,
-    pub keyring: CosmosKeyring,
+    pub keyring: CosmosSdkKeyring,
     pub tm_client: cometbft_rpc::Client,
     pub grpc_url: String,
     pub gas_config: GasConfig,
     pub bech32_prefix: String,
+    pub fee_middleware: Vec<FeeMiddlewareChannel>,
+    pub max_batch_size: usize,
+    /// `(relayer, port_id, channel_id)` tuples that the ICS-29 payees have been registered for.
+    pub registered_payees: Arc<Mutex<HashSet<(String, String, String)>>>,
+    pub wasm_code_governance: Option<WasmCodeGovernanceConfig>,
+    pub dry_run: bool,
+    pub memo: String,
+    pub fee_granter: Option<String>,
+    pub authz_granter: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -75,8 +99,69 @@ pub struct Config {
     pub ibc_host_contract_address: Bech32<H256>,
     pub keyring: KeyringConfig,
     pub ws_url: String,
+    /// Fallback endpoints to use if `ws_url` is unavailable.
+    #[serde(default)]
+    pub failover: cometbft_rpc::FailoverConfig,
     pub grpc_url: String,
     pub gas_config: GasConfig,
+    /// Fee enabled (ICS-29) channels on this chain that fees should be collected on.
+    #[serde(default)]
+    pub fee_middleware: Vec<FeeMiddlewareChannel>,
+    /// The maximum amount of IBC messages that will be submitted in a single transaction.
+    #[serde(default = "default_max_batch_size")]
+    pub max_batch_size: usize,
+    /// Store 08-wasm client code through a governance proposal instead of submitting
+    /// `MsgStoreCode` directly. This is required on chains where the authority of the 08-wasm
+    /// module is the gov module.
+    #[serde(default)]
+    pub wasm_code_governance: Option<WasmCodeGovernanceConfig>,
+    /// Simulate transactions and report the expected gas usage and any errors instead of
+    /// broadcasting them. Messages are dropped after being simulated.
+    #[serde(default)]
+    pub dry_run: bool,
+    /// The memo to set on all submitted transactions, i.e. to identify the operator of this relayer
+    /// to indexers and fee grant programs. Defaults to `Voyager <version>`.
+    #[serde(default = "default_memo")]
+    pub memo: String,
+    /// The address of an account that has granted a fee allowance (via the feegrant module) to all
+    /// of the signers in the keyring. If set, the fees of all submitted transactions are paid by
+    /// this account instead of the signer.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fee_granter: Option<String>,
+    /// The address of an account that has granted all of the signers in the keyring authorization
+    /// (via the authz module) to execute the relayed messages on its behalf. If set, all messages
+    /// are submitted with this account as the sender, wrapped in a `MsgExec` signed by the key
+    /// from the keyring. The grants must cover every message type that is relayed, including the
+    /// ICS-29 payee registrations if [`Self::fee_middleware`] is configured.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub authz_granter: Option<String>,
+}
+
+fn default_max_batch_size() -> usize {
+    5
+}
+
+fn default_memo() -> String {
+    format!("Voyager {}", env!("CARGO_PKG_VERSION"))
+}
+
+/// A fee enabled (ICS-29) channel.
+///
+/// The payees are registered for every signer in the keyring the first time that signer relays
+/// a packet on this channel, by including `MsgRegisterCounterpartyPayee` and `MsgRegisterPayee` in
+/// the same transaction.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FeeMiddlewareChannel {
+    pub port_id: String,
+    pub channel_id: String,
+    /// The address on the counterparty chain that will receive the recv fee for packets received
+    /// on this chain.
+    pub counterparty_payee: String,
+    /// The address on this chain that will receive the ack and timeout fees for packets sent from
+    /// this chain. If this is not set, the fees are paid to the signer that relayed the message.
+    #[serde(default)]
+    pub payee: Option<String>,
 }
 
 impl Plugin for Module {
@@ -87,7 +172,12 @@ impl Plugin for Module {
     type Cmd = DefaultCmd;
 
     async fn new(config: Self::Config) -> Result<Self, BoxDynError> {
-        let tm_client = cometbft_rpc::Client::new(config.ws_url).await?;
+        if config.max_batch_size == 0 {
+            return Err("max_batch_size must be greater than 0".into());
+        }
+
+        let tm_client =
+            cometbft_rpc::Client::new_with_failover(config.ws_url, &config.failover).await?;
 
         let chain_id = tm_client.status().await?.node_info.network.to_string();
 
@@ -102,31 +192,39 @@ impl Plugin for Module {
         .into_inner()
         .bech32_prefix;
 
+        let keys = futures::future::try_join_all(config.keyring.keys.iter().map(|entry| {
+            let bech32_prefix = bech32_prefix.clone();
+
+            async move {
+                let signer =
+                    CosmosSdkSigner::new(AnySigner::from_config(entry).await?, bech32_prefix);
+
+                Ok::<_, SignerError>(KeyringEntry {
+                    name: entry.name(),
+                    address: signer.to_string(),
+                    signer,
+                })
+            }
+        }))
+        .await?;
+
         Ok(Self {
             ibc_host_contract_address: config.ibc_host_contract_address,
-            keyring: CosmosKeyring::new(
-                config.keyring.name,
-                config.keyring.keys.into_iter().map(|entry| {
-                    let signer = CosmosSigner::new(
-                        bip32::secp256k1::ecdsa::SigningKey::from_bytes(
-                            entry.value().as_slice().into(),
-                        )
-                        .expect("invalid private key"),
-                        bech32_prefix.clone(),
-                    );
-
-                    KeyringEntry {
-                        name: entry.name(),
-                        address: signer.to_string(),
-                        signer,
-                    }
-                }),
-            ),
+            keyring: CosmosSdkKeyring::new(config.keyring.name, keys.into_iter())
+                .with_max_in_flight(config.keyring.max_in_flight.unwrap_or(NonZeroUsize::MIN)),
             tm_client,
             chain_id: ChainId::new(chain_id),
             grpc_url: config.grpc_url,
             gas_config: config.gas_config,
             bech32_prefix,
+            fee_middleware: config.fee_middleware,
+            max_batch_size: config.max_batch_size,
+            registered_payees: Arc::new(Mutex::new(HashSet::new())),
+            wasm_code_governance: config.wasm_code_governance,
+            dry_run: config.dry_run,
+            memo: config.memo,
+            fee_granter: config.fee_granter,
+            authz_granter: config.authz_granter,
         })
     }
 
@@ -166,10 +264,65 @@ impl Module {
         plugin_name(&self.chain_id)
     }
 
+    /// The maximum amount of messages to submit in a single transaction. This is 1 if the chain
+    /// is declared to not support batching transactions, see [`ChainCapabilities::batch_txs`].
+    ///
+    /// [`ChainCapabilities::batch_txs`]: voyager_message::module::ChainCapabilities::batch_txs
+    async fn max_batch_size(&self, e: &Extensions) -> RpcResult<usize> {
+        let capabilities = e
+            .try_get::<VoyagerClient>()?
+            .chain_capabilities(self.chain_id.clone())
+            .await?;
+
+        Ok(if capabilities.batch_txs == Some(false) {
+            1
+        } else {
+            self.max_batch_size
+        })
+    }
+
     pub async fn do_send_transaction(
         &self,
         msgs: Vec<IbcMessage>,
     ) -> Result<Op<VoyagerMessage>, BroadcastTxCommitError> {
+        // migrating to code that doesn't exist fails on chain, after paying for the transaction
+        for msg in &msgs {
+            if let IbcMessage::IbcV1(ibc_classic_spec::Datagram::MigrateWasmClient(message)) = msg {
+                self.ensure_wasm_code_exists(message.checksum).await?;
+            }
+        }
+
+        if self.dry_run {
+            let res = self
+                .keyring
+                .with(|signer| {
+                    let msgs = msgs.clone();
+
+                    async move {
+                        let msgs = process_msgs(
+                            msgs,
+                            &self.sender(signer),
+                            self.ibc_host_contract_address.clone(),
+                        )
+                        .into_iter()
+                        .map(|(_, msg)| msg)
+                        .collect();
+
+                        self.simulate_and_report(signer, self.authz_exec(signer, msgs))
+                            .await;
+                    }
+                })
+                .await;
+
+            return Ok(match res {
+                Some(()) => noop(),
+                None => call(PluginMessage::new(
+                    self.plugin_name(),
+                    ModuleCall::SubmitTransaction(msgs),
+                )),
+            });
+        }
+
         let res = self
             .keyring
             .with(|signer| {
@@ -178,10 +331,11 @@ impl Module {
                 dbg!(&msgs);
 
                 async move {
-                    // TODO: Figure out a way to thread this value through
-                    let memo = format!("Voyager {}", env!("CARGO_PKG_VERSION"));
+                    let sender = self.sender(signer);
+
+                    let payee_registrations = self.payee_registrations(&sender, &msgs);
 
-                    let msgs = process_msgs(msgs, signer, self.ibc_host_contract_address.clone());
+                    let msgs = process_msgs(msgs, &sender, self.ibc_host_contract_address.clone());
 
                     // let simulation_results = stream::iter(msgs.clone().into_iter().enumerate())
                     //     .then(move |(idx, (effect, msg))| async move {
@@ -245,31 +399,64 @@ impl Module {
                     let batch_size = msgs.len();
                     let msg_names = msgs.iter().map(|x| x.1.type_url.clone()).collect::<Vec<_>>();
 
+                    let tx_msgs = payee_registrations
+                        .iter()
+                        .map(|(_, msg)| TxMsg {
+                            msg_type: msg.type_url.clone(),
+                            client_id: None,
+                            error: None,
+                        })
+                        .chain(msgs.iter().map(|(msg, encoded)| {
+                            TxMsg::from_datagram(
+                                encoded.type_url.clone(),
+                                &match msg {
+                                    IbcMessage::IbcV1(datagram) => into_value(datagram),
+                                    IbcMessage::IbcUnion(datagram) => into_value(datagram),
+                                },
+                            )
+                        }))
+                        .collect();
+
                     match self.broadcast_tx_commit(
                         signer,
-                        msgs.iter().map(move |x| x.1.clone()).collect::<Vec<_>>(),
-                        memo
+                        self.authz_exec(
+                            signer,
+                            payee_registrations
+                                .iter()
+                                .map(|(_, msg)| msg.clone())
+                                .chain(msgs.iter().map(move |x| x.1.clone()))
+                                .collect::<Vec<_>>(),
+                        ),
+                        self.memo.clone(),
                     ).await {
-                        Ok((tx_hash, gas_used)) => {
+                        Ok(tx) => {
+                            let receipt = tx_receipt(&tx, tx_msgs);
+                            let tx_hash = receipt.tx_hash;
+
                             info!(
                                 %tx_hash,
-                                %gas_used,
+                                gas_used = %receipt.gas_used,
                                 batch.size = %batch_size,
                                 "submitted cosmos transaction"
                             );
 
+                            self.registered_payees
+                                .lock()
+                                .expect("mutex is poisoned")
+                                .extend(payee_registrations.into_iter().map(|(key, _)| key));
+
                             for msg in msg_names {
                                 info!(%tx_hash, %msg, "cosmos tx");
                             }
 
-                            Ok(())
+                            Ok(Some(receipt))
                         }
                         Err(err) => match err {
                             BroadcastTxCommitError::Tx(CosmosSdkError::ChannelError(
                                 ChannelError::ErrRedundantTx,
                             )) => {
                                 info!("packet messages are redundant");
-                                Ok(())
+                                Ok(None)
                             }
                             // BroadcastTxCommitError::Tx(CosmosSdkError::SdkError(
                             //     SdkError::ErrOutOfGas
@@ -285,8 +472,17 @@ impl Module {
                             }
                             BroadcastTxCommitError::SimulateTx(err) if err.message().contains("account sequence mismatch") => {
                                 warn!("account sequence mismatch on simulation, message will be requeued and retried");
+                                self.keyring.nonces().lock(&signer.to_string()).await.resync(expected_sequence(err.message()));
                                 Err(BroadcastTxCommitError::AccountSequenceMismatch(Some(err)))
                             }
+                            err if err.is_missing_authz_grant() => {
+                                error!(
+                                    authz_granter = ?self.authz_granter,
+                                    grantee = %signer,
+                                    "authz grant does not exist or has expired, it must be renewed by the granter"
+                                );
+                                Err(err)
+                            }
                             err => Err(err),
                         },
                     }
@@ -298,41 +494,125 @@ impl Module {
             || PluginMessage::new(self.plugin_name(), ModuleCall::SubmitTransaction(msgs));
 
         match res {
-            Some(Err(BroadcastTxCommitError::AccountSequenceMismatch(_))) => Ok(call(rewrap_msg())),
-            Some(Err(BroadcastTxCommitError::OutOfGas)) => Ok(call(rewrap_msg())),
-            Some(Err(BroadcastTxCommitError::SimulateTx(err))) => {
-                error!(
-                    error = %ErrorReporter(err),
-                    "transaction simulation failed, message will be requeued and retried"
-                );
-
-                Ok(call(rewrap_msg()))
-            }
-            Some(Err(BroadcastTxCommitError::QueryLatestHeight(err))) => {
-                error!(error = %ErrorReporter(err), "error querying latest height");
-
-                Ok(call(rewrap_msg()))
-            }
-            Some(res) => res.map(|()| noop()),
+            // a sequence mismatch is returned as a retryable error (after the sequence has been
+            // resynced), such that a persistent mismatch (i.e. another relayer using the same
+            // key) is subject to the retry limit and backoff
+            Some(res) => res.map(|receipt| match receipt {
+                Some(receipt) => data(WithChainId {
+                    chain_id: self.chain_id.clone(),
+                    message: receipt,
+                }),
+                None => noop(),
+            }),
             // None => Ok(seq([defer_relative(1), effect(WithChainId{chain_id: self.chain_id.clone(), message: msg})])),
             None => Ok(call(rewrap_msg())),
         }
     }
 
+    /// Build the ICS-29 payee registration messages for all fee enabled channels that packets in
+    /// `msgs` are relayed on, that have not yet been registered for `relayer`.
+    fn payee_registrations(
+        &self,
+        relayer: &str,
+        msgs: &[IbcMessage],
+    ) -> Vec<((String, String, String), protos::google::protobuf::Any)> {
+        if self.fee_middleware.is_empty() {
+            return vec![];
+        }
+
+        let registered_payees = self.registered_payees.lock().expect("mutex is poisoned");
+
+        msgs.iter()
+            .filter_map(|msg| match msg {
+                // recv fees are paid out on the counterparty chain, to the counterparty payee of the
+                // relayer on the destination channel
+                IbcMessage::IbcV1(ibc_classic_spec::Datagram::RecvPacket(msg)) => Some((
+                    msg.packet.destination_port.to_string(),
+                    msg.packet.destination_channel.to_string(),
+                )),
+                // ack and timeout fees are paid out on this chain, to the payee of the relayer on
+                // the source channel
+                IbcMessage::IbcV1(ibc_classic_spec::Datagram::AcknowledgePacket(msg)) => Some((
+                    msg.packet.source_port.to_string(),
+                    msg.packet.source_channel.to_string(),
+                )),
+                IbcMessage::IbcV1(ibc_classic_spec::Datagram::TimeoutPacket(msg)) => Some((
+                    msg.packet.source_port.to_string(),
+                    msg.packet.source_channel.to_string(),
+                )),
+                _ => None,
+            })
+            .collect::<HashSet<_>>()
+            .into_iter()
+            .filter(|(port_id, channel_id)| {
+                !registered_payees.contains(&(
+                    relayer.to_owned(),
+                    port_id.clone(),
+                    channel_id.clone(),
+                ))
+            })
+            .filter_map(|(port_id, channel_id)| {
+                let channel = self
+                    .fee_middleware
+                    .iter()
+                    .find(|c| c.port_id == port_id && c.channel_id == channel_id)?;
+
+                info!(
+                    %port_id,
+                    %channel_id,
+                    %relayer,
+                    counterparty_payee = %channel.counterparty_payee,
+                    payee = ?channel.payee,
+                    "registering ics-29 payees"
+                );
+
+                Some(
+                    [
+                        mk_any(
+                            &protos::ibc::applications::fee::v1::MsgRegisterCounterpartyPayee {
+                                port_id: port_id.clone(),
+                                channel_id: channel_id.clone(),
+                                relayer: relayer.to_owned(),
+                                counterparty_payee: channel.counterparty_payee.clone(),
+                            },
+                        ),
+                        mk_any(&protos::ibc::applications::fee::v1::MsgRegisterPayee {
+                            port_id: port_id.clone(),
+                            channel_id: channel_id.clone(),
+                            relayer: relayer.to_owned(),
+                            payee: channel.payee.clone().unwrap_or_else(|| relayer.to_owned()),
+                        }),
+                    ]
+                    .map(|msg| {
+                        (
+                            (relayer.to_owned(), port_id.clone(), channel_id.clone()),
+                            msg,
+                        )
+                    }),
+                )
+            })
+            .flatten()
+            .collect()
+    }
+
     /// - simulate tx
     /// - submit tx
     /// - wait for inclusion
-    /// - return (tx_hash, gas_used)
+    /// - return the included tx
     pub async fn broadcast_tx_commit(
         &self,
-        signer: &CosmosSigner,
+        signer: &CosmosSdkSigner,
         messages: impl IntoIterator<Item = protos::google::protobuf::Any> + Clone,
         memo: String,
-    ) -> Result<(H256, BoundedI64<0, { i64::MAX }>), BroadcastTxCommitError> {
+    ) -> Result<TxResponse, BroadcastTxCommitError> {
         let account = self.account_info(&signer.to_string()).await;
 
+        // held until the tx is in the mempool, such that concurrent submissions from this signer
+        // are broadcast in sequence order
+        let mut nonce = self.keyring.nonces().lock(&signer.to_string()).await;
+
         let (tx_body, mut auth_info, simulation_gas_info) =
-            match self.simulate_tx(signer, messages, memo).await {
+            match self.simulate_tx(signer, &nonce, messages, memo).await {
                 Ok((tx_body, auth_info, simulation_gas_info)) => {
                     (tx_body, auth_info, simulation_gas_info)
                 }
@@ -353,7 +633,9 @@ impl Module {
             "tx simulation successful"
         );
 
-        auth_info.fee = self.gas_config.mk_fee(simulation_gas_info.gas_used);
+        auth_info.fee = self.mk_fee(simulation_gas_info.gas_used);
+
+        self.gas_config.check_max_fee(&auth_info.fee)?;
 
         // dbg!(&auth_info.fee);
 
@@ -365,7 +647,7 @@ impl Module {
 
         // re-sign the new auth info with the simulated gas
         let signature = signer
-            .try_sign(
+            .sign(
                 &SignDoc {
                     body_bytes: tx_body.clone().encode_as::<Proto>(),
                     auth_info_bytes: auth_info.clone().encode_as::<Proto>(),
@@ -374,8 +656,8 @@ impl Module {
                 }
                 .encode_as::<Proto>(),
             )
-            .expect("signing failed")
-            .to_vec();
+            .await
+            .map_err(BroadcastTxCommitError::Sign)?;
 
         let tx_raw_bytes = TxRaw {
             body_bytes: tx_body.clone().encode_as::<Proto>(),
@@ -391,7 +673,7 @@ impl Module {
 
         if let Ok(tx) = self.tm_client.tx(tx_hash, false).await {
             debug!(%tx_hash, "tx already included");
-            return Ok((tx_hash, tx.tx_result.gas_used));
+            return Ok(tx);
         }
 
         let response = self
@@ -414,9 +696,29 @@ impl Module {
 
             error!(%error, "cosmos tx failed");
 
+            if let (CosmosSdkError::FeegrantError(_), Some(fee_granter)) =
+                (&error, &self.fee_granter)
+            {
+                error!(
+                    %fee_granter,
+                    grantee = %signer,
+                    check_tx_log = %response.log,
+                    "fee grant is exhausted, expired, or does not allow this transaction"
+                );
+            }
+
+            if tx_error_class(&error) == TxErrorClass::RetryAfterResync {
+                nonce.resync(expected_sequence(&response.log));
+            }
+
             return Err(BroadcastTxCommitError::Tx(error));
         };
 
+        // the sequence is consumed as soon as the tx passes CheckTx, regardless of whether it
+        // succeeds once included
+        nonce.submitted(auth_info.signer_infos[0].sequence);
+        drop(nonce);
+
         let mut target_height = self
             .tm_client
             .block(None)
@@ -452,7 +754,7 @@ impl Module {
             match tx_inclusion {
                 Ok(tx) => {
                     if tx.tx_result.code == 0 {
-                        break Ok((tx_hash, tx.tx_result.gas_used));
+                        break Ok(tx);
                     } else {
                         let error = CosmosSdkError::from_code_and_codespace(
                             &tx.tx_result.codespace,
@@ -499,13 +801,16 @@ impl Module {
 
     pub async fn simulate_tx(
         &self,
-        signer: &CosmosSigner,
+        signer: &CosmosSdkSigner,
+        nonce: &SignerNonce<String>,
         messages: impl IntoIterator<Item = protos::google::protobuf::Any> + Clone,
         memo: String,
     ) -> Result<(TxBody, AuthInfo, GasInfo), (TxBody, AuthInfo, tonic::Status)> {
         use protos::cosmos::tx;
 
-        let account = self.account_info(&signer.to_string()).await;
+        let mut account = self.account_info(&signer.to_string()).await;
+
+        account.sequence = nonce.next(account.sequence);
 
         let mut client = tx::v1beta1::service_client::ServiceClient::connect(self.grpc_url.clone())
             .await
@@ -533,11 +838,11 @@ impl Module {
                 sequence: account.sequence,
             }]
             .to_vec(),
-            fee: self.gas_config.mk_fee(self.gas_config.max_gas).clone(),
+            fee: self.mk_fee(self.gas_config.max_gas),
         };
 
-        let simulation_signature = signer
-            .try_sign(
+        let simulation_signature = match signer
+            .sign(
                 &SignDoc {
                     body_bytes: tx_body.clone().encode_as::<Proto>(),
                     auth_info_bytes: auth_info.clone().encode_as::<Proto>(),
@@ -546,8 +851,18 @@ impl Module {
                 }
                 .encode_as::<Proto>(),
             )
-            .expect("signing failed")
-            .to_vec();
+            .await
+        {
+            Ok(signature) => signature,
+            Err(err) => {
+                // surfaced as a simulation failure, which is retried
+                let status = tonic::Status::unavailable(format!(
+                    "error signing transaction for simulation: {}",
+                    ErrorReporter(err)
+                ));
+                return Err((tx_body, auth_info, status));
+            }
+        };
 
         let result = client
             .simulate(tx::v1beta1::SimulateRequest {
@@ -577,6 +892,237 @@ impl Module {
         }
     }
 
+    /// The sender of the relayed messages, which is the authz granter if one is configured.
+    fn sender(&self, signer: &CosmosSdkSigner) -> String {
+        self.authz_granter
+            .clone()
+            .unwrap_or_else(|| signer.to_string())
+    }
+
+    /// Wrap `msgs` in a `MsgExec` executed by `signer` if an authz granter is configured.
+    fn authz_exec(
+        &self,
+        signer: &CosmosSdkSigner,
+        msgs: Vec<protos::google::protobuf::Any>,
+    ) -> Vec<protos::google::protobuf::Any> {
+        match self.authz_granter {
+            Some(_) => vec![mk_any(&authz::MsgExec {
+                grantee: signer.to_string(),
+                msgs,
+            })],
+            None => msgs,
+        }
+    }
+
+    /// Build the fee for a transaction using `gas`, paid by the fee granter if one is configured.
+    fn mk_fee(&self, gas: u64) -> Fee {
+        Fee {
+            granter: self.fee_granter.clone().unwrap_or_default(),
+            ..self.gas_config.mk_fee(gas)
+        }
+    }
+
+    fn wasm_code_proposal(
+        &self,
+        proposer: &CosmosSdkSigner,
+        governance: &WasmCodeGovernanceConfig,
+        message: ibc_classic_spec::MsgStoreWasmCode,
+    ) -> protos::google::protobuf::Any {
+        mk_any(&gov::MsgSubmitProposal {
+            messages: vec![mk_any(&protos::ibc::lightclients::wasm::v1::MsgStoreCode {
+                signer: gov::gov_module_address(&self.bech32_prefix),
+                wasm_byte_code: message.wasm_byte_code.into(),
+            })],
+            initial_deposit: governance.deposit.iter().cloned().map(Into::into).collect(),
+            proposer: proposer.to_string(),
+            metadata: String::new(),
+            title: "Store 08-wasm light client code".to_owned(),
+            summary: format!(
+                "Store 08-wasm light client code, submitted by voyager {}",
+                env!("CARGO_PKG_VERSION")
+            ),
+            expedited: false,
+        })
+    }
+
+    /// Submit a governance proposal to store the 08-wasm client code in `message`, returning the id
+    /// of the proposal. In dry run mode, the proposal is only simulated and `None` is returned.
+    async fn submit_wasm_code_proposal(
+        &self,
+        governance: &WasmCodeGovernanceConfig,
+        message: ibc_classic_spec::MsgStoreWasmCode,
+    ) -> Option<Result<Option<u64>, BroadcastTxCommitError>> {
+        self.keyring
+            .with(|signer| {
+                let message = message.clone();
+
+                async move {
+                    let msg = self.wasm_code_proposal(signer, governance, message);
+
+                    if self.dry_run {
+                        self.simulate_and_report(signer, vec![msg]).await;
+
+                        return Ok(None);
+                    }
+
+                    let tx = self
+                        .broadcast_tx_commit(signer, [msg], self.memo.clone())
+                        .await?;
+
+                    let tx_hash = tx.hash.into_encoding();
+
+                    let proposal_id = gov::proposal_id_from_events(tx.tx_result.events.iter())
+                        .ok_or(BroadcastTxCommitError::ProposalIdNotFound(tx_hash))?;
+
+                    info!(%tx_hash, %proposal_id, "submitted wasm code proposal");
+
+                    Ok(Some(proposal_id))
+                }
+            })
+            .await
+    }
+
+    async fn vote_on_proposal(
+        &self,
+        proposal_id: u64,
+    ) -> Option<Result<(), BroadcastTxCommitError>> {
+        self.keyring
+            .with(|signer| async move {
+                let msg = mk_any(&gov::MsgVote {
+                    proposal_id,
+                    voter: signer.to_string(),
+                    option: gov::VoteOption::Yes.into(),
+                    metadata: String::new(),
+                });
+
+                let tx = self
+                    .broadcast_tx_commit(signer, [msg], self.memo.clone())
+                    .await?;
+
+                info!(tx_hash = %tx.hash, %proposal_id, voter = %signer, "voted on proposal");
+
+                Ok(())
+            })
+            .await
+    }
+
+    async fn query_proposal(&self, proposal_id: u64) -> RpcResult<gov::Proposal> {
+        let res = self
+            .tm_client
+            .abci_query(
+                gov::QUERY_PROPOSAL_PATH,
+                gov::QueryProposalRequest { proposal_id }.encode_to_vec(),
+                None,
+                false,
+            )
+            .await
+            .map_err(|err| ErrorObject::owned(-1, ErrorReporter(err).to_string(), None::<()>))?
+            .response;
+
+        if res.code != 0 {
+            return Err(ErrorObject::owned(
+                -1,
+                format!("error querying proposal {proposal_id}: {}", res.log),
+                None::<()>,
+            ));
+        }
+
+        gov::QueryProposalResponse::decode(res.value.as_deref().unwrap_or_default())
+            .map_err(|err| {
+                ErrorObject::owned(
+                    FATAL_JSONRPC_ERROR_CODE,
+                    format!(
+                        "error decoding proposal {proposal_id}: {}",
+                        ErrorReporter(err)
+                    ),
+                    None::<()>,
+                )
+            })?
+            .proposal
+            .ok_or_else(|| {
+                ErrorObject::owned(
+                    FATAL_JSONRPC_ERROR_CODE,
+                    format!("proposal {proposal_id} not found"),
+                    None::<()>,
+                )
+            })
+    }
+
+    fn wasm_code_governance(&self) -> RpcResult<&WasmCodeGovernanceConfig> {
+        self.wasm_code_governance.as_ref().ok_or_else(|| {
+            ErrorObject::owned(
+                FATAL_JSONRPC_ERROR_CODE,
+                "wasm_code_governance is not configured",
+                None::<()>,
+            )
+        })
+    }
+
+    /// Simulate a transaction containing `msgs` and report the result, without broadcasting it.
+    async fn simulate_and_report(
+        &self,
+        signer: &CosmosSdkSigner,
+        msgs: Vec<protos::google::protobuf::Any>,
+    ) {
+        let msg_names = msgs
+            .iter()
+            .map(|msg| msg.type_url.clone())
+            .collect::<Vec<_>>();
+
+        let nonce = self.keyring.nonces().lock(&signer.to_string()).await;
+
+        match self
+            .simulate_tx(signer, &nonce, msgs, self.memo.clone())
+            .await
+        {
+            Ok((_, _, gas_info)) => {
+                let fee = self.gas_config.mk_fee(gas_info.gas_used);
+
+                info!(
+                    gas_used = %gas_info.gas_used,
+                    gas_wanted = %gas_info.gas_wanted,
+                    fee = %fee.amount[0].amount,
+                    max_fee_exceeded = self.gas_config.check_max_fee(&fee).is_err(),
+                    ?msg_names,
+                    "dry run: tx simulation successful"
+                );
+            }
+            Err((_, _, err)) => {
+                error!(
+                    error = %ErrorReporter(err),
+                    ?msg_names,
+                    "dry run: tx simulation failed"
+                );
+            }
+        }
+    }
+
+    /// Check that 08-wasm client code with `checksum` has been stored on chain.
+    async fn ensure_wasm_code_exists(&self, checksum: H256) -> Result<(), BroadcastTxCommitError> {
+        debug!(%checksum, "querying wasm client code");
+
+        let res = protos::ibc::lightclients::wasm::v1::query_client::QueryClient::connect(
+            self.grpc_url.clone(),
+        )
+        .await
+        .map_err(|err| {
+            BroadcastTxCommitError::QueryWasmCode(tonic::Status::from_error(err.into()))
+        })?
+        .code(protos::ibc::lightclients::wasm::v1::QueryCodeRequest {
+            // ibc-go expects the checksum to be hex encoded, without a 0x prefix
+            checksum: hex::encode(checksum.get()),
+        })
+        .await;
+
+        match res {
+            Ok(_) => Ok(()),
+            Err(status) if status.code() == tonic::Code::NotFound => {
+                Err(BroadcastTxCommitError::WasmCodeNotFound(checksum))
+            }
+            Err(status) => Err(BroadcastTxCommitError::QueryWasmCode(status)),
+        }
+    }
+
     async fn account_info(&self, account: &str) -> BaseAccount {
         debug!(%account, "fetching account");
 
@@ -600,6 +1146,75 @@ impl Module {
     }
 }
 
+/// Events emitted by the cosmos-sdk for every transaction, which are not included in receipts.
+const SDK_EVENT_TYPES: &[&str] = &[
+    "tx",
+    "message",
+    "coin_spent",
+    "coin_received",
+    "transfer",
+    "use_feegrant",
+];
+
+fn tx_receipt(tx: &TxResponse, msgs: Vec<TxMsg>) -> TxReceipt {
+    TxReceipt {
+        tx_hash: tx.hash.into_encoding(),
+        height: tx.height.map_or(0, NonZeroU64::get),
+        gas_used: tx.tx_result.gas_used.inner().unsigned_abs(),
+        fee: tx_fee(tx),
+        msgs,
+        events: tx
+            .tx_result
+            .events
+            .iter()
+            .filter(|event| !SDK_EVENT_TYPES.contains(&event.ty.as_str()))
+            .map(|event| TxEvent {
+                name: event.ty.clone(),
+                attributes: event
+                    .attributes
+                    .iter()
+                    .map(|attr| (attr.key.clone(), Value::String(attr.value.clone())))
+                    .collect::<serde_json::Map<_, _>>()
+                    .into(),
+            })
+            .collect(),
+    }
+}
+
+/// The fee paid for a transaction, taken from the `fee` attribute of the `tx` event (i.e.
+/// `1500muno`). Only the first coin is returned if the fee was paid in multiple denoms.
+fn tx_fee(tx: &TxResponse) -> Option<TxFee> {
+    let fee = tx
+        .tx_result
+        .events
+        .iter()
+        .filter(|event| event.ty == "tx")
+        .flat_map(|event| &event.attributes)
+        .find(|attr| attr.key == "fee")?
+        .value
+        .split(',')
+        .next()?;
+
+    let denom_start = fee.find(|c: char| !c.is_ascii_digit())?;
+    let (amount, denom) = fee.split_at(denom_start);
+
+    Some(TxFee {
+        amount: amount.parse().ok()?,
+        denom: denom.to_owned(),
+    })
+}
+
+/// Parse the expected sequence out of an account sequence mismatch error log, i.e.
+/// `account sequence mismatch, expected 10, got 9: incorrect account sequence`.
+fn expected_sequence(log: &str) -> Option<u64> {
+    log.split_once("expected ")?
+        .1
+        .split(|c: char| !c.is_ascii_digit())
+        .next()?
+        .parse()
+        .ok()
+}
+
 #[derive(Debug, thiserror::Error)]
 pub enum BroadcastTxCommitError {
     #[error("error querying latest height")]
@@ -612,12 +1227,96 @@ pub enum BroadcastTxCommitError {
     Tx(CosmosSdkError),
     #[error("tx simulation failed")]
     SimulateTx(#[source] tonic::Status),
+    #[error("error signing tx")]
+    Sign(#[source] SignerError),
     #[error("account sequence mismatch")]
     AccountSequenceMismatch(#[source] Option<tonic::Status>),
     #[error("union IBC error: {0}")]
     UnionIbcError(union_ibc::ContractErrorKind),
     #[error("out of gas")]
     OutOfGas,
+    #[error(transparent)]
+    MaxFeeExceeded(#[from] MaxFeeExceeded),
+    #[error("error querying wasm client code")]
+    QueryWasmCode(#[source] tonic::Status),
+    #[error("wasm client code with checksum {0} has not been stored")]
+    WasmCodeNotFound(H256),
+    #[error("no proposal id found in the events of proposal submission tx {0}")]
+    ProposalIdNotFound(H256),
+}
+
+impl BroadcastTxCommitError {
+    /// Classify this error, which determines how the failed submission is retried.
+    pub fn class(&self) -> TxErrorClass {
+        match self {
+            Self::QueryLatestHeight(_)
+            | Self::BroadcastTxSync(_)
+            | Self::Inclusion(_)
+            | Self::Sign(_)
+            | Self::QueryWasmCode(_) => TxErrorClass::RetryNow,
+            Self::SimulateTx(status) if status.message().contains("account sequence mismatch") => {
+                TxErrorClass::RetryAfterResync
+            }
+            // the grant can be renewed by the granter
+            Self::SimulateTx(status) if authz::is_missing_grant_message(status.message()) => {
+                TxErrorClass::RequiresHigherGas
+            }
+            Self::SimulateTx(_) => TxErrorClass::RetryNow,
+            Self::AccountSequenceMismatch(_) => TxErrorClass::RetryAfterResync,
+            Self::OutOfGas | Self::MaxFeeExceeded(_) => TxErrorClass::RequiresHigherGas,
+            Self::Tx(error) => tx_error_class(error),
+            Self::UnionIbcError(_) | Self::WasmCodeNotFound(_) => TxErrorClass::Permanent,
+            // the tx may have been included, in which case the proposal would be submitted twice
+            // on retry
+            Self::ProposalIdNotFound(_) => TxErrorClass::Permanent,
+        }
+    }
+
+    /// Whether the submission failed because the authz grant for one of the messages does not exist
+    /// or has expired.
+    fn is_missing_authz_grant(&self) -> bool {
+        match self {
+            Self::Tx(CosmosSdkError::AuthzError(
+                AuthzError::ErrNoAuthorizationFound | AuthzError::ErrAuthorizationExpired,
+            )) => true,
+            Self::SimulateTx(status) => authz::is_missing_grant_message(status.message()),
+            _ => false,
+        }
+    }
+
+    fn into_error_object(self) -> ErrorObject<'static> {
+        self.class()
+            .into_error_object(ErrorReporter(self).to_string())
+    }
+}
+
+/// Classify an error returned by the chain for a submitted transaction.
+fn tx_error_class(error: &CosmosSdkError) -> TxErrorClass {
+    match error {
+        CosmosSdkError::SdkError(SdkError::ErrWrongSequence | SdkError::ErrInvalidSequence) => {
+            TxErrorClass::RetryAfterResync
+        }
+        CosmosSdkError::SdkError(
+            SdkError::ErrOutOfGas | SdkError::ErrInsufficientFee | SdkError::ErrInsufficientFunds,
+        ) => TxErrorClass::RequiresHigherGas,
+        CosmosSdkError::CapabilityError(_)
+        | CosmosSdkError::IbcWasmError(IbcWasmError::ErrInvalidChecksum)
+        | CosmosSdkError::ClientError(ClientError::ErrClientNotFound) => TxErrorClass::Permanent,
+        // the fee grant can be topped up or renewed by the granter
+        CosmosSdkError::FeegrantError(
+            FeegrantError::ErrFeeLimitExceeded
+            | FeegrantError::ErrFeeLimitExpired
+            | FeegrantError::ErrNoAllowance,
+        ) => TxErrorClass::RequiresHigherGas,
+        CosmosSdkError::FeegrantError(FeegrantError::ErrMessageNotAllowed) => {
+            TxErrorClass::Permanent
+        }
+        // the authz grant can be renewed by the granter
+        CosmosSdkError::AuthzError(
+            AuthzError::ErrNoAuthorizationFound | AuthzError::ErrAuthorizationExpired,
+        ) => TxErrorClass::RequiresHigherGas,
+        _ => TxErrorClass::RetryNow,
+    }
 }
 
 #[async_trait]
@@ -625,108 +1324,200 @@ impl PluginServer<ModuleCall, ModuleCallback> for Module {
     #[instrument(skip_all)]
     async fn run_pass(
         &self,
-        _: &Extensions,
+        e: &Extensions,
         msgs: Vec<Op<VoyagerMessage>>,
     ) -> RpcResult<PassResult<VoyagerMessage>> {
+        let max_batch_size = self.max_batch_size(e).await?;
+
+        let msgs = msgs
+            .into_iter()
+            .enumerate()
+            .map(|(idx, msg)| {
+                Ok((
+                    idx,
+                    match msg {
+                        Op::Data(Data::IdentifiedIbcDatagram(WithChainId {
+                            chain_id,
+                            message,
+                        })) => {
+                            assert_eq!(chain_id, self.chain_id);
+
+                            vec![IbcMessage::from_raw_datagram(message)?]
+                        }
+                        Op::Data(Data::IdentifiedIbcDatagramBatch(WithChainId {
+                            chain_id,
+                            message,
+                        })) => {
+                            assert_eq!(chain_id, self.chain_id);
+
+                            message
+                                .into_iter()
+                                .map(IbcMessage::from_raw_datagram)
+                                .collect::<Result<_, _>>()?
+                        }
+                        _ => panic!("unexpected message: {msg:?}"),
+                    },
+                ))
+            })
+            .collect::<RpcResult<Vec<_>>>()?;
+
+        // pack the datagrams from all of the messages in this pass into as few transactions as
+        // possible. the datagrams of a single message are never split across multiple batches
+        // here, but may still be split if there are more than `max_batch_size` of them (see
+        // `call`).
+        let mut batches = Vec::<(Vec<usize>, Vec<IbcMessage>)>::new();
+
+        for (idx, msgs) in msgs {
+            match batches.last_mut() {
+                Some((idxs, batch)) if batch.len() + msgs.len() <= max_batch_size => {
+                    idxs.push(idx);
+                    batch.extend(msgs);
+                }
+                _ => batches.push((vec![idx], msgs)),
+            }
+        }
+
         Ok(PassResult {
             optimize_further: vec![],
-            ready: msgs
+            ready: batches
                 .into_iter()
-                .enumerate()
-                .map(|(idx, msg)| {
-                    Ok((
-                        vec![idx],
-                        match msg {
-                            Op::Data(Data::IdentifiedIbcDatagram(WithChainId {
-                                chain_id,
-                                message,
-                            })) => {
-                                assert_eq!(chain_id, self.chain_id);
-
-                                call(PluginMessage::new(
-                                    self.plugin_name(),
-                                    ModuleCall::SubmitTransaction(vec![
-                                        IbcMessage::from_raw_datagram(message)?,
-                                    ]),
-                                ))
-                            }
-                            Op::Data(Data::IdentifiedIbcDatagramBatch(WithChainId {
-                                chain_id,
-                                message,
-                            })) => {
-                                assert_eq!(chain_id, self.chain_id);
-
-                                call(PluginMessage::new(
-                                    self.plugin_name(),
-                                    ModuleCall::SubmitTransaction(
-                                        message
-                                            .into_iter()
-                                            .map(IbcMessage::from_raw_datagram)
-                                            .collect::<Result<_, _>>()?,
-                                    ),
-                                ))
-                            }
-                            _ => panic!("unexpected message: {msg:?}"),
-                        },
-                    ))
+                .map(|(idxs, batch)| {
+                    (
+                        idxs,
+                        call(PluginMessage::new(
+                            self.plugin_name(),
+                            ModuleCall::SubmitTransaction(batch),
+                        )),
+                    )
                 })
-                .collect::<RpcResult<_>>()?,
+                .collect(),
         })
     }
 
     #[instrument(skip_all, fields(chain_id = %self.chain_id))]
     #[allow(clippy::collapsible_match)]
-    async fn call(&self, _: &Extensions, msg: ModuleCall) -> RpcResult<Op<VoyagerMessage>> {
+    async fn call(&self, e: &Extensions, msg: ModuleCall) -> RpcResult<Op<VoyagerMessage>> {
         match msg {
-            ModuleCall::SubmitTransaction(msgs) => {
+            ModuleCall::SubmitTransaction(mut msgs) => {
                 let mut out = vec![];
 
-                for msgs in msgs.chunks(5) {
+                // code uploads that require a proposal are submitted separately, since the
+                // proposal id is required to track the proposal
+                if self.wasm_code_governance.is_some() {
+                    msgs.retain(|msg| match msg {
+                        IbcMessage::IbcV1(ibc_classic_spec::Datagram::StoreWasmCode(message)) => {
+                            out.push(call(PluginMessage::new(
+                                self.plugin_name(),
+                                ModuleCall::SubmitWasmCodeProposal(message.clone()),
+                            )));
+
+                            false
+                        }
+                        _ => true,
+                    });
+                }
+
+                for msgs in msgs.chunks(self.max_batch_size(e).await?) {
                     let res = self
                         .do_send_transaction(msgs.to_vec())
                         .await
-                        .map_err(|err| match &err {
-                            BroadcastTxCommitError::Tx(tx_err) => match tx_err {
-                                CosmosSdkError::CapabilityError(capability_error) => {
-                                    ErrorObject::owned(
-                                        FATAL_JSONRPC_ERROR_CODE,
-                                        ErrorReporter(capability_error).to_string(),
-                                        None::<()>,
-                                    )
-                                }
-                                CosmosSdkError::IbcWasmError(IbcWasmError::ErrInvalidChecksum) => {
-                                    ErrorObject::owned(
-                                        FATAL_JSONRPC_ERROR_CODE,
-                                        ErrorReporter(err).to_string(),
-                                        None::<()>,
-                                    )
-                                }
-                                CosmosSdkError::ClientError(ClientError::ErrClientNotFound) => {
-                                    ErrorObject::owned(
-                                        FATAL_JSONRPC_ERROR_CODE,
-                                        ErrorReporter(err).to_string(),
-                                        None::<()>,
-                                    )
-                                }
-                                _ => ErrorObject::owned(
-                                    -1,
-                                    ErrorReporter(err).to_string(),
-                                    None::<()>,
-                                ),
-                            },
-                            BroadcastTxCommitError::UnionIbcError(_) => ErrorObject::owned(
-                                FATAL_JSONRPC_ERROR_CODE,
-                                ErrorReporter(err).to_string(),
-                                None::<()>,
-                            ),
-                            _ => ErrorObject::owned(-1, ErrorReporter(err).to_string(), None::<()>),
-                        })?;
+                        .map_err(BroadcastTxCommitError::into_error_object)?;
 
                     out.push(res);
                 }
 
                 Ok(conc(out))
             }
+            ModuleCall::SubmitWasmCodeProposal(message) => {
+                let governance = self.wasm_code_governance()?;
+
+                match self
+                    .submit_wasm_code_proposal(governance, message.clone())
+                    .await
+                {
+                    Some(Ok(None)) => Ok(noop()),
+                    Some(Ok(Some(proposal_id))) => Ok(seq(governance
+                        .vote
+                        .then(|| {
+                            call(PluginMessage::new(
+                                self.plugin_name(),
+                                ModuleCall::VoteOnProposal { proposal_id },
+                            ))
+                        })
+                        .into_iter()
+                        .chain([call(PluginMessage::new(
+                            self.plugin_name(),
+                            ModuleCall::WaitForProposal { proposal_id },
+                        ))]))),
+                    Some(Err(err)) => Err(err.into_error_object()),
+                    None => Ok(call(PluginMessage::new(
+                        self.plugin_name(),
+                        ModuleCall::SubmitWasmCodeProposal(message),
+                    ))),
+                }
+            }
+            ModuleCall::VoteOnProposal { proposal_id } => {
+                match self.vote_on_proposal(proposal_id).await {
+                    Some(Ok(())) => Ok(noop()),
+                    Some(Err(err)) => Err(err.into_error_object()),
+                    None => Ok(call(PluginMessage::new(
+                        self.plugin_name(),
+                        ModuleCall::VoteOnProposal { proposal_id },
+                    ))),
+                }
+            }
+            ModuleCall::WaitForProposal { proposal_id } => {
+                let governance = self.wasm_code_governance()?;
+
+                let proposal = self.query_proposal(proposal_id).await?;
+
+                match gov::ProposalStatus::try_from(proposal.status) {
+                    Ok(gov::ProposalStatus::Passed) => {
+                        info!(%proposal_id, "wasm code proposal passed");
+
+                        Ok(noop())
+                    }
+                    Ok(status @ (gov::ProposalStatus::Rejected | gov::ProposalStatus::Failed)) => {
+                        Err(ErrorObject::owned(
+                            FATAL_JSONRPC_ERROR_CODE,
+                            format!(
+                                "wasm code proposal {proposal_id} did not pass \
+                                ({status:?}): {}",
+                                proposal.failed_reason
+                            ),
+                            None::<()>,
+                        ))
+                    }
+                    status => {
+                        debug!(%proposal_id, ?status, "wasm code proposal is pending");
+
+                        Ok(seq([
+                            defer(now() + governance.poll_interval_seconds),
+                            call(PluginMessage::new(
+                                self.plugin_name(),
+                                ModuleCall::WaitForProposal { proposal_id },
+                            )),
+                        ]))
+                    }
+                }
+            }
+            ModuleCall::QuerySignerBalances => Ok(data(WithChainId {
+                chain_id: self.chain_id.clone(),
+                message: fetch_balances(
+                    &self.keyring,
+                    self.gas_config.gas_denom.clone(),
+                    self.grpc_url.clone(),
+                )
+                .await
+                .into_iter()
+                .map(|balance| SignerBalance {
+                    key_name: balance.key_name,
+                    address: balance.address,
+                    balance: balance.balance,
+                    denom: balance.denom,
+                })
+                .collect::<Vec<_>>(),
+            })),
         }
     }
 
@@ -743,7 +1534,7 @@ impl PluginServer<ModuleCall, ModuleCallback> for Module {
 
 fn process_msgs(
     msgs: Vec<IbcMessage>,
-    signer: &CosmosSigner,
+    signer: &str,
     ibc_host_contract_address: Bech32<H256>,
 ) -> Vec<(IbcMessage, protos::google::protobuf::Any)> {
     msgs.into_iter()
@@ -868,6 +1659,38 @@ fn process_msgs(
                             signer: signer.to_string(),
                         })
                     }
+                    ibc_classic_spec::Datagram::UpgradeClient(message) => {
+                        mk_any(&protos::ibc::core::client::v1::MsgUpgradeClient {
+                            client_id: message.client_id.to_string(),
+                            client_state: Some(
+                                protos::google::protobuf::Any::decode(&*message.client_state)
+                                    .expect("value should be encoded as an `Any`"),
+                            ),
+                            consensus_state: Some(
+                                protos::google::protobuf::Any::decode(&*message.consensus_state)
+                                    .expect("value should be encoded as an `Any`"),
+                            ),
+                            proof_upgrade_client: message.proof_upgrade_client.into(),
+                            proof_upgrade_consensus_state: message
+                                .proof_upgrade_consensus_state
+                                .into(),
+                            signer: signer.to_string(),
+                        })
+                    }
+                    ibc_classic_spec::Datagram::StoreWasmCode(message) => {
+                        mk_any(&protos::ibc::lightclients::wasm::v1::MsgStoreCode {
+                            signer: signer.to_string(),
+                            wasm_byte_code: message.wasm_byte_code.into(),
+                        })
+                    }
+                    ibc_classic_spec::Datagram::MigrateWasmClient(message) => {
+                        mk_any(&protos::ibc::lightclients::wasm::v1::MsgMigrateContract {
+                            signer: signer.to_string(),
+                            client_id: message.client_id.to_string(),
+                            checksum: message.checksum.into(),
+                            msg: message.migrate_msg.into(),
+                        })
+                    }
                     ibc_classic_spec::Datagram::CreateClient(message) => {
                         mk_any(&protos::ibc::core::client::v1::MsgCreateClient {
                             client_state: Some(
@@ -1052,9 +1875,38 @@ fn process_msgs(
                             funds: vec![],
                         })
                     }
-                    ibc_union_spec::Datagram::ChannelCloseInit(_msg_channel_close_init) => todo!(),
-                    ibc_union_spec::Datagram::ChannelCloseConfirm(_msg_channel_close_confirm) => {
-                        todo!()
+                    ibc_union_spec::Datagram::ChannelCloseInit(msg_channel_close_init) => {
+                        let channel_close_init = union_ibc_msg::msg::ExecuteMsg::ChannelCloseInit(
+                            union_ibc_msg::msg::MsgChannelCloseInit {
+                                channel_id: msg_channel_close_init.channel_id,
+                                relayer: signer.to_string(),
+                            },
+                        );
+
+                        mk_any(&protos::cosmwasm::wasm::v1::MsgExecuteContract {
+                            sender: signer.to_string(),
+                            contract: ibc_host_contract_address.to_string(),
+                            msg: serde_json::to_vec(&channel_close_init).unwrap(),
+                            funds: vec![],
+                        })
+                    }
+                    ibc_union_spec::Datagram::ChannelCloseConfirm(msg_channel_close_confirm) => {
+                        let channel_close_confirm =
+                            union_ibc_msg::msg::ExecuteMsg::ChannelCloseConfirm(
+                                union_ibc_msg::msg::MsgChannelCloseConfirm {
+                                    channel_id: msg_channel_close_confirm.channel_id,
+                                    proof_init: msg_channel_close_confirm.proof_init,
+                                    proof_height: msg_channel_close_confirm.proof_height,
+                                    relayer: signer.to_string(),
+                                },
+                            );
+
+                        mk_any(&protos::cosmwasm::wasm::v1::MsgExecuteContract {
+                            sender: signer.to_string(),
+                            contract: ibc_host_contract_address.to_string(),
+                            msg: serde_json::to_vec(&channel_close_confirm).unwrap(),
+                            funds: vec![],
+                        })
                     }
                     ibc_union_spec::Datagram::PacketRecv(msg_packet_recv) => {
                         dbg!(&msg_packet_recv);
@@ -1100,7 +1952,23 @@ fn process_msgs(
                             funds: vec![],
                         })
                     }
-                    ibc_union_spec::Datagram::PacketTimeout(_msg_packet_timeout) => todo!(),
+                    ibc_union_spec::Datagram::PacketTimeout(msg_packet_timeout) => {
+                        let packet_timeout = union_ibc_msg::msg::ExecuteMsg::PacketTimeout(
+                            union_ibc_msg::msg::MsgPacketTimeout {
+                                packet: msg_packet_timeout.packet,
+                                proof: msg_packet_timeout.proof,
+                                proof_height: msg_packet_timeout.proof_height,
+                                relayer: signer.to_string(),
+                            },
+                        );
+
+                        mk_any(&protos::cosmwasm::wasm::v1::MsgExecuteContract {
+                            sender: signer.to_string(),
+                            contract: ibc_host_contract_address.to_string(),
+                            msg: serde_json::to_vec(&packet_timeout).unwrap(),
+                            funds: vec![],
+                        })
+                    }
                     ibc_union_spec::Datagram::IntentPacketRecv(_msg_intent_packet_recv) => todo!(),
                     ibc_union_spec::Datagram::BatchSend(_msg_batch_send) => todo!(),
                     ibc_union_spec::Datagram::BatchAcks(_msg_batch_acks) => todo!(),
@@ -0,0 +1,104 @@
+use ibc_union_spec::Datagram;
+
+/// The default maximum amount of datagrams to submit in a single multicall.
+pub const DEFAULT_MAX_MULTICALL_SIZE: usize = 32;
+
+/// Aggregate the datagrams of several ops into batches that are each submitted in a single
+/// multicall, returning the indices of the ops in each batch along with its datagrams.
+///
+/// Ops are added to a batch in order until it would exceed `max_size` datagrams. The datagrams of a
+/// single op are never split across batches (since they usually depend on each other, i.e. a client
+/// update followed by the messages proven against it), so an op with more than `max_size`
+/// datagrams is submitted in a batch on its own.
+pub fn aggregate(
+    ops: impl IntoIterator<Item = (usize, Vec<Datagram>)>,
+    max_size: usize,
+) -> Vec<(Vec<usize>, Vec<Datagram>)> {
+    let mut batches = Vec::<(Vec<usize>, Vec<Datagram>)>::new();
+
+    for (idx, datagrams) in ops {
+        match batches.last_mut() {
+            Some((idxs, batch)) if batch.len() + datagrams.len() <= max_size => {
+                idxs.push(idx);
+                batch.extend(datagrams);
+            }
+            _ => batches.push((vec![idx], datagrams)),
+        }
+    }
+
+    for (_, batch) in &mut batches {
+        order(batch);
+    }
+
+    batches
+}
+
+/// Order the datagrams in a batch such that all clients are created and updated before any of the
+/// other messages are executed, as these may be proven against the updated clients. The order is
+/// otherwise preserved.
+pub fn order(datagrams: &mut [Datagram]) {
+    datagrams.sort_by_key(|datagram| match datagram {
+        Datagram::CreateClient(_) => 0,
+        Datagram::UpdateClient(_) => 1,
+        _ => 2,
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use ibc_union_spec::{MsgChannelCloseInit, MsgUpdateClient};
+
+    use super::*;
+
+    fn update(client_id: u32) -> Datagram {
+        Datagram::UpdateClient(MsgUpdateClient {
+            client_id,
+            client_message: Default::default(),
+        })
+    }
+
+    fn close(channel_id: u32) -> Datagram {
+        Datagram::ChannelCloseInit(MsgChannelCloseInit { channel_id })
+    }
+
+    #[test]
+    fn ops_are_aggregated_up_to_max_size() {
+        let batches = aggregate(
+            [
+                (0, vec![update(1), close(1)]),
+                (1, vec![close(2)]),
+                (2, vec![update(2), close(3)]),
+                (3, vec![close(4), close(5), close(6), close(7)]),
+                (4, vec![close(8)]),
+            ],
+            3,
+        );
+
+        assert_eq!(
+            batches,
+            [
+                (vec![0, 1], vec![update(1), close(1), close(2)]),
+                (vec![2], vec![update(2), close(3)]),
+                // larger than the max size, but not split
+                (vec![3], vec![close(4), close(5), close(6), close(7)]),
+                (vec![4], vec![close(8)]),
+            ]
+        );
+    }
+
+    #[test]
+    fn client_updates_are_ordered_first() {
+        let batches = aggregate(
+            [
+                (0, vec![update(1), close(1)]),
+                (1, vec![update(2), close(2)]),
+            ],
+            4,
+        );
+
+        assert_eq!(
+            batches,
+            [(vec![0, 1], vec![update(1), update(2), close(1), close(2)])]
+        );
+    }
+}
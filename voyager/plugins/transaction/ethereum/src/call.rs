@@ -5,4 +5,7 @@ use macros::model;
 #[derive(Enumorph)]
 pub enum ModuleCall {
     SubmitMulticall(Vec<ibc_union_spec::Datagram>),
+    /// Report the native balances of all signers in the keyring. Used by the voyager balance
+    /// monitor.
+    QuerySignerBalances,
 }
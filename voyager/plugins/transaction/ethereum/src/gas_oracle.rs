@@ -0,0 +1,147 @@
+use std::time::Duration;
+
+use alloy::{providers::Provider, transports::Transport};
+use tracing::{debug, warn};
+
+use crate::TxSubmitError;
+
+/// The minimum bump (in percent) that nodes require for a replacement transaction to be accepted
+/// into the mempool.
+pub const MIN_FEE_BUMP_PERCENT: u128 = 10;
+
+/// EIP-1559 fee estimation and replacement pricing for transaction submission.
+///
+/// The oracle refreshes the base fee from the node on every submission, caps the resulting fees at
+/// the configured maximums, and bumps the fees of transactions that have not been included within
+/// [`Self::stuck_tx_timeout`].
+#[derive(Debug, Clone)]
+pub struct GasOracle {
+    pub max_fee_per_gas: Option<u128>,
+    pub max_priority_fee_per_gas: Option<u128>,
+    pub fee_bump_percent: u128,
+    pub stuck_tx_timeout: Duration,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Fees {
+    pub max_fee_per_gas: u128,
+    pub max_priority_fee_per_gas: u128,
+}
+
+impl GasOracle {
+    /// Estimate the fees for a new transaction, based on the latest base fee and fee history.
+    ///
+    /// If the estimated max fee is above the configured cap, it is lowered to the cap as long as the
+    /// current base fee is still covered; otherwise the submission should be retried later.
+    pub async fn estimate<T: Transport + Clone, P: Provider<T>>(
+        &self,
+        provider: &P,
+    ) -> Result<Fees, TxSubmitError> {
+        let estimation = provider
+            .estimate_eip1559_fees(None)
+            .await
+            .map_err(alloy::contract::Error::TransportError)?;
+
+        let max_priority_fee_per_gas = self
+            .max_priority_fee_per_gas
+            .map_or(estimation.max_priority_fee_per_gas, |max| {
+                estimation.max_priority_fee_per_gas.min(max)
+            });
+
+        let max_fee_per_gas = match self.max_fee_per_gas {
+            Some(max) if estimation.max_fee_per_gas > max => {
+                // the estimated max fee is twice the current base fee plus the priority fee; it's
+                // fine to eat into that headroom but not into the base fee itself
+                let base_fee =
+                    (estimation.max_fee_per_gas - estimation.max_priority_fee_per_gas) / 2;
+
+                if max < base_fee + max_priority_fee_per_gas {
+                    warn!(
+                        %max,
+                        estimated = %estimation.max_fee_per_gas,
+                        "max fee per gas is too high"
+                    );
+
+                    return Err(TxSubmitError::GasPriceTooHigh {
+                        max,
+                        price: estimation.max_fee_per_gas,
+                    });
+                }
+
+                max
+            }
+            _ => estimation.max_fee_per_gas,
+        };
+
+        let fees = Fees {
+            max_fee_per_gas,
+            max_priority_fee_per_gas: max_priority_fee_per_gas.min(max_fee_per_gas),
+        };
+
+        debug!(?fees, "estimated fees");
+
+        Ok(fees)
+    }
+
+    /// Bump the fees of a stuck transaction such that the replacement will be accepted by the
+    /// mempool. Returns `None` if the fees cannot be bumped any further without exceeding the
+    /// configured caps.
+    pub fn bump(&self, fees: Fees) -> Option<Fees> {
+        let bump = |fee: u128| fee + (fee * self.fee_bump_percent).div_ceil(100);
+
+        let bumped = Fees {
+            max_fee_per_gas: bump(fees.max_fee_per_gas),
+            max_priority_fee_per_gas: bump(fees.max_priority_fee_per_gas),
+        };
+
+        let within_cap = |fee: u128, cap: Option<u128>| cap.map_or(true, |cap| fee <= cap);
+
+        (within_cap(bumped.max_fee_per_gas, self.max_fee_per_gas)
+            && within_cap(
+                bumped.max_priority_fee_per_gas,
+                self.max_priority_fee_per_gas,
+            ))
+        .then_some(bumped)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn oracle(max_fee_per_gas: Option<u128>) -> GasOracle {
+        GasOracle {
+            max_fee_per_gas,
+            max_priority_fee_per_gas: None,
+            fee_bump_percent: MIN_FEE_BUMP_PERCENT,
+            stuck_tx_timeout: Duration::from_secs(60),
+        }
+    }
+
+    #[test]
+    fn bump_rounds_up() {
+        let fees = Fees {
+            max_fee_per_gas: 105,
+            max_priority_fee_per_gas: 1,
+        };
+
+        assert_eq!(
+            oracle(None).bump(fees),
+            Some(Fees {
+                max_fee_per_gas: 116,
+                max_priority_fee_per_gas: 2,
+            })
+        );
+    }
+
+    #[test]
+    fn bump_respects_cap() {
+        let fees = Fees {
+            max_fee_per_gas: 100,
+            max_priority_fee_per_gas: 10,
+        };
+
+        assert_eq!(oracle(Some(109)).bump(fees), None);
+        assert!(oracle(Some(110)).bump(fees).is_some());
+    }
+}
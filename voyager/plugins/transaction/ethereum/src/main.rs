@@ -1,16 +1,15 @@
-use std::collections::VecDeque;
+use std::{collections::VecDeque, num::NonZeroUsize, time::Duration};
 
 use alloy::{
     contract::{Error, RawCallBuilder},
-    network::EthereumWallet,
-    providers::{PendingTransactionError, Provider, ProviderBuilder, RootProvider},
-    signers::local::LocalSigner,
-    sol_types::{SolEvent, SolInterface},
+    network::{EthereumWallet, TransactionBuilder},
+    providers::{PendingTransactionError, Provider, ProviderBuilder, RootProvider, WatchTxError},
+    sol_types::{SolEvent, SolEventInterface, SolInterface},
     transports::{BoxTransport, Transport, TransportError},
 };
-use bip32::secp256k1::ecdsa::{self, SigningKey};
 use chain_utils::{
     keyring::{ConcurrentKeyring, KeyringConfig, KeyringEntry},
+    signer::{AnySigner, SignerError},
     BoxDynError,
 };
 use ibc_solidity::Ibc::{self, IbcErrors};
@@ -23,26 +22,36 @@ use jsonrpsee::{
 use serde::{Deserialize, Serialize};
 use tracing::{error, info, info_span, instrument, warn, Instrument};
 use unionlabs::{
+    bytes::Bytes,
     hash::{H160, H256},
     ErrorReporter,
 };
 use voyager_message::{
     core::{ChainId, IbcSpec},
-    data::{Data, WithChainId},
+    data::{Data, IbcDatagram, SignerBalance, TxEvent, TxFee, TxMsg, TxReceipt, WithChainId},
+    into_value,
     module::{PluginInfo, PluginServer},
-    DefaultCmd, Plugin, PluginMessage, VoyagerMessage, FATAL_JSONRPC_ERROR_CODE,
+    tx_error::TxErrorClass,
+    DefaultCmd, ExtensionsExt, Plugin, PluginMessage, VoyagerClient, VoyagerMessage,
+    FATAL_JSONRPC_ERROR_CODE,
 };
-use voyager_vm::{call, defer, now, pass::PassResult, seq, Op};
+use voyager_vm::{call, data, defer, now, pass::PassResult, seq, Op};
 
 use crate::{
+    batch::DEFAULT_MAX_MULTICALL_SIZE,
     call::ModuleCall,
     callback::ModuleCallback,
+    gas_oracle::{GasOracle, MIN_FEE_BUMP_PERCENT},
     multicall::{Call3, Multicall, MulticallResult},
+    signer::EthereumSigner,
 };
 
+pub mod batch;
 pub mod call;
 pub mod callback;
 pub mod data;
+pub mod gas_oracle;
+pub mod signer;
 
 #[tokio::main(flavor = "multi_thread")]
 async fn main() {
@@ -59,10 +68,18 @@ pub struct Module {
 
     pub provider: RootProvider<BoxTransport>,
 
-    pub keyring: ConcurrentKeyring<alloy::primitives::Address, LocalSigner<SigningKey>>,
+    pub keyring: ConcurrentKeyring<alloy::primitives::Address, EthereumSigner>,
 
     pub max_gas_price: Option<u128>,
     pub legacy: bool,
+
+    pub gas_oracle: GasOracle,
+
+    pub dry_run: bool,
+
+    pub relayer_identifier: Option<Bytes>,
+
+    pub max_multicall_size: usize,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -84,6 +101,55 @@ pub struct Config {
 
     #[serde(default)]
     pub legacy: bool,
+
+    /// The maximum fee per gas to pay for EIP-1559 transactions. Fees are estimated from the
+    /// current base fee and capped at this value.
+    #[serde(default)]
+    pub max_fee_per_gas: Option<u128>,
+
+    /// The maximum priority fee (tip) per gas to pay for EIP-1559 transactions.
+    #[serde(default)]
+    pub max_priority_fee_per_gas: Option<u128>,
+
+    /// The percentage to bump the fees of a stuck transaction by when replacing it. Must be at
+    /// least 10%, as otherwise the replacement will be rejected by the mempool.
+    #[serde(default = "default_fee_bump_percent")]
+    pub fee_bump_percent: u128,
+
+    /// How long to wait (in seconds) for a transaction to be included before replacing it with
+    /// higher fees.
+    #[serde(default = "default_stuck_tx_timeout")]
+    pub stuck_tx_timeout: u64,
+
+    /// Simulate transactions with `eth_call` and report the estimated gas and the result of every
+    /// message instead of submitting them. Messages are dropped after being simulated.
+    #[serde(default)]
+    pub dry_run: bool,
+
+    /// An identifier of the operator of this relayer, appended to the calldata of all submitted
+    /// transactions such that indexers can attribute relayed packets to the operator. Trailing
+    /// calldata is ignored by the ABI decoder of the multicall contract, and as such this does not
+    /// affect execution (other than the calldata gas cost).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub relayer_identifier: Option<Bytes>,
+
+    /// The maximum amount of datagrams to aggregate into a single multicall. Datagrams that are
+    /// ready to be submitted at the same time are submitted together in as few transactions as
+    /// possible.
+    #[serde(default = "default_max_multicall_size")]
+    pub max_multicall_size: usize,
+}
+
+fn default_fee_bump_percent() -> u128 {
+    MIN_FEE_BUMP_PERCENT
+}
+
+fn default_stuck_tx_timeout() -> u64 {
+    60
+}
+
+fn default_max_multicall_size() -> usize {
+    DEFAULT_MAX_MULTICALL_SIZE
 }
 
 impl Plugin for Module {
@@ -109,30 +175,43 @@ impl Plugin for Module {
             .into());
         }
 
+        if config.fee_bump_percent < MIN_FEE_BUMP_PERCENT {
+            return Err(format!(
+                "fee_bump_percent must be at least {MIN_FEE_BUMP_PERCENT}, found {}",
+                config.fee_bump_percent
+            )
+            .into());
+        }
+
+        let keys = futures::future::try_join_all(config.keyring.keys.iter().map(|entry| async {
+            let signer = EthereumSigner::new(AnySigner::from_config(entry).await?);
+
+            Ok::<_, SignerError>(KeyringEntry {
+                name: entry.name(),
+                address: signer.address(),
+                signer,
+            })
+        }))
+        .await?;
+
         Ok(Self {
             chain_id,
             ibc_handler_address: config.ibc_handler_address,
             multicall_address: config.multicall_address,
             provider,
-            keyring: ConcurrentKeyring::new(
-                config.keyring.name,
-                config.keyring.keys.into_iter().map(|config| {
-                    let signing_key = <ecdsa::SigningKey as bip32::PrivateKey>::from_bytes(
-                        &config.value().as_slice().try_into().unwrap(),
-                    )
-                    .unwrap();
-
-                    let signer = LocalSigner::from_signing_key(signing_key);
-
-                    KeyringEntry {
-                        name: config.name(),
-                        address: signer.address(),
-                        signer,
-                    }
-                }),
-            ),
+            keyring: ConcurrentKeyring::new(config.keyring.name, keys.into_iter())
+                .with_max_in_flight(config.keyring.max_in_flight.unwrap_or(NonZeroUsize::MIN)),
             max_gas_price: config.max_gas_price,
             legacy: config.legacy,
+            gas_oracle: GasOracle {
+                max_fee_per_gas: config.max_fee_per_gas,
+                max_priority_fee_per_gas: config.max_priority_fee_per_gas,
+                fee_bump_percent: config.fee_bump_percent,
+                stuck_tx_timeout: Duration::from_secs(config.stuck_tx_timeout),
+            },
+            dry_run: config.dry_run,
+            relayer_identifier: config.relayer_identifier,
+            max_multicall_size: config.max_multicall_size.max(1),
         })
     }
 
@@ -176,6 +255,24 @@ impl Module {
     fn plugin_name(&self) -> String {
         plugin_name(&self.chain_id)
     }
+
+    /// The maximum amount of datagrams to aggregate into a single multicall. This is 1 if the
+    /// chain is declared to not support batching transactions, see
+    /// [`ChainCapabilities::batch_txs`].
+    ///
+    /// [`ChainCapabilities::batch_txs`]: voyager_message::module::ChainCapabilities::batch_txs
+    async fn max_multicall_size(&self, e: &Extensions) -> RpcResult<usize> {
+        let capabilities = e
+            .try_get::<VoyagerClient>()?
+            .chain_capabilities(self.chain_id.clone())
+            .await?;
+
+        Ok(if capabilities.batch_txs == Some(false) {
+            1
+        } else {
+            self.max_multicall_size
+        })
+    }
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -186,6 +283,8 @@ pub enum TxSubmitError {
     PendingTransactionError(#[from] PendingTransactionError),
     #[error("out of gas")]
     OutOfGas,
+    #[error("nonce too low")]
+    NonceTooLow,
     #[error("0x revert")]
     EmptyRevert(Vec<Datagram>),
     #[error("gas price is too high: max {max}, price {price}")]
@@ -194,80 +293,103 @@ pub enum TxSubmitError {
     RpcError(#[from] ErrorObjectOwned),
 }
 
+impl TxSubmitError {
+    /// Classify this error, which determines how the failed submission is retried.
+    pub fn class(&self) -> TxErrorClass {
+        match self {
+            Self::NonceTooLow => TxErrorClass::RetryAfterResync,
+            Self::OutOfGas | Self::GasPriceTooHigh { .. } => TxErrorClass::RequiresHigherGas,
+            Self::EmptyRevert(_) | Self::PendingTransactionError(_) => TxErrorClass::RetryNow,
+            Self::RpcError(_) => TxErrorClass::Permanent,
+            Self::Error(
+                Error::PendingTransactionError(PendingTransactionError::TransportError(
+                    TransportError::ErrorResp(e),
+                ))
+                | Error::TransportError(TransportError::ErrorResp(e)),
+            ) => error_message_class(&e.message),
+            Self::Error(_) => TxErrorClass::RetryNow,
+        }
+    }
+}
+
+/// Classify an error returned by the node for a submitted transaction, by its message. The messages
+/// are not standardized, these are the ones returned by geth and reth.
+fn error_message_class(message: &str) -> TxErrorClass {
+    if message.contains("nonce too low") {
+        TxErrorClass::RetryAfterResync
+    } else if [
+        "insufficient funds for gas * price + value",
+        "transaction underpriced",
+        "replacement transaction underpriced",
+        "max fee per gas less than block base fee",
+        "intrinsic gas too low",
+    ]
+    .iter()
+    .any(|pattern| message.contains(pattern))
+    {
+        TxErrorClass::RequiresHigherGas
+    } else {
+        TxErrorClass::RetryNow
+    }
+}
+
 #[async_trait]
 impl PluginServer<ModuleCall, ModuleCallback> for Module {
     async fn run_pass(
         &self,
-        _: &Extensions,
+        e: &Extensions,
         msgs: Vec<Op<VoyagerMessage>>,
     ) -> RpcResult<PassResult<VoyagerMessage>> {
+        let decode = |message: IbcDatagram| {
+            message.decode_datagram::<IbcUnion>().unwrap().map_err(|e| {
+                ErrorObject::owned(
+                    FATAL_JSONRPC_ERROR_CODE,
+                    format!("unable to deserialize datagram: {}", ErrorReporter(e)),
+                    None::<()>,
+                )
+            })
+        };
+
+        let datagrams = msgs
+            .into_iter()
+            .enumerate()
+            .map(|(idx, msg)| {
+                let datagrams = match msg {
+                    Op::Data(Data::IdentifiedIbcDatagram(WithChainId { chain_id, message })) => {
+                        assert_eq!(chain_id, self.chain_id);
+
+                        vec![decode(message)?]
+                    }
+                    Op::Data(Data::IdentifiedIbcDatagramBatch(WithChainId {
+                        chain_id,
+                        message,
+                    })) => {
+                        assert_eq!(chain_id, self.chain_id);
+
+                        message.into_iter().map(decode).collect::<Result<_, _>>()?
+                    }
+                    _ => panic!("unexpected message: {msg:?}"),
+                };
+
+                Ok((idx, datagrams))
+            })
+            .collect::<RpcResult<Vec<_>>>()?;
+
+        // all datagrams ready to be submitted are aggregated into as few multicalls as possible
         Ok(PassResult {
             optimize_further: vec![],
-            ready: msgs
+            ready: batch::aggregate(datagrams, self.max_multicall_size(e).await?)
                 .into_iter()
-                .enumerate()
-                .map(|(idx, msg)| {
-                    Ok((
-                        vec![idx],
-                        match msg {
-                            Op::Data(Data::IdentifiedIbcDatagram(WithChainId {
-                                chain_id,
-                                message,
-                            })) => {
-                                assert_eq!(chain_id, self.chain_id);
-
-                                call(PluginMessage::new(
-                                    self.plugin_name(),
-                                    ModuleCall::SubmitMulticall(vec![message
-                                        .decode_datagram::<IbcUnion>()
-                                        .unwrap()
-                                        .map_err(|e| {
-                                            ErrorObject::owned(
-                                                FATAL_JSONRPC_ERROR_CODE,
-                                                format!(
-                                                    "unable to deserialize datagram: {}",
-                                                    ErrorReporter(e)
-                                                ),
-                                                None::<()>,
-                                            )
-                                        })?]),
-                                ))
-                            }
-                            Op::Data(Data::IdentifiedIbcDatagramBatch(WithChainId {
-                                chain_id,
-                                message,
-                            })) => {
-                                assert_eq!(chain_id, self.chain_id);
-
-                                call(PluginMessage::new(
-                                    self.plugin_name(),
-                                    ModuleCall::SubmitMulticall(
-                                        message
-                                            .into_iter()
-                                            .map(|message| {
-                                                message
-                                                    .decode_datagram::<IbcUnion>()
-                                                    .unwrap()
-                                                    .map_err(|e| {
-                                                        ErrorObject::owned(
-                                                            FATAL_JSONRPC_ERROR_CODE,
-                                                            format!(
-                                                            "unable to deserialize datagram: {}",
-                                                            ErrorReporter(e)
-                                                        ),
-                                                            None::<()>,
-                                                        )
-                                                    })
-                                            })
-                                            .collect::<Result<_, _>>()?,
-                                    ),
-                                ))
-                            }
-                            _ => panic!("unexpected message: {msg:?}"),
-                        },
-                    ))
+                .map(|(idxs, datagrams)| {
+                    (
+                        idxs,
+                        call(PluginMessage::new(
+                            self.plugin_name(),
+                            ModuleCall::SubmitMulticall(datagrams),
+                        )),
+                    )
                 })
-                .collect::<RpcResult<_>>()?,
+                .collect(),
         })
     }
 
@@ -290,13 +412,12 @@ impl PluginServer<ModuleCall, ModuleCallback> for Module {
                     || PluginMessage::new(self.plugin_name(), ModuleCall::SubmitMulticall(msgs));
 
                 match res {
-                    Some(Ok(())) => Ok(Op::Noop),
-                    Some(Err(TxSubmitError::GasPriceTooHigh { .. })) => {
-                        Ok(seq([defer(now() + 6), call(rewrap_msg())]))
-                    }
-                    Some(Err(TxSubmitError::OutOfGas)) => {
-                        Ok(seq([defer(now() + 12), call(rewrap_msg())]))
-                    }
+                    Some(Ok(Some(receipt))) => Ok(data(WithChainId {
+                        chain_id: self.chain_id.clone(),
+                        message: receipt,
+                    })),
+                    Some(Ok(None)) => Ok(Op::Noop),
+                    // only the messages that reverted are retried
                     Some(Err(TxSubmitError::EmptyRevert(msgs))) => Ok(seq([
                         defer(now() + 12),
                         call(PluginMessage::new(
@@ -304,14 +425,36 @@ impl PluginServer<ModuleCall, ModuleCallback> for Module {
                             ModuleCall::SubmitMulticall(msgs),
                         )),
                     ])),
-                    Some(Err(err)) => Err(ErrorObject::owned(
-                        -1,
-                        ErrorReporter(err).to_string(),
-                        None::<()>,
-                    )),
+                    // a nonce that is too low is returned as a retryable error (after the nonce has
+                    // been resynced), such that a persistent mismatch (i.e. another relayer using
+                    // the same key) is subject to the retry limit and backoff
+                    Some(Err(err)) => Err(err
+                        .class()
+                        .into_error_object(ErrorReporter(err).to_string())),
                     None => Ok(call(rewrap_msg())),
                 }
             }
+            ModuleCall::QuerySignerBalances => {
+                let mut balances = vec![];
+
+                for (key_name, address) in self.keyring.keys() {
+                    let balance = self.provider.get_balance(*address).await.map_err(|err| {
+                        ErrorObject::owned(-1, ErrorReporter(err).to_string(), None::<()>)
+                    })?;
+
+                    balances.push(SignerBalance {
+                        key_name: key_name.to_owned(),
+                        address: address.to_string(),
+                        balance: u128::try_from(balance).unwrap_or(u128::MAX),
+                        denom: "wei".to_owned(),
+                    });
+                }
+
+                Ok(data(WithChainId {
+                    chain_id: self.chain_id.clone(),
+                    message: balances,
+                }))
+            }
         }
     }
 
@@ -329,9 +472,9 @@ impl PluginServer<ModuleCall, ModuleCallback> for Module {
 impl Module {
     async fn submit_transaction(
         &self,
-        wallet: &LocalSigner<SigningKey>,
+        wallet: &EthereumSigner,
         ibc_messages: Vec<Datagram>,
-    ) -> Result<(), TxSubmitError> {
+    ) -> Result<Option<TxReceipt>, TxSubmitError> {
         let signer = ProviderBuilder::new()
             .with_recommended_fillers()
             // .filler(<NonceFiller>::default())
@@ -370,6 +513,11 @@ impl Module {
             .map(|x| (x.0.clone(), x.0.name()))
             .collect::<Vec<_>>();
 
+        let mut tx_msgs = msg_names
+            .iter()
+            .map(|(msg, msg_name)| TxMsg::from_datagram(*msg_name, &into_value(msg)))
+            .collect::<Vec<_>>();
+
         let call = multicall.multicall(
             msgs.into_iter()
                 .map(|(_, x)| Call3 {
@@ -380,45 +528,30 @@ impl Module {
                 .collect(),
         );
 
-        info!("submitting evm tx");
-
-        match call.send().await {
-            Ok(ok) => {
-                let tx_hash = <H256>::from(*ok.tx_hash());
-                async move {
-                    let receipt = ok.get_receipt().await?;
+        let call = match &self.relayer_identifier {
+            Some(relayer_identifier) => {
+                let input = [&**call.calldata(), &**relayer_identifier].concat();
 
-                    info!(%tx_hash, "tx included");
+                call.map(|tx| tx.with_input(input))
+            }
+            None => call,
+        };
 
-                    let result = MulticallResult::decode_log_data(
-                        receipt
-                            .inner
-                            .logs()
-                            .last()
-                            .expect("multicall event should be last log")
-                            .data(),
-                        true,
-                    )
-                    .expect("unable to decode multicall result log");
+        if self.dry_run {
+            let call = call.from(wallet.address());
 
-                    info!(
-                        gas_used = %receipt.gas_used,
-                        batch.size = msg_names.len(),
-                        "submitted batched evm messages"
-                    );
-
-                    let mut retry_msgs = vec![];
+            match call.estimate_gas().await {
+                Ok(gas) => info!(%gas, batch.size = msg_names.len(), "dry run: estimated gas"),
+                Err(err) => error!(error = %ErrorReporter(err), "dry run: gas estimation failed"),
+            }
 
+            match call.call().await {
+                Ok(result) => {
                     for (idx, (result, (msg, msg_name))) in
-                        result._0.into_iter().zip(msg_names).enumerate()
+                        result.returnData.into_iter().zip(msg_names).enumerate()
                     {
                         if result.success {
-                            info_span!(
-                                "evm tx",
-                                msg = msg_name,
-                                %idx,
-                                data = %serde_json::to_string(&msg).unwrap(),
-                            );
+                            info!(msg = %msg_name, %idx, "dry run: evm message succeeded");
                         } else if let Ok(known_revert) =
                             IbcErrors::abi_decode(&result.returnData, true)
                         {
@@ -428,66 +561,295 @@ impl Module {
                                 revert = ?known_revert,
                                 well_known = true,
                                 data = %serde_json::to_string(&msg).unwrap(),
-                                "evm message failed",
+                                "dry run: evm message failed",
                             );
-                        } else if result.returnData.is_empty() {
+                        } else {
                             error!(
                                 msg = %msg_name,
                                 %idx,
                                 revert = %result.returnData,
                                 well_known = false,
                                 data = %serde_json::to_string(&msg).unwrap(),
-                                "evm message failed",
+                                "dry run: evm message failed",
                             );
+                        }
+                    }
+                }
+                Err(err) => error!(error = %ErrorReporter(err), "dry run: multicall failed"),
+            }
 
-                            retry_msgs.push((true, msg));
-                        } else {
-                            error!(
-                                msg = %msg_name,
-                                %idx,
-                                revert = %result.returnData,
-                                well_known = false,
-                                data = %serde_json::to_string(&msg).unwrap(),
-                                "evm message failed",
+            return Ok(None);
+        }
+
+        // legacy transactions are priced by the gas filler, and are never replaced
+        let mut fees = if self.legacy {
+            None
+        } else {
+            Some(self.gas_oracle.estimate(&self.provider).await?)
+        };
+
+        // held until the first tx is in the mempool, such that concurrent submissions from this
+        // signer are broadcast in nonce order
+        let mut nonce_lock = Some(self.keyring.nonces().lock(&wallet.address()).await);
+
+        let nonce = self
+            .provider
+            .get_transaction_count(wallet.address())
+            .pending()
+            .await
+            .map_err(Error::TransportError)?;
+
+        let nonce = nonce_lock
+            .as_ref()
+            .expect("lock is held until the first submission; qed;")
+            .next(nonce);
+
+        // the hashes of all txs broadcast with this nonce, i.e. the original tx and its
+        // replacements
+        let mut broadcast = Vec::<H256>::new();
+
+        let (tx_hash, receipt) = 'submit: loop {
+            let call = match fees {
+                Some(fees) => call
+                    .clone()
+                    .max_fee_per_gas(fees.max_fee_per_gas)
+                    .max_priority_fee_per_gas(fees.max_priority_fee_per_gas),
+                None => call.clone(),
+            }
+            .nonce(nonce);
+
+            info!(%nonce, ?fees, "submitting evm tx");
+
+            match call.send().await {
+                Ok(ok) => {
+                    let tx_hash = <H256>::from(*ok.tx_hash());
+
+                    broadcast.push(tx_hash);
+
+                    if let Some(mut nonce_lock) = nonce_lock.take() {
+                        nonce_lock.submitted(nonce);
+                    }
+
+                    let timeout = fees.map(|_| self.gas_oracle.stuck_tx_timeout);
+
+                    match ok
+                        .with_timeout(timeout)
+                        .get_receipt()
+                        .instrument(info_span!("evm tx", %tx_hash))
+                        .await
+                    {
+                        Ok(receipt) => break (tx_hash, receipt),
+                        Err(PendingTransactionError::TxWatcher(WatchTxError::Timeout)) => {
+                            let Some(bumped) = fees.and_then(|fees| self.gas_oracle.bump(fees))
+                            else {
+                                warn!(%tx_hash, "tx is stuck and fees cannot be bumped further");
+
+                                return Err(TxSubmitError::GasPriceTooHigh {
+                                    max: self.gas_oracle.max_fee_per_gas.unwrap_or_default(),
+                                    price: fees.map_or(0, |fees| fees.max_fee_per_gas),
+                                });
+                            };
+
+                            warn!(
+                                %tx_hash,
+                                ?bumped,
+                                "tx not included in time, replacing with higher fees"
                             );
 
-                            retry_msgs.push((false, msg));
+                            fees = Some(bumped);
                         }
+                        Err(err) => return Err(err.into()),
                     }
+                }
+                Err(
+                    Error::PendingTransactionError(PendingTransactionError::TransportError(
+                        TransportError::ErrorResp(e),
+                    ))
+                    | Error::TransportError(TransportError::ErrorResp(e)),
+                ) if e
+                    .message
+                    .contains("insufficient funds for gas * price + value") =>
+                {
+                    error!("out of gas");
+                    return Err(TxSubmitError::OutOfGas);
+                }
+                Err(
+                    Error::PendingTransactionError(PendingTransactionError::TransportError(
+                        TransportError::ErrorResp(e),
+                    ))
+                    | Error::TransportError(TransportError::ErrorResp(e)),
+                ) if error_message_class(&e.message) == TxErrorClass::RetryAfterResync => {
+                    // the tx being replaced may have been included before the replacement was
+                    // broadcast, in which case its receipt is used instead of resubmitting all of
+                    // the msgs
+                    for tx_hash in broadcast.iter().rev() {
+                        if let Some(receipt) = self
+                            .provider
+                            .get_transaction_receipt((*tx_hash).into())
+                            .await
+                            .map_err(Error::TransportError)?
+                        {
+                            info!(%tx_hash, "replaced tx was included before its replacement");
 
-                    // NOTE: An empty iterator returns false
-                    if retry_msgs
-                        .iter()
-                        .any(|(is_empty_revert, _)| *is_empty_revert)
-                    {
-                        Err(TxSubmitError::EmptyRevert(
-                            retry_msgs.into_iter().map(|(_, msg)| msg).collect(),
-                        ))
-                    } else {
-                        Ok(())
+                            break 'submit (*tx_hash, receipt);
+                        }
                     }
+
+                    match nonce_lock.take() {
+                        Some(mut nonce_lock) => nonce_lock.resync(None),
+                        None => self
+                            .keyring
+                            .nonces()
+                            .lock(&wallet.address())
+                            .await
+                            .resync(None),
+                    }
+
+                    return Err(TxSubmitError::NonceTooLow);
                 }
-                .instrument(info_span!(
-                    "evm tx",
-                    %tx_hash,
-                ))
-                .await
+                Err(err) => return Err(TxSubmitError::Error(err)),
             }
-            Err(
-                Error::PendingTransactionError(PendingTransactionError::TransportError(
-                    TransportError::ErrorResp(e),
-                ))
-                | Error::TransportError(TransportError::ErrorResp(e)),
-            ) if e
-                .message
-                .contains("insufficient funds for gas * price + value") =>
+        };
+
+        async move {
+            info!(%tx_hash, "tx included");
+
+            let result = MulticallResult::decode_log_data(
+                receipt
+                    .inner
+                    .logs()
+                    .last()
+                    .expect("multicall event should be last log")
+                    .data(),
+                true,
+            )
+            .expect("unable to decode multicall result log");
+
+            info!(
+                gas_used = %receipt.gas_used,
+                batch.size = msg_names.len(),
+                "submitted batched evm messages"
+            );
+
+            let mut retry_msgs = vec![];
+
+            for (idx, (result, (msg, msg_name))) in result._0.into_iter().zip(msg_names).enumerate()
             {
-                error!("out of gas");
-                Err(TxSubmitError::OutOfGas)
+                if result.success {
+                    info_span!(
+                        "evm tx",
+                        msg = msg_name,
+                        %idx,
+                        data = %serde_json::to_string(&msg).unwrap(),
+                    );
+                } else if let Ok(known_revert) = IbcErrors::abi_decode(&result.returnData, true) {
+                    error!(
+                        msg = %msg_name,
+                        %idx,
+                        revert = ?known_revert,
+                        well_known = true,
+                        data = %serde_json::to_string(&msg).unwrap(),
+                        "evm message failed",
+                    );
+
+                    tx_msgs[idx].error = Some(format!("reverted with {known_revert:?}"));
+                } else if result.returnData.is_empty() {
+                    error!(
+                        msg = %msg_name,
+                        %idx,
+                        revert = %result.returnData,
+                        well_known = false,
+                        data = %serde_json::to_string(&msg).unwrap(),
+                        "evm message failed",
+                    );
+
+                    retry_msgs.push((true, msg));
+                } else {
+                    error!(
+                        msg = %msg_name,
+                        %idx,
+                        revert = %result.returnData,
+                        well_known = false,
+                        data = %serde_json::to_string(&msg).unwrap(),
+                        "evm message failed",
+                    );
+
+                    tx_msgs[idx].error = Some(format!("reverted with {}", result.returnData));
+
+                    retry_msgs.push((false, msg));
+                }
+            }
+
+            // NOTE: An empty iterator returns false
+            if retry_msgs
+                .iter()
+                .any(|(is_empty_revert, _)| *is_empty_revert)
+            {
+                Err(TxSubmitError::EmptyRevert(
+                    retry_msgs.into_iter().map(|(_, msg)| msg).collect(),
+                ))
+            } else {
+                Ok(Some(TxReceipt {
+                    tx_hash,
+                    height: receipt.block_number.unwrap_or_default(),
+                    gas_used: receipt.gas_used.try_into().unwrap_or(u64::MAX),
+                    fee: Some(TxFee {
+                        amount: u128::from(receipt.gas_used)
+                            .saturating_mul(receipt.effective_gas_price),
+                        denom: "wei".to_owned(),
+                    }),
+                    msgs: tx_msgs,
+                    events: receipt
+                        .inner
+                        .logs()
+                        .iter()
+                        .filter(|log| H160::from(log.address()) == self.ibc_handler_address)
+                        .filter_map(|log| Ibc::IbcEvents::decode_log(&log.inner, true).ok())
+                        .map(|log| tx_event(log.data))
+                        .collect(),
+                }))
             }
-            Err(err) => Err(TxSubmitError::Error(err)),
         }
+        .instrument(info_span!("evm tx", %tx_hash))
+        .await
+    }
+}
+
+fn tx_event(event: Ibc::IbcEvents) -> TxEvent {
+    macro_rules! tx_event {
+        ($($variant:ident),* $(,)?) => {
+            match event {
+                $(
+                    Ibc::IbcEvents::$variant(event) => TxEvent {
+                        name: stringify!($variant).to_owned(),
+                        attributes: into_value(event),
+                    },
+                )*
+            }
+        };
     }
+
+    tx_event!(
+        ClientRegistered,
+        ClientCreated,
+        ClientUpdated,
+        ConnectionOpenInit,
+        ConnectionOpenTry,
+        ConnectionOpenAck,
+        ConnectionOpenConfirm,
+        ChannelOpenInit,
+        ChannelOpenTry,
+        ChannelOpenAck,
+        ChannelOpenConfirm,
+        ChannelCloseInit,
+        ChannelCloseConfirm,
+        SendPacket,
+        RecvPacket,
+        RecvIntentPacket,
+        WriteAcknowledgement,
+        AcknowledgePacket,
+        TimeoutPacket,
+    )
 }
 
 #[allow(clippy::type_complexity)]
@@ -617,6 +979,26 @@ fn process_msgs<T: Transport + Clone, P: Provider<T>>(
                         })
                         .clear_decoder(),
                 ),
+                Datagram::ChannelCloseInit(data) => (
+                    msg,
+                    ibc_handler
+                        .channelCloseInit(ibc_solidity::MsgChannelCloseInit {
+                            channel_id: data.channel_id,
+                            relayer: relayer.into(),
+                        })
+                        .clear_decoder(),
+                ),
+                Datagram::ChannelCloseConfirm(data) => (
+                    msg,
+                    ibc_handler
+                        .channelCloseConfirm(ibc_solidity::MsgChannelCloseConfirm {
+                            channel_id: data.channel_id,
+                            proof_init: data.proof_init.into(),
+                            proof_height: data.proof_height,
+                            relayer: relayer.into(),
+                        })
+                        .clear_decoder(),
+                ),
                 Datagram::PacketRecv(data) => (
                     msg,
                     ibc_handler
@@ -641,17 +1023,17 @@ fn process_msgs<T: Transport + Clone, P: Provider<T>>(
                 //         })
                 //         .clear_decoder(),
                 // ),
-                // Datagram::TimeoutPacket(data) => (
-                //     msg,
-                //     ibc_handler
-                //         .timeoutPacket(MsgPacketTimeout {
-                //             packet: convert_packet(data.packet)?,
-                //             proof: data.proof_unreceived.into(),
-                //             proofHeight: data.proof_height.height(),
-                //             relayer: relayer.into(),
-                //         })
-                //         .clear_decoder(),
-                // ),
+                Datagram::PacketTimeout(data) => (
+                    msg,
+                    ibc_handler
+                        .timeoutPacket(ibc_solidity::MsgPacketTimeout {
+                            packet: data.packet,
+                            proof: data.proof.into(),
+                            proof_height: data.proof_height,
+                            relayer: relayer.into(),
+                        })
+                        .clear_decoder(),
+                ),
                 _ => todo!(),
             })
         })
@@ -715,6 +1097,28 @@ mod tests {
         dbg!(result);
     }
 
+    #[test]
+    fn error_message_classes() {
+        assert_eq!(
+            error_message_class("nonce too low: next nonce 5, tx nonce 4"),
+            TxErrorClass::RetryAfterResync
+        );
+        assert_eq!(
+            error_message_class("replacement transaction underpriced"),
+            TxErrorClass::RequiresHigherGas
+        );
+        assert_eq!(
+            error_message_class(
+                "insufficient funds for gas * price + value: balance 0, tx cost 100"
+            ),
+            TxErrorClass::RequiresHigherGas
+        );
+        assert_eq!(
+            error_message_class("header not found"),
+            TxErrorClass::RetryNow
+        );
+    }
+
     #[test]
     fn create_client_decode() {
         let bz = hex::decode("0x000000000000000000000000000000000000000000000000000000000000004000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000008636f6d6574626c73000000000000000000000000000000000000000000000000").unwrap();
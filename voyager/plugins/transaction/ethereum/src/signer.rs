@@ -0,0 +1,52 @@
+use alloy::{
+    consensus::SignableTransaction,
+    network::TxSigner,
+    primitives::{Address, PrimitiveSignature},
+    signers::utils::public_key_to_address,
+};
+use chain_utils::signer::{AnySigner, Signer};
+use jsonrpsee::core::async_trait;
+use unionlabs::hash::H256;
+
+/// A [`TxSigner`] for any of the signing backends supported by the keyring.
+#[derive(Debug, Clone)]
+pub struct EthereumSigner {
+    address: Address,
+    signer: AnySigner,
+}
+
+impl EthereumSigner {
+    pub fn new(signer: AnySigner) -> Self {
+        Self {
+            address: public_key_to_address(signer.verifying_key()),
+            signer,
+        }
+    }
+
+    pub fn address(&self) -> Address {
+        self.address
+    }
+}
+
+#[async_trait]
+impl TxSigner<PrimitiveSignature> for EthereumSigner {
+    fn address(&self) -> Address {
+        self.address
+    }
+
+    async fn sign_transaction(
+        &self,
+        tx: &mut dyn SignableTransaction<PrimitiveSignature>,
+    ) -> alloy::signers::Result<PrimitiveSignature> {
+        let signature = self
+            .signer
+            .sign_prehash(H256::new(tx.signature_hash().0))
+            .await
+            .map_err(alloy::signers::Error::other)?;
+
+        Ok(PrimitiveSignature::from_signature_and_parity(
+            signature.signature,
+            signature.recovery_id.is_y_odd(),
+        ))
+    }
+}
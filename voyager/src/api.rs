@@ -12,16 +12,30 @@ use futures::{
 use prometheus::TextEncoder;
 use reqwest::StatusCode;
 use tracing::error;
-use voyager_message::VoyagerMessage;
+use voyager_message::{op_schema, VoyagerMessage};
 use voyager_vm::Op;
 
-pub fn run(laddr: &SocketAddr) -> UnboundedReceiver<Op<VoyagerMessage>> {
+use crate::health::{Health, HealthReport};
+
+pub fn run(laddr: &SocketAddr, health: Health) -> UnboundedReceiver<Op<VoyagerMessage>> {
     let (queue_tx, queue_rx) = unbounded::<Op<VoyagerMessage>>();
 
     let app = axum::Router::new()
         .route("/enqueue", post(enqueue))
         .route("/health", get(|| async move { StatusCode::OK }))
+        .route(
+            "/healthz",
+            get({
+                let health = health.clone();
+                || async move { report(&health, |report| report.healthy) }
+            }),
+        )
+        .route(
+            "/readyz",
+            get(|| async move { report(&health, |report| report.ready) }),
+        )
         .route("/metrics", get(metrics))
+        .route("/schema", get(|| async move { Json(op_schema()) }))
         // .route(
         //     "/signer/balances",
         //     get({
@@ -46,6 +60,18 @@ async fn enqueue(
     StatusCode::OK
 }
 
+fn report(health: &Health, ok: fn(&HealthReport) -> bool) -> (StatusCode, Json<HealthReport>) {
+    let report = health.report();
+
+    let status = if ok(&report) {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+
+    (status, Json(report))
+}
+
 async fn metrics() -> Result<String, StatusCode> {
     TextEncoder::new()
         .encode_to_string(&prometheus::gather())
@@ -0,0 +1,224 @@
+//! Monitoring of the balances of the signers of transaction plugins.
+//!
+//! Transaction plugins that support balance monitoring report the balance of every signer in their
+//! keyring (in the denom that fees are paid in) when called with [`QUERY_SIGNER_BALANCES`]. The
+//! monitor periodically queries the configured plugins and exposes the balances as metrics.
+//!
+//! If a minimum balance is configured for a plugin, signers below it are reported, and once every
+//! signer of the plugin is below it the submissions of the plugin can optionally be held (see
+//! [`Pauses::hold_submissions`]) until at least one of them is topped up again. This holds the
+//! transactions in the queue instead of failing every submission while there are no funds to pay
+//! the fees with. Only the calls to the transaction plugin are held; the events on the chain are
+//! still fetched, and packets from the chain are still relayed to its counterparties.
+//!
+//! [`Pauses::hold_submissions`]: voyager_message::pause::Pauses::hold_submissions
+//!
+
+use std::{sync::LazyLock, time::Duration};
+
+use anyhow::{anyhow, Context as _};
+use prometheus::{register_gauge_vec, register_int_gauge_vec, GaugeVec, IntGaugeVec};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tracing::{debug, info, warn};
+use voyager_message::{
+    context::Context,
+    data::{Data, SignerBalance, WithChainId},
+    module::PluginClient,
+};
+use voyager_vm::Op;
+
+/// The message that transaction plugins handle in `call` to report the balances of their signers,
+/// returning the balances as [`Data::SignerBalances`].
+pub const QUERY_SIGNER_BALANCES: &str = "query_signer_balances";
+
+pub static SIGNER_BALANCE: LazyLock<GaugeVec> = LazyLock::new(|| {
+    register_gauge_vec!(
+        "voyager_signer_balance",
+        "The balance of a signer of a transaction plugin, in the smallest unit of the denom.",
+        &["chain_id", "key_name", "address", "denom"],
+    )
+    .unwrap()
+});
+
+pub static SIGNERS_BELOW_MIN_BALANCE: LazyLock<IntGaugeVec> = LazyLock::new(|| {
+    register_int_gauge_vec!(
+        "voyager_signers_below_min_balance",
+        "The amount of signers of a transaction plugin with a balance below the configured \
+        minimum.",
+        &["chain_id"],
+    )
+    .unwrap()
+});
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct BalanceMonitorConfig {
+    /// How often the balances are queried.
+    #[serde(default = "default_interval_seconds")]
+    pub interval_seconds: u64,
+    pub plugins: Vec<MonitoredPlugin>,
+}
+
+#[must_use]
+#[inline]
+pub const fn default_interval_seconds() -> u64 {
+    60
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct MonitoredPlugin {
+    /// The name of the transaction plugin, i.e.
+    /// `voyager-transaction-plugin-cosmos-sdk/union-testnet-9`.
+    pub plugin: String,
+    /// The balance below which signers are reported, in the smallest unit of the fee denom.
+    #[serde(
+        default,
+        with = "::serde_utils::string_opt",
+        skip_serializing_if = "Option::is_none"
+    )]
+    #[schemars(with = "Option<String>")]
+    pub min_balance: Option<u128>,
+    /// Hold the submissions of the plugin once all of its signers are below
+    /// [`Self::min_balance`], and release them once any of them is above it again.
+    #[serde(default)]
+    pub pause_below_min_balance: bool,
+}
+
+#[derive(Debug)]
+pub struct BalanceMonitor {
+    config: BalanceMonitorConfig,
+}
+
+impl BalanceMonitor {
+    #[must_use]
+    pub fn new(config: BalanceMonitorConfig) -> Self {
+        Self { config }
+    }
+
+    #[must_use]
+    pub fn interval(&self) -> Duration {
+        Duration::from_secs(self.config.interval_seconds)
+    }
+
+    /// Check the balances of the signers of all of the monitored plugins. Errors are logged and do
+    /// not prevent the remaining plugins from being checked.
+    pub async fn check_all(&mut self, ctx: &Context) {
+        for plugin in self.config.plugins.clone() {
+            if let Err(error) = self.check(ctx, &plugin).await {
+                warn!(
+                    plugin = %plugin.plugin,
+                    "error checking signer balances: {error:#}"
+                );
+            }
+        }
+    }
+
+    async fn check(&mut self, ctx: &Context, plugin: &MonitoredPlugin) -> anyhow::Result<()> {
+        let op = ctx
+            .plugin(&plugin.plugin)?
+            .call(json!({ "@type": QUERY_SIGNER_BALANCES }))
+            .await
+            .map_err(|err| anyhow!("{err}"))
+            .context("error querying signer balances")?;
+
+        let Op::Data(Data::SignerBalances(WithChainId {
+            chain_id,
+            message: balances,
+        })) = op
+        else {
+            return Err(anyhow!(
+                "unexpected response: {}",
+                serde_json::to_string(&op).unwrap_or_default()
+            ));
+        };
+
+        for balance in &balances {
+            #[allow(clippy::cast_precision_loss)]
+            SIGNER_BALANCE
+                .with_label_values(&[
+                    chain_id.as_str(),
+                    &balance.key_name,
+                    &balance.address,
+                    &balance.denom,
+                ])
+                .set(balance.balance as f64);
+        }
+
+        let Some(min_balance) = plugin.min_balance else {
+            return Ok(());
+        };
+
+        let below = below_min_balance(&balances, min_balance);
+
+        SIGNERS_BELOW_MIN_BALANCE
+            .with_label_values(&[chain_id.as_str()])
+            .set(i64::try_from(below.len()).unwrap_or(i64::MAX));
+
+        for balance in &below {
+            warn!(
+                %chain_id,
+                key_name = %balance.key_name,
+                address = %balance.address,
+                balance = %balance.balance,
+                %min_balance,
+                denom = %balance.denom,
+                "signer balance is below the minimum"
+            );
+        }
+
+        if !plugin.pause_below_min_balance {
+            return Ok(());
+        }
+
+        // held submissions are tracked separately from the pauses set by operators, so this never
+        // resumes anything that was paused by an operator
+        if !balances.is_empty() && below.len() == balances.len() {
+            if ctx.pauses.hold_submissions(plugin.plugin.clone()) {
+                info!(
+                    %chain_id,
+                    "all signers are below the minimum balance, holding submissions"
+                );
+            }
+        } else if ctx.pauses.release_submissions(&plugin.plugin) {
+            info!(%chain_id, "signers have been topped up, releasing submissions");
+        } else {
+            debug!(%chain_id, "signer balances are sufficient");
+        }
+
+        Ok(())
+    }
+}
+
+/// The signers with a balance below `min_balance`.
+fn below_min_balance(balances: &[SignerBalance], min_balance: u128) -> Vec<&SignerBalance> {
+    balances
+        .iter()
+        .filter(|balance| balance.balance < min_balance)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn balance(key_name: &str, balance: u128) -> SignerBalance {
+        SignerBalance {
+            key_name: key_name.to_owned(),
+            address: key_name.to_owned(),
+            balance,
+            denom: "muno".to_owned(),
+        }
+    }
+
+    #[test]
+    fn below_min_balance_is_exclusive() {
+        let balances = [balance("a", 99), balance("b", 100), balance("c", 101)];
+
+        assert_eq!(below_min_balance(&balances, 100), [&balances[0]]);
+        assert!(below_min_balance(&balances, 0).is_empty());
+        assert_eq!(below_min_balance(&balances, 1000).len(), 3);
+    }
+}
@@ -0,0 +1,129 @@
+//! Persistence of the per-chain event source checkpoints.
+//!
+//! Event source plugins queue a [`RecordCheckpoint`] after the events of every
+//! block, which advances the checkpoint of the chain once all of the events in
+//! that block and in all blocks before it have been processed. These are
+//! periodically flushed to disk, and on startup are used to backfill any
+//! blocks that were produced (or not fully processed) while voyager was not
+//! running.
+//!
+//! [`RecordCheckpoint`]: voyager_message::call::RecordCheckpoint
+
+use std::{collections::HashMap, path::Path};
+
+use anyhow::Context as _;
+use tracing::info;
+use unionlabs::ibc::core::client::height::Height;
+use voyager_message::{
+    call::{FetchBlockRange, FetchBlocks},
+    core::ChainId,
+    VoyagerMessage,
+};
+use voyager_vm::{call, conc, seq, Op};
+
+/// The amount of blocks that are backfilled at once. The chunks are fetched one after another,
+/// such that resuming from a checkpoint far behind the chain doesn't enqueue all of the missed
+/// blocks at once.
+pub const BACKFILL_CHUNK_SIZE: u64 = 100;
+
+/// Read the checkpoints from `path`. A missing file is treated as no checkpoints.
+pub fn load(path: &Path) -> anyhow::Result<HashMap<ChainId, Height>> {
+    match std::fs::read_to_string(path) {
+        Ok(s) => serde_json::from_str(&s)
+            .with_context(|| format!("error parsing checkpoints at {}", path.display())),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(HashMap::new()),
+        Err(err) => {
+            Err(err).with_context(|| format!("error reading checkpoints at {}", path.display()))
+        }
+    }
+}
+
+/// Write the checkpoints to `path`. The file is replaced atomically, such that a crash while
+/// writing never leaves a corrupted checkpoint file behind.
+pub fn save(path: &Path, checkpoints: &HashMap<ChainId, Height>) -> anyhow::Result<()> {
+    let tmp = path.with_extension("tmp");
+
+    std::fs::write(&tmp, serde_json::to_vec_pretty(checkpoints)?)
+        .with_context(|| format!("error writing checkpoints to {}", tmp.display()))?;
+
+    std::fs::rename(&tmp, path)
+        .with_context(|| format!("error writing checkpoints to {}", path.display()))
+}
+
+/// Build the op to resume fetching blocks on `chain_id`, given the last checkpointed height and
+/// the current latest height of the chain. Any blocks between the two are backfilled via
+/// [`FetchBlockRange`]s of at most [`BACKFILL_CHUNK_SIZE`] blocks, concurrently with the live
+/// [`FetchBlocks`] stream.
+pub fn resume(chain_id: ChainId, checkpoint: Height, latest_height: Height) -> Op<VoyagerMessage> {
+    if latest_height > checkpoint {
+        info!(
+            %chain_id,
+            %checkpoint,
+            %latest_height,
+            "resuming from checkpoint, backfilling missed blocks"
+        );
+
+        conc([
+            backfill(&chain_id, checkpoint, latest_height),
+            call(FetchBlocks {
+                chain_id,
+                start_height: latest_height.increment(),
+            }),
+        ])
+    } else {
+        info!(%chain_id, %checkpoint, "resuming from checkpoint");
+
+        call(FetchBlocks {
+            chain_id,
+            start_height: checkpoint.increment(),
+        })
+    }
+}
+
+/// Fetch all blocks after `checkpoint` up to and including `latest_height`, in chunks of
+/// [`BACKFILL_CHUNK_SIZE`] blocks.
+fn backfill(chain_id: &ChainId, checkpoint: Height, latest_height: Height) -> Op<VoyagerMessage> {
+    let chunks = std::iter::successors(Some(checkpoint.height() + 1), |from| {
+        from.checked_add(BACKFILL_CHUNK_SIZE)
+    })
+    .take_while(|from| *from <= latest_height.height());
+
+    seq(chunks.map(|from| {
+        call(FetchBlockRange {
+            chain_id: chain_id.clone(),
+            from_height: Height::new_with_revision(checkpoint.revision(), from),
+            to_height: Height::new_with_revision(
+                checkpoint.revision(),
+                (from + BACKFILL_CHUNK_SIZE - 1).min(latest_height.height()),
+            ),
+        })
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn backfill_is_chunked() {
+        let chain_id = ChainId::new("chain");
+
+        let range = |from, to| {
+            call(FetchBlockRange {
+                chain_id: chain_id.clone(),
+                from_height: Height::new(from),
+                to_height: Height::new(to),
+            })
+        };
+
+        assert_eq!(
+            backfill(&chain_id, Height::new(10), Height::new(250)),
+            seq([range(11, 110), range(111, 210), range(211, 250)])
+        );
+
+        assert_eq!(
+            backfill(&chain_id, Height::new(10), Height::new(11)),
+            seq([range(11, 11)])
+        );
+    }
+}
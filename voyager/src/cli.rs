@@ -1,7 +1,15 @@
-use std::{ffi::OsString, str::FromStr};
+use std::{ffi::OsString, num::NonZeroU64, path::PathBuf, str::FromStr};
 
 use clap::{self, Parser, Subcommand};
-use unionlabs::{self, bounded::BoundedI64, ibc::core::client::height::Height, result_unwrap};
+use unionlabs::{
+    self,
+    bounded::BoundedI64,
+    bytes::Bytes,
+    hash::H256,
+    ibc::core::client::height::Height,
+    id::{ChannelId, ClientId, PortId},
+    option_unwrap, result_unwrap,
+};
 use voyager_message::{
     core::{ChainId, ClientType, IbcInterface, IbcSpecId, QueryHeight},
     module::{ClientModuleInfo, ConsensusModuleInfo, ProofModuleInfo, StateModuleInfo},
@@ -72,6 +80,24 @@ pub enum Command {
         #[arg(long, short = 'e', default_value_t = false)]
         enqueue: bool,
     },
+    /// Relay all pending packets (and acknowledgements) sent on a channel.
+    ///
+    /// This queries a running voyager instance for all packets sent on the
+    /// channel that have not yet been received or acknowledged on the
+    /// counterparty, and constructs an op to relay them.
+    ClearPackets {
+        #[arg(value_parser(|s: &str| ok(ChainId::new(s.to_owned()))))]
+        on: ChainId,
+        port_id: PortId,
+        #[arg(value_parser(|s: &str| ChannelId::from_str_prefixed(s)))]
+        channel_id: ChannelId,
+        /// Only check packets with a sequence greater than or equal to this.
+        #[arg(long, default_value_t = option_unwrap!(NonZeroU64::new(1)))]
+        from_sequence: NonZeroU64,
+        /// Automatically enqueue the op.
+        #[arg(long, short = 'e', default_value_t = false)]
+        enqueue: bool,
+    },
     /// Run Voyager.
     Start,
     /// Query and interact with the queue.
@@ -86,6 +112,9 @@ pub enum Command {
     Rpc(RpcCmd),
     #[command(subcommand)]
     Msg(MsgCmd),
+    /// Manage encrypted keystore files for keyring entries.
+    #[command(subcommand)]
+    Keystore(KeystoreCmd),
     // Query {
     //     #[arg(value_parser(|s: &str| Ok::<_, BoxDynError>(ChainId::new(s.to_owned()))))]
     //     on: ChainId,
@@ -154,6 +183,9 @@ pub enum QueueCmd {
         #[arg(long, short = 'e')]
         requeue: bool,
     },
+    /// Replay a log recorded with `voyager.record_path` against the configured plugins and
+    /// modules, printing the ops whose outcome differs from the recorded outcome.
+    Replay { log: PathBuf },
 }
 
 #[derive(Debug, Subcommand)]
@@ -173,6 +205,18 @@ pub enum PluginCmd {
     },
 }
 
+#[derive(Debug, Subcommand)]
+pub enum KeystoreCmd {
+    /// Encrypt a 0x-prefixed hex encoded private key, read from stdin, into a keystore file.
+    Encrypt {
+        /// The path to write the keystore file to.
+        path: PathBuf,
+        /// The passphrase to encrypt the keystore with.
+        #[arg(long, env = "VOYAGER_KEYSTORE_PASSPHRASE", hide_env_values = true)]
+        passphrase: String,
+    },
+}
+
 #[derive(Debug, Subcommand)]
 pub enum ModuleCmd {
     State(StateModuleInfo),
@@ -226,6 +270,8 @@ pub enum MsgCmd {
         client_type: ClientType,
         #[arg(long, default_value_t = QueryHeight::Finalized)]
         height: QueryHeight,
+        /// The metadata required to encode the client state. If not provided, it will be
+        /// discovered from the existing clients on the chain, if required.
         #[arg(
             long,
             // the autoref value parser selector chooses From<String> before FromStr, but Value's From<String> impl always returns Value::String(..), whereas FromStr actually parses the json contained within the string
@@ -238,6 +284,169 @@ pub enum MsgCmd {
         #[arg(long, short = 'e', default_value_t = false)]
         enqueue: bool,
     },
+    /// Open an IBC union connection between two existing clients, resuming the handshake of an
+    /// unfinished connection between the clients if there is one.
+    InitConnection {
+        #[arg(long, value_parser(|s: &str| ok(ChainId::new(s.to_owned()))))]
+        on: ChainId,
+        #[arg(long)]
+        client_id: u32,
+        #[arg(long, value_parser(|s: &str| ok(ChainId::new(s.to_owned()))))]
+        counterparty: ChainId,
+        #[arg(long)]
+        counterparty_client_id: u32,
+
+        /// Automatically enqueue the op.
+        #[arg(long, short = 'e', default_value_t = false)]
+        enqueue: bool,
+    },
+    /// Open an IBC union channel on an existing connection, resuming the handshake of an unfinished
+    /// channel to the same counterparty port if there is one.
+    InitChannel {
+        #[arg(long, value_parser(|s: &str| ok(ChainId::new(s.to_owned()))))]
+        on: ChainId,
+        #[arg(long)]
+        connection_id: u32,
+        #[arg(long)]
+        port_id: Bytes,
+        #[arg(long)]
+        counterparty_port_id: Bytes,
+        #[arg(long)]
+        version: String,
+
+        /// Automatically enqueue the op.
+        #[arg(long, short = 'e', default_value_t = false)]
+        enqueue: bool,
+    },
+    /// Resume the handshake of an existing IBC union connection or channel, for example if a
+    /// previous relayer instance stopped between two steps of the handshake. Only the remaining
+    /// steps of the handshake are relayed.
+    ResumeHandshake {
+        #[arg(long, value_parser(|s: &str| ok(ChainId::new(s.to_owned()))))]
+        on: ChainId,
+        #[arg(
+            long,
+            conflicts_with = "channel_id",
+            required_unless_present = "channel_id"
+        )]
+        connection_id: Option<u32>,
+        #[arg(long)]
+        channel_id: Option<u32>,
+        /// The port of the channel. This is only required if the channel has not yet been opened
+        /// on the counterparty.
+        #[arg(long, requires = "channel_id")]
+        port_id: Option<Bytes>,
+
+        /// Automatically enqueue the op.
+        #[arg(long, short = 'e', default_value_t = false)]
+        enqueue: bool,
+    },
+    /// Store the code of an 08-wasm light client. The transaction plugin for the chain must either
+    /// be configured with the key of the authority of the 08-wasm module, or with
+    /// `wasm_code_governance` to store the code through a governance proposal.
+    StoreWasmCode {
+        #[arg(long, value_parser(|s: &str| ok(ChainId::new(s.to_owned()))))]
+        on: ChainId,
+        /// Path to the (optionally gzip compressed) wasm byte code of the light client.
+        #[arg(long)]
+        wasm: PathBuf,
+
+        /// Automatically enqueue the op.
+        #[arg(long, short = 'e', default_value_t = false)]
+        enqueue: bool,
+    },
+    /// Migrate an existing 08-wasm client to code that has already been stored with
+    /// `store-wasm-code`. The transaction plugin for the chain must be configured with the key of
+    /// the authority of the 08-wasm module.
+    MigrateWasmClient {
+        #[arg(long, value_parser(|s: &str| ok(ChainId::new(s.to_owned()))))]
+        on: ChainId,
+        #[arg(long)]
+        client_id: ClientId,
+        /// The sha256 checksum of the stored code to migrate the client to.
+        #[arg(long)]
+        checksum: H256,
+        /// The message passed to the `migrate` entrypoint of the contract.
+        #[arg(long, value_parser(serde_json::Value::from_str), default_value = "{}")]
+        migrate_msg: serde_json::Value,
+
+        /// Automatically enqueue the op.
+        #[arg(long, short = 'e', default_value_t = false)]
+        enqueue: bool,
+    },
+    /// Upgrade an IBC classic client after the chain it tracks has halted for a planned upgrade.
+    /// The op waits for the client to be updated to the upgrade height before submitting the
+    /// upgrade.
+    UpgradeClient {
+        #[arg(long, value_parser(|s: &str| ok(ChainId::new(s.to_owned()))))]
+        on: ChainId,
+        #[arg(long)]
+        client_id: ClientId,
+        /// The height of the upgrade plan on the counterparty chain.
+        #[arg(long)]
+        upgrade_height: u64,
+
+        /// Automatically enqueue the op.
+        #[arg(long, short = 'e', default_value_t = false)]
+        enqueue: bool,
+    },
+    /// Generate the canonical JSON of the op for a common operation. The generated op is validated
+    /// against the message schema before it is printed.
+    #[command(subcommand)]
+    Generate(GenerateCmd),
+    /// Print the JSON Schema of the ops accepted by the queue, as enqueued with `queue enqueue` or
+    /// via the `/enqueue` endpoint of the REST API.
+    Schema,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum GenerateCmd {
+    /// Create a client from already encoded client and consensus states.
+    CreateClient {
+        #[arg(long, value_parser(|s: &str| ok(ChainId::new(s.to_owned()))))]
+        on: ChainId,
+        #[arg(long, value_parser(|s: &str| ok(IbcSpecId::new(s.to_owned()))))]
+        ibc_spec_id: IbcSpecId,
+        #[arg(long, value_parser(|s: &str| ok(ClientType::new(s.to_owned()))))]
+        client_type: ClientType,
+        #[arg(long)]
+        client_state: Bytes,
+        #[arg(long)]
+        consensus_state: Bytes,
+    },
+    /// Update a client from one height of the tracked chain to another.
+    UpdateClient {
+        #[arg(long, value_parser(|s: &str| ok(ChainId::new(s.to_owned()))))]
+        on: ChainId,
+        #[arg(long)]
+        client_id: RawClientId,
+        #[arg(long, value_parser(|s: &str| ok(IbcSpecId::new(s.to_owned()))))]
+        ibc_spec_id: IbcSpecId,
+        #[arg(long, value_parser(|s: &str| ok(ChainId::new(s.to_owned()))))]
+        tracking: ChainId,
+        #[arg(long)]
+        update_from: Height,
+        #[arg(long)]
+        update_to: Height,
+    },
+    /// Relay packets sent on a channel, i.e. a transfer that was missed by the event source.
+    RelayPackets {
+        #[arg(long, value_parser(|s: &str| ok(ChainId::new(s.to_owned()))))]
+        on: ChainId,
+        #[arg(long)]
+        port_id: PortId,
+        #[arg(long, value_parser(|s: &str| ChannelId::from_str_prefixed(s)))]
+        channel_id: ChannelId,
+        #[arg(long, value_delimiter = ',', required = true)]
+        sequences: Vec<NonZeroU64>,
+    },
+    /// Start indexing a chain from a height.
+    FetchBlocks {
+        #[arg(long, value_parser(|s: &str| ok(ChainId::new(s.to_owned()))))]
+        on: ChainId,
+        #[arg(long)]
+        start_height: Height,
+    },
 }
 
 #[allow(
@@ -0,0 +1,346 @@
+//! Monitoring of client expiry, and scheduling of standalone client updates.
+//!
+//! A light client can only be updated if its latest consensus state is still within the trusting
+//! period of the client. Clients on low traffic routes may not be updated for long stretches of
+//! time (updates are normally only sent alongside IBC messages), at which point they expire and
+//! require governance intervention to be recovered.
+//!
+//! The monitor periodically checks all of the configured clients, exposing the remaining time until
+//! expiry as a metric, and enqueues a standalone client update once less than
+//! [`ClientExpiryConfig::refresh_threshold`] of the trusting period remains, or once the latest
+//! consensus state is older than [`MonitoredClient::max_staleness_seconds`]. Updates sent
+//! alongside relayed packets produce a new consensus state, so clients on busy routes are left
+//! alone by the monitor.
+
+use std::{
+    collections::HashMap,
+    sync::LazyLock,
+    time::{Duration, Instant},
+};
+
+use anyhow::Context as _;
+use prometheus::{register_gauge_vec, register_int_counter_vec, GaugeVec, IntCounterVec};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tracing::{debug, info, warn};
+use unionlabs::ibc::core::client::height::Height;
+use voyager_message::{
+    context::Context,
+    core::{ChainId, IbcSpecId, QueryHeight},
+    template::update_client,
+    RawClientId, VoyagerMessage,
+};
+use voyager_vm::Op;
+
+/// How long to wait for a refresh to land before enqueueing another one for the same client.
+const REFRESH_TIMEOUT: Duration = Duration::from_secs(10 * 60);
+
+pub static CLIENT_TIME_TO_EXPIRY: LazyLock<GaugeVec> = LazyLock::new(|| {
+    register_gauge_vec!(
+        "voyager_client_time_to_expiry_seconds",
+        "The time remaining until the client expires. Negative if the client has already expired.",
+        &["chain_id", "client_id", "counterparty_chain_id"],
+    )
+    .unwrap()
+});
+
+pub static CLIENT_REFRESH_COUNT: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!(
+        "voyager_client_refresh_total",
+        "The amount of standalone client updates that have been enqueued, by the reason they were \
+        required.",
+        &["chain_id", "client_id", "reason"],
+    )
+    .unwrap()
+});
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct ClientExpiryConfig {
+    /// How often the clients are checked.
+    #[serde(default = "default_interval_seconds")]
+    pub interval_seconds: u64,
+    /// The fraction of the trusting period below which the remaining time until expiry must drop
+    /// for the client to be refreshed.
+    #[serde(default = "default_refresh_threshold")]
+    pub refresh_threshold: f64,
+    pub clients: Vec<MonitoredClient>,
+}
+
+#[must_use]
+#[inline]
+pub const fn default_interval_seconds() -> u64 {
+    60
+}
+
+#[must_use]
+#[inline]
+pub const fn default_refresh_threshold() -> f64 {
+    0.5
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct MonitoredClient {
+    /// The chain the client is on.
+    pub chain_id: ChainId,
+    pub ibc_spec_id: IbcSpecId,
+    #[schemars(with = "Value")]
+    pub client_id: RawClientId,
+    /// The trusting period of the client. This is not exposed in a client agnostic way by the
+    /// client modules, and as such must be configured explicitly.
+    pub trusting_period_seconds: u64,
+    /// The maximum age of the latest consensus state of the client, after which the client is
+    /// updated even if it is not close to expiry. If not set, the client is only updated to
+    /// prevent it from expiring.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_staleness_seconds: Option<u64>,
+}
+
+/// Why a standalone client update is required.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum RefreshReason {
+    /// Less than the refresh threshold of the trusting period remains.
+    Expiry,
+    /// The latest consensus state is older than the max staleness.
+    Staleness,
+}
+
+impl RefreshReason {
+    /// Whether a client with `remaining_seconds` until expiry and a latest consensus state that is
+    /// `age_seconds` old needs to be updated.
+    #[allow(clippy::cast_precision_loss)]
+    fn of(
+        remaining_seconds: f64,
+        threshold: f64,
+        age_seconds: f64,
+        max_staleness_seconds: Option<u64>,
+    ) -> Option<Self> {
+        if remaining_seconds < threshold {
+            Some(Self::Expiry)
+        } else if max_staleness_seconds.is_some_and(|max| age_seconds >= max as f64) {
+            Some(Self::Staleness)
+        } else {
+            None
+        }
+    }
+
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Expiry => "expiry",
+            Self::Staleness => "staleness",
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct ClientExpiryMonitor {
+    config: ClientExpiryConfig,
+    /// The height each client was refreshed from, and when. Used to avoid refreshing a client
+    /// again while a previous refresh is still in flight.
+    refreshes: HashMap<(ChainId, RawClientId), (Height, Instant)>,
+}
+
+impl ClientExpiryMonitor {
+    #[must_use]
+    pub fn new(config: ClientExpiryConfig) -> Self {
+        Self {
+            config,
+            refreshes: HashMap::new(),
+        }
+    }
+
+    #[must_use]
+    pub fn interval(&self) -> Duration {
+        Duration::from_secs(self.config.interval_seconds)
+    }
+
+    /// Check all of the monitored clients, returning the ops to refresh those that are close to
+    /// expiry. Errors are logged and do not prevent the remaining clients from being checked.
+    pub async fn check_all(&mut self, ctx: &Context) -> Vec<Op<VoyagerMessage>> {
+        let mut ops = vec![];
+
+        for client in self.config.clients.clone() {
+            match self.check(ctx, &client).await {
+                Ok(Some(op)) => ops.push(op),
+                Ok(None) => {}
+                Err(error) => {
+                    warn!(
+                        chain_id = %client.chain_id,
+                        client_id = %client_id_label(&client.client_id),
+                        "error checking client expiry: {error:#}"
+                    );
+                }
+            }
+        }
+
+        ops
+    }
+
+    async fn check(
+        &mut self,
+        ctx: &Context,
+        client: &MonitoredClient,
+    ) -> anyhow::Result<Option<Op<VoyagerMessage>>> {
+        let client_id = client_id_label(&client.client_id);
+
+        let client_meta = ctx
+            .rpc_server
+            .client_meta(
+                &client.chain_id,
+                &client.ibc_spec_id,
+                QueryHeight::Finalized,
+                client.client_id.clone(),
+            )
+            .await
+            .context("error fetching client meta")?;
+
+        let consensus_meta = ctx
+            .rpc_server
+            .consensus_meta(
+                &client.chain_id,
+                &client.ibc_spec_id,
+                QueryHeight::Finalized,
+                client.client_id.clone(),
+                client_meta.height,
+            )
+            .await
+            .context("error fetching consensus meta")?;
+
+        // expiry is checked against the time of the chain the client is on
+        let now = ctx
+            .rpc_server
+            .query_latest_timestamp(&client.chain_id, true)
+            .await
+            .context("error querying latest timestamp")?;
+
+        let trusting_period = i128::from(client.trusting_period_seconds) * 1_000_000_000;
+        let age = i128::from(now) - i128::from(consensus_meta.timestamp_nanos);
+        let remaining = trusting_period - age;
+
+        #[allow(clippy::cast_precision_loss)]
+        let (remaining_seconds, threshold, age_seconds) = (
+            remaining as f64 / 1_000_000_000.0,
+            trusting_period as f64 / 1_000_000_000.0 * self.config.refresh_threshold,
+            age as f64 / 1_000_000_000.0,
+        );
+
+        CLIENT_TIME_TO_EXPIRY
+            .with_label_values(&[
+                client.chain_id.as_str(),
+                &client_id,
+                client_meta.chain_id.as_str(),
+            ])
+            .set(remaining_seconds);
+
+        if remaining <= 0 {
+            warn!(
+                chain_id = %client.chain_id,
+                %client_id,
+                height = %client_meta.height,
+                "client has expired and can no longer be refreshed"
+            );
+
+            return Ok(None);
+        }
+
+        let Some(reason) = RefreshReason::of(
+            remaining_seconds,
+            threshold,
+            age_seconds,
+            client.max_staleness_seconds,
+        ) else {
+            debug!(
+                chain_id = %client.chain_id,
+                %client_id,
+                remaining_seconds,
+                age_seconds,
+                "client does not need to be updated"
+            );
+
+            return Ok(None);
+        };
+
+        let key = (client.chain_id.clone(), client.client_id.clone());
+
+        if let Some((height, refreshed_at)) = self.refreshes.get(&key) {
+            if *height == client_meta.height && refreshed_at.elapsed() < REFRESH_TIMEOUT {
+                debug!(
+                    chain_id = %client.chain_id,
+                    %client_id,
+                    "client refresh is already in flight"
+                );
+
+                return Ok(None);
+            }
+        }
+
+        let latest_height = ctx
+            .rpc_server
+            .query_latest_height(&client_meta.chain_id, true)
+            .await
+            .context("error querying latest height of the counterparty")?;
+
+        info!(
+            chain_id = %client.chain_id,
+            %client_id,
+            remaining_seconds,
+            age_seconds,
+            reason = reason.as_str(),
+            update_from = %client_meta.height,
+            update_to = %latest_height,
+            "refreshing client"
+        );
+
+        CLIENT_REFRESH_COUNT
+            .with_label_values(&[client.chain_id.as_str(), &client_id, reason.as_str()])
+            .inc();
+
+        self.refreshes
+            .insert(key, (client_meta.height, Instant::now()));
+
+        Ok(Some(update_client(
+            client.chain_id.clone(),
+            client.ibc_spec_id.clone(),
+            client.client_id.clone(),
+            client_meta.chain_id,
+            client_meta.height,
+            latest_height,
+        )))
+    }
+}
+
+fn client_id_label(client_id: &RawClientId) -> String {
+    match client_id.as_raw() {
+        Value::String(s) => s.clone(),
+        value => value.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn refresh_reason() {
+        // plenty of time left, no max staleness
+        assert_eq!(RefreshReason::of(1000.0, 500.0, 10_000.0, None), None);
+        // close to expiry
+        assert_eq!(
+            RefreshReason::of(400.0, 500.0, 0.0, None),
+            Some(RefreshReason::Expiry)
+        );
+        // not close to expiry, but stale
+        assert_eq!(
+            RefreshReason::of(1000.0, 500.0, 600.0, Some(600)),
+            Some(RefreshReason::Staleness)
+        );
+        assert_eq!(RefreshReason::of(1000.0, 500.0, 599.0, Some(600)), None);
+        // expiry takes precedence
+        assert_eq!(
+            RefreshReason::of(400.0, 500.0, 600.0, Some(600)),
+            Some(RefreshReason::Expiry)
+        );
+    }
+}
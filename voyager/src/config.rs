@@ -1,10 +1,24 @@
-use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::{
+    fs::read_to_string,
+    net::{IpAddr, Ipv4Addr, SocketAddr},
+    path::{Path, PathBuf},
+};
 
+use anyhow::Context as _;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use voyager_message::context::{ModulesConfig, PluginConfig};
+use voyager_message::{
+    call::CallTimeouts,
+    context::{ModulesConfig, PluginConfig},
+    dedup::DEFAULT_EVENT_DEDUP_WINDOW_SECONDS,
+    notify::WebhookConfig,
+};
+use voyager_vm::Limits;
 
-use crate::queue::QueueConfig;
+use crate::{
+    balances::BalanceMonitorConfig, client_expiry::ClientExpiryConfig, health::HealthConfig,
+    input::InputConfig, queue::QueueConfig,
+};
 
 #[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 #[serde(deny_unknown_fields)]
@@ -17,6 +31,26 @@ pub struct Config {
     pub voyager: VoyagerConfig,
 }
 
+impl Config {
+    pub fn from_file(config_file_path: &Path) -> anyhow::Result<Self> {
+        read_to_string(config_file_path)
+            .with_context(|| {
+                format!(
+                    "unable to read the config file at `{}`",
+                    config_file_path.to_string_lossy()
+                )
+            })
+            .and_then(|s| {
+                serde_json::from_str::<Config>(&s).with_context(|| {
+                    format!(
+                        "unable to parse the config file at `{}`",
+                        config_file_path.to_string_lossy()
+                    )
+                })
+            })
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 #[serde(deny_unknown_fields)]
 pub struct VoyagerConfig {
@@ -25,10 +59,56 @@ pub struct VoyagerConfig {
     pub rest_laddr: SocketAddr,
     #[serde(default = "default_rpc_laddr")]
     pub rpc_laddr: SocketAddr,
+    /// The address to serve the control api on; see [`crate::control`].
+    #[serde(default = "default_control_laddr")]
+    pub control_laddr: SocketAddr,
     pub queue: QueueConfig,
     // TODO: Specify per plugin
     #[serde(default = "default_optimizer_delay_milliseconds")]
     pub optimizer_delay_milliseconds: u64,
+    /// File to persist the last height that all events have been processed up to on each chain.
+    /// If set, chains with a checkpoint are resumed from it on startup, backfilling any blocks
+    /// that were missed while voyager was not running.
+    ///
+    /// Only the in-memory queue resumes from checkpoints, as the pg queue already persists the
+    /// pending fetch ops across restarts.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub checkpoint_path: Option<PathBuf>,
+    /// Periodically check the configured clients for expiry, refreshing them before they expire.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_expiry: Option<ClientExpiryConfig>,
+    /// Periodically check the balances of the signers of the configured transaction plugins.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub balance_monitor: Option<BalanceMonitorConfig>,
+    /// Configuration for the per-chain health checks served on `/healthz` and `/readyz`.
+    #[serde(default)]
+    pub health: HealthConfig,
+    /// Limits on the nesting depth and retries of ops. Ops exceeding these are failed.
+    #[serde(default)]
+    pub limits: Limits,
+    /// Timeouts for the processing of a single call; see [`CallTimeouts`].
+    #[serde(default)]
+    pub call_timeouts: CallTimeouts,
+    /// Events returned from plugins are dropped if an identical event (on the same chain, in the
+    /// same transaction) has already been returned within this window, such that multiple event
+    /// source plugins can be run for the same chain for redundancy. Set to 0 to disable.
+    #[serde(default = "default_event_dedup_window_seconds")]
+    pub event_dedup_window_seconds: u64,
+    /// Additional inputs that ops can be enqueued through, besides the `/enqueue` endpoint of the
+    /// REST api; see [`crate::input`].
+    #[serde(default)]
+    pub inputs: InputConfig,
+    /// Webhooks to notify of relaying lifecycle events; see [`voyager_message::notify`].
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub webhooks: Vec<WebhookConfig>,
+    /// File to record every handled op (and the outcome of handling it) to. The recording can be
+    /// replayed with `voyager queue replay`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub record_path: Option<PathBuf>,
+    /// How long to wait for the ops that are being processed to complete when shutting down,
+    /// before exiting anyways.
+    #[serde(default = "default_shutdown_timeout_seconds")]
+    pub shutdown_timeout_seconds: u64,
 }
 
 #[must_use]
@@ -43,8 +123,26 @@ pub const fn default_rpc_laddr() -> SocketAddr {
     SocketAddr::new(IpAddr::V4(Ipv4Addr::new(0, 0, 0, 0)), 7178)
 }
 
+#[must_use]
+#[inline]
+pub const fn default_control_laddr() -> SocketAddr {
+    SocketAddr::new(IpAddr::V4(Ipv4Addr::new(0, 0, 0, 0)), 7179)
+}
+
 #[must_use]
 #[inline]
 pub const fn default_optimizer_delay_milliseconds() -> u64 {
     100
 }
+
+#[must_use]
+#[inline]
+pub const fn default_shutdown_timeout_seconds() -> u64 {
+    30
+}
+
+#[must_use]
+#[inline]
+pub const fn default_event_dedup_window_seconds() -> u64 {
+    DEFAULT_EVENT_DEDUP_WINDOW_SECONDS
+}
@@ -0,0 +1,453 @@
+//! The control API of a running voyager instance.
+//!
+//! This is served separately from the voyager rpc server (which is used by plugins and modules),
+//! and allows operators to inspect and manipulate the queue at runtime.
+
+use std::net::SocketAddr;
+
+use futures::{stream::FuturesUnordered, StreamExt};
+use jsonrpsee::{
+    core::{async_trait, RpcResult},
+    proc_macros::rpc,
+    types::ErrorObject,
+};
+use serde::{Deserialize, Serialize};
+use tracing::{info, instrument};
+use unionlabs::{ibc::core::client::height::Height, ErrorReporter};
+use voyager_message::{
+    chain_pair_key,
+    context::Context,
+    core::ChainId,
+    costs::{CostEntry, TxCosts},
+    filter::JaqInterestFilter,
+    packet_status::{PacketId, PacketStatus, PacketStatuses},
+    pause::{Paused, Pauses},
+    rpc::server::Server,
+    RawClientId, VoyagerMessage, FATAL_JSONRPC_ERROR_CODE,
+};
+use voyager_vm::{now, Op, OpTree, Queue, QueueMessage};
+
+use crate::queue::QueueImpl;
+
+#[rpc(client, server, namespace = "control")]
+pub trait ControlRpc {
+    /// Enqueue `op`, as if it were sent to the `/enqueue` endpoint of the REST api.
+    #[method(name = "enqueue")]
+    async fn enqueue(&self, op: Op<VoyagerMessage>) -> RpcResult<()>;
+
+    /// List the ops that are waiting to be processed or optimized, ordered by id. `page` is
+    /// 1-indexed.
+    #[method(name = "queue")]
+    async fn queue(&self, page: u32, per_page: u32) -> RpcResult<Vec<QueueItem>>;
+
+    /// List the ops that failed with a fatal error, most recent first. `page` is 1-indexed.
+    ///
+    /// The in-memory queue only keeps the most recent failures, see
+    /// [`MAX_FAILED_ITEMS`](voyager_vm::in_memory::MAX_FAILED_ITEMS).
+    #[method(name = "failed")]
+    async fn failed(&self, page: u32, per_page: u32) -> RpcResult<Vec<FailedQueueItem>>;
+
+    /// Remove the failed op with `id` and enqueue it again. Returns `false` if there is no failed
+    /// op with that id.
+    #[method(name = "requeueFailed")]
+    async fn requeue_failed(&self, id: i64) -> RpcResult<bool>;
+
+    /// The same items as `queue`, as a structural view of each op along with its age and the
+    /// chain pair it relays between. Intended for dashboards, to show where in its execution each
+    /// op is waiting.
+    #[method(name = "tree")]
+    async fn tree(&self, page: u32, per_page: u32) -> RpcResult<Vec<QueueTree>>;
+
+    /// Pause processing of all ops relaying between `chain_a` and `chain_b` (in either direction).
+    /// Returns `false` if the pair was already paused.
+    #[method(name = "pause")]
+    async fn pause(&self, chain_a: ChainId, chain_b: ChainId) -> RpcResult<bool>;
+
+    /// Resume processing of all ops relaying between `chain_a` and `chain_b`. Returns `false` if
+    /// the pair was not paused.
+    #[method(name = "resume")]
+    async fn resume(&self, chain_a: ChainId, chain_b: ChainId) -> RpcResult<bool>;
+
+    /// Pause processing of all ops interacting with `chain_id`, i.e. during a chain halt or
+    /// upgrade. Returns `false` if the chain was already paused.
+    #[method(name = "pauseChain")]
+    async fn pause_chain(&self, chain_id: ChainId) -> RpcResult<bool>;
+
+    /// Resume processing of all ops interacting with `chain_id`. Returns `false` if the chain was
+    /// not paused.
+    #[method(name = "resumeChain")]
+    async fn resume_chain(&self, chain_id: ChainId) -> RpcResult<bool>;
+
+    /// Pause processing of all ops targeting the client `client_id` on `chain_id`. Returns
+    /// `false` if the client was already paused.
+    #[method(name = "pauseClient")]
+    async fn pause_client(&self, chain_id: ChainId, client_id: RawClientId) -> RpcResult<bool>;
+
+    /// Resume processing of all ops targeting the client `client_id` on `chain_id`. Returns
+    /// `false` if the client was not paused.
+    #[method(name = "resumeClient")]
+    async fn resume_client(&self, chain_id: ChainId, client_id: RawClientId) -> RpcResult<bool>;
+
+    /// All currently paused concurrency keys, chains, and clients, and the plugins whose
+    /// submissions are held.
+    #[method(name = "paused")]
+    async fn paused(&self) -> RpcResult<Paused>;
+
+    /// The latest height of every chain with a loaded consensus module.
+    #[method(name = "latestHeights")]
+    async fn latest_heights(&self, finalized: bool) -> RpcResult<Vec<ChainHeight>>;
+
+    /// The gas used and fees paid by all transactions submitted since startup, per chain, client,
+    /// and message type.
+    #[method(name = "txCosts")]
+    async fn tx_costs(&self) -> RpcResult<Vec<CostEntry>>;
+
+    /// The lifecycle state of `packet` on `chain_id`, or `null` if the packet has not been
+    /// observed. Either end of the packet can be queried.
+    #[method(name = "packetStatus")]
+    async fn packet_status(
+        &self,
+        chain_id: ChainId,
+        packet: PacketId,
+    ) -> RpcResult<Option<PacketStatus>>;
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct QueueItem {
+    pub id: i64,
+    pub parents: Vec<i64>,
+    /// The id of the item that was originally enqueued and that this item descends from. All items
+    /// in the same op tree share a correlation id, which is also recorded on their tracing spans.
+    pub correlation_id: i64,
+    /// The tag of the optimizer the item is waiting for, if any.
+    pub tag: Option<String>,
+    pub op: Op<VoyagerMessage>,
+    /// The unix timestamp (in seconds) at which the item was enqueued.
+    pub created_at: i64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FailedQueueItem {
+    pub id: i64,
+    pub parents: Vec<i64>,
+    pub correlation_id: i64,
+    pub op: Op<VoyagerMessage>,
+    /// The error the op failed with.
+    pub message: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct QueueTree {
+    pub id: i64,
+    pub parents: Vec<i64>,
+    pub correlation_id: i64,
+    pub tag: Option<String>,
+    /// How long the item has been waiting in the queue, in seconds.
+    ///
+    /// Note that ops are requeued as a new item every time they are processed, so this is not the
+    /// age of the op that was originally enqueued.
+    pub age_seconds: u64,
+    pub depth: usize,
+    /// The pair of chains the op relays between, if any.
+    pub chain_pair: Option<String>,
+    pub tree: OpTree,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ChainHeight {
+    pub chain_id: ChainId,
+    /// The latest height of the chain, or `None` if it could not be queried.
+    pub height: Option<Height>,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ControlServer {
+    queue: QueueImpl,
+    interest_filter: JaqInterestFilter,
+    rpc_server: Server,
+    pauses: Pauses,
+    tx_costs: TxCosts,
+    packet_statuses: PacketStatuses,
+}
+
+impl ControlServer {
+    pub fn new(context: &Context, queue: QueueImpl, interest_filter: JaqInterestFilter) -> Self {
+        Self {
+            queue,
+            interest_filter,
+            rpc_server: context.rpc_server.clone(),
+            pauses: context.pauses.clone(),
+            tx_costs: context.tx_costs.clone(),
+            packet_statuses: context.packet_statuses.clone(),
+        }
+    }
+
+    /// Serve the control api on `laddr` until the server is stopped.
+    pub async fn run(self, laddr: &SocketAddr) -> anyhow::Result<()> {
+        let server = jsonrpsee::server::Server::builder().build(laddr).await?;
+        let addr = server.local_addr()?;
+        let handle = server.start(self.into_rpc());
+
+        info!("control api listening on {addr}");
+
+        handle.stopped().await;
+
+        Ok(())
+    }
+
+    /// The items waiting to be processed or optimized, ordered by id. `page` is 1-indexed.
+    async fn queued(&self, page: u32, per_page: u32) -> RpcResult<Vec<QueueItem>> {
+        check_page(page)?;
+
+        match &self.queue {
+            QueueImpl::InMemory(queue) => Ok(queue
+                .queued()
+                .into_iter()
+                .skip(page_offset(page, per_page))
+                .take(per_page as usize)
+                .map(|item| QueueItem {
+                    id: item.id.into(),
+                    parents: item.parents.into_iter().map(Into::into).collect(),
+                    correlation_id: item.correlation_id.into(),
+                    tag: item.tag,
+                    op: item.op,
+                    created_at: item.created_at.try_into().unwrap_or(i64::MAX),
+                })
+                .collect()),
+            QueueImpl::PgQueue(queue) => Ok(queue
+                .query_queued(page.into(), per_page.into())
+                .await
+                .map_err(pg_error)?
+                .into_iter()
+                .map(|record| QueueItem {
+                    id: record.id,
+                    parents: record.parents,
+                    correlation_id: record.correlation_id,
+                    tag: record.tag,
+                    op: record.item.0,
+                    created_at: record.created_at,
+                })
+                .collect()),
+            QueueImpl::Amqp(_) => Err(ErrorObject::owned(
+                FATAL_JSONRPC_ERROR_CODE,
+                "the amqp queue does not support listing queued items",
+                None::<()>,
+            )),
+        }
+    }
+}
+
+fn check_page(page: u32) -> RpcResult<()> {
+    if page == 0 {
+        Err(ErrorObject::owned(
+            FATAL_JSONRPC_ERROR_CODE,
+            "page must be greater than 0",
+            None::<()>,
+        ))
+    } else {
+        Ok(())
+    }
+}
+
+/// The amount of items to skip to get to the 1-indexed `page`.
+fn page_offset(page: u32, per_page: u32) -> usize {
+    page.saturating_sub(1).saturating_mul(per_page) as usize
+}
+
+fn pg_error(err: sqlx::Error) -> ErrorObject<'static> {
+    ErrorObject::owned(-1, ErrorReporter(err).to_string(), None::<()>)
+}
+
+#[async_trait]
+impl ControlRpcServer for ControlServer {
+    #[instrument(skip_all)]
+    async fn enqueue(&self, op: Op<VoyagerMessage>) -> RpcResult<()> {
+        self.queue
+            .enqueue(op, &self.interest_filter)
+            .await
+            .map_err(|err| ErrorObject::owned(-1, ErrorReporter(err).to_string(), None::<()>))
+    }
+
+    #[instrument(skip_all, fields(page, per_page))]
+    async fn queue(&self, page: u32, per_page: u32) -> RpcResult<Vec<QueueItem>> {
+        self.queued(page, per_page).await
+    }
+
+    #[instrument(skip_all, fields(page, per_page))]
+    async fn failed(&self, page: u32, per_page: u32) -> RpcResult<Vec<FailedQueueItem>> {
+        check_page(page)?;
+
+        match &self.queue {
+            QueueImpl::InMemory(queue) => Ok(queue
+                .failed()
+                .into_iter()
+                .skip(page_offset(page, per_page))
+                .take(per_page as usize)
+                .map(|item| FailedQueueItem {
+                    id: item.id.into(),
+                    parents: item.parents.into_iter().map(Into::into).collect(),
+                    correlation_id: item.correlation_id.into(),
+                    op: item.op,
+                    message: item.message,
+                })
+                .collect()),
+            QueueImpl::PgQueue(queue) => Ok(queue
+                .query_failed(page.into(), per_page.into(), vec![], vec![])
+                .await
+                .map_err(pg_error)?
+                .into_iter()
+                .map(|record| FailedQueueItem {
+                    id: record.id,
+                    parents: record.parents,
+                    correlation_id: record.correlation_id,
+                    op: record.item.0,
+                    message: record.message,
+                })
+                .collect()),
+            QueueImpl::Amqp(_) => Err(ErrorObject::owned(
+                FATAL_JSONRPC_ERROR_CODE,
+                "the amqp queue does not keep failed items",
+                None::<()>,
+            )),
+        }
+    }
+
+    #[instrument(skip_all, fields(id))]
+    async fn requeue_failed(&self, id: i64) -> RpcResult<bool> {
+        match &self.queue {
+            QueueImpl::InMemory(queue) => {
+                let Some(op) = u32::try_from(id).ok().and_then(|id| queue.take_failed(id)) else {
+                    return Ok(false);
+                };
+
+                info!(%id, "requeueing failed op");
+
+                self.enqueue(op).await?;
+
+                Ok(true)
+            }
+            QueueImpl::PgQueue(queue) => {
+                let requeued = queue
+                    .requeue_failed(id, &self.interest_filter)
+                    .await
+                    .map_err(pg_error)?;
+
+                if requeued {
+                    info!(%id, "requeued failed op");
+                }
+
+                Ok(requeued)
+            }
+            QueueImpl::Amqp(_) => Err(ErrorObject::owned(
+                FATAL_JSONRPC_ERROR_CODE,
+                "the amqp queue does not keep failed items",
+                None::<()>,
+            )),
+        }
+    }
+
+    #[instrument(skip_all, fields(page, per_page))]
+    async fn tree(&self, page: u32, per_page: u32) -> RpcResult<Vec<QueueTree>> {
+        let now = i64::try_from(now()).expect("timestamp is in range; qed;");
+
+        Ok(self
+            .queued(page, per_page)
+            .await?
+            .into_iter()
+            .map(|item| {
+                let tree = item.op.tree();
+
+                QueueTree {
+                    id: item.id,
+                    parents: item.parents,
+                    correlation_id: item.correlation_id,
+                    tag: item.tag,
+                    age_seconds: now.saturating_sub(item.created_at).try_into().unwrap_or(0),
+                    depth: tree.depth(),
+                    chain_pair: VoyagerMessage::concurrency_key(&item.op),
+                    tree,
+                }
+            })
+            .collect())
+    }
+
+    async fn pause(&self, chain_a: ChainId, chain_b: ChainId) -> RpcResult<bool> {
+        Ok(self.pauses.pause(chain_pair_key(&chain_a, &chain_b)))
+    }
+
+    async fn resume(&self, chain_a: ChainId, chain_b: ChainId) -> RpcResult<bool> {
+        Ok(self.pauses.resume(&chain_pair_key(&chain_a, &chain_b)))
+    }
+
+    async fn pause_chain(&self, chain_id: ChainId) -> RpcResult<bool> {
+        Ok(self.pauses.pause_chain(chain_id))
+    }
+
+    async fn resume_chain(&self, chain_id: ChainId) -> RpcResult<bool> {
+        Ok(self.pauses.resume_chain(&chain_id))
+    }
+
+    async fn pause_client(&self, chain_id: ChainId, client_id: RawClientId) -> RpcResult<bool> {
+        Ok(self.pauses.pause_client(chain_id, client_id))
+    }
+
+    async fn resume_client(&self, chain_id: ChainId, client_id: RawClientId) -> RpcResult<bool> {
+        Ok(self.pauses.resume_client(chain_id, client_id))
+    }
+
+    async fn paused(&self) -> RpcResult<Paused> {
+        Ok(self.pauses.paused())
+    }
+
+    #[instrument(skip_all, fields(finalized))]
+    async fn latest_heights(&self, finalized: bool) -> RpcResult<Vec<ChainHeight>> {
+        let chain_ids = self
+            .rpc_server
+            .modules()?
+            .info()
+            .consensus
+            .into_iter()
+            .map(|info| info.chain_id);
+
+        let mut heights = chain_ids
+            .map(|chain_id| async move {
+                let res = self
+                    .rpc_server
+                    .query_latest_height(&chain_id, finalized)
+                    .await;
+
+                match res {
+                    Ok(height) => ChainHeight {
+                        chain_id,
+                        height: Some(height),
+                        error: None,
+                    },
+                    Err(err) => ChainHeight {
+                        chain_id,
+                        height: None,
+                        error: Some(err.message().to_owned()),
+                    },
+                }
+            })
+            .collect::<FuturesUnordered<_>>()
+            .collect::<Vec<_>>()
+            .await;
+
+        heights.sort_unstable_by(|a, b| a.chain_id.as_str().cmp(b.chain_id.as_str()));
+
+        Ok(heights)
+    }
+
+    async fn tx_costs(&self) -> RpcResult<Vec<CostEntry>> {
+        Ok(self.tx_costs.report())
+    }
+
+    async fn packet_status(
+        &self,
+        chain_id: ChainId,
+        packet: PacketId,
+    ) -> RpcResult<Option<PacketStatus>> {
+        Ok(self.packet_statuses.packet_status(&chain_id, packet))
+    }
+}
@@ -0,0 +1,188 @@
+//! Per-chain health checks.
+//!
+//! The latest height and timestamp of every chain with a loaded consensus module are periodically
+//! queried, and the results are served on the `/healthz` and `/readyz` endpoints of the REST api:
+//!
+//! - `/readyz` fails if *any* chain has not been successfully queried within
+//!   [`HealthConfig::max_staleness_seconds`], such that the instance can be taken out of rotation
+//!   (or an operator paged) while a chain is unreachable.
+//! - `/healthz` only fails if *none* of the chains could be queried, since in that case the problem
+//!   is most likely local to this instance and restarting it may help.
+//!
+//! Both endpoints return the status of every chain as the response body.
+
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, LazyLock, RwLock},
+    time::Duration,
+};
+
+use prometheus::{register_int_counter_vec, IntCounterVec};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use tracing::{debug, warn};
+use unionlabs::ibc::core::client::height::Height;
+use voyager_message::{context::Context, core::ChainId};
+use voyager_vm::now;
+
+pub static HEALTH_CHECK_ERROR_COUNT: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!(
+        "voyager_health_check_errors_total",
+        "The amount of failed health checks.",
+        &["chain_id"],
+    )
+    .unwrap()
+});
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct HealthConfig {
+    /// How often the chains are checked.
+    #[serde(default = "default_interval_seconds")]
+    pub interval_seconds: u64,
+    /// How long ago the last successful check of a chain may have been for the chain to still be
+    /// considered healthy.
+    #[serde(default = "default_max_staleness_seconds")]
+    pub max_staleness_seconds: u64,
+}
+
+impl Default for HealthConfig {
+    fn default() -> Self {
+        Self {
+            interval_seconds: default_interval_seconds(),
+            max_staleness_seconds: default_max_staleness_seconds(),
+        }
+    }
+}
+
+#[must_use]
+#[inline]
+pub const fn default_interval_seconds() -> u64 {
+    15
+}
+
+#[must_use]
+#[inline]
+pub const fn default_max_staleness_seconds() -> u64 {
+    60
+}
+
+#[derive(Debug, Clone)]
+pub struct Health {
+    config: HealthConfig,
+    chains: Arc<RwLock<BTreeMap<ChainId, ChainHealth>>>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ChainHealth {
+    pub healthy: bool,
+    pub latest_height: Option<Height>,
+    /// The latest timestamp of the chain, in nanoseconds.
+    pub latest_timestamp: Option<i64>,
+    /// The unix timestamp (in seconds) of the last successful check.
+    pub last_success: Option<u64>,
+    pub last_error: Option<String>,
+    pub checks: u64,
+    pub errors: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HealthReport {
+    pub healthy: bool,
+    pub ready: bool,
+    pub chains: BTreeMap<ChainId, ChainHealth>,
+}
+
+impl Health {
+    #[must_use]
+    pub fn new(config: HealthConfig) -> Self {
+        Self {
+            config,
+            chains: Arc::default(),
+        }
+    }
+
+    #[must_use]
+    pub fn interval(&self) -> Duration {
+        Duration::from_secs(self.config.interval_seconds)
+    }
+
+    /// Check all chains with a loaded consensus module.
+    pub async fn check_all(&self, ctx: &Context) {
+        let chain_ids = match ctx.rpc_server.modules() {
+            Ok(modules) => modules
+                .info()
+                .consensus
+                .into_iter()
+                .map(|info| info.chain_id)
+                .collect::<Vec<_>>(),
+            Err(_) => {
+                debug!("modules are not yet loaded");
+                return;
+            }
+        };
+
+        for chain_id in chain_ids {
+            let res = check(ctx, &chain_id).await;
+
+            let mut chains = self.chains.write().expect("lock is poisoned");
+            let health = chains.entry(chain_id.clone()).or_default();
+
+            health.checks += 1;
+
+            match res {
+                Ok((height, timestamp)) => {
+                    health.latest_height = Some(height);
+                    health.latest_timestamp = Some(timestamp);
+                    health.last_success = Some(now());
+                    health.last_error = None;
+                }
+                Err(error) => {
+                    warn!(%chain_id, %error, "health check failed");
+
+                    HEALTH_CHECK_ERROR_COUNT
+                        .with_label_values(&[chain_id.as_str()])
+                        .inc();
+
+                    health.errors += 1;
+                    health.last_error = Some(error);
+                }
+            }
+        }
+    }
+
+    #[must_use]
+    pub fn report(&self) -> HealthReport {
+        let now = now();
+
+        let mut chains = self.chains.read().expect("lock is poisoned").clone();
+
+        for health in chains.values_mut() {
+            health.healthy = health.last_success.is_some_and(|last_success| {
+                now.saturating_sub(last_success) <= self.config.max_staleness_seconds
+            });
+        }
+
+        HealthReport {
+            healthy: chains.is_empty() || chains.values().any(|health| health.healthy),
+            ready: !chains.is_empty() && chains.values().all(|health| health.healthy),
+            chains,
+        }
+    }
+}
+
+async fn check(ctx: &Context, chain_id: &ChainId) -> Result<(Height, i64), String> {
+    let height = ctx
+        .rpc_server
+        .query_latest_height(chain_id, false)
+        .await
+        .map_err(|err| format!("error querying latest height: {}", err.message()))?;
+
+    let timestamp = ctx
+        .rpc_server
+        .query_latest_timestamp(chain_id, false)
+        .await
+        .map_err(|err| format!("error querying latest timestamp: {}", err.message()))?;
+
+    Ok((height, timestamp))
+}
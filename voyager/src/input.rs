@@ -0,0 +1,158 @@
+//! Enqueueing of ops without going through the REST api.
+//!
+//! Ops can additionally be fed to a running voyager instance as newline delimited JSON, either on a
+//! unix domain socket or on stdin, which is more convenient than the REST api for scripts and for
+//! other processes running on the same machine (i.e. `jq -c ... | socat - UNIX-CONNECT:voyager.sock`
+//! or `generate-ops | voyager start`).
+//!
+//! Every line written to the socket is answered with a single line, either `ok` if the op was
+//! accepted, or `error: ...` if it could not be parsed. Invalid lines read from stdin are logged and
+//! skipped.
+
+use std::{io::ErrorKind, path::PathBuf};
+
+use anyhow::Context as _;
+use futures::channel::mpsc::{unbounded, UnboundedReceiver, UnboundedSender};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader},
+    net::UnixListener,
+};
+use tracing::{debug, info, warn};
+use unionlabs::ErrorReporter;
+use voyager_message::VoyagerMessage;
+use voyager_vm::Op;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct InputConfig {
+    /// Path of a unix domain socket to accept newline delimited ops on. An existing socket at this
+    /// path is replaced.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub unix_socket_path: Option<PathBuf>,
+    /// Read newline delimited ops from stdin.
+    #[serde(default)]
+    pub stdin: bool,
+}
+
+/// Start all of the configured inputs. The returned receiver yields the ops received on any of
+/// them.
+pub fn run(config: &InputConfig) -> anyhow::Result<UnboundedReceiver<Op<VoyagerMessage>>> {
+    let (queue_tx, queue_rx) = unbounded::<Op<VoyagerMessage>>();
+
+    if let Some(path) = &config.unix_socket_path {
+        match std::fs::remove_file(path) {
+            Ok(()) => debug!("removed existing socket at {}", path.display()),
+            Err(err) if err.kind() == ErrorKind::NotFound => {}
+            Err(err) => {
+                return Err(err).with_context(|| {
+                    format!("unable to remove existing socket at {}", path.display())
+                })
+            }
+        }
+
+        let listener = UnixListener::bind(path)
+            .with_context(|| format!("unable to bind unix socket at {}", path.display()))?;
+
+        info!("accepting ops on {}", path.display());
+
+        tokio::spawn(accept(listener, queue_tx.clone()));
+    }
+
+    if config.stdin {
+        info!("accepting ops on stdin");
+
+        tokio::spawn(read_lines(
+            tokio::io::stdin(),
+            None::<tokio::io::Sink>,
+            queue_tx,
+        ));
+    }
+
+    Ok(queue_rx)
+}
+
+async fn accept(listener: UnixListener, queue_tx: UnboundedSender<Op<VoyagerMessage>>) {
+    loop {
+        match listener.accept().await {
+            Ok((stream, _)) => {
+                let (reader, writer) = stream.into_split();
+                tokio::spawn(read_lines(reader, Some(writer), queue_tx.clone()));
+            }
+            Err(err) => {
+                warn!(error = %ErrorReporter(err), "error accepting connection");
+            }
+        }
+    }
+}
+
+/// Read ops from `reader` until it is closed, answering every line on `writer` if provided.
+async fn read_lines(
+    reader: impl AsyncRead + Unpin,
+    mut writer: Option<impl AsyncWrite + Unpin>,
+    queue_tx: UnboundedSender<Op<VoyagerMessage>>,
+) {
+    let mut lines = BufReader::new(reader).lines();
+
+    loop {
+        let line = match lines.next_line().await {
+            Ok(Some(line)) => line,
+            Ok(None) => return,
+            Err(err) => {
+                warn!(error = %ErrorReporter(err), "error reading input");
+                return;
+            }
+        };
+
+        let response = match parse_line(&line) {
+            Ok(None) => continue,
+            Ok(Some(op)) => {
+                if queue_tx.unbounded_send(op).is_err() {
+                    // the queue is shutting down
+                    return;
+                }
+
+                "ok".to_owned()
+            }
+            Err(err) => {
+                warn!(error = %ErrorReporter(&err), "received invalid op");
+
+                format!("error: {}", ErrorReporter(err))
+            }
+        };
+
+        if let Some(writer) = &mut writer {
+            if let Err(err) = writer.write_all(format!("{response}\n").as_bytes()).await {
+                debug!(error = %ErrorReporter(err), "error writing response");
+                return;
+            }
+        }
+    }
+}
+
+/// Parse a single line of input. Blank lines are ignored.
+fn parse_line(line: &str) -> Result<Option<Op<VoyagerMessage>>, serde_json::Error> {
+    let line = line.trim();
+
+    if line.is_empty() {
+        Ok(None)
+    } else {
+        serde_json::from_str(line).map(Some)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use voyager_vm::noop;
+
+    use super::*;
+
+    #[test]
+    fn parse_lines() {
+        assert!(parse_line("").unwrap().is_none());
+        assert!(parse_line("   ").unwrap().is_none());
+        assert_eq!(parse_line(r#" {"@type":"noop"} "#).unwrap(), Some(noop()));
+        assert!(parse_line(r#"{"@type":"unknown"}"#).is_err());
+    }
+}
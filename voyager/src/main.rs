@@ -10,35 +10,62 @@
 )]
 
 use std::{
-    collections::HashMap, fmt::Write, fs::read_to_string, iter, net::SocketAddr, process::ExitCode,
+    fmt::Write,
+    iter,
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    process::ExitCode,
 };
 
 use anyhow::{anyhow, Context as _};
+use chain_utils::keystore::Keystore;
 use clap::Parser;
 use ibc_classic_spec::IbcClassic;
 use ibc_union_spec::IbcUnion;
 use pg_queue::PgQueueConfig;
 use schemars::gen::{SchemaGenerator, SchemaSettings};
 use serde::Serialize;
+use sha2::Digest;
 use tikv_jemallocator::Jemalloc;
 use tracing::info;
-use tracing_subscriber::EnvFilter;
+use unionlabs::hash::H256;
 use voyager_message::{
-    call::FetchBlocks,
-    context::{get_plugin_info, Context, IbcSpecHandler, ModulesConfig},
-    core::{IbcSpec, QueryHeight},
+    call::{CallTimeouts, FetchBlocks},
+    clear_packets::pending_packets,
+    context::{get_plugin_info, Context, IbcSpecHandlers, ModulesConfig},
+    core::QueryHeight,
+    data::{IbcDatagram, WithChainId},
     filter::{make_filter, run_filter, JaqInterestFilter},
+    handshake::{
+        init_channel, init_connection, resume_channel_handshake, resume_connection_handshake,
+    },
+    op_schema,
     rpc::{IbcState, VoyagerRpcClient},
+    telemetry, template,
+    upgrade::upgrade_client,
     VoyagerMessage,
 };
-use voyager_vm::{call, filter::FilterResult, Op, Queue};
+use voyager_vm::{
+    call, data,
+    filter::FilterResult,
+    record::{read_log, replay},
+    Limits, Op, Queue,
+};
 
 #[global_allocator]
 static GLOBAL: Jemalloc = Jemalloc;
 
 use crate::{
-    cli::{AppArgs, Command, ConfigCmd, ModuleCmd, MsgCmd, PluginCmd, QueueCmd, RpcCmd},
-    config::{default_rest_laddr, default_rpc_laddr, Config, VoyagerConfig},
+    cli::{
+        AppArgs, Command, ConfigCmd, GenerateCmd, KeystoreCmd, ModuleCmd, MsgCmd, PluginCmd,
+        QueueCmd, RpcCmd,
+    },
+    config::{
+        default_control_laddr, default_event_dedup_window_seconds, default_rest_laddr,
+        default_rpc_laddr, default_shutdown_timeout_seconds, Config, VoyagerConfig,
+    },
+    health::HealthConfig,
+    input::InputConfig,
     queue::{QueueConfig, Voyager},
     utils::make_msg_create_client,
 };
@@ -50,35 +77,44 @@ compile_error!(
 );
 
 pub mod api;
+pub mod balances;
+pub mod checkpoint;
 pub mod cli;
+pub mod client_expiry;
 pub mod config;
+pub mod control;
+pub mod health;
+pub mod input;
 pub mod queue;
+pub mod reload;
 
 fn main() -> ExitCode {
     let args = AppArgs::parse();
 
-    match args.log_format {
-        cli::LogFormat::Text => {
-            tracing_subscriber::fmt()
-                .with_env_filter(EnvFilter::from_default_env())
-                // .with_span_events(FmtSpan::CLOSE)
-                .init();
-        }
-        cli::LogFormat::Json => {
-            tracing_subscriber::fmt()
-                .with_env_filter(EnvFilter::from_default_env())
-                // .with_span_events(FmtSpan::CLOSE)
-                .json()
-                .init();
-        }
-    }
-
-    let res = tokio::runtime::Builder::new_multi_thread()
+    let runtime = tokio::runtime::Builder::new_multi_thread()
         .enable_all()
         .thread_stack_size(args.stack_size)
         .build()
-        .unwrap()
-        .block_on(do_main(args));
+        .unwrap();
+
+    // spans are exported from a background task, which must be spawned on the runtime
+    let tracer_provider = runtime.block_on(async {
+        telemetry::init(
+            match args.log_format {
+                cli::LogFormat::Text => telemetry::LogFormat::Text,
+                cli::LogFormat::Json => telemetry::LogFormat::Json,
+            },
+            "voyager",
+        )
+    });
+
+    let res = runtime.block_on(do_main(args));
+
+    if let Some(tracer_provider) = tracer_provider {
+        if let Err(err) = tracer_provider.shutdown() {
+            eprintln!("error flushing exported spans: {err}");
+        }
+    }
 
     match res {
         Ok(()) => ExitCode::SUCCESS,
@@ -101,21 +137,7 @@ fn main() -> ExitCode {
 // NOTE: This function is a mess, will be cleaned up
 async fn do_main(args: cli::AppArgs) -> anyhow::Result<()> {
     let get_voyager_config = || match &args.config_file_path {
-        Some(config_file_path) => read_to_string(config_file_path)
-            .with_context(|| {
-                format!(
-                    "unable to read the config file at `{}`",
-                    config_file_path.to_string_lossy()
-                )
-            })
-            .and_then(|s| {
-                serde_json::from_str::<Config>(&s).with_context(|| {
-                    format!(
-                        "unable to parse the config file at `{}`",
-                        config_file_path.to_string_lossy()
-                    )
-                })
-            }),
+        Some(config_file_path) => Config::from_file(Path::new(config_file_path)),
         None => Err(anyhow!("config file must be specified")),
     };
 
@@ -137,14 +159,27 @@ async fn do_main(args: cli::AppArgs) -> anyhow::Result<()> {
                     num_workers: 1,
                     rest_laddr: default_rest_laddr(),
                     rpc_laddr: default_rpc_laddr(),
+                    control_laddr: default_control_laddr(),
                     queue: QueueConfig::PgQueue(PgQueueConfig {
                         database_url: String::new(),
                         max_connections: None,
                         min_connections: None,
                         idle_timeout: None,
                         max_lifetime: None,
+                        sharding: None,
                     }),
                     optimizer_delay_milliseconds: 100,
+                    checkpoint_path: None,
+                    client_expiry: None,
+                    balance_monitor: None,
+                    health: HealthConfig::default(),
+                    limits: Limits::default(),
+                    call_timeouts: CallTimeouts::default(),
+                    event_dedup_window_seconds: default_event_dedup_window_seconds(),
+                    inputs: InputConfig::default(),
+                    webhooks: vec![],
+                    record_path: None,
+                    shutdown_timeout_seconds: default_shutdown_timeout_seconds(),
                 },
             }),
             ConfigCmd::Schema => print_json(
@@ -156,12 +191,28 @@ async fn do_main(args: cli::AppArgs) -> anyhow::Result<()> {
             ),
         },
         Command::Start => {
-            let voyager = Voyager::new(get_voyager_config()?).await?;
+            let voyager = Voyager::new(
+                get_voyager_config()?,
+                args.config_file_path.clone().map(PathBuf::from),
+            )
+            .await?;
 
             info!("starting relay service");
 
             voyager.run().await?;
         }
+        Command::Keystore(cmd) => match cmd {
+            KeystoreCmd::Encrypt { path, passphrase } => {
+                let key = serde_utils::parse_hex::<Vec<u8>>(
+                    std::io::read_to_string(std::io::stdin())?.trim(),
+                )
+                .context("the private key must be 0x-prefixed hex")?;
+
+                Keystore::encrypt(&key, passphrase.as_bytes())?.write(&path)?;
+
+                info!(path = %path.display(), "wrote keystore");
+            }
+        },
         Command::Plugin(cmd) => match cmd {
             PluginCmd::Interest {
                 plugin_name,
@@ -296,7 +347,7 @@ async fn do_main(args: cli::AppArgs) -> anyhow::Result<()> {
             let db = || {
                 Ok(match get_voyager_config()?.voyager.queue {
                     QueueConfig::PgQueue(cfg) => pg_queue::PgQueue::<VoyagerMessage>::new(cfg),
-                    QueueConfig::InMemory => {
+                    QueueConfig::InMemory(_) | QueueConfig::Amqp(_) => {
                         return Err(anyhow!(
                             "no database set in config, queue commands \
                             require the `pg-queue` database backend"
@@ -352,6 +403,22 @@ async fn do_main(args: cli::AppArgs) -> anyhow::Result<()> {
 
                     print_json(&record);
                 }
+                QueueCmd::Replay { log } => {
+                    let config = get_voyager_config()?;
+
+                    let records = read_log::<VoyagerMessage>(&log)
+                        .with_context(|| format!("unable to read `{}`", log.display()))?;
+
+                    let context =
+                        Context::new(config.plugins, config.modules, register_ibc_spec_handlers)
+                            .await?;
+
+                    let divergences = replay(records, &context, config.voyager.limits).await;
+
+                    context.shutdown().await;
+
+                    print_json(&divergences);
+                }
             }
         }
         // Command::Handshake(HandshakeCmd {
@@ -444,11 +511,9 @@ async fn do_main(args: cli::AppArgs) -> anyhow::Result<()> {
                 QueryHeight::Latest => {
                     let config = get_voyager_config()?;
 
-                    let context = Context::new(config.plugins, config.modules, |h| {
-                        h.register::<IbcClassic>();
-                        h.register::<IbcUnion>();
-                    })
-                    .await?;
+                    let context =
+                        Context::new(config.plugins, config.modules, register_ibc_spec_handlers)
+                            .await?;
 
                     let latest_height = context
                         .rpc_server
@@ -462,11 +527,9 @@ async fn do_main(args: cli::AppArgs) -> anyhow::Result<()> {
                 QueryHeight::Finalized => {
                     let config = get_voyager_config()?;
 
-                    let context = Context::new(config.plugins, config.modules, |h| {
-                        h.register::<IbcClassic>();
-                        h.register::<IbcUnion>();
-                    })
-                    .await?;
+                    let context =
+                        Context::new(config.plugins, config.modules, register_ibc_spec_handlers)
+                            .await?;
 
                     let latest_height = context
                         .rpc_server
@@ -492,6 +555,36 @@ async fn do_main(args: cli::AppArgs) -> anyhow::Result<()> {
                 print_json(&op);
             }
         }
+        Command::ClearPackets {
+            on,
+            port_id,
+            channel_id,
+            from_sequence,
+            enqueue,
+        } => {
+            let voyager_client = jsonrpsee::http_client::HttpClient::builder().build(format!(
+                "http://{}",
+                get_voyager_config()?.voyager.rpc_laddr
+            ))?;
+
+            let pending =
+                pending_packets(&voyager_client, on, port_id, channel_id, from_sequence).await?;
+
+            println!(
+                "found {} unreceived packets and {} unreceived acknowledgements",
+                pending.unreceived_packets.len(),
+                pending.unreceived_acks.len(),
+            );
+
+            let op = pending.into_op();
+
+            if enqueue {
+                println!("enqueueing op");
+                send_enqueue(&get_voyager_config()?.voyager.rest_laddr, op).await?;
+            } else {
+                print_json(&op);
+            }
+        }
         Command::Rpc(rpc) => {
             let voyager_client = jsonrpsee::http_client::HttpClient::builder().build(format!(
                 "http://{}",
@@ -507,19 +600,15 @@ async fn do_main(args: cli::AppArgs) -> anyhow::Result<()> {
                     height,
                     decode,
                 } => {
-                    let ibc_handlers = [
-                            (IbcClassic::ID, IbcSpecHandler::new::<IbcClassic>()),
-                            (IbcUnion::ID, IbcSpecHandler::new::<IbcUnion>())
-                         ]
-                        .into_iter()
-                        .collect::<HashMap<_, _>>();
+                    let mut ibc_spec_handlers = IbcSpecHandlers::new();
+                    register_ibc_spec_handlers(&mut ibc_spec_handlers);
 
                     let ibc_state = voyager_client
                         .query_ibc_state(
                             on.clone(),
                             ibc_spec_id.clone(),
                             height,
-                            (ibc_handlers.get(&ibc_spec_id).unwrap().client_state_path)(
+                            (ibc_spec_handlers.get(&ibc_spec_id)?.client_state_path)(
                                 client_id.clone(),
                             )?,
                         )
@@ -548,45 +637,49 @@ async fn do_main(args: cli::AppArgs) -> anyhow::Result<()> {
                     }
                 }
                 RpcCmd::ConsensusState {
-                    // on,
-                    // client_id,
-                    // ibc_spec_id,
-                    // trusted_height,
-                    // height,
-                    // decode,
-                    ..
+                    on,
+                    client_id,
+                    ibc_spec_id,
+                    trusted_height,
+                    height,
+                    decode,
                 } => {
-                    // let ibc_state = voyager_client
-                    //     .query_client_consensus_state(
-                    //         on.clone(),
-                    //         height,
-                    //         client_id.clone(),
-                    //         trusted_height,
-                    //     )
-                    //     .await?;
-
-                    // if decode {
-                    //     let client_info = voyager_client
-                    //         .client_info(on, ibc_spec_id, client_id)
-                    //         .await?;
-
-                    //     let decoded = voyager_client
-                    //         .decode_consensus_state(
-                    //             client_info.client_type,
-                    //             client_info.ibc_interface,
-                    //             ibc_state.state,
-                    //         )
-                    //         .await?;
-
-                    //     print_json(&IbcState {
-                    //         height: ibc_state.height,
-                    //         state: decoded,
-                    //     });
-                    // } else {
-                    //     print_json(&ibc_state);
-                    // }
-
-                    todo!()
+                    let mut ibc_spec_handlers = IbcSpecHandlers::new();
+                    register_ibc_spec_handlers(&mut ibc_spec_handlers);
+
+                    let ibc_state = voyager_client
+                        .query_ibc_state(
+                            on.clone(),
+                            ibc_spec_id.clone(),
+                            height,
+                            (ibc_spec_handlers.get(&ibc_spec_id)?.consensus_state_path)(
+                                client_id.clone(),
+                                trusted_height.to_string(),
+                            )?,
+                        )
+                        .await?;
+
+                    if decode {
+                        let client_info = voyager_client
+                            .client_info(on, ibc_spec_id.clone(), client_id)
+                            .await?;
+
+                        let decoded = voyager_client
+                            .decode_consensus_state(
+                                client_info.client_type,
+                                client_info.ibc_interface,
+                                ibc_spec_id,
+                                serde_json::from_value(ibc_state.state).unwrap(),
+                            )
+                            .await?;
+
+                        print_json(&IbcState {
+                            height: ibc_state.height,
+                            state: decoded,
+                        });
+                    } else {
+                        print_json(&ibc_state);
+                    }
                 }
             }
         }
@@ -603,10 +696,11 @@ async fn do_main(args: cli::AppArgs) -> anyhow::Result<()> {
             } => {
                 let voyager_config = get_voyager_config()?;
 
-                let ctx = Context::new(voyager_config.plugins, voyager_config.modules, |h| {
-                    h.register::<IbcClassic>();
-                    h.register::<IbcUnion>();
-                })
+                let ctx = Context::new(
+                    voyager_config.plugins,
+                    voyager_config.modules,
+                    register_ibc_spec_handlers,
+                )
                 .await?;
 
                 // weird race condition in Context::new that i don't feel like debugging right now
@@ -640,6 +734,206 @@ async fn do_main(args: cli::AppArgs) -> anyhow::Result<()> {
                     print_json(&msg);
                 }
             }
+            MsgCmd::InitConnection {
+                on,
+                client_id,
+                counterparty,
+                counterparty_client_id,
+                enqueue,
+            } => {
+                let voyager_client = jsonrpsee::http_client::HttpClient::builder().build(
+                    format!("http://{}", get_voyager_config()?.voyager.rpc_laddr),
+                )?;
+
+                let op = init_connection(
+                    &voyager_client,
+                    on,
+                    counterparty,
+                    client_id,
+                    counterparty_client_id,
+                )
+                .await?;
+
+                if enqueue {
+                    println!("enqueueing op");
+                    send_enqueue(&get_voyager_config()?.voyager.rest_laddr, op).await?;
+                } else {
+                    print_json(&op);
+                }
+            }
+            MsgCmd::InitChannel {
+                on,
+                connection_id,
+                port_id,
+                counterparty_port_id,
+                version,
+                enqueue,
+            } => {
+                let voyager_client = jsonrpsee::http_client::HttpClient::builder().build(
+                    format!("http://{}", get_voyager_config()?.voyager.rpc_laddr),
+                )?;
+
+                let op = init_channel(
+                    &voyager_client,
+                    on,
+                    connection_id,
+                    port_id,
+                    counterparty_port_id,
+                    version,
+                )
+                .await?;
+
+                if enqueue {
+                    println!("enqueueing op");
+                    send_enqueue(&get_voyager_config()?.voyager.rest_laddr, op).await?;
+                } else {
+                    print_json(&op);
+                }
+            }
+            MsgCmd::ResumeHandshake {
+                on,
+                connection_id,
+                channel_id,
+                port_id,
+                enqueue,
+            } => {
+                let voyager_client = jsonrpsee::http_client::HttpClient::builder().build(
+                    format!("http://{}", get_voyager_config()?.voyager.rpc_laddr),
+                )?;
+
+                let op = match (connection_id, channel_id) {
+                    (Some(connection_id), _) => {
+                        resume_connection_handshake(&voyager_client, on, connection_id).await?
+                    }
+                    (None, Some(channel_id)) => {
+                        resume_channel_handshake(&voyager_client, on, channel_id, port_id).await?
+                    }
+                    (None, None) => unreachable!("either a connection or channel id is required"),
+                };
+
+                if enqueue {
+                    println!("enqueueing op");
+                    send_enqueue(&get_voyager_config()?.voyager.rest_laddr, op).await?;
+                } else {
+                    print_json(&op);
+                }
+            }
+            MsgCmd::StoreWasmCode { on, wasm, enqueue } => {
+                let wasm_byte_code = std::fs::read(&wasm)
+                    .map_err(|err| anyhow!("unable to read {}: {err}", wasm.display()))?;
+
+                info!(
+                    checksum = %H256::new(sha2::Sha256::digest(&wasm_byte_code).into()),
+                    "storing wasm code"
+                );
+
+                let op = data(WithChainId {
+                    chain_id: on,
+                    message: IbcDatagram::new::<IbcClassic>(ibc_classic_spec::Datagram::from(
+                        ibc_classic_spec::MsgStoreWasmCode {
+                            wasm_byte_code: wasm_byte_code.into(),
+                        },
+                    )),
+                });
+
+                if enqueue {
+                    println!("enqueueing op");
+                    send_enqueue(&get_voyager_config()?.voyager.rest_laddr, op).await?;
+                } else {
+                    print_json(&op);
+                }
+            }
+            MsgCmd::MigrateWasmClient {
+                on,
+                client_id,
+                checksum,
+                migrate_msg,
+                enqueue,
+            } => {
+                let op = data(WithChainId {
+                    chain_id: on,
+                    message: IbcDatagram::new::<IbcClassic>(ibc_classic_spec::Datagram::from(
+                        ibc_classic_spec::MsgMigrateWasmClient {
+                            client_id,
+                            checksum,
+                            migrate_msg: serde_json::to_vec(&migrate_msg)?.into(),
+                        },
+                    )),
+                });
+
+                if enqueue {
+                    println!("enqueueing op");
+                    send_enqueue(&get_voyager_config()?.voyager.rest_laddr, op).await?;
+                } else {
+                    print_json(&op);
+                }
+            }
+            MsgCmd::UpgradeClient {
+                on,
+                client_id,
+                upgrade_height,
+                enqueue,
+            } => {
+                let voyager_client = jsonrpsee::http_client::HttpClient::builder().build(
+                    format!("http://{}", get_voyager_config()?.voyager.rpc_laddr),
+                )?;
+
+                let op = upgrade_client(&voyager_client, on, client_id, upgrade_height).await?;
+
+                if enqueue {
+                    println!("enqueueing op");
+                    send_enqueue(&get_voyager_config()?.voyager.rest_laddr, op).await?;
+                } else {
+                    print_json(&op);
+                }
+            }
+            MsgCmd::Generate(cmd) => {
+                let op = match cmd {
+                    GenerateCmd::CreateClient {
+                        on,
+                        ibc_spec_id,
+                        client_type,
+                        client_state,
+                        consensus_state,
+                    } => template::create_client(
+                        on,
+                        ibc_spec_id,
+                        client_type,
+                        client_state,
+                        consensus_state,
+                    )?,
+                    GenerateCmd::UpdateClient {
+                        on,
+                        client_id,
+                        ibc_spec_id,
+                        tracking,
+                        update_from,
+                        update_to,
+                    } => template::update_client(
+                        on,
+                        ibc_spec_id,
+                        client_id,
+                        tracking,
+                        update_from,
+                        update_to,
+                    ),
+                    GenerateCmd::RelayPackets {
+                        on,
+                        port_id,
+                        channel_id,
+                        sequences,
+                    } => template::relay_packets(on, port_id, channel_id, sequences),
+                    GenerateCmd::FetchBlocks { on, start_height } => {
+                        template::fetch_blocks(on, start_height)
+                    }
+                };
+
+                println!(
+                    "{}",
+                    serde_json::to_string_pretty(&template::to_canonical_json(&op)?).unwrap()
+                );
+            }
+            MsgCmd::Schema => print_json(&op_schema()),
         },
     }
 
@@ -657,6 +951,13 @@ async fn send_enqueue(
         .await?)
 }
 
+/// Registers all of the IBC specifications supported by voyager. This is the
+/// only place that needs to be updated in order to add support for a new
+/// [`IbcSpec`](voyager_message::core::IbcSpec).
+pub(crate) fn register_ibc_spec_handlers(handlers: &mut IbcSpecHandlers) {
+    handlers.register::<IbcClassic>().register::<IbcUnion>();
+}
+
 fn print_json<T: Serialize>(t: &T) {
     println!("{}", serde_json::to_string(&t).unwrap());
 }
@@ -738,6 +1039,14 @@ pub mod utils {
                 .modules()?
                 .client_module(&client_type, &ibc_interface, &ibc_spec_id)?;
 
+        let metadata = if metadata.is_null() {
+            ctx.rpc_server
+                .discover_client_metadata(&chain_id, &client_type, &ibc_interface)
+                .await?
+        } else {
+            metadata
+        };
+
         Ok(data(WithChainId {
             chain_id,
             message: match ibc_spec_id.as_str() {
@@ -1,27 +1,53 @@
 #![allow(clippy::type_complexity)]
 
-use std::{fmt::Debug, net::SocketAddr, panic::AssertUnwindSafe};
+use std::{
+    any::Any,
+    fmt::Debug,
+    net::SocketAddr,
+    panic::AssertUnwindSafe,
+    path::{Path, PathBuf},
+    time::Duration,
+};
 
+use amqp_queue::{AmqpQueue, AmqpQueueConfig, AmqpQueueError};
 use anyhow::{bail, Context as _};
 use frame_support_procedural::{CloneNoBound, DebugNoBound};
 use futures::{future::BoxFuture, stream::FuturesUnordered, Future, FutureExt, StreamExt};
-use ibc_classic_spec::IbcClassic;
-use ibc_union_spec::IbcUnion;
 use pg_queue::{PgQueue, PgQueueConfig};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use tracing::{debug, error, info, info_span, trace, trace_span};
+use tokio::signal::unix::{signal, SignalKind};
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, error, info, info_span, trace, trace_span, warn};
 use tracing_futures::Instrument;
 use unionlabs::ErrorReporter;
 use voyager_message::{
-    context::Context, filter::JaqInterestFilter, into_value, module::PluginInfo,
-    pass::PluginOptPass, rpc::VoyagerRpcServer, VoyagerMessage,
+    context::Context, dedup::EventDedup, filter::JaqInterestFilter, into_value, module::PluginInfo,
+    notify::Notifier, pass::PluginOptPass, rpc::VoyagerRpcServer, VoyagerMessage,
 };
 use voyager_vm::{
-    engine::Engine, in_memory::InMemoryQueue, pass::Pass, BoxDynError, Captures, Op, Queue,
+    engine::Engine,
+    in_memory::{InMemoryQueue, InMemoryQueueConfig},
+    pass::Pass,
+    record::Recorder,
+    BoxDynError, Captures, Limits, Op, Queue,
+};
+
+use crate::{
+    api,
+    balances::{BalanceMonitor, BalanceMonitorConfig},
+    checkpoint,
+    client_expiry::{ClientExpiryConfig, ClientExpiryMonitor},
+    config::Config,
+    control::ControlServer,
+    health::Health,
+    input::{self, InputConfig},
+    register_ibc_spec_handlers,
+    reload::{ConfigWatcher, CONFIG_POLL_INTERVAL},
 };
 
-use crate::{api, config::Config};
+/// How often the checkpoints are flushed to disk.
+const CHECKPOINT_FLUSH_INTERVAL: Duration = Duration::from_secs(10);
 
 #[derive(Debug)]
 pub struct Voyager {
@@ -30,21 +56,33 @@ pub struct Voyager {
     num_workers: u16,
     rest_laddr: SocketAddr,
     rpc_laddr: SocketAddr,
+    control_laddr: SocketAddr,
+    inputs: InputConfig,
     queue: QueueImpl,
     optimizer_delay_milliseconds: u64,
+    checkpoint_path: Option<PathBuf>,
+    client_expiry: Option<ClientExpiryConfig>,
+    balance_monitor: Option<BalanceMonitorConfig>,
+    health: Health,
+    limits: Limits,
+    recorder: Option<Recorder>,
+    config_watcher: Option<ConfigWatcher>,
+    shutdown_timeout: Duration,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "kebab-case", tag = "type")]
 pub enum QueueConfig {
-    InMemory,
+    InMemory(InMemoryQueueConfig),
     PgQueue(PgQueueConfig),
+    Amqp(AmqpQueueConfig),
 }
 
 #[derive(DebugNoBound, CloneNoBound)]
 pub enum QueueImpl {
     InMemory(InMemoryQueue<VoyagerMessage>),
     PgQueue(PgQueue<VoyagerMessage>),
+    Amqp(AmqpQueue<VoyagerMessage>),
 }
 
 #[derive(DebugNoBound, thiserror::Error)]
@@ -52,6 +90,7 @@ pub enum QueueImpl {
 pub enum AnyQueueError {
     InMemory(std::convert::Infallible),
     PgQueue(sqlx::Error),
+    Amqp(AmqpQueueError),
 }
 
 impl Queue<VoyagerMessage> for QueueImpl {
@@ -61,14 +100,17 @@ impl Queue<VoyagerMessage> for QueueImpl {
     fn new(cfg: Self::Config) -> impl Future<Output = Result<Self, Self::Error>> {
         async move {
             Ok(match cfg {
-                QueueConfig::InMemory => Self::InMemory(
-                    InMemoryQueue::new(())
+                QueueConfig::InMemory(cfg) => Self::InMemory(
+                    InMemoryQueue::new(cfg)
                         .await
                         .map_err(AnyQueueError::InMemory)?,
                 ),
                 QueueConfig::PgQueue(cfg) => {
                     Self::PgQueue(PgQueue::new(cfg).await.map_err(AnyQueueError::PgQueue)?)
                 }
+                QueueConfig::Amqp(cfg) => {
+                    Self::Amqp(AmqpQueue::new(cfg).await.map_err(AnyQueueError::Amqp)?)
+                }
             })
         }
     }
@@ -88,6 +130,10 @@ impl Queue<VoyagerMessage> for QueueImpl {
                     .enqueue(item, filter)
                     .await
                     .map_err(AnyQueueError::PgQueue)?,
+                QueueImpl::Amqp(queue) => queue
+                    .enqueue(item, filter)
+                    .await
+                    .map_err(AnyQueueError::Amqp)?,
             };
 
             trace!("queued");
@@ -99,6 +145,7 @@ impl Queue<VoyagerMessage> for QueueImpl {
     fn process<'a, F, Fut, R>(
         &'a self,
         filter: &'a JaqInterestFilter,
+        paused: &'a [String],
         f: F,
     ) -> impl Future<Output = Result<Option<R>, Self::Error>> + Send + Captures<'a>
     where
@@ -109,13 +156,17 @@ impl Queue<VoyagerMessage> for QueueImpl {
         async move {
             let res = match self {
                 QueueImpl::InMemory(queue) => queue
-                    .process(filter, f)
+                    .process(filter, paused, f)
                     .await
                     .map_err(AnyQueueError::InMemory),
                 QueueImpl::PgQueue(queue) => queue
-                    .process(filter, f)
+                    .process(filter, paused, f)
                     .await
                     .map_err(AnyQueueError::PgQueue),
+                QueueImpl::Amqp(queue) => queue
+                    .process(filter, paused, f)
+                    .await
+                    .map_err(AnyQueueError::Amqp),
             };
 
             trace!("processed");
@@ -138,33 +189,67 @@ impl Queue<VoyagerMessage> for QueueImpl {
                 .optimize(tag, optimizer)
                 .await
                 .map_err(|e| e.map_left(AnyQueueError::PgQueue)),
+            QueueImpl::Amqp(queue) => queue
+                .optimize(tag, optimizer)
+                .await
+                .map_err(|e| e.map_left(AnyQueueError::Amqp)),
         }
     }
 }
 
 impl Voyager {
-    pub async fn new(config: Config) -> anyhow::Result<Self> {
+    /// If `config_path` is set, the config is reloaded whenever the file changes; see
+    /// [`crate::reload`].
+    pub async fn new(config: Config, config_path: Option<PathBuf>) -> anyhow::Result<Self> {
         let queue = QueueImpl::new(config.voyager.queue.clone())
             .await
             .context("error initializing queue")?;
 
-        Ok(Self {
-            context: Context::new(config.plugins, config.modules, |h| {
-                h.register::<IbcClassic>();
-                h.register::<IbcUnion>();
+        let config_watcher = config_path.map(|path| ConfigWatcher::new(path, &config));
+
+        let recorder = config
+            .voyager
+            .record_path
+            .as_ref()
+            .map(|path| {
+                Recorder::open(path).with_context(|| {
+                    format!("unable to open the record log at `{}`", path.display())
+                })
             })
+            .transpose()?;
+
+        let mut context = Context::new(config.plugins, config.modules, register_ibc_spec_handlers)
             .await
-            .context("error initializing plugins")?,
+            .context("error initializing plugins")?;
+
+        context.call_timeouts = config.voyager.call_timeouts;
+        context.event_dedup = EventDedup::new(Duration::from_secs(
+            config.voyager.event_dedup_window_seconds,
+        ));
+        context.notifier = Notifier::new(config.voyager.webhooks);
+
+        Ok(Self {
+            context,
             num_workers: config.voyager.num_workers,
             rest_laddr: config.voyager.rest_laddr,
             rpc_laddr: config.voyager.rpc_laddr,
+            control_laddr: config.voyager.control_laddr,
+            inputs: config.voyager.inputs,
             queue,
             optimizer_delay_milliseconds: config.voyager.optimizer_delay_milliseconds,
+            checkpoint_path: config.voyager.checkpoint_path,
+            client_expiry: config.voyager.client_expiry,
+            balance_monitor: config.voyager.balance_monitor,
+            health: Health::new(config.voyager.health),
+            limits: config.voyager.limits,
+            recorder,
+            config_watcher,
+            shutdown_timeout: Duration::from_secs(config.voyager.shutdown_timeout_seconds),
         })
     }
 
     #[allow(clippy::too_many_lines)]
-    pub async fn run(self) -> anyhow::Result<()> {
+    pub async fn run(mut self) -> anyhow::Result<()> {
         let interest_filter = JaqInterestFilter::new(
             self.context
                 .interest_filters()
@@ -177,7 +262,17 @@ impl Voyager {
                 .collect(),
         )?;
 
-        let queue_rx = api::run(&self.rest_laddr);
+        if let Some(checkpoint_path) = &self.checkpoint_path {
+            self.resume_from_checkpoints(checkpoint_path, &interest_filter)
+                .await?;
+        }
+
+        let queue_rx = futures::stream::select(
+            api::run(&self.rest_laddr, self.health.clone()),
+            input::run(&self.inputs)?,
+        );
+
+        let mut config_watcher = self.config_watcher.take();
 
         {
             let mut tasks =
@@ -200,6 +295,24 @@ impl Voyager {
                 .catch_unwind(),
             ));
 
+            tasks.push(Box::pin(
+                AssertUnwindSafe(
+                    async {
+                        ControlServer::new(
+                            &self.context,
+                            self.queue.clone(),
+                            interest_filter.clone(),
+                        )
+                        .run(&self.control_laddr)
+                        .await?;
+
+                        Err("control server exited".into())
+                    }
+                    .instrument(trace_span!("control_server")),
+                )
+                .catch_unwind(),
+            ));
+
             tasks.push(Box::pin(
                 AssertUnwindSafe(async {
                     debug!("checking for new messages");
@@ -217,14 +330,147 @@ impl Voyager {
                 .catch_unwind(),
             ));
 
+            if let Some(checkpoint_path) = &self.checkpoint_path {
+                tasks.push(Box::pin(
+                    AssertUnwindSafe(
+                        async {
+                            loop {
+                                tokio::time::sleep(CHECKPOINT_FLUSH_INTERVAL).await;
+
+                                let res = checkpoint::save(
+                                    checkpoint_path,
+                                    &self.context.rpc_server.checkpoints(),
+                                );
+
+                                if let Err(error) = res {
+                                    error!(
+                                        error = %ErrorReporter(&*error),
+                                        "error flushing checkpoints"
+                                    );
+                                }
+                            }
+                        }
+                        .instrument(trace_span!("checkpoints")),
+                    )
+                    .catch_unwind(),
+                ));
+            }
+
+            if let Some(client_expiry) = &self.client_expiry {
+                tasks.push(Box::pin(
+                    AssertUnwindSafe(
+                        async {
+                            let mut monitor = ClientExpiryMonitor::new(client_expiry.clone());
+
+                            loop {
+                                for op in monitor.check_all(&self.context).await {
+                                    if let Err(error) =
+                                        self.queue.enqueue(op, &interest_filter).await
+                                    {
+                                        error!(
+                                            error = %ErrorReporter(&error),
+                                            "error enqueueing client refresh"
+                                        );
+                                    }
+                                }
+
+                                tokio::time::sleep(monitor.interval()).await;
+                            }
+                        }
+                        .instrument(info_span!("client_expiry")),
+                    )
+                    .catch_unwind(),
+                ));
+            }
+
+            if let Some(balance_monitor) = &self.balance_monitor {
+                tasks.push(Box::pin(
+                    AssertUnwindSafe(
+                        async {
+                            let mut monitor = BalanceMonitor::new(balance_monitor.clone());
+
+                            loop {
+                                monitor.check_all(&self.context).await;
+
+                                tokio::time::sleep(monitor.interval()).await;
+                            }
+                        }
+                        .instrument(info_span!("balance_monitor")),
+                    )
+                    .catch_unwind(),
+                ));
+            }
+
+            tasks.push(Box::pin(
+                AssertUnwindSafe(
+                    async {
+                        loop {
+                            self.health.check_all(&self.context).await;
+
+                            tokio::time::sleep(self.health.interval()).await;
+                        }
+                    }
+                    .instrument(info_span!("health")),
+                )
+                .catch_unwind(),
+            ));
+
+            if let Some(config_watcher) = &mut config_watcher {
+                tasks.push(Box::pin(
+                    AssertUnwindSafe(
+                        async {
+                            loop {
+                                tokio::time::sleep(CONFIG_POLL_INTERVAL).await;
+
+                                if let Err(error) =
+                                    config_watcher.poll(&self.context, &interest_filter).await
+                                {
+                                    error!(
+                                        error = %ErrorReporter(&*error),
+                                        "error reloading config"
+                                    );
+                                }
+                            }
+                        }
+                        .instrument(info_span!("config_watcher")),
+                    )
+                    .catch_unwind(),
+                ));
+            }
+
+            // workers and optimizers stop picking up new ops once `shutdown` is cancelled, and are
+            // then given `shutdown_timeout` to finish the ops they are processing
+            let shutdown = CancellationToken::new();
+            let mut workers =
+                FuturesUnordered::<BoxFuture<Result<Result<(), BoxDynError>, _>>>::new();
+
+            if let QueueImpl::PgQueue(queue) = &self.queue {
+                tasks.push(Box::pin(
+                    AssertUnwindSafe(
+                        queue
+                            .record_depth(shutdown.clone().cancelled_owned())
+                            .map(Ok),
+                    )
+                    .catch_unwind(),
+                ));
+            }
+
             info!("spawning {} workers", self.num_workers);
 
             for id in 0..self.num_workers {
                 debug!("spawning worker {id}");
 
-                tasks.push(Box::pin(
+                let mut engine = Engine::new(&self.context, &self.queue, &interest_filter)
+                    .with_limits(self.limits)
+                    .with_shutdown(shutdown.clone());
+
+                if let Some(recorder) = &self.recorder {
+                    engine = engine.with_recorder(recorder.clone());
+                }
+
+                workers.push(Box::pin(
                     AssertUnwindSafe(
-                        Engine::new(&self.context, &self.queue, &interest_filter)
+                        engine
                             .run()
                             .for_each(|res| async move {
                                 match res {
@@ -252,7 +498,7 @@ impl Voyager {
             for (plugin_name, filter) in self.context.interest_filters() {
                 info!(%plugin_name, "spawning optimizer");
 
-                tasks.push(Box::pin(
+                workers.push(Box::pin(
                     AssertUnwindSafe(
                         async {
                             let plugin_name = plugin_name.clone();
@@ -264,7 +510,7 @@ impl Voyager {
                                     .client(),
                             );
 
-                            loop {
+                            while !shutdown.is_cancelled() {
                                 trace!("optimizing");
 
                                 let res =
@@ -288,6 +534,8 @@ impl Voyager {
                                 ))
                                 .await;
                             }
+
+                            Ok(())
                         }
                         .instrument(info_span!("optimize", %plugin_name))
                         .instrument(trace_span!("optimize_verbose", %filter)),
@@ -296,30 +544,63 @@ impl Voyager {
                 ));
             }
 
-            self.context
+            let drained = self
+                .context
                 .cancellation_token
                 .run_until_cancelled(async {
-                    while let Some(res) = tasks.next().await {
-                        match res {
-                            Ok(Ok(())) => {
-                                info!("task exited gracefully");
+                    let shutdown_signal = shutdown_signal();
+                    pin_utils::pin_mut!(shutdown_signal);
+
+                    let drain_deadline = tokio::time::sleep(Duration::ZERO);
+                    pin_utils::pin_mut!(drain_deadline);
+
+                    loop {
+                        tokio::select! {
+                            Some(res) = tasks.next() => {
+                                if !task_exited(res) {
+                                    return false;
+                                }
                             }
-                            Ok(Err(error)) => {
-                                error!(
-                                    error = %ErrorReporter(&*error),
-                                    "task returned with an error"
+                            Some(res) = workers.next() => {
+                                if !task_exited(res) {
+                                    return false;
+                                }
+
+                                if workers.is_empty() && shutdown.is_cancelled() {
+                                    info!("all in-flight ops have been processed");
+                                    return true;
+                                }
+                            }
+                            () = &mut shutdown_signal, if !shutdown.is_cancelled() => {
+                                info!(
+                                    timeout = ?self.shutdown_timeout,
+                                    "shutting down, waiting for in-flight ops to be processed"
                                 );
-                                break;
+
+                                shutdown.cancel();
+                                drain_deadline
+                                    .as_mut()
+                                    .reset(tokio::time::Instant::now() + self.shutdown_timeout);
                             }
-                            Err(_err) => {
-                                // can't do anything with dyn Any
-                                error!("task panicked");
-                                break;
+                            () = &mut drain_deadline, if shutdown.is_cancelled() => {
+                                warn!(
+                                    in_flight = workers.len(),
+                                    "timed out waiting for in-flight ops to be processed"
+                                );
+                                return true;
                             }
                         }
                     }
                 })
                 .await;
+
+            if drained == Some(true) {
+                self.context.shutdown().await;
+
+                info!("shut down");
+
+                return Ok(());
+            }
         }
 
         self.context.shutdown().await;
@@ -327,7 +608,74 @@ impl Voyager {
         bail!("runtime error, exiting")
     }
 
+    /// Load the checkpoints at `checkpoint_path` and, for the in-memory queue, enqueue the ops to
+    /// resume fetching blocks on every checkpointed chain.
+    async fn resume_from_checkpoints(
+        &self,
+        checkpoint_path: &Path,
+        interest_filter: &JaqInterestFilter,
+    ) -> anyhow::Result<()> {
+        let checkpoints = checkpoint::load(checkpoint_path)?;
+
+        for (chain_id, height) in checkpoints {
+            self.context
+                .rpc_server
+                .record_checkpoint(chain_id.clone(), height);
+
+            if let QueueImpl::InMemory(_) = self.queue {
+                let latest_height = self
+                    .context
+                    .rpc_server
+                    .query_latest_height(&chain_id, true)
+                    .await
+                    .with_context(|| format!("error querying latest height of {chain_id}"))?;
+
+                self.queue
+                    .enqueue(
+                        checkpoint::resume(chain_id, height, latest_height),
+                        interest_filter,
+                    )
+                    .await?;
+            }
+        }
+
+        Ok(())
+    }
+
     pub async fn shutdown(self) {
         self.context.shutdown().await;
     }
 }
+
+/// Log the result of a task exiting, returning whether it exited gracefully.
+fn task_exited(res: Result<Result<(), BoxDynError>, Box<dyn Any + Send>>) -> bool {
+    match res {
+        Ok(Ok(())) => {
+            info!("task exited gracefully");
+            true
+        }
+        Ok(Err(error)) => {
+            error!(
+                error = %ErrorReporter(&*error),
+                "task returned with an error"
+            );
+            false
+        }
+        Err(_err) => {
+            // can't do anything with dyn Any
+            error!("task panicked");
+            false
+        }
+    }
+}
+
+/// Resolves once either `SIGTERM` or `SIGINT` is received.
+async fn shutdown_signal() {
+    let mut sigterm =
+        signal(SignalKind::terminate()).expect("unable to install the SIGTERM handler");
+
+    tokio::select! {
+        _ = sigterm.recv() => {}
+        _ = tokio::signal::ctrl_c() => {}
+    }
+}
@@ -0,0 +1,90 @@
+//! Hot reloading of the config file.
+//!
+//! The config file is polled for changes, and the plugins and modules whose config changed are
+//! restarted with their new config (see [`Context::reload`]). The queue, and any ops in it, are
+//! left untouched; the rpc clients of the restarted plugins and modules reconnect to the new
+//! processes once they're up.
+//!
+//! Changes to the `voyager` section of the config are not applied until voyager is restarted.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+use anyhow::Context as _;
+use tracing::{info, warn};
+use voyager_message::{context::Context, filter::JaqInterestFilter};
+
+use crate::config::Config;
+
+/// How often the config file is checked for changes.
+pub const CONFIG_POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+#[derive(Debug)]
+pub struct ConfigWatcher {
+    path: PathBuf,
+    modified: Option<SystemTime>,
+    voyager_config: serde_json::Value,
+}
+
+impl ConfigWatcher {
+    pub fn new(path: PathBuf, config: &Config) -> Self {
+        Self {
+            modified: modified(&path).ok(),
+            voyager_config: serde_json::to_value(&config.voyager)
+                .expect("serialization is infallible; qed;"),
+            path,
+        }
+    }
+
+    /// Reload the config if the file has been modified since the last time it was checked.
+    pub async fn poll(
+        &mut self,
+        ctx: &Context,
+        interest_filter: &JaqInterestFilter,
+    ) -> anyhow::Result<()> {
+        let modified = modified(&self.path)?;
+
+        if self.modified == Some(modified) {
+            return Ok(());
+        }
+
+        // an invalid config is only reported once, until the file is modified again
+        self.modified = Some(modified);
+
+        info!("config file changed, reloading");
+
+        let config = Config::from_file(&self.path)?;
+
+        if serde_json::to_value(&config.voyager).expect("serialization is infallible; qed;")
+            != self.voyager_config
+        {
+            warn!("the voyager config changed, a restart is required for the changes to apply");
+        }
+
+        for plugin_info in ctx.reload(config.plugins, config.modules).await? {
+            let name = plugin_info.name.clone();
+
+            interest_filter
+                .update(plugin_info)
+                .with_context(|| format!("error updating the interest filter of plugin {name}"))?;
+        }
+
+        info!("reloaded config");
+
+        Ok(())
+    }
+}
+
+fn modified(path: &Path) -> anyhow::Result<SystemTime> {
+    fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .with_context(|| {
+            format!(
+                "unable to read the metadata of the config file at `{}`",
+                path.to_string_lossy()
+            )
+        })
+}